@@ -0,0 +1,52 @@
+use std::{path::Path, process::Command};
+
+/// Embeds the commit the binaries were built from as INFRAPASS_GIT_HASH.
+/// Docker builds have no `.git`, so a non-empty GIT_HASH build arg wins.
+fn main() {
+    println!("cargo:rerun-if-env-changed=GIT_HASH");
+    if let Ok(head) = std::fs::read_to_string(".git/HEAD") {
+        println!("cargo:rerun-if-changed=.git/HEAD");
+        // A new commit moves the branch ref, not HEAD
+        if let Some(branch) = head.trim().strip_prefix("ref: ") {
+            let path = format!(".git/{}", branch);
+            if Path::new(&path).exists() {
+                println!("cargo:rerun-if-changed={}", path);
+            }
+        }
+    }
+
+    let hash = std::env::var("GIT_HASH")
+        .ok()
+        .filter(|h| !h.is_empty())
+        .or_else(|| {
+            Command::new("git")
+                .args(["rev-parse", "--short=12", "HEAD"])
+                .output()
+                .ok()
+                .filter(|out| out.status.success())
+                .map(|out| String::from_utf8_lossy(&out.stdout).trim().to_string())
+        })
+        .unwrap_or_else(|| "unknown".to_string());
+
+    println!("cargo:rustc-env=INFRAPASS_GIT_HASH={}", hash);
+
+    compile_protos();
+}
+
+/// Generates the gRPC validator service from `proto/validator.proto`, with
+/// the vendored protoc unless PROTOC names another
+fn compile_protos() {
+    println!("cargo:rerun-if-changed=proto/validator.proto");
+    println!("cargo:rerun-if-env-changed=PROTOC");
+    if std::env::var_os("PROTOC").is_none() {
+        let protoc =
+            protoc_bin_vendored::protoc_bin_path().expect("no vendored protoc for this platform");
+        // SAFETY: the build script runs no other threads
+        unsafe { std::env::set_var("PROTOC", protoc) };
+    }
+
+    tonic_prost_build::configure()
+        .build_client(false)
+        .compile_protos(&["proto/validator.proto"], &["proto"])
+        .expect("failed to compile proto/validator.proto");
+}
@@ -16,6 +16,9 @@ const EInvalidBatchSize: u64 = 5;
 const ENoExpiry: u64 = 6;
 const EExpired: u64 = 7;
 const EQuotaExceeded: u64 = 8;
+const ENotHolder: u64 = 9;
+const ETierMismatch: u64 = 10;
+const ENotUsageBased: u64 = 11;
 
 public struct EntitlementStore has key {
     id: UID,
@@ -59,6 +62,17 @@ public struct EntitlementPurchased has copy, drop {
     inner: EntitlementConfig,
 }
 
+public struct EntitlementToppedUp has copy, drop {
+    entitlement_id: ID,
+    buyer: address,
+    service_id: ID,
+    tier_id: ID,
+    price_paid: u64,
+    units_added: u64,
+    timestamp: u64,
+    inner: EntitlementConfig,
+}
+
 public struct QuotaConsumed has copy, drop {
     entitlement_id: ID,
     amount: u64,
@@ -151,6 +165,58 @@ entry fun purchase_entitlement<CoinType>(
     // transfer::transfer(entitlement, buyer);
 }
 
+/// Adds units to the sender's usage-based entitlement, bought at its tier's
+/// current price, instead of purchasing a new entitlement.
+entry fun top_up_entitlement<CoinType>(
+    store: &mut EntitlementStore,
+    service: &ServiceListing,
+    registry: &ServiceRegistry,
+    tier: &PricingTier<CoinType>,
+    entitlement_id: ID,
+    payment: Coin<CoinType>,
+    clock: &Clock,
+    ctx: &mut TxContext,
+) {
+    let buyer = tx_context::sender(ctx);
+    let timestamp = clock::timestamp_ms(clock);
+    let service_id = registry::get_service_id(service);
+
+    assert!(registry::is_service_active(service), EServiceNotActive);
+    assert!(pricing::get_tier_service_id(tier) == service_id, ETierNotInService);
+    assert!(pricing::is_tier_active(tier), ETierNotActive);
+    assert!(pricing::is_usage_based(tier), ENotUsageBased);
+
+    let payment_amount = coin::value(&payment);
+    assert!(payment_amount >= pricing::get_tier_price(tier), EInsufficientPayment);
+
+    let ent: &mut Entitlement = bag::borrow_mut(&mut store.entitlements, entitlement_id);
+    assert!(ent.holder == buyer, ENotHolder);
+    assert!(ent.service_id == service_id, ETierMismatch);
+    assert!(ent.tier_id == pricing::get_tier_id(tier), ETierMismatch);
+
+    let (_, units) = pricing::calculate_entitlement_details(tier, timestamp, payment_amount);
+    let units_added = *option::borrow(&units);
+
+    match (&mut ent.inner) {
+        EntitlementConfig::UsageBased { units } => *units = *units + units_added,
+        _ => abort ENotUsageBased,
+    };
+
+    event::emit(EntitlementToppedUp {
+        entitlement_id,
+        buyer,
+        service_id,
+        tier_id: ent.tier_id,
+        price_paid: payment_amount,
+        units_added,
+        timestamp,
+        inner: ent.inner,
+    });
+
+    let provider = registry::get_service_provider_address(registry, service);
+    transfer::public_transfer(payment, provider);
+}
+
 /// Batch-settle usage by providing entitlement object IDs + the actual mutable objects.
 /// Caller must own/pass all entitlements being settled.
 entry fun settle_usage_batch(
@@ -75,6 +75,16 @@ public struct ServiceUpdated has copy, drop {
     timestamp: u64,
 }
 
+public struct ServiceDeactivated has copy, drop {
+    service_id: ID,
+    timestamp: u64,
+}
+
+public struct ServiceReactivated has copy, drop {
+    service_id: ID,
+    timestamp: u64,
+}
+
 public struct TierAddedToService has copy, drop {
     service_id: ID,
     tier_id: ID,
@@ -409,9 +419,26 @@ entry fun set_service_active_entry(
     service.active = true;
     service.updated_at = clock::timestamp_ms(clock);
 
-    event::emit(ServiceUpdated {
+    event::emit(ServiceReactivated {
+        service_id: object::uid_to_inner(&service.id),
+        timestamp: service.updated_at,
+    });
+}
+
+entry fun set_service_inactive_entry(
+    registry: &ServiceRegistry,
+    service: &mut ServiceListing,
+    clock: &Clock,
+    ctx: &TxContext,
+) {
+    verify_sender_is_provider(registry, tx_context::sender(ctx));
+    verify_sender_owns_service(registry, service, tx_context::sender(ctx));
+
+    service.active = false;
+    service.updated_at = clock::timestamp_ms(clock);
+
+    event::emit(ServiceDeactivated {
         service_id: object::uid_to_inner(&service.id),
-        metadata_uri: service.metadata_uri,
         timestamp: service.updated_at,
     });
 }
@@ -0,0 +1,111 @@
+use anyhow::{Result, anyhow};
+use serde::Deserialize;
+
+use crate::alerting::types::AlertRule;
+
+/// Alerting configuration, read from `ALERT_*` environment variables
+#[derive(Debug, Clone, Deserialize)]
+pub struct AlertConfig {
+    /// Slack incoming webhook; receives warning and critical alerts
+    pub slack_webhook_url: Option<String>,
+
+    /// PagerDuty Events API v2 routing key; receives critical alerts only
+    pub pagerduty_routing_key: Option<String>,
+
+    /// Minimum seconds between two deliveries of the same alert
+    #[serde(default = "default_dedup_window_secs")]
+    pub dedup_window_secs: u64,
+
+    /// Comma separated rule names that are never delivered
+    /// e.g. "checkpoint_lag,validator_error_rate"
+    #[serde(default)]
+    pub silenced_rules: String,
+
+    /// UTC hour range during which only critical alerts are delivered
+    /// e.g. "22-06"
+    pub quiet_hours: Option<String>,
+
+    /// Seconds without a checkpoint before the indexer is considered lagging
+    #[serde(default = "default_checkpoint_lag_secs")]
+    pub checkpoint_lag_secs: u64,
+
+    /// Fraction of failed validations (0.0 - 1.0) that triggers an alert
+    #[serde(default = "default_validator_error_rate")]
+    pub validator_error_rate: f64,
+
+    /// Minimum validations in a window before the error rate is evaluated
+    #[serde(default = "default_validator_min_requests")]
+    pub validator_min_requests: u64,
+
+    /// Length of the validator error rate window in seconds
+    #[serde(default = "default_validator_window_secs")]
+    pub validator_window_secs: u64,
+
+    /// Gas balance in MIST below which a relayer or sponsor key alerts
+    #[serde(default = "default_min_gas_balance")]
+    pub min_gas_balance: u64,
+
+    /// Seconds between relayer and sponsor key checks
+    #[serde(default = "default_key_check_interval_secs")]
+    pub key_check_interval_secs: u64,
+}
+
+impl AlertConfig {
+    pub fn load() -> Result<Self> {
+        let cfg: AlertConfig = config::Config::builder()
+            .add_source(config::Environment::with_prefix("ALERT"))
+            .build()?
+            .try_deserialize()?;
+
+        cfg.quiet_hours_range()?;
+
+        Ok(cfg)
+    }
+
+    pub fn is_silenced(&self, rule: AlertRule) -> bool {
+        self.silenced_rules
+            .split(',')
+            .map(str::trim)
+            .any(|r| r == rule.as_str())
+    }
+
+    pub fn quiet_hours_range(&self) -> Result<Option<(u32, u32)>> {
+        let Some(raw) = self.quiet_hours.as_deref().filter(|s| !s.is_empty()) else {
+            return Ok(None);
+        };
+
+        let (start, end) = raw
+            .split_once('-')
+            .ok_or_else(|| anyhow!("ALERT_QUIET_HOURS must look like 22-06, got {}", raw))?;
+        let start: u32 = start.trim().parse()?;
+        let end: u32 = end.trim().parse()?;
+
+        if start > 23 || end > 23 {
+            return Err(anyhow!("ALERT_QUIET_HOURS hours must be between 0 and 23"));
+        }
+
+        Ok(Some((start, end)))
+    }
+}
+
+fn default_dedup_window_secs() -> u64 {
+    900
+}
+fn default_checkpoint_lag_secs() -> u64 {
+    120
+}
+fn default_validator_error_rate() -> f64 {
+    0.2
+}
+fn default_validator_min_requests() -> u64 {
+    50
+}
+fn default_validator_window_secs() -> u64 {
+    60
+}
+fn default_min_gas_balance() -> u64 {
+    1_000_000_000
+}
+fn default_key_check_interval_secs() -> u64 {
+    300
+}
@@ -0,0 +1,199 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use chrono::{Timelike, Utc};
+use tracing::{info, warn};
+
+use crate::alerting::{
+    config::AlertConfig,
+    types::{Alert, Severity},
+};
+
+const PAGERDUTY_EVENTS_URL: &str = "https://events.pagerduty.com/v2/enqueue";
+
+struct ErrorWindow {
+    started_at: Instant,
+    total: u64,
+    errors: u64,
+}
+
+pub struct AlertManager {
+    cfg: AlertConfig,
+    http: reqwest::Client,
+    last_sent: Mutex<HashMap<String, Instant>>,
+    validations: Mutex<ErrorWindow>,
+}
+
+impl AlertManager {
+    pub fn new(cfg: AlertConfig) -> Self {
+        let http = reqwest::Client::builder()
+            .timeout(Duration::from_secs(5))
+            .build()
+            .expect("Failed to build alerting HTTP client");
+
+        Self {
+            cfg,
+            http,
+            last_sent: Mutex::new(HashMap::new()),
+            validations: Mutex::new(ErrorWindow {
+                started_at: Instant::now(),
+                total: 0,
+                errors: 0,
+            }),
+        }
+    }
+
+    pub fn checkpoint_lag_threshold(&self) -> Duration {
+        Duration::from_secs(self.cfg.checkpoint_lag_secs)
+    }
+
+    pub fn min_gas_balance(&self) -> u64 {
+        self.cfg.min_gas_balance
+    }
+
+    pub fn key_check_interval(&self) -> Duration {
+        Duration::from_secs(self.cfg.key_check_interval_secs)
+    }
+
+    pub async fn raise(&self, alert: Alert) {
+        if self.cfg.is_silenced(alert.rule) {
+            return;
+        }
+
+        if alert.severity < Severity::Critical && self.in_quiet_hours() {
+            return;
+        }
+
+        if !self.claim_dedup(&alert.dedup_key) {
+            return;
+        }
+
+        warn!(
+            rule = alert.rule.as_str(),
+            severity = alert.severity.as_str(),
+            "ALERT: {}",
+            alert.summary
+        );
+
+        if alert.severity >= Severity::Warning {
+            if let Some(url) = &self.cfg.slack_webhook_url {
+                self.send_slack(url, &alert).await;
+            }
+        }
+
+        if alert.severity == Severity::Critical {
+            if let Some(key) = &self.cfg.pagerduty_routing_key {
+                self.send_pagerduty(key, &alert).await;
+            }
+        }
+    }
+
+    /// Feeds one validation outcome into the rolling error rate window.
+    /// The rate is evaluated once per window when it rolls over. A breach is
+    /// delivered in the background, so a slow webhook doesn't hold up the
+    /// validation that tripped it.
+    pub fn record_validation(self: &Arc<Self>, failed: bool) {
+        let window = Duration::from_secs(self.cfg.validator_window_secs);
+
+        let breached = {
+            let mut w = self.validations.lock().unwrap();
+            w.total += 1;
+            if failed {
+                w.errors += 1;
+            }
+
+            if w.started_at.elapsed() < window {
+                None
+            } else {
+                let (errors, total) = (w.errors, w.total);
+                *w = ErrorWindow {
+                    started_at: Instant::now(),
+                    total: 0,
+                    errors: 0,
+                };
+
+                let rate = errors as f64 / total as f64;
+                (total >= self.cfg.validator_min_requests
+                    && rate >= self.cfg.validator_error_rate)
+                    .then_some((errors, total))
+            }
+        };
+
+        if let Some((errors, total)) = breached {
+            let alert = Alert::validator_error_rate(errors, total, self.cfg.validator_window_secs);
+            let alerts = self.clone();
+            tokio::spawn(async move { alerts.raise(alert).await });
+        }
+    }
+
+    fn claim_dedup(&self, key: &str) -> bool {
+        let window = Duration::from_secs(self.cfg.dedup_window_secs);
+        let mut last_sent = self.last_sent.lock().unwrap();
+
+        match last_sent.get(key) {
+            Some(at) if at.elapsed() < window => false,
+            _ => {
+                last_sent.insert(key.to_string(), Instant::now());
+                true
+            }
+        }
+    }
+
+    fn in_quiet_hours(&self) -> bool {
+        let Ok(Some((start, end))) = self.cfg.quiet_hours_range() else {
+            return false;
+        };
+
+        let hour = Utc::now().hour();
+        if start <= end {
+            hour >= start && hour < end
+        } else {
+            hour >= start || hour < end
+        }
+    }
+
+    async fn send_slack(&self, url: &str, alert: &Alert) {
+        let body = serde_json::json!({
+            "text": format!(
+                "[{}] {}: {}",
+                alert.severity.as_str().to_uppercase(),
+                alert.rule.as_str(),
+                alert.summary
+            ),
+        });
+
+        match self.http.post(url).json(&body).send().await {
+            Ok(resp) if resp.status().is_success() => {
+                info!(rule = alert.rule.as_str(), "Alert delivered to Slack");
+            }
+            Ok(resp) => warn!(status = %resp.status(), "Slack webhook rejected alert"),
+            Err(e) => warn!(error = %e, "Failed to deliver alert to Slack"),
+        }
+    }
+
+    async fn send_pagerduty(&self, routing_key: &str, alert: &Alert) {
+        let body = serde_json::json!({
+            "routing_key": routing_key,
+            "event_action": "trigger",
+            "dedup_key": alert.dedup_key,
+            "payload": {
+                "summary": alert.summary,
+                "source": "infrapass-server",
+                "severity": alert.severity.as_str(),
+                "component": alert.rule.as_str(),
+                "custom_details": alert.details,
+            },
+        });
+
+        match self.http.post(PAGERDUTY_EVENTS_URL).json(&body).send().await {
+            Ok(resp) if resp.status().is_success() => {
+                info!(rule = alert.rule.as_str(), "Alert delivered to PagerDuty");
+            }
+            Ok(resp) => warn!(status = %resp.status(), "PagerDuty rejected alert"),
+            Err(e) => warn!(error = %e, "Failed to deliver alert to PagerDuty"),
+        }
+    }
+}
@@ -0,0 +1,177 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Info,
+    Warning,
+    Critical,
+}
+
+impl Severity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Critical => "critical",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertRule {
+    TierDeactivatedWithEntitlements,
+    CheckpointLag,
+    ValidatorErrorRate,
+    KeyGasLow,
+    RelayerCapInvalid,
+    SigningFailure,
+    ReconcileDivergence,
+}
+
+impl AlertRule {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AlertRule::TierDeactivatedWithEntitlements => "tier_deactivated_with_entitlements",
+            AlertRule::CheckpointLag => "checkpoint_lag",
+            AlertRule::ValidatorErrorRate => "validator_error_rate",
+            AlertRule::KeyGasLow => "key_gas_low",
+            AlertRule::RelayerCapInvalid => "relayer_cap_invalid",
+            AlertRule::SigningFailure => "signing_failure",
+            AlertRule::ReconcileDivergence => "reconcile_divergence",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Alert {
+    pub rule: AlertRule,
+    pub severity: Severity,
+    pub summary: String,
+    /// Alerts sharing a dedup key are only delivered once per dedup window
+    pub dedup_key: String,
+    pub details: serde_json::Value,
+}
+
+impl Alert {
+    pub fn tier_deactivated(tier_id: &str, service_id: &str, active_entitlements: i64) -> Self {
+        Self {
+            rule: AlertRule::TierDeactivatedWithEntitlements,
+            severity: Severity::Critical,
+            summary: format!(
+                "Tier {} deactivated with {} active entitlements",
+                tier_id, active_entitlements
+            ),
+            dedup_key: format!("tier_deactivated:{}", tier_id),
+            details: serde_json::json!({
+                "tier_id": tier_id,
+                "service_id": service_id,
+                "active_entitlements": active_entitlements,
+            }),
+        }
+    }
+
+    pub fn checkpoint_lag(lag_secs: u64, last_checkpoint: Option<u64>) -> Self {
+        Self {
+            rule: AlertRule::CheckpointLag,
+            severity: Severity::Critical,
+            summary: format!("No checkpoint received in {}s", lag_secs),
+            dedup_key: "checkpoint_lag".to_string(),
+            details: serde_json::json!({
+                "lag_secs": lag_secs,
+                "last_checkpoint": last_checkpoint,
+            }),
+        }
+    }
+
+    pub fn validator_error_rate(errors: u64, total: u64, window_secs: u64) -> Self {
+        let rate = errors as f64 / total.max(1) as f64;
+        Self {
+            rule: AlertRule::ValidatorErrorRate,
+            severity: Severity::Warning,
+            summary: format!(
+                "Validator error rate {:.1}% ({}/{}) over the last {}s",
+                rate * 100.0,
+                errors,
+                total,
+                window_secs
+            ),
+            dedup_key: "validator_error_rate".to_string(),
+            details: serde_json::json!({
+                "errors": errors,
+                "total": total,
+                "rate": rate,
+                "window_secs": window_secs,
+            }),
+        }
+    }
+
+    pub fn key_gas_low(role: &str, address: &str, balance: u128, threshold: u64) -> Self {
+        let severity = if balance == 0 {
+            Severity::Critical
+        } else {
+            Severity::Warning
+        };
+        Self {
+            rule: AlertRule::KeyGasLow,
+            severity,
+            summary: format!(
+                "{} key {} has {} MIST of gas, below {}",
+                role, address, balance, threshold
+            ),
+            dedup_key: format!("key_gas_low:{}", address),
+            details: serde_json::json!({
+                "role": role,
+                "address": address,
+                "balance": balance.to_string(),
+                "threshold": threshold,
+            }),
+        }
+    }
+
+    pub fn relayer_cap_invalid(cap_id: &str, reason: &str) -> Self {
+        Self {
+            rule: AlertRule::RelayerCapInvalid,
+            severity: Severity::Critical,
+            summary: format!("Relayer cap {} {}", cap_id, reason),
+            dedup_key: format!("relayer_cap_invalid:{}", cap_id),
+            details: serde_json::json!({
+                "cap_id": cap_id,
+                "reason": reason,
+            }),
+        }
+    }
+
+    pub fn signing_failure(role: &str, address: &str, error: &str) -> Self {
+        Self {
+            rule: AlertRule::SigningFailure,
+            severity: Severity::Critical,
+            summary: format!("{} key {} failed to sign: {}", role, address, error),
+            dedup_key: format!("signing_failure:{}", address),
+            details: serde_json::json!({
+                "role": role,
+                "address": address,
+                "error": error,
+            }),
+        }
+    }
+
+    /// `examples` is a few of the divergences found, for the message
+    pub fn reconcile_divergence(divergences: usize, sampled: usize, examples: &[String]) -> Self {
+        Self {
+            rule: AlertRule::ReconcileDivergence,
+            severity: Severity::Warning,
+            summary: format!(
+                "{} divergence(s) between chain and Postgres in {} sampled rows",
+                divergences, sampled
+            ),
+            dedup_key: "reconcile_divergence".to_string(),
+            details: serde_json::json!({
+                "divergences": divergences,
+                "sampled": sampled,
+                "examples": examples,
+            }),
+        }
+    }
+}
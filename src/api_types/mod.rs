@@ -0,0 +1,2 @@
+pub mod validator;
+pub mod version;
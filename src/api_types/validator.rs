@@ -0,0 +1,183 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+/// Body of `POST /validate`
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ValidateRequest {
+    pub user_address: String,
+    pub service_id: String,
+    /// Units the request would consume
+    pub request_cost: u64,
+}
+
+/// Answer to `POST /validate`, sent with 403 and empty fields when the user
+/// holds no usable entitlement
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ValidateResponse {
+    pub entitlement_id: String,
+    pub tier: String,
+    /// Requests left on a quota tier
+    pub quota: Option<u64>,
+    /// Units left on a usage-based tier
+    pub units: Option<u64>,
+    /// 0 subscription, 1 quota, 2 usage-based
+    pub tier_type: u8,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub notify_provider: Option<ProviderNotification>,
+    /// Tier display fields, only returned for `?detail=full` (since 1.1)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tier_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub price: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub coin_type: Option<String>,
+    /// The retired tier the entitlement was bought on, when it is served
+    /// under its replacement `tier` (since 1.2)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub replaced_tier: Option<String>,
+}
+
+impl ValidateResponse {
+    pub fn denied() -> Self {
+        Self {
+            entitlement_id: String::new(),
+            tier: String::new(),
+            quota: None,
+            units: None,
+            tier_type: 0,
+            expires_at: None,
+            notify_provider: None,
+            tier_name: None,
+            price: None,
+            coin_type: None,
+            replaced_tier: None,
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ValidateParams {
+    /// `full` to include tier display fields in the response
+    pub detail: Option<String>,
+}
+
+impl ValidateParams {
+    pub fn full(&self) -> bool {
+        self.detail.as_deref() == Some("full")
+    }
+}
+
+/// Most items accepted by one `POST /validate/batch`
+pub const MAX_VALIDATE_BATCH: usize = 100;
+
+/// Body of `POST /validate/batch` (since 1.4)
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ValidateBatchRequest {
+    #[schema(max_items = 100)]
+    pub items: Vec<ValidateRequest>,
+}
+
+/// What `/validate` would have answered for one item of a batch
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ValidateBatchResult {
+    /// 200 if granted, 403 if denied or the service is another provider's,
+    /// 500 if the check failed
+    pub status: u16,
+    /// The entitlement, when granted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub entitlement: Option<ValidateResponse>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Answer to `POST /validate/batch`, with one result per item in order
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ValidateBatchResponse {
+    pub results: Vec<ValidateBatchResult>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, ToSchema)]
+pub struct ProviderNotification {
+    pub event: String,
+    pub user_address: String,
+    pub service_id: String,
+    #[schema(value_type = Object)]
+    pub detail: serde_json::Value,
+}
+
+/// Header naming a mutation so a retry is answered with the first
+/// response instead of running again (since 1.6)
+pub const IDEMPOTENCY_KEY: &str = "idempotency-key";
+
+/// Longest `request_id` accepted on `/record_usage`
+pub const MAX_REQUEST_ID_LEN: usize = 128;
+
+/// Body of `POST /record_usage`
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct RecordUsageRequest {
+    pub user_address: String,
+    pub entitlement_id: String,
+    pub cost: u64,
+    /// Idempotency key, unique per submission and resent unchanged on retry.
+    /// A repeat for the same entitlement is answered with 409 and not charged
+    /// again (since 1.3)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(max_length = 128)]
+    pub request_id: Option<String>,
+}
+
+/// Most records accepted by one `POST /record_usage/batch`
+pub const MAX_RECORD_USAGE_BATCH: usize = 100;
+
+/// Body of `POST /record_usage/batch`. Every record needs a `request_id`, so
+/// a retried batch charges only the records that weren't committed (since
+/// 1.5)
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct RecordUsageBatchRequest {
+    #[schema(max_items = 100)]
+    pub records: Vec<RecordUsageRequest>,
+}
+
+/// What `/record_usage` would have answered for one record of a batch
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct RecordUsageBatchResult {
+    /// 200 if charged, 409 if the request ID was already recorded, 400 if
+    /// the entitlement was not found or can't cover the cost
+    pub status: u16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Answer to `POST /record_usage/batch`, with one result per record in order
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct RecordUsageBatchResponse {
+    pub results: Vec<RecordUsageBatchResult>,
+}
+
+/// Sent by each sidecar every `heartbeat_interval_secs` to
+/// `POST /sidecars/heartbeat` (since 1.1)
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct SidecarHeartbeat {
+    pub provider_id: String,
+    pub instance_id: String,
+    pub version: String,
+    pub git_hash: String,
+    /// Fingerprint of the config with secrets redacted
+    pub config_hash: String,
+}
+
+/// An active or upcoming maintenance window of one of a provider's
+/// services, as listed by `GET /providers/{provider_id}/maintenance` for a
+/// sidecar to seed its maintenance state (since 1.7)
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ScheduledMaintenance {
+    #[schema(value_type = String)]
+    pub id: Uuid,
+    pub service_id: String,
+    pub starts_at: DateTime<Utc>,
+    pub ends_at: DateTime<Utc>,
+    pub reason: Option<String>,
+}
@@ -0,0 +1,94 @@
+use std::fmt;
+
+/// Header a client sends with the contract version it was built against,
+/// e.g. `Accept-Version: 1.1`
+pub const ACCEPT_VERSION: &str = "accept-version";
+
+/// Header the backend answers with, carrying its own contract version
+pub const API_VERSION: &str = "api-version";
+
+/// Version of the sidecar to validator API contract. Minor versions only add
+/// optional fields and routes, so any client on the same major version whose
+/// minor is not newer than the backend's is served.
+///
+/// - 1.0.0: `/validate` and `/record_usage`
+/// - 1.1.0: tier display fields behind `?detail=full`, `/sidecars/heartbeat`
+/// - 1.2.0: `replaced_tier` on entitlements served under a replacement tier
+/// - 1.3.0: `request_id` on `/record_usage`, repeats answered with 409
+/// - 1.4.0: `/validate/batch`
+/// - 1.5.0: `/record_usage/batch`
+/// - 1.6.0: `Idempotency-Key` on `/record_usage` and `/record_usage/batch`
+/// - 1.7.0: `/providers/{provider_id}/maintenance`
+pub const CURRENT: ApiVersion = ApiVersion::new(1, 7, 0);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ApiVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl ApiVersion {
+    pub const fn new(major: u32, minor: u32, patch: u32) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+        }
+    }
+
+    /// Parses `1`, `1.1` or `1.1.0`, with an optional leading `v`. Missing
+    /// parts are zero.
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let invalid = || format!("invalid API version {}, expected e.g. 1.1", s);
+
+        let mut parts = s.trim().trim_start_matches('v').split('.');
+        let mut next = |required: bool| match parts.next() {
+            Some(p) => p.parse::<u32>().map_err(|_| invalid()),
+            None if required => Err(invalid()),
+            None => Ok(0),
+        };
+        let version = Self::new(next(true)?, next(false)?, next(false)?);
+
+        if parts.next().is_some() {
+            return Err(invalid());
+        }
+        Ok(version)
+    }
+
+    /// The `major.minor` a client sends in `Accept-Version`
+    pub fn requirement(&self) -> String {
+        format!("{}.{}", self.major, self.minor)
+    }
+
+    /// Whether a backend on this version can serve a client built against
+    /// `requested`
+    pub fn serves(&self, requested: &ApiVersion) -> bool {
+        requested.major == self.major && requested.minor <= self.minor
+    }
+}
+
+impl fmt::Display for ApiVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// Picks the version to answer a request with. Clients that send no
+/// `Accept-Version` predate versioning and are served as 1.0.
+pub fn negotiate(accept_version: Option<&str>) -> Result<ApiVersion, String> {
+    let requested = match accept_version {
+        Some(v) => ApiVersion::parse(v)?,
+        None => ApiVersion::new(1, 0, 0),
+    };
+
+    if !CURRENT.serves(&requested) {
+        return Err(format!(
+            "API version {} is not supported, this backend serves {}.0 to {}",
+            requested.requirement(),
+            CURRENT.major,
+            CURRENT.requirement()
+        ));
+    }
+    Ok(CURRENT)
+}
@@ -0,0 +1,85 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::db::models::AccessRow;
+
+const CSV_HEADER: &str =
+    "user_address,entitlement_id,tier_id,tier_name,tier_type,expires_at,remaining,purchased_at";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AccessFormat {
+    #[default]
+    Json,
+    Csv,
+}
+
+impl AccessFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            AccessFormat::Json => "application/json",
+            AccessFormat::Csv => "text/csv; charset=utf-8",
+        }
+    }
+}
+
+/// Body of `GET /services/{service_id}/access`: every address holding an
+/// unexpired entitlement to the service when the list was taken
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccessList {
+    pub service_id: String,
+    pub as_of: DateTime<Utc>,
+    pub addresses: usize,
+    pub entitlements: Vec<AccessRow>,
+}
+
+impl AccessList {
+    pub fn new(service_id: String, entitlements: Vec<AccessRow>) -> Self {
+        let mut addresses: Vec<&str> = entitlements
+            .iter()
+            .map(|e| e.user_address.as_str())
+            .collect();
+        addresses.dedup();
+        let addresses = addresses.len();
+
+        Self {
+            service_id,
+            as_of: Utc::now(),
+            addresses,
+            entitlements,
+        }
+    }
+
+    /// One line per entitlement, RFC 4180 quoted. An empty `remaining` is a
+    /// subscription.
+    pub fn to_csv(&self) -> String {
+        let mut out = String::from(CSV_HEADER);
+        out.push_str("\r\n");
+
+        for row in &self.entitlements {
+            let fields = [
+                row.user_address.clone(),
+                row.entitlement_id.clone(),
+                row.tier_id.clone(),
+                row.tier_name.clone(),
+                row.tier_type.clone(),
+                row.expires_at.map(|t| t.to_rfc3339()).unwrap_or_default(),
+                row.remaining.map(|r| r.to_string()).unwrap_or_default(),
+                row.purchased_at.to_rfc3339(),
+            ];
+            let line: Vec<String> = fields.iter().map(|f| csv_field(f)).collect();
+            out.push_str(&line.join(","));
+            out.push_str("\r\n");
+        }
+
+        out
+    }
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
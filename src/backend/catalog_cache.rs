@@ -0,0 +1,135 @@
+use std::{future::Future, sync::Arc, time::Duration};
+
+use axum::{
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use moka::future::Cache;
+use sha2::{Digest, Sha256};
+
+use crate::utils::error::InfrapassError;
+
+/// Distinct catalog URLs kept at once
+const MAX_ENTRIES: u64 = 1_000;
+
+const HTTP_DATE: &str = "%a, %d %b %Y %H:%M:%S GMT";
+
+/// A rendered catalog response with its validators
+pub struct CatalogResponse {
+    body: String,
+    etag: String,
+    /// Newest `updated_at` among the rows, if any
+    last_modified: Option<DateTime<Utc>>,
+}
+
+impl CatalogResponse {
+    fn new(body: serde_json::Value, last_modified: Option<DateTime<Utc>>) -> Self {
+        let body = body.to_string();
+        let digest = Sha256::digest(body.as_bytes());
+        Self {
+            etag: format!("\"{}\"", hex::encode(&digest[..16])),
+            body,
+            last_modified,
+        }
+    }
+
+    /// The body, or `304` when the request's `If-None-Match`, or without it
+    /// its `If-Modified-Since`, shows the client already holds it
+    pub fn respond(&self, headers: &HeaderMap) -> Response {
+        let mut resp = if self.is_fresh(headers) {
+            StatusCode::NOT_MODIFIED.into_response()
+        } else {
+            (
+                [(header::CONTENT_TYPE, "application/json")],
+                self.body.clone(),
+            )
+                .into_response()
+        };
+
+        let resp_headers = resp.headers_mut();
+        if let Ok(etag) = HeaderValue::from_str(&self.etag) {
+            resp_headers.insert(header::ETAG, etag);
+        }
+        if let Some(modified) = self.last_modified {
+            if let Ok(value) = HeaderValue::from_str(&modified.format(HTTP_DATE).to_string()) {
+                resp_headers.insert(header::LAST_MODIFIED, value);
+            }
+        }
+        // Cacheable, but revalidated on every use, which is a cheap 304
+        resp_headers.insert(
+            header::CACHE_CONTROL,
+            HeaderValue::from_static("public, no-cache"),
+        );
+        resp
+    }
+
+    fn is_fresh(&self, headers: &HeaderMap) -> bool {
+        if let Some(tags) = headers.get(header::IF_NONE_MATCH) {
+            let tags = tags.to_str().unwrap_or_default();
+            return tags.split(',').any(|tag| {
+                let tag = tag.trim();
+                tag == "*" || tag.trim_start_matches("W/") == self.etag
+            });
+        }
+
+        let since = headers
+            .get(header::IF_MODIFIED_SINCE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| DateTime::parse_from_rfc2822(v).ok());
+        match (since, self.last_modified) {
+            // HTTP dates have whole seconds
+            (Some(since), Some(modified)) => modified.timestamp() <= since.timestamp(),
+            _ => false,
+        }
+    }
+}
+
+/// Public catalog responses kept in process by URL, so tier lists polled by
+/// many sidecars and frontends are rendered once. The event worker clears it
+/// after each catalog change it commits; changes made elsewhere, such as a
+/// reindex run by another process, show within `ttl`.
+pub struct CatalogCache {
+    cache: Cache<String, Arc<CatalogResponse>>,
+}
+
+impl CatalogCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            cache: Cache::builder()
+                .max_capacity(MAX_ENTRIES)
+                .time_to_live(ttl)
+                .build(),
+        }
+    }
+
+    /// The cached response for `key`, or the one `load` renders. `load`
+    /// returns the body with its newest `updated_at`, or `None` for a
+    /// missing resource, which is not cached.
+    pub async fn get_or_load<F, Fut>(
+        &self,
+        key: String,
+        load: F,
+    ) -> Result<Option<Arc<CatalogResponse>>, InfrapassError>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<
+            Output = Result<Option<(serde_json::Value, Option<DateTime<Utc>>)>, InfrapassError>,
+        >,
+    {
+        if let Some(hit) = self.cache.get(&key).await {
+            return Ok(Some(hit));
+        }
+
+        let Some((body, last_modified)) = load().await? else {
+            return Ok(None);
+        };
+        let resp = Arc::new(CatalogResponse::new(body, last_modified));
+        self.cache.insert(key, resp.clone()).await;
+        Ok(Some(resp))
+    }
+
+    pub fn invalidate_all(&self) {
+        self.cache.invalidate_all();
+    }
+}
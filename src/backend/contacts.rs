@@ -0,0 +1,90 @@
+use anyhow::{Result, anyhow};
+use shared_crypto::intent::{Intent, IntentMessage, PersonalMessage};
+use sui_types::{
+    base_types::SuiAddress,
+    crypto::{EncodeDecodeBase64, Signature, SuiSignature},
+};
+
+use crate::utils::{error::InfrapassError, vault::SecretVault};
+
+/// Signed requests older (or further in the future) than this are rejected,
+/// so a leaked signature cannot be replayed later
+const MAX_SIGNATURE_AGE_MS: i64 = 5 * 60 * 1000;
+
+/// Personal message a buyer signs to link `contact` to an entitlement
+pub fn link_message(entitlement_id: &str, contact: &str, timestamp_ms: i64) -> String {
+    format!(
+        "infrapass:link-contact:{}:{}:{}",
+        entitlement_id, contact, timestamp_ms
+    )
+}
+
+/// Personal message a buyer signs to delete the contact of an entitlement
+pub fn unlink_message(entitlement_id: &str, timestamp_ms: i64) -> String {
+    format!(
+        "infrapass:unlink-contact:{}:{}",
+        entitlement_id, timestamp_ms
+    )
+}
+
+/// Personal message a provider signs to read its buyers' contacts
+pub fn list_message(provider_id: &str, timestamp_ms: i64) -> String {
+    format!("infrapass:list-contacts:{}:{}", provider_id, timestamp_ms)
+}
+
+/// Checks that `signature` (base64, as produced by `sui keytool sign` or a
+/// wallet's signPersonalMessage) is `address` signing `message` recently
+pub fn verify_signed_message(
+    address: SuiAddress,
+    message: &str,
+    timestamp_ms: i64,
+    signature: &str,
+) -> Result<()> {
+    let age = chrono::Utc::now().timestamp_millis() - timestamp_ms;
+    if age.abs() > MAX_SIGNATURE_AGE_MS {
+        return Err(anyhow!("signature timestamp is too old or in the future"));
+    }
+
+    verify_personal_message(address, message, signature)
+}
+
+/// Checks that `signature` is `address` signing `message`, for messages
+/// whose freshness is checked some other way
+pub fn verify_personal_message(address: SuiAddress, message: &str, signature: &str) -> Result<()> {
+    let signature =
+        Signature::decode_base64(signature).map_err(|e| anyhow!("invalid signature: {}", e))?;
+    let intent_msg = IntentMessage::new(
+        Intent::personal_message(),
+        PersonalMessage {
+            message: message.as_bytes().to_vec(),
+        },
+    );
+
+    signature
+        .verify_secure(&intent_msg, address, signature.scheme())
+        .map_err(|e| anyhow!("signature verification failed: {}", e))
+}
+
+/// Seals buyer contacts with AES-256-GCM. Without a key, contact capture is
+/// disabled and every call fails.
+pub struct ContactVault(SecretVault);
+
+impl ContactVault {
+    /// `key_hex` is the 32-byte `CONTACT_ENCRYPTION_KEY`, hex encoded
+    pub fn new(key_hex: Option<&str>) -> Result<Self> {
+        Ok(Self(SecretVault::new(
+            "CONTACT_ENCRYPTION_KEY",
+            key_hex,
+            "contact capture is not enabled",
+        )?))
+    }
+
+    /// Returns the random nonce followed by the ciphertext
+    pub fn seal(&self, contact: &str) -> Result<Vec<u8>, InfrapassError> {
+        self.0.seal(contact)
+    }
+
+    pub fn open(&self, sealed: &[u8]) -> Result<String, InfrapassError> {
+        self.0.open(sealed)
+    }
+}
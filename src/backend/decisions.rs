@@ -0,0 +1,65 @@
+use std::{collections::VecDeque, sync::Mutex};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+/// One answer given by `/validate`
+#[derive(Debug, Clone, Serialize)]
+pub struct ValidationDecision {
+    pub at: DateTime<Utc>,
+    pub user_address: String,
+    pub service_id: String,
+    pub request_cost: u64,
+    /// `granted`, `denied` or `error`
+    pub outcome: &'static str,
+    pub entitlement_id: Option<String>,
+    pub error: Option<String>,
+}
+
+/// The latest `/validate` answers of this API process, for support staff
+/// debugging a buyer's access. Older ones are dropped once `capacity` is
+/// reached, and nothing survives a restart.
+pub struct DecisionLog {
+    entries: Mutex<VecDeque<ValidationDecision>>,
+    capacity: usize,
+}
+
+impl DecisionLog {
+    /// A capacity of 0 keeps nothing
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: Mutex::new(VecDeque::with_capacity(capacity)),
+            capacity,
+        }
+    }
+
+    pub fn record(&self, decision: ValidationDecision) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() == self.capacity {
+            entries.pop_front();
+        }
+        entries.push_back(decision);
+    }
+
+    /// Up to `limit` decisions, newest first, optionally for one buyer and
+    /// one service
+    pub fn recent(
+        &self,
+        user_address: Option<&str>,
+        service_id: Option<&str>,
+        limit: usize,
+    ) -> Vec<ValidationDecision> {
+        let entries = self.entries.lock().unwrap();
+        entries
+            .iter()
+            .rev()
+            .filter(|d| user_address.is_none_or(|u| d.user_address.eq_ignore_ascii_case(u)))
+            .filter(|d| service_id.is_none_or(|s| d.service_id == s))
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+}
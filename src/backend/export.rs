@@ -0,0 +1,253 @@
+use std::future::Future;
+
+use anyhow::Result;
+use axum::{
+    body::Body,
+    http::{HeaderValue, header},
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::{
+    db::{
+        models::{PurchaseExportRow, UsageExportRow},
+        page::{MAX_PAGE_SIZE, Page, PageRequest, SortOrder},
+        tenant,
+    },
+    utils::error::InfrapassError,
+};
+
+/// Window exported when `from` is not given
+const DEFAULT_WINDOW_DAYS: i64 = 30;
+
+/// Longest window one export may cover
+const MAX_WINDOW_DAYS: i64 = 366;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Csv,
+    /// A single JSON array
+    Json,
+}
+
+impl ExportFormat {
+    fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+            ExportFormat::Json => "application/json",
+        }
+    }
+
+    fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Json => "json",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ExportParams {
+    /// Start of the window, RFC 3339; 30 days before `to` by default
+    pub from: Option<DateTime<Utc>>,
+    /// End of the window, exclusive; now by default
+    pub to: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub format: ExportFormat,
+}
+
+impl ExportParams {
+    /// `[from, to)`, at most `MAX_WINDOW_DAYS` long
+    pub fn window(&self) -> Result<(DateTime<Utc>, DateTime<Utc>), InfrapassError> {
+        let to = self.to.unwrap_or_else(Utc::now);
+        let from = self
+            .from
+            .unwrap_or_else(|| to - Duration::days(DEFAULT_WINDOW_DAYS));
+        if from >= to {
+            return Err(InfrapassError::ValidationError(
+                "from must be before to".into(),
+            ));
+        }
+        if to - from > Duration::days(MAX_WINDOW_DAYS) {
+            return Err(InfrapassError::ValidationError(format!(
+                "an export may cover at most {} days",
+                MAX_WINDOW_DAYS
+            )));
+        }
+        Ok((from, to))
+    }
+}
+
+/// A row that can be exported as a CSV record as well as JSON
+pub trait ExportRecord: Serialize {
+    const CSV_HEADER: &'static str;
+
+    fn csv_fields(&self) -> Vec<String>;
+}
+
+impl ExportRecord for UsageExportRow {
+    const CSV_HEADER: &'static str = "id,recorded_at,entitlement_id,service_id,tier_id,user_address,amount,settled_at,settlement_id";
+
+    fn csv_fields(&self) -> Vec<String> {
+        vec![
+            self.id.to_string(),
+            self.recorded_at.to_rfc3339(),
+            self.entitlement_id.clone(),
+            self.service_id.clone(),
+            self.tier_id.clone(),
+            self.user_address.clone(),
+            self.amount.to_string(),
+            self.settled_at.map(|t| t.to_rfc3339()).unwrap_or_default(),
+            self.settlement_id
+                .map(|id| id.to_string())
+                .unwrap_or_default(),
+        ]
+    }
+}
+
+impl ExportRecord for PurchaseExportRow {
+    const CSV_HEADER: &'static str =
+        "entitlement_id,created_at,buyer,service_id,tier_id,tier_name,coin_type,price_paid";
+
+    fn csv_fields(&self) -> Vec<String> {
+        vec![
+            self.entitlement_id.clone(),
+            self.created_at.to_rfc3339(),
+            self.buyer.clone(),
+            self.service_id.clone(),
+            self.tier_id.clone(),
+            self.tier_name.clone(),
+            self.coin_type.clone(),
+            self.price_paid.to_string(),
+        ]
+    }
+}
+
+/// Quotes a CSV field when it holds a separator, quote or line break
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Where an export has got to: the cursor of the next page, and whether a
+/// row was written yet
+struct Progress<F> {
+    fetch: F,
+    tenant: Option<String>,
+    cursor: Option<String>,
+    started: bool,
+    done: bool,
+}
+
+impl<F> Progress<F> {
+    /// Fetches the next page and renders it, opening the document before the
+    /// first page and closing it after the last
+    async fn next_chunk<T, Fut>(&mut self, format: ExportFormat) -> Result<String>
+    where
+        T: ExportRecord,
+        F: Fn(PageRequest) -> Fut,
+        Fut: Future<Output = Result<Page<T>>>,
+    {
+        let request = PageRequest::new(
+            Some(MAX_PAGE_SIZE),
+            self.cursor.as_deref(),
+            SortOrder::Oldest,
+        )?;
+        let page = match self.tenant.clone() {
+            Some(tenant_id) => tenant::scope(tenant_id, (self.fetch)(request)).await?,
+            None => (self.fetch)(request).await?,
+        };
+
+        let mut chunk = String::new();
+        if !self.started {
+            match format {
+                ExportFormat::Csv => {
+                    chunk.push_str(T::CSV_HEADER);
+                    chunk.push('\n');
+                }
+                ExportFormat::Json => chunk.push('['),
+            }
+        }
+        for (i, row) in page.items.iter().enumerate() {
+            match format {
+                ExportFormat::Csv => {
+                    let fields: Vec<String> =
+                        row.csv_fields().iter().map(|f| csv_field(f)).collect();
+                    chunk.push_str(&fields.join(","));
+                    chunk.push('\n');
+                }
+                ExportFormat::Json => {
+                    if self.started || i > 0 {
+                        chunk.push(',');
+                    }
+                    chunk.push_str(&serde_json::to_string(row)?);
+                }
+            }
+        }
+
+        self.started = true;
+        self.cursor = page.next_cursor;
+        if self.cursor.is_none() {
+            self.done = true;
+            if format == ExportFormat::Json {
+                chunk.push(']');
+            }
+        }
+        Ok(chunk)
+    }
+}
+
+/// Streams every row `fetch` pages through, oldest first, as a chunked
+/// download. Pages are fetched as the client reads, so a large export never
+/// sits in memory. The body is streamed after the handler returns, so each
+/// page is fetched under the caller's tenant again. A failed page ends the
+/// download early.
+pub fn export_response<T, F, Fut>(format: ExportFormat, name: &str, fetch: F) -> Response
+where
+    T: ExportRecord + Send + 'static,
+    F: Fn(PageRequest) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<Page<T>>> + Send,
+{
+    let progress = Progress {
+        fetch,
+        tenant: tenant::current(),
+        cursor: None,
+        started: false,
+        done: false,
+    };
+
+    let stream = futures::stream::unfold(progress, move |mut progress| async move {
+        if progress.done {
+            return None;
+        }
+
+        let chunk = progress.next_chunk(format).await;
+        if let Err(e) = &chunk {
+            warn!("Export aborted: {}", e);
+            progress.done = true;
+        }
+        Some((chunk, progress))
+    });
+
+    let mut response = Body::from_stream(stream).into_response();
+    let headers = response.headers_mut();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static(format.content_type()),
+    );
+    if let Ok(disposition) = HeaderValue::from_str(&format!(
+        "attachment; filename=\"{}.{}\"",
+        name,
+        format.extension()
+    )) {
+        headers.insert(header::CONTENT_DISPOSITION, disposition);
+    }
+    response
+}
@@ -0,0 +1,259 @@
+use std::{sync::Arc, time::Duration};
+
+use chrono::{DateTime, Utc};
+use moka::future::Cache;
+use serde::Deserialize;
+
+use crate::{
+    db::{models::CatalogEvent, repository::Repository},
+    utils::error::InfrapassError,
+};
+
+const FEED_TITLE: &str = "Infrapass catalog";
+const FEED_ID: &str = "urn:infrapass:catalog";
+const FEED_ITEMS: i64 = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FeedFormat {
+    Json,
+    Atom,
+}
+
+impl FeedFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            FeedFormat::Json => "application/feed+json",
+            FeedFormat::Atom => "application/atom+xml",
+        }
+    }
+}
+
+/// One feed entry. The ID is derived from on-chain data only, so it stays
+/// the same across re-indexing.
+struct FeedItem {
+    id: String,
+    title: String,
+    content: String,
+    published: DateTime<Utc>,
+    event: CatalogEvent,
+}
+
+impl FeedItem {
+    fn from_event(event: CatalogEvent) -> Option<Self> {
+        let service = event.service_id.as_deref().unwrap_or("unknown");
+        let service_type = event.service_type.as_deref().unwrap_or("service");
+        let tier = event.tier_id.as_deref().unwrap_or("unknown");
+        let tier_name = event.tier_name.as_deref().unwrap_or("tier");
+        let price = match (event.price, event.coin_type.as_deref()) {
+            (Some(p), Some(coin)) => format!("{} {}", p, coin),
+            (Some(p), None) => p.to_string(),
+            _ => "an unknown price".to_string(),
+        };
+
+        let (id, title, content) = match event.event_type.as_str() {
+            "ServiceCreated" => (
+                format!("urn:infrapass:service:{}", service),
+                format!("New {} service listed", service_type),
+                format!(
+                    "Provider {} listed {} service {}. Metadata: {}",
+                    event.provider_id.as_deref().unwrap_or("unknown"),
+                    service_type,
+                    service,
+                    event.metadata_uri.as_deref().unwrap_or("none"),
+                ),
+            ),
+            "TierCreated" => (
+                format!("urn:infrapass:tier:{}", tier),
+                format!("New tier {} for {} service", tier_name, service_type),
+                match &event.sla {
+                    Some(sla) => format!(
+                        "Tier {} ({}) was added to service {} at {}. SLA: {}",
+                        tier_name, tier, service, price, sla.0
+                    ),
+                    None => format!(
+                        "Tier {} ({}) was added to service {} at {}",
+                        tier_name, tier, service, price
+                    ),
+                },
+            ),
+            "TierPriceUpdated" => (
+                format!(
+                    "urn:infrapass:tier:{}:price:{}",
+                    tier,
+                    event
+                        .transaction_digest
+                        .clone()
+                        .unwrap_or_else(|| event.checkpoint_number.to_string())
+                ),
+                format!("Price change for tier {}", tier_name),
+                format!(
+                    "Tier {} ({}) of service {} now costs {}",
+                    tier_name, tier, service, price
+                ),
+            ),
+            "TierAddedToService" => (
+                format!(
+                    "urn:infrapass:tier:{}:service:{}:added:{}",
+                    tier,
+                    service,
+                    event
+                        .transaction_digest
+                        .clone()
+                        .unwrap_or_else(|| event.checkpoint_number.to_string())
+                ),
+                format!("Tier {} now offered by {} service", tier_name, service_type),
+                format!(
+                    "Tier {} ({}) was added to service {} at {}",
+                    tier_name, tier, service, price
+                ),
+            ),
+            "TierRemovedFromService" => (
+                format!(
+                    "urn:infrapass:tier:{}:service:{}:removed:{}",
+                    tier,
+                    service,
+                    event
+                        .transaction_digest
+                        .clone()
+                        .unwrap_or_else(|| event.checkpoint_number.to_string())
+                ),
+                format!("Tier {} withdrawn from {} service", tier_name, service_type),
+                format!(
+                    "Tier {} ({}) is no longer offered by service {}",
+                    tier_name, tier, service
+                ),
+            ),
+            _ => return None,
+        };
+
+        let published = event
+            .timestamp_ms
+            .and_then(DateTime::from_timestamp_millis)
+            .unwrap_or(event.event_time);
+
+        Some(Self {
+            id,
+            title,
+            content,
+            published,
+            event,
+        })
+    }
+}
+
+/// Renders the catalog feed and keeps each format cached for `ttl`
+pub struct CatalogFeed {
+    repo: Arc<Repository>,
+    cache: Cache<FeedFormat, Arc<String>>,
+    ttl: Duration,
+}
+
+impl CatalogFeed {
+    pub fn new(repo: Arc<Repository>, ttl: Duration) -> Self {
+        Self {
+            repo,
+            cache: Cache::builder().max_capacity(2).time_to_live(ttl).build(),
+            ttl,
+        }
+    }
+
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    pub async fn render(&self, format: FeedFormat) -> Result<Arc<String>, InfrapassError> {
+        self.cache
+            .try_get_with(format, async {
+                let items: Vec<FeedItem> = self
+                    .repo
+                    .get_catalog_events(FEED_ITEMS)
+                    .await?
+                    .into_iter()
+                    .filter_map(FeedItem::from_event)
+                    .collect();
+
+                let body = match format {
+                    FeedFormat::Json => render_json(&items)?,
+                    FeedFormat::Atom => render_atom(&items),
+                };
+                Ok::<_, InfrapassError>(Arc::new(body))
+            })
+            .await
+            .map_err(|e| InfrapassError::Other(e.to_string()))
+    }
+}
+
+fn render_json(items: &[FeedItem]) -> Result<String, InfrapassError> {
+    let items: Vec<serde_json::Value> = items
+        .iter()
+        .map(|item| {
+            serde_json::json!({
+                "id": item.id,
+                "title": item.title,
+                "content_text": item.content,
+                "date_published": item.published.to_rfc3339(),
+                "tags": [item.event.event_type],
+                "_infrapass": {
+                    "event_type": item.event.event_type,
+                    "provider_id": item.event.provider_id,
+                    "service_id": item.event.service_id,
+                    "tier_id": item.event.tier_id,
+                    "price": item.event.price,
+                    "coin_type": item.event.coin_type,
+                    "sla": item.event.sla,
+                    "checkpoint": item.event.checkpoint_number,
+                    "transaction_digest": item.event.transaction_digest,
+                },
+            })
+        })
+        .collect();
+
+    Ok(serde_json::to_string(&serde_json::json!({
+        "version": "https://jsonfeed.org/version/1.1",
+        "title": FEED_TITLE,
+        "description": "New service listings and tier price changes",
+        "items": items,
+    }))?)
+}
+
+fn render_atom(items: &[FeedItem]) -> String {
+    let updated = items.first().map(|i| i.published).unwrap_or_else(Utc::now);
+
+    let mut xml = String::from(r#"<?xml version="1.0" encoding="utf-8"?>"#);
+    xml.push_str("\n<feed xmlns=\"http://www.w3.org/2005/Atom\">\n");
+    xml.push_str(&format!("  <id>{}</id>\n", FEED_ID));
+    xml.push_str(&format!("  <title>{}</title>\n", FEED_TITLE));
+    xml.push_str(&format!("  <updated>{}</updated>\n", updated.to_rfc3339()));
+    xml.push_str("  <author><name>Infrapass</name></author>\n");
+
+    for item in items {
+        xml.push_str("  <entry>\n");
+        xml.push_str(&format!("    <id>{}</id>\n", escape_xml(&item.id)));
+        xml.push_str(&format!("    <title>{}</title>\n", escape_xml(&item.title)));
+        xml.push_str(&format!(
+            "    <updated>{}</updated>\n",
+            item.published.to_rfc3339()
+        ));
+        xml.push_str(&format!(
+            "    <category term=\"{}\"/>\n",
+            escape_xml(&item.event.event_type)
+        ));
+        xml.push_str(&format!(
+            "    <content type=\"text\">{}</content>\n",
+            escape_xml(&item.content)
+        ));
+        xml.push_str("  </entry>\n");
+    }
+
+    xml.push_str("</feed>\n");
+    xml
+}
+
+fn escape_xml(raw: &str) -> String {
+    raw.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
@@ -0,0 +1,218 @@
+use std::{future::Future, sync::Arc};
+
+use tonic::{Request, Response, Status, metadata::MetadataValue};
+use tracing::{error, info};
+
+use crate::{
+    alerting::manager::AlertManager,
+    api_types::validator::{self, RecordUsageRequest, ValidateRequest},
+    backend::{
+        decisions::DecisionLog,
+        handlers::{authorize_usage, check_entitlement},
+        middleware::{Authenticated, authenticate},
+        rate_limit::RateLimiter,
+        session::SessionSigner,
+        state::AppState,
+    },
+    db::{models::UsageCommit, repository::Repository, storage::Storage, tenant},
+    types::amount::Units,
+    utils::error::InfrapassError,
+};
+
+pub mod proto {
+    tonic::include_proto!("infrapass.validator.v1");
+}
+
+use proto::validator_server::{Validator, ValidatorServer};
+
+/// The validator contract over gRPC, see `proto/validator.proto`. Calls are
+/// authenticated, rate limited and checked exactly as their REST routes, and
+/// share their rate limit buckets.
+pub struct ValidatorService {
+    repo: Arc<Repository>,
+    storage: Arc<dyn Storage>,
+    alerts: Arc<AlertManager>,
+    decisions: Arc<DecisionLog>,
+    sessions: Arc<SessionSigner>,
+    rate_limiter: Option<Arc<RateLimiter>>,
+}
+
+impl ValidatorService {
+    pub fn new(state: &AppState) -> Self {
+        Self {
+            repo: state.repo.clone(),
+            storage: state.storage.clone(),
+            alerts: state.alerts.clone(),
+            decisions: state.decisions.clone(),
+            sessions: state.sessions.clone(),
+            rate_limiter: state.rate_limiter.clone(),
+        }
+    }
+
+    pub fn into_server(self) -> ValidatorServer<Self> {
+        ValidatorServer::new(self)
+    }
+
+    /// Resolves the call's bearer token and takes a token from the caller's
+    /// bucket for `route`
+    async fn authorize<T>(
+        &self,
+        request: &Request<T>,
+        route: &str,
+    ) -> Result<Authenticated, Status> {
+        let Some(key) = request
+            .metadata()
+            .get("authorization")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "))
+        else {
+            return Err(Status::unauthenticated("invalid or missing API key"));
+        };
+
+        let auth = match authenticate(&self.repo, &self.sessions, key).await {
+            Ok(Some(auth)) => auth,
+            Ok(None) => return Err(Status::unauthenticated("invalid or missing API key")),
+            Err(e) => {
+                error!("{:#}", e);
+                return Err(Status::internal("internal error"));
+            }
+        };
+
+        if let Some(limiter) = &self.rate_limiter {
+            if let Some(retry_after) = limiter.check(key, route).await {
+                let mut status = Status::resource_exhausted("rate limit exceeded");
+                if let Ok(value) = MetadataValue::try_from(retry_after.to_string()) {
+                    status.metadata_mut().insert("retry-after", value);
+                }
+                return Err(status);
+            }
+        }
+
+        Ok(auth)
+    }
+}
+
+/// Runs `f` bound to the caller's tenant, as `api_key_auth` runs a request
+async fn scoped<F: Future>(tenant_id: Option<String>, f: F) -> F::Output {
+    match tenant_id {
+        Some(tenant_id) => tenant::scope(tenant_id, f).await,
+        None => f.await,
+    }
+}
+
+/// The status a REST route would have answered `e` with
+fn to_status(e: InfrapassError) -> Status {
+    match e {
+        InfrapassError::ValidationError(msg) => Status::invalid_argument(msg),
+        InfrapassError::Forbidden(msg) => Status::permission_denied(msg),
+        e => Status::internal(e.to_string()),
+    }
+}
+
+impl From<validator::ValidateResponse> for proto::ValidateResponse {
+    fn from(r: validator::ValidateResponse) -> Self {
+        Self {
+            granted: true,
+            entitlement_id: r.entitlement_id,
+            tier: r.tier,
+            quota: r.quota,
+            units: r.units,
+            tier_type: r.tier_type as u32,
+            expires_at: r.expires_at.map(|t| t.to_rfc3339()),
+            notify_provider: r.notify_provider.map(|n| proto::ProviderNotification {
+                event: n.event,
+                user_address: n.user_address,
+                service_id: n.service_id,
+                detail_json: n.detail.to_string(),
+            }),
+            tier_name: r.tier_name,
+            price: r.price,
+            coin_type: r.coin_type,
+            replaced_tier: r.replaced_tier,
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl Validator for ValidatorService {
+    async fn validate(
+        &self,
+        request: Request<proto::ValidateRequest>,
+    ) -> Result<Response<proto::ValidateResponse>, Status> {
+        let auth = self.authorize(&request, "validate").await?;
+        let request = request.into_inner();
+        let payload = ValidateRequest {
+            user_address: request.user_address,
+            service_id: request.service_id,
+            request_cost: request.request_cost,
+        };
+
+        let result = scoped(
+            auth.tenant_id,
+            check_entitlement(
+                &auth.caller,
+                self.storage.as_ref(),
+                &self.alerts,
+                &self.decisions,
+                &payload,
+                request.full,
+            ),
+        )
+        .await
+        .map_err(to_status)?;
+
+        info!(
+            user = %payload.user_address,
+            service = %payload.service_id,
+            cost = payload.request_cost,
+            "gRPC entitlement validation request"
+        );
+        Ok(Response::new(result.map(Into::into).unwrap_or_default()))
+    }
+
+    async fn record_usage(
+        &self,
+        request: Request<proto::RecordUsageRequest>,
+    ) -> Result<Response<proto::RecordUsageResponse>, Status> {
+        let auth = self.authorize(&request, "record_usage").await?;
+        let request = request.into_inner();
+        let payload = RecordUsageRequest {
+            user_address: request.user_address,
+            entitlement_id: request.entitlement_id,
+            cost: request.cost,
+            request_id: request.request_id,
+        };
+
+        let commit = scoped(auth.tenant_id, async {
+            authorize_usage(&auth.caller, self.storage.as_ref(), &payload)
+                .await
+                .map_err(to_status)?;
+            // An entitlement that can't cover the cost is answered with 400
+            // over REST; here the request itself was fine
+            self.storage
+                .commit_usage(
+                    &payload.entitlement_id,
+                    &payload.user_address,
+                    Units::new(payload.cost),
+                    payload.request_id.as_deref(),
+                )
+                .await
+                .map_err(|e| match e {
+                    InfrapassError::ValidationError(msg) => Status::failed_precondition(msg),
+                    e => to_status(e),
+                })
+        })
+        .await?;
+
+        info!(
+            user = %payload.user_address,
+            entitlement_id = %payload.entitlement_id,
+            cost = payload.cost,
+            duplicate = matches!(commit, UsageCommit::Duplicate),
+            "gRPC usage recorded"
+        );
+        Ok(Response::new(proto::RecordUsageResponse {
+            duplicate: matches!(commit, UsageCommit::Duplicate),
+        }))
+    }
+}
@@ -1,35 +1,349 @@
-use std::sync::Arc;
+use std::{collections::HashSet, convert::Infallible, str::FromStr, sync::Arc};
 
 use crate::{
-    sidecar::validator::{ValidateRequest, ValidateResponse},
-    db::repository::Repository,
-    utils::error::InfrapassError,
+    alerting::manager::AlertManager,
+    api_types::validator::{
+        MAX_RECORD_USAGE_BATCH, MAX_REQUEST_ID_LEN, MAX_VALIDATE_BATCH, RecordUsageBatchRequest,
+        RecordUsageBatchResponse, RecordUsageBatchResult, RecordUsageRequest, ScheduledMaintenance,
+        SidecarHeartbeat, ValidateBatchRequest, ValidateBatchResponse, ValidateBatchResult,
+        ValidateParams, ValidateRequest, ValidateResponse,
+    },
+    backend::{
+        access::{AccessFormat, AccessList},
+        catalog_cache::{CatalogCache, CatalogResponse},
+        contacts::{self, ContactVault},
+        decisions::{DecisionLog, ValidationDecision},
+        export::{ExportParams, export_response},
+        feed::{CatalogFeed, FeedFormat},
+        keys::KEY_METRICS,
+        middleware::Caller,
+        provider_webhooks::{self, WebhookSecrets},
+        readiness::Readiness,
+        reconcile::RECONCILE_METRICS,
+        revenue::RevenueReport,
+        scheduler::{JobBoard, SCHEDULER_METRICS},
+        session::{self, CHALLENGE_TTL_SECS, SessionSigner},
+        stats::ProviderStats,
+        spend::{MAX_LOOKBACK_DAYS, SpendBucket, SpendReport, indexed_coin_type, parse_lookback},
+        usage::{ProviderUsageReport, UsageReport},
+    },
+    sidecar::fleet,
+    db::{
+        models::{ApiKey, Service, ServiceListing, TierHistory, TierType, UsageCommit},
+        page::{
+            EntitlementFilter, MAX_PAGE_SIZE, Page, PageRequest, ProviderFilter, ServiceFilter,
+            SortOrder, TierFilter,
+        },
+        repository::Repository,
+        storage::Storage,
+    },
+    events::{filter::EventFilter, metrics::INDEXER_METRICS, stream::EventStream},
+    pubsub::{publisher::PubSubPublisher, types::MaintenanceNotice},
+    transactions::payments::top_up_entitlement_tx,
+    types::{
+        amount::{MistAmount, Units},
+        sla::SlaTerms,
+    },
+    utils::{error::InfrapassError, package::upgraded_package_id, webhook::generate_secret},
 };
 use axum::{
-    extract::{Json, State},
-    http::StatusCode,
-    response::IntoResponse,
+    extract::{Extension, Json, Path, Query, State},
+    http::{HeaderMap, StatusCode, Uri, header},
+    response::{
+        IntoResponse, Response,
+        sse::{Event, KeepAlive, Sse},
+    },
 };
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
+use chrono::{DateTime, NaiveDate, Utc};
+use futures::{Stream, StreamExt};
+use sui_sdk::SuiClient;
+use sui_types::base_types::{ObjectID, SuiAddress};
+use tokio::sync::broadcast::error::RecvError;
 use tracing::{info, warn};
+use uuid::Uuid;
+
+/// `/validate` answers shown with an admin entitlement or buyer lookup
+const ADMIN_RECENT_DECISIONS: usize = 20;
+
+/// Longest a rotated API key keeps working alongside its replacement
+const MAX_KEY_ROTATION_GRACE_SECS: u64 = 7 * 24 * 3600;
+
+/// Items of a `/validate/batch` looked up at once
+const VALIDATE_BATCH_CONCURRENCY: usize = 8;
+
+#[derive(Debug, serde::Deserialize)]
+pub struct MaintenanceRequest {
+    pub service_id: String,
+    pub starts_at: DateTime<Utc>,
+    pub ends_at: DateTime<Utc>,
+    pub reason: Option<String>,
+}
 
 #[derive(Debug, serde::Deserialize)]
-pub struct RecordUsageRequest {
+pub struct BuyerWebhookRequest {
     pub user_address: String,
+    pub url: String,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct ProviderWebhookRequest {
+    pub provider_id: String,
+    pub url: String,
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+pub struct DeliveryParams {
+    /// `pending`, `delivered` or `failed`
+    pub status: Option<String>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+pub struct SettlementParams {
+    /// `submitted`, `settled` or `failed`
+    pub status: Option<String>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct LinkContactRequest {
     pub entitlement_id: String,
-    pub cost: u64,
+    pub contact: String,
+    pub timestamp_ms: i64,
+    /// Base64 Sui signature of the personal message from `contacts::link_message`
+    pub signature: String,
+}
+
+/// Timestamp and signature of a signed request whose subject is in the path
+#[derive(Debug, serde::Deserialize)]
+pub struct SignedRequest {
+    pub timestamp_ms: i64,
+    pub signature: String,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct SignInChallengeRequest {
+    pub provider_id: String,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct SignInRequest {
+    pub provider_id: String,
+    pub nonce: String,
+    /// Base64 Sui signature of the challenge's `message`
+    pub signature: String,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct TopUpRequest {
+    /// Base units of the tier's coin to pay; buys `amount / price` units
+    pub amount: MistAmount,
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+pub struct FeedParams {
+    /// `json` (default) or `atom`; falls back to the Accept header
+    pub format: Option<FeedFormat>,
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+pub struct ListProvidersParams {
+    pub active: Option<bool>,
+    pub limit: Option<i64>,
+    pub cursor: Option<String>,
+    #[serde(default)]
+    pub sort: SortOrder,
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+pub struct ListEntitlementsParams {
+    /// Unexpired (true) or expired (false) entitlements only
+    pub active: Option<bool>,
+    pub limit: Option<i64>,
+    pub cursor: Option<String>,
+    #[serde(default)]
+    pub sort: SortOrder,
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+pub struct ListServicesParams {
+    pub provider_id: Option<String>,
+    pub service_type: Option<String>,
+    pub active: Option<bool>,
+    pub limit: Option<i64>,
+    pub cursor: Option<String>,
+    #[serde(default)]
+    pub sort: SortOrder,
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+pub struct SearchServicesParams {
+    /// Web-search style: words, "quoted phrases", `or` and `-excluded`
+    pub q: String,
+    pub active: Option<bool>,
+    /// Matches to return, 20 by default
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+pub struct ListTiersParams {
+    pub provider_id: Option<String>,
+    /// Anything `--coin-type` accepts, e.g. `SUI`
+    pub coin_type: Option<String>,
+    pub active: Option<bool>,
+    pub limit: Option<i64>,
+    pub cursor: Option<String>,
+    #[serde(default)]
+    pub sort: SortOrder,
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+pub struct AccessParams {
+    /// `json` (default) or `csv`; falls back to the Accept header
+    pub format: Option<AccessFormat>,
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+pub struct SpendParams {
+    /// Lookback such as `30d` (default), `12h` or `2w`
+    pub since: Option<String>,
+    pub bucket: Option<SpendBucket>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct UsageParams {
+    /// Lookback such as `30d` (default) or `2w`, rounded down to whole UTC days
+    pub since: Option<String>,
+    /// Top buyers to list, 20 by default
+    pub limit: Option<i64>,
+}
+
+impl UsageParams {
+    fn since(&self) -> Result<NaiveDate, InfrapassError> {
+        let lookback = parse_lookback(self.since.as_deref().unwrap_or("30d"))
+            .map_err(InfrapassError::ValidationError)?;
+        Ok((Utc::now() - lookback).date_naive())
+    }
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct StatsParams {
+    /// Lookback such as `30d` (default) or `2w`
+    pub since: Option<String>,
+    /// Period purchases are counted by, `day` by default
+    pub bucket: Option<SpendBucket>,
+    /// Top buyers to list, 10 by default
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct BuyerBudgetRequest {
+    pub user_address: String,
+    /// `SUI`, `USDC`, ... or a full coin type
+    pub coin_type: String,
+    /// Base units of the coin, e.g. MIST for SUI
+    pub amount: MistAmount,
+    #[serde(default = "default_budget_period_days")]
+    pub period_days: i32,
+}
+
+fn default_budget_period_days() -> i32 {
+    30
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct IssueApiKeyRequest {
+    pub provider_id: String,
+    pub label: Option<String>,
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+pub struct ListApiKeysParams {
+    pub provider_id: Option<String>,
 }
 
+#[derive(Debug, Default, serde::Deserialize)]
+pub struct RotateApiKeyParams {
+    /// Seconds the old key keeps working, so sidecars can switch over; 0 by
+    /// default
+    pub grace_secs: Option<u64>,
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+pub struct AdminBuyerParams {
+    /// Also show what `/validate` answers for this service
+    pub service_id: Option<String>,
+    pub active: Option<bool>,
+    pub limit: Option<i64>,
+    pub cursor: Option<String>,
+    #[serde(default)]
+    pub sort: SortOrder,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct InvalidateRequest {
+    pub user_address: String,
+    pub service_id: String,
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+pub struct ValidationLogParams {
+    pub user_address: Option<String>,
+    pub service_id: Option<String>,
+    /// 100 by default
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+pub struct EventStreamParams {
+    /// Only this provider's events; a provider key gets its own by default
+    pub provider_id: Option<String>,
+    /// Modules and events to send, as `INDEXER_EVENTS` takes them
+    pub types: Option<String>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct TierReplacementRequest {
+    pub replacement_tier_id: String,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct BudgetCoinParams {
+    pub coin_type: String,
+}
+
+/// Whether a buyer holds an entitlement to a service that covers a request
+#[utoipa::path(
+    post,
+    path = "/validate",
+    tag = "validator",
+    params(ValidateParams),
+    request_body = ValidateRequest,
+    responses(
+        (status = 200, description = "Entitlement found", body = ValidateResponse),
+        (status = 403, description = "No usable entitlement, with empty fields", body = ValidateResponse),
+        (status = 429, description = "API key over its rate limit; see `Retry-After`"),
+    ),
+    security(("api_key" = []))
+)]
 pub async fn validate_entitlements_handler(
-    State(repo): State<Arc<Repository>>,
+    State(storage): State<Arc<dyn Storage>>,
+    State(alerts): State<Arc<AlertManager>>,
+    State(decisions): State<Arc<DecisionLog>>,
+    Extension(caller): Extension<Caller>,
+    Query(params): Query<ValidateParams>,
     Json(payload): Json<ValidateRequest>,
 ) -> Result<impl IntoResponse, InfrapassError> {
-    let result = repo
-        .get_valid_entitlement_response(
-            &payload.user_address,
-            &payload.service_id,
-            payload.request_cost,
-        )
-        .await?;
+    let result = check_entitlement(
+        &caller,
+        storage.as_ref(),
+        &alerts,
+        &decisions,
+        &payload,
+        params.full(),
+    )
+    .await?;
 
     info!(
         user = %payload.user_address,
@@ -40,23 +354,145 @@ pub async fn validate_entitlements_handler(
 
     match result {
         Some(entitlement) => Ok((StatusCode::OK, Json(entitlement))),
-        None => Ok((
-            StatusCode::FORBIDDEN,
-            Json(ValidateResponse {
-                entitlement_id: String::new(),
-                tier: String::new(),
-                quota: None,
-                units: None,
-                tier_type: 0,
-                expires_at: None,
-                notify_provider: None,
-            }),
-        )),
+        None => Ok((StatusCode::FORBIDDEN, Json(ValidateResponse::denied()))),
+    }
+}
+
+/// Checks up to `MAX_VALIDATE_BATCH` buyers and services in one request,
+/// answering each as `/validate` would. A batch is one request to the rate
+/// limiter.
+#[utoipa::path(
+    post,
+    path = "/validate/batch",
+    tag = "validator",
+    params(ValidateParams),
+    request_body = ValidateBatchRequest,
+    responses(
+        (status = 200, description = "One result per item, in order", body = ValidateBatchResponse),
+        (status = 400, description = "More than 100 items"),
+        (status = 429, description = "API key over its rate limit; see `Retry-After`"),
+    ),
+    security(("api_key" = []))
+)]
+pub async fn validate_batch_handler(
+    State(storage): State<Arc<dyn Storage>>,
+    State(alerts): State<Arc<AlertManager>>,
+    State(decisions): State<Arc<DecisionLog>>,
+    Extension(caller): Extension<Caller>,
+    Query(params): Query<ValidateParams>,
+    Json(payload): Json<ValidateBatchRequest>,
+) -> Result<Json<ValidateBatchResponse>, InfrapassError> {
+    if payload.items.len() > MAX_VALIDATE_BATCH {
+        return Err(InfrapassError::ValidationError(format!(
+            "at most {} items per batch",
+            MAX_VALIDATE_BATCH
+        )));
     }
+
+    let results = futures::stream::iter(&payload.items)
+        .map(|item| async {
+            let result = check_entitlement(
+                &caller,
+                storage.as_ref(),
+                &alerts,
+                &decisions,
+                item,
+                params.full(),
+            )
+            .await;
+            match result {
+                Ok(Some(entitlement)) => ValidateBatchResult {
+                    status: StatusCode::OK.as_u16(),
+                    entitlement: Some(entitlement),
+                    error: None,
+                },
+                Ok(None) => ValidateBatchResult {
+                    status: StatusCode::FORBIDDEN.as_u16(),
+                    entitlement: None,
+                    error: None,
+                },
+                Err(InfrapassError::Forbidden(msg)) => ValidateBatchResult {
+                    status: StatusCode::FORBIDDEN.as_u16(),
+                    entitlement: None,
+                    error: Some(msg),
+                },
+                Err(e) => ValidateBatchResult {
+                    status: StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
+                    entitlement: None,
+                    error: Some(e.to_string()),
+                },
+            }
+        })
+        .buffered(VALIDATE_BATCH_CONCURRENCY)
+        .collect::<Vec<_>>()
+        .await;
+
+    info!(
+        items = results.len(),
+        "Batch entitlement validation request"
+    );
+    Ok(Json(ValidateBatchResponse { results }))
 }
 
+/// Looks up a buyer's entitlement for `/validate`, its batch form and the
+/// gRPC `Validate`, recording the outcome for alerts and the admin decision
+/// log. A provider key may only check its own services.
+pub(crate) async fn check_entitlement(
+    caller: &Caller,
+    storage: &dyn Storage,
+    alerts: &Arc<AlertManager>,
+    decisions: &DecisionLog,
+    payload: &ValidateRequest,
+    full: bool,
+) -> Result<Option<ValidateResponse>, InfrapassError> {
+    require_service_owner(caller, storage, &payload.service_id).await?;
+
+    let result = storage
+        .get_valid_entitlement_response(
+            &payload.user_address,
+            &payload.service_id,
+            payload.request_cost,
+            full,
+        )
+        .await;
+    alerts.record_validation(result.is_err());
+    decisions.record(ValidationDecision {
+        at: Utc::now(),
+        user_address: payload.user_address.clone(),
+        service_id: payload.service_id.clone(),
+        request_cost: payload.request_cost,
+        outcome: match &result {
+            Ok(Some(_)) => "granted",
+            Ok(None) => "denied",
+            Err(_) => "error",
+        },
+        entitlement_id: result
+            .as_ref()
+            .ok()
+            .and_then(Option::as_ref)
+            .map(|e| e.entitlement_id.clone()),
+        error: result.as_ref().err().map(|e| e.to_string()),
+    });
+    result
+}
+
+/// Charges a request to an entitlement
+#[utoipa::path(
+    post,
+    path = "/record_usage",
+    tag = "validator",
+    request_body = RecordUsageRequest,
+    responses(
+        (status = 200, description = "Usage recorded"),
+        (status = 400, description = "Invalid cost or request_id, or the entitlement can't cover it"),
+        (status = 409, description = "request_id already recorded for the entitlement"),
+        (status = 429, description = "API key over its rate limit; see `Retry-After`"),
+    ),
+    security(("api_key" = []))
+)]
 pub async fn record_usage_handler(
-    State(repo): State<Arc<Repository>>,
+    State(storage): State<Arc<dyn Storage>>,
+    Extension(caller): Extension<Caller>,
     Json(payload): Json<RecordUsageRequest>,
 ) -> Result<impl IntoResponse, InfrapassError> {
     let timer = std::time::Instant::now();
@@ -67,18 +503,32 @@ pub async fn record_usage_handler(
         "Recording usage"
     );
 
-    if payload.cost == 0 {
-        return Ok((
-            StatusCode::BAD_REQUEST,
-            Json(serde_json::json!({"error": "cost must be > 0"})),
-        ));
-    }
+    authorize_usage(&caller, storage.as_ref(), &payload).await?;
 
-    match repo
-        .commit_usage(&payload.entitlement_id, &payload.user_address, payload.cost)
+    match storage
+        .commit_usage(
+            &payload.entitlement_id,
+            &payload.user_address,
+            Units::new(payload.cost),
+            payload.request_id.as_deref(),
+        )
         .await
     {
-        Ok(()) => {
+        Ok(UsageCommit::Duplicate) => {
+            info!(
+                user = %payload.user_address,
+                entitlement_id = %payload.entitlement_id,
+                request_id = payload.request_id.as_deref().unwrap_or_default(),
+                "Duplicate usage submission ignored"
+            );
+
+            Ok((
+                StatusCode::CONFLICT,
+                Json(serde_json::json!({"status": "already recorded"})),
+            ))
+        }
+
+        Ok(UsageCommit::Recorded) => {
             let duration = timer.elapsed().as_secs_f64();
 
             info!(
@@ -112,3 +562,1919 @@ pub async fn record_usage_handler(
         }
     }
 }
+
+/// Checks a `/record_usage` or gRPC `RecordUsage` submission before it is
+/// charged: a cost, a well-formed `request_id` if any, and for a provider
+/// key, an entitlement to one of its own services
+pub(crate) async fn authorize_usage(
+    caller: &Caller,
+    storage: &dyn Storage,
+    payload: &RecordUsageRequest,
+) -> Result<(), InfrapassError> {
+    if payload.cost == 0 {
+        return Err(InfrapassError::ValidationError("cost must be > 0".into()));
+    }
+    if let Some(request_id) = &payload.request_id {
+        if request_id.is_empty() || request_id.len() > MAX_REQUEST_ID_LEN {
+            return Err(InfrapassError::ValidationError(format!(
+                "request_id must be 1 to {} bytes",
+                MAX_REQUEST_ID_LEN
+            )));
+        }
+    }
+    if let Caller::Provider { .. } = caller {
+        match storage
+            .entitlement_provider(&payload.entitlement_id)
+            .await?
+        {
+            Some(provider_id) => caller.require_provider(&provider_id)?,
+            None => return Err(not_own_resource()),
+        }
+    }
+    Ok(())
+}
+
+/// Charges up to `MAX_RECORD_USAGE_BATCH` records in one transaction. A
+/// malformed record or one on another provider's entitlement rejects the
+/// whole batch before anything is charged.
+#[utoipa::path(
+    post,
+    path = "/record_usage/batch",
+    tag = "validator",
+    request_body = RecordUsageBatchRequest,
+    responses(
+        (status = 200, description = "One result per record, in order", body = RecordUsageBatchResponse),
+        (status = 400, description = "More than 100 records, or a record with an invalid cost or no request_id"),
+        (status = 429, description = "API key over its rate limit; see `Retry-After`"),
+    ),
+    security(("api_key" = []))
+)]
+pub async fn record_usage_batch_handler(
+    State(storage): State<Arc<dyn Storage>>,
+    Extension(caller): Extension<Caller>,
+    Json(payload): Json<RecordUsageBatchRequest>,
+) -> Result<Json<RecordUsageBatchResponse>, InfrapassError> {
+    let timer = std::time::Instant::now();
+    if payload.records.len() > MAX_RECORD_USAGE_BATCH {
+        return Err(InfrapassError::ValidationError(format!(
+            "at most {} records per batch",
+            MAX_RECORD_USAGE_BATCH
+        )));
+    }
+    for (i, record) in payload.records.iter().enumerate() {
+        if record.cost == 0 {
+            return Err(InfrapassError::ValidationError(format!(
+                "records[{}]: cost must be > 0",
+                i
+            )));
+        }
+        match &record.request_id {
+            Some(id) if !id.is_empty() && id.len() <= MAX_REQUEST_ID_LEN => {}
+            _ => {
+                return Err(InfrapassError::ValidationError(format!(
+                    "records[{}]: request_id must be 1 to {} bytes",
+                    i, MAX_REQUEST_ID_LEN
+                )));
+            }
+        }
+    }
+    if let Caller::Provider { .. } = caller {
+        let entitlements: HashSet<&str> = payload
+            .records
+            .iter()
+            .map(|r| r.entitlement_id.as_str())
+            .collect();
+        for entitlement_id in entitlements {
+            match storage.entitlement_provider(entitlement_id).await? {
+                Some(provider_id) => caller.require_provider(&provider_id)?,
+                None => return Err(not_own_resource()),
+            }
+        }
+    }
+
+    let results: Vec<RecordUsageBatchResult> = storage
+        .commit_usage_batch(&payload.records)
+        .await?
+        .into_iter()
+        .map(|result| match result {
+            Ok(UsageCommit::Recorded) => RecordUsageBatchResult {
+                status: StatusCode::OK.as_u16(),
+                error: None,
+            },
+            Ok(UsageCommit::Duplicate) => RecordUsageBatchResult {
+                status: StatusCode::CONFLICT.as_u16(),
+                error: None,
+            },
+            Err(e) => RecordUsageBatchResult {
+                status: StatusCode::BAD_REQUEST.as_u16(),
+                error: Some(e.to_string()),
+            },
+        })
+        .collect();
+
+    let recorded = results
+        .iter()
+        .filter(|r| r.status == StatusCode::OK.as_u16())
+        .count();
+    info!(
+        records = results.len(),
+        recorded,
+        duration_ms = timer.elapsed().as_secs_f64() * 1000.0,
+        "Usage batch recorded"
+    );
+    Ok(Json(RecordUsageBatchResponse { results }))
+}
+
+pub async fn create_maintenance_handler(
+    State(repo): State<Arc<Repository>>,
+    State(publisher): State<Arc<PubSubPublisher>>,
+    State(catalog): State<Arc<CatalogCache>>,
+    Extension(caller): Extension<Caller>,
+    Json(payload): Json<MaintenanceRequest>,
+) -> Result<impl IntoResponse, InfrapassError> {
+    if payload.ends_at <= payload.starts_at {
+        return Err(InfrapassError::ValidationError(
+            "ends_at must be after starts_at".into(),
+        ));
+    }
+    if payload.ends_at <= Utc::now() {
+        return Err(InfrapassError::ValidationError(
+            "maintenance window is already over".into(),
+        ));
+    }
+
+    let service = repo
+        .get_service(&payload.service_id)
+        .await?
+        .ok_or_else(|| InfrapassError::ValidationError("unknown service".into()))?;
+    caller.require_provider(&service.provider_id)?;
+
+    let window = repo
+        .create_maintenance_window(
+            &payload.service_id,
+            payload.starts_at,
+            payload.ends_at,
+            payload.reason.as_deref(),
+        )
+        .await?;
+
+    publisher
+        .publish_maintenance(
+            &service.provider_id,
+            &service.service_id,
+            MaintenanceNotice::from(&window),
+        )
+        .await?;
+    catalog.invalidate_all();
+
+    info!(
+        service = %window.service_id,
+        starts_at = %window.starts_at,
+        ends_at = %window.ends_at,
+        "Maintenance window scheduled"
+    );
+
+    Ok((StatusCode::CREATED, Json(window)))
+}
+
+pub async fn list_maintenance_handler(
+    State(repo): State<Arc<Repository>>,
+    Extension(caller): Extension<Caller>,
+    Path(service_id): Path<String>,
+) -> Result<impl IntoResponse, InfrapassError> {
+    require_service_owner(&caller, repo.as_ref(), &service_id).await?;
+    let windows = repo.get_upcoming_maintenance(&service_id).await?;
+    Ok(Json(windows))
+}
+
+pub async fn cancel_maintenance_handler(
+    State(repo): State<Arc<Repository>>,
+    State(publisher): State<Arc<PubSubPublisher>>,
+    State(catalog): State<Arc<CatalogCache>>,
+    Extension(caller): Extension<Caller>,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, InfrapassError> {
+    if let Caller::Provider { .. } = caller {
+        if let Some(window) = repo.get_maintenance_window(id).await? {
+            require_service_owner(&caller, repo.as_ref(), &window.service_id).await?;
+        }
+    }
+
+    let Some(window) = repo.cancel_maintenance_window(id).await? else {
+        return Ok((
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({"error": "maintenance window not found"})),
+        ));
+    };
+    catalog.invalidate_all();
+
+    if let Some(service) = repo.get_service(&window.service_id).await? {
+        publisher
+            .publish_maintenance_cancelled(&service.provider_id, &service.service_id, window.id)
+            .await?;
+    }
+
+    Ok((
+        StatusCode::OK,
+        Json(serde_json::json!({"status": "maintenance window cancelled"})),
+    ))
+}
+
+pub async fn register_buyer_webhook_handler(
+    State(repo): State<Arc<Repository>>,
+    Extension(caller): Extension<Caller>,
+    Json(payload): Json<BuyerWebhookRequest>,
+) -> Result<impl IntoResponse, InfrapassError> {
+    caller.reject_provider_key()?;
+    let user_address = normalize_address(&payload.user_address)?;
+    if !payload.url.starts_with("https://") && !payload.url.starts_with("http://") {
+        return Err(InfrapassError::ValidationError(
+            "url must be an http(s) URL".into(),
+        ));
+    }
+
+    let webhook = repo
+        .create_buyer_webhook(&user_address, &payload.url, &generate_secret())
+        .await?;
+
+    info!(user = %user_address, url = %webhook.url, "Buyer webhook registered");
+
+    // The secret is only ever returned here
+    Ok((
+        StatusCode::CREATED,
+        Json(serde_json::json!({
+            "id": webhook.id,
+            "user_address": webhook.user_address,
+            "url": webhook.url,
+            "secret": webhook.secret,
+        })),
+    ))
+}
+
+pub async fn list_buyer_webhooks_handler(
+    State(repo): State<Arc<Repository>>,
+    Extension(caller): Extension<Caller>,
+    Path(user_address): Path<String>,
+) -> Result<impl IntoResponse, InfrapassError> {
+    caller.reject_provider_key()?;
+    let webhooks = repo
+        .list_buyer_webhooks(&normalize_address(&user_address)?)
+        .await?;
+    Ok(Json(webhooks))
+}
+
+pub async fn delete_buyer_webhook_handler(
+    State(repo): State<Arc<Repository>>,
+    Extension(caller): Extension<Caller>,
+    Path((user_address, id)): Path<(String, Uuid)>,
+) -> Result<impl IntoResponse, InfrapassError> {
+    caller.reject_provider_key()?;
+    if !repo
+        .delete_buyer_webhook(&normalize_address(&user_address)?, id)
+        .await?
+    {
+        return Ok((
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({"error": "webhook not found"})),
+        ));
+    }
+
+    Ok((
+        StatusCode::OK,
+        Json(serde_json::json!({"status": "webhook deleted"})),
+    ))
+}
+
+pub async fn register_provider_webhook_handler(
+    State(repo): State<Arc<Repository>>,
+    State(secrets): State<Arc<WebhookSecrets>>,
+    Extension(caller): Extension<Caller>,
+    Json(payload): Json<ProviderWebhookRequest>,
+) -> Result<impl IntoResponse, InfrapassError> {
+    require_provider_access(&caller, &repo, &payload.provider_id).await?;
+    if !payload.url.starts_with("https://") && !payload.url.starts_with("http://") {
+        return Err(InfrapassError::ValidationError(
+            "url must be an http(s) URL".into(),
+        ));
+    }
+    if repo.get_provider(&payload.provider_id).await?.is_none() {
+        return Ok((
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({"error": "provider not found"})),
+        ));
+    }
+
+    let secret = generate_secret();
+    let (plaintext, ciphertext) = secrets.store(&secret)?;
+    let webhook = repo
+        .create_provider_webhook(
+            &payload.provider_id,
+            &payload.url,
+            plaintext.as_deref(),
+            ciphertext.as_deref(),
+        )
+        .await?;
+
+    info!(provider_id = %webhook.provider_id, url = %webhook.url, "Provider webhook registered");
+
+    // The secret is only ever returned here
+    Ok((
+        StatusCode::CREATED,
+        Json(serde_json::json!({
+            "id": webhook.id,
+            "provider_id": webhook.provider_id,
+            "url": webhook.url,
+            "secret": secret,
+        })),
+    ))
+}
+
+pub async fn list_provider_webhooks_handler(
+    State(repo): State<Arc<Repository>>,
+    Extension(caller): Extension<Caller>,
+    Path(provider_id): Path<String>,
+) -> Result<impl IntoResponse, InfrapassError> {
+    require_provider_access(&caller, &repo, &provider_id).await?;
+    let webhooks = repo.list_provider_webhooks(&provider_id).await?;
+    Ok(Json(webhooks))
+}
+
+pub async fn delete_provider_webhook_handler(
+    State(repo): State<Arc<Repository>>,
+    Extension(caller): Extension<Caller>,
+    Path((provider_id, id)): Path<(String, Uuid)>,
+) -> Result<impl IntoResponse, InfrapassError> {
+    require_provider_access(&caller, &repo, &provider_id).await?;
+    if !repo.delete_provider_webhook(&provider_id, id).await? {
+        return Ok((
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({"error": "webhook not found"})),
+        ));
+    }
+
+    Ok((
+        StatusCode::OK,
+        Json(serde_json::json!({"status": "webhook deleted"})),
+    ))
+}
+
+/// Posts a signed `webhook_test` notification to a provider webhook and
+/// reports how the endpoint answered
+pub async fn test_provider_webhook_handler(
+    State(repo): State<Arc<Repository>>,
+    State(secrets): State<Arc<WebhookSecrets>>,
+    Extension(caller): Extension<Caller>,
+    Path((provider_id, id)): Path<(String, Uuid)>,
+) -> Result<Response, InfrapassError> {
+    require_provider_access(&caller, &repo, &provider_id).await?;
+    let Some(webhook) = repo.get_provider_webhook(&provider_id, id).await? else {
+        return Ok((
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({"error": "webhook not found"})),
+        )
+            .into_response());
+    };
+
+    let delivery = provider_webhooks::send_test(&secrets, &webhook).await?;
+    info!(
+        webhook_id = %webhook.id,
+        delivered = delivery.delivered,
+        status_code = delivery.status_code,
+        "Provider webhook test sent"
+    );
+    Ok(Json(delivery).into_response())
+}
+
+/// Most recent deliveries to a provider webhook, newest first
+pub async fn list_webhook_deliveries_handler(
+    State(repo): State<Arc<Repository>>,
+    Extension(caller): Extension<Caller>,
+    Path((provider_id, id)): Path<(String, Uuid)>,
+    Query(params): Query<DeliveryParams>,
+) -> Result<impl IntoResponse, InfrapassError> {
+    require_provider_access(&caller, &repo, &provider_id).await?;
+    if let Some(status) = params.status.as_deref() {
+        if !matches!(status, "pending" | "delivered" | "failed") {
+            return Err(InfrapassError::ValidationError(
+                "status must be pending, delivered or failed".into(),
+            ));
+        }
+    }
+
+    let deliveries = repo
+        .list_webhook_deliveries(
+            &provider_id,
+            id,
+            params.status.as_deref(),
+            params.limit.unwrap_or(100).clamp(1, 1000),
+        )
+        .await?;
+    Ok(Json(deliveries))
+}
+
+/// A buyer's purchases per service and coin. Public, since every purchase
+/// is on chain anyway.
+pub async fn buyer_spend_handler(
+    State(repo): State<Arc<Repository>>,
+    Path(user_address): Path<String>,
+    Query(params): Query<SpendParams>,
+) -> Result<impl IntoResponse, InfrapassError> {
+    let user_address = normalize_address(&user_address)?;
+    let lookback = parse_lookback(params.since.as_deref().unwrap_or("30d"))
+        .map_err(InfrapassError::ValidationError)?;
+    let bucket = params.bucket.unwrap_or_default();
+    let since = Utc::now() - lookback;
+
+    let periods = repo
+        .buyer_spend(&user_address, since, bucket.as_str())
+        .await?;
+    Ok(Json(SpendReport::new(user_address, since, bucket, periods)))
+}
+
+/// A provider's sales per service and coin. Public, like buyer spend,
+/// since every purchase is on chain anyway.
+pub async fn provider_revenue_handler(
+    State(repo): State<Arc<Repository>>,
+    Path(provider_id): Path<String>,
+    Query(params): Query<SpendParams>,
+) -> Result<impl IntoResponse, InfrapassError> {
+    let lookback = parse_lookback(params.since.as_deref().unwrap_or("30d"))
+        .map_err(InfrapassError::ValidationError)?;
+    let bucket = params.bucket.unwrap_or_default();
+    let since = Utc::now() - lookback;
+
+    let periods = repo
+        .provider_revenue(&provider_id, since, bucket.as_str())
+        .await?;
+    Ok(Json(RevenueReport::new(
+        provider_id,
+        since,
+        bucket,
+        periods,
+    )))
+}
+
+pub async fn set_buyer_budget_handler(
+    State(repo): State<Arc<Repository>>,
+    Extension(caller): Extension<Caller>,
+    Json(payload): Json<BuyerBudgetRequest>,
+) -> Result<impl IntoResponse, InfrapassError> {
+    caller.reject_provider_key()?;
+    let user_address = normalize_address(&payload.user_address)?;
+    let coin_type = indexed_coin_type(&payload.coin_type)
+        .map_err(|e| InfrapassError::ValidationError(e.to_string()))?;
+    if payload.amount.is_zero() {
+        return Err(InfrapassError::ValidationError(
+            "amount must be positive".into(),
+        ));
+    }
+    if payload.period_days < 1 || payload.period_days as i64 > MAX_LOOKBACK_DAYS {
+        return Err(InfrapassError::ValidationError(format!(
+            "period_days must be between 1 and {}",
+            MAX_LOOKBACK_DAYS
+        )));
+    }
+
+    let budget = repo
+        .upsert_buyer_budget(
+            &user_address,
+            &coin_type,
+            payload.amount,
+            payload.period_days,
+        )
+        .await?;
+
+    info!(
+        user = %user_address,
+        coin_type = %coin_type,
+        amount = %budget.amount,
+        "Buyer budget set"
+    );
+
+    Ok(Json(budget))
+}
+
+/// A buyer's budgets with what was spent in each window so far
+pub async fn list_buyer_budgets_handler(
+    State(repo): State<Arc<Repository>>,
+    Extension(caller): Extension<Caller>,
+    Path(user_address): Path<String>,
+) -> Result<impl IntoResponse, InfrapassError> {
+    caller.reject_provider_key()?;
+    let user_address = normalize_address(&user_address)?;
+
+    let mut out = vec![];
+    for budget in repo.list_buyer_budgets(&user_address).await? {
+        let spent = repo
+            .buyer_spend_in_coin(&user_address, &budget.coin_type, budget.window_start())
+            .await?;
+        out.push(serde_json::json!({
+            "coin_type": budget.coin_type,
+            "amount": budget.amount,
+            "period_days": budget.period_days,
+            "spent": spent,
+            "exceeded": spent > budget.amount,
+            "updated_at": budget.updated_at,
+        }));
+    }
+
+    Ok(Json(serde_json::json!(out)))
+}
+
+pub async fn delete_buyer_budget_handler(
+    State(repo): State<Arc<Repository>>,
+    Extension(caller): Extension<Caller>,
+    Path(user_address): Path<String>,
+    Query(params): Query<BudgetCoinParams>,
+) -> Result<impl IntoResponse, InfrapassError> {
+    caller.reject_provider_key()?;
+    let coin_type = indexed_coin_type(&params.coin_type)
+        .map_err(|e| InfrapassError::ValidationError(e.to_string()))?;
+    if !repo
+        .delete_buyer_budget(&normalize_address(&user_address)?, &coin_type)
+        .await?
+    {
+        return Ok((
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({"error": "budget not found"})),
+        ));
+    }
+
+    Ok((
+        StatusCode::OK,
+        Json(serde_json::json!({"status": "budget deleted"})),
+    ))
+}
+
+pub async fn set_tier_sla_handler(
+    State(repo): State<Arc<Repository>>,
+    State(catalog): State<Arc<CatalogCache>>,
+    Extension(caller): Extension<Caller>,
+    Path(tier_id): Path<String>,
+    Json(payload): Json<SlaTerms>,
+) -> Result<impl IntoResponse, InfrapassError> {
+    require_tier_owner(&caller, &repo, &tier_id).await?;
+    payload
+        .validate()
+        .map_err(InfrapassError::ValidationError)?;
+
+    let Some(tier) = repo.set_tier_sla(&tier_id, Some(&payload)).await? else {
+        return Err(InfrapassError::ValidationError("unknown tier".into()));
+    };
+
+    catalog.invalidate_all();
+    info!(tier_id = %tier.tier_id, sla = %payload, "Tier SLA published");
+
+    Ok(Json(tier))
+}
+
+pub async fn clear_tier_sla_handler(
+    State(repo): State<Arc<Repository>>,
+    State(catalog): State<Arc<CatalogCache>>,
+    Extension(caller): Extension<Caller>,
+    Path(tier_id): Path<String>,
+) -> Result<impl IntoResponse, InfrapassError> {
+    require_tier_owner(&caller, &repo, &tier_id).await?;
+    let Some(tier) = repo.set_tier_sla(&tier_id, None).await? else {
+        return Err(InfrapassError::ValidationError("unknown tier".into()));
+    };
+    catalog.invalidate_all();
+
+    Ok(Json(tier))
+}
+
+/// Points holders of a tier to a replacement for when the tier is retired.
+/// The replacement must be active, of the same type and listed by a service
+/// that lists the tier.
+pub async fn set_tier_replacement_handler(
+    State(repo): State<Arc<Repository>>,
+    Extension(caller): Extension<Caller>,
+    Path(tier_id): Path<String>,
+    Json(payload): Json<TierReplacementRequest>,
+) -> Result<impl IntoResponse, InfrapassError> {
+    require_tier_owner(&caller, &repo, &tier_id).await?;
+    if payload.replacement_tier_id == tier_id {
+        return Err(InfrapassError::ValidationError(
+            "a tier cannot replace itself".into(),
+        ));
+    }
+    let Some(tier) = repo.get_tier(&tier_id).await? else {
+        return Err(InfrapassError::ValidationError("unknown tier".into()));
+    };
+    let Some(replacement) = repo.get_tier(&payload.replacement_tier_id).await? else {
+        return Err(InfrapassError::ValidationError(
+            "unknown replacement tier".into(),
+        ));
+    };
+
+    if replacement.is_active == Some(false) {
+        return Err(InfrapassError::ValidationError(
+            "replacement tier is not active".into(),
+        ));
+    }
+    if replacement.tier_type != tier.tier_type {
+        return Err(InfrapassError::ValidationError(
+            "replacement tier must be of the same type".into(),
+        ));
+    }
+    let services = tier.service_ids.unwrap_or_default();
+    let shares_service = replacement
+        .service_ids
+        .unwrap_or_default()
+        .iter()
+        .any(|s| services.contains(s));
+    if !shares_service {
+        return Err(InfrapassError::ValidationError(
+            "replacement tier is not listed by the tier's services".into(),
+        ));
+    }
+
+    let mapping = repo
+        .set_tier_replacement(&tier_id, &payload.replacement_tier_id)
+        .await?;
+    info!(
+        tier_id = %tier_id,
+        replacement_tier_id = %payload.replacement_tier_id,
+        "Tier replacement set"
+    );
+
+    Ok(Json(mapping))
+}
+
+pub async fn clear_tier_replacement_handler(
+    State(repo): State<Arc<Repository>>,
+    Extension(caller): Extension<Caller>,
+    Path(tier_id): Path<String>,
+) -> Result<impl IntoResponse, InfrapassError> {
+    require_tier_owner(&caller, &repo, &tier_id).await?;
+    if !repo.clear_tier_replacement(&tier_id).await? {
+        return Ok((
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({"error": "tier has no replacement"})),
+        ));
+    }
+
+    Ok((
+        StatusCode::OK,
+        Json(serde_json::json!({"status": "cleared"})),
+    ))
+}
+
+/// The tier buyers of a retired tier should purchase instead
+pub async fn get_tier_replacement_handler(
+    State(repo): State<Arc<Repository>>,
+    Path(tier_id): Path<String>,
+) -> Result<impl IntoResponse, InfrapassError> {
+    let replacement = match repo.get_tier_replacement(&tier_id).await? {
+        Some(mapping) => repo.get_tier(&mapping.replacement_tier_id).await?,
+        None => None,
+    };
+    let Some(replacement) = replacement else {
+        return Ok((
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({"error": "tier has no replacement"})),
+        ));
+    };
+
+    Ok((
+        StatusCode::OK,
+        Json(serde_json::json!({
+            "tier_id": tier_id,
+            "replacement": replacement,
+        })),
+    ))
+}
+
+pub async fn tier_history_handler(
+    State(repo): State<Arc<Repository>>,
+    Path(tier_id): Path<String>,
+) -> Result<impl IntoResponse, InfrapassError> {
+    let Some(tier) = repo.get_tier(&tier_id).await? else {
+        return Ok((
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({"error": "tier not found"})),
+        ));
+    };
+    let changes = repo.tier_history(&tier_id).await?;
+
+    Ok((
+        StatusCode::OK,
+        Json(serde_json::json!(TierHistory { tier, changes })),
+    ))
+}
+
+pub async fn list_providers_handler(
+    State(repo): State<Arc<Repository>>,
+    State(catalog): State<Arc<CatalogCache>>,
+    Query(params): Query<ListProvidersParams>,
+    uri: Uri,
+    headers: HeaderMap,
+) -> Result<Response, InfrapassError> {
+    let page = page_request(params.limit, params.cursor.as_deref(), params.sort)?;
+    let filter = ProviderFilter {
+        active: params.active,
+    };
+
+    let resp = catalog
+        .get_or_load(uri.to_string(), || async {
+            let providers = repo.list_providers(&filter, &page).await?;
+            let modified = providers.items.iter().map(|p| p.updated_at).max();
+            Ok(Some((serde_json::json!(providers), modified)))
+        })
+        .await?;
+    Ok(catalog_response(resp, &headers, "provider not found"))
+}
+
+pub async fn list_services_handler(
+    State(repo): State<Arc<Repository>>,
+    State(catalog): State<Arc<CatalogCache>>,
+    Query(params): Query<ListServicesParams>,
+    uri: Uri,
+    headers: HeaderMap,
+) -> Result<Response, InfrapassError> {
+    let page = page_request(params.limit, params.cursor.as_deref(), params.sort)?;
+    let filter = ServiceFilter {
+        provider_id: params.provider_id,
+        service_type: params.service_type,
+        active: params.active,
+    };
+
+    let resp = catalog
+        .get_or_load(uri.to_string(), || async {
+            let services = repo.list_services(&filter, &page).await?;
+            let modified = services.items.iter().map(|s| s.updated_at).max();
+            let services = with_maintenance(&repo, services).await?;
+            Ok(Some((serde_json::json!(services), modified)))
+        })
+        .await?;
+    Ok(catalog_response(resp, &headers, "service not found"))
+}
+
+/// A provider's services, paged like `/services`, whose `provider_id`
+/// filter the path replaces
+pub async fn list_provider_services_handler(
+    State(repo): State<Arc<Repository>>,
+    State(catalog): State<Arc<CatalogCache>>,
+    Path(provider_id): Path<String>,
+    Query(params): Query<ListServicesParams>,
+    uri: Uri,
+    headers: HeaderMap,
+) -> Result<Response, InfrapassError> {
+    let page = page_request(params.limit, params.cursor.as_deref(), params.sort)?;
+
+    let resp = catalog
+        .get_or_load(uri.to_string(), || async {
+            if repo.get_provider(&provider_id).await?.is_none() {
+                return Ok(None);
+            }
+            let filter = ServiceFilter {
+                provider_id: Some(provider_id.clone()),
+                service_type: params.service_type,
+                active: params.active,
+            };
+            let services = repo.list_services(&filter, &page).await?;
+            let modified = services.items.iter().map(|s| s.updated_at).max();
+            let services = with_maintenance(&repo, services).await?;
+            Ok(Some((serde_json::json!(services), modified)))
+        })
+        .await?;
+    Ok(catalog_response(resp, &headers, "provider not found"))
+}
+
+/// A page of services with each one's next maintenance window
+async fn with_maintenance(
+    repo: &Repository,
+    services: Page<Service>,
+) -> Result<Page<ServiceListing>, InfrapassError> {
+    let ids: Vec<String> = services
+        .items
+        .iter()
+        .map(|s| s.service_id.clone())
+        .collect();
+    let mut windows = repo.next_maintenance(&ids).await?;
+
+    Ok(services.map(|service| ServiceListing {
+        upcoming_maintenance: windows.remove(&service.service_id),
+        service,
+    }))
+}
+
+/// A cached catalog response, or 404 with `missing` when there is none
+fn catalog_response(
+    resp: Option<Arc<CatalogResponse>>,
+    headers: &HeaderMap,
+    missing: &str,
+) -> Response {
+    match resp {
+        Some(resp) => resp.respond(headers),
+        None => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "error": missing })),
+        )
+            .into_response(),
+    }
+}
+
+/// Longest search query accepted
+const MAX_SEARCH_QUERY_LEN: usize = 256;
+
+pub async fn search_services_handler(
+    State(repo): State<Arc<Repository>>,
+    Query(params): Query<SearchServicesParams>,
+) -> Result<impl IntoResponse, InfrapassError> {
+    let query = params.q.trim();
+    if query.is_empty() {
+        return Err(InfrapassError::ValidationError(
+            "q must not be empty".to_string(),
+        ));
+    }
+    if query.len() > MAX_SEARCH_QUERY_LEN {
+        return Err(InfrapassError::ValidationError(format!(
+            "q must be at most {} bytes",
+            MAX_SEARCH_QUERY_LEN
+        )));
+    }
+
+    let limit = params.limit.unwrap_or(20).clamp(1, MAX_PAGE_SIZE);
+    let matches = repo.search_services(query, params.active, limit).await?;
+
+    Ok(Json(serde_json::json!({
+        "query": query,
+        "matches": matches,
+    })))
+}
+
+pub async fn list_tiers_handler(
+    State(repo): State<Arc<Repository>>,
+    State(catalog): State<Arc<CatalogCache>>,
+    Query(params): Query<ListTiersParams>,
+    uri: Uri,
+    headers: HeaderMap,
+) -> Result<Response, InfrapassError> {
+    let page = page_request(params.limit, params.cursor.as_deref(), params.sort)?;
+    let coin_type = params
+        .coin_type
+        .as_deref()
+        .map(indexed_coin_type)
+        .transpose()
+        .map_err(|e| InfrapassError::ValidationError(e.to_string()))?;
+    let filter = TierFilter {
+        provider_id: params.provider_id,
+        coin_type,
+        active: params.active,
+    };
+
+    let resp = catalog
+        .get_or_load(uri.to_string(), || async {
+            let tiers = repo.list_tiers(&filter, &page).await?;
+            let modified = tiers.items.iter().map(|t| t.updated_at).max();
+            Ok(Some((serde_json::json!(tiers), modified)))
+        })
+        .await?;
+    Ok(catalog_response(resp, &headers, "tier not found"))
+}
+
+/// One tier, inactive or not, with the services that list it
+pub async fn get_tier_handler(
+    State(repo): State<Arc<Repository>>,
+    State(catalog): State<Arc<CatalogCache>>,
+    Path(tier_id): Path<String>,
+    uri: Uri,
+    headers: HeaderMap,
+) -> Result<Response, InfrapassError> {
+    let resp = catalog
+        .get_or_load(uri.to_string(), || async {
+            Ok(repo
+                .get_tier(&tier_id)
+                .await?
+                .map(|tier| (serde_json::json!(tier), Some(tier.updated_at))))
+        })
+        .await?;
+    Ok(catalog_response(resp, &headers, "tier not found"))
+}
+
+/// Active tiers of a service with their SLA terms, cheapest first
+pub async fn list_service_tiers_handler(
+    State(repo): State<Arc<Repository>>,
+    State(catalog): State<Arc<CatalogCache>>,
+    Path(service_id): Path<String>,
+    uri: Uri,
+    headers: HeaderMap,
+) -> Result<Response, InfrapassError> {
+    let resp = catalog
+        .get_or_load(uri.to_string(), || async {
+            let tiers = repo.list_tiers_by_service(&service_id).await?;
+            let modified = tiers.iter().map(|t| t.updated_at).max();
+            Ok(Some((serde_json::json!(tiers), modified)))
+        })
+        .await?;
+    Ok(catalog_response(resp, &headers, "service not found"))
+}
+
+/// A service's usage per day and its heaviest buyers, read from the daily
+/// rollup, so the current day lags by up to one `usage_rollup` run
+pub async fn service_usage_handler(
+    State(repo): State<Arc<Repository>>,
+    Extension(caller): Extension<Caller>,
+    Path(service_id): Path<String>,
+    Query(params): Query<UsageParams>,
+) -> Result<impl IntoResponse, InfrapassError> {
+    require_service_owner(&caller, repo.as_ref(), &service_id).await?;
+    let since = params.since()?;
+    let limit = params.limit.unwrap_or(20).clamp(1, 100);
+
+    let days = repo.service_usage_by_day(&service_id, since).await?;
+    let top_buyers = repo
+        .service_usage_by_buyer(&service_id, since, limit)
+        .await?;
+    Ok(Json(UsageReport {
+        service_id,
+        since,
+        days,
+        top_buyers,
+    }))
+}
+
+/// Usage of each of a provider's services, from the daily rollup
+pub async fn provider_usage_handler(
+    State(repo): State<Arc<Repository>>,
+    Extension(caller): Extension<Caller>,
+    Path(provider_id): Path<String>,
+    Query(params): Query<UsageParams>,
+) -> Result<impl IntoResponse, InfrapassError> {
+    require_provider_access(&caller, &repo, &provider_id).await?;
+    let since = params.since()?;
+
+    let services = repo.provider_usage_by_service(&provider_id, since).await?;
+    Ok(Json(ProviderUsageReport {
+        provider_id,
+        since,
+        services,
+    }))
+}
+
+/// Figures for a provider dashboard, from the same aggregates as the
+/// revenue and usage reports
+pub async fn provider_stats_handler(
+    State(repo): State<Arc<Repository>>,
+    Extension(caller): Extension<Caller>,
+    Path(provider_id): Path<String>,
+    Query(params): Query<StatsParams>,
+) -> Result<impl IntoResponse, InfrapassError> {
+    require_provider_access(&caller, &repo, &provider_id).await?;
+    let lookback = parse_lookback(params.since.as_deref().unwrap_or("30d"))
+        .map_err(InfrapassError::ValidationError)?;
+    let bucket = params.bucket.unwrap_or_default();
+    let limit = params.limit.unwrap_or(10).clamp(1, 100);
+    let since = Utc::now() - lookback;
+
+    let active_entitlements = repo.count_active_entitlements(&provider_id).await?;
+    let revenue = repo
+        .provider_revenue(&provider_id, since, bucket.as_str())
+        .await?;
+    let usage = repo
+        .provider_usage_by_day(&provider_id, since.date_naive())
+        .await?;
+    let top_buyers = repo
+        .provider_usage_by_buyer(&provider_id, since.date_naive(), limit)
+        .await?;
+
+    Ok(Json(ProviderStats::new(
+        provider_id,
+        since,
+        bucket,
+        active_entitlements,
+        revenue,
+        usage,
+        top_buyers,
+    )))
+}
+
+/// A provider's usage events in a window, streamed as CSV or JSON for
+/// accounting systems
+pub async fn export_provider_usage_handler(
+    State(repo): State<Arc<Repository>>,
+    Extension(caller): Extension<Caller>,
+    Path(provider_id): Path<String>,
+    Query(params): Query<ExportParams>,
+) -> Result<impl IntoResponse, InfrapassError> {
+    require_provider_access(&caller, &repo, &provider_id).await?;
+    let (from, to) = params.window()?;
+
+    let name = format!("usage-{}", provider_id);
+    Ok(export_response(params.format, &name, move |page| {
+        let repo = repo.clone();
+        let provider_id = provider_id.clone();
+        async move {
+            repo.export_provider_usage(&provider_id, from, to, &page)
+                .await
+        }
+    }))
+}
+
+/// Purchases of a provider's services in a window, streamed as CSV or JSON
+pub async fn export_provider_revenue_handler(
+    State(repo): State<Arc<Repository>>,
+    Extension(caller): Extension<Caller>,
+    Path(provider_id): Path<String>,
+    Query(params): Query<ExportParams>,
+) -> Result<impl IntoResponse, InfrapassError> {
+    require_provider_access(&caller, &repo, &provider_id).await?;
+    let (from, to) = params.window()?;
+
+    let name = format!("revenue-{}", provider_id);
+    Ok(export_response(params.format, &name, move |page| {
+        let repo = repo.clone();
+        let provider_id = provider_id.clone();
+        async move {
+            repo.export_provider_purchases(&provider_id, from, to, &page)
+                .await
+        }
+    }))
+}
+
+/// Addresses with unexpired entitlements to a service, for access reviews
+pub async fn service_access_handler(
+    State(repo): State<Arc<Repository>>,
+    Extension(caller): Extension<Caller>,
+    Path(service_id): Path<String>,
+    Query(params): Query<AccessParams>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, InfrapassError> {
+    require_service_owner(&caller, repo.as_ref(), &service_id).await?;
+    let format = params.format.unwrap_or_else(|| {
+        let accept = headers
+            .get(header::ACCEPT)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("");
+        if accept.contains("text/csv") {
+            AccessFormat::Csv
+        } else {
+            AccessFormat::Json
+        }
+    });
+
+    let entitlements = repo.list_service_access(&service_id).await?;
+    let list = AccessList::new(service_id, entitlements);
+    let body = match format {
+        AccessFormat::Json => serde_json::to_string(&list)?,
+        AccessFormat::Csv => list.to_csv(),
+    };
+
+    Ok(([(header::CONTENT_TYPE, format.content_type())], body))
+}
+
+pub async fn catalog_feed_handler(
+    State(feed): State<Arc<CatalogFeed>>,
+    Query(params): Query<FeedParams>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, InfrapassError> {
+    let format = params.format.unwrap_or_else(|| {
+        let accept = headers
+            .get(header::ACCEPT)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("");
+        if accept.contains("application/atom+xml") {
+            FeedFormat::Atom
+        } else {
+            FeedFormat::Json
+        }
+    });
+
+    let body = feed.render(format).await?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, format.content_type().to_string()),
+            (
+                header::CACHE_CONTROL,
+                format!("public, max-age={}", feed.ttl().as_secs()),
+            ),
+        ],
+        body.to_string(),
+    ))
+}
+
+/// Longest contact accepted, enough for an email address or a chat handle
+const MAX_CONTACT_LEN: usize = 320;
+
+/// Links an encrypted contact to an entitlement, signed by its buyer
+pub async fn link_contact_handler(
+    State(repo): State<Arc<Repository>>,
+    State(vault): State<Arc<ContactVault>>,
+    Json(payload): Json<LinkContactRequest>,
+) -> Result<impl IntoResponse, InfrapassError> {
+    let contact = payload.contact.trim();
+    if contact.is_empty() || contact.len() > MAX_CONTACT_LEN {
+        return Err(InfrapassError::ValidationError(format!(
+            "contact must be 1 to {} bytes",
+            MAX_CONTACT_LEN
+        )));
+    }
+
+    let Some(entitlement) = repo.get_entitlement(&payload.entitlement_id).await? else {
+        return Ok((
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({"error": "entitlement not found"})),
+        ));
+    };
+
+    let message =
+        contacts::link_message(&entitlement.entitlement_id, contact, payload.timestamp_ms);
+    if let Err(e) = verify_signer(
+        &entitlement.buyer,
+        &message,
+        payload.timestamp_ms,
+        &payload.signature,
+    ) {
+        return Ok(e);
+    }
+
+    let sealed = vault.seal(contact)?;
+    repo.upsert_buyer_contact(&entitlement, &sealed).await?;
+
+    info!(
+        entitlement_id = %entitlement.entitlement_id,
+        provider_id = %entitlement.provider_id,
+        "Buyer contact linked"
+    );
+
+    Ok((
+        StatusCode::OK,
+        Json(serde_json::json!({"status": "contact linked"})),
+    ))
+}
+
+/// Deletes the contact linked to an entitlement, signed by its buyer
+pub async fn unlink_contact_handler(
+    State(repo): State<Arc<Repository>>,
+    Path(entitlement_id): Path<String>,
+    Json(payload): Json<SignedRequest>,
+) -> Result<impl IntoResponse, InfrapassError> {
+    let Some(entitlement) = repo.get_entitlement(&entitlement_id).await? else {
+        return Ok((
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({"error": "entitlement not found"})),
+        ));
+    };
+
+    let message = contacts::unlink_message(&entitlement.entitlement_id, payload.timestamp_ms);
+    if let Err(e) = verify_signer(
+        &entitlement.buyer,
+        &message,
+        payload.timestamp_ms,
+        &payload.signature,
+    ) {
+        return Ok(e);
+    }
+
+    if !repo
+        .delete_buyer_contact(&entitlement.entitlement_id)
+        .await?
+    {
+        return Ok((
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({"error": "no contact linked"})),
+        ));
+    }
+
+    info!(entitlement_id = %entitlement.entitlement_id, "Buyer contact deleted");
+
+    Ok((
+        StatusCode::OK,
+        Json(serde_json::json!({"status": "contact deleted"})),
+    ))
+}
+
+/// Decrypted contacts of a provider's buyers, signed by the provider's address
+pub async fn list_provider_contacts_handler(
+    State(repo): State<Arc<Repository>>,
+    State(vault): State<Arc<ContactVault>>,
+    Path(provider_id): Path<String>,
+    Json(payload): Json<SignedRequest>,
+) -> Result<impl IntoResponse, InfrapassError> {
+    let Some(provider) = repo.get_provider(&provider_id).await? else {
+        return Ok((
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({"error": "provider not found"})),
+        ));
+    };
+
+    let message = contacts::list_message(&provider.profile_id, payload.timestamp_ms);
+    if let Err(e) = verify_signer(
+        &provider.provider_address,
+        &message,
+        payload.timestamp_ms,
+        &payload.signature,
+    ) {
+        return Ok(e);
+    }
+
+    let mut out = vec![];
+    for contact in repo.list_buyer_contacts(&provider.profile_id).await? {
+        out.push(serde_json::json!({
+            "entitlement_id": contact.entitlement_id,
+            "buyer": contact.buyer,
+            "contact": vault.open(&contact.contact_ciphertext)?,
+            "updated_at": contact.updated_at,
+        }));
+    }
+
+    Ok((StatusCode::OK, Json(serde_json::json!(out))))
+}
+
+/// Starts Sign-in-with-Sui: a nonce and the message the provider's address
+/// must sign to answer it
+pub async fn sign_in_challenge_handler(
+    State(repo): State<Arc<Repository>>,
+    State(sessions): State<Arc<SessionSigner>>,
+    Json(payload): Json<SignInChallengeRequest>,
+) -> Result<impl IntoResponse, InfrapassError> {
+    if !sessions.is_enabled() {
+        return Err(InfrapassError::ValidationError(
+            "sign-in is not enabled".into(),
+        ));
+    }
+    let Some(provider) = repo.get_provider(&payload.provider_id).await? else {
+        return Ok((
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({"error": "provider not found"})),
+        ));
+    };
+
+    let (nonce, expires_at) = repo
+        .create_sign_in_challenge(
+            &provider.profile_id,
+            chrono::Duration::seconds(CHALLENGE_TTL_SECS),
+        )
+        .await?;
+
+    Ok((
+        StatusCode::OK,
+        Json(serde_json::json!({
+            "provider_id": provider.profile_id,
+            "nonce": nonce,
+            "message": session::sign_in_message(&provider.profile_id, &nonce, expires_at),
+            "expires_at": expires_at,
+        })),
+    ))
+}
+
+/// Exchanges a challenge signed by the provider's address for a session
+/// token. A challenge is spent by the first answer, right or wrong.
+pub async fn sign_in_handler(
+    State(repo): State<Arc<Repository>>,
+    State(sessions): State<Arc<SessionSigner>>,
+    Json(payload): Json<SignInRequest>,
+) -> Result<impl IntoResponse, InfrapassError> {
+    let Some(expires_at) = repo
+        .take_sign_in_challenge(&payload.nonce, &payload.provider_id)
+        .await?
+    else {
+        return Ok((
+            StatusCode::UNAUTHORIZED,
+            Json(serde_json::json!({"error": "unknown or expired challenge"})),
+        ));
+    };
+    let Some(provider) = repo.get_provider(&payload.provider_id).await? else {
+        return Ok((
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({"error": "provider not found"})),
+        ));
+    };
+
+    let message = session::sign_in_message(&provider.profile_id, &payload.nonce, expires_at);
+    let verified = SuiAddress::from_str(&provider.provider_address)
+        .map_err(|e| anyhow::anyhow!("invalid provider address: {}", e))
+        .and_then(|address| {
+            contacts::verify_personal_message(address, &message, &payload.signature)
+        });
+    if let Err(e) = verified {
+        warn!(provider_id = %provider.profile_id, error = %e, "Rejected sign-in");
+        return Ok((
+            StatusCode::UNAUTHORIZED,
+            Json(serde_json::json!({"error": e.to_string()})),
+        ));
+    }
+
+    let tenant_id = repo.provider_tenant(&provider.profile_id).await?;
+    let (token, expires_at) =
+        sessions.issue(&provider.profile_id, &provider.provider_address, tenant_id)?;
+    info!(provider_id = %provider.profile_id, "Provider signed in");
+
+    Ok((
+        StatusCode::OK,
+        Json(serde_json::json!({
+            "token": token,
+            "token_type": "Bearer",
+            "provider_id": provider.profile_id,
+            "expires_at": expires_at,
+        })),
+    ))
+}
+
+/// Reports a sidecar's version and config fingerprint
+#[utoipa::path(
+    post,
+    path = "/sidecars/heartbeat",
+    tag = "validator",
+    request_body = SidecarHeartbeat,
+    responses((status = 204, description = "Heartbeat recorded")),
+    security(("api_key" = []))
+)]
+pub async fn sidecar_heartbeat_handler(
+    State(repo): State<Arc<Repository>>,
+    Extension(caller): Extension<Caller>,
+    Json(payload): Json<SidecarHeartbeat>,
+) -> Result<impl IntoResponse, InfrapassError> {
+    require_provider_access(&caller, &repo, &payload.provider_id).await?;
+    if payload.provider_id.is_empty() || payload.instance_id.is_empty() {
+        return Err(InfrapassError::ValidationError(
+            "provider_id and instance_id are required".into(),
+        ));
+    }
+
+    repo.record_sidecar_heartbeat(&payload).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Active and upcoming maintenance windows across a provider's services,
+/// which a sidecar loads on start and after reconnecting to Redis, since it
+/// only hears of windows scheduled while it is subscribed
+#[utoipa::path(
+    get,
+    path = "/providers/{provider_id}/maintenance",
+    tag = "validator",
+    params(("provider_id" = String, Path, description = "Provider profile object ID")),
+    responses((status = 200, body = [ScheduledMaintenance])),
+    security(("api_key" = []))
+)]
+pub async fn list_provider_maintenance_handler(
+    State(repo): State<Arc<Repository>>,
+    Extension(caller): Extension<Caller>,
+    Path(provider_id): Path<String>,
+) -> Result<impl IntoResponse, InfrapassError> {
+    require_provider_access(&caller, &repo, &provider_id).await?;
+    let windows: Vec<ScheduledMaintenance> = repo
+        .get_provider_upcoming_maintenance(&provider_id)
+        .await?
+        .into_iter()
+        .map(|w| ScheduledMaintenance {
+            id: w.id,
+            service_id: w.service_id,
+            starts_at: w.starts_at,
+            ends_at: w.ends_at,
+            reason: w.reason,
+        })
+        .collect();
+
+    Ok(Json(windows))
+}
+
+/// Sidecars that reported for a provider, newest heartbeat first. A sidecar
+/// is outdated when this backend or another of the provider's sidecars runs
+/// a newer version.
+pub async fn list_provider_sidecars_handler(
+    State(repo): State<Arc<Repository>>,
+    Extension(caller): Extension<Caller>,
+    Path(provider_id): Path<String>,
+) -> Result<impl IntoResponse, InfrapassError> {
+    require_provider_access(&caller, &repo, &provider_id).await?;
+    let mut sidecars = repo.list_sidecars(&provider_id).await?;
+
+    let latest = sidecars
+        .iter()
+        .map(|s| s.version.as_str())
+        .fold(fleet::VERSION, |latest, v| {
+            if fleet::is_older_version(latest, v) {
+                v
+            } else {
+                latest
+            }
+        })
+        .to_string();
+    for sidecar in &mut sidecars {
+        sidecar.outdated = fleet::is_older_version(&sidecar.version, &latest);
+    }
+
+    Ok(Json(serde_json::json!({
+        "provider_id": provider_id,
+        "latest_version": latest,
+        "sidecars": sidecars,
+    })))
+}
+
+/// Indexed events as server-sent events, sent as the worker commits them.
+/// Each is named by its `module::Name` label and has `<checkpoint>:<index>`
+/// as its ID. A subscriber that falls behind gets a `lagged` event with the
+/// number it missed.
+pub async fn event_stream_handler(
+    State(repo): State<Arc<Repository>>,
+    State(events): State<Arc<EventStream>>,
+    Extension(caller): Extension<Caller>,
+    Query(params): Query<EventStreamParams>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, InfrapassError> {
+    let provider_id = match (&caller, params.provider_id) {
+        (Caller::Provider { provider_id, .. }, None) => Some(provider_id.clone()),
+        (_, provider_id) => provider_id,
+    };
+    // The stream bypasses row-level security, so a tenant names one of its
+    // providers
+    if let (Caller::Tenant(_), None) = (&caller, &provider_id) {
+        return Err(InfrapassError::Forbidden(
+            "tenant API keys must pass provider_id".into(),
+        ));
+    }
+    if let Some(provider_id) = &provider_id {
+        require_provider_access(&caller, &repo, provider_id).await?;
+    }
+    let filter = match params.types.as_deref() {
+        Some(types) => {
+            EventFilter::parse(types).map_err(|e| InfrapassError::ValidationError(e.to_string()))?
+        }
+        None => EventFilter::all(),
+    };
+
+    let state = (events.subscribe(), filter, provider_id);
+    let stream = futures::stream::unfold(state, |(mut rx, filter, provider_id)| async move {
+        loop {
+            let sse = match rx.recv().await {
+                Ok(event) => {
+                    if !filter.allows_label(event.event_type)
+                        || (provider_id.is_some() && event.provider_id != provider_id)
+                    {
+                        continue;
+                    }
+                    Event::default()
+                        .event(event.event_type)
+                        .id(event.id)
+                        .data(&*event.data)
+                }
+                Err(RecvError::Lagged(missed)) => {
+                    Event::default().event("lagged").data(missed.to_string())
+                }
+                Err(RecvError::Closed) => return None,
+            };
+            return Some((Ok(sse), (rx, filter, provider_id)));
+        }
+    });
+
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+/// Whether this instance should get traffic: 200 while every critical
+/// dependency is up, else 503, with the state of each
+pub async fn readyz_handler(State(readiness): State<Arc<Readiness>>) -> impl IntoResponse {
+    let report = readiness.check().await;
+    let status = if report.is_ready() {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(report))
+}
+
+/// Indexer, relayer key, scheduled job and reconciliation metrics in
+/// Prometheus text format
+pub async fn metrics_handler() -> String {
+    format!(
+        "{}{}{}{}",
+        INDEXER_METRICS.encode(),
+        KEY_METRICS.encode(),
+        SCHEDULER_METRICS.encode(),
+        RECONCILE_METRICS.encode()
+    )
+}
+
+/// Schedule and last run of every periodic server job
+pub async fn list_scheduled_jobs_handler(
+    State(jobs): State<Arc<JobBoard>>,
+    Extension(caller): Extension<Caller>,
+) -> Result<Json<serde_json::Value>, InfrapassError> {
+    caller.reject_provider_key()?;
+    Ok(Json(serde_json::json!({ "jobs": jobs.snapshot() })))
+}
+
+/// Issues an API key that acts for one provider. The key is only ever
+/// returned here.
+pub async fn issue_api_key_handler(
+    State(repo): State<Arc<Repository>>,
+    Json(payload): Json<IssueApiKeyRequest>,
+) -> Result<impl IntoResponse, InfrapassError> {
+    if repo.get_provider(&payload.provider_id).await?.is_none() {
+        return Ok((
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({"error": "provider not found"})),
+        ));
+    }
+
+    let (api_key, key) = repo
+        .create_api_key(&payload.provider_id, payload.label.as_deref())
+        .await?;
+    info!(key_id = %api_key.key_id, provider_id = %api_key.provider_id, "API key issued");
+
+    Ok((StatusCode::CREATED, Json(issued_api_key(api_key, key))))
+}
+
+pub async fn list_api_keys_handler(
+    State(repo): State<Arc<Repository>>,
+    Query(params): Query<ListApiKeysParams>,
+) -> Result<impl IntoResponse, InfrapassError> {
+    let keys = repo.list_api_keys(params.provider_id.as_deref()).await?;
+    Ok(Json(keys))
+}
+
+/// Replaces a key with a new one for the same provider. The old key keeps
+/// working for `grace_secs`.
+pub async fn rotate_api_key_handler(
+    State(repo): State<Arc<Repository>>,
+    Path(key_id): Path<Uuid>,
+    Query(params): Query<RotateApiKeyParams>,
+) -> Result<impl IntoResponse, InfrapassError> {
+    let grace_secs = params.grace_secs.unwrap_or(0);
+    if grace_secs > MAX_KEY_ROTATION_GRACE_SECS {
+        return Err(InfrapassError::ValidationError(format!(
+            "grace_secs must be at most {}",
+            MAX_KEY_ROTATION_GRACE_SECS
+        )));
+    }
+
+    let Some((api_key, key)) = repo
+        .rotate_api_key(key_id, chrono::Duration::seconds(grace_secs as i64))
+        .await?
+    else {
+        return Ok((
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({"error": "API key not found"})),
+        ));
+    };
+    info!(
+        key_id = %api_key.key_id,
+        rotated_from = %key_id,
+        provider_id = %api_key.provider_id,
+        grace_secs,
+        "API key rotated"
+    );
+
+    Ok((StatusCode::CREATED, Json(issued_api_key(api_key, key))))
+}
+
+pub async fn revoke_api_key_handler(
+    State(repo): State<Arc<Repository>>,
+    Path(key_id): Path<Uuid>,
+) -> Result<impl IntoResponse, InfrapassError> {
+    let Some(api_key) = repo.revoke_api_key(key_id).await? else {
+        return Ok((
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({"error": "API key not found"})),
+        ));
+    };
+    info!(key_id = %api_key.key_id, provider_id = %api_key.provider_id, "API key revoked");
+
+    Ok((
+        StatusCode::OK,
+        Json(serde_json::json!({"status": "API key revoked"})),
+    ))
+}
+
+/// An entitlement by ID, with the latest `/validate` answers for its buyer
+/// and service
+pub async fn admin_entitlement_handler(
+    State(repo): State<Arc<Repository>>,
+    State(decisions): State<Arc<DecisionLog>>,
+    Path(entitlement_id): Path<String>,
+) -> Result<impl IntoResponse, InfrapassError> {
+    let Some(entitlement) = repo.get_entitlement(&entitlement_id).await? else {
+        return Ok((
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({"error": "entitlement not found"})),
+        ));
+    };
+    let recent = decisions.recent(
+        Some(entitlement.buyer.as_str()),
+        Some(entitlement.service_id.as_str()),
+        ADMIN_RECENT_DECISIONS,
+    );
+
+    Ok((
+        StatusCode::OK,
+        Json(serde_json::json!({
+            "entitlement": entitlement,
+            "recent_validations": recent,
+        })),
+    ))
+}
+
+/// A buyer's entitlements, expired ones included unless filtered, their
+/// latest `/validate` answers and, with `service_id`, what Postgres would
+/// answer for that service now, bypassing every cache
+pub async fn admin_buyer_handler(
+    State(repo): State<Arc<Repository>>,
+    State(decisions): State<Arc<DecisionLog>>,
+    Path(user_address): Path<String>,
+    Query(params): Query<AdminBuyerParams>,
+) -> Result<impl IntoResponse, InfrapassError> {
+    let user_address = normalize_address(&user_address)?;
+    let page = page_request(params.limit, params.cursor.as_deref(), params.sort)?;
+    let filter = EntitlementFilter {
+        active: params.active,
+    };
+
+    let entitlements = repo
+        .list_entitlements_by_buyer(&user_address, &filter, &page)
+        .await?;
+    let validation = match params.service_id.as_deref() {
+        Some(service_id) => Some(
+            repo.get_valid_entitlement_response(&user_address, service_id, 1, true)
+                .await?
+                .unwrap_or_else(ValidateResponse::denied),
+        ),
+        None => None,
+    };
+    let recent = decisions.recent(
+        Some(user_address.as_str()),
+        params.service_id.as_deref(),
+        ADMIN_RECENT_DECISIONS,
+    );
+
+    Ok(Json(serde_json::json!({
+        "user_address": user_address,
+        "entitlements": entitlements,
+        "validation": validation,
+        "recent_validations": recent,
+    })))
+}
+
+/// Publishes `Invalidate` for a buyer and service, so the provider's
+/// sidecars and the backend's validation cache ask Postgres again
+pub async fn admin_invalidate_handler(
+    State(repo): State<Arc<Repository>>,
+    State(publisher): State<Arc<PubSubPublisher>>,
+    Json(payload): Json<InvalidateRequest>,
+) -> Result<impl IntoResponse, InfrapassError> {
+    let user_address = normalize_address(&payload.user_address)?;
+    let Some(service) = repo.get_service(&payload.service_id).await? else {
+        return Ok((
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({"error": "service not found"})),
+        ));
+    };
+
+    publisher
+        .publish_invalidate(&service.provider_id, &user_address, &service.service_id)
+        .await?;
+
+    Ok((
+        StatusCode::OK,
+        Json(serde_json::json!({"status": "invalidation published"})),
+    ))
+}
+
+/// The latest `/validate` answers given by this API process, newest first
+pub async fn admin_validations_handler(
+    State(decisions): State<Arc<DecisionLog>>,
+    Query(params): Query<ValidationLogParams>,
+) -> Json<serde_json::Value> {
+    let recent = decisions.recent(
+        params.user_address.as_deref(),
+        params.service_id.as_deref(),
+        params.limit.unwrap_or(100).clamp(1, 1000),
+    );
+    Json(serde_json::json!({ "validations": recent }))
+}
+
+/// Recent on-chain usage settlements, newest first
+pub async fn admin_settlements_handler(
+    State(repo): State<Arc<Repository>>,
+    Query(params): Query<SettlementParams>,
+) -> Result<impl IntoResponse, InfrapassError> {
+    if let Some(status) = params.status.as_deref() {
+        if !matches!(status, "submitted" | "settled" | "failed") {
+            return Err(InfrapassError::ValidationError(
+                "status must be submitted, settled or failed".into(),
+            ));
+        }
+    }
+
+    let settlements = repo
+        .list_settlements(
+            params.status.as_deref(),
+            params.limit.unwrap_or(100).clamp(1, 1000),
+        )
+        .await?;
+    Ok(Json(settlements))
+}
+
+fn issued_api_key(api_key: ApiKey, key: String) -> serde_json::Value {
+    serde_json::json!({
+        "key_id": api_key.key_id,
+        "provider_id": api_key.provider_id,
+        "label": api_key.label,
+        "created_at": api_key.created_at,
+        "rotated_from": api_key.rotated_from,
+        "key": key,
+    })
+}
+
+/// A buyer's indexed entitlements. Public, like the purchases they came from.
+pub async fn list_buyer_entitlements_handler(
+    State(repo): State<Arc<Repository>>,
+    Path(user_address): Path<String>,
+    Query(params): Query<ListEntitlementsParams>,
+) -> Result<impl IntoResponse, InfrapassError> {
+    let user_address = normalize_address(&user_address)?;
+    let page = page_request(params.limit, params.cursor.as_deref(), params.sort)?;
+    let filter = EntitlementFilter {
+        active: params.active,
+    };
+
+    Ok(Json(
+        repo.list_entitlements_by_buyer(&user_address, &filter, &page)
+            .await?,
+    ))
+}
+
+/// Every indexed entitlement to a service
+pub async fn list_service_entitlements_handler(
+    State(repo): State<Arc<Repository>>,
+    Path(service_id): Path<String>,
+    Query(params): Query<ListEntitlementsParams>,
+) -> Result<impl IntoResponse, InfrapassError> {
+    let page = page_request(params.limit, params.cursor.as_deref(), params.sort)?;
+    let filter = EntitlementFilter {
+        active: params.active,
+    };
+
+    Ok(Json(
+        repo.list_entitlements_by_service(&service_id, &filter, &page)
+            .await?,
+    ))
+}
+
+/// Refuses a provider key of another provider, and a tenant key unless
+/// `provider_id` is one of the tenant's. Row-level security hides other
+/// tenants' providers, so a tenant only finds its own.
+async fn require_provider_access(
+    caller: &Caller,
+    repo: &Repository,
+    provider_id: &str,
+) -> Result<(), InfrapassError> {
+    caller.require_provider(provider_id)?;
+    if let Caller::Tenant(_) = caller {
+        if repo.get_provider(provider_id).await?.is_none() {
+            return Err(not_own_resource());
+        }
+    }
+    Ok(())
+}
+
+/// Refuses a provider key unless its provider owns `service_id`, and a
+/// tenant key unless the service is the tenant's
+async fn require_service_owner(
+    caller: &Caller,
+    storage: &dyn Storage,
+    service_id: &str,
+) -> Result<(), InfrapassError> {
+    if let Caller::Operator = caller {
+        return Ok(());
+    }
+    match storage.service_provider(service_id).await? {
+        Some(provider_id) => caller.require_provider(&provider_id),
+        None => Err(not_own_resource()),
+    }
+}
+
+/// Refuses a provider key unless its provider's service created `tier_id`,
+/// and a tenant key unless the tier is the tenant's
+async fn require_tier_owner(
+    caller: &Caller,
+    repo: &Repository,
+    tier_id: &str,
+) -> Result<(), InfrapassError> {
+    if let Caller::Operator = caller {
+        return Ok(());
+    }
+    match repo.get_tier(tier_id).await? {
+        Some(tier) => require_service_owner(caller, repo, &tier.service_id).await,
+        None => Err(not_own_resource()),
+    }
+}
+
+/// An unsigned transaction that adds units to a usage-based entitlement, for
+/// its buyer to sign and submit. Once the top-up is indexed, the sidecars are
+/// sent the new units instead of the buyer needing a new entitlement.
+pub async fn top_up_entitlement_handler(
+    State(repo): State<Arc<Repository>>,
+    State(sui): State<Arc<SuiClient>>,
+    Path(entitlement_id): Path<String>,
+    Json(payload): Json<TopUpRequest>,
+) -> Result<impl IntoResponse, InfrapassError> {
+    if let Err(e) = upgraded_package_id("payments::top_up_entitlement") {
+        return Ok((
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(serde_json::json!({"error": e.to_string()})),
+        ));
+    }
+    let Some(ent) = repo.get_entitlement(&entitlement_id).await? else {
+        return Ok((
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({"error": "entitlement not found"})),
+        ));
+    };
+    let tier = repo
+        .get_tier(&ent.tier_id)
+        .await?
+        .ok_or_else(|| InfrapassError::Other("entitlement's tier is not indexed".into()))?;
+    if tier.tier_type != TierType::UsageBased {
+        return Err(InfrapassError::ValidationError(
+            "only usage-based entitlements can be topped up".into(),
+        ));
+    }
+    if tier.is_active == Some(false) {
+        return Err(InfrapassError::ValidationError(
+            "the entitlement's tier is no longer active".into(),
+        ));
+    }
+    if tier.price.is_zero() || payload.amount < tier.price {
+        return Err(InfrapassError::ValidationError(format!(
+            "amount must be at least the tier price of {}",
+            tier.price
+        )));
+    }
+    let units = payload.amount.get() / tier.price.get();
+
+    let id = |raw: &str| {
+        ObjectID::from_hex_literal(raw)
+            .map_err(|e| InfrapassError::Other(format!("invalid object ID {}: {}", raw, e)))
+    };
+    let sender = SuiAddress::from_str(&ent.buyer)
+        .map_err(|e| InfrapassError::Other(format!("invalid buyer address: {}", e)))?;
+    let tx_data = top_up_entitlement_tx(
+        &sui,
+        sender,
+        id(&ent.entitlement_id)?,
+        id(&ent.service_id)?,
+        id(&ent.tier_id)?,
+        payload.amount.get(),
+    )
+    .await
+    .map_err(|e| InfrapassError::ValidationError(format!("cannot build top-up: {}", e)))?;
+    let tx_bytes = bcs::to_bytes(&tx_data)
+        .map_err(|e| InfrapassError::Other(format!("failed to encode transaction: {}", e)))?;
+
+    Ok((
+        StatusCode::OK,
+        Json(serde_json::json!({
+            "entitlement_id": ent.entitlement_id,
+            "sender": ent.buyer,
+            "amount": payload.amount,
+            "units": units.to_string(),
+            "tx_bytes": BASE64.encode(tx_bytes),
+        })),
+    ))
+}
+
+/// An unknown resource looks the same to a provider key as another
+/// provider's, so keys can't probe for IDs
+fn not_own_resource() -> InfrapassError {
+    InfrapassError::Forbidden("API key belongs to another provider".into())
+}
+
+fn page_request(
+    limit: Option<i64>,
+    cursor: Option<&str>,
+    sort: SortOrder,
+) -> Result<PageRequest, InfrapassError> {
+    PageRequest::new(limit, cursor, sort)
+        .map_err(|e| InfrapassError::ValidationError(e.to_string()))
+}
+
+/// Buyers are stored in the canonical `0x` + 64 hex form used by the indexer
+fn normalize_address(address: &str) -> Result<String, InfrapassError> {
+    SuiAddress::from_str(address)
+        .map(|a| a.to_string())
+        .map_err(|_| InfrapassError::ValidationError("invalid user_address".into()))
+}
+
+/// Checks a signed request against `signer`, returning the 401 to send back
+/// when it doesn't verify
+fn verify_signer(
+    signer: &str,
+    message: &str,
+    timestamp_ms: i64,
+    signature: &str,
+) -> Result<(), (StatusCode, Json<serde_json::Value>)> {
+    let verified = SuiAddress::from_str(signer)
+        .map_err(|e| anyhow::anyhow!("invalid signer address: {}", e))
+        .and_then(|address| {
+            contacts::verify_signed_message(address, message, timestamp_ms, signature)
+        });
+
+    verified.map_err(|e| {
+        warn!(signer = %signer, error = %e, "Rejected signed request");
+        (
+            StatusCode::UNAUTHORIZED,
+            Json(serde_json::json!({"error": e.to_string()})),
+        )
+    })
+}
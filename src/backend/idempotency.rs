@@ -0,0 +1,348 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::Result;
+use axum::{
+    body::{Body, to_bytes},
+    extract::{Json, Request, State},
+    http::{HeaderValue, StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use chrono::Utc;
+use moka::{
+    future::Cache,
+    ops::compute::{CompResult, Op},
+};
+use redis::{AsyncCommands, Client as RedisClient, Script, aio::MultiplexedConnection};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::{debug, warn};
+
+use crate::{api_types::validator::IDEMPOTENCY_KEY, backend::middleware::bearer_token, db::tenant};
+
+/// Longest `Idempotency-Key` accepted
+pub const MAX_KEY_LEN: usize = 255;
+
+/// Set on a response replayed from the cache rather than produced again
+pub const REPLAYED: &str = "idempotent-replayed";
+
+/// Largest request or response body kept for a key
+const MAX_BODY_BYTES: usize = 1 << 20;
+
+/// How long a claimed key waits for its response before another request
+/// may take it over. Longer than the server's request timeout, so a claim is
+/// only abandoned by a replica that went away mid-request.
+const IN_FLIGHT_TTL: Duration = Duration::from_secs(30);
+
+/// Takes over `KEYS[1]` with `ARGV[2]` for `ARGV[3]` ms if it still holds
+/// `ARGV[1]`, the abandoned claim that was read, or is gone. Returns nil once
+/// taken over, else what the key holds now, so of two requests taking over
+/// the same claim only one runs.
+const TAKE_OVER: &str = r#"
+local current = redis.call('GET', KEYS[1])
+if current and current ~= ARGV[1] then
+    return current
+end
+redis.call('SET', KEYS[1], ARGV[2], 'PX', ARGV[3])
+return false
+"#;
+
+/// What is kept for a key: the body it was first sent with and, once the
+/// handler answered, the response
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Entry {
+    /// SHA-256 of the request body, hex encoded
+    fingerprint: String,
+    claimed_at_ms: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    response: Option<StoredResponse>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct StoredResponse {
+    status: u16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    content_type: Option<String>,
+    body: String,
+}
+
+impl Entry {
+    fn pending(fingerprint: &str) -> Self {
+        Self {
+            fingerprint: fingerprint.to_string(),
+            claimed_at_ms: Utc::now().timestamp_millis(),
+            response: None,
+        }
+    }
+
+    /// An unanswered claim past `IN_FLIGHT_TTL` is abandoned
+    fn is_abandoned(&self) -> bool {
+        self.response.is_none()
+            && Utc::now().timestamp_millis() - self.claimed_at_ms > IN_FLIGHT_TTL.as_millis() as i64
+    }
+}
+
+enum Backend {
+    /// Shared by every API replica
+    Redis(MultiplexedConnection),
+    /// The SQLite backend runs a single process without Redis
+    Memory(Cache<String, Entry>),
+}
+
+/// Responses to mutations sent with an `Idempotency-Key`, kept for `ttl` so
+/// a retried request is answered as the first one was instead of running
+/// again
+pub struct IdempotencyCache {
+    backend: Backend,
+    ttl: Duration,
+}
+
+impl IdempotencyCache {
+    pub async fn redis(redis_client: RedisClient, ttl: Duration) -> Result<Self> {
+        Ok(Self {
+            backend: Backend::Redis(redis_client.get_multiplexed_async_connection().await?),
+            ttl,
+        })
+    }
+
+    pub fn in_memory(ttl: Duration) -> Self {
+        Self {
+            backend: Backend::Memory(
+                Cache::builder()
+                    .max_capacity(100_000)
+                    .time_to_live(ttl)
+                    .build(),
+            ),
+            ttl,
+        }
+    }
+
+    /// Claims `key` for a request with `fingerprint`. `None` if the request
+    /// should run, else what was already kept for the key.
+    async fn claim(&self, key: &str, fingerprint: &str) -> Result<Option<Entry>> {
+        let pending = Entry::pending(fingerprint);
+        match &self.backend {
+            Backend::Redis(redis) => {
+                let mut conn = redis.clone();
+                let value = serde_json::to_string(&pending)?;
+                let claimed: Option<String> = redis::cmd("SET")
+                    .arg(key)
+                    .arg(&value)
+                    .arg("NX")
+                    .arg("PX")
+                    .arg(self.ttl.as_millis() as u64)
+                    .query_async(&mut conn)
+                    .await?;
+                if claimed.is_some() {
+                    return Ok(None);
+                }
+
+                let raw: Option<String> = conn.get(key).await?;
+                if let Some(raw) = &raw {
+                    let entry: Entry = serde_json::from_str(raw)?;
+                    if !entry.is_abandoned() {
+                        return Ok(Some(entry));
+                    }
+                }
+
+                let current: Option<String> = Script::new(TAKE_OVER)
+                    .key(key)
+                    .arg(raw.unwrap_or_default())
+                    .arg(&value)
+                    .arg(self.ttl.as_millis() as u64)
+                    .invoke_async(&mut conn)
+                    .await?;
+                let current = current
+                    .map(|v| serde_json::from_str::<Entry>(&v))
+                    .transpose()?;
+                Ok(current)
+            }
+            Backend::Memory(cache) => {
+                // Computed under the key's lock, so of two requests taking
+                // over the same claim only one runs
+                let result = cache
+                    .entry(key.to_string())
+                    .and_compute_with(|current| {
+                        let op = match current {
+                            Some(entry) if !entry.value().is_abandoned() => Op::Nop,
+                            _ => Op::Put(pending),
+                        };
+                        std::future::ready(op)
+                    })
+                    .await;
+
+                match result {
+                    CompResult::Unchanged(entry) => Ok(Some(entry.into_value())),
+                    _ => Ok(None),
+                }
+            }
+        }
+    }
+
+    /// Keeps the response to a claimed key for the rest of the TTL
+    async fn complete(&self, key: &str, entry: Entry) -> Result<()> {
+        match &self.backend {
+            Backend::Redis(redis) => {
+                let mut conn = redis.clone();
+                let _: () = conn
+                    .pset_ex(
+                        key,
+                        serde_json::to_string(&entry)?,
+                        self.ttl.as_millis() as u64,
+                    )
+                    .await?;
+            }
+            Backend::Memory(cache) => cache.insert(key.to_string(), entry).await,
+        }
+        Ok(())
+    }
+
+    /// Frees a claimed key so the request can be retried
+    async fn release(&self, key: &str) -> Result<()> {
+        match &self.backend {
+            Backend::Redis(redis) => {
+                let mut conn = redis.clone();
+                let _: () = conn.del(key).await?;
+            }
+            Backend::Memory(cache) => cache.invalidate(key).await,
+        }
+        Ok(())
+    }
+}
+
+fn reject(status: StatusCode, error: &str) -> Response {
+    (status, Json(serde_json::json!({ "error": error }))).into_response()
+}
+
+fn replay(stored: StoredResponse) -> Response {
+    let status = StatusCode::from_u16(stored.status).unwrap_or(StatusCode::OK);
+    let mut response = (status, stored.body).into_response();
+    let headers = response.headers_mut();
+    headers.remove(header::CONTENT_TYPE);
+    if let Some(content_type) = stored
+        .content_type
+        .and_then(|v| HeaderValue::from_str(&v).ok())
+    {
+        headers.insert(header::CONTENT_TYPE, content_type);
+    }
+    headers.insert(REPLAYED, HeaderValue::from_static("true"));
+    response
+}
+
+/// Answers a request carrying an `Idempotency-Key` it has seen before with
+/// the response kept from the first one, so a sidecar retrying after a lost
+/// response isn't charged twice. Keys are scoped to the caller's API key and
+/// the route. A key reused with a different body is refused with 422, and
+/// one whose first request is still running with 409. Server errors and 429s
+/// aren't kept, so those requests can be retried under the same key. Cache
+/// errors let the request run unprotected.
+pub async fn idempotency(
+    State(cache): State<Arc<IdempotencyCache>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let Some(value) = req.headers().get(IDEMPOTENCY_KEY) else {
+        return next.run(req).await;
+    };
+    let idempotency_key = match value.to_str() {
+        Ok(key) if !key.is_empty() && key.len() <= MAX_KEY_LEN => key.to_string(),
+        _ => {
+            return reject(
+                StatusCode::BAD_REQUEST,
+                "Idempotency-Key must be 1 to 255 visible ASCII characters",
+            );
+        }
+    };
+    let caller = bearer_token(&req)
+        .map(|token| tenant::hash_api_key(&token))
+        .unwrap_or_default();
+    let key = format!(
+        "infrapass:idempotency:{}:{}:{}",
+        caller,
+        req.uri().path().trim_start_matches('/'),
+        idempotency_key
+    );
+
+    let (parts, body) = req.into_parts();
+    let Ok(body) = to_bytes(body, MAX_BODY_BYTES).await else {
+        return reject(StatusCode::PAYLOAD_TOO_LARGE, "request body too large");
+    };
+    let fingerprint = hex::encode(Sha256::digest(&body));
+    let req = Request::from_parts(parts, Body::from(body));
+
+    match cache.claim(&key, &fingerprint).await {
+        Ok(None) => {}
+        Ok(Some(entry)) if entry.fingerprint != fingerprint => {
+            return reject(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "Idempotency-Key was already used with a different request body",
+            );
+        }
+        Ok(Some(Entry {
+            response: Some(stored),
+            ..
+        })) => {
+            debug!(key = %idempotency_key, "Replaying idempotent response");
+            return replay(stored);
+        }
+        Ok(Some(_)) => {
+            return reject(
+                StatusCode::CONFLICT,
+                "a request with this Idempotency-Key is still in progress",
+            );
+        }
+        Err(e) => {
+            warn!("Idempotency check failed, running request: {}", e);
+            return next.run(req).await;
+        }
+    }
+
+    let response = next.run(req).await;
+    let status = response.status();
+    if status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS {
+        if let Err(e) = cache.release(&key).await {
+            warn!("Failed to release idempotency key: {}", e);
+        }
+        return response;
+    }
+
+    let (parts, body) = response.into_parts();
+    let body = match to_bytes(body, MAX_BODY_BYTES).await {
+        Ok(body) => body,
+        Err(e) => {
+            warn!("Failed to buffer response for idempotency key: {}", e);
+            if let Err(e) = cache.release(&key).await {
+                warn!("Failed to release idempotency key: {}", e);
+            }
+            return reject(StatusCode::INTERNAL_SERVER_ERROR, "failed to read response");
+        }
+    };
+
+    match std::str::from_utf8(&body) {
+        Ok(text) => {
+            let entry = Entry {
+                fingerprint,
+                claimed_at_ms: Utc::now().timestamp_millis(),
+                response: Some(StoredResponse {
+                    status: status.as_u16(),
+                    content_type: parts
+                        .headers
+                        .get(header::CONTENT_TYPE)
+                        .and_then(|v| v.to_str().ok())
+                        .map(str::to_string),
+                    body: text.to_string(),
+                }),
+            };
+            if let Err(e) = cache.complete(&key, entry).await {
+                warn!("Failed to keep idempotent response: {}", e);
+            }
+        }
+        Err(_) => {
+            if let Err(e) = cache.release(&key).await {
+                warn!("Failed to release idempotency key: {}", e);
+            }
+        }
+    }
+
+    Response::from_parts(parts, Body::from(body))
+}
@@ -0,0 +1,301 @@
+use std::{fmt, sync::Arc};
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use once_cell::sync::Lazy;
+use prometheus::{GaugeVec, IntCounterVec, IntGauge, Opts, Registry, TextEncoder};
+use sui_json_rpc_types::SuiObjectDataOptions;
+use sui_sdk::SuiClient;
+use sui_types::{
+    base_types::{ObjectID, SuiAddress},
+    object::Owner,
+};
+use tracing::info;
+
+use crate::{
+    alerting::{manager::AlertManager, types::Alert},
+    backend::scheduler::Job,
+    client::client_ext::SuiClientExt,
+    types::coin::CoinType,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyRole {
+    /// Signs usage settlements and holds the relayer cap
+    Relayer,
+    /// Pays gas for sponsored transactions
+    Sponsor,
+}
+
+impl KeyRole {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            KeyRole::Relayer => "relayer",
+            KeyRole::Sponsor => "sponsor",
+        }
+    }
+}
+
+impl fmt::Display for KeyRole {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct MonitoredKey {
+    pub role: KeyRole,
+    pub address: SuiAddress,
+}
+
+#[derive(Debug, Clone)]
+pub struct KeyStatus {
+    pub key: MonitoredKey,
+    pub gas_balance: u128,
+    pub low: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct CapStatus {
+    pub cap_id: ObjectID,
+    pub exists: bool,
+    pub owner: Option<SuiAddress>,
+    pub version: Option<u64>,
+    /// True when the cap is address-owned by the relayer key
+    pub owned_by_relayer: bool,
+}
+
+impl CapStatus {
+    /// Why the relayer cannot use the cap, if anything is wrong with it
+    pub fn problem(&self) -> Option<&'static str> {
+        if !self.exists {
+            Some("does not exist")
+        } else if !self.owned_by_relayer {
+            Some("is not owned by the relayer key")
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct KeysReport {
+    pub keys: Vec<KeyStatus>,
+    pub cap: CapStatus,
+}
+
+pub struct KeyMetrics {
+    pub gas_balance: GaugeVec,
+    pub cap_present: IntGauge,
+    pub cap_version: IntGauge,
+    pub signing_failures: IntCounterVec,
+    registry: Registry,
+}
+
+impl KeyMetrics {
+    fn new() -> Self {
+        let registry = Registry::new();
+
+        let gas_balance = GaugeVec::new(
+            Opts::new(
+                "infrapass_key_gas_balance_mist",
+                "SUI gas balance of a relayer or sponsor key",
+            ),
+            &["role", "address"],
+        )
+        .unwrap();
+        let cap_present = IntGauge::new(
+            "infrapass_relayer_cap_valid",
+            "1 while the relayer cap exists and is owned by the relayer key",
+        )
+        .unwrap();
+        let cap_version = IntGauge::new(
+            "infrapass_relayer_cap_version",
+            "Object version of the relayer cap",
+        )
+        .unwrap();
+        let signing_failures = IntCounterVec::new(
+            Opts::new(
+                "infrapass_key_signing_failures_total",
+                "Transactions a key failed to sign or execute",
+            ),
+            &["role"],
+        )
+        .unwrap();
+
+        registry.register(Box::new(gas_balance.clone())).unwrap();
+        registry.register(Box::new(cap_present.clone())).unwrap();
+        registry.register(Box::new(cap_version.clone())).unwrap();
+        registry
+            .register(Box::new(signing_failures.clone()))
+            .unwrap();
+
+        Self {
+            gas_balance,
+            cap_present,
+            cap_version,
+            signing_failures,
+            registry,
+        }
+    }
+
+    pub fn encode(&self) -> String {
+        let encoder = TextEncoder::new();
+        let families = self.registry.gather();
+        encoder.encode_to_string(&families).unwrap_or_default()
+    }
+}
+
+pub static KEY_METRICS: Lazy<KeyMetrics> = Lazy::new(KeyMetrics::new);
+
+/// Counts a failed signature and raises an alert for the key
+pub async fn record_signing_failure(
+    alerts: &AlertManager,
+    role: KeyRole,
+    address: SuiAddress,
+    error: &str,
+) {
+    KEY_METRICS
+        .signing_failures
+        .with_label_values(&[role.as_str()])
+        .inc();
+    alerts
+        .raise(Alert::signing_failure(
+            role.as_str(),
+            &address.to_string(),
+            error,
+        ))
+        .await;
+}
+
+/// Reads gas balances of every key and the relayer cap's owner and version
+pub async fn check_keys(
+    client: &SuiClient,
+    keys: &[MonitoredKey],
+    cap_id: ObjectID,
+    min_gas_balance: u64,
+) -> Result<KeysReport> {
+    let mut statuses = vec![];
+    for key in keys {
+        let gas_balance = client.get_balance(key.address, CoinType::SUI).await?;
+        statuses.push(KeyStatus {
+            key: *key,
+            gas_balance,
+            low: gas_balance < min_gas_balance as u128,
+        });
+    }
+
+    let obj = client
+        .read_api()
+        .get_object_with_options(cap_id, SuiObjectDataOptions::new().with_owner())
+        .await?;
+
+    let relayer = keys.iter().find(|k| k.role == KeyRole::Relayer);
+    let cap = match obj.data {
+        Some(data) => {
+            let owner = match data.owner {
+                Some(Owner::AddressOwner(address)) => Some(address),
+                _ => None,
+            };
+            CapStatus {
+                cap_id,
+                exists: true,
+                owner,
+                version: Some(data.version.value()),
+                owned_by_relayer: owner.is_some() && owner == relayer.map(|k| k.address),
+            }
+        }
+        None => CapStatus {
+            cap_id,
+            exists: false,
+            owner: None,
+            version: None,
+            owned_by_relayer: false,
+        },
+    };
+
+    Ok(KeysReport {
+        keys: statuses,
+        cap,
+    })
+}
+
+/// Checks the relayer and sponsor keys, exporting the results as metrics and
+/// alerting on low gas or an unusable relayer cap
+pub struct KeyMonitorJob {
+    client: Arc<SuiClient>,
+    alerts: Arc<AlertManager>,
+    keys: Vec<MonitoredKey>,
+    cap_id: ObjectID,
+}
+
+impl KeyMonitorJob {
+    pub fn new(
+        client: Arc<SuiClient>,
+        alerts: Arc<AlertManager>,
+        keys: Vec<MonitoredKey>,
+        cap_id: ObjectID,
+    ) -> Self {
+        Self {
+            client,
+            alerts,
+            keys,
+            cap_id,
+        }
+    }
+}
+
+#[async_trait]
+impl Job for KeyMonitorJob {
+    fn name(&self) -> &'static str {
+        "key_monitor"
+    }
+
+    async fn run(&self) -> Result<()> {
+        let (alerts, cap_id) = (&self.alerts, self.cap_id);
+        let min_gas_balance = alerts.min_gas_balance();
+
+        let report = check_keys(&self.client, &self.keys, cap_id, min_gas_balance)
+            .await
+            .context("failed to check relayer keys")?;
+
+        for status in &report.keys {
+            let address = status.key.address.to_string();
+            KEY_METRICS
+                .gas_balance
+                .with_label_values(&[status.key.role.as_str(), &address])
+                .set(status.gas_balance as f64);
+
+            if status.low {
+                alerts
+                    .raise(Alert::key_gas_low(
+                        status.key.role.as_str(),
+                        &address,
+                        status.gas_balance,
+                        min_gas_balance,
+                    ))
+                    .await;
+            }
+        }
+
+        let problem = report.cap.problem();
+        KEY_METRICS.cap_present.set(problem.is_none() as i64);
+        if let Some(version) = report.cap.version {
+            KEY_METRICS.cap_version.set(version as i64);
+        }
+        match problem {
+            Some(reason) => {
+                alerts
+                    .raise(Alert::relayer_cap_invalid(&cap_id.to_string(), reason))
+                    .await;
+            }
+            None => info!(
+                cap_id = %cap_id,
+                version = ?report.cap.version,
+                "Relayer keys healthy"
+            ),
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,114 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::{Result, anyhow, bail};
+use async_trait::async_trait;
+use tracing::{debug, warn};
+
+use crate::{backend::scheduler::Job, db::repository::Repository};
+
+/// Services fetched per run
+const BATCH_SIZE: i64 = 50;
+/// Largest metadata document accepted
+const MAX_METADATA_BYTES: usize = 256 * 1024;
+const FETCH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Fetches each service's `metadata_uri` and caches the JSON in `services`,
+/// where it feeds catalog search. Documents are refetched once they are
+/// `refresh_after_secs` old; a changed URI is fetched on the next run.
+pub struct MetadataRefreshJob {
+    repo: Arc<Repository>,
+    http: reqwest::Client,
+    refresh_after_secs: u64,
+}
+
+impl MetadataRefreshJob {
+    pub fn new(repo: Arc<Repository>, refresh_after_secs: u64) -> Self {
+        Self {
+            repo,
+            http: reqwest::Client::new(),
+            refresh_after_secs,
+        }
+    }
+
+    async fn fetch(&self, uri: &str) -> Result<serde_json::Value> {
+        if !(uri.starts_with("https://") || uri.starts_with("http://")) {
+            bail!("unsupported metadata URI {}", uri);
+        }
+
+        let mut resp = self
+            .http
+            .get(uri)
+            .timeout(FETCH_TIMEOUT)
+            .send()
+            .await?
+            .error_for_status()?;
+        if resp.content_length().unwrap_or(0) as usize > MAX_METADATA_BYTES {
+            bail!("metadata is larger than {} bytes", MAX_METADATA_BYTES);
+        }
+
+        let mut body = Vec::new();
+        while let Some(chunk) = resp.chunk().await? {
+            body.extend_from_slice(&chunk);
+            if body.len() > MAX_METADATA_BYTES {
+                bail!("metadata is larger than {} bytes", MAX_METADATA_BYTES);
+            }
+        }
+
+        let metadata: serde_json::Value = serde_json::from_slice(&body)
+            .map_err(|e| anyhow!("metadata is not valid JSON: {}", e))?;
+        if !metadata.is_object() {
+            bail!("metadata is not a JSON object");
+        }
+
+        Ok(metadata)
+    }
+}
+
+#[async_trait]
+impl Job for MetadataRefreshJob {
+    fn name(&self) -> &'static str {
+        "metadata_refresh"
+    }
+
+    async fn run(&self) -> Result<()> {
+        let targets = self
+            .repo
+            .services_due_for_metadata(self.refresh_after_secs as i64, BATCH_SIZE)
+            .await?;
+
+        let mut failed = 0;
+        for target in &targets {
+            match self.fetch(&target.metadata_uri).await {
+                Ok(metadata) => {
+                    self.repo
+                        .store_service_metadata(
+                            &target.service_id,
+                            &target.metadata_uri,
+                            Ok(&metadata),
+                        )
+                        .await?;
+                }
+                Err(e) => {
+                    failed += 1;
+                    warn!(
+                        service_id = %target.service_id,
+                        uri = %target.metadata_uri,
+                        "Failed to fetch service metadata: {}",
+                        e
+                    );
+                    self.repo
+                        .store_service_metadata(
+                            &target.service_id,
+                            &target.metadata_uri,
+                            Err(&e.to_string()),
+                        )
+                        .await?;
+                }
+            }
+        }
+
+        let fetched = targets.len();
+        debug!(fetched, failed, "Refreshed service metadata");
+        Ok(())
+    }
+}
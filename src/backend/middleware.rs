@@ -1,30 +1,227 @@
-use std::sync::OnceLock;
+use std::sync::{Arc, OnceLock};
 
+use anyhow::Context;
 use axum::{
-    extract::{Json, Request},
-    http::StatusCode,
+    extract::{Json, Request, State},
+    http::{HeaderValue, StatusCode},
     middleware::Next,
     response::Response,
 };
 
+use tracing::error;
+use uuid::Uuid;
+
+use crate::{
+    api_types::version::{self, ACCEPT_VERSION, API_VERSION},
+    backend::session::{self, SessionSigner},
+    db::{repository::Repository, tenant},
+    utils::error::InfrapassError,
+};
+
+/// Who a request to a protected route authenticated as. The auth middleware
+/// adds it to the request's extensions.
+#[derive(Debug, Clone)]
+pub enum Caller {
+    /// The operator's `API_KEY`
+    Operator,
+    /// A tenant API key. Row-level security keeps it to the tenant's rows.
+    Tenant(String),
+    /// A provider API key, which may only act on the provider's own
+    /// services, or a Sign-in-with-Sui session, which has no `key_id`
+    Provider {
+        key_id: Option<Uuid>,
+        provider_id: String,
+    },
+}
+
+impl Caller {
+    /// Refuses a provider key of any provider but `provider_id`
+    pub fn require_provider(&self, provider_id: &str) -> Result<(), InfrapassError> {
+        match self {
+            Caller::Provider {
+                provider_id: own, ..
+            } if own != provider_id => Err(InfrapassError::Forbidden(
+                "API key belongs to another provider".into(),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Refuses provider keys, for routes that act for buyers or the whole
+    /// deployment
+    pub fn reject_provider_key(&self) -> Result<(), InfrapassError> {
+        match self {
+            Caller::Provider { .. } => Err(InfrapassError::Forbidden(
+                "not available to provider API keys".into(),
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Who a bearer token authenticated as, and the tenant whose rows the
+/// request is bound to
+pub struct Authenticated {
+    pub caller: Caller,
+    pub tenant_id: Option<String>,
+}
+
+/// Resolves a bearer token the way `api_key_auth` does, for transports other
+/// than the REST API. `None` if the token is not a valid key or session.
+pub async fn authenticate(
+    repo: &Repository,
+    sessions: &SessionSigner,
+    key: &str,
+) -> anyhow::Result<Option<Authenticated>> {
+    static MULTI_TENANT: OnceLock<bool> = OnceLock::new();
+    let multi_tenant = *MULTI_TENANT.get_or_init(|| {
+        std::env::var("MULTI_TENANT")
+            .map(|v| v.parse().expect("MULTI_TENANT must be true or false"))
+            .unwrap_or(false)
+    });
+
+    if key == operator_key() {
+        return Ok(Some(Authenticated {
+            caller: Caller::Operator,
+            tenant_id: None,
+        }));
+    }
+
+    if session::is_session_token(key) {
+        return Ok(sessions.verify(key).map(|claims| Authenticated {
+            caller: Caller::Provider {
+                key_id: None,
+                provider_id: claims.sub,
+            },
+            tenant_id: claims.tenant_id.filter(|_| multi_tenant),
+        }));
+    }
+
+    let owner = repo
+        .resolve_api_key(key)
+        .await
+        .context("failed to look up provider API key")?;
+    if let Some(owner) = owner {
+        return Ok(Some(Authenticated {
+            caller: Caller::Provider {
+                key_id: Some(owner.key_id),
+                provider_id: owner.provider_id,
+            },
+            tenant_id: owner.tenant_id.filter(|_| multi_tenant),
+        }));
+    }
+
+    if !multi_tenant {
+        return Ok(None);
+    }
+    let tenant_id = repo
+        .resolve_tenant_api_key(key)
+        .await
+        .context("failed to look up tenant API key")?;
+    Ok(tenant_id.map(|tenant_id| Authenticated {
+        caller: Caller::Tenant(tenant_id.clone()),
+        tenant_id: Some(tenant_id),
+    }))
+}
+
+/// Accepts the operator's `API_KEY`, which sees every row, a provider API
+/// key or session token, which acts for that provider only, or with
+/// `MULTI_TENANT=true` a tenant API key, which binds the request to that
+/// tenant's rows. A provider key or session of a tenant's provider is bound
+/// to that tenant too.
 pub async fn api_key_auth(
-    req: Request,
+    State(repo): State<Arc<Repository>>,
+    State(sessions): State<Arc<SessionSigner>>,
+    mut req: Request,
     next: Next,
 ) -> Result<Response, (StatusCode, Json<serde_json::Value>)> {
+    let Some(key) = bearer_token(&req) else {
+        return Err(unauthorized());
+    };
+
+    match authenticate(&repo, &sessions, &key).await {
+        Ok(Some(auth)) => {
+            req.extensions_mut().insert(auth.caller);
+            Ok(match auth.tenant_id {
+                Some(tenant_id) => tenant::scope(tenant_id, next.run(req)).await,
+                None => next.run(req).await,
+            })
+        }
+        Ok(None) => Err(unauthorized()),
+        Err(e) => {
+            error!("{:#}", e);
+            Err(internal_error())
+        }
+    }
+}
+
+/// Accepts only the operator's `API_KEY`, for API key management and the
+/// SQLite backend, which has no tenants or provider keys
+pub async fn operator_key_auth(
+    mut req: Request,
+    next: Next,
+) -> Result<Response, (StatusCode, Json<serde_json::Value>)> {
+    match bearer_token(&req) {
+        Some(key) if key == operator_key() => {
+            req.extensions_mut().insert(Caller::Operator);
+            Ok(next.run(req).await)
+        }
+        _ => Err(unauthorized()),
+    }
+}
+
+fn operator_key() -> &'static str {
     static API_KEY: OnceLock<String> = OnceLock::new();
-    let expected = API_KEY.get_or_init(|| std::env::var("API_KEY").expect("API_KEY must be set"));
+    API_KEY.get_or_init(|| std::env::var("API_KEY").expect("API_KEY must be set"))
+}
 
-    let provided = req
-        .headers()
+pub(crate) fn bearer_token(req: &Request) -> Option<String> {
+    req.headers()
         .get("Authorization")
         .and_then(|v| v.to_str().ok())
-        .and_then(|v| v.strip_prefix("Bearer "));
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(str::to_string)
+}
+
+fn unauthorized() -> (StatusCode, Json<serde_json::Value>) {
+    (
+        StatusCode::UNAUTHORIZED,
+        Json(serde_json::json!({ "error": "invalid or missing API key" })),
+    )
+}
+
+fn internal_error() -> (StatusCode, Json<serde_json::Value>) {
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(serde_json::json!({ "error": "internal error" })),
+    )
+}
+
+/// Rejects clients built against a contract version this backend can't serve
+/// with 406, and tags every response with the version it was served with
+pub async fn api_version(
+    req: Request,
+    next: Next,
+) -> Result<Response, (StatusCode, Json<serde_json::Value>)> {
+    let requested = req
+        .headers()
+        .get(ACCEPT_VERSION)
+        .map(|v| v.to_str().unwrap_or_default());
 
-    match provided {
-        Some(key) if key == expected => Ok(next.run(req).await),
-        _ => Err((
-            StatusCode::UNAUTHORIZED,
-            Json(serde_json::json!({ "error": "invalid or missing API key" })),
+    match version::negotiate(requested) {
+        Ok(served) => {
+            let mut resp = next.run(req).await;
+            if let Ok(value) = HeaderValue::from_str(&served.to_string()) {
+                resp.headers_mut().insert(API_VERSION, value);
+            }
+            Ok(resp)
+        }
+        Err(e) => Err((
+            StatusCode::NOT_ACCEPTABLE,
+            Json(serde_json::json!({
+                "error": e,
+                "api_version": version::CURRENT.to_string(),
+            })),
         )),
     }
 }
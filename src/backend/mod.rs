@@ -1,4 +1,29 @@
+pub mod access;
+pub mod catalog_cache;
+pub mod contacts;
+pub mod decisions;
+pub mod export;
+pub mod feed;
+pub mod grpc;
 pub mod handlers;
+pub mod idempotency;
+pub mod keys;
+pub mod metadata;
 pub mod middleware;
+pub mod openapi;
+pub mod provider_webhooks;
+pub mod rate_limit;
+pub mod readiness;
+pub mod reconcile;
+pub mod revenue;
 pub mod router;
-pub mod settlement;
\ No newline at end of file
+pub mod scheduler;
+pub mod session;
+pub mod settlement;
+pub mod spend;
+pub mod stats;
+pub mod state;
+pub mod usage;
+pub mod validation_cache;
+pub mod verify;
+pub mod webhooks;
\ No newline at end of file
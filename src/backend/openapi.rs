@@ -0,0 +1,74 @@
+use utoipa::{
+    Modify, OpenApi,
+    openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme},
+};
+
+use crate::{
+    api_types::{
+        validator::{
+            ProviderNotification, RecordUsageBatchRequest, RecordUsageBatchResponse,
+            RecordUsageBatchResult, RecordUsageRequest, ScheduledMaintenance, SidecarHeartbeat,
+            ValidateBatchRequest, ValidateBatchResponse, ValidateBatchResult, ValidateRequest,
+            ValidateResponse,
+        },
+        version,
+    },
+    backend::handlers,
+};
+
+/// The sidecar to validator API contract, for providers writing their own
+/// sidecar
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "Infrapass validator API",
+        description = "Send the contract version a client was built against in \
+            `Accept-Version`; the backend answers with its own in `Api-Version`, \
+            or `406` if it can't serve it."
+    ),
+    paths(
+        handlers::validate_entitlements_handler,
+        handlers::validate_batch_handler,
+        handlers::record_usage_handler,
+        handlers::record_usage_batch_handler,
+        handlers::sidecar_heartbeat_handler,
+        handlers::list_provider_maintenance_handler,
+    ),
+    components(schemas(
+        ValidateRequest,
+        ValidateResponse,
+        ValidateBatchRequest,
+        ValidateBatchResult,
+        ValidateBatchResponse,
+        ProviderNotification,
+        RecordUsageRequest,
+        RecordUsageBatchRequest,
+        RecordUsageBatchResult,
+        RecordUsageBatchResponse,
+        SidecarHeartbeat,
+        ScheduledMaintenance,
+    )),
+    modifiers(&BearerKey),
+    tags((name = "validator", description = "Entitlement checks and usage metering"))
+)]
+struct ApiDoc;
+
+/// The operator's `API_KEY` or a provider or tenant key, as a bearer token
+struct BearerKey;
+
+impl Modify for BearerKey {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "api_key",
+            SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+        );
+    }
+}
+
+/// The spec served at `/openapi.json`, versioned with the contract
+pub fn spec() -> utoipa::openapi::OpenApi {
+    let mut spec = ApiDoc::openapi();
+    spec.info.version = version::CURRENT.to_string();
+    spec
+}
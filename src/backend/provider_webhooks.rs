@@ -0,0 +1,385 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::Result;
+use chrono::Utc;
+use futures::future::join_all;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Notify;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, warn};
+
+use crate::{
+    db::{
+        models::{Entitlement, PendingDelivery, PricingTier, ProviderWebhook},
+        repository::Repository,
+    },
+    utils::{error::InfrapassError, vault::SecretVault, webhook::post_signed},
+};
+
+/// Deliveries attempted per round
+const BATCH_SIZE: i64 = 50;
+
+/// How often due deliveries are checked when nobody wakes the dispatcher
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How often newly expired entitlements are looked up
+const EXPIRY_SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Expiries older than this when first seen, e.g. after a long outage, are
+/// not announced
+const EXPIRY_LOOKBACK_SECS: u64 = 24 * 3600;
+
+/// Attempts before a delivery is given up on as `failed`
+const MAX_ATTEMPTS: i32 = 8;
+
+/// Delay before the first retry, doubled after each failed attempt
+const RETRY_BASE_SECS: i64 = 30;
+const RETRY_MAX_SECS: i64 = 3600;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProviderEvent {
+    EntitlementPurchased,
+    EntitlementToppedUp,
+    EntitlementExpired,
+    TierDeactivated,
+    /// Sent on request, to check an endpoint and its signature checking
+    WebhookTest,
+}
+
+impl ProviderEvent {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ProviderEvent::EntitlementPurchased => "entitlement_purchased",
+            ProviderEvent::EntitlementToppedUp => "entitlement_topped_up",
+            ProviderEvent::EntitlementExpired => "entitlement_expired",
+            ProviderEvent::TierDeactivated => "tier_deactivated",
+            ProviderEvent::WebhookTest => "webhook_test",
+        }
+    }
+}
+
+/// Body posted to a provider's webhook, signed with that webhook's own secret
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProviderWebhookPayload {
+    pub event: ProviderEvent,
+    pub provider_id: String,
+    pub service_id: String,
+    /// Entitlement or tier the notification is about
+    pub subject_id: String,
+    pub detail: serde_json::Value,
+}
+
+impl ProviderWebhookPayload {
+    pub fn purchased(ent: &Entitlement) -> Self {
+        Self {
+            event: ProviderEvent::EntitlementPurchased,
+            provider_id: ent.provider_id.clone(),
+            service_id: ent.service_id.clone(),
+            subject_id: ent.entitlement_id.clone(),
+            detail: serde_json::json!({
+                "buyer": ent.buyer,
+                "tier_id": ent.tier_id,
+                "price_paid": ent.price_paid,
+                "expires_at": ent.expires_at,
+                "quota": ent.quota,
+                "units": ent.units,
+            }),
+        }
+    }
+
+    /// `ent` as it is after the top-up, with `units_added` for `amount`
+    pub fn topped_up(ent: &Entitlement, units_added: u64, amount: u64) -> Self {
+        Self {
+            event: ProviderEvent::EntitlementToppedUp,
+            provider_id: ent.provider_id.clone(),
+            service_id: ent.service_id.clone(),
+            subject_id: ent.entitlement_id.clone(),
+            detail: serde_json::json!({
+                "buyer": ent.buyer,
+                "tier_id": ent.tier_id,
+                "amount": amount.to_string(),
+                "units_added": units_added.to_string(),
+                "units": ent.units,
+            }),
+        }
+    }
+
+    pub fn expired(ent: &Entitlement) -> Self {
+        Self {
+            event: ProviderEvent::EntitlementExpired,
+            provider_id: ent.provider_id.clone(),
+            service_id: ent.service_id.clone(),
+            subject_id: ent.entitlement_id.clone(),
+            detail: serde_json::json!({
+                "buyer": ent.buyer,
+                "tier_id": ent.tier_id,
+                "expires_at": ent.expires_at,
+            }),
+        }
+    }
+
+    pub fn tier_deactivated(provider_id: &str, tier: &PricingTier, active: i64) -> Self {
+        Self {
+            event: ProviderEvent::TierDeactivated,
+            provider_id: provider_id.to_string(),
+            service_id: tier.service_id.clone(),
+            subject_id: tier.tier_id.clone(),
+            detail: serde_json::json!({
+                "tier_name": tier.tier_name,
+                "active_entitlements": active,
+            }),
+        }
+    }
+
+    /// A test notification, about the webhook itself rather than a service
+    pub fn test(webhook: &ProviderWebhook) -> Self {
+        Self {
+            event: ProviderEvent::WebhookTest,
+            provider_id: webhook.provider_id.clone(),
+            service_id: String::new(),
+            subject_id: webhook.id.to_string(),
+            detail: serde_json::json!({
+                "url": webhook.url,
+                "sent_at": Utc::now(),
+            }),
+        }
+    }
+}
+
+/// Signing secrets of provider webhooks. With `WEBHOOK_ENCRYPTION_KEY` set
+/// they are stored encrypted, otherwise as is.
+pub struct WebhookSecrets(SecretVault);
+
+impl WebhookSecrets {
+    /// `key_hex` is the 32-byte `WEBHOOK_ENCRYPTION_KEY`, hex encoded
+    pub fn new(key_hex: Option<&str>) -> Result<Self> {
+        Ok(Self(SecretVault::new(
+            "WEBHOOK_ENCRYPTION_KEY",
+            key_hex,
+            "webhook secret is encrypted but WEBHOOK_ENCRYPTION_KEY is not set",
+        )?))
+    }
+
+    /// The `secret` and `secret_ciphertext` to store for a new secret
+    pub fn store(&self, secret: &str) -> Result<(Option<String>, Option<Vec<u8>>), InfrapassError> {
+        if !self.0.is_enabled() {
+            return Ok((Some(secret.to_string()), None));
+        }
+        Ok((None, Some(self.0.seal(secret)?)))
+    }
+
+    /// The plaintext secret of a stored webhook
+    pub fn reveal(
+        &self,
+        secret: Option<&str>,
+        secret_ciphertext: Option<&[u8]>,
+    ) -> Result<String, InfrapassError> {
+        match (secret, secret_ciphertext) {
+            (Some(secret), _) => Ok(secret.to_string()),
+            (None, Some(sealed)) => self.0.open(sealed),
+            (None, None) => Err(InfrapassError::Other("webhook has no secret".into())),
+        }
+    }
+
+    /// Encrypts the secrets stored before `WEBHOOK_ENCRYPTION_KEY` was set,
+    /// returning how many were sealed. Does nothing without a key.
+    pub async fn seal_existing(&self, repo: &Repository) -> Result<usize> {
+        if !self.0.is_enabled() {
+            return Ok(0);
+        }
+
+        let webhooks = repo.unsealed_provider_webhooks().await?;
+        for webhook in &webhooks {
+            if let Some(secret) = &webhook.secret {
+                repo.seal_provider_webhook_secret(webhook.id, &self.0.seal(secret)?)
+                    .await?;
+            }
+        }
+        Ok(webhooks.len())
+    }
+}
+
+/// Result of a test notification
+#[derive(Debug, Clone, Serialize)]
+pub struct TestDelivery {
+    pub delivered: bool,
+    pub status_code: Option<u16>,
+    pub error: Option<String>,
+}
+
+/// Posts a `webhook_test` notification right away. It bypasses the delivery
+/// queue, so it is neither retried nor listed with the webhook's deliveries.
+pub async fn send_test(
+    secrets: &WebhookSecrets,
+    webhook: &ProviderWebhook,
+) -> Result<TestDelivery, InfrapassError> {
+    let secret = secrets.reveal(
+        webhook.secret.as_deref(),
+        webhook.secret_ciphertext.as_deref(),
+    )?;
+    let payload = serde_json::to_vec(&ProviderWebhookPayload::test(webhook))?;
+
+    let delivery = match post_signed(&reqwest::Client::new(), &webhook.url, &secret, payload).await
+    {
+        Ok(resp) => TestDelivery {
+            delivered: resp.status().is_success(),
+            status_code: Some(resp.status().as_u16()),
+            error: (!resp.status().is_success())
+                .then(|| format!("webhook responded with {}", resp.status())),
+        },
+        Err(e) => TestDelivery {
+            delivered: false,
+            status_code: None,
+            error: Some(e.to_string()),
+        },
+    };
+    Ok(delivery)
+}
+
+/// Delivers the provider notifications the event worker queued in
+/// `provider_webhook_deliveries`, and queues expiry notices as entitlements
+/// run out. Failed deliveries are retried with backoff and their status is
+/// kept for the provider to inspect.
+pub struct WebhookDispatcher {
+    repo: Arc<Repository>,
+    secrets: Arc<WebhookSecrets>,
+    http: reqwest::Client,
+    wake: Arc<Notify>,
+}
+
+impl WebhookDispatcher {
+    pub fn new(repo: Arc<Repository>, secrets: Arc<WebhookSecrets>) -> Self {
+        Self {
+            repo,
+            secrets,
+            http: reqwest::Client::new(),
+            wake: Arc::new(Notify::new()),
+        }
+    }
+
+    /// Notified by the worker after a commit that may have queued
+    /// deliveries, so they go out without waiting for the next poll
+    pub fn waker(&self) -> Arc<Notify> {
+        self.wake.clone()
+    }
+
+    pub async fn run(self, shutdown: CancellationToken) -> Result<()> {
+        info!("Provider webhook dispatcher started");
+        let mut sweep = tokio::time::interval(EXPIRY_SWEEP_INTERVAL);
+
+        loop {
+            match self.deliver_due().await {
+                // A full batch means more may be waiting
+                Ok(attempted) if attempted == BATCH_SIZE as usize => continue,
+                Ok(_) => {}
+                Err(e) => error!("Failed to deliver provider webhooks: {}", e),
+            }
+
+            tokio::select! {
+                _ = self.wake.notified() => {}
+                _ = tokio::time::sleep(POLL_INTERVAL) => {}
+                _ = sweep.tick() => {
+                    if let Err(e) = self.queue_expired().await {
+                        error!("Failed to queue expiry notices: {}", e);
+                    }
+                }
+                _ = shutdown.cancelled() => break,
+            }
+        }
+
+        info!("Provider webhook dispatcher stopped");
+        Ok(())
+    }
+
+    async fn queue_expired(&self) -> Result<()> {
+        let expired = self
+            .repo
+            .expired_entitlements_to_announce(EXPIRY_LOOKBACK_SECS)
+            .await?;
+        if expired.is_empty() {
+            return Ok(());
+        }
+
+        let mut tx = self.repo.begin().await?;
+        for ent in &expired {
+            self.repo
+                .enqueue_provider_webhooks(&mut tx, &ProviderWebhookPayload::expired(ent))
+                .await?;
+        }
+        tx.commit().await?;
+
+        debug!(count = expired.len(), "Queued entitlement expiry notices");
+        Ok(())
+    }
+
+    /// Attempts one batch of due deliveries concurrently, returning how many
+    /// were attempted
+    async fn deliver_due(&self) -> Result<usize> {
+        let mut tx = self.repo.begin().await?;
+        let deliveries = self.repo.claim_due_deliveries(&mut tx, BATCH_SIZE).await?;
+        if deliveries.is_empty() {
+            return Ok(0);
+        }
+
+        let results = join_all(deliveries.iter().map(|d| self.attempt(d))).await;
+        for (delivery, result) in deliveries.iter().zip(results) {
+            match result {
+                Ok(status_code) => {
+                    self.repo
+                        .mark_delivery_delivered(&mut tx, delivery.id, status_code)
+                        .await?;
+                }
+                Err((status_code, error)) => {
+                    let attempts = delivery.attempts + 1;
+                    let retry_in_secs = (attempts < MAX_ATTEMPTS).then(|| {
+                        RETRY_BASE_SECS
+                            .saturating_mul(1i64 << delivery.attempts.min(16))
+                            .min(RETRY_MAX_SECS)
+                    });
+                    warn!(
+                        delivery_id = delivery.id,
+                        event = %delivery.event,
+                        attempts,
+                        retry_in_secs,
+                        error = %error,
+                        "Provider webhook delivery failed"
+                    );
+                    self.repo
+                        .record_delivery_failure(
+                            &mut tx,
+                            delivery.id,
+                            status_code,
+                            &error,
+                            retry_in_secs,
+                        )
+                        .await?;
+                }
+            }
+        }
+        tx.commit().await?;
+
+        Ok(deliveries.len())
+    }
+
+    /// Posts one delivery, returning the response status or the status and
+    /// reason it failed with
+    async fn attempt(&self, delivery: &PendingDelivery) -> Result<u16, (Option<u16>, String)> {
+        let secret = self
+            .secrets
+            .reveal(
+                delivery.secret.as_deref(),
+                delivery.secret_ciphertext.as_deref(),
+            )
+            .map_err(|e| (None, e.to_string()))?;
+        let payload = delivery.payload.clone().into_bytes();
+        match post_signed(&self.http, &delivery.url, &secret, payload).await {
+            Ok(resp) if resp.status().is_success() => Ok(resp.status().as_u16()),
+            Ok(resp) => Err((
+                Some(resp.status().as_u16()),
+                format!("webhook responded with {}", resp.status()),
+            )),
+            Err(e) => Err((None, e.to_string())),
+        }
+    }
+}
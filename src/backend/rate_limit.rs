@@ -0,0 +1,119 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use axum::{
+    extract::{Json, Request, State},
+    http::{StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use redis::{Client as RedisClient, Script, aio::MultiplexedConnection};
+use tracing::{debug, warn};
+
+use crate::{backend::middleware::bearer_token, db::tenant};
+
+/// Refills `KEYS[1]` at `ARGV[1]` tokens per second up to `ARGV[2]` and takes
+/// one. Returns whether a token was taken and, if not, how many milliseconds
+/// until one is. Redis's clock is used so every API replica shares a bucket.
+const TOKEN_BUCKET: &str = r#"
+local rate = tonumber(ARGV[1])
+local burst = tonumber(ARGV[2])
+local time = redis.call('TIME')
+local now = tonumber(time[1]) * 1000 + math.floor(tonumber(time[2]) / 1000)
+
+local bucket = redis.call('HMGET', KEYS[1], 'tokens', 'ts')
+local tokens = tonumber(bucket[1]) or burst
+local ts = tonumber(bucket[2]) or now
+tokens = math.min(burst, tokens + math.max(0, now - ts) * rate / 1000)
+
+local allowed = 0
+local wait_ms = 0
+if tokens >= 1 then
+    tokens = tokens - 1
+    allowed = 1
+else
+    wait_ms = math.ceil((1 - tokens) * 1000 / rate)
+end
+
+redis.call('HSET', KEYS[1], 'tokens', tostring(tokens), 'ts', now)
+redis.call('PEXPIRE', KEYS[1], math.ceil(burst * 1000 / rate) + 1000)
+return {allowed, wait_ms}
+"#;
+
+/// Token buckets in Redis, one per API key and route, so a single sidecar
+/// can't take the validator API's capacity from the others
+pub struct RateLimiter {
+    redis: MultiplexedConnection,
+    script: Script,
+    rps: f64,
+    burst: u64,
+}
+
+impl RateLimiter {
+    /// `rps` requests per second per key, with bursts of up to `burst`
+    pub async fn new(redis_client: RedisClient, rps: f64, burst: u64) -> Result<Self> {
+        Ok(Self {
+            redis: redis_client.get_multiplexed_async_connection().await?,
+            script: Script::new(TOKEN_BUCKET),
+            rps,
+            burst: burst.max(1),
+        })
+    }
+
+    /// `None` if the request may go ahead, else the seconds to wait. Redis
+    /// errors let the request through.
+    pub(crate) async fn check(&self, api_key: &str, route: &str) -> Option<u64> {
+        let key = format!(
+            "infrapass:ratelimit:{}:{}",
+            route,
+            tenant::hash_api_key(api_key)
+        );
+        let mut conn = self.redis.clone();
+        let result: redis::RedisResult<(i64, i64)> = self
+            .script
+            .key(key)
+            .arg(self.rps)
+            .arg(self.burst)
+            .invoke_async(&mut conn)
+            .await;
+
+        match result {
+            Ok((1, _)) => None,
+            Ok((_, wait_ms)) => Some((wait_ms.max(0) as u64).div_ceil(1000).max(1)),
+            Err(e) => {
+                warn!("Rate limit check failed, allowing request: {}", e);
+                None
+            }
+        }
+    }
+}
+
+/// Answers `429` with `Retry-After` once the caller's API key is out of
+/// tokens for the route. Runs after authentication, so unknown keys are
+/// rejected without touching Redis.
+pub async fn rate_limit(
+    State(limiter): State<Option<Arc<RateLimiter>>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let Some(limiter) = limiter else {
+        return next.run(req).await;
+    };
+    let Some(api_key) = bearer_token(&req) else {
+        return next.run(req).await;
+    };
+
+    let route = req.uri().path().trim_start_matches('/').to_string();
+    match limiter.check(&api_key, &route).await {
+        None => next.run(req).await,
+        Some(retry_after) => {
+            debug!(route = %route, retry_after, "Rate limit exceeded");
+            (
+                StatusCode::TOO_MANY_REQUESTS,
+                [(header::RETRY_AFTER, retry_after.to_string())],
+                Json(serde_json::json!({ "error": "rate limit exceeded" })),
+            )
+                .into_response()
+        }
+    }
+}
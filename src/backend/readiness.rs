@@ -0,0 +1,159 @@
+use std::{sync::Arc, time::Duration};
+
+use serde::Serialize;
+use tokio::{sync::RwLock, time::Instant};
+use tracing::warn;
+
+use crate::{
+    db::{self, repository::Repository},
+    events::metrics::EventMetrics,
+    pubsub::publisher::PubSubPublisher,
+};
+
+/// Longest a single dependency check may take before it counts as down
+const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// State of one dependency in a `/readyz` answer
+#[derive(Debug, Clone, Serialize)]
+pub struct ComponentStatus {
+    /// `ok`, `down`, or for the indexer `waiting`, `disconnected` or `stalled`
+    pub status: &'static str,
+    /// Whether this component failing makes the instance not ready
+    pub critical: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latency_ms: Option<u64>,
+}
+
+/// The indexer's part of a `/readyz` answer
+#[derive(Debug, Clone, Serialize)]
+pub struct IndexerStatus {
+    #[serde(flatten)]
+    pub component: ComponentStatus,
+    pub last_checkpoint: Option<u64>,
+    /// Seconds since the last checkpoint arrived
+    pub checkpoint_lag_secs: Option<u64>,
+}
+
+/// Body of `GET /readyz`
+#[derive(Debug, Clone, Serialize)]
+pub struct ReadinessReport {
+    /// `ready` or `not_ready`
+    pub status: &'static str,
+    pub postgres: ComponentStatus,
+    pub redis: ComponentStatus,
+    pub indexer: IndexerStatus,
+}
+
+impl ReadinessReport {
+    pub fn is_ready(&self) -> bool {
+        [&self.postgres, &self.redis, &self.indexer.component]
+            .iter()
+            .all(|c| !c.critical || c.status == "ok")
+    }
+}
+
+/// Checks what the server needs to serve traffic. Postgres and Redis are
+/// critical. The indexer is reported but only critical when `require_indexer`
+/// is set, since a fullnode outage would otherwise take every replica out of
+/// rotation at once.
+pub struct Readiness {
+    repo: Arc<Repository>,
+    publisher: Arc<PubSubPublisher>,
+    indexer: Arc<RwLock<EventMetrics>>,
+    max_checkpoint_lag: Duration,
+    require_indexer: bool,
+}
+
+impl Readiness {
+    pub fn new(
+        repo: Arc<Repository>,
+        publisher: Arc<PubSubPublisher>,
+        indexer: Arc<RwLock<EventMetrics>>,
+        max_checkpoint_lag: Duration,
+    ) -> Self {
+        Self {
+            repo,
+            publisher,
+            indexer,
+            max_checkpoint_lag,
+            require_indexer: false,
+        }
+    }
+
+    /// Report not ready while the listener is disconnected or stalled
+    pub fn with_required_indexer(mut self, required: bool) -> Self {
+        self.require_indexer = required;
+        self
+    }
+
+    pub async fn check(&self) -> ReadinessReport {
+        let (postgres, redis) = tokio::join!(
+            timed("postgres", db::health_check(self.repo.pool())),
+            timed("redis", self.publisher.ping()),
+        );
+        let indexer = self.indexer_status().await;
+
+        let mut report = ReadinessReport {
+            status: "ready",
+            postgres,
+            redis,
+            indexer,
+        };
+        if !report.is_ready() {
+            report.status = "not_ready";
+        }
+        report
+    }
+
+    async fn indexer_status(&self) -> IndexerStatus {
+        let metrics = self.indexer.read().await;
+        let lag = metrics
+            .last_checkpoint_received_at
+            .map(|t| Instant::now().duration_since(t));
+
+        let status = if !metrics.connection_healthy {
+            "disconnected"
+        } else {
+            match lag {
+                None => "waiting",
+                Some(lag) if lag > self.max_checkpoint_lag => "stalled",
+                Some(_) => "ok",
+            }
+        };
+
+        IndexerStatus {
+            component: ComponentStatus {
+                status,
+                critical: self.require_indexer,
+                latency_ms: None,
+            },
+            last_checkpoint: metrics.last_checkpoint_received,
+            checkpoint_lag_secs: lag.map(|l| l.as_secs()),
+        }
+    }
+}
+
+/// Runs a critical check under `CHECK_TIMEOUT`
+async fn timed<E: std::fmt::Display>(
+    name: &str,
+    check: impl Future<Output = Result<(), E>>,
+) -> ComponentStatus {
+    let started = Instant::now();
+    let status = match tokio::time::timeout(CHECK_TIMEOUT, check).await {
+        Ok(Ok(())) => "ok",
+        Ok(Err(e)) => {
+            warn!(component = name, error = %e, "Readiness check failed");
+            "down"
+        }
+        Err(_) => {
+            warn!(component = name, "Readiness check timed out");
+            "down"
+        }
+    };
+
+    ComponentStatus {
+        status,
+        critical: true,
+        latency_ms: Some(started.elapsed().as_millis() as u64),
+    }
+}
@@ -0,0 +1,427 @@
+use std::{fmt, sync::Arc};
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use prometheus::{IntCounterVec, Opts, Registry, TextEncoder};
+use sui_sdk::SuiClient;
+use sui_types::base_types::ObjectID;
+use tracing::{info, warn};
+
+use crate::{
+    alerting::{manager::AlertManager, types::Alert},
+    backend::{scheduler::Job, verify},
+    client::client_ext::SuiClientExt,
+    db::repository::Repository,
+    events::types::EntitlementConfig,
+    types::amount::MistAmount,
+};
+
+/// Divergences named in an alert; the rest are only counted
+const ALERT_EXAMPLES: usize = 5;
+
+/// A sampled row whose values differ from the object on chain
+#[derive(Debug, Clone)]
+pub enum Divergence {
+    TierNotOnchain {
+        tier_id: String,
+    },
+    TierPrice {
+        tier_id: String,
+        chain: u64,
+        db: u64,
+    },
+    TierActive {
+        tier_id: String,
+        chain: bool,
+        db: Option<bool>,
+    },
+    EntitlementNotOnchain {
+        entitlement_id: String,
+    },
+    EntitlementExpiry {
+        entitlement_id: String,
+        /// Unix milliseconds
+        chain: u64,
+        db: Option<DateTime<Utc>>,
+    },
+    /// Remaining quota, or units for usage based entitlements, is higher in
+    /// Postgres than on chain. Lower is expected until usage is settled.
+    EntitlementQuota {
+        entitlement_id: String,
+        chain: EntitlementConfig,
+        db: u64,
+    },
+}
+
+impl Divergence {
+    /// Label for the `field` metric label
+    pub fn field(&self) -> &'static str {
+        match self {
+            Divergence::TierNotOnchain { .. } => "tier_missing",
+            Divergence::TierPrice { .. } => "price",
+            Divergence::TierActive { .. } => "active",
+            Divergence::EntitlementNotOnchain { .. } => "entitlement_missing",
+            Divergence::EntitlementExpiry { .. } => "expiry",
+            Divergence::EntitlementQuota { .. } => "quota",
+        }
+    }
+
+    /// Whether `repair` can fix this divergence from on-chain state alone
+    pub fn is_repairable(&self) -> bool {
+        !matches!(
+            self,
+            Divergence::TierNotOnchain { .. } | Divergence::EntitlementNotOnchain { .. }
+        )
+    }
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Divergence::TierNotOnchain { tier_id } => {
+                write!(f, "tier {} is indexed but not on-chain", tier_id)
+            }
+            Divergence::TierPrice { tier_id, chain, db } => {
+                write!(f, "tier {} price: chain={} db={}", tier_id, chain, db)
+            }
+            Divergence::TierActive { tier_id, chain, db } => {
+                write!(f, "tier {} active: chain={} db={:?}", tier_id, chain, db)
+            }
+            Divergence::EntitlementNotOnchain { entitlement_id } => {
+                write!(
+                    f,
+                    "entitlement {} is indexed but not on-chain",
+                    entitlement_id
+                )
+            }
+            Divergence::EntitlementExpiry {
+                entitlement_id,
+                chain,
+                db,
+            } => write!(
+                f,
+                "entitlement {} expires_at: chain={} db={}",
+                entitlement_id,
+                DateTime::<Utc>::from_timestamp_millis(*chain as i64)
+                    .map(|t| t.to_string())
+                    .unwrap_or_else(|| chain.to_string()),
+                db.map(|t| t.to_string())
+                    .unwrap_or_else(|| "<none>".to_string())
+            ),
+            Divergence::EntitlementQuota {
+                entitlement_id,
+                chain,
+                db,
+            } => write!(
+                f,
+                "entitlement {} remaining: chain={} db={}",
+                entitlement_id,
+                chain.quota().or(chain.units()).unwrap_or_default(),
+                db
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ReconcileReport {
+    pub tiers_checked: usize,
+    pub entitlements_checked: usize,
+    pub divergences: Vec<Divergence>,
+}
+
+impl ReconcileReport {
+    pub fn sampled(&self) -> usize {
+        self.tiers_checked + self.entitlements_checked
+    }
+}
+
+/// Compares `sample` random tiers and `sample` random entitlements in
+/// Postgres with their objects on chain
+pub async fn reconcile(
+    client: &SuiClient,
+    repo: &Repository,
+    sample: i64,
+) -> Result<ReconcileReport> {
+    let mut report = ReconcileReport::default();
+
+    for tier in repo.sample_tiers(sample).await? {
+        let tier_id = ObjectID::from_hex_literal(&tier.tier_id)?;
+        let onchain = verify::find_tier(client, tier_id)
+            .await
+            .with_context(|| format!("failed to read tier {}", tier.tier_id))?;
+        report.tiers_checked += 1;
+
+        let Some(onchain) = onchain else {
+            report.divergences.push(Divergence::TierNotOnchain {
+                tier_id: tier.tier_id,
+            });
+            continue;
+        };
+        if tier.price.get() != onchain.price {
+            report.divergences.push(Divergence::TierPrice {
+                tier_id: tier.tier_id.clone(),
+                chain: onchain.price,
+                db: tier.price.get(),
+            });
+        }
+        if tier.is_active != Some(onchain.active) {
+            report.divergences.push(Divergence::TierActive {
+                tier_id: tier.tier_id.clone(),
+                chain: onchain.active,
+                db: tier.is_active,
+            });
+        }
+    }
+
+    for entitlement in repo.sample_entitlements(sample).await? {
+        let entitlement_id = ObjectID::from_hex_literal(&entitlement.entitlement_id)?;
+        let onchain = client
+            .get_entitlement(entitlement_id)
+            .await
+            .with_context(|| {
+                format!("failed to read entitlement {}", entitlement.entitlement_id)
+            })?;
+        report.entitlements_checked += 1;
+
+        let Some(onchain) = onchain else {
+            report.divergences.push(Divergence::EntitlementNotOnchain {
+                entitlement_id: entitlement.entitlement_id,
+            });
+            continue;
+        };
+        let db_expiry = entitlement.expires_at.map(|t| t.timestamp_millis() as u64);
+        if let Some(chain) = onchain
+            .expires_at()
+            .filter(|chain| db_expiry != Some(*chain))
+        {
+            report.divergences.push(Divergence::EntitlementExpiry {
+                entitlement_id: entitlement.entitlement_id.clone(),
+                chain,
+                db: entitlement.expires_at,
+            });
+        }
+
+        let db_remaining = match onchain.config {
+            EntitlementConfig::UsageBased { .. } => Some(entitlement.units.get()),
+            _ => entitlement.quota.map(|q| q.get()),
+        };
+        let higher = onchain
+            .remaining()
+            .zip(db_remaining)
+            .filter(|(chain, db)| db > chain);
+        if let Some((_, db)) = higher {
+            report.divergences.push(Divergence::EntitlementQuota {
+                entitlement_id: entitlement.entitlement_id.clone(),
+                chain: onchain.config.clone(),
+                db,
+            });
+        }
+    }
+
+    Ok(report)
+}
+
+/// Rewrites the diverging rows from the on-chain values carried by each
+/// divergence. Returns the number repaired.
+pub async fn repair(repo: &Repository, divergences: &[Divergence]) -> Result<usize> {
+    let mut repaired = 0;
+
+    for divergence in divergences {
+        let mut tx = repo.begin().await?;
+
+        match divergence {
+            Divergence::TierPrice { tier_id, chain, .. } => {
+                repo.update_tier_price(&mut tx, tier_id, MistAmount::new(*chain))
+                    .await?;
+            }
+            Divergence::TierActive { tier_id, chain, .. } => {
+                if *chain {
+                    repo.reactivate_tier(&mut tx, tier_id).await?;
+                } else {
+                    repo.deactivate_tier(&mut tx, tier_id).await?;
+                }
+            }
+            Divergence::EntitlementExpiry {
+                entitlement_id,
+                chain,
+                ..
+            } => {
+                let Some(expires_at) = DateTime::from_timestamp_millis(*chain as i64) else {
+                    continue;
+                };
+                repo.set_entitlement_expiry(&mut tx, entitlement_id, expires_at)
+                    .await?;
+            }
+            Divergence::EntitlementQuota {
+                entitlement_id,
+                chain,
+                ..
+            } => {
+                repo.reconcile_consumption(&mut tx, entitlement_id, chain)
+                    .await?;
+            }
+            Divergence::TierNotOnchain { .. } | Divergence::EntitlementNotOnchain { .. } => {
+                continue;
+            }
+        }
+        tx.commit().await?;
+
+        info!(divergence = %divergence, "Repaired");
+        RECONCILE_METRICS
+            .repaired
+            .with_label_values(&[divergence.field()])
+            .inc();
+        repaired += 1;
+    }
+
+    Ok(repaired)
+}
+
+/// Samples rows on a schedule, counts what diverges and raises an alert when
+/// anything does, optionally repairing it
+pub struct ReconcileJob {
+    client: Arc<SuiClient>,
+    repo: Arc<Repository>,
+    alerts: Arc<AlertManager>,
+    sample: i64,
+    repair: bool,
+}
+
+impl ReconcileJob {
+    pub fn new(
+        client: Arc<SuiClient>,
+        repo: Arc<Repository>,
+        alerts: Arc<AlertManager>,
+        sample: i64,
+    ) -> Self {
+        Self {
+            client,
+            repo,
+            alerts,
+            sample,
+            repair: false,
+        }
+    }
+
+    pub fn with_repair(mut self, repair: bool) -> Self {
+        self.repair = repair;
+        self
+    }
+}
+
+#[async_trait]
+impl Job for ReconcileJob {
+    fn name(&self) -> &'static str {
+        "reconcile"
+    }
+
+    async fn run(&self) -> Result<()> {
+        let report = reconcile(&self.client, &self.repo, self.sample).await?;
+
+        RECONCILE_METRICS
+            .checked
+            .with_label_values(&["tier"])
+            .inc_by(report.tiers_checked as u64);
+        RECONCILE_METRICS
+            .checked
+            .with_label_values(&["entitlement"])
+            .inc_by(report.entitlements_checked as u64);
+        for divergence in &report.divergences {
+            warn!(divergence = %divergence, "Chain and Postgres diverge");
+            RECONCILE_METRICS
+                .divergences
+                .with_label_values(&[divergence.field()])
+                .inc();
+        }
+
+        if report.divergences.is_empty() {
+            info!(sampled = report.sampled(), "Sampled rows match chain");
+            return Ok(());
+        }
+
+        let examples: Vec<String> = report
+            .divergences
+            .iter()
+            .take(ALERT_EXAMPLES)
+            .map(|d| d.to_string())
+            .collect();
+        self.alerts
+            .raise(Alert::reconcile_divergence(
+                report.divergences.len(),
+                report.sampled(),
+                &examples,
+            ))
+            .await;
+
+        if self.repair {
+            let repaired = repair(&self.repo, &report.divergences).await?;
+            info!(
+                repaired,
+                divergences = report.divergences.len(),
+                "Repaired diverging rows"
+            );
+        }
+
+        Ok(())
+    }
+}
+
+pub struct ReconcileMetrics {
+    pub checked: IntCounterVec,
+    pub divergences: IntCounterVec,
+    pub repaired: IntCounterVec,
+    registry: Registry,
+}
+
+impl ReconcileMetrics {
+    fn new() -> Self {
+        let registry = Registry::new();
+
+        let checked = IntCounterVec::new(
+            Opts::new(
+                "infrapass_reconcile_checked_total",
+                "Rows compared with their on-chain object, by kind",
+            ),
+            &["kind"],
+        )
+        .unwrap();
+        let divergences = IntCounterVec::new(
+            Opts::new(
+                "infrapass_reconcile_divergences_total",
+                "Sampled rows that differ from chain, by field",
+            ),
+            &["field"],
+        )
+        .unwrap();
+        let repaired = IntCounterVec::new(
+            Opts::new(
+                "infrapass_reconcile_repaired_total",
+                "Diverging rows rewritten from chain, by field",
+            ),
+            &["field"],
+        )
+        .unwrap();
+
+        registry.register(Box::new(checked.clone())).unwrap();
+        registry.register(Box::new(divergences.clone())).unwrap();
+        registry.register(Box::new(repaired.clone())).unwrap();
+
+        Self {
+            checked,
+            divergences,
+            repaired,
+            registry,
+        }
+    }
+
+    pub fn encode(&self) -> String {
+        let encoder = TextEncoder::new();
+        let families = self.registry.gather();
+        encoder.encode_to_string(&families).unwrap_or_default()
+    }
+}
+
+pub static RECONCILE_METRICS: Lazy<ReconcileMetrics> = Lazy::new(ReconcileMetrics::new);
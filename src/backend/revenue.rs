@@ -0,0 +1,93 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{backend::spend::SpendBucket, db::models::RevenueRow, types::amount::MistAmount};
+
+/// Sales in one coin over the whole report, across services
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CoinRevenue {
+    pub coin_type: String,
+    pub purchases: i64,
+    pub total: MistAmount,
+}
+
+/// Sales of one service in one coin over the whole report
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServiceRevenue {
+    pub service_id: String,
+    pub coin_type: String,
+    pub purchases: i64,
+    pub total: MistAmount,
+}
+
+/// Body of `GET /providers/{provider_id}/revenue`. Amounts are in each
+/// coin's base units; coins are never added together.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RevenueReport {
+    pub provider_id: String,
+    pub since: DateTime<Utc>,
+    pub bucket: SpendBucket,
+    pub totals: Vec<CoinRevenue>,
+    pub services: Vec<ServiceRevenue>,
+    pub periods: Vec<RevenueRow>,
+}
+
+impl RevenueReport {
+    pub fn new(
+        provider_id: String,
+        since: DateTime<Utc>,
+        bucket: SpendBucket,
+        periods: Vec<RevenueRow>,
+    ) -> Self {
+        let mut services: BTreeMap<(&str, &str), (i64, u64)> = BTreeMap::new();
+        for row in &periods {
+            let service = services
+                .entry((row.service_id.as_str(), row.coin_type.as_str()))
+                .or_default();
+            service.0 += row.purchases;
+            service.1 = service.1.saturating_add(row.total.get());
+        }
+
+        let services = services
+            .into_iter()
+            .map(
+                |((service_id, coin_type), (purchases, total))| ServiceRevenue {
+                    service_id: service_id.to_string(),
+                    coin_type: coin_type.to_string(),
+                    purchases,
+                    total: MistAmount::new(total),
+                },
+            )
+            .collect();
+
+        Self {
+            provider_id,
+            since,
+            bucket,
+            totals: coin_totals(&periods),
+            services,
+            periods,
+        }
+    }
+}
+
+/// Sales per coin across every period and service of `rows`
+pub fn coin_totals(rows: &[RevenueRow]) -> Vec<CoinRevenue> {
+    let mut coins: BTreeMap<&str, (i64, u64)> = BTreeMap::new();
+    for row in rows {
+        let coin = coins.entry(row.coin_type.as_str()).or_default();
+        coin.0 += row.purchases;
+        coin.1 = coin.1.saturating_add(row.total.get());
+    }
+
+    coins
+        .into_iter()
+        .map(|(coin_type, (purchases, total))| CoinRevenue {
+            coin_type: coin_type.to_string(),
+            purchases,
+            total: MistAmount::new(total),
+        })
+        .collect()
+}
@@ -1,22 +1,276 @@
-use std::sync::Arc;
-
-use crate::{
-    backend::{
-        handlers::{record_usage_handler, validate_entitlements_handler},
-        middleware::api_key_auth,
+use crate::backend::{
+    handlers::{
+        admin_buyer_handler, admin_entitlement_handler, admin_invalidate_handler,
+        admin_settlements_handler, admin_validations_handler, buyer_spend_handler,
+        cancel_maintenance_handler, catalog_feed_handler, clear_tier_replacement_handler,
+        clear_tier_sla_handler, create_maintenance_handler, delete_buyer_budget_handler,
+        delete_buyer_webhook_handler, delete_provider_webhook_handler, event_stream_handler,
+        export_provider_revenue_handler, export_provider_usage_handler, get_tier_handler,
+        get_tier_replacement_handler, issue_api_key_handler, link_contact_handler,
+        list_api_keys_handler, list_buyer_budgets_handler, list_buyer_entitlements_handler,
+        list_buyer_webhooks_handler, list_maintenance_handler, list_provider_contacts_handler,
+        list_provider_maintenance_handler, list_provider_services_handler,
+        list_provider_sidecars_handler, list_provider_webhooks_handler, list_providers_handler,
+        list_scheduled_jobs_handler, list_service_entitlements_handler, list_service_tiers_handler,
+        list_services_handler, list_tiers_handler, list_webhook_deliveries_handler,
+        metrics_handler, provider_revenue_handler, provider_stats_handler, provider_usage_handler,
+        readyz_handler, record_usage_batch_handler, record_usage_handler,
+        register_buyer_webhook_handler, register_provider_webhook_handler, revoke_api_key_handler,
+        rotate_api_key_handler, search_services_handler, service_access_handler,
+        service_usage_handler, set_buyer_budget_handler, set_tier_replacement_handler,
+        set_tier_sla_handler, sidecar_heartbeat_handler, sign_in_challenge_handler,
+        sign_in_handler, test_provider_webhook_handler, tier_history_handler,
+        top_up_entitlement_handler, unlink_contact_handler, validate_batch_handler,
+        validate_entitlements_handler,
     },
-    db::repository::Repository,
+    idempotency::idempotency,
+    middleware::{api_key_auth, api_version, operator_key_auth},
+    openapi,
+    rate_limit::rate_limit,
+    state::{AppState, LiteState},
 };
 use axum::{
     Router,
     middleware::{self},
     routing,
 };
+use utoipa_swagger_ui::SwaggerUi;
+
+pub fn build_router(state: AppState) -> Router {
+    Router::new()
+        .route(
+            "/admin/api_keys",
+            routing::post(issue_api_key_handler).get(list_api_keys_handler),
+        )
+        .route(
+            "/admin/api_keys/{key_id}",
+            routing::delete(revoke_api_key_handler),
+        )
+        .route(
+            "/admin/api_keys/{key_id}/rotate",
+            routing::post(rotate_api_key_handler),
+        )
+        .route(
+            "/admin/entitlements/{entitlement_id}",
+            routing::get(admin_entitlement_handler),
+        )
+        .route(
+            "/admin/buyers/{user_address}",
+            routing::get(admin_buyer_handler),
+        )
+        .route("/admin/invalidate", routing::post(admin_invalidate_handler))
+        .route(
+            "/admin/validations",
+            routing::get(admin_validations_handler),
+        )
+        .route(
+            "/admin/settlements",
+            routing::get(admin_settlements_handler),
+        )
+        // Admin routes take the operator's `API_KEY`; the routes below
+        // also take provider and tenant keys
+        .route_layer(middleware::from_fn(operator_key_auth))
+        // Limited per API key, inside authentication so bad keys cost nothing.
+        // Usage replays are answered before they reach the handler.
+        .route(
+            "/validate",
+            routing::post(validate_entitlements_handler)
+                .layer(middleware::from_fn_with_state(state.clone(), rate_limit)),
+        )
+        .route(
+            "/validate/batch",
+            routing::post(validate_batch_handler)
+                .layer(middleware::from_fn_with_state(state.clone(), rate_limit)),
+        )
+        .route(
+            "/record_usage",
+            routing::post(record_usage_handler)
+                .layer(middleware::from_fn_with_state(state.clone(), idempotency))
+                .layer(middleware::from_fn_with_state(state.clone(), rate_limit)),
+        )
+        .route(
+            "/record_usage/batch",
+            routing::post(record_usage_batch_handler)
+                .layer(middleware::from_fn_with_state(state.clone(), idempotency))
+                .layer(middleware::from_fn_with_state(state.clone(), rate_limit)),
+        )
+        .route("/maintenance", routing::post(create_maintenance_handler))
+        .route(
+            "/maintenance/{service_id}",
+            routing::get(list_maintenance_handler),
+        )
+        .route(
+            "/maintenance/window/{id}",
+            routing::delete(cancel_maintenance_handler),
+        )
+        .route(
+            "/webhooks/buyer",
+            routing::post(register_buyer_webhook_handler),
+        )
+        .route(
+            "/webhooks/buyer/{user_address}",
+            routing::get(list_buyer_webhooks_handler),
+        )
+        .route(
+            "/webhooks/buyer/{user_address}/{id}",
+            routing::delete(delete_buyer_webhook_handler),
+        )
+        .route(
+            "/webhooks/provider",
+            routing::post(register_provider_webhook_handler),
+        )
+        .route(
+            "/webhooks/provider/{provider_id}",
+            routing::get(list_provider_webhooks_handler),
+        )
+        .route(
+            "/webhooks/provider/{provider_id}/{id}",
+            routing::delete(delete_provider_webhook_handler),
+        )
+        .route(
+            "/webhooks/provider/{provider_id}/{id}/deliveries",
+            routing::get(list_webhook_deliveries_handler),
+        )
+        .route(
+            "/webhooks/provider/{provider_id}/{id}/test",
+            routing::post(test_provider_webhook_handler),
+        )
+        .route("/budgets/buyer", routing::put(set_buyer_budget_handler))
+        .route(
+            "/budgets/buyer/{user_address}",
+            routing::get(list_buyer_budgets_handler).delete(delete_buyer_budget_handler),
+        )
+        .route(
+            "/tiers/{tier_id}/sla",
+            routing::put(set_tier_sla_handler).delete(clear_tier_sla_handler),
+        )
+        .route(
+            "/tiers/{tier_id}/replacement",
+            routing::put(set_tier_replacement_handler).delete(clear_tier_replacement_handler),
+        )
+        .route(
+            "/sidecars/heartbeat",
+            routing::post(sidecar_heartbeat_handler),
+        )
+        .route(
+            "/providers/{provider_id}/sidecars",
+            routing::get(list_provider_sidecars_handler),
+        )
+        .route(
+            "/providers/{provider_id}/maintenance",
+            routing::get(list_provider_maintenance_handler),
+        )
+        .route("/scheduler/jobs", routing::get(list_scheduled_jobs_handler))
+        .route(
+            "/services/{service_id}/access",
+            routing::get(service_access_handler),
+        )
+        .route(
+            "/services/{service_id}/usage",
+            routing::get(service_usage_handler),
+        )
+        .route(
+            "/providers/{provider_id}/usage",
+            routing::get(provider_usage_handler),
+        )
+        .route(
+            "/providers/{provider_id}/stats",
+            routing::get(provider_stats_handler),
+        )
+        .route(
+            "/providers/{provider_id}/export/usage",
+            routing::get(export_provider_usage_handler),
+        )
+        .route(
+            "/providers/{provider_id}/export/revenue",
+            routing::get(export_provider_revenue_handler),
+        )
+        .route("/events/stream", routing::get(event_stream_handler))
+        .route_layer(middleware::from_fn_with_state(state.clone(), api_key_auth))
+        // Public, so aggregators and scrapers can poll without an API key
+        .route("/feed", routing::get(catalog_feed_handler))
+        .route("/providers", routing::get(list_providers_handler))
+        .route(
+            "/providers/{provider_id}/services",
+            routing::get(list_provider_services_handler),
+        )
+        .route("/services", routing::get(list_services_handler))
+        .route("/services/search", routing::get(search_services_handler))
+        .route("/tiers", routing::get(list_tiers_handler))
+        .route("/tiers/{tier_id}", routing::get(get_tier_handler))
+        .route(
+            "/buyers/{user_address}/entitlements",
+            routing::get(list_buyer_entitlements_handler),
+        )
+        .route(
+            "/services/{service_id}/entitlements",
+            routing::get(list_service_entitlements_handler),
+        )
+        .route(
+            "/services/{service_id}/tiers",
+            routing::get(list_service_tiers_handler),
+        )
+        .route(
+            "/buyers/{user_address}/spend",
+            routing::get(buyer_spend_handler),
+        )
+        .route(
+            "/providers/{provider_id}/revenue",
+            routing::get(provider_revenue_handler),
+        )
+        // Merged with the protected PUT and DELETE, so buyers can look it up
+        .route(
+            "/tiers/{tier_id}/replacement",
+            routing::get(get_tier_replacement_handler),
+        )
+        .route(
+            "/tiers/{tier_id}/history",
+            routing::get(tier_history_handler),
+        )
+        .route("/metrics", routing::get(metrics_handler))
+        .route("/readyz", routing::get(readyz_handler))
+        // The validator API contract, with Swagger UI at /docs
+        .merge(SwaggerUi::new("/docs").url("/openapi.json", openapi::spec()))
+        // Sign-in-with-Sui, which issues provider session tokens
+        .route("/auth/challenge", routing::post(sign_in_challenge_handler))
+        .route("/auth/login", routing::post(sign_in_handler))
+        // Public, since buyers have no API key; only the buyer can sign it
+        .route(
+            "/entitlements/{entitlement_id}/top_up",
+            routing::post(top_up_entitlement_handler),
+        )
+        // Public, but every request must be signed by the buyer or provider
+        .route("/contacts", routing::post(link_contact_handler))
+        .route(
+            "/contacts/{entitlement_id}",
+            routing::delete(unlink_contact_handler),
+        )
+        .route(
+            "/contacts/provider/{provider_id}",
+            routing::post(list_provider_contacts_handler),
+        )
+        .layer(middleware::from_fn(api_version))
+        .with_state(state)
+}
 
-pub fn build_router(repo: Arc<Repository>) -> Router {
+/// The validator API of the SQLite backend. Catalog listings, reports and
+/// webhooks read Postgres-only tables, so they are not served.
+pub fn build_lite_router(state: LiteState) -> Router {
     Router::new()
         .route("/validate", routing::post(validate_entitlements_handler))
-        .route("/record_usage", routing::post(record_usage_handler))
-        .route_layer(middleware::from_fn(api_key_auth))
-        .with_state(repo)
+        .route("/validate/batch", routing::post(validate_batch_handler))
+        .route(
+            "/record_usage",
+            routing::post(record_usage_handler)
+                .layer(middleware::from_fn_with_state(state.clone(), idempotency)),
+        )
+        .route(
+            "/record_usage/batch",
+            routing::post(record_usage_batch_handler)
+                .layer(middleware::from_fn_with_state(state.clone(), idempotency)),
+        )
+        .route_layer(middleware::from_fn(operator_key_auth))
+        .route("/metrics", routing::get(metrics_handler))
+        .layer(middleware::from_fn(api_version))
+        .with_state(state)
 }
@@ -0,0 +1,451 @@
+use std::{
+    collections::BTreeMap,
+    fmt,
+    panic::AssertUnwindSafe,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::FutureExt;
+use once_cell::sync::Lazy;
+use prometheus::{
+    GaugeVec, HistogramOpts, HistogramVec, IntCounterVec, IntGaugeVec, Opts, Registry, TextEncoder,
+};
+use rand::Rng;
+use serde::Serialize;
+use tokio::task::JoinSet;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, warn};
+
+use crate::db::repository::Repository;
+
+/// A periodic server task hosted by the `Scheduler`
+#[async_trait]
+pub trait Job: Send + Sync {
+    /// Name used in logs, metrics and the status endpoint
+    fn name(&self) -> &'static str;
+
+    /// One pass of the task. An error is logged and kept in the job's
+    /// status; the job still runs again at its next time.
+    async fn run(&self) -> Result<()>;
+}
+
+#[derive(Debug, Clone)]
+pub enum JobSchedule {
+    /// Runs at startup, then every period
+    Every(Duration),
+    /// Runs at the times a cron expression matches, in UTC
+    Cron(cron::Schedule),
+}
+
+impl JobSchedule {
+    /// Parses a cron expression: the usual five fields (minute, hour, day of
+    /// month, month, day of week), optionally preceded by seconds, or a
+    /// shorthand such as `@hourly`
+    pub fn parse(expr: &str) -> Result<Self> {
+        let expr = expr.trim();
+        let full = if !expr.starts_with('@') && expr.split_whitespace().count() == 5 {
+            format!("0 {}", expr)
+        } else {
+            expr.to_string()
+        };
+
+        let schedule = cron::Schedule::from_str(&full)
+            .map_err(|e| anyhow!("invalid cron expression {}: {}", expr, e))?;
+        Ok(JobSchedule::Cron(schedule))
+    }
+
+    /// First time the job is due after `after`
+    fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        match self {
+            JobSchedule::Every(period) => Some(after + chrono::Duration::from_std(*period).ok()?),
+            JobSchedule::Cron(schedule) => schedule.after(&after).next(),
+        }
+    }
+}
+
+impl fmt::Display for JobSchedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JobSchedule::Every(period) => write!(f, "every {}s", period.as_secs()),
+            JobSchedule::Cron(schedule) => write!(f, "{}", schedule),
+        }
+    }
+}
+
+/// Last known state of a job, as served by `GET /scheduler/jobs`
+#[derive(Debug, Clone, Serialize)]
+pub struct JobStatus {
+    pub name: &'static str,
+    pub schedule: String,
+    pub jitter_secs: u64,
+    pub running: bool,
+    pub next_run_at: Option<DateTime<Utc>>,
+    pub last_started_at: Option<DateTime<Utc>>,
+    pub last_finished_at: Option<DateTime<Utc>>,
+    pub last_duration_ms: Option<u64>,
+    pub last_success_at: Option<DateTime<Utc>>,
+    /// Set while the latest run failed
+    pub last_error: Option<String>,
+    pub runs: u64,
+    pub failures: u64,
+    /// Times that passed while the previous run was still going
+    pub skipped: u64,
+}
+
+/// Status of every scheduled job, shared with the HTTP handlers
+#[derive(Default)]
+pub struct JobBoard {
+    jobs: Mutex<BTreeMap<&'static str, JobStatus>>,
+}
+
+impl JobBoard {
+    pub fn snapshot(&self) -> Vec<JobStatus> {
+        self.jobs.lock().unwrap().values().cloned().collect()
+    }
+
+    fn update(&self, name: &'static str, f: impl FnOnce(&mut JobStatus)) {
+        if let Some(status) = self.jobs.lock().unwrap().get_mut(name) {
+            f(status);
+        }
+    }
+}
+
+struct ScheduledJob {
+    job: Arc<dyn Job>,
+    schedule: JobSchedule,
+    jitter: Duration,
+}
+
+/// Runs the server's periodic jobs, each on its own schedule. A job never
+/// overlaps itself: times that pass while it is still running are skipped
+/// and counted. `jitter` delays each run by a random amount up to it, so
+/// several servers on the same schedule don't all start at once.
+#[derive(Default)]
+pub struct Scheduler {
+    jobs: Vec<ScheduledJob>,
+    board: Arc<JobBoard>,
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn board(&self) -> Arc<JobBoard> {
+        self.board.clone()
+    }
+
+    pub fn with_job(
+        mut self,
+        job: impl Job + 'static,
+        schedule: JobSchedule,
+        jitter: Duration,
+    ) -> Self {
+        self.board.jobs.lock().unwrap().insert(
+            job.name(),
+            JobStatus {
+                name: job.name(),
+                schedule: schedule.to_string(),
+                jitter_secs: jitter.as_secs(),
+                running: false,
+                next_run_at: None,
+                last_started_at: None,
+                last_finished_at: None,
+                last_duration_ms: None,
+                last_success_at: None,
+                last_error: None,
+                runs: 0,
+                failures: 0,
+                skipped: 0,
+            },
+        );
+        self.jobs.push(ScheduledJob {
+            job: Arc::new(job),
+            schedule,
+            jitter,
+        });
+        self
+    }
+
+    /// Runs every job until `shutdown` is cancelled. Runs in progress are
+    /// finished, not interrupted.
+    pub async fn run(self, shutdown: CancellationToken) -> Result<()> {
+        info!(jobs = self.jobs.len(), "Scheduler started");
+
+        let mut tasks = JoinSet::new();
+        for scheduled in self.jobs {
+            tasks.spawn(run_job(scheduled, self.board.clone(), shutdown.clone()));
+        }
+        while let Some(result) = tasks.join_next().await {
+            if let Err(e) = result {
+                error!("Scheduled job task failed: {}", e);
+            }
+        }
+
+        info!("Scheduler stopped");
+        Ok(())
+    }
+}
+
+async fn run_job(scheduled: ScheduledJob, board: Arc<JobBoard>, shutdown: CancellationToken) {
+    let ScheduledJob {
+        job,
+        schedule,
+        jitter,
+    } = scheduled;
+    let name = job.name();
+
+    // Interval jobs start right away, as their loops always have
+    let mut due = match schedule {
+        JobSchedule::Every(_) => Some(Utc::now()),
+        JobSchedule::Cron(_) => schedule.next_after(Utc::now()),
+    };
+
+    while let Some(at) = due {
+        let start_at = at + random_jitter(jitter);
+        board.update(name, |s| s.next_run_at = Some(start_at));
+
+        let wait = (start_at - Utc::now()).to_std().unwrap_or_default();
+        tokio::select! {
+            _ = tokio::time::sleep(wait) => {}
+            _ = shutdown.cancelled() => return,
+        }
+
+        run_once(job.as_ref(), &board).await;
+
+        let now = Utc::now();
+        let mut skipped = 0;
+        due = schedule.next_after(at);
+        while let Some(next) = due.filter(|next| *next <= now) {
+            skipped += 1;
+            due = schedule.next_after(next);
+        }
+        if skipped > 0 {
+            warn!(job = name, skipped, "Job ran past its next scheduled time");
+            SCHEDULER_METRICS
+                .skipped
+                .with_label_values(&[name])
+                .inc_by(skipped);
+            board.update(name, |s| s.skipped += skipped);
+        }
+    }
+
+    warn!(job = name, "Job has no more scheduled runs");
+}
+
+async fn run_once(job: &dyn Job, board: &JobBoard) {
+    let name = job.name();
+    let started_at = Utc::now();
+    board.update(name, |s| {
+        s.running = true;
+        s.last_started_at = Some(started_at);
+    });
+    SCHEDULER_METRICS.running.with_label_values(&[name]).set(1);
+    debug!(job = name, "Running scheduled job");
+
+    let timer = Instant::now();
+    // A panicking job is reported like a failed one and keeps its schedule
+    let result = AssertUnwindSafe(job.run())
+        .catch_unwind()
+        .await
+        .unwrap_or_else(|_| Err(anyhow!("job panicked")));
+    let elapsed = timer.elapsed();
+    let finished_at = Utc::now();
+
+    SCHEDULER_METRICS.running.with_label_values(&[name]).set(0);
+    SCHEDULER_METRICS
+        .duration
+        .with_label_values(&[name])
+        .observe(elapsed.as_secs_f64());
+    let outcome = match &result {
+        Ok(()) => {
+            SCHEDULER_METRICS
+                .last_success
+                .with_label_values(&[name])
+                .set(finished_at.timestamp_millis() as f64 / 1000.0);
+            "ok"
+        }
+        Err(e) => {
+            error!(job = name, error = %e, "Scheduled job failed");
+            "error"
+        }
+    };
+    SCHEDULER_METRICS
+        .runs
+        .with_label_values(&[name, outcome])
+        .inc();
+
+    board.update(name, |s| {
+        s.running = false;
+        s.last_finished_at = Some(finished_at);
+        s.last_duration_ms = Some(elapsed.as_millis() as u64);
+        s.runs += 1;
+        match result {
+            Ok(()) => {
+                s.last_success_at = Some(finished_at);
+                s.last_error = None;
+            }
+            Err(e) => {
+                s.failures += 1;
+                s.last_error = Some(e.to_string());
+            }
+        }
+    });
+}
+
+fn random_jitter(jitter: Duration) -> chrono::Duration {
+    let max_ms = jitter.as_millis() as i64;
+    if max_ms == 0 {
+        return chrono::Duration::zero();
+    }
+    chrono::Duration::milliseconds(rand::thread_rng().gen_range(0..=max_ms))
+}
+
+/// Deletes rows older than `retention_days` from the tables `prune` covers
+pub struct PruneJob {
+    repo: Arc<Repository>,
+    retention_days: u32,
+}
+
+impl PruneJob {
+    pub fn new(repo: Arc<Repository>, retention_days: u32) -> Self {
+        Self {
+            repo,
+            retention_days,
+        }
+    }
+}
+
+#[async_trait]
+impl Job for PruneJob {
+    fn name(&self) -> &'static str {
+        "prune"
+    }
+
+    async fn run(&self) -> Result<()> {
+        let cutoff = Utc::now() - chrono::Duration::days(self.retention_days as i64);
+        for (table, deleted) in self.repo.prune_before(cutoff).await? {
+            if deleted > 0 {
+                info!(table, deleted, "Pruned old rows");
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Drops chunks of `blockchain_events` older than `retention_days`. Only the
+/// raw event history goes; the tables projected from it are untouched.
+pub struct EventRetentionJob {
+    repo: Arc<Repository>,
+    retention_days: u32,
+}
+
+impl EventRetentionJob {
+    pub fn new(repo: Arc<Repository>, retention_days: u32) -> Self {
+        Self {
+            repo,
+            retention_days,
+        }
+    }
+}
+
+#[async_trait]
+impl Job for EventRetentionJob {
+    fn name(&self) -> &'static str {
+        "event_retention"
+    }
+
+    async fn run(&self) -> Result<()> {
+        let cutoff = Utc::now() - chrono::Duration::days(self.retention_days as i64);
+        let dropped = self.repo.drop_event_partitions(cutoff).await?;
+        if !dropped.is_empty() {
+            info!(dropped = dropped.len(), %cutoff, "Dropped old event partitions");
+        }
+        Ok(())
+    }
+}
+
+pub struct SchedulerMetrics {
+    pub runs: IntCounterVec,
+    pub skipped: IntCounterVec,
+    pub running: IntGaugeVec,
+    pub duration: HistogramVec,
+    pub last_success: GaugeVec,
+    registry: Registry,
+}
+
+impl SchedulerMetrics {
+    fn new() -> Self {
+        let registry = Registry::new();
+
+        let runs = IntCounterVec::new(
+            Opts::new(
+                "infrapass_job_runs_total",
+                "Scheduled job runs, by job and outcome",
+            ),
+            &["job", "outcome"],
+        )
+        .unwrap();
+        let skipped = IntCounterVec::new(
+            Opts::new(
+                "infrapass_job_skipped_total",
+                "Scheduled times skipped because the job was still running",
+            ),
+            &["job"],
+        )
+        .unwrap();
+        let running = IntGaugeVec::new(
+            Opts::new(
+                "infrapass_job_running",
+                "1 while a scheduled job is running",
+            ),
+            &["job"],
+        )
+        .unwrap();
+        let duration = HistogramVec::new(
+            HistogramOpts::new(
+                "infrapass_job_duration_seconds",
+                "Time a scheduled job run took",
+            )
+            .buckets(vec![0.1, 0.5, 1.0, 5.0, 15.0, 60.0, 300.0, 900.0]),
+            &["job"],
+        )
+        .unwrap();
+        let last_success = GaugeVec::new(
+            Opts::new(
+                "infrapass_job_last_success_timestamp_seconds",
+                "Unix time a scheduled job last finished without error",
+            ),
+            &["job"],
+        )
+        .unwrap();
+
+        registry.register(Box::new(runs.clone())).unwrap();
+        registry.register(Box::new(skipped.clone())).unwrap();
+        registry.register(Box::new(running.clone())).unwrap();
+        registry.register(Box::new(duration.clone())).unwrap();
+        registry.register(Box::new(last_success.clone())).unwrap();
+
+        Self {
+            runs,
+            skipped,
+            running,
+            duration,
+            last_success,
+            registry,
+        }
+    }
+
+    pub fn encode(&self) -> String {
+        let encoder = TextEncoder::new();
+        let families = self.registry.gather();
+        encoder.encode_to_string(&families).unwrap_or_default()
+    }
+}
+
+pub static SCHEDULER_METRICS: Lazy<SchedulerMetrics> = Lazy::new(SchedulerMetrics::new);
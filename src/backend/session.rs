@@ -0,0 +1,122 @@
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Utc};
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use serde::{Deserialize, Serialize};
+
+use crate::utils::error::InfrapassError;
+
+/// How long a sign-in challenge can be answered
+pub const CHALLENGE_TTL_SECS: i64 = 5 * 60;
+
+/// Shortest `SESSION_SECRET` accepted, since it signs every session
+const MIN_SECRET_LEN: usize = 32;
+
+const ISSUER: &str = "infrapass";
+
+/// Personal message a provider signs with its address to sign in
+pub fn sign_in_message(provider_id: &str, nonce: &str, expires_at: DateTime<Utc>) -> String {
+    format!(
+        "infrapass:sign-in:{}:{}:{}",
+        provider_id,
+        nonce,
+        expires_at.timestamp_millis()
+    )
+}
+
+/// What a session token carries
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionClaims {
+    /// The provider's profile ID
+    pub sub: String,
+    /// The address that signed in, the provider's `provider_address` then
+    pub addr: String,
+    /// The provider's tenant, if it has one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tenant_id: Option<String>,
+    pub iss: String,
+    pub iat: i64,
+    pub exp: i64,
+}
+
+/// Issues and checks the short-lived HS256 tokens providers get from
+/// Sign-in-with-Sui. Without a secret, sign-in is disabled and no token is
+/// accepted.
+pub struct SessionSigner {
+    keys: Option<(EncodingKey, DecodingKey)>,
+    ttl: chrono::Duration,
+}
+
+impl SessionSigner {
+    /// `secret` is `SESSION_SECRET`, at least 32 bytes
+    pub fn new(secret: Option<&str>, ttl_secs: u64) -> Result<Self> {
+        let keys = match secret {
+            Some(secret) if secret.len() < MIN_SECRET_LEN => {
+                return Err(anyhow!(
+                    "SESSION_SECRET must be at least {} bytes",
+                    MIN_SECRET_LEN
+                ));
+            }
+            Some(secret) => Some((
+                EncodingKey::from_secret(secret.as_bytes()),
+                DecodingKey::from_secret(secret.as_bytes()),
+            )),
+            None => None,
+        };
+
+        Ok(Self {
+            keys,
+            ttl: chrono::Duration::seconds(ttl_secs as i64),
+        })
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.keys.is_some()
+    }
+
+    fn keys(&self) -> Result<&(EncodingKey, DecodingKey), InfrapassError> {
+        self.keys
+            .as_ref()
+            .ok_or_else(|| InfrapassError::ValidationError("sign-in is not enabled".into()))
+    }
+
+    /// A token for `provider_id`, and when it expires
+    pub fn issue(
+        &self,
+        provider_id: &str,
+        address: &str,
+        tenant_id: Option<String>,
+    ) -> Result<(String, DateTime<Utc>), InfrapassError> {
+        let (encoding, _) = self.keys()?;
+        let now = Utc::now();
+        let expires_at = now + self.ttl;
+        let claims = SessionClaims {
+            sub: provider_id.to_string(),
+            addr: address.to_string(),
+            tenant_id,
+            iss: ISSUER.to_string(),
+            iat: now.timestamp(),
+            exp: expires_at.timestamp(),
+        };
+
+        let token = jsonwebtoken::encode(&Header::new(Algorithm::HS256), &claims, encoding)
+            .map_err(|e| InfrapassError::Other(format!("failed to sign session: {}", e)))?;
+        Ok((token, expires_at))
+    }
+
+    /// The claims of a valid, unexpired token
+    pub fn verify(&self, token: &str) -> Option<SessionClaims> {
+        let (_, decoding) = self.keys.as_ref()?;
+        let mut validation = Validation::new(Algorithm::HS256);
+        validation.set_issuer(&[ISSUER]);
+
+        jsonwebtoken::decode::<SessionClaims>(token, decoding, &validation)
+            .ok()
+            .map(|data| data.claims)
+    }
+}
+
+/// Whether a bearer token is a session rather than an API key, which never
+/// contains a dot
+pub fn is_session_token(token: &str) -> bool {
+    token.split('.').count() == 3
+}
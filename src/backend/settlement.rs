@@ -1,52 +1,134 @@
-use std::{sync::Arc, time::Duration};
-use sui_types::base_types::ObjectID;
+use std::sync::Arc;
+
+use anyhow::Context;
+use async_trait::async_trait;
+use sui_types::base_types::{ObjectID, SuiAddress};
+use tokio::sync::Mutex;
 use tracing::{error, info};
 
-use sui_sdk::SuiClient;
+use sui_sdk::{SuiClient, wallet_context::WalletContext};
 use uuid::Uuid;
 
 use crate::{
+    alerting::manager::AlertManager,
+    backend::{
+        keys::{KeyRole, record_signing_failure},
+        scheduler::Job,
+    },
     client::client_ext::SuiClientExt,
-    db::repository::Repository,
+    db::{
+        models::{AggregatedPending, UnsettledTotals},
+        repository::Repository,
+    },
     transactions::payments::settle_usage_batch_tx,
-    types::settlement::UsageSettlement,
+    types::{amount::Units, settlement::UsageSettlement},
     utils::{
         config::{default_wallet_config, load_wallet_context},
         error::InfrapassError,
     },
 };
 
-pub async fn settlement_worker(
+/// Entitlements settled per transaction unless `SETTLEMENT_BATCH_SIZE` says
+/// otherwise
+pub const DEFAULT_BATCH_SIZE: usize = 100;
+
+/// Keeps a batch's entitlement IDs well under the size limit of a pure
+/// transaction argument
+pub const MAX_BATCH_SIZE: usize = 500;
+
+/// Unsettled usage at which settlement runs ahead of its schedule
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SettlementThresholds {
+    /// Units recorded but not settled, across all entitlements
+    pub units: Option<u64>,
+    /// Entitlements with unsettled usage
+    pub entitlements: Option<i64>,
+}
+
+impl SettlementThresholds {
+    pub fn is_set(&self) -> bool {
+        self.units.is_some() || self.entitlements.is_some()
+    }
+
+    fn is_hit(&self, totals: &UnsettledTotals) -> bool {
+        self.units.is_some_and(|units| totals.amount.get() >= units)
+            || self
+                .entitlements
+                .is_some_and(|entitlements| totals.entitlements >= entitlements)
+    }
+}
+
+/// Settles recorded usage on chain, signed by the relayer key from the
+/// default wallet. Each batch is recorded in `settlements`.
+struct Settler {
     repo: Arc<Repository>,
     client: Arc<SuiClient>,
-    interval_secs: u64,
-) -> Result<(), InfrapassError> {
-    let mut ticker = tokio::time::interval(Duration::from_secs(interval_secs));
-    let default_path = default_wallet_config()?;
-    let mut wallet = load_wallet_context(default_path)?;
-    let sender = wallet.active_address()?;
-
-    loop {
-        ticker.tick().await;
-
-        let pending = match repo.get_unsettled_aggregated().await {
-            Ok(p) => p,
-            Err(e) => {
-                error!("Failed to fetch pending settlements: {}", e);
-                continue;
+    alerts: Arc<AlertManager>,
+    wallet: Mutex<WalletContext>,
+    sender: SuiAddress,
+    batch_size: usize,
+}
+
+impl Settler {
+    /// Settles all unsettled usage, `batch_size` entitlements per
+    /// transaction. The wallet stays locked throughout, so a threshold run
+    /// and a scheduled one never submit the same usage twice. A failed batch
+    /// is logged and left unsettled for the next run while the rest go
+    /// ahead; the run then fails with a summary of every failed batch.
+    async fn settle(&self, trigger: &str) -> anyhow::Result<()> {
+        let mut wallet = self.wallet.lock().await;
+
+        let pending = self
+            .repo
+            .get_unsettled_aggregated()
+            .await
+            .context("failed to fetch pending settlements")?;
+
+        let batches = pending.chunks(self.batch_size);
+        let total = batches.len();
+        let mut failures = vec![];
+        for (i, batch) in batches.enumerate() {
+            if let Err(e) = self.settle_batch(&mut wallet, trigger, batch).await {
+                error!(
+                    trigger,
+                    batch = i + 1,
+                    entitlements = batch.len(),
+                    "Settlement batch failed, continuing with the next: {:#}",
+                    e
+                );
+                failures.push(format!(
+                    "batch {} ({} entitlements): {:#}",
+                    i + 1,
+                    batch.len(),
+                    e
+                ));
             }
-        };
+        }
 
-        if pending.is_empty() {
-            continue;
+        if !failures.is_empty() {
+            anyhow::bail!(
+                "{} of {} settlement batch(es) failed: {}",
+                failures.len(),
+                total,
+                failures.join("; ")
+            );
         }
 
-        let settlements: Vec<UsageSettlement> = pending
+        Ok(())
+    }
+
+    async fn settle_batch(
+        &self,
+        wallet: &mut WalletContext,
+        trigger: &str,
+        batch: &[AggregatedPending],
+    ) -> anyhow::Result<()> {
+        let settlements: Vec<UsageSettlement> = batch
             .iter()
             .filter_map(|p| match ObjectID::from_hex_literal(&p.entitlement_id) {
                 Ok(oid) => Some(UsageSettlement {
                     entitlement_id: sui_types::id::ID::new(oid),
-                    amount: p.total_amount as u64,
+                    amount: p.total_amount.get(),
                 }),
                 Err(e) => {
                     error!("Invalid entitlement_id {}: {}", p.entitlement_id, e);
@@ -56,24 +138,134 @@ pub async fn settlement_worker(
             .collect();
 
         if settlements.is_empty() {
-            continue;
+            return Ok(());
         }
 
-        match settle_usage_batch_tx(&client, sender, settlements).await {
-            Ok(tx_data) => match client.sign_and_execute_tx(tx_data, &mut wallet).await {
-                Ok(digest) => {
-                    info!("Settled batch digest={}", digest);
-                    let ids: Vec<Uuid> = pending
-                        .iter()
-                        .flat_map(|p| p.event_ids.iter().copied())
-                        .collect();
-                    if let Err(e) = repo.mark_settled(&ids).await {
-                        error!("Settled onchain but failed to mark in DB: {}", e);
-                    }
-                }
-                Err(e) => error!("Tx execution failed: {}", e),
-            },
-            Err(e) => error!("Tx build failed: {}", e),
+        let total = settlements
+            .iter()
+            .fold(0u64, |total, s| total.saturating_add(s.amount));
+        let id = self
+            .repo
+            .create_settlement(trigger, settlements.len(), Units::new(total))
+            .await
+            .context("failed to record settlement")?;
+
+        let tx_data = match settle_usage_batch_tx(&self.client, self.sender, settlements).await {
+            Ok(tx_data) => tx_data,
+            Err(e) => {
+                self.record_failure(id, &e.to_string()).await;
+                return Err(e).context("tx build failed");
+            }
+        };
+
+        let digest = match self.client.sign_and_execute_tx(tx_data, wallet).await {
+            Ok(digest) => digest,
+            Err(e) => {
+                record_signing_failure(&self.alerts, KeyRole::Relayer, self.sender, &e.to_string())
+                    .await;
+                self.record_failure(id, &e.to_string()).await;
+                return Err(e).context("tx execution failed");
+            }
+        };
+        info!(settlement_id = %id, trigger, "Settled batch digest={}", digest);
+
+        let ids: Vec<Uuid> = batch
+            .iter()
+            .flat_map(|p| p.event_ids.iter().copied())
+            .collect();
+        self.repo
+            .complete_settlement(id, &digest.to_string(), &ids)
+            .await
+            .context("settled onchain but failed to mark in DB")?;
+
+        Ok(())
+    }
+
+    async fn record_failure(&self, id: Uuid, reason: &str) {
+        if let Err(e) = self.repo.fail_settlement(id, reason).await {
+            error!(settlement_id = %id, "Failed to record failed settlement: {}", e);
+        }
+    }
+}
+
+/// Settles recorded usage on chain on the scheduler's schedule
+pub struct SettlementJob {
+    settler: Arc<Settler>,
+}
+
+impl SettlementJob {
+    pub fn new(
+        repo: Arc<Repository>,
+        client: Arc<SuiClient>,
+        alerts: Arc<AlertManager>,
+        batch_size: usize,
+    ) -> Result<Self, InfrapassError> {
+        let default_path = default_wallet_config()?;
+        let mut wallet = load_wallet_context(default_path)?;
+        let sender = wallet.active_address()?;
+
+        Ok(Self {
+            settler: Arc::new(Settler {
+                repo,
+                client,
+                alerts,
+                wallet: Mutex::new(wallet),
+                sender,
+                batch_size: batch_size.max(1),
+            }),
+        })
+    }
+
+    /// A job that settles as soon as unsettled usage reaches `thresholds`,
+    /// sharing this job's wallet
+    pub fn threshold_job(&self, thresholds: SettlementThresholds) -> SettlementThresholdJob {
+        SettlementThresholdJob {
+            settler: self.settler.clone(),
+            thresholds,
         }
     }
 }
+
+#[async_trait]
+impl Job for SettlementJob {
+    fn name(&self) -> &'static str {
+        "settlement"
+    }
+
+    async fn run(&self) -> anyhow::Result<()> {
+        self.settler.settle("schedule").await
+    }
+}
+
+/// Checks unsettled usage against the settlement thresholds and settles
+/// early when one is reached
+pub struct SettlementThresholdJob {
+    settler: Arc<Settler>,
+    thresholds: SettlementThresholds,
+}
+
+#[async_trait]
+impl Job for SettlementThresholdJob {
+    fn name(&self) -> &'static str {
+        "settlement_threshold"
+    }
+
+    async fn run(&self) -> anyhow::Result<()> {
+        let totals = self
+            .settler
+            .repo
+            .unsettled_totals()
+            .await
+            .context("failed to read unsettled usage")?;
+        if !self.thresholds.is_hit(&totals) {
+            return Ok(());
+        }
+
+        info!(
+            entitlements = totals.entitlements,
+            units = totals.amount.get(),
+            "Unsettled usage reached threshold, settling early"
+        );
+        self.settler.settle("threshold").await
+    }
+}
@@ -0,0 +1,133 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    db::models::{BuyerBudget, SpendRow},
+    types::{amount::MistAmount, coin::CoinType},
+};
+
+/// Longest window a report or budget may cover
+pub const MAX_LOOKBACK_DAYS: i64 = 366 * 5;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SpendBucket {
+    #[default]
+    Day,
+    Week,
+    Month,
+}
+
+impl SpendBucket {
+    /// The `date_trunc` field for this bucket
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SpendBucket::Day => "day",
+            SpendBucket::Week => "week",
+            SpendBucket::Month => "month",
+        }
+    }
+}
+
+/// Parses a lookback such as `30d`, `12h` or `2w`
+pub fn parse_lookback(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+
+    let n: i64 = number
+        .parse()
+        .map_err(|_| format!("invalid lookback {}, expected e.g. 30d", s))?;
+    let hours = match unit {
+        "h" => 1,
+        "d" | "" => 24,
+        "w" => 24 * 7,
+        _ => return Err(format!("invalid lookback unit in {}, use h, d or w", s)),
+    };
+
+    if n == 0 || n > MAX_LOOKBACK_DAYS * 24 / hours {
+        return Err(format!(
+            "lookback must be between 1h and {} days",
+            MAX_LOOKBACK_DAYS
+        ));
+    }
+
+    Ok(Duration::hours(n * hours))
+}
+
+/// The coin type as indexed tiers store it: the coin's `type_name`, without
+/// a 0x prefix. Accepts anything `--coin-type` does.
+pub fn indexed_coin_type(input: &str) -> anyhow::Result<String> {
+    Ok(CoinType::from_str(input)?
+        .to_type_tag()?
+        .to_canonical_string(false))
+}
+
+/// Spend on one service in one coin over the whole report
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpendTotal {
+    pub service_id: String,
+    pub coin_type: String,
+    pub purchases: i64,
+    pub total: MistAmount,
+}
+
+/// Body of `GET /buyers/{user_address}/spend`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpendReport {
+    pub user_address: String,
+    pub since: DateTime<Utc>,
+    pub bucket: SpendBucket,
+    pub totals: Vec<SpendTotal>,
+    pub periods: Vec<SpendRow>,
+}
+
+impl SpendReport {
+    pub fn new(
+        user_address: String,
+        since: DateTime<Utc>,
+        bucket: SpendBucket,
+        periods: Vec<SpendRow>,
+    ) -> Self {
+        let mut totals: BTreeMap<(&str, &str), (i64, u64)> = BTreeMap::new();
+        for row in &periods {
+            let entry = totals
+                .entry((row.service_id.as_str(), row.coin_type.as_str()))
+                .or_default();
+            entry.0 += row.purchases;
+            entry.1 = entry.1.saturating_add(row.total.get());
+        }
+        let totals = totals
+            .into_iter()
+            .map(|((service_id, coin_type), (purchases, total))| SpendTotal {
+                service_id: service_id.to_string(),
+                coin_type: coin_type.to_string(),
+                purchases,
+                total: MistAmount::new(total),
+            })
+            .collect();
+
+        Self {
+            user_address,
+            since,
+            bucket,
+            totals,
+            periods,
+        }
+    }
+}
+
+impl BuyerBudget {
+    /// Start of the budget's rolling window
+    pub fn window_start(&self) -> DateTime<Utc> {
+        Utc::now() - Duration::days(self.period_days as i64)
+    }
+
+    /// Whether a purchase of `price_paid` took `spent`, which includes it,
+    /// over the budget. True only for the purchase that crosses the limit.
+    pub fn crossed_by(&self, spent: MistAmount, price_paid: MistAmount) -> bool {
+        spent > self.amount && spent.saturating_sub(price_paid) <= self.amount
+    }
+}
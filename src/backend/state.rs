@@ -0,0 +1,169 @@
+use std::sync::Arc;
+
+use axum::extract::FromRef;
+use sui_sdk::SuiClient;
+
+use crate::{
+    alerting::manager::AlertManager,
+    backend::{
+        catalog_cache::CatalogCache, contacts::ContactVault, decisions::DecisionLog,
+        feed::CatalogFeed, idempotency::IdempotencyCache, provider_webhooks::WebhookSecrets,
+        rate_limit::RateLimiter, readiness::Readiness, scheduler::JobBoard, session::SessionSigner,
+    },
+    db::{repository::Repository, storage::Storage},
+    events::stream::EventStream,
+    pubsub::publisher::PubSubPublisher,
+};
+
+#[derive(Clone)]
+pub struct AppState {
+    pub repo: Arc<Repository>,
+    /// `repo`, as seen by the handlers the SQLite backend also serves
+    pub storage: Arc<dyn Storage>,
+    pub alerts: Arc<AlertManager>,
+    pub publisher: Arc<PubSubPublisher>,
+    pub feed: Arc<CatalogFeed>,
+    pub catalog: Arc<CatalogCache>,
+    pub contacts: Arc<ContactVault>,
+    pub webhook_secrets: Arc<WebhookSecrets>,
+    pub jobs: Arc<JobBoard>,
+    /// Unset when `/validate` and `/record_usage` are not rate limited
+    pub rate_limiter: Option<Arc<RateLimiter>>,
+    pub idempotency: Arc<IdempotencyCache>,
+    pub decisions: Arc<DecisionLog>,
+    pub events: Arc<EventStream>,
+    pub readiness: Arc<Readiness>,
+    pub sessions: Arc<SessionSigner>,
+    /// Builds the transactions handed to buyers to sign
+    pub sui: Arc<SuiClient>,
+}
+
+impl FromRef<AppState> for Arc<Repository> {
+    fn from_ref(state: &AppState) -> Self {
+        state.repo.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<dyn Storage> {
+    fn from_ref(state: &AppState) -> Self {
+        state.storage.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<AlertManager> {
+    fn from_ref(state: &AppState) -> Self {
+        state.alerts.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<PubSubPublisher> {
+    fn from_ref(state: &AppState) -> Self {
+        state.publisher.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<CatalogFeed> {
+    fn from_ref(state: &AppState) -> Self {
+        state.feed.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<CatalogCache> {
+    fn from_ref(state: &AppState) -> Self {
+        state.catalog.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<ContactVault> {
+    fn from_ref(state: &AppState) -> Self {
+        state.contacts.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<SessionSigner> {
+    fn from_ref(state: &AppState) -> Self {
+        state.sessions.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<WebhookSecrets> {
+    fn from_ref(state: &AppState) -> Self {
+        state.webhook_secrets.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<JobBoard> {
+    fn from_ref(state: &AppState) -> Self {
+        state.jobs.clone()
+    }
+}
+
+impl FromRef<AppState> for Option<Arc<RateLimiter>> {
+    fn from_ref(state: &AppState) -> Self {
+        state.rate_limiter.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<IdempotencyCache> {
+    fn from_ref(state: &AppState) -> Self {
+        state.idempotency.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<DecisionLog> {
+    fn from_ref(state: &AppState) -> Self {
+        state.decisions.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<EventStream> {
+    fn from_ref(state: &AppState) -> Self {
+        state.events.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<SuiClient> {
+    fn from_ref(state: &AppState) -> Self {
+        state.sui.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<Readiness> {
+    fn from_ref(state: &AppState) -> Self {
+        state.readiness.clone()
+    }
+}
+
+/// State of the SQLite backend's router, which serves only `/validate` and
+/// `/record_usage`
+#[derive(Clone)]
+pub struct LiteState {
+    pub storage: Arc<dyn Storage>,
+    pub alerts: Arc<AlertManager>,
+    pub decisions: Arc<DecisionLog>,
+    pub idempotency: Arc<IdempotencyCache>,
+}
+
+impl FromRef<LiteState> for Arc<dyn Storage> {
+    fn from_ref(state: &LiteState) -> Self {
+        state.storage.clone()
+    }
+}
+
+impl FromRef<LiteState> for Arc<AlertManager> {
+    fn from_ref(state: &LiteState) -> Self {
+        state.alerts.clone()
+    }
+}
+
+impl FromRef<LiteState> for Arc<DecisionLog> {
+    fn from_ref(state: &LiteState) -> Self {
+        state.decisions.clone()
+    }
+}
+
+impl FromRef<LiteState> for Arc<IdempotencyCache> {
+    fn from_ref(state: &LiteState) -> Self {
+        state.idempotency.clone()
+    }
+}
@@ -0,0 +1,70 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    backend::{
+        revenue::{CoinRevenue, coin_totals},
+        spend::SpendBucket,
+    },
+    db::models::{BuyerUsage, RevenueRow, UsageDay},
+};
+
+/// Purchases of a provider's services in one period, across services and
+/// coins
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PurchasePeriod {
+    pub period: DateTime<Utc>,
+    pub purchases: i64,
+}
+
+/// Body of `GET /providers/{provider_id}/stats`, the figures behind a
+/// provider dashboard. Purchases and revenue come from indexed purchases,
+/// usage and top buyers from the daily usage rollup.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProviderStats {
+    pub provider_id: String,
+    pub since: DateTime<Utc>,
+    pub bucket: SpendBucket,
+    /// Unexpired entitlements, and usage-based ones with units left
+    pub active_entitlements: i64,
+    pub purchases: Vec<PurchasePeriod>,
+    /// Amounts are in each coin's base units
+    pub revenue: Vec<CoinRevenue>,
+    /// Usage per UTC day across the provider's services
+    pub usage: Vec<UsageDay>,
+    /// Heaviest buyers over the whole window, by amount used
+    pub top_buyers: Vec<BuyerUsage>,
+}
+
+impl ProviderStats {
+    pub fn new(
+        provider_id: String,
+        since: DateTime<Utc>,
+        bucket: SpendBucket,
+        active_entitlements: i64,
+        revenue: Vec<RevenueRow>,
+        usage: Vec<UsageDay>,
+        top_buyers: Vec<BuyerUsage>,
+    ) -> Self {
+        let mut purchases: BTreeMap<DateTime<Utc>, i64> = BTreeMap::new();
+        for row in &revenue {
+            *purchases.entry(row.period).or_default() += row.purchases;
+        }
+
+        Self {
+            provider_id,
+            since,
+            bucket,
+            active_entitlements,
+            purchases: purchases
+                .into_iter()
+                .map(|(period, purchases)| PurchasePeriod { period, purchases })
+                .collect(),
+            revenue: coin_totals(&revenue),
+            usage,
+            top_buyers,
+        }
+    }
+}
@@ -0,0 +1,59 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::{
+    backend::scheduler::Job,
+    db::{
+        models::{BuyerUsage, ServiceUsage, UsageDay},
+        repository::Repository,
+    },
+};
+
+/// Keeps `usage_daily` current, so usage reports read the rollup instead of
+/// every recorded usage event
+pub struct UsageRollupJob {
+    repo: Arc<Repository>,
+}
+
+impl UsageRollupJob {
+    pub fn new(repo: Arc<Repository>) -> Self {
+        Self { repo }
+    }
+}
+
+#[async_trait]
+impl Job for UsageRollupJob {
+    fn name(&self) -> &'static str {
+        "usage_rollup"
+    }
+
+    async fn run(&self) -> Result<()> {
+        let rows = self.repo.rollup_usage().await?;
+        debug!(rows, "Rolled up usage");
+        Ok(())
+    }
+}
+
+/// Body of `GET /services/{service_id}/usage`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageReport {
+    pub service_id: String,
+    /// First UTC day covered
+    pub since: NaiveDate,
+    pub days: Vec<UsageDay>,
+    /// Heaviest buyers over the whole report
+    pub top_buyers: Vec<BuyerUsage>,
+}
+
+/// Body of `GET /providers/{provider_id}/usage`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProviderUsageReport {
+    pub provider_id: String,
+    pub since: NaiveDate,
+    pub services: Vec<ServiceUsage>,
+}
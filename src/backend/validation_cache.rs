@@ -0,0 +1,285 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::StreamExt;
+use redis::{AsyncCommands, Client as RedisClient, aio::MultiplexedConnection};
+use serde::Deserialize;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+
+use crate::{
+    api_types::validator::{RecordUsageRequest, ValidateResponse},
+    db::{models::UsageCommit, repository::Repository, storage::Storage},
+    pubsub::types::{PubSubAction, PubSubEvent},
+    types::amount::Units,
+    utils::error::InfrapassError,
+};
+
+/// Every provider's sidecar channel, see `get_channel`
+const EVENT_CHANNELS: &str = "infrapass:*:events";
+
+#[derive(Deserialize)]
+struct CachedValidation {
+    response: ValidateResponse,
+    cached_at: DateTime<Utc>,
+}
+
+/// `Repository` with `/validate` answers cached in Redis, one hash per buyer
+/// keyed by service. Entries are dropped by the purchase and settlement
+/// messages the worker publishes to sidecars, and by usage recorded through
+/// this backend; anything else, such as a tier replacement, shows after at
+/// most `ttl_secs`. Denials are not cached, and an entry is only served while
+/// it covers the request's cost. Redis errors fall back to Postgres.
+pub struct CachedStorage {
+    repo: Arc<Repository>,
+    redis_client: RedisClient,
+    redis: MultiplexedConnection,
+    ttl_secs: u64,
+}
+
+impl CachedStorage {
+    pub async fn new(
+        repo: Arc<Repository>,
+        redis_client: RedisClient,
+        ttl_secs: u64,
+    ) -> Result<Self> {
+        let redis = redis_client.get_multiplexed_async_connection().await?;
+        Ok(Self {
+            repo,
+            redis_client,
+            redis,
+            ttl_secs,
+        })
+    }
+
+    /// Drops the entries named by sidecar messages until `shutdown` is
+    /// cancelled. A message missed while this reconnects is covered by the TTL.
+    pub async fn run_invalidator(&self, shutdown: CancellationToken) -> Result<()> {
+        let mut pubsub = self.redis_client.get_async_pubsub().await?;
+        pubsub.psubscribe(EVENT_CHANNELS).await?;
+        info!("Validation cache invalidator started");
+
+        let mut stream = pubsub.on_message();
+        loop {
+            let msg = tokio::select! {
+                msg = stream.next() => msg,
+                _ = shutdown.cancelled() => break,
+            };
+            let Some(msg) = msg else {
+                anyhow::bail!("Redis pub/sub connection closed");
+            };
+
+            let event: PubSubEvent = match serde_json::from_str(&msg.get_payload::<String>()?) {
+                Ok(event) => event,
+                Err(e) => {
+                    warn!("Ignoring undecodable pub/sub message: {}", e);
+                    continue;
+                }
+            };
+            match event.action {
+                PubSubAction::Invalidate
+                | PubSubAction::Refresh(_)
+                | PubSubAction::ReconcileQuota(_) => {
+                    self.invalidate(&event.user, Some(&event.service)).await;
+                }
+                PubSubAction::Maintenance(_) | PubSubAction::MaintenanceCancelled(_) => {}
+            }
+        }
+
+        info!("Validation cache invalidator stopped");
+        Ok(())
+    }
+
+    async fn lookup(&self, user_address: &str, service_id: &str) -> Option<CachedValidation> {
+        let mut conn = self.redis.clone();
+        let cached = match conn
+            .hget::<_, _, Option<String>>(cache_key(user_address), service_id)
+            .await
+        {
+            Ok(cached) => cached,
+            Err(e) => {
+                warn!("Validation cache read failed: {}", e);
+                return None;
+            }
+        };
+
+        cached.and_then(|json| serde_json::from_str(&json).ok())
+    }
+
+    async fn store(&self, user_address: &str, service_id: &str, response: &ValidateResponse) {
+        let json = serde_json::json!({ "response": response, "cached_at": Utc::now() }).to_string();
+
+        let key = cache_key(user_address);
+        let mut conn = self.redis.clone();
+        let result: redis::RedisResult<()> = redis::pipe()
+            .hset(&key, service_id, json)
+            .ignore()
+            .expire(&key, self.ttl_secs as i64)
+            .ignore()
+            .query_async(&mut conn)
+            .await;
+        if let Err(e) = result {
+            warn!("Validation cache write failed: {}", e);
+        }
+    }
+
+    /// Drops one service's entry, or all of the buyer's without `service_id`
+    async fn invalidate(&self, user_address: &str, service_id: Option<&str>) {
+        let key = cache_key(user_address);
+        let mut conn = self.redis.clone();
+        let result: redis::RedisResult<()> = match service_id {
+            Some(service_id) => conn.hdel(&key, service_id).await,
+            None => conn.del(&key).await,
+        };
+        if let Err(e) = result {
+            warn!("Validation cache invalidation failed: {}", e);
+        }
+    }
+
+    /// Whether a cached entry is fresh and still covers `cost`, by the same
+    /// rule as the query; anything borderline goes back to Postgres
+    fn covers(&self, entry: &CachedValidation, cost: u64) -> bool {
+        let now = Utc::now();
+        if (now - entry.cached_at).num_seconds() >= self.ttl_secs as i64 {
+            return false;
+        }
+
+        let r = &entry.response;
+        if r.expires_at.is_some_and(|exp| exp <= now) {
+            return false;
+        }
+        match r.tier_type {
+            1 => r.quota.is_some_and(|q| q > cost),
+            2 => r.units.is_some_and(|u| u > cost),
+            _ => true,
+        }
+    }
+}
+
+fn cache_key(user_address: &str) -> String {
+    format!("infrapass:validate:{user_address}")
+}
+
+/// Drops the `?detail=full` fields unless they were asked for
+fn with_detail(mut response: ValidateResponse, include_detail: bool) -> ValidateResponse {
+    if !include_detail {
+        response.tier_name = None;
+        response.price = None;
+        response.coin_type = None;
+    }
+    response
+}
+
+#[async_trait]
+impl Storage for CachedStorage {
+    async fn get_checkpoint_cursor(&self, name: &str) -> Result<Option<u64>> {
+        self.repo.get_checkpoint_cursor(name).await
+    }
+
+    async fn save_checkpoint_cursor(&self, name: &str, checkpoint: u64) -> Result<()> {
+        self.repo.save_checkpoint_cursor(name, checkpoint).await
+    }
+
+    async fn store_failed_event(
+        &self,
+        checkpoint: u64,
+        tx_digest: Option<&str>,
+        event_index: u64,
+        event_type: &str,
+        package_id: Option<&str>,
+        protocol_version: u64,
+        bcs: &[u8],
+        error: &str,
+    ) -> Result<()> {
+        self.repo
+            .store_failed_event(
+                checkpoint,
+                tx_digest,
+                event_index,
+                event_type,
+                package_id,
+                protocol_version,
+                bcs,
+                error,
+            )
+            .await
+    }
+
+    async fn get_valid_entitlement_response(
+        &self,
+        user_address: &str,
+        service_id: &str,
+        cost: u64,
+        include_detail: bool,
+    ) -> Result<Option<ValidateResponse>, InfrapassError> {
+        if let Some(entry) = self.lookup(user_address, service_id).await {
+            if self.covers(&entry, cost) {
+                debug!(user = %user_address, service = %service_id, "Validation cache hit");
+                return Ok(Some(with_detail(entry.response, include_detail)));
+            }
+        }
+
+        // Always fetched with detail, so one entry serves both forms
+        let Some(response) = self
+            .repo
+            .get_valid_entitlement_response(user_address, service_id, cost, true)
+            .await?
+        else {
+            return Ok(None);
+        };
+
+        self.store(user_address, service_id, &response).await;
+
+        Ok(Some(with_detail(response, include_detail)))
+    }
+
+    async fn commit_usage(
+        &self,
+        entitlement_id: &str,
+        user_address: &str,
+        cost: Units,
+        request_id: Option<&str>,
+    ) -> Result<UsageCommit, InfrapassError> {
+        let commit = self
+            .repo
+            .commit_usage(entitlement_id, user_address, cost, request_id)
+            .await?;
+        // The request doesn't name the service, so all of the buyer's go
+        if commit == UsageCommit::Recorded {
+            self.invalidate(user_address, None).await;
+        }
+
+        Ok(commit)
+    }
+
+    async fn commit_usage_batch(
+        &self,
+        records: &[RecordUsageRequest],
+    ) -> Result<Vec<Result<UsageCommit, InfrapassError>>, InfrapassError> {
+        let results = self.repo.commit_usage_batch(records).await?;
+
+        let mut charged: Vec<&str> = records
+            .iter()
+            .zip(&results)
+            .filter(|(_, r)| matches!(r, Ok(UsageCommit::Recorded)))
+            .map(|(record, _)| record.user_address.as_str())
+            .collect();
+        charged.sort_unstable();
+        charged.dedup();
+        for user_address in charged {
+            self.invalidate(user_address, None).await;
+        }
+
+        Ok(results)
+    }
+
+    async fn service_provider(&self, service_id: &str) -> Result<Option<String>> {
+        self.repo.service_provider(service_id).await
+    }
+
+    async fn entitlement_provider(&self, entitlement_id: &str) -> Result<Option<String>> {
+        self.repo.entitlement_provider(entitlement_id).await
+    }
+}
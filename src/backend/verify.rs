@@ -0,0 +1,504 @@
+use std::{collections::HashSet, fmt};
+
+use anyhow::{Result, anyhow};
+use serde_json::Value;
+use sui_json_rpc_types::{SuiData, SuiObjectDataOptions};
+use sui_sdk::SuiClient;
+use sui_types::{base_types::ObjectID, parse_sui_struct_tag};
+use tracing::info;
+
+use crate::{
+    db::repository::Repository,
+    types::{
+        amount::{MistAmount, Units},
+        types::TierConfigInput,
+    },
+};
+
+/// A service as currently stored on-chain
+#[derive(Debug, Clone)]
+pub struct OnchainService {
+    pub service_id: String,
+    pub service_type: String,
+    pub metadata_uri: String,
+    pub active: bool,
+    pub tier_ids: Vec<ObjectID>,
+}
+
+/// A pricing tier as currently stored on-chain
+#[derive(Debug, Clone)]
+pub struct OnchainTier {
+    pub tier_id: String,
+    pub service_id: String,
+    pub tier_name: String,
+    pub price: u64,
+    pub coin_type: String,
+    pub inner: TierConfigInput,
+    pub active: bool,
+}
+
+/// A difference between on-chain state and the Postgres projection
+#[derive(Debug, Clone)]
+pub enum Mismatch {
+    ProviderMissing {
+        provider_id: String,
+    },
+    ServiceMissing(OnchainService),
+    ServiceNotOnchain {
+        service_id: String,
+    },
+    ServiceMetadata {
+        service_id: String,
+        chain: String,
+        db: Option<String>,
+    },
+    ServiceActive {
+        service_id: String,
+        chain: bool,
+        db: Option<bool>,
+    },
+    TierMissing(OnchainTier),
+    TierNotOnchain {
+        tier_id: String,
+        service_id: String,
+    },
+    TierNotListed {
+        tier_id: String,
+        service_id: String,
+    },
+    TierPrice {
+        tier_id: String,
+        chain: u64,
+        db: u64,
+    },
+    TierActive {
+        tier_id: String,
+        chain: bool,
+        db: Option<bool>,
+    },
+}
+
+impl Mismatch {
+    /// Whether `repair` can fix this mismatch from on-chain state alone
+    pub fn is_repairable(&self) -> bool {
+        !matches!(
+            self,
+            Mismatch::ProviderMissing { .. } | Mismatch::ServiceNotOnchain { .. }
+        )
+    }
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Mismatch::ProviderMissing { provider_id } => {
+                write!(f, "provider {} is not indexed", provider_id)
+            }
+            Mismatch::ServiceMissing(s) => write!(f, "service {} is not indexed", s.service_id),
+            Mismatch::ServiceNotOnchain { service_id } => {
+                write!(
+                    f,
+                    "service {} is indexed but not listed on-chain",
+                    service_id
+                )
+            }
+            Mismatch::ServiceMetadata {
+                service_id,
+                chain,
+                db,
+            } => write!(
+                f,
+                "service {} metadata_uri: chain={} db={}",
+                service_id,
+                chain,
+                db.as_deref().unwrap_or("<none>")
+            ),
+            Mismatch::ServiceActive {
+                service_id,
+                chain,
+                db,
+            } => {
+                write!(
+                    f,
+                    "service {} active: chain={} db={:?}",
+                    service_id, chain, db
+                )
+            }
+            Mismatch::TierMissing(t) => write!(
+                f,
+                "tier {} ({}) of service {} is not indexed",
+                t.tier_id, t.tier_name, t.service_id
+            ),
+            Mismatch::TierNotOnchain {
+                tier_id,
+                service_id,
+            } => write!(
+                f,
+                "tier {} is indexed as listed by service {} but not listed on-chain",
+                tier_id, service_id
+            ),
+            Mismatch::TierNotListed {
+                tier_id,
+                service_id,
+            } => write!(
+                f,
+                "tier {} is listed by service {} on-chain but not in the index",
+                tier_id, service_id
+            ),
+            Mismatch::TierPrice { tier_id, chain, db } => {
+                write!(f, "tier {} price: chain={} db={}", tier_id, chain, db)
+            }
+            Mismatch::TierActive { tier_id, chain, db } => {
+                write!(f, "tier {} active: chain={} db={:?}", tier_id, chain, db)
+            }
+        }
+    }
+}
+
+/// Reads the provider's services and tiers from chain and diffs them against
+/// the indexed rows in Postgres
+pub async fn verify_provider(
+    client: &SuiClient,
+    repo: &Repository,
+    provider_id: ObjectID,
+) -> Result<Vec<Mismatch>> {
+    let provider_key = provider_id.to_string();
+    let mut mismatches = vec![];
+
+    if repo.get_provider(&provider_key).await?.is_none() {
+        mismatches.push(Mismatch::ProviderMissing {
+            provider_id: provider_key.clone(),
+        });
+    }
+
+    let profile = object_fields(client, provider_id).await?;
+    let service_ids = vec_set_ids(&profile, "service_ids");
+    info!(
+        provider_id = %provider_key,
+        services = service_ids.len(),
+        "Verifying provider against on-chain state"
+    );
+
+    let mut onchain_services = HashSet::new();
+    for service_id in service_ids {
+        let service = read_service(client, service_id).await?;
+        onchain_services.insert(service.service_id.clone());
+        verify_service(client, repo, service, &mut mismatches).await?;
+    }
+
+    for service in repo.list_services_by_provider(&provider_key).await? {
+        if !onchain_services.contains(&service.service_id) {
+            mismatches.push(Mismatch::ServiceNotOnchain {
+                service_id: service.service_id,
+            });
+        }
+    }
+
+    Ok(mismatches)
+}
+
+async fn verify_service(
+    client: &SuiClient,
+    repo: &Repository,
+    service: OnchainService,
+    mismatches: &mut Vec<Mismatch>,
+) -> Result<()> {
+    let service_id = service.service_id.clone();
+    let tier_ids = service.tier_ids.clone();
+
+    match repo.get_service(&service_id).await? {
+        None => mismatches.push(Mismatch::ServiceMissing(service)),
+        Some(db) => {
+            if db.metadata_uri.as_deref() != Some(service.metadata_uri.as_str()) {
+                mismatches.push(Mismatch::ServiceMetadata {
+                    service_id: service_id.clone(),
+                    chain: service.metadata_uri.clone(),
+                    db: db.metadata_uri.clone(),
+                });
+            }
+            if db.is_active != Some(service.active) {
+                mismatches.push(Mismatch::ServiceActive {
+                    service_id: service_id.clone(),
+                    chain: service.active,
+                    db: db.is_active,
+                });
+            }
+        }
+    }
+
+    let indexed_tiers: HashSet<String> = repo
+        .list_service_tier_ids(&service_id)
+        .await?
+        .into_iter()
+        .collect();
+
+    let mut onchain_tiers = HashSet::new();
+    for tier_id in tier_ids {
+        let tier = read_tier(client, tier_id).await?;
+        let tier_id = tier.tier_id.clone();
+        onchain_tiers.insert(tier_id.clone());
+
+        match repo.get_tier(&tier.tier_id).await? {
+            None => mismatches.push(Mismatch::TierMissing(tier)),
+            Some(db) => {
+                if db.price.get() != tier.price {
+                    mismatches.push(Mismatch::TierPrice {
+                        tier_id: tier.tier_id.clone(),
+                        chain: tier.price,
+                        db: db.price.get(),
+                    });
+                }
+                if db.is_active != Some(tier.active) {
+                    mismatches.push(Mismatch::TierActive {
+                        tier_id: tier.tier_id.clone(),
+                        chain: tier.active,
+                        db: db.is_active,
+                    });
+                }
+            }
+        }
+
+        if !indexed_tiers.contains(&tier_id) {
+            mismatches.push(Mismatch::TierNotListed {
+                tier_id,
+                service_id: service_id.clone(),
+            });
+        }
+    }
+
+    for tier_id in indexed_tiers {
+        if !onchain_tiers.contains(&tier_id) {
+            mismatches.push(Mismatch::TierNotOnchain {
+                tier_id,
+                service_id: service_id.clone(),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// Rewrites the Postgres projection from the on-chain values carried by each
+/// mismatch. Returns the number of mismatches repaired.
+pub async fn repair(
+    repo: &Repository,
+    provider_id: &str,
+    mismatches: &[Mismatch],
+) -> Result<usize> {
+    let mut repaired = 0;
+
+    for mismatch in mismatches {
+        let mut tx = repo.begin().await?;
+
+        match mismatch {
+            Mismatch::ServiceMissing(s) => {
+                repo.create_service(
+                    &mut tx,
+                    &s.service_id,
+                    provider_id,
+                    &s.service_type,
+                    Some(s.metadata_uri.clone()),
+                )
+                .await?;
+                repo.set_service_active(&mut tx, &s.service_id, s.active)
+                    .await?;
+            }
+            Mismatch::ServiceMetadata {
+                service_id, chain, ..
+            } => {
+                repo.update_service_metadata(&mut tx, service_id, chain)
+                    .await?;
+            }
+            Mismatch::ServiceActive {
+                service_id, chain, ..
+            } => {
+                repo.set_service_active(&mut tx, service_id, *chain).await?;
+            }
+            Mismatch::TierMissing(t) => {
+                repo.create_tier(
+                    &mut tx,
+                    &t.tier_id,
+                    &t.service_id,
+                    &t.tier_name,
+                    MistAmount::new(t.price),
+                    &t.coin_type,
+                    t.inner.as_tier_type(),
+                    t.inner.duration().map(|d| d as i64),
+                    t.inner.quota().map(Units::new),
+                )
+                .await?;
+                if !t.active {
+                    repo.deactivate_tier(&mut tx, &t.tier_id).await?;
+                }
+            }
+            Mismatch::TierPrice { tier_id, chain, .. } => {
+                repo.update_tier_price(&mut tx, tier_id, MistAmount::new(*chain))
+                    .await?;
+            }
+            Mismatch::TierActive { tier_id, chain, .. } => {
+                if *chain {
+                    repo.reactivate_tier(&mut tx, tier_id).await?;
+                } else {
+                    repo.deactivate_tier(&mut tx, tier_id).await?;
+                }
+            }
+            Mismatch::TierNotOnchain {
+                tier_id,
+                service_id,
+            } => {
+                repo.remove_service_tier(&mut tx, service_id, tier_id)
+                    .await?;
+            }
+            Mismatch::TierNotListed {
+                tier_id,
+                service_id,
+            } => {
+                repo.add_service_tier(&mut tx, service_id, tier_id).await?;
+            }
+            Mismatch::ProviderMissing { .. } | Mismatch::ServiceNotOnchain { .. } => continue,
+        }
+        tx.commit().await?;
+
+        info!(mismatch = %mismatch, "Repaired");
+        repaired += 1;
+    }
+
+    Ok(repaired)
+}
+
+/// Reads a service from chain
+pub(crate) async fn read_service(
+    client: &SuiClient,
+    service_id: ObjectID,
+) -> Result<OnchainService> {
+    let fields = object_fields(client, service_id).await?;
+
+    Ok(OnchainService {
+        service_id: service_id.to_string(),
+        service_type: string_field(&fields, "service_type")?,
+        metadata_uri: string_field(&fields, "metadata_uri")?,
+        active: bool_field(&fields, "active")?,
+        tier_ids: vec_set_ids(&fields, "pricing_tier_ids"),
+    })
+}
+
+async fn read_tier(client: &SuiClient, tier_id: ObjectID) -> Result<OnchainTier> {
+    find_tier(client, tier_id)
+        .await?
+        .ok_or_else(|| anyhow!("Tier object {} not found", tier_id))
+}
+
+/// Reads a tier from chain, `None` when the object doesn't exist
+pub(crate) async fn find_tier(
+    client: &SuiClient,
+    tier_id: ObjectID,
+) -> Result<Option<OnchainTier>> {
+    let obj = client
+        .read_api()
+        .get_object_with_options(
+            tier_id,
+            SuiObjectDataOptions::new().with_type().with_content(),
+        )
+        .await?;
+
+    let Some(data) = obj.data else {
+        return Ok(None);
+    };
+    let tier_type = data
+        .type_
+        .ok_or_else(|| anyhow!("Could not get type of tier {}", tier_id))?
+        .to_string();
+    let fields = data
+        .content
+        .and_then(|c| c.try_into_move())
+        .map(|obj| obj.fields.to_json_value())
+        .ok_or_else(|| anyhow!("No content in tier {}", tier_id))?;
+
+    // Indexed tiers carry the coin's `type_name`, which has no 0x prefix
+    let coin_type = parse_sui_struct_tag(&tier_type)?
+        .type_params
+        .into_iter()
+        .next()
+        .ok_or_else(|| anyhow!("Unknown coin type in tier: {}", tier_type))?
+        .to_canonical_string(false);
+
+    Ok(Some(OnchainTier {
+        tier_id: tier_id.to_string(),
+        service_id: string_field(&fields, "service_id")?,
+        tier_name: string_field(&fields, "tier_name")?,
+        price: u64_field(&fields, "price")?,
+        coin_type,
+        inner: tier_config(&fields)?,
+        active: bool_field(&fields, "active")?,
+    }))
+}
+
+async fn object_fields(client: &SuiClient, object_id: ObjectID) -> Result<Value> {
+    let obj = client
+        .read_api()
+        .get_object_with_options(object_id, SuiObjectDataOptions::new().with_content())
+        .await?;
+
+    obj.data
+        .and_then(|d| d.content)
+        .and_then(|c| c.try_into_move())
+        .map(|obj| obj.fields.to_json_value())
+        .ok_or_else(|| anyhow!("Object {} not found", object_id))
+}
+
+/// Parses the `inner: TierConfig` enum, rendered as `{ variant, fields }`
+fn tier_config(fields: &Value) -> Result<TierConfigInput> {
+    let inner = fields
+        .get("inner")
+        .ok_or_else(|| anyhow!("Tier has no config"))?;
+    let variant = inner
+        .get("variant")
+        .and_then(|v| v.as_str())
+        .unwrap_or_default();
+    let values = inner.get("fields").cloned().unwrap_or(Value::Null);
+
+    match variant {
+        "Subscription" => Ok(TierConfigInput::Subscription {
+            expires_at: u64_field(&values, "duration_ms")?,
+        }),
+        "Quota" => Ok(TierConfigInput::Quota {
+            quota_limit: u64_field(&values, "quota_limit")?,
+            expires_at: u64_field(&values, "duration_ms")?,
+        }),
+        "UsageBased" => Ok(TierConfigInput::UsageBased {}),
+        other => Err(anyhow!("Unknown tier config variant: {}", other)),
+    }
+}
+
+fn vec_set_ids(fields: &Value, name: &str) -> Vec<ObjectID> {
+    fields
+        .get(name)
+        .and_then(|set| set.get("contents"))
+        .and_then(|v| v.as_array())
+        .map(|contents| {
+            contents
+                .iter()
+                .filter_map(|id| id.as_str().and_then(|s| ObjectID::from_hex_literal(s).ok()))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn string_field(fields: &Value, name: &str) -> Result<String> {
+    fields
+        .get(name)
+        .and_then(|v| v.as_str())
+        .map(str::to_string)
+        .ok_or_else(|| anyhow!("Missing field {}", name))
+}
+
+fn u64_field(fields: &Value, name: &str) -> Result<u64> {
+    Ok(string_field(fields, name)?.parse()?)
+}
+
+fn bool_field(fields: &Value, name: &str) -> Result<bool> {
+    fields
+        .get(name)
+        .and_then(|v| v.as_bool())
+        .ok_or_else(|| anyhow!("Missing field {}", name))
+}
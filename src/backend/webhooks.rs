@@ -0,0 +1,282 @@
+use std::sync::Arc;
+
+use anyhow::Context;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use tracing::{error, info, warn};
+
+use crate::{
+    backend::scheduler::Job,
+    db::{models::Entitlement, repository::Repository},
+    utils::webhook::post_signed,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BuyerEvent {
+    PurchaseConfirmed,
+    ExpiryApproaching,
+    QuotaThreshold,
+    BudgetExceeded,
+}
+
+impl BuyerEvent {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BuyerEvent::PurchaseConfirmed => "purchase_confirmed",
+            BuyerEvent::ExpiryApproaching => "expiry_approaching",
+            BuyerEvent::QuotaThreshold => "quota_threshold",
+            BuyerEvent::BudgetExceeded => "budget_exceeded",
+        }
+    }
+}
+
+/// Body posted to a buyer's webhook, signed with that webhook's own secret
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BuyerNotification {
+    pub event: BuyerEvent,
+    pub user_address: String,
+    pub entitlement_id: String,
+    pub service_id: String,
+    pub detail: serde_json::Value,
+}
+
+impl BuyerNotification {
+    pub fn new(event: BuyerEvent, entitlement: &Entitlement, detail: serde_json::Value) -> Self {
+        Self {
+            event,
+            user_address: entitlement.buyer.clone(),
+            entitlement_id: entitlement.entitlement_id.clone(),
+            service_id: entitlement.service_id.clone(),
+            detail,
+        }
+    }
+}
+
+/// Delivers entitlement notifications to the webhooks a buyer registered
+#[derive(Clone)]
+pub struct BuyerNotifier {
+    repo: Arc<Repository>,
+    http: reqwest::Client,
+}
+
+impl BuyerNotifier {
+    pub fn new(repo: Arc<Repository>) -> Self {
+        Self {
+            repo,
+            http: reqwest::Client::new(),
+        }
+    }
+
+    pub async fn purchase_confirmed(&self, entitlement: &Entitlement) {
+        let tier = match self.repo.get_tier(&entitlement.tier_id).await {
+            Ok(tier) => tier,
+            Err(e) => {
+                warn!(tier_id = %entitlement.tier_id, error = %e, "Failed to load tier");
+                None
+            }
+        };
+        // The SLA in force at purchase time, so the receipt records what was bought
+        let sla = tier.as_ref().and_then(|t| t.sla.clone());
+        let detail = serde_json::json!({
+            "tier_id": entitlement.tier_id,
+            "price_paid": entitlement.price_paid,
+            "expires_at": entitlement.expires_at,
+            "quota": entitlement.quota,
+            "units": entitlement.units,
+            "sla": sla,
+        });
+        self.notify(BuyerNotification::new(
+            BuyerEvent::PurchaseConfirmed,
+            entitlement,
+            detail,
+        ))
+        .await;
+
+        if let Some(tier) = tier {
+            self.check_budgets(entitlement, &tier.coin_type).await;
+        }
+    }
+
+    /// Notifies the buyer when this purchase took their spend in the tier's
+    /// coin over a budget they set. Later purchases in the same window don't
+    /// notify again.
+    async fn check_budgets(&self, entitlement: &Entitlement, coin_type: &str) {
+        let budgets = match self.repo.list_buyer_budgets(&entitlement.buyer).await {
+            Ok(budgets) => budgets,
+            Err(e) => {
+                error!("Failed to load buyer budgets: {}", e);
+                return;
+            }
+        };
+
+        for budget in budgets.iter().filter(|b| b.coin_type == coin_type) {
+            let spent = match self
+                .repo
+                .buyer_spend_in_coin(&entitlement.buyer, coin_type, budget.window_start())
+                .await
+            {
+                Ok(spent) => spent,
+                Err(e) => {
+                    error!("Failed to compute buyer spend: {}", e);
+                    continue;
+                }
+            };
+
+            if budget.crossed_by(spent, entitlement.price_paid) {
+                info!(
+                    user = %entitlement.buyer,
+                    coin_type = %coin_type,
+                    spent = %spent,
+                    budget = %budget.amount,
+                    "Buyer budget exceeded"
+                );
+                let detail = serde_json::json!({
+                    "coin_type": coin_type,
+                    "budget": budget.amount,
+                    "period_days": budget.period_days,
+                    "spent": spent,
+                });
+                self.notify(BuyerNotification::new(
+                    BuyerEvent::BudgetExceeded,
+                    entitlement,
+                    detail,
+                ))
+                .await;
+            }
+        }
+    }
+
+    /// Posts to every webhook of the buyer in the background so event
+    /// processing is never held up by a slow endpoint
+    pub async fn notify(&self, notification: BuyerNotification) {
+        let webhooks = match self
+            .repo
+            .list_buyer_webhooks(&notification.user_address)
+            .await
+        {
+            Ok(w) if !w.is_empty() => w,
+            Ok(_) => return,
+            Err(e) => {
+                error!("Failed to load buyer webhooks: {}", e);
+                return;
+            }
+        };
+
+        let payload = match serde_json::to_vec(&notification) {
+            Ok(p) => p,
+            Err(e) => {
+                warn!(error = %e, "Failed to serialize buyer notification");
+                return;
+            }
+        };
+
+        for webhook in webhooks {
+            let http = self.http.clone();
+            let payload = payload.clone();
+            let event = notification.event;
+            tokio::spawn(async move {
+                match post_signed(&http, &webhook.url, &webhook.secret, payload).await {
+                    Ok(resp) if resp.status().is_success() => {}
+                    Ok(resp) => warn!(
+                        webhook_id = %webhook.id,
+                        status = %resp.status(),
+                        event = event.as_str(),
+                        "Buyer webhook rejected notification"
+                    ),
+                    Err(e) => warn!(
+                        webhook_id = %webhook.id,
+                        error = %e,
+                        event = event.as_str(),
+                        "Buyer webhook delivery failed"
+                    ),
+                }
+            });
+        }
+    }
+}
+
+/// Notifies buyers of entitlements that are about to expire or have used up
+/// most of their quota. Each notification is sent once.
+pub struct BuyerNotificationJob {
+    repo: Arc<Repository>,
+    notifier: BuyerNotifier,
+    expiry_notice_secs: u64,
+    quota_notice_percent: u8,
+}
+
+impl BuyerNotificationJob {
+    pub fn new(
+        repo: Arc<Repository>,
+        notifier: BuyerNotifier,
+        expiry_notice_secs: u64,
+        quota_notice_percent: u8,
+    ) -> Self {
+        Self {
+            repo,
+            notifier,
+            expiry_notice_secs,
+            quota_notice_percent,
+        }
+    }
+}
+
+#[async_trait]
+impl Job for BuyerNotificationJob {
+    fn name(&self) -> &'static str {
+        "buyer_notifications"
+    }
+
+    async fn run(&self) -> anyhow::Result<()> {
+        let (repo, notifier) = (&self.repo, &self.notifier);
+
+        let expiring = repo
+            .entitlements_expiring_soon(self.expiry_notice_secs)
+            .await
+            .context("failed to fetch expiring entitlements")?;
+        for ent in expiring {
+            let detail = serde_json::json!({ "expires_at": ent.expires_at });
+            send_once(repo, notifier, BuyerEvent::ExpiryApproaching, &ent, detail).await;
+        }
+
+        let consumed = repo
+            .entitlements_quota_consumed(self.quota_notice_percent)
+            .await
+            .context("failed to fetch quota usage")?;
+        for ent in consumed {
+            let detail = serde_json::json!({
+                "threshold_percent": self.quota_notice_percent,
+                "remaining": ent.quota,
+            });
+            send_once(repo, notifier, BuyerEvent::QuotaThreshold, &ent, detail).await;
+        }
+
+        Ok(())
+    }
+}
+
+async fn send_once(
+    repo: &Repository,
+    notifier: &BuyerNotifier,
+    event: BuyerEvent,
+    entitlement: &Entitlement,
+    detail: serde_json::Value,
+) {
+    match repo
+        .claim_buyer_notification(&entitlement.entitlement_id, event.as_str())
+        .await
+    {
+        Ok(true) => {
+            info!(
+                entitlement_id = %entitlement.entitlement_id,
+                event = event.as_str(),
+                "Notifying buyer"
+            );
+            notifier
+                .notify(BuyerNotification::new(event, entitlement, detail))
+                .await;
+        }
+        Ok(false) => {}
+        Err(e) => error!("Failed to record buyer notification: {}", e),
+    }
+}
@@ -0,0 +1,198 @@
+//! Example buyer agent built on `InfrapassClient`. Keeps the wallet holding a
+//! usable entitlement for each service in its config, buying a new one when
+//! the current one is about to expire or run out.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::{Result, anyhow};
+use chrono::Utc;
+use clap::Parser;
+use infrapass::{
+    client::infrapass::InfrapassClient,
+    types::entitlement::OnchainEntitlement,
+    utils::{config::resolve_wallet_config, network::Network},
+};
+use serde::Deserialize;
+use sui_types::base_types::ObjectID;
+use tracing::{error, info, warn};
+
+#[derive(Parser)]
+#[command(name = "infrapass-buyer-agent")]
+#[command(about = "Keeps a wallet's entitlements to a list of services current")]
+struct Args {
+    /// JSON list of the services to hold entitlements for
+    #[arg(long)]
+    config: PathBuf,
+
+    /// Seconds between checks
+    #[arg(long, default_value_t = 300)]
+    interval_secs: u64,
+
+    /// Check once and exit
+    #[arg(long)]
+    once: bool,
+
+    /// Sui client config of the buyer wallet
+    #[arg(long)]
+    wallet_config: Option<String>,
+
+    #[arg(long)]
+    rpc_url: Option<String>,
+
+    #[arg(long, value_enum, default_value_t = Network::Testnet)]
+    network: Network,
+}
+
+/// ```json
+/// {
+///   "services": [
+///     {
+///       "service_id": "0x...",
+///       "tier_id": "0x...",
+///       "renew_before_secs": 86400,
+///       "min_remaining": 100,
+///       "max_price": 1000000000
+///     }
+///   ]
+/// }
+/// ```
+#[derive(Debug, Deserialize)]
+struct AgentConfig {
+    services: Vec<WantedService>,
+}
+
+#[derive(Debug, Deserialize)]
+struct WantedService {
+    service_id: ObjectID,
+    tier_id: ObjectID,
+    /// Buy again when the entitlement expires within this many seconds
+    #[serde(default)]
+    renew_before_secs: u64,
+    /// Buy again when fewer requests or units than this remain on chain
+    #[serde(default = "default_min_remaining")]
+    min_remaining: u64,
+    /// Skip the purchase if the tier costs more than this, in base units
+    max_price: Option<u64>,
+}
+
+fn default_min_remaining() -> u64 {
+    1
+}
+
+impl AgentConfig {
+    fn load(path: &Path) -> Result<Self> {
+        let raw = fs::read_to_string(path)
+            .map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?;
+        serde_json::from_str(&raw).map_err(|e| anyhow!("Invalid config {}: {}", path.display(), e))
+    }
+}
+
+impl WantedService {
+    /// Whether `ent` still covers this service for longer than the renewal
+    /// window and with enough left
+    fn covered_by(&self, ent: &OnchainEntitlement, now_ms: u64) -> bool {
+        if ent.service_id != self.service_id || ent.tier_id != self.tier_id {
+            return false;
+        }
+        if !ent.is_active(now_ms) {
+            return false;
+        }
+
+        let renew_at = now_ms.saturating_add(self.renew_before_secs.saturating_mul(1000));
+        let lasts = ent
+            .expires_at()
+            .is_none_or(|expires_at| expires_at > renew_at);
+        let enough = ent
+            .remaining()
+            .is_none_or(|remaining| remaining >= self.min_remaining);
+        lasts && enough
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    tracing_subscriber::fmt()
+        .compact()
+        .with_target(false)
+        .init();
+
+    let args = Args::parse();
+    args.network.select();
+
+    let rpc_url = args
+        .rpc_url
+        .unwrap_or_else(|| args.network.default_rpc_url().to_string());
+    let wallet_config = resolve_wallet_config(args.wallet_config.as_deref())?;
+    let mut client = InfrapassClient::connect(&rpc_url, wallet_config).await?;
+    info!(
+        address = %client.address()?,
+        rpc_url = %rpc_url,
+        "Buyer agent started"
+    );
+
+    loop {
+        // The config is read on every pass so edits apply without a restart
+        let result = match AgentConfig::load(&args.config) {
+            Ok(config) => maintain(&mut client, &config).await,
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(()) if args.once => return Ok(()),
+            Ok(()) => {}
+            Err(e) if args.once => return Err(e),
+            Err(e) => error!("Check failed: {:#}", e),
+        }
+
+        tokio::select! {
+            _ = tokio::time::sleep(Duration::from_secs(args.interval_secs)) => {}
+            _ = tokio::signal::ctrl_c() => {
+                info!("Buyer agent stopped");
+                return Ok(());
+            }
+        }
+    }
+}
+
+async fn maintain(client: &mut InfrapassClient, config: &AgentConfig) -> Result<()> {
+    let held = client.entitlements().await?;
+    let now_ms = Utc::now().timestamp_millis() as u64;
+
+    for wanted in &config.services {
+        if held.iter().any(|ent| wanted.covered_by(ent, now_ms)) {
+            continue;
+        }
+
+        let tier = client.tier(wanted.tier_id).await?;
+        if !tier.active {
+            warn!(
+                tier_id = %wanted.tier_id,
+                "Tier is retired; pick a replacement in the config"
+            );
+            continue;
+        }
+        if let Some(max_price) = wanted.max_price.filter(|max| tier.price > *max) {
+            warn!(
+                tier_id = %wanted.tier_id,
+                price = tier.price,
+                max_price,
+                "Tier costs more than max_price, not buying"
+            );
+            continue;
+        }
+
+        info!(
+            service_id = %wanted.service_id,
+            tier_id = %wanted.tier_id,
+            price = tier.price,
+            "Buying entitlement"
+        );
+        let resp = client.purchase(wanted.service_id, wanted.tier_id).await?;
+        info!(digest = %resp.digest, "Entitlement purchased");
+    }
+
+    Ok(())
+}
@@ -25,11 +25,13 @@ async fn main() -> Result<()> {
 
     tracing::subscriber::set_global_default(subscriber)?;
 
+    cli.network.select();
+
     let rpc_url = cli
         .rpc_url
-        .unwrap_or_else(|| "https://fullnode.testnet.sui.io:443".to_string());
+        .unwrap_or_else(|| cli.network.default_rpc_url().to_string());
 
-    info!("Connecting to Sui RPC: {}", rpc_url);
+    info!("Connecting to Sui RPC: {} ({})", rpc_url, cli.network);
 
     let client = SuiClientBuilder::default().build(&rpc_url).await?;
 
@@ -38,6 +40,7 @@ async fn main() -> Result<()> {
         Commands::Pricing(cmd) => cmd.execute(&client).await?,
         Commands::Payment(cmd) => cmd.execute(&client).await?,
         Commands::Query(cmd) => cmd.execute(&client).await?,
+        Commands::Coin(cmd) => cmd.execute(&client).await?,
     }
 
     Ok(())
@@ -0,0 +1,190 @@
+//! Example provider daemon built on `InfrapassClient`. Registers the wallet as
+//! a provider, creates the services listed in a manifest and keeps their
+//! metadata URIs in sync with it.
+
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::{Result, anyhow};
+use clap::Parser;
+use infrapass::{
+    client::infrapass::InfrapassClient,
+    utils::{config::resolve_wallet_config, network::Network},
+};
+use serde::Deserialize;
+use sui_types::base_types::ObjectID;
+use tracing::{error, info, warn};
+
+#[derive(Parser)]
+#[command(name = "infrapass-provider-daemon")]
+#[command(about = "Keeps a provider's on-chain services in line with a manifest")]
+struct Args {
+    /// JSON manifest of the provider and its services
+    #[arg(long)]
+    manifest: PathBuf,
+
+    /// Seconds between syncs
+    #[arg(long, default_value_t = 300)]
+    interval_secs: u64,
+
+    /// Sync once and exit
+    #[arg(long)]
+    once: bool,
+
+    /// Sui client config of the provider wallet
+    #[arg(long)]
+    wallet_config: Option<String>,
+
+    #[arg(long)]
+    rpc_url: Option<String>,
+
+    #[arg(long, value_enum, default_value_t = Network::Testnet)]
+    network: Network,
+}
+
+/// ```json
+/// {
+///   "metadata_uri": "https://example.com/provider.json",
+///   "services": [
+///     { "service_type": "rpc", "metadata_uri": "https://example.com/rpc.json" }
+///   ]
+/// }
+/// ```
+#[derive(Debug, Deserialize)]
+struct ProviderManifest {
+    /// Used when registering; an existing profile's is left alone
+    metadata_uri: String,
+    services: Vec<ManifestService>,
+}
+
+/// Services are matched to on-chain ones by `service_type`, so each type may
+/// appear only once
+#[derive(Debug, Deserialize)]
+struct ManifestService {
+    service_type: String,
+    metadata_uri: String,
+}
+
+impl ProviderManifest {
+    fn load(path: &Path) -> Result<Self> {
+        let raw = fs::read_to_string(path)
+            .map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?;
+        let manifest: Self = serde_json::from_str(&raw)
+            .map_err(|e| anyhow!("Invalid manifest {}: {}", path.display(), e))?;
+
+        let mut types = HashSet::new();
+        for service in &manifest.services {
+            if !types.insert(service.service_type.as_str()) {
+                return Err(anyhow!(
+                    "Manifest {} lists service type {} twice",
+                    path.display(),
+                    service.service_type
+                ));
+            }
+        }
+
+        Ok(manifest)
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    tracing_subscriber::fmt()
+        .compact()
+        .with_target(false)
+        .init();
+
+    let args = Args::parse();
+    args.network.select();
+
+    let rpc_url = args
+        .rpc_url
+        .unwrap_or_else(|| args.network.default_rpc_url().to_string());
+    let wallet_config = resolve_wallet_config(args.wallet_config.as_deref())?;
+    let mut client = InfrapassClient::connect(&rpc_url, wallet_config).await?;
+    info!(
+        address = %client.address()?,
+        rpc_url = %rpc_url,
+        "Provider daemon started"
+    );
+
+    loop {
+        // The manifest is read on every pass so edits apply without a restart
+        let result = match ProviderManifest::load(&args.manifest) {
+            Ok(manifest) => sync(&mut client, &manifest).await,
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(()) if args.once => return Ok(()),
+            Ok(()) => {}
+            Err(e) if args.once => return Err(e),
+            Err(e) => error!("Sync failed: {:#}", e),
+        }
+
+        tokio::select! {
+            _ = tokio::time::sleep(Duration::from_secs(args.interval_secs)) => {}
+            _ = tokio::signal::ctrl_c() => {
+                info!("Provider daemon stopped");
+                return Ok(());
+            }
+        }
+    }
+}
+
+async fn sync(client: &mut InfrapassClient, manifest: &ProviderManifest) -> Result<()> {
+    if client.provider().await?.is_none() {
+        info!("Registering provider");
+        let resp = client.register_provider(&manifest.metadata_uri).await?;
+        info!(digest = %resp.digest, "Provider registered");
+    }
+
+    let services = client.services().await?;
+    for wanted in &manifest.services {
+        match services
+            .iter()
+            .find(|s| s.service_type == wanted.service_type)
+        {
+            None => {
+                info!(service_type = %wanted.service_type, "Creating service");
+                let resp = client
+                    .create_service(&wanted.service_type, &wanted.metadata_uri)
+                    .await?;
+                info!(digest = %resp.digest, "Service created");
+            }
+            Some(service) if service.metadata_uri != wanted.metadata_uri => {
+                info!(
+                    service_id = %service.service_id,
+                    from = %service.metadata_uri,
+                    to = %wanted.metadata_uri,
+                    "Updating service metadata"
+                );
+                let service_id = ObjectID::from_hex_literal(&service.service_id)?;
+                client
+                    .update_service_metadata(service_id, &wanted.metadata_uri)
+                    .await?;
+            }
+            Some(_) => {}
+        }
+    }
+
+    // Services are never deactivated from here; that is left to the operator
+    for service in &services {
+        if !manifest
+            .services
+            .iter()
+            .any(|s| s.service_type == service.service_type)
+        {
+            warn!(
+                service_id = %service.service_id,
+                service_type = %service.service_type,
+                "Service is not in the manifest"
+            );
+        }
+    }
+
+    Ok(())
+}
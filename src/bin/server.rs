@@ -1,76 +1,859 @@
-use std::{sync::Arc, time::Duration};
+use std::{str::FromStr, sync::Arc, time::Duration};
 
-use anyhow::Result;
+use anyhow::{Result, bail};
+use chrono::Utc;
+use clap::{Parser, Subcommand, ValueEnum};
 use dotenvy::dotenv;
 use infrapass::{
-    backend::{router::build_router, settlement::settlement_worker},
-    db::{create_pool, repository::Repository, run_migrations},
-    events::{listener::EventListener, types::EventPayload, worker::EventWorker},
+    alerting::{config::AlertConfig, manager::AlertManager},
+    backend::{
+        catalog_cache::CatalogCache,
+        contacts::ContactVault,
+        decisions::DecisionLog,
+        feed::CatalogFeed,
+        grpc::ValidatorService,
+        idempotency::IdempotencyCache,
+        keys::{self, KeyMonitorJob, KeyRole, MonitoredKey},
+        metadata::MetadataRefreshJob,
+        openapi,
+        provider_webhooks::{WebhookDispatcher, WebhookSecrets},
+        rate_limit::RateLimiter,
+        readiness::Readiness,
+        reconcile::{self, ReconcileJob},
+        router::{build_lite_router, build_router},
+        scheduler::{EventRetentionJob, JobSchedule, PruneJob, Scheduler},
+        session::SessionSigner,
+        settlement::{self, SettlementJob, SettlementThresholds},
+        state::{AppState, LiteState},
+        usage::UsageRollupJob,
+        validation_cache::CachedStorage,
+        verify,
+        webhooks::{BuyerNotificationJob, BuyerNotifier},
+    },
+    db::{
+        create_pool,
+        repository::Repository,
+        run_migrations,
+        sqlite::SqliteStorage,
+        storage::{Storage, StorageBackend},
+    },
+    events::{
+        dlq::replay_failed_events,
+        filter::EventFilter,
+        listener::{
+            CheckpointSource, DEFAULT_PIPELINE_DEPTH, DEFAULT_POLL_INTERVAL, EventListener,
+        },
+        lite::LiteWorker,
+        packages::{WatchedPackage, parse_watched_packages},
+        replay::replay_stored_events,
+        sink::EventSink,
+        stream::EventStream,
+        types::EventPayload,
+        worker::EventWorker,
+    },
+    pubsub::{outbox::OutboxPublisher, publisher::PubSubPublisher},
+    utils::{
+        config::{default_wallet_config, load_wallet_context},
+        constants::USAGE_RELAYER_ID,
+        network::Network,
+        package::ensure_upgrade_watched,
+    },
 };
-use sui_sdk::SuiClientBuilder;
+use sui_sdk::{SuiClient, SuiClientBuilder};
+use sui_types::base_types::{ObjectID, SuiAddress};
 use tokio::{signal, sync::mpsc};
+use tokio_util::sync::CancellationToken;
 use tower_http::timeout::TimeoutLayer;
 use tower_http::trace::TraceLayer;
 use tracing::{error, info};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
+#[derive(Parser)]
+#[command(name = "infrapass-server")]
+#[command(about = "Infrapass validator API, event indexer and settlement worker")]
+struct Args {
+    /// Defaults to `serve`
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Run the validator API, event indexer and background workers
+    Serve,
+
+    /// Apply pending database migrations and exit
+    Migrate,
+
+    /// Index past checkpoints through the event worker pipeline
+    Backfill {
+        /// First checkpoint to index
+        #[arg(long)]
+        from_checkpoint: u64,
+
+        /// Last checkpoint to index (inclusive)
+        #[arg(long)]
+        to_checkpoint: u64,
+    },
+
+    /// Forget the events of a checkpoint range and index it again
+    Reindex {
+        /// First checkpoint to reindex
+        #[arg(long)]
+        from_checkpoint: u64,
+
+        /// Last checkpoint to reindex (inclusive)
+        #[arg(long)]
+        to_checkpoint: u64,
+    },
+
+    /// Delete API request logs, settled usage, replayed dead letters, published
+    /// outbox messages, settled webhook deliveries and stale sidecars
+    Prune {
+        /// Keep rows newer than this many days
+        #[arg(long)]
+        older_than_days: u32,
+    },
+
+    /// Diff a provider's on-chain services and tiers against Postgres
+    Verify {
+        /// Provider profile object ID
+        #[arg(long)]
+        provider: String,
+
+        /// Rewrite mismatched rows from on-chain state
+        #[arg(long)]
+        repair: bool,
+    },
+
+    /// Compare random tiers and entitlements in Postgres with chain
+    Reconcile {
+        /// Number of tiers, and of entitlements, to sample
+        #[arg(long, default_value_t = 100)]
+        sample: i64,
+
+        /// Rewrite diverging rows from on-chain state
+        #[arg(long)]
+        repair: bool,
+    },
+
+    /// Index dead-lettered events again: undecodable ones that now parse and
+    /// ones the worker gave up on
+    ReplayDlq {
+        /// Maximum number of failed events to replay
+        #[arg(long, default_value_t = 1000)]
+        limit: i64,
+    },
+
+    /// Relayer and sponsor key health
+    #[command(subcommand)]
+    Keys(KeysCommand),
+
+    /// Stored event history
+    #[command(subcommand)]
+    Events(EventsCommand),
+
+    /// Tenants of a hosted, multi-tenant deployment
+    #[command(subcommand)]
+    Tenants(TenantsCommand),
+
+    /// Print the validator API's OpenAPI spec, for generating client types
+    Openapi,
+}
+
+#[derive(Subcommand)]
+enum KeysCommand {
+    /// Show gas balances and the relayer cap's owner and version
+    Status,
+}
+
+#[derive(Subcommand)]
+enum TenantsCommand {
+    /// Add a tenant
+    Create {
+        #[arg(long)]
+        id: String,
+
+        #[arg(long)]
+        name: String,
+    },
+
+    /// List tenants
+    List,
+
+    /// Put a provider and its indexed rows under a tenant
+    Assign {
+        #[arg(long)]
+        tenant: String,
+
+        /// Provider profile object ID
+        #[arg(long)]
+        provider: String,
+    },
+
+    /// Issue an API key bound to a tenant; it is printed once
+    AddKey {
+        #[arg(long)]
+        tenant: String,
+
+        /// Name to revoke the key by later
+        #[arg(long)]
+        label: Option<String>,
+    },
+
+    /// Revoke a tenant's API keys with the given label
+    RevokeKey {
+        #[arg(long)]
+        tenant: String,
+
+        #[arg(long)]
+        label: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum EventsCommand {
+    /// Run events stored in blockchain_events through the worker's handlers
+    /// again, rebuilding derived tables without re-streaming from the chain
+    Replay {
+        #[arg(long)]
+        from_checkpoint: u64,
+
+        /// Last checkpoint to replay; defaults to the newest stored
+        #[arg(long)]
+        to_checkpoint: Option<u64>,
+
+        /// Comma separated modules or events to replay, as for INDEXER_EVENTS
+        #[arg(long)]
+        event_type: Option<String>,
+    },
+
+    /// List the partitions (hypertable chunks) of blockchain_events
+    Partitions,
+
+    /// Drop the partitions of blockchain_events that hold only events older
+    /// than the given age; projected tables are kept
+    Prune {
+        /// Keep partitions with any event newer than this many days
+        #[arg(long)]
+        older_than_days: u32,
+    },
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     dotenv().ok();
     init_tracing();
+    network().select();
+    ensure_upgrade_watched(&watched_packages())?;
+
+    match Args::parse().command.unwrap_or(Command::Serve) {
+        Command::Serve => run_server().await,
+        Command::Migrate => run_migrate().await,
+        Command::Backfill {
+            from_checkpoint,
+            to_checkpoint,
+        } => run_backfill(from_checkpoint, to_checkpoint).await,
+        Command::Reindex {
+            from_checkpoint,
+            to_checkpoint,
+        } => run_reindex(from_checkpoint, to_checkpoint).await,
+        Command::Prune { older_than_days } => run_prune(older_than_days).await,
+        Command::Verify { provider, repair } => run_verify(&provider, repair).await,
+        Command::Reconcile { sample, repair } => run_reconcile(sample, repair).await,
+        Command::ReplayDlq { limit } => run_replay_dlq(limit).await,
+        Command::Keys(KeysCommand::Status) => run_keys_status().await,
+        Command::Events(EventsCommand::Replay {
+            from_checkpoint,
+            to_checkpoint,
+            event_type,
+        }) => run_events_replay(from_checkpoint, to_checkpoint, event_type.as_deref()).await,
+        Command::Events(EventsCommand::Partitions) => run_event_partitions().await,
+        Command::Events(EventsCommand::Prune { older_than_days }) => {
+            run_event_prune(older_than_days).await
+        }
+        Command::Tenants(command) => run_tenants(command).await,
+        Command::Openapi => {
+            println!("{}", openapi::spec().to_pretty_json()?);
+            Ok(())
+        }
+    }
+}
+
+async fn run_migrate() -> Result<()> {
+    match StorageBackend::from_url(&required_env("DATABASE_URL"))? {
+        StorageBackend::Postgres => {
+            connect_repo().await?;
+        }
+        StorageBackend::Sqlite => {
+            connect_sqlite().await?;
+        }
+    }
+    println!("Database is up to date");
+    Ok(())
+}
+
+async fn run_keys_status() -> Result<()> {
+    let sui_client = connect_sui().await?;
+    let min_gas_balance = AlertConfig::load()?.min_gas_balance;
+
+    let cap_id = ObjectID::from_hex_literal(USAGE_RELAYER_ID)?;
+    let report = keys::check_keys(&sui_client, &monitored_keys()?, cap_id, min_gas_balance).await?;
+
+    println!("Keys (alert below {} MIST):", min_gas_balance);
+    for status in &report.keys {
+        let note = if status.low { " (low)" } else { "" };
+        println!(
+            "  {:<8} {}  {} MIST{}",
+            status.key.role, status.key.address, status.gas_balance, note
+        );
+    }
+
+    println!("Relayer cap {}:", report.cap.cap_id);
+    match report.cap.problem() {
+        Some(reason) => println!("  {}", reason),
+        None => println!(
+            "  owned by {}, version {}",
+            report.cap.owner.map(|o| o.to_string()).unwrap_or_default(),
+            report.cap.version.unwrap_or_default()
+        ),
+    }
+
+    Ok(())
+}
+
+async fn run_replay_dlq(limit: i64) -> Result<()> {
+    let repo = connect_repo().await?;
+    let alerts = Arc::new(AlertManager::new(AlertConfig::load()?));
+
+    let (tx, rx) = mpsc::channel::<EventPayload>(256);
+    let worker = EventWorker::new(repo.clone(), rx, alerts)
+        .with_shards(worker_shards())
+        .with_sinks(event_sinks().await?);
+    let worker_handle = tokio::spawn(worker.run(CancellationToken::new()));
+
+    let result = replay_failed_events(&repo, &tx, limit).await;
+    drop(tx);
+    worker_handle.await??;
+
+    let summary = result?;
+    println!(
+        "Replayed {} event(s); {} still failing, {} unhandled",
+        summary.replayed, summary.still_failing, summary.unhandled
+    );
+
+    Ok(())
+}
+
+async fn run_tenants(command: TenantsCommand) -> Result<()> {
+    let repo = connect_repo().await?;
 
+    match command {
+        TenantsCommand::Create { id, name } => {
+            let tenant = repo.create_tenant(&id, &name).await?;
+            println!("Created tenant {} ({})", tenant.tenant_id, tenant.name);
+        }
+        TenantsCommand::List => {
+            for tenant in repo.list_tenants().await? {
+                println!(
+                    "  {:<24} {}  created {}",
+                    tenant.tenant_id, tenant.name, tenant.created_at
+                );
+            }
+        }
+        TenantsCommand::Assign { tenant, provider } => {
+            let provider_id = ObjectID::from_hex_literal(&provider)?.to_string();
+            repo.assign_tenant_provider(&tenant, &provider_id).await?;
+            println!("Provider {} now belongs to tenant {}", provider_id, tenant);
+        }
+        TenantsCommand::AddKey { tenant, label } => {
+            let key = repo
+                .create_tenant_api_key(&tenant, label.as_deref())
+                .await?;
+            println!("API key for tenant {} (shown once):", tenant);
+            println!("{}", key);
+        }
+        TenantsCommand::RevokeKey { tenant, label } => {
+            let revoked = repo.revoke_tenant_api_keys(&tenant, &label).await?;
+            println!("Revoked {} key(s) of tenant {}", revoked, tenant);
+        }
+    }
+
+    Ok(())
+}
+
+/// Replays through a worker without sinks, webhooks or buyer notifications,
+/// so only the tables change and nothing is announced a second time
+async fn run_events_replay(from: u64, to: Option<u64>, event_type: Option<&str>) -> Result<()> {
+    if let Some(to) = to.filter(|to| from > *to) {
+        bail!("Invalid checkpoint range: {} is after {}", from, to);
+    }
+    let filter = match event_type {
+        Some(raw) => EventFilter::parse(raw)?,
+        None => EventFilter::all(),
+    };
+
+    let repo = connect_repo().await?;
+    let alerts = Arc::new(AlertManager::new(AlertConfig::load()?));
+
+    let (tx, rx) = mpsc::channel::<EventPayload>(256);
+    let worker = EventWorker::new(repo.clone(), rx, alerts)
+        .with_shards(worker_shards())
+        .with_reprocess();
+    let worker_handle = tokio::spawn(worker.run(CancellationToken::new()));
+
+    let result = replay_stored_events(&repo, &tx, from, to, &filter).await;
+    drop(tx);
+    worker_handle.await??;
+
+    let summary = result?;
+    println!(
+        "Replayed {} event(s); {} filtered out, {} undecodable",
+        summary.replayed, summary.filtered, summary.undecodable
+    );
+
+    Ok(())
+}
+
+async fn run_backfill(from: u64, to: u64) -> Result<()> {
+    let repo = connect_repo().await?;
+    index_range(repo, from, to).await
+}
+
+async fn run_reindex(from: u64, to: u64) -> Result<()> {
+    if from > to {
+        bail!("Invalid checkpoint range: {} is after {}", from, to);
+    }
+
+    let repo = connect_repo().await?;
+    let forgotten = repo.reset_indexed_range(from, to).await?;
+    info!(
+        "Cleared {} indexed event(s) in checkpoints {}..={}",
+        forgotten, from, to
+    );
+
+    index_range(repo, from, to).await
+}
+
+async fn run_prune(older_than_days: u32) -> Result<()> {
+    let cutoff = Utc::now() - chrono::Duration::days(older_than_days as i64);
+    let repo = connect_repo().await?;
+
+    println!("Pruning rows older than {}:", cutoff);
+    for (table, deleted) in repo.prune_before(cutoff).await? {
+        println!("  {:<18} {} deleted", table, deleted);
+    }
+
+    Ok(())
+}
+
+async fn run_event_partitions() -> Result<()> {
+    let repo = connect_repo().await?;
+
+    let partitions = repo.event_partitions().await?;
+    println!("{} partition(s) of blockchain_events:", partitions.len());
+    for partition in &partitions {
+        println!(
+            "  {:<28} {} to {}",
+            partition.chunk_name, partition.range_start, partition.range_end
+        );
+    }
+
+    Ok(())
+}
+
+async fn run_event_prune(older_than_days: u32) -> Result<()> {
+    let cutoff = Utc::now() - chrono::Duration::days(older_than_days as i64);
+    let repo = connect_repo().await?;
+
+    let dropped = repo.drop_event_partitions(cutoff).await?;
+    println!(
+        "Dropped {} partition(s) of events older than {}",
+        dropped.len(),
+        cutoff
+    );
+    for chunk in &dropped {
+        println!("  {}", chunk);
+    }
+
+    Ok(())
+}
+
+async fn run_verify(provider: &str, repair: bool) -> Result<()> {
+    let provider_id = ObjectID::from_hex_literal(provider)?;
+    let repo = connect_repo().await?;
+    let sui_client = connect_sui().await?;
+
+    let mismatches = verify::verify_provider(&sui_client, &repo, provider_id).await?;
+    if mismatches.is_empty() {
+        println!("Provider {} is in sync", provider_id);
+        return Ok(());
+    }
+
+    println!("Found {} mismatch(es):", mismatches.len());
+    for mismatch in &mismatches {
+        let note = if mismatch.is_repairable() {
+            ""
+        } else {
+            " (manual)"
+        };
+        println!("  - {}{}", mismatch, note);
+    }
+
+    if !repair {
+        bail!(
+            "Provider {} is out of sync; rerun with --repair to fix",
+            provider_id
+        );
+    }
+
+    let repaired = verify::repair(&repo, &provider_id.to_string(), &mismatches).await?;
+    println!("Repaired {} of {} mismatch(es)", repaired, mismatches.len());
+
+    Ok(())
+}
+
+async fn run_reconcile(sample: i64, repair: bool) -> Result<()> {
+    let repo = connect_repo().await?;
+    let sui_client = connect_sui().await?;
+
+    let report = reconcile::reconcile(&sui_client, &repo, sample).await?;
+    println!(
+        "Checked {} tier(s) and {} entitlement(s)",
+        report.tiers_checked, report.entitlements_checked
+    );
+    if report.divergences.is_empty() {
+        println!("No divergence found");
+        return Ok(());
+    }
+
+    println!("Found {} divergence(s):", report.divergences.len());
+    for divergence in &report.divergences {
+        let note = if divergence.is_repairable() {
+            ""
+        } else {
+            " (manual)"
+        };
+        println!("  - {}{}", divergence, note);
+    }
+
+    if !repair {
+        bail!("Postgres diverges from chain; rerun with --repair to fix");
+    }
+
+    let repaired = reconcile::repair(&repo, &report.divergences).await?;
+    println!(
+        "Repaired {} of {} divergence(s)",
+        repaired,
+        report.divergences.len()
+    );
+
+    Ok(())
+}
+
+/// Runs `from..=to` through a listener and worker of its own, returning once
+/// the worker has handled every event. Sidecar notifications stay in the
+/// outbox for the running server to publish.
+async fn index_range(repo: Arc<Repository>, from: u64, to: u64) -> Result<()> {
+    let sui_client = Arc::new(connect_sui().await?);
+    let alerts = Arc::new(AlertManager::new(AlertConfig::load()?));
+
+    let (tx, rx) = mpsc::channel::<EventPayload>(256);
+    let listener = event_listener(sui_client, tx, alerts.clone(), repo.clone()).await?;
+    let worker = EventWorker::new(repo, rx, alerts)
+        .with_shards(worker_shards())
+        .with_batching(backfill_batch_size(), backfill_flush_interval())
+        .with_sinks(event_sinks().await?);
+
+    let worker_handle = tokio::spawn(worker.run(CancellationToken::new()));
+
+    let result = listener.backfill(from, to).await;
+    worker_handle.await??;
+
+    result
+}
+
+async fn run_server() -> Result<()> {
+    if StorageBackend::from_url(&required_env("DATABASE_URL"))? == StorageBackend::Sqlite {
+        return run_lite_server().await;
+    }
     info!("Starting Infrapass");
 
     let config = load_config();
-    let pool = Arc::new(create_pool(&config.database_url).await?);
-    run_migrations(&pool).await?;
-
-    let repo = Arc::new(Repository::new(pool));
+    let repo = connect_repo().await?;
     let redis_client = redis::Client::open(config.redis_url)?;
 
-    let sui_client = Arc::new(SuiClientBuilder::default().build(&config.grpc_url).await?);
+    let sui_client = Arc::new(connect_sui().await?);
+
+    let alerts = Arc::new(AlertManager::new(AlertConfig::load()?));
+
+    let publisher = Arc::new(PubSubPublisher::new(redis_client.clone()).await?);
+
+    let validation_cache = match config.validate_cache_ttl_secs {
+        Some(ttl) => Some(Arc::new(
+            CachedStorage::new(repo.clone(), redis_client.clone(), ttl).await?,
+        )),
+        None => None,
+    };
+    let storage: Arc<dyn Storage> = match &validation_cache {
+        Some(cache) => cache.clone(),
+        None => repo.clone(),
+    };
+
+    let rate_limiter = match config.rate_limit_rps {
+        Some(rps) => {
+            let burst = config.rate_limit_burst.unwrap_or(rps.ceil() as u64);
+            Some(Arc::new(
+                RateLimiter::new(redis_client.clone(), rps, burst).await?,
+            ))
+        }
+        None => None,
+    };
+    let idempotency = Arc::new(
+        IdempotencyCache::redis(
+            redis_client.clone(),
+            Duration::from_secs(config.idempotency_ttl_secs),
+        )
+        .await?,
+    );
 
-    let app = build_router(repo.clone())
+    let outbox = OutboxPublisher::new(repo.clone(), publisher.clone());
+    let webhook_secrets = Arc::new(WebhookSecrets::new(
+        config.webhook_encryption_key.as_deref(),
+    )?);
+    let sealed = webhook_secrets.seal_existing(&repo).await?;
+    if sealed > 0 {
+        info!(count = sealed, "Encrypted stored provider webhook secrets");
+    }
+    let dispatcher = WebhookDispatcher::new(repo.clone(), webhook_secrets.clone());
+    let buyer_notifier = BuyerNotifier::new(repo.clone());
+
+    let cap_id = ObjectID::from_hex_literal(USAGE_RELAYER_ID)?;
+    let settlement = SettlementJob::new(
+        repo.clone(),
+        sui_client.clone(),
+        alerts.clone(),
+        config.settlement_batch_size,
+    )?;
+    let settlement_threshold = config
+        .settlement_thresholds
+        .is_set()
+        .then(|| settlement.threshold_job(config.settlement_thresholds));
+    let mut scheduler = Scheduler::new()
+        .with_job(
+            settlement,
+            config.settlement_schedule,
+            job_jitter("SETTLEMENT"),
+        )
+        .with_job(
+            BuyerNotificationJob::new(
+                repo.clone(),
+                buyer_notifier.clone(),
+                config.buyer_expiry_notice_secs,
+                config.buyer_quota_notice_percent,
+            ),
+            config.buyer_notify_schedule,
+            job_jitter("BUYER_NOTIFY"),
+        )
+        .with_job(
+            KeyMonitorJob::new(
+                sui_client.clone(),
+                alerts.clone(),
+                monitored_keys()?,
+                cap_id,
+            ),
+            job_schedule("KEY_CHECK")
+                .unwrap_or_else(|| JobSchedule::Every(alerts.key_check_interval())),
+            job_jitter("KEY_CHECK"),
+        )
+        .with_job(
+            UsageRollupJob::new(repo.clone()),
+            config.usage_rollup_schedule,
+            job_jitter("USAGE_ROLLUP"),
+        )
+        .with_job(
+            MetadataRefreshJob::new(repo.clone(), config.metadata_max_age_secs),
+            config.metadata_refresh_schedule,
+            job_jitter("METADATA_REFRESH"),
+        );
+    // Early settlement is only checked for when a threshold is set
+    if let Some(job) = settlement_threshold {
+        scheduler = scheduler.with_job(
+            job,
+            job_schedule("SETTLEMENT_THRESHOLD")
+                .unwrap_or_else(|| JobSchedule::Every(Duration::from_secs(30))),
+            job_jitter("SETTLEMENT_THRESHOLD"),
+        );
+    }
+    // Pruning only runs when scheduled
+    if let Some(schedule) = job_schedule("PRUNE") {
+        scheduler = scheduler.with_job(
+            PruneJob::new(repo.clone(), config.prune_retention_days),
+            schedule,
+            job_jitter("PRUNE"),
+        );
+    }
+    // Event history is kept for good unless a retention is set
+    if let Some(days) = config.event_retention_days {
+        scheduler = scheduler.with_job(
+            EventRetentionJob::new(repo.clone(), days),
+            job_schedule("EVENT_RETENTION")
+                .unwrap_or_else(|| JobSchedule::Every(Duration::from_secs(3600))),
+            job_jitter("EVENT_RETENTION"),
+        );
+    }
+    // So does reconciliation, which reads a few hundred objects per run
+    if let Some(schedule) = job_schedule("RECONCILE") {
+        scheduler = scheduler.with_job(
+            ReconcileJob::new(
+                sui_client.clone(),
+                repo.clone(),
+                alerts.clone(),
+                config.reconcile_sample,
+            )
+            .with_repair(config.reconcile_repair),
+            schedule,
+            job_jitter("RECONCILE"),
+        );
+    }
+
+    let catalog = Arc::new(CatalogCache::new(Duration::from_secs(
+        config.catalog_cache_secs,
+    )));
+
+    let (tx, rx) = mpsc::channel::<EventPayload>(256);
+    let listener = event_listener(sui_client.clone(), tx, alerts.clone(), repo.clone()).await?;
+
+    let events = Arc::new(EventStream::new(repo.clone()));
+    let readiness = Readiness::new(
+        repo.clone(),
+        publisher.clone(),
+        listener.health(),
+        alerts.checkpoint_lag_threshold(),
+    )
+    .with_required_indexer(readyz_require_indexer());
+
+    let state = AppState {
+        repo: repo.clone(),
+        storage,
+        alerts: alerts.clone(),
+        publisher,
+        feed: Arc::new(CatalogFeed::new(
+            repo.clone(),
+            Duration::from_secs(config.feed_cache_secs),
+        )),
+        catalog: catalog.clone(),
+        contacts: Arc::new(ContactVault::new(config.contact_encryption_key.as_deref())?),
+        webhook_secrets,
+        jobs: scheduler.board(),
+        rate_limiter,
+        idempotency,
+        decisions: Arc::new(DecisionLog::new(validation_log_size())),
+        events: events.clone(),
+        readiness: Arc::new(readiness),
+        sessions: Arc::new(SessionSigner::new(
+            config.session_secret.as_deref(),
+            config.session_ttl_secs,
+        )?),
+        sui: sui_client.clone(),
+    };
+    let grpc = ValidatorService::new(&state).into_server();
+    let app = build_router(state)
         .layer(TraceLayer::new_for_http())
         .layer(TimeoutLayer::new(Duration::from_secs(10)));
 
     let tcp_listener = tokio::net::TcpListener::bind(&config.addr).await?;
     info!("Validator API listening on {}", config.addr);
 
-    let (tx, rx) = mpsc::channel::<EventPayload>(256);
+    let mut sinks = event_sinks().await?;
+    sinks.push(events);
+
+    let worker = EventWorker::new(repo.clone(), rx, alerts.clone())
+        .with_shards(worker_shards())
+        .with_sinks(sinks)
+        .with_outbox_waker(outbox.waker())
+        .with_provider_webhooks(dispatcher.waker())
+        .with_buyer_notifier(buyer_notifier)
+        .with_catalog_cache(catalog);
 
-    let listener = EventListener::new(sui_client.clone(), &config.grpc_url, tx).await?;
-    let worker = EventWorker::new(repo.clone(), rx, redis_client).await?;
+    // The listener stops first so the worker can drain everything it handed
+    // over before being asked to stop itself, and the outbox and webhook
+    // dispatcher go last
+    let shutdown = CancellationToken::new();
+    let worker_shutdown = CancellationToken::new();
+    let outbox_shutdown = CancellationToken::new();
 
-    let server_handle = tokio::spawn(async move {
-        if let Err(e) = axum::serve(tcp_listener, app).await {
+    let server_shutdown = shutdown.clone();
+    let mut server_handle = tokio::spawn(async move {
+        if let Err(e) = axum::serve(tcp_listener, app)
+            .with_graceful_shutdown(server_shutdown.cancelled_owned())
+            .await
+        {
             tracing::error!("HTTP server error: {}", e);
         }
     });
 
-    let listener_handle = tokio::spawn(async move {
-        if let Err(e) = listener.run().await {
+    // The gRPC validator service only runs when given a port
+    let grpc_handle = match config.grpc_addr {
+        Some(addr) => {
+            let addr: std::net::SocketAddr = addr.parse()?;
+            info!("gRPC validator listening on {}", addr);
+            let grpc_shutdown = shutdown.clone();
+            Some(tokio::spawn(async move {
+                if let Err(e) = tonic::transport::Server::builder()
+                    .timeout(Duration::from_secs(10))
+                    .add_service(grpc)
+                    .serve_with_shutdown(addr, grpc_shutdown.cancelled_owned())
+                    .await
+                {
+                    tracing::error!("gRPC server error: {}", e);
+                }
+            }))
+        }
+        None => None,
+    };
+
+    let listener_shutdown = shutdown.clone();
+    let mut listener_handle = tokio::spawn(async move {
+        if let Err(e) = listener.run(listener_shutdown).await {
             tracing::error!("Event listener failed: {}", e);
         }
     });
 
-    let worker_handle = tokio::spawn(async move {
-        if let Err(e) = worker.run().await {
+    let worker_token = worker_shutdown.clone();
+    let mut worker_handle = tokio::spawn(async move {
+        if let Err(e) = worker.run(worker_token).await {
             tracing::error!("Event worker failed: {}", e);
         }
     });
 
-    let settlement_repo = repo.clone();
-    let settlement_client = sui_client.clone();
-    let settlement_handle = tokio::spawn(async move {
-        if let Err(e) = settlement_worker(
-            settlement_repo,
-            settlement_client,
-            config.settlement_interval,
-        )
-        .await
-        {
-            error!("Settlement worker failed: {}", e);
+    let outbox_token = outbox_shutdown.clone();
+    let outbox_handle = tokio::spawn(async move {
+        if let Err(e) = outbox.run(outbox_token).await {
+            tracing::error!("Outbox publisher failed: {}", e);
+        }
+    });
+
+    let dispatcher_token = outbox_shutdown.clone();
+    let dispatcher_handle = tokio::spawn(async move {
+        if let Err(e) = dispatcher.run(dispatcher_token).await {
+            tracing::error!("Provider webhook dispatcher failed: {}", e);
+        }
+    });
+
+    let cache_token = shutdown.clone();
+    let cache_handle = validation_cache.map(|cache| {
+        tokio::spawn(async move {
+            if let Err(e) = cache.run_invalidator(cache_token).await {
+                error!("Validation cache invalidator failed: {}", e);
+            }
+        })
+    });
+
+    let scheduler_token = shutdown.clone();
+    let mut scheduler_handle = tokio::spawn(async move {
+        if let Err(e) = scheduler.run(scheduler_token).await {
+            error!("Scheduler failed: {}", e);
         }
     });
 
@@ -80,55 +863,644 @@ async fn main() -> Result<()> {
         _ = signal::ctrl_c() => {
             info!("Received shutdown signal");
         }
-        result = server_handle => {
+        result = &mut server_handle => {
             match result {
                 Ok(_) => info!("HTTP server stopped"),
                 Err(e) => tracing::error!("HTTP server panicked: {}", e),
             }
         }
-        result = listener_handle => {
+        result = &mut listener_handle => {
             match result {
                 Ok(_) => info!("Event listener stopped"),
                 Err(e) => tracing::error!("Event listener panicked: {}", e),
             }
         }
-        result = worker_handle => {
+        result = &mut worker_handle => {
             match result {
                 Ok(_) => info!("Event worker stopped"),
                 Err(e) => tracing::error!("Event worker panicked: {}", e),
             }
         }
+        result = &mut scheduler_handle => tracing::error!("Scheduler stopped: {:?}", result),
+    }
 
-        result = settlement_handle => tracing::error!("Settlement worker stopped: {:?}", result),
+    info!("Shutting down gracefully");
+    shutdown.cancel();
+    if !listener_handle.is_finished() {
+        let _ = listener_handle.await;
+    }
+    worker_shutdown.cancel();
+    if !worker_handle.is_finished() {
+        let _ = worker_handle.await;
+    }
+    if !scheduler_handle.is_finished() {
+        let _ = scheduler_handle.await;
+    }
+    if let Some(handle) = cache_handle {
+        let _ = handle.await;
+    }
+    outbox_shutdown.cancel();
+    let _ = outbox_handle.await;
+    let _ = dispatcher_handle.await;
+    if !server_handle.is_finished() {
+        let _ = server_handle.await;
+    }
+    if let Some(handle) = grpc_handle {
+        let _ = handle.await;
+    }
+    info!("Shutdown complete");
+
+    Ok(())
+}
+
+/// `serve` against a SQLite `DATABASE_URL`: the indexer and the validator
+/// API only. Without Postgres and Redis there is no scheduler, so usage is
+/// charged locally but never settled on chain, and no sidecar notifications,
+/// webhooks or alerts from jobs.
+async fn run_lite_server() -> Result<()> {
+    info!("Starting Infrapass on SQLite");
+
+    std::env::var("API_KEY").expect("API_KEY must be set");
+    let storage = Arc::new(connect_sqlite().await?);
+    let sui_client = Arc::new(connect_sui().await?);
+    let alerts = Arc::new(AlertManager::new(AlertConfig::load()?));
+
+    let app = build_lite_router(LiteState {
+        storage: storage.clone(),
+        alerts: alerts.clone(),
+        decisions: Arc::new(DecisionLog::new(validation_log_size())),
+        idempotency: Arc::new(IdempotencyCache::in_memory(Duration::from_secs(
+            idempotency_ttl_secs(),
+        ))),
+    })
+    .layer(TraceLayer::new_for_http())
+    .layer(TimeoutLayer::new(Duration::from_secs(10)));
+
+    let addr = api_addr();
+    let tcp_listener = tokio::net::TcpListener::bind(&addr).await?;
+    info!("Validator API listening on {}", addr);
+
+    let (tx, rx) = mpsc::channel::<EventPayload>(256);
+    let listener = event_listener(sui_client, tx, alerts, storage.clone()).await?;
+    let worker = LiteWorker::new(storage, rx);
+
+    let shutdown = CancellationToken::new();
+    let worker_shutdown = CancellationToken::new();
+
+    let server_shutdown = shutdown.clone();
+    let mut server_handle = tokio::spawn(async move {
+        if let Err(e) = axum::serve(tcp_listener, app)
+            .with_graceful_shutdown(server_shutdown.cancelled_owned())
+            .await
+        {
+            error!("HTTP server error: {}", e);
+        }
+    });
+
+    let listener_shutdown = shutdown.clone();
+    let mut listener_handle = tokio::spawn(async move {
+        if let Err(e) = listener.run(listener_shutdown).await {
+            error!("Event listener failed: {}", e);
+        }
+    });
+
+    let worker_token = worker_shutdown.clone();
+    let mut worker_handle = tokio::spawn(async move {
+        if let Err(e) = worker.run(worker_token).await {
+            error!("Event worker failed: {}", e);
+        }
+    });
+
+    tokio::select! {
+        _ = signal::ctrl_c() => info!("Received shutdown signal"),
+        result = &mut server_handle => error!("HTTP server stopped: {:?}", result),
+        result = &mut listener_handle => error!("Event listener stopped: {:?}", result),
+        result = &mut worker_handle => error!("Event worker stopped: {:?}", result),
     }
 
     info!("Shutting down gracefully");
+    shutdown.cancel();
+    if !listener_handle.is_finished() {
+        let _ = listener_handle.await;
+    }
+    worker_shutdown.cancel();
+    if !worker_handle.is_finished() {
+        let _ = worker_handle.await;
+    }
+    if !server_handle.is_finished() {
+        let _ = server_handle.await;
+    }
+    info!("Shutdown complete");
+
     Ok(())
 }
 
+/// Settings only `serve` needs; the rest are read where they are used, so
+/// one-shot commands don't require the full server environment
 struct IConfig {
-    grpc_url: String,
-    database_url: String,
     redis_url: String,
     addr: String,
-    settlement_interval: u64,
+    /// Unset disables the gRPC validator service
+    grpc_addr: Option<String>,
+    settlement_schedule: JobSchedule,
+    /// Entitlements settled per transaction
+    settlement_batch_size: usize,
+    settlement_thresholds: SettlementThresholds,
+    buyer_notify_schedule: JobSchedule,
+    usage_rollup_schedule: JobSchedule,
+    metadata_refresh_schedule: JobSchedule,
+    /// Age at which cached service metadata is fetched again
+    metadata_max_age_secs: u64,
+    buyer_expiry_notice_secs: u64,
+    buyer_quota_notice_percent: u8,
+    prune_retention_days: u32,
+    /// Days of `blockchain_events` to keep; unset keeps all of it
+    event_retention_days: Option<u32>,
+    reconcile_sample: i64,
+    reconcile_repair: bool,
+    feed_cache_secs: u64,
+    /// Upper bound on how long a catalog response outlives a change the
+    /// worker did not see
+    catalog_cache_secs: u64,
+    /// Unset disables buyer contact capture
+    contact_encryption_key: Option<String>,
+    /// Unset stores provider webhook secrets unencrypted
+    webhook_encryption_key: Option<String>,
+    /// Unset disables Sign-in-with-Sui
+    session_secret: Option<String>,
+    session_ttl_secs: u64,
+    /// How long a cached validation is served; `None` disables the cache
+    validate_cache_ttl_secs: Option<u64>,
+    /// Requests per second per API key on `/validate` and `/record_usage`;
+    /// `None` disables the limit
+    rate_limit_rps: Option<f64>,
+    /// Defaults to one second's worth of requests
+    rate_limit_burst: Option<u64>,
+    /// How long a response to a request with an `Idempotency-Key` is replayed
+    idempotency_ttl_secs: u64,
 }
 
 fn load_config() -> IConfig {
     std::env::var("API_KEY").expect("API_KEY must be set");
     IConfig {
-        grpc_url: std::env::var("GRPC_URL").expect("GRPC_URL must be set"),
-        database_url: std::env::var("DATABASE_URL").expect("DATABASE_URL must be set"),
-        redis_url: std::env::var("BACKEND_REDIS_URL").expect("BACKEND_REDIS_URL must be set"),
-        addr: format!(
-            "0.0.0.0:{}",
-            std::env::var("API_PORT").unwrap_or_else(|_| "8088".to_string())
-        ),
-        settlement_interval: std::env::var("SETTLEMENT_INTERVAL")
-            .expect("SETTLEMENT_INTERVAL must be set")
+        redis_url: required_env("BACKEND_REDIS_URL"),
+        addr: api_addr(),
+        grpc_addr: std::env::var("GRPC_PORT")
+            .ok()
+            .map(|port| format!("0.0.0.0:{}", port)),
+        settlement_schedule: job_schedule("SETTLEMENT").unwrap_or_else(|| {
+            let secs = std::env::var("SETTLEMENT_INTERVAL")
+                .expect("SETTLEMENT_INTERVAL or SCHEDULE_SETTLEMENT must be set")
+                .parse::<u64>()
+                .expect("SETTLEMENT_INTERVAL must be a valid number");
+            JobSchedule::Every(Duration::from_secs(secs))
+        }),
+        settlement_batch_size: std::env::var("SETTLEMENT_BATCH_SIZE")
+            .ok()
+            .map(|v| {
+                v.parse::<usize>()
+                    .ok()
+                    .filter(|n| (1..=settlement::MAX_BATCH_SIZE).contains(n))
+                    .unwrap_or_else(|| {
+                        panic!(
+                            "SETTLEMENT_BATCH_SIZE must be between 1 and {}",
+                            settlement::MAX_BATCH_SIZE
+                        )
+                    })
+            })
+            .unwrap_or(settlement::DEFAULT_BATCH_SIZE),
+        settlement_thresholds: SettlementThresholds {
+            units: std::env::var("SETTLEMENT_THRESHOLD_UNITS").ok().map(|v| {
+                v.parse()
+                    .expect("SETTLEMENT_THRESHOLD_UNITS must be a valid number")
+            }),
+            entitlements: std::env::var("SETTLEMENT_THRESHOLD_ENTITLEMENTS")
+                .ok()
+                .map(|v| {
+                    v.parse()
+                        .expect("SETTLEMENT_THRESHOLD_ENTITLEMENTS must be a valid number")
+                }),
+        },
+        buyer_notify_schedule: job_schedule("BUYER_NOTIFY").unwrap_or_else(|| {
+            let secs = std::env::var("BUYER_NOTIFY_INTERVAL")
+                .unwrap_or_else(|_| "60".to_string())
+                .parse::<u64>()
+                .expect("BUYER_NOTIFY_INTERVAL must be a valid number");
+            JobSchedule::Every(Duration::from_secs(secs))
+        }),
+        usage_rollup_schedule: job_schedule("USAGE_ROLLUP").unwrap_or_else(|| {
+            let secs = std::env::var("USAGE_ROLLUP_INTERVAL")
+                .unwrap_or_else(|_| "300".to_string())
+                .parse::<u64>()
+                .expect("USAGE_ROLLUP_INTERVAL must be a valid number");
+            JobSchedule::Every(Duration::from_secs(secs))
+        }),
+        metadata_refresh_schedule: job_schedule("METADATA_REFRESH").unwrap_or_else(|| {
+            let secs = std::env::var("METADATA_REFRESH_INTERVAL")
+                .unwrap_or_else(|_| "600".to_string())
+                .parse::<u64>()
+                .expect("METADATA_REFRESH_INTERVAL must be a valid number");
+            JobSchedule::Every(Duration::from_secs(secs))
+        }),
+        metadata_max_age_secs: std::env::var("METADATA_MAX_AGE_SECS")
+            .unwrap_or_else(|_| "86400".to_string())
+            .parse::<u64>()
+            .expect("METADATA_MAX_AGE_SECS must be a valid number"),
+        buyer_expiry_notice_secs: std::env::var("BUYER_EXPIRY_NOTICE_SECS")
+            .unwrap_or_else(|_| "86400".to_string())
+            .parse::<u64>()
+            .expect("BUYER_EXPIRY_NOTICE_SECS must be a valid number"),
+        buyer_quota_notice_percent: std::env::var("BUYER_QUOTA_NOTICE_PERCENT")
+            .unwrap_or_else(|_| "80".to_string())
+            .parse::<u8>()
+            .ok()
+            .filter(|p| (1..=100).contains(p))
+            .expect("BUYER_QUOTA_NOTICE_PERCENT must be between 1 and 100"),
+        prune_retention_days: std::env::var("PRUNE_RETENTION_DAYS")
+            .unwrap_or_else(|_| "30".to_string())
+            .parse::<u32>()
+            .expect("PRUNE_RETENTION_DAYS must be a valid number"),
+        event_retention_days: std::env::var("EVENT_RETENTION_DAYS").ok().map(|v| {
+            v.parse::<u32>()
+                .ok()
+                .filter(|d| *d > 0)
+                .expect("EVENT_RETENTION_DAYS must be a positive number")
+        }),
+        reconcile_sample: std::env::var("RECONCILE_SAMPLE")
+            .unwrap_or_else(|_| "100".to_string())
+            .parse::<i64>()
+            .expect("RECONCILE_SAMPLE must be a valid number"),
+        reconcile_repair: std::env::var("RECONCILE_REPAIR")
+            .map(|v| v.parse().expect("RECONCILE_REPAIR must be true or false"))
+            .unwrap_or(false),
+        feed_cache_secs: std::env::var("FEED_CACHE_SECS")
+            .unwrap_or_else(|_| "60".to_string())
+            .parse::<u64>()
+            .expect("FEED_CACHE_SECS must be a valid number"),
+        catalog_cache_secs: std::env::var("CATALOG_CACHE_SECS")
+            .unwrap_or_else(|_| "60".to_string())
+            .parse::<u64>()
+            .expect("CATALOG_CACHE_SECS must be a valid number"),
+        contact_encryption_key: std::env::var("CONTACT_ENCRYPTION_KEY").ok(),
+        webhook_encryption_key: std::env::var("WEBHOOK_ENCRYPTION_KEY").ok(),
+        session_secret: std::env::var("SESSION_SECRET").ok(),
+        session_ttl_secs: std::env::var("SESSION_TTL_SECS")
+            .unwrap_or_else(|_| "900".to_string())
             .parse::<u64>()
-            .expect("SETTLEMENT_INTERVAL must be a valid number"),
+            .ok()
+            .filter(|secs| *secs > 0)
+            .expect("SESSION_TTL_SECS must be a positive number"),
+        // Invalidations name no tenant, so tenants' views can't share a cache
+        validate_cache_ttl_secs: Some(
+            std::env::var("VALIDATE_CACHE_TTL_SECS")
+                .unwrap_or_else(|_| "30".to_string())
+                .parse::<u64>()
+                .expect("VALIDATE_CACHE_TTL_SECS must be a valid number"),
+        )
+        .filter(|ttl| *ttl > 0 && !multi_tenant()),
+        rate_limit_rps: Some(
+            std::env::var("RATE_LIMIT_RPS")
+                .unwrap_or_else(|_| "0".to_string())
+                .parse::<f64>()
+                .expect("RATE_LIMIT_RPS must be a valid number"),
+        )
+        .filter(|rps| *rps > 0.0),
+        rate_limit_burst: std::env::var("RATE_LIMIT_BURST")
+            .ok()
+            .map(|v| v.parse().expect("RATE_LIMIT_BURST must be a valid number")),
+        idempotency_ttl_secs: idempotency_ttl_secs(),
+    }
+}
+
+/// `IDEMPOTENCY_TTL_SECS`, a day by default so a sidecar's retries are
+/// covered across a backend outage
+fn idempotency_ttl_secs() -> u64 {
+    std::env::var("IDEMPOTENCY_TTL_SECS")
+        .unwrap_or_else(|_| "86400".to_string())
+        .parse::<u64>()
+        .expect("IDEMPOTENCY_TTL_SECS must be a valid number")
+        .max(1)
+}
+
+/// `SCHEDULE_<JOB>` is a cron expression for one of the scheduler's jobs
+fn job_schedule(job: &str) -> Option<JobSchedule> {
+    let var = format!("SCHEDULE_{}", job);
+    std::env::var(&var).ok().map(|expr| {
+        JobSchedule::parse(&expr)
+            .unwrap_or_else(|e| panic!("{} must be a valid cron expression: {}", var, e))
+    })
+}
+
+/// `SCHEDULE_<JOB>_JITTER_SECS` delays each run of the job by a random
+/// amount up to that many seconds
+fn job_jitter(job: &str) -> Duration {
+    let var = format!("SCHEDULE_{}_JITTER_SECS", job);
+    std::env::var(&var)
+        .map(|s| {
+            s.parse::<u64>()
+                .unwrap_or_else(|_| panic!("{} must be a valid number", var))
+        })
+        .map(Duration::from_secs)
+        .unwrap_or_default()
+}
+
+fn api_addr() -> String {
+    format!(
+        "0.0.0.0:{}",
+        std::env::var("API_PORT").unwrap_or_else(|_| "8088".to_string())
+    )
+}
+
+fn expiry_grace_secs() -> u64 {
+    std::env::var("EXPIRY_GRACE_SECS")
+        .unwrap_or_else(|_| "0".to_string())
+        .parse::<u64>()
+        .expect("EXPIRY_GRACE_SECS must be a valid number")
+}
+
+/// `/validate` answers kept for the admin API
+fn validation_log_size() -> usize {
+    std::env::var("VALIDATION_LOG_SIZE")
+        .unwrap_or_else(|_| "1000".to_string())
+        .parse()
+        .expect("VALIDATION_LOG_SIZE must be a valid number")
+}
+
+/// Whether `/readyz` fails while the event listener is disconnected or
+/// stalled
+fn readyz_require_indexer() -> bool {
+    std::env::var("READYZ_REQUIRE_INDEXER")
+        .map(|v| {
+            v.parse()
+                .expect("READYZ_REQUIRE_INDEXER must be true or false")
+        })
+        .unwrap_or(false)
+}
+
+fn multi_tenant() -> bool {
+    std::env::var("MULTI_TENANT")
+        .map(|v| v.parse().expect("MULTI_TENANT must be true or false"))
+        .unwrap_or(false)
+}
+
+fn required_env(name: &str) -> String {
+    std::env::var(name).unwrap_or_else(|_| panic!("{} must be set", name))
+}
+
+/// Connects to Postgres and applies pending migrations, so every command
+/// runs against the current schema
+async fn connect_repo() -> Result<Arc<Repository>> {
+    let database_url = required_env("DATABASE_URL");
+    if StorageBackend::from_url(&database_url)? == StorageBackend::Sqlite {
+        bail!("This command needs a Postgres DATABASE_URL; SQLite only supports serve and migrate");
     }
+    let multi_tenant = multi_tenant();
+    let pool = Arc::new(create_pool(&database_url, multi_tenant).await?);
+    run_migrations(&pool).await?;
+
+    let mut repo = Repository::new(pool).with_expiry_grace(expiry_grace_secs());
+    // A read replica takes validation and public listings off the primary
+    if let Ok(read_url) = std::env::var("DATABASE_READ_URL") {
+        repo = repo.with_read_pool(Arc::new(create_pool(&read_url, multi_tenant).await?));
+    }
+
+    Ok(Arc::new(repo))
+}
+
+/// Opens the SQLite database and applies pending migrations
+async fn connect_sqlite() -> Result<SqliteStorage> {
+    Ok(SqliteStorage::connect(&required_env("DATABASE_URL"))
+        .await?
+        .with_expiry_grace(expiry_grace_secs()))
+}
+
+async fn connect_sui() -> Result<SuiClient> {
+    Ok(SuiClientBuilder::default()
+        .build(required_env("GRPC_URL"))
+        .await?)
+}
+
+/// Listener configured from the `INDEXER_*` and `PACKAGE_IDS` settings
+async fn event_listener(
+    sui_client: Arc<SuiClient>,
+    tx: mpsc::Sender<EventPayload>,
+    alerts: Arc<AlertManager>,
+    repo: Arc<dyn Storage>,
+) -> Result<EventListener> {
+    Ok(
+        EventListener::new(sui_client, &required_env("GRPC_URL"), tx, alerts, repo)
+            .await?
+            .with_packages(watched_packages())
+            .with_pipeline_depth(pipeline_depth())
+            .with_event_filter(event_filter())
+            .with_checkpoint_source(checkpoint_source())
+            .with_poll_interval(poll_interval())
+            .with_additional_endpoints(&extra_grpc_urls())?,
+    )
+}
+
+/// `PACKAGE_IDS` lists every published version of the package, e.g.
+/// `0xabc=1,0xdef=2`; defaults to the original `PACKAGE_ID`
+fn watched_packages() -> Vec<WatchedPackage> {
+    match std::env::var("PACKAGE_IDS") {
+        Ok(raw) => parse_watched_packages(&raw).expect("PACKAGE_IDS must be a valid package list"),
+        Err(_) => vec![WatchedPackage::original()],
+    }
+}
+
+/// The relayer is the wallet that signs settlements; `SPONSOR_ADDRESS` adds
+/// a gas sponsor to watch
+fn monitored_keys() -> Result<Vec<MonitoredKey>> {
+    let mut wallet = load_wallet_context(default_wallet_config()?)?;
+    let mut keys = vec![MonitoredKey {
+        role: KeyRole::Relayer,
+        address: wallet.active_address()?,
+    }];
+
+    if let Ok(sponsor) = std::env::var("SPONSOR_ADDRESS") {
+        keys.push(MonitoredKey {
+            role: KeyRole::Sponsor,
+            address: SuiAddress::from_str(&sponsor)
+                .map_err(|e| anyhow::anyhow!("Invalid SPONSOR_ADDRESS: {}", e))?,
+        });
+    }
+
+    Ok(keys)
+}
+
+/// `SUI_NETWORK` (`mainnet`, `testnet`, `devnet` or `localnet`, default
+/// `testnet`) picks the coin packages that WAL, USDC and USDT resolve to. It
+/// must match the network `GRPC_URL` points at.
+fn network() -> Network {
+    std::env::var("SUI_NETWORK")
+        .map(|s| {
+            Network::from_str(&s, true)
+                .expect("SUI_NETWORK must be mainnet, testnet, devnet or localnet")
+        })
+        .unwrap_or_default()
+}
+
+/// `INDEXER_EVENTS` narrows indexing to some modules or events, e.g.
+/// `payments` for a billing-only indexer
+fn event_filter() -> EventFilter {
+    match std::env::var("INDEXER_EVENTS") {
+        Ok(raw) => EventFilter::parse(&raw).expect("INDEXER_EVENTS must be a valid event list"),
+        Err(_) => EventFilter::all(),
+    }
+}
+
+/// `INDEXER_SOURCE` is `auto` (gRPC, polling if it can't connect), `grpc` or
+/// `polling`; polling asks for the tip every `INDEXER_POLL_INTERVAL_MS`
+fn checkpoint_source() -> CheckpointSource {
+    std::env::var("INDEXER_SOURCE")
+        .map(|s| {
+            s.parse()
+                .expect("INDEXER_SOURCE must be auto, grpc or polling")
+        })
+        .unwrap_or(CheckpointSource::Auto)
+}
+
+/// `INDEXER_EXTRA_GRPC_URLS` lists more full nodes to stream checkpoints
+/// from alongside `GRPC_URL`, comma separated
+fn extra_grpc_urls() -> Vec<String> {
+    std::env::var("INDEXER_EXTRA_GRPC_URLS")
+        .map(|raw| {
+            raw.split(',')
+                .map(str::trim)
+                .filter(|url| !url.is_empty())
+                .map(String::from)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn poll_interval() -> Duration {
+    std::env::var("INDEXER_POLL_INTERVAL_MS")
+        .map(|ms| {
+            Duration::from_millis(
+                ms.parse::<u64>()
+                    .expect("INDEXER_POLL_INTERVAL_MS must be a valid number"),
+            )
+        })
+        .unwrap_or(DEFAULT_POLL_INTERVAL)
+}
+
+/// `INDEXER_PIPELINE_DEPTH` checkpoints are decoded ahead of the one being
+/// committed; raise it to speed up backfills
+fn pipeline_depth() -> usize {
+    std::env::var("INDEXER_PIPELINE_DEPTH")
+        .map(|d| {
+            d.parse::<usize>()
+                .expect("INDEXER_PIPELINE_DEPTH must be a valid number")
+        })
+        .unwrap_or(DEFAULT_PIPELINE_DEPTH)
+}
+
+/// `INDEXER_WORKER_SHARDS` events are handled at once, each shard keeping
+/// one provider's events in order; raise it to speed up backfills
+fn worker_shards() -> usize {
+    std::env::var("INDEXER_WORKER_SHARDS")
+        .map(|n| {
+            n.parse::<usize>()
+                .expect("INDEXER_WORKER_SHARDS must be a valid number")
+        })
+        .unwrap_or(1)
+}
+
+/// `BACKFILL_BATCH_SIZE` events are committed together by each shard during
+/// `backfill` and `reindex`; 1 goes back to one transaction per event
+fn backfill_batch_size() -> usize {
+    std::env::var("BACKFILL_BATCH_SIZE")
+        .unwrap_or_else(|_| "500".to_string())
+        .parse::<usize>()
+        .expect("BACKFILL_BATCH_SIZE must be a valid number")
+}
+
+/// `BACKFILL_FLUSH_MS` bounds how long a shard waits for a batch to fill
+fn backfill_flush_interval() -> Duration {
+    let ms = std::env::var("BACKFILL_FLUSH_MS")
+        .unwrap_or_else(|_| "1000".to_string())
+        .parse::<u64>()
+        .expect("BACKFILL_FLUSH_MS must be a valid number");
+    Duration::from_millis(ms)
+}
+
+/// Sinks indexed events are copied to: Kafka when `INDEXER_KAFKA_BROKERS`
+/// is set and NATS JetStream when `INDEXER_NATS_URL` is, in builds with the
+/// matching feature
+async fn event_sinks() -> Result<Vec<Arc<dyn EventSink>>> {
+    let mut sinks = Vec::new();
+    if let Ok(brokers) = std::env::var("INDEXER_KAFKA_BROKERS") {
+        sinks.push(kafka_sink(&brokers)?);
+    }
+    if let Ok(url) = std::env::var("INDEXER_NATS_URL") {
+        sinks.push(nats_sink(&url).await?);
+    }
+    Ok(sinks)
+}
+
+/// Publishes to `INDEXER_KAFKA_TOPIC`
+#[cfg(feature = "kafka")]
+fn kafka_sink(brokers: &str) -> Result<Arc<dyn EventSink>> {
+    let topic = std::env::var("INDEXER_KAFKA_TOPIC")
+        .unwrap_or_else(|_| "infrapass.protocol-events".to_string());
+    info!("Publishing indexed events to Kafka topic {}", topic);
+    Ok(Arc::new(infrapass::events::kafka::KafkaSink::new(
+        brokers, topic,
+    )?))
+}
+
+#[cfg(not(feature = "kafka"))]
+fn kafka_sink(_brokers: &str) -> Result<Arc<dyn EventSink>> {
+    bail!(
+        "INDEXER_KAFKA_BROKERS is set but this build has no Kafka support; rebuild with --features kafka"
+    )
+}
+
+/// Publishes to the `INDEXER_NATS_STREAM` stream under
+/// `INDEXER_NATS_SUBJECT_PREFIX`. `INDEXER_NATS_STORAGE` (`file` or
+/// `memory`), `INDEXER_NATS_REPLICAS` and `INDEXER_NATS_ACK` set how durably
+/// events are kept.
+#[cfg(feature = "nats")]
+async fn nats_sink(url: &str) -> Result<Arc<dyn EventSink>> {
+    use async_nats::jetstream::stream::StorageType;
+    use infrapass::events::nats::{NatsSink, NatsSinkConfig};
+
+    let mut cfg = NatsSinkConfig::default();
+    if let Ok(stream) = std::env::var("INDEXER_NATS_STREAM") {
+        cfg.stream = stream;
+    }
+    if let Ok(prefix) = std::env::var("INDEXER_NATS_SUBJECT_PREFIX") {
+        cfg.subject_prefix = prefix;
+    }
+    if let Ok(storage) = std::env::var("INDEXER_NATS_STORAGE") {
+        cfg.storage = match storage.as_str() {
+            "file" => StorageType::File,
+            "memory" => StorageType::Memory,
+            _ => bail!("INDEXER_NATS_STORAGE must be file or memory"),
+        };
+    }
+    if let Ok(replicas) = std::env::var("INDEXER_NATS_REPLICAS") {
+        cfg.replicas = replicas
+            .parse()
+            .expect("INDEXER_NATS_REPLICAS must be a valid number");
+    }
+    if let Ok(ack) = std::env::var("INDEXER_NATS_ACK") {
+        cfg.await_ack = ack.parse().expect("INDEXER_NATS_ACK must be true or false");
+    }
+
+    info!(
+        "Publishing indexed events to NATS stream {} under {}",
+        cfg.stream, cfg.subject_prefix
+    );
+    Ok(Arc::new(NatsSink::connect(url, cfg).await?))
+}
+
+#[cfg(not(feature = "nats"))]
+async fn nats_sink(_url: &str) -> Result<Arc<dyn EventSink>> {
+    bail!(
+        "INDEXER_NATS_URL is set but this build has no NATS support; rebuild with --features nats"
+    )
 }
 
 fn init_tracing() {
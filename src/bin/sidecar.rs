@@ -3,9 +3,12 @@ use infrapass::{
     pubsub::subscriber::PubSubSubscriber,
     sidecar::{
         config::SidecarConfig,
+        fleet,
+        grpc::grpc_status_middleware,
         metrics,
         middleware::auth_middleware,
         proxy::{self, ProxyState},
+        sampling, usage,
     },
     utils::logs_fmt::UptimeSeconds,
 };
@@ -28,10 +31,39 @@ async fn main() -> anyhow::Result<()> {
 
     let cfg = SidecarConfig::load()?;
     cfg.validate()?;
-    info!(upstream = %cfg.upstream_url, port = cfg.port, "Sidecar starting");
 
     let state = Arc::new(ProxyState::new(cfg.clone()).await?);
     let pubsub_state = state.clone();
+    info!(
+        upstream = %cfg.upstream_url,
+        port = cfg.port,
+        version = state.build.version,
+        git_hash = state.build.git_hash,
+        config_hash = %state.build.config_hash,
+        instance_id = %cfg.instance_id,
+        "Sidecar starting"
+    );
+
+    if state.sampler.is_enabled() {
+        info!(flush_ms = cfg.sampling_flush_ms, "Usage sampling enabled");
+        tokio::spawn(sampling::run_flusher(state.clone()));
+    }
+
+    if state.usage.is_enabled() {
+        info!(
+            batch_size = cfg.usage_batch_size,
+            flush_ms = cfg.usage_flush_ms,
+            "Usage batching enabled"
+        );
+        tokio::spawn(usage::run_reporter(state.clone()));
+    }
+
+    if cfg.heartbeat_interval_secs > 0 {
+        tokio::spawn(fleet::run_heartbeat(
+            state.clone(),
+            Duration::from_secs(cfg.heartbeat_interval_secs),
+        ));
+    }
 
     let app = Router::new()
         .route("/metrics", axum::routing::get(metrics::metrics_handler))
@@ -45,6 +77,7 @@ async fn main() -> anyhow::Result<()> {
         .layer(TimeoutLayer::new(Duration::from_millis(
             cfg.request_timeout_ms,
         )))
+        .layer(middleware::from_fn(grpc_status_middleware))
         .with_state(state);
 
     let addr = format!("0.0.0.0:{}", cfg.port);
@@ -70,7 +103,11 @@ async fn health_handler(State(state): State<Arc<ProxyState>>) -> impl IntoRespon
     Json(serde_json::json!({
         "status": status,
         "redis": redis_ok,
-        "service": "infrapass-sidecar"
+        "service": "infrapass-sidecar",
+        "version": state.build.version,
+        "git_hash": state.build.git_hash,
+        "config_hash": state.build.config_hash,
+        "instance_id": state.cfg.instance_id,
     }))
 }
 
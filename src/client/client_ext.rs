@@ -2,31 +2,44 @@ use anyhow::{Result, anyhow};
 use async_trait::async_trait;
 use shared_crypto::intent::Intent;
 use sui_json_rpc_types::{
-    SuiData, SuiObjectDataOptions, SuiObjectResponseQuery, SuiTransactionBlockResponse,
-    SuiTransactionBlockResponseOptions,
+    SuiData, SuiObjectDataFilter, SuiObjectDataOptions, SuiObjectResponseQuery,
+    SuiTransactionBlockResponse, SuiTransactionBlockResponseOptions,
 };
 use sui_keys::key_identity::KeyIdentity;
 use sui_sdk::{SuiClient, types::transaction::Transaction, wallet_context::WalletContext};
 use sui_types::{
     base_types::{ObjectID, SuiAddress},
+    dynamic_field::DynamicFieldName,
+    parse_sui_struct_tag, parse_sui_type_tag,
     transaction::{ProgrammableTransaction, TransactionData},
     transaction_driver_types::ExecuteTransactionRequestType,
 };
 
 use crate::{
+    client::coin_metadata::{COIN_METADATA, CoinInfo},
     transactions::provider::ProviderState,
-    types::{coin::CoinType, types::TierInfo},
+    types::{coin::CoinType, entitlement::OnchainEntitlement, types::TierInfo},
     utils::{
-        coin::{extract_coin_type_from_tier_type, extract_price_from_content},
-        constants::PACKAGE_ID,
+        coin::{
+            extract_active_from_content, extract_coin_type_from_tier_type,
+            extract_price_from_content,
+        },
+        constants::{DEFAULT_GAS_BUDGET, ENTITLEMENT_STORE_ID, PACKAGE_ID},
     },
 };
 
+const MULTI_GET_CHUNK: usize = 50;
+
 #[async_trait]
 pub trait SuiClientExt {
     async fn get_tier_info(&self, tier_id: ObjectID) -> Result<TierInfo>;
     async fn get_balance(&self, owner: SuiAddress, coin_type: CoinType) -> Result<u128>;
+    async fn balances(&self, owner: SuiAddress) -> Result<Vec<(CoinType, u128)>>;
+    async fn coin_info(&self, coin_type: &CoinType) -> Result<CoinInfo>;
     async fn provider_state(&self, sender: SuiAddress) -> Result<ProviderState>;
+    async fn get_entitlements(&self, owner: SuiAddress) -> Result<Vec<OnchainEntitlement>>;
+    async fn get_entitlement(&self, entitlement_id: ObjectID)
+    -> Result<Option<OnchainEntitlement>>;
     async fn sign_and_execute_tx(
         &self,
         tx_data: TransactionData,
@@ -61,11 +74,13 @@ impl SuiClientExt for SuiClient {
         let coin_type = extract_coin_type_from_tier_type(&tier_type.to_string())?;
 
         let price = extract_price_from_content(&tier_data.content)?;
+        let active = extract_active_from_content(&tier_data.content)?;
 
         Ok(TierInfo {
             coin_type,
             price,
             tier_type_string: tier_type.to_string(),
+            active,
         })
     }
 
@@ -77,6 +92,29 @@ impl SuiClientExt for SuiClient {
         Ok(balance.total_balance)
     }
 
+    /// Balances for every supported coin type, fetched in a single call
+    async fn balances(&self, owner: SuiAddress) -> Result<Vec<(CoinType, u128)>> {
+        let all = self.coin_read_api().get_all_balances(owner).await?;
+
+        let mut balances = vec![];
+        for coin_type in CoinType::all() {
+            let type_tag = coin_type.to_type_tag()?.to_string();
+            let total = all
+                .iter()
+                .find(|b| b.coin_type == type_tag)
+                .map(|b| b.total_balance)
+                .unwrap_or(0);
+            balances.push((coin_type, total));
+        }
+
+        Ok(balances)
+    }
+
+    /// Decimals and symbol from the coin's on-chain metadata, cached per type
+    async fn coin_info(&self, coin_type: &CoinType) -> Result<CoinInfo> {
+        COIN_METADATA.get(self, coin_type).await
+    }
+
     async fn provider_state(&self, sender: SuiAddress) -> Result<ProviderState> {
         let objects = self
             .read_api()
@@ -137,6 +175,132 @@ impl SuiClientExt for SuiClient {
         Ok(provider_state)
     }
 
+    /// Entitlements held by `owner`, both those kept in the shared
+    /// `EntitlementStore` and any transferred directly to the owner.
+    /// The store is scanned in full, so this is meant for CLI and batch jobs
+    /// rather than hot paths.
+    async fn get_entitlements(&self, owner: SuiAddress) -> Result<Vec<OnchainEntitlement>> {
+        let bag_id = entitlement_bag_id(self).await?;
+
+        let mut field_ids = vec![];
+        let mut cursor = None;
+        loop {
+            let page = self
+                .read_api()
+                .get_dynamic_fields(bag_id, cursor, None)
+                .await?;
+            field_ids.extend(page.data.iter().map(|f| f.object_id));
+            if !page.has_next_page {
+                break;
+            }
+            cursor = page.next_cursor;
+        }
+
+        let mut entitlements = vec![];
+
+        for chunk in field_ids.chunks(MULTI_GET_CHUNK) {
+            let objects = self
+                .read_api()
+                .multi_get_object_with_options(
+                    chunk.to_vec(),
+                    SuiObjectDataOptions::new().with_content(),
+                )
+                .await?;
+
+            for obj in objects {
+                let Some(fields) = obj
+                    .data
+                    .and_then(|d| d.content)
+                    .and_then(|c| c.try_into_move())
+                    .map(|o| o.fields.to_json_value())
+                else {
+                    continue;
+                };
+
+                let Some(value) = fields.get("value") else {
+                    continue;
+                };
+
+                let ent = OnchainEntitlement::from_json(value)?;
+                if ent.holder == owner {
+                    entitlements.push(ent);
+                }
+            }
+        }
+
+        let entitlement_type =
+            parse_sui_struct_tag(&format!("{}::payments::Entitlement", PACKAGE_ID))?;
+        let mut cursor = None;
+        loop {
+            let page = self
+                .read_api()
+                .get_owned_objects(
+                    owner,
+                    Some(SuiObjectResponseQuery::new(
+                        Some(SuiObjectDataFilter::StructType(entitlement_type.clone())),
+                        Some(SuiObjectDataOptions::new().with_content()),
+                    )),
+                    cursor,
+                    None,
+                )
+                .await?;
+
+            for obj in page.data {
+                if let Some(fields) = obj
+                    .data
+                    .and_then(|d| d.content)
+                    .and_then(|c| c.try_into_move())
+                    .map(|o| o.fields.to_json_value())
+                {
+                    entitlements.push(OnchainEntitlement::from_json(&fields)?);
+                }
+            }
+
+            if !page.has_next_page {
+                break;
+            }
+            cursor = page.next_cursor;
+        }
+
+        Ok(entitlements)
+    }
+
+    /// One entitlement by ID, looked up in the `EntitlementStore` first and
+    /// then as an object of its own. `None` when it exists in neither.
+    async fn get_entitlement(
+        &self,
+        entitlement_id: ObjectID,
+    ) -> Result<Option<OnchainEntitlement>> {
+        let bag_id = entitlement_bag_id(self).await?;
+        let name = DynamicFieldName {
+            type_: parse_sui_type_tag("0x2::object::ID")?,
+            value: serde_json::Value::String(entitlement_id.to_string()),
+        };
+
+        let field = self
+            .read_api()
+            .get_dynamic_field_object(bag_id, name)
+            .await?;
+        if let Some(value) = field
+            .data
+            .and_then(|d| d.content)
+            .and_then(|c| c.try_into_move())
+            .and_then(|o| o.fields.to_json_value().get("value").cloned())
+        {
+            return Ok(Some(OnchainEntitlement::from_json(&value)?));
+        }
+
+        let obj = self
+            .read_api()
+            .get_object_with_options(entitlement_id, SuiObjectDataOptions::new().with_content())
+            .await?;
+        obj.data
+            .and_then(|d| d.content)
+            .and_then(|c| c.try_into_move())
+            .map(|o| OnchainEntitlement::from_json(&o.fields.to_json_value()))
+            .transpose()
+    }
+
     async fn sign_and_execute_tx(
         &self,
         tx_data: TransactionData,
@@ -182,9 +346,38 @@ impl SuiClientExt for SuiClient {
 
         let gas_price = self.read_api().get_reference_gas_price().await?;
 
-        let tx_data =
-            TransactionData::new_programmable(sender, vec![gas_object], pt, 10_000_000, gas_price);
+        let tx_data = TransactionData::new_programmable(
+            sender,
+            vec![gas_object],
+            pt,
+            DEFAULT_GAS_BUDGET,
+            gas_price,
+        );
 
         Ok(tx_data)
     }
 }
+
+/// ID of the `Bag` inside the shared `EntitlementStore`
+async fn entitlement_bag_id(client: &SuiClient) -> Result<ObjectID> {
+    let store_id = ObjectID::from_hex_literal(ENTITLEMENT_STORE_ID)?;
+    let store = client
+        .read_api()
+        .get_object_with_options(store_id, SuiObjectDataOptions::new().with_content())
+        .await?;
+
+    let bag_id = store
+        .data
+        .and_then(|d| d.content)
+        .and_then(|c| c.try_into_move())
+        .and_then(|o| {
+            o.fields
+                .to_json_value()
+                .pointer("/entitlements/id/id")
+                .and_then(|v| v.as_str())
+                .map(|s| s.to_string())
+        })
+        .ok_or_else(|| anyhow!("Could not read entitlement bag from store"))?;
+
+    Ok(ObjectID::from_hex_literal(&bag_id)?)
+}
@@ -0,0 +1,89 @@
+use std::{collections::HashMap, sync::Mutex};
+
+use anyhow::{Result, anyhow};
+use once_cell::sync::Lazy;
+use sui_sdk::SuiClient;
+
+use crate::types::{amount::AmountInput, coin::CoinType};
+
+/// Display data for a coin, as published in its on-chain `CoinMetadata`
+#[derive(Debug, Clone)]
+pub struct CoinInfo {
+    pub decimals: u8,
+    pub symbol: String,
+}
+
+impl CoinInfo {
+    /// Convert human-readable amount to smallest unit
+    pub fn to_smallest_unit(&self, amount: f64) -> u64 {
+        (amount * 10_f64.powi(self.decimals as i32)) as u64
+    }
+
+    /// Convert smallest unit to human-readable amount
+    pub fn from_smallest_unit(&self, amount: u64) -> f64 {
+        amount as f64 / 10_f64.powi(self.decimals as i32)
+    }
+
+    pub fn format_amount(&self, amount: u64) -> String {
+        format!("{} {}", self.from_smallest_unit(amount), self.symbol)
+    }
+
+    /// Converts a CLI amount to base units, checking any symbol suffix
+    /// matches this coin
+    pub fn parse_amount(&self, input: &AmountInput) -> Result<u64> {
+        if let Some(symbol) = &input.symbol {
+            if !symbol.eq_ignore_ascii_case(&self.symbol) {
+                return Err(anyhow!(
+                    "Amount is in {} but the coin is {}",
+                    symbol,
+                    self.symbol
+                ));
+            }
+        }
+
+        input.to_base_units(self.decimals)
+    }
+}
+
+/// Caches `CoinMetadata` lookups so each coin type is fetched once per process
+pub struct CoinMetadataCache {
+    entries: Mutex<HashMap<String, CoinInfo>>,
+}
+
+impl CoinMetadataCache {
+    fn new() -> Self {
+        Self {
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub async fn get(&self, client: &SuiClient, coin_type: &CoinType) -> Result<CoinInfo> {
+        let key = coin_type.to_type_tag()?.to_string();
+
+        if let Some(info) = self.entries.lock().unwrap().get(&key) {
+            return Ok(info.clone());
+        }
+
+        let info = match client.coin_read_api().get_coin_metadata(key.clone()).await? {
+            Some(meta) => CoinInfo {
+                decimals: meta.decimals,
+                symbol: meta.symbol,
+            },
+            // Built-in coins fall back to their known decimals
+            None => match coin_type {
+                CoinType::Custom(tag) => {
+                    return Err(anyhow!("No CoinMetadata found for {}", tag));
+                }
+                known => CoinInfo {
+                    decimals: known.decimals(),
+                    symbol: known.symbol().to_string(),
+                },
+            },
+        };
+
+        self.entries.lock().unwrap().insert(key, info.clone());
+        Ok(info)
+    }
+}
+
+pub static COIN_METADATA: Lazy<CoinMetadataCache> = Lazy::new(CoinMetadataCache::new);
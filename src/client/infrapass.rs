@@ -0,0 +1,168 @@
+use std::path::Path;
+
+use anyhow::{Result, bail};
+use sui_json_rpc_types::{
+    SuiObjectDataFilter, SuiObjectResponseQuery, SuiTransactionBlockResponse,
+};
+use sui_sdk::{SuiClient, SuiClientBuilder, wallet_context::WalletContext};
+use sui_types::{
+    base_types::{ObjectID, SuiAddress},
+    parse_sui_struct_tag,
+    transaction::TransactionData,
+};
+
+use crate::{
+    backend::verify::{self, OnchainService},
+    client::client_ext::SuiClientExt,
+    transactions::{
+        payments::purchase_entitlement_tx,
+        provider::ProviderState,
+        registry::{provider_create_service, register_provider_tx, update_service_metadata_tx},
+    },
+    types::{entitlement::OnchainEntitlement, types::TierInfo},
+    utils::{config::load_wallet_context, constants::PACKAGE_ID, preflight::Preflight},
+};
+
+/// Provider and buyer operations for a program acting as one wallet. Each
+/// write builds the transaction, checks the wallet can pay for it, signs it
+/// and fails unless it executed successfully.
+pub struct InfrapassClient {
+    sui: SuiClient,
+    wallet: WalletContext,
+}
+
+impl InfrapassClient {
+    pub fn new(sui: SuiClient, wallet: WalletContext) -> Self {
+        Self { sui, wallet }
+    }
+
+    /// Connects to `rpc_url` with the wallet in the Sui client config at
+    /// `wallet_config`
+    pub async fn connect(rpc_url: &str, wallet_config: impl AsRef<Path>) -> Result<Self> {
+        let sui = SuiClientBuilder::default().build(rpc_url).await?;
+        let wallet = load_wallet_context(wallet_config)?;
+        Ok(Self::new(sui, wallet))
+    }
+
+    pub fn sui(&self) -> &SuiClient {
+        &self.sui
+    }
+
+    /// The wallet's active address, which signs every transaction
+    pub fn address(&mut self) -> Result<SuiAddress> {
+        self.wallet.active_address()
+    }
+
+    /// The wallet's provider profile, `None` until it registers
+    pub async fn provider(&mut self) -> Result<Option<ProviderState>> {
+        let owner = self.address()?;
+        let cap_type = parse_sui_struct_tag(&format!("{}::registry::ProviderCap", PACKAGE_ID))?;
+        let caps = self
+            .sui
+            .read_api()
+            .get_owned_objects(
+                owner,
+                Some(SuiObjectResponseQuery::new(
+                    Some(SuiObjectDataFilter::StructType(cap_type)),
+                    None,
+                )),
+                None,
+                Some(1),
+            )
+            .await?;
+        if caps.data.is_empty() {
+            return Ok(None);
+        }
+
+        Ok(Some(self.sui.provider_state(owner).await?))
+    }
+
+    pub async fn register_provider(
+        &mut self,
+        metadata_uri: &str,
+    ) -> Result<SuiTransactionBlockResponse> {
+        let sender = self.address()?;
+        let data = register_provider_tx(&self.sui, sender, metadata_uri.to_string()).await?;
+        self.execute(data, Preflight::new()).await
+    }
+
+    /// The services the wallet's provider profile lists
+    pub async fn services(&mut self) -> Result<Vec<OnchainService>> {
+        let Some(provider) = self.provider().await? else {
+            return Ok(vec![]);
+        };
+
+        let mut services = vec![];
+        for service_id in provider.service_ids {
+            services.push(verify::read_service(&self.sui, service_id).await?);
+        }
+        Ok(services)
+    }
+
+    pub async fn create_service(
+        &mut self,
+        service_type: &str,
+        metadata_uri: &str,
+    ) -> Result<SuiTransactionBlockResponse> {
+        let sender = self.address()?;
+        let data = provider_create_service(
+            &self.sui,
+            sender,
+            service_type.to_string(),
+            metadata_uri.to_string(),
+        )
+        .await?;
+        self.execute(data, Preflight::new()).await
+    }
+
+    pub async fn update_service_metadata(
+        &mut self,
+        service_id: ObjectID,
+        metadata_uri: &str,
+    ) -> Result<SuiTransactionBlockResponse> {
+        let sender = self.address()?;
+        let data =
+            update_service_metadata_tx(&self.sui, sender, service_id, metadata_uri.to_string())
+                .await?;
+        self.execute(data, Preflight::new()).await
+    }
+
+    pub async fn tier(&self, tier_id: ObjectID) -> Result<TierInfo> {
+        self.sui.get_tier_info(tier_id).await
+    }
+
+    /// Entitlements the wallet holds, including expired and used up ones
+    pub async fn entitlements(&mut self) -> Result<Vec<OnchainEntitlement>> {
+        let owner = self.address()?;
+        self.sui.get_entitlements(owner).await
+    }
+
+    /// Buys one entitlement to `tier_id` at the tier's current price
+    pub async fn purchase(
+        &mut self,
+        service_id: ObjectID,
+        tier_id: ObjectID,
+    ) -> Result<SuiTransactionBlockResponse> {
+        let sender = self.address()?;
+        let tier = self.tier(tier_id).await?;
+        let data =
+            purchase_entitlement_tx(&self.sui, sender, service_id, tier_id, tier.price).await?;
+        self.execute(data, Preflight::new().payment(tier.coin_type, tier.price))
+            .await
+    }
+
+    async fn execute(
+        &mut self,
+        data: TransactionData,
+        preflight: Preflight,
+    ) -> Result<SuiTransactionBlockResponse> {
+        let sender = self.address()?;
+        preflight.check(&self.sui, sender).await?;
+
+        let resp = self.sui.sign_and_execute_tx(data, &mut self.wallet).await?;
+        if resp.status_ok() != Some(true) {
+            bail!("Transaction {} failed", resp.digest);
+        }
+        Ok(resp)
+    }
+}
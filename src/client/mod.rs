@@ -1 +1,4 @@
 pub mod client_ext;
+pub mod coin_metadata;
+pub mod infrapass;
+pub mod price_quote;
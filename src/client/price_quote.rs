@@ -0,0 +1,189 @@
+use std::{collections::HashMap, sync::Mutex, time::Duration};
+
+use anyhow::{Result, anyhow};
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+use tracing::debug;
+
+use crate::client::coin_metadata::CoinInfo;
+
+const DEFAULT_PYTH_URL: &str = "https://hermes.pyth.network";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PriceSourceKind {
+    #[default]
+    Pyth,
+    Http,
+}
+
+/// Fiat price source, read from `PRICE_*` environment variables.
+/// Quotes are disabled unless feeds or an HTTP URL are configured.
+#[derive(Debug, Clone, Deserialize)]
+pub struct PriceQuoteConfig {
+    #[serde(default)]
+    pub source: PriceSourceKind,
+
+    /// Pyth Hermes endpoint
+    #[serde(default = "default_pyth_url")]
+    pub pyth_url: String,
+
+    /// Comma separated `SYMBOL=feed_id` pairs for Pyth USD feeds
+    /// e.g. "SUI=0x23d7...,USDC=0xeaa0..."
+    #[serde(default)]
+    pub feeds: String,
+
+    /// HTTP price endpoint with a `{symbol}` placeholder
+    pub http_url: Option<String>,
+
+    /// JSON pointer to the USD price in the HTTP response
+    #[serde(default = "default_http_pointer")]
+    pub http_pointer: String,
+}
+
+impl PriceQuoteConfig {
+    pub fn load() -> Result<Self> {
+        let cfg = config::Config::builder()
+            .add_source(config::Environment::with_prefix("PRICE"))
+            .build()?
+            .try_deserialize()?;
+        Ok(cfg)
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        match self.source {
+            PriceSourceKind::Pyth => !self.feeds.trim().is_empty(),
+            PriceSourceKind::Http => self.http_url.is_some(),
+        }
+    }
+
+    fn feed_id(&self, symbol: &str) -> Option<&str> {
+        self.feeds.split(',').find_map(|pair| {
+            let (sym, id) = pair.split_once('=')?;
+            sym.trim().eq_ignore_ascii_case(symbol).then(|| id.trim())
+        })
+    }
+}
+
+/// Approximate USD prices, fetched once per symbol per process
+pub struct PriceQuoter {
+    cfg: PriceQuoteConfig,
+    http: reqwest::Client,
+    prices: Mutex<HashMap<String, f64>>,
+}
+
+impl PriceQuoter {
+    pub fn new(cfg: PriceQuoteConfig) -> Self {
+        let http = reqwest::Client::builder()
+            .timeout(Duration::from_secs(3))
+            .build()
+            .expect("Failed to build price quote HTTP client");
+
+        Self {
+            cfg,
+            http,
+            prices: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub async fn usd_price(&self, symbol: &str) -> Result<f64> {
+        let symbol = symbol.to_uppercase();
+        if let Some(price) = self.prices.lock().unwrap().get(&symbol) {
+            return Ok(*price);
+        }
+
+        let price = match self.cfg.source {
+            PriceSourceKind::Pyth => self.fetch_pyth(&symbol).await?,
+            PriceSourceKind::Http => self.fetch_http(&symbol).await?,
+        };
+
+        self.prices.lock().unwrap().insert(symbol, price);
+        Ok(price)
+    }
+
+    /// USD value of `amount` base units, or `None` when no quote is available
+    pub async fn usd_value(&self, coin_info: &CoinInfo, amount: u64) -> Option<f64> {
+        match self.usd_price(&coin_info.symbol).await {
+            Ok(price) => Some(coin_info.from_smallest_unit(amount) * price),
+            Err(e) => {
+                debug!(symbol = %coin_info.symbol, error = %e, "No USD quote");
+                None
+            }
+        }
+    }
+
+    async fn fetch_pyth(&self, symbol: &str) -> Result<f64> {
+        let feed_id = self
+            .cfg
+            .feed_id(symbol)
+            .ok_or_else(|| anyhow!("No Pyth feed configured for {}", symbol))?;
+
+        let url = format!(
+            "{}/v2/updates/price/latest?ids[]={}&parsed=true",
+            self.cfg.pyth_url.trim_end_matches('/'),
+            feed_id
+        );
+        let body: serde_json::Value = self.http.get(&url).send().await?.json().await?;
+
+        let price = body
+            .pointer("/parsed/0/price")
+            .ok_or_else(|| anyhow!("Malformed Pyth response for {}", symbol))?;
+        let mantissa: i64 = price
+            .get("price")
+            .and_then(|v| v.as_str())
+            .and_then(|s| s.parse().ok())
+            .ok_or_else(|| anyhow!("Missing Pyth price for {}", symbol))?;
+        let expo = price
+            .get("expo")
+            .and_then(|v| v.as_i64())
+            .ok_or_else(|| anyhow!("Missing Pyth exponent for {}", symbol))?;
+
+        Ok(mantissa as f64 * 10_f64.powi(expo as i32))
+    }
+
+    async fn fetch_http(&self, symbol: &str) -> Result<f64> {
+        let template = self
+            .cfg
+            .http_url
+            .as_deref()
+            .ok_or_else(|| anyhow!("PRICE_HTTP_URL is not set"))?;
+        let url = template.replace("{symbol}", symbol);
+
+        let body: serde_json::Value = self.http.get(&url).send().await?.json().await?;
+        let value = body
+            .pointer(&self.cfg.http_pointer)
+            .ok_or_else(|| anyhow!("No price at {} for {}", self.cfg.http_pointer, symbol))?;
+
+        value
+            .as_f64()
+            .or_else(|| value.as_str().and_then(|s| s.parse().ok()))
+            .ok_or_else(|| anyhow!("Price for {} is not a number", symbol))
+    }
+}
+
+/// Formats a USD suffix such as ` (~$12.34)`, empty when no quote is available
+pub async fn usd_suffix(coin_info: &CoinInfo, amount: u64) -> String {
+    let Some(quoter) = PRICE_QUOTES.as_ref() else {
+        return String::new();
+    };
+
+    match quoter.usd_value(coin_info, amount).await {
+        Some(usd) => format!(" (~${:.2})", usd),
+        None => String::new(),
+    }
+}
+
+pub static PRICE_QUOTES: Lazy<Option<PriceQuoter>> = Lazy::new(|| {
+    PriceQuoteConfig::load()
+        .ok()
+        .filter(PriceQuoteConfig::is_enabled)
+        .map(PriceQuoter::new)
+});
+
+fn default_pyth_url() -> String {
+    DEFAULT_PYTH_URL.to_string()
+}
+
+fn default_http_pointer() -> String {
+    "/price".to_string()
+}
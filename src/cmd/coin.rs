@@ -0,0 +1,264 @@
+use std::{str::FromStr, time::Duration};
+
+use anyhow::Result;
+use clap::Subcommand;
+use sui_json_rpc_types::SuiTransactionBlockEffectsAPI;
+use sui_sdk::{SuiClient, wallet_context::WalletContext};
+use sui_types::base_types::SuiAddress;
+use tracing::info;
+
+use crate::{
+    backend::spend::parse_lookback,
+    client::client_ext::SuiClientExt,
+    transactions::coin::{
+        MAX_COINS_PER_CONSOLIDATION, consolidate_coins_tx, fragmented_coin_types,
+        mint_test_token_tx, request_sui_from_faucet,
+    },
+    types::{amount::AmountInput, coin::CoinType},
+    utils::{
+        coin::resolve_amount,
+        config::{default_wallet_config, load_wallet_context},
+        handle_response,
+        network::Network,
+        preflight::Preflight,
+    },
+};
+
+#[derive(Subcommand)]
+pub enum CoinCommands {
+    /// Get testnet SUI for gas, or mint test tokens (WAL, USDC, USDT).
+    /// Minting needs the token's TreasuryCap, so only the test token
+    /// deployer can mint, to any `--recipient`
+    Faucet {
+        /// Coin to request (sui, wal, usdc, usdt)
+        #[arg(short, long)]
+        coin: String,
+
+        /// Amount in whole tokens, e.g. 100 or 100USDC (ignored for SUI)
+        #[arg(short, long, default_value = "100")]
+        amount: AmountInput,
+
+        /// Recipient address (defaults to the active wallet address)
+        #[arg(short, long)]
+        recipient: Option<String>,
+
+        /// Faucet endpoint used for SUI requests (defaults to the selected network's faucet)
+        #[arg(long)]
+        faucet_url: Option<String>,
+    },
+
+    /// Merge fragmented coin objects, e.g. accumulated payments, into
+    /// larger ones
+    Consolidate {
+        /// Coin type (SUI, WAL, USDC, USDT or a full coin type); every coin
+        /// type in the wallet when omitted
+        #[arg(short, long)]
+        coin_type: Option<String>,
+
+        /// Only merge coin objects holding less than this amount, e.g. 1SUI
+        #[arg(long, requires = "coin_type")]
+        below: Option<AmountInput>,
+
+        /// Skip coin types with fewer coin objects than this to merge
+        #[arg(long, default_value_t = 2)]
+        min_coins: usize,
+
+        /// Transactions sent per run at most
+        #[arg(long, default_value_t = 10)]
+        max_txs: usize,
+
+        /// Pause between transactions, in milliseconds
+        #[arg(long, default_value_t = 2000)]
+        delay_ms: u64,
+
+        /// Stop the run once it has spent this much SUI on gas, e.g. 0.05SUI
+        #[arg(long)]
+        gas_budget: Option<AmountInput>,
+
+        /// Keep running, consolidating again at this interval (e.g. 6h, 1d)
+        #[arg(long)]
+        every: Option<String>,
+    },
+}
+
+/// Limits shared by every coin type in one consolidation run
+struct ConsolidationRun {
+    below: Option<u64>,
+    min_coins: usize,
+    max_txs: usize,
+    delay: Duration,
+    gas_budget: Option<u64>,
+    txs: usize,
+    gas_spent: u64,
+}
+
+impl ConsolidationRun {
+    fn exhausted(&self) -> bool {
+        self.txs >= self.max_txs || self.gas_budget.is_some_and(|b| self.gas_spent >= b)
+    }
+
+    /// Merges `coin_type` until nothing is left to merge or a limit is hit
+    async fn consolidate(
+        &mut self,
+        client: &SuiClient,
+        wallet: &mut WalletContext,
+        sender: SuiAddress,
+        coin_type: &CoinType,
+    ) -> Result<()> {
+        while !self.exhausted() {
+            if self.txs > 0 {
+                tokio::time::sleep(self.delay).await;
+            }
+
+            let Some((tx_data, merged)) =
+                consolidate_coins_tx(client, sender, coin_type, self.below, self.min_coins).await?
+            else {
+                info!("Nothing to consolidate for {}", coin_type);
+                return Ok(());
+            };
+
+            info!("Merging {} {} coin objects ...", merged, coin_type);
+            let resp = client.sign_and_execute_tx(tx_data, wallet).await?;
+            handle_response(&resp);
+            self.txs += 1;
+
+            let effects = resp
+                .effects
+                .as_ref()
+                .ok_or_else(|| anyhow::anyhow!("Consolidation returned no effects"))?;
+            // Deleting the merged objects earns a storage rebate, so the
+            // net cost may be negative
+            self.gas_spent += effects.gas_cost_summary().net_gas_usage().max(0) as u64;
+            if resp.status_ok() != Some(true) {
+                anyhow::bail!("Consolidating {} failed, stopping", coin_type);
+            }
+
+            if merged < MAX_COINS_PER_CONSOLIDATION {
+                return Ok(());
+            }
+        }
+
+        info!(
+            "Stopped after {} transactions and {} MIST of gas; run again to merge the rest",
+            self.txs, self.gas_spent
+        );
+        Ok(())
+    }
+}
+
+impl CoinCommands {
+    pub async fn execute(self, client: &SuiClient) -> Result<()> {
+        match self {
+            CoinCommands::Faucet {
+                coin,
+                amount,
+                recipient,
+                faucet_url,
+            } => {
+                let coin_type = CoinType::from_str(&coin)?;
+                let default_path = default_wallet_config()?;
+                let mut wallet = load_wallet_context(default_path)?;
+                let sender = wallet.active_address()?;
+                let recipient = match recipient {
+                    Some(addr) => SuiAddress::from_str(&addr)?,
+                    None => sender,
+                };
+
+                if matches!(coin_type, CoinType::SUI) {
+                    let network = Network::current();
+                    let faucet_url = faucet_url
+                        .or_else(|| network.faucet_url().map(str::to_string))
+                        .ok_or_else(|| anyhow::anyhow!("No SUI faucet on {}", network))?;
+                    info!("Requesting SUI from {} for {} ...", faucet_url, recipient);
+                    request_sui_from_faucet(&faucet_url, recipient).await?;
+                    info!("Faucet request accepted; gas coins should arrive shortly");
+                    return Ok(());
+                }
+
+                let base_units = resolve_amount(client, &coin_type, &amount).await?;
+                Preflight::new().check(client, sender).await?;
+                info!("Minting {} to {} ...", amount, recipient);
+
+                let tx_data =
+                    mint_test_token_tx(client, sender, coin_type, base_units, recipient).await?;
+                let resp = client.sign_and_execute_tx(tx_data, &mut wallet).await?;
+                handle_response(&resp);
+
+                Ok(())
+            }
+            CoinCommands::Consolidate {
+                coin_type,
+                below,
+                min_coins,
+                max_txs,
+                delay_ms,
+                gas_budget,
+                every,
+            } => {
+                let coin_type = coin_type.as_deref().map(CoinType::from_str).transpose()?;
+                let every = every
+                    .as_deref()
+                    .map(parse_lookback)
+                    .transpose()
+                    .map_err(|e| anyhow::anyhow!("invalid --every: {}", e))?;
+                let below = match (&coin_type, &below) {
+                    (Some(coin_type), Some(below)) => {
+                        Some(resolve_amount(client, coin_type, below).await?)
+                    }
+                    _ => None,
+                };
+                let gas_budget = match &gas_budget {
+                    Some(budget) => Some(resolve_amount(client, &CoinType::SUI, budget).await?),
+                    None => None,
+                };
+
+                let default_path = default_wallet_config()?;
+                let mut wallet = load_wallet_context(default_path)?;
+                let sender = wallet.active_address()?;
+
+                loop {
+                    Preflight::new().check(client, sender).await?;
+
+                    let coin_types = match &coin_type {
+                        Some(coin_type) => vec![coin_type.clone()],
+                        None => fragmented_coin_types(client, sender, min_coins)
+                            .await?
+                            .into_iter()
+                            .map(|(coin_type, count)| {
+                                info!("{} is held in {} coin objects", coin_type, count);
+                                coin_type
+                            })
+                            .collect(),
+                    };
+
+                    let mut run = ConsolidationRun {
+                        below,
+                        min_coins,
+                        max_txs,
+                        delay: Duration::from_millis(delay_ms),
+                        gas_budget,
+                        txs: 0,
+                        gas_spent: 0,
+                    };
+                    for coin_type in &coin_types {
+                        if run.exhausted() {
+                            break;
+                        }
+                        run.consolidate(client, &mut wallet, sender, coin_type)
+                            .await?;
+                    }
+                    info!(
+                        "Consolidation done: {} transactions, {} MIST of gas",
+                        run.txs, run.gas_spent
+                    );
+
+                    let Some(every) = every else {
+                        return Ok(());
+                    };
+                    info!("Next consolidation in {}s", every.num_seconds());
+                    tokio::time::sleep(every.to_std()?).await;
+                }
+            }
+        }
+    }
+}
@@ -1,14 +1,17 @@
+pub mod coin;
 pub mod payment;
 pub mod pricing;
 pub mod query;
 pub mod regsitry;
+pub mod templates;
 
 use clap::{Parser, Subcommand};
 
 use crate::cmd::{
-    payment::PaymentCommands, pricing::PricingCommands, query::QueryCommands,
+    coin::CoinCommands, payment::PaymentCommands, pricing::PricingCommands, query::QueryCommands,
     regsitry::RegistryCommands,
 };
+use crate::utils::network::Network;
 
 #[derive(Parser)]
 #[command(name = "infrapass")]
@@ -24,6 +27,10 @@ pub struct Cli {
     // RPC URL
     #[arg(long, global = true)]
     pub rpc_url: Option<String>,
+
+    /// Network used to resolve coin addresses and the default RPC URL
+    #[arg(long, global = true, value_enum, default_value_t = Network::Testnet)]
+    pub network: Network,
 }
 
 #[derive(Subcommand)]
@@ -43,4 +50,8 @@ pub enum Commands {
     /// Query blockchain data
     #[command(subcommand)]
     Query(QueryCommands),
+
+    /// Coin utilities (test token faucet, consolidation)
+    #[command(subcommand)]
+    Coin(CoinCommands),
 }
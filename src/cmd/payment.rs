@@ -1,14 +1,21 @@
-use anyhow::Result;
+use anyhow::{Result, anyhow};
 use clap::Subcommand;
-use sui_sdk::SuiClient;
-use sui_types::base_types::ObjectID;
+use shared_crypto::intent::{Intent, PersonalMessage};
+use sui_keys::key_identity::KeyIdentity;
+use sui_sdk::{SuiClient, wallet_context::WalletContext};
+use sui_types::{base_types::ObjectID, crypto::EncodeDecodeBase64};
+use tracing::info;
 
 use crate::{
+    backend::contacts,
     client::client_ext::SuiClientExt,
     transactions::payments::purchase_entitlement_tx,
+    types::amount::AmountInput,
     utils::{
+        coin::resolve_amount,
         config::{default_wallet_config, load_wallet_context},
         handle_response,
+        preflight::Preflight,
     },
 };
 
@@ -24,9 +31,38 @@ pub enum PaymentCommands {
         #[arg(short, long)]
         tier_id: String,
 
-        /// Payment amount in smallest unit
+        /// Payment amount in whole tokens, e.g. 10.5 or 10.5SUI
         #[arg(short, long)]
-        amount: u64,
+        amount: AmountInput,
+
+        /// Infrapass API base URL, used to suggest a replacement when the
+        /// tier is retired (defaults to INFRAPASS_API_URL)
+        #[arg(long)]
+        api_url: Option<String>,
+    },
+    /// Share a contact (e.g. an email) with the provider of an entitlement
+    LinkContact {
+        /// Entitlement object ID
+        #[arg(short, long)]
+        entitlement_id: String,
+
+        /// Contact the provider may reach you at
+        #[arg(short, long)]
+        contact: String,
+
+        /// Infrapass API base URL (defaults to INFRAPASS_API_URL)
+        #[arg(long)]
+        api_url: Option<String>,
+    },
+    /// Delete the contact shared for an entitlement
+    UnlinkContact {
+        /// Entitlement object ID
+        #[arg(short, long)]
+        entitlement_id: String,
+
+        /// Infrapass API base URL (defaults to INFRAPASS_API_URL)
+        #[arg(long)]
+        api_url: Option<String>,
     },
 }
 
@@ -37,12 +73,22 @@ impl PaymentCommands {
                 service_id,
                 tier_id,
                 amount,
+                api_url,
             } => {
                 let default_path = default_wallet_config()?;
                 let mut wallet = load_wallet_context(default_path)?;
                 let sender = wallet.active_address()?;
                 let service = ObjectID::from_hex_literal(&service_id)?;
                 let tier = ObjectID::from_hex_literal(&tier_id)?;
+                let tier_info = client.get_tier_info(tier).await?;
+                if !tier_info.active {
+                    return Err(retired_tier_error(&service_id, &tier_id, api_url).await);
+                }
+                let amount = resolve_amount(client, &tier_info.coin_type, &amount).await?;
+                Preflight::new()
+                    .payment(tier_info.coin_type, amount)
+                    .check(client, sender)
+                    .await?;
                 let tx_data =
                     purchase_entitlement_tx(client, sender, service, tier, amount).await?;
                 let resp = client.sign_and_execute_tx(tx_data, &mut wallet).await?;
@@ -51,6 +97,122 @@ impl PaymentCommands {
 
                 Ok(())
             }
+            PaymentCommands::LinkContact {
+                entitlement_id,
+                contact,
+                api_url,
+            } => {
+                let mut wallet = load_wallet_context(default_wallet_config()?)?;
+                let timestamp_ms = chrono::Utc::now().timestamp_millis();
+                let message = contacts::link_message(&entitlement_id, &contact, timestamp_ms);
+                let signature = sign_message(&mut wallet, message).await?;
+
+                let url = format!("{}/contacts", resolve_api_url(api_url)?);
+                reqwest::Client::new()
+                    .post(&url)
+                    .json(&serde_json::json!({
+                        "entitlement_id": entitlement_id,
+                        "contact": contact,
+                        "timestamp_ms": timestamp_ms,
+                        "signature": signature,
+                    }))
+                    .send()
+                    .await?
+                    .error_for_status()?;
+
+                info!("Contact linked to entitlement {}", entitlement_id);
+                Ok(())
+            }
+            PaymentCommands::UnlinkContact {
+                entitlement_id,
+                api_url,
+            } => {
+                let mut wallet = load_wallet_context(default_wallet_config()?)?;
+                let timestamp_ms = chrono::Utc::now().timestamp_millis();
+                let message = contacts::unlink_message(&entitlement_id, timestamp_ms);
+                let signature = sign_message(&mut wallet, message).await?;
+
+                let url = format!("{}/contacts/{}", resolve_api_url(api_url)?, entitlement_id);
+                reqwest::Client::new()
+                    .delete(&url)
+                    .json(&serde_json::json!({
+                        "timestamp_ms": timestamp_ms,
+                        "signature": signature,
+                    }))
+                    .send()
+                    .await?
+                    .error_for_status()?;
+
+                info!("Contact deleted for entitlement {}", entitlement_id);
+                Ok(())
+            }
         }
     }
 }
+
+/// Signs `message` as a personal message with the active address, base64 encoded
+async fn sign_message(wallet: &mut WalletContext, message: String) -> Result<String> {
+    let sender = wallet.active_address()?;
+    let signature = wallet
+        .sign_secure(
+            &KeyIdentity::Address(sender),
+            &PersonalMessage {
+                message: message.into_bytes(),
+            },
+            Intent::personal_message(),
+        )
+        .await?;
+
+    Ok(signature.encode_base64())
+}
+
+/// Error for a purchase on a retired tier, suggesting the replacement the
+/// provider set for it when the backend can be reached
+async fn retired_tier_error(
+    service_id: &str,
+    tier_id: &str,
+    api_url: Option<String>,
+) -> anyhow::Error {
+    let retired = format!("Tier {} is retired and can no longer be purchased", tier_id);
+
+    let replacement = match resolve_api_url(api_url) {
+        Ok(api_url) => fetch_tier_replacement(&api_url, tier_id).await,
+        Err(_) => None,
+    };
+    match replacement {
+        Some((replacement_id, name)) => anyhow!(
+            "{}\n  hint: the provider replaced it with {} ({}); run `infrapass-cli payment purchase --service-id {} --tier-id {} --amount <AMOUNT>`",
+            retired,
+            name,
+            replacement_id,
+            service_id,
+            replacement_id
+        ),
+        None => anyhow!("{}", retired),
+    }
+}
+
+/// ID and name of the tier replacing `tier_id`, if the backend has one
+async fn fetch_tier_replacement(api_url: &str, tier_id: &str) -> Option<(String, String)> {
+    let url = format!("{}/tiers/{}/replacement", api_url, tier_id);
+    let resp = reqwest::get(&url).await.ok()?.error_for_status().ok()?;
+    let body: serde_json::Value = resp.json().await.ok()?;
+
+    let replacement = &body["replacement"];
+    Some((
+        replacement["tier_id"].as_str()?.to_string(),
+        replacement["tier_name"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string(),
+    ))
+}
+
+fn resolve_api_url(api_url: Option<String>) -> Result<String> {
+    let api_url = match api_url {
+        Some(url) => url,
+        None => std::env::var("INFRAPASS_API_URL")
+            .map_err(|_| anyhow!("Pass --api-url or set INFRAPASS_API_URL"))?,
+    };
+    Ok(api_url.trim_end_matches('/').to_string())
+}
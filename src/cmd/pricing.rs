@@ -1,18 +1,24 @@
+use std::path::PathBuf;
+
 use anyhow::{Ok, Result};
 use clap::Subcommand;
 use sui_sdk::SuiClient;
 use sui_types::base_types::ObjectID;
+use tracing::info;
 
 use crate::{
     client::client_ext::SuiClientExt,
+    cmd::templates::{PricingManifest, TemplateCommands},
     transactions::pricing::{
         add_tier_to_service_tx, create_pricing_tier_tx, deactivate_tier_tx, reactivate_tier_tx,
         remove_tier_from_service_tx, update_tier_price_tx,
     },
-    types::types::TierConfigInput,
+    types::{amount::AmountInput, coin::CoinType, types::TierConfigInput},
     utils::{
+        coin::resolve_amount,
         config::{default_wallet_config, load_wallet_context},
         handle_response,
+        preflight::Preflight,
     },
 };
 
@@ -32,13 +38,13 @@ pub enum PricingCommands {
         #[arg(short, long)]
         tier: u8,
 
-        /// Price in smallest unit
+        /// Price in whole tokens, e.g. 10.5 or 10.5USDC
         #[arg(short, long)]
-        price: u64,
+        price: AmountInput,
 
-        /// Coin type (0=SUI, 1=WAL, 2=USDC, 3=USDT)
+        /// Coin type (0=SUI, 1=WAL, 2=USDC, 3=USDT, or a full type like 0x..::coin::COIN)
         #[arg(short, long)]
-        coin_type: u8,
+        coin_type: String,
 
         /// Duration in days (for subscription)
         #[arg(long)]
@@ -66,13 +72,13 @@ pub enum PricingCommands {
         #[arg(short, long)]
         tier_id: String,
 
-        /// New price in smallest unit
+        /// New price in whole tokens, e.g. 10.5 or 10.5USDC
         #[arg(short, long)]
-        new_price: u64,
+        new_price: AmountInput,
 
-        /// Coin type (0=SUI, 1=WAL, 2=USDC, 3=USDT)
+        /// Coin type (0=SUI, 1=WAL, 2=USDC, 3=USDT, or a full type like 0x..::coin::COIN)
         #[arg(short, long)]
-        coin_type: u8,
+        coin_type: String,
     },
 
     /// Deactivate a tier
@@ -81,9 +87,9 @@ pub enum PricingCommands {
         #[arg(short, long)]
         tier_id: String,
 
-        /// Coin type (0=SUI, 1=WAL, 2=USDC, 3=USDT)
+        /// Coin type (0=SUI, 1=WAL, 2=USDC, 3=USDT, or a full type like 0x..::coin::COIN)
         #[arg(short, long)]
-        coin_type: u8,
+        coin_type: String,
     },
 
     /// Reactivate a tier
@@ -92,9 +98,9 @@ pub enum PricingCommands {
         #[arg(short, long)]
         tier_id: String,
 
-        /// Coin type (0=SUI, 1=WAL, 2=USDC, 3=USDT)
+        /// Coin type (0=SUI, 1=WAL, 2=USDC, 3=USDT, or a full type like 0x..::coin::COIN)
         #[arg(short, long)]
-        coin_type: u8,
+        coin_type: String,
     },
 
     /// Remove tier from service
@@ -107,6 +113,17 @@ pub enum PricingCommands {
         #[arg(short, long)]
         service_id: String,
     },
+
+    /// Built-in pricing templates
+    #[command(subcommand)]
+    Templates(TemplateCommands),
+
+    /// Create every tier in a pricing manifest
+    CreateTiers {
+        /// Manifest written by `pricing templates apply`
+        #[arg(short, long)]
+        manifest: PathBuf,
+    },
 }
 
 impl PricingCommands {
@@ -124,16 +141,19 @@ impl PricingCommands {
                 let default_path = default_wallet_config()?;
                 let mut wallet = load_wallet_context(default_path)?;
                 let sender = wallet.active_address()?;
+                Preflight::new().check(client, sender).await?;
                 let service = ObjectID::from_hex_literal(&service_id)?;
                 let config = TierConfigInput::from_u8(tier, duration, quota)?;
+                let coin_type = CoinType::from_str(coin_type)?;
+                let price = resolve_amount(client, &coin_type, price).await?;
                 let tx_data = create_pricing_tier_tx(
                     &client,
                     sender,
                     service,
                     name.to_string(),
-                    *price,
+                    price,
                     config,
-                    *coin_type,
+                    &coin_type,
                 )
                 .await?;
                 let resp = client.sign_and_execute_tx(tx_data, &mut wallet).await?;
@@ -148,6 +168,7 @@ impl PricingCommands {
                 let default_path = default_wallet_config()?;
                 let mut wallet = load_wallet_context(default_path)?;
                 let sender = wallet.active_address()?;
+                Preflight::new().check(client, sender).await?;
                 let service = ObjectID::from_hex_literal(&service_id)?;
                 let tier = ObjectID::from_hex_literal(&tier_id)?;
 
@@ -164,11 +185,14 @@ impl PricingCommands {
                 let default_path = default_wallet_config()?;
                 let mut wallet = load_wallet_context(default_path)?;
                 let sender = wallet.active_address()?;
+                Preflight::new().check(client, sender).await?;
 
                 let tier = ObjectID::from_hex_literal(&tier_id)?;
+                let coin_type = CoinType::from_str(coin_type)?;
+                let new_price = resolve_amount(client, &coin_type, new_price).await?;
 
                 let tx_data =
-                    update_tier_price_tx(&client, sender, *new_price, tier, *coin_type).await?;
+                    update_tier_price_tx(&client, sender, new_price, tier, &coin_type).await?;
 
                 let resp = client.sign_and_execute_tx(tx_data, &mut wallet).await?;
                 handle_response(&resp);
@@ -178,8 +202,11 @@ impl PricingCommands {
                 let default_path = default_wallet_config()?;
                 let mut wallet = load_wallet_context(default_path)?;
                 let sender = wallet.active_address()?;
+                Preflight::new().check(client, sender).await?;
                 let tier = ObjectID::from_hex_literal(&tier_id)?;
-                let tx_data = deactivate_tier_tx(&client, sender, tier, *coin_type).await?;
+                let tx_data =
+                    deactivate_tier_tx(&client, sender, tier, &CoinType::from_str(coin_type)?)
+                        .await?;
 
                 let _ = client.sign_and_execute_tx(tx_data, &mut wallet).await?;
                 Ok(())
@@ -188,9 +215,12 @@ impl PricingCommands {
                 let default_path = default_wallet_config()?;
                 let mut wallet = load_wallet_context(default_path)?;
                 let sender = wallet.active_address()?;
+                Preflight::new().check(client, sender).await?;
 
                 let tier = ObjectID::from_hex_literal(&tier_id)?;
-                let tx_data = reactivate_tier_tx(&client, sender, tier, *coin_type).await?;
+                let tx_data =
+                    reactivate_tier_tx(&client, sender, tier, &CoinType::from_str(coin_type)?)
+                        .await?;
                 let resp = client.sign_and_execute_tx(tx_data, &mut wallet).await?;
                 handle_response(&resp);
                 Ok(())
@@ -202,6 +232,7 @@ impl PricingCommands {
                 let default_path = default_wallet_config()?;
                 let mut wallet = load_wallet_context(default_path)?;
                 let sender = wallet.active_address()?;
+                Preflight::new().check(client, sender).await?;
                 let service = ObjectID::from_hex_literal(&service_id)?;
                 let tier = ObjectID::from_hex_literal(&tier_id)?;
 
@@ -211,6 +242,38 @@ impl PricingCommands {
                 handle_response(&resp);
                 Ok(())
             }
+            PricingCommands::Templates(cmd) => cmd.execute(),
+            PricingCommands::CreateTiers { manifest } => {
+                let manifest = PricingManifest::load(manifest)?;
+                let service = ObjectID::from_hex_literal(&manifest.service_id)?;
+                let coin_type = CoinType::from_str(&manifest.coin_type)?;
+
+                // Check every tier before submitting any, so a typo does not
+                // leave the service half set up
+                let mut tiers = Vec::with_capacity(manifest.tiers.len());
+                for tier in &manifest.tiers {
+                    let config = tier.tier_config()?;
+                    let price = resolve_amount(client, &coin_type, &tier.price()?).await?;
+                    tiers.push((tier.name.clone(), price, config));
+                }
+
+                let default_path = default_wallet_config()?;
+                let mut wallet = load_wallet_context(default_path)?;
+                let sender = wallet.active_address()?;
+                Preflight::new().check(client, sender).await?;
+
+                for (name, price, config) in tiers {
+                    info!("Creating tier {}", name);
+                    let tx_data = create_pricing_tier_tx(
+                        &client, sender, service, name, price, config, &coin_type,
+                    )
+                    .await?;
+                    let resp = client.sign_and_execute_tx(tx_data, &mut wallet).await?;
+                    handle_response(&resp);
+                }
+
+                Ok(())
+            }
         }
     }
 }
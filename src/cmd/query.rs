@@ -1,17 +1,175 @@
+use std::str::FromStr;
+
 use anyhow::{Ok, Result};
-use clap::Subcommand;
+use clap::{Args, Subcommand};
+use serde::de::DeserializeOwned;
 use sui_sdk::SuiClient;
-use tracing::info;
+use sui_types::base_types::SuiAddress;
+use tracing::{info, warn};
 
 use crate::{
+    backend::{revenue::RevenueReport, spend::SpendReport},
+    client::{client_ext::SuiClientExt, price_quote::usd_suffix},
+    db::{
+        models::{Entitlement, PricingTier, Provider, ServiceListing, TierChange, TierHistory},
+        page::Page,
+    },
     transactions::provider::get_provider_state,
-    utils::config::{default_wallet_config, load_wallet_context},
+    types::{amount::AmountInput, coin::CoinType},
+    utils::{
+        config::{default_wallet_config, load_wallet_context},
+        constants::DEFAULT_GAS_BUDGET,
+    },
 };
 
 #[derive(Subcommand)]
 pub enum QueryCommands {
     /// Get provider info
     Provider {},
+
+    /// List entitlements owned by an address
+    Entitlements {
+        /// Owner address (defaults to the active wallet address)
+        #[arg(short, long)]
+        owner: Option<String>,
+    },
+
+    /// Show balances for all supported coins
+    Balances {
+        /// Owner address (defaults to the active wallet address)
+        #[arg(short, long)]
+        owner: Option<String>,
+    },
+
+    /// Compare the active tiers of a service, including their SLA terms
+    Compare {
+        /// Service object ID
+        #[arg(short, long)]
+        service_id: String,
+
+        /// Infrapass API base URL (defaults to INFRAPASS_API_URL)
+        #[arg(long)]
+        api_url: Option<String>,
+    },
+
+    /// Summarize what an address spent per service and coin, from indexed purchases
+    Spend {
+        /// Buyer address (defaults to the active wallet address)
+        #[arg(short, long)]
+        owner: Option<String>,
+
+        /// How far back to look, e.g. 30d, 12h or 2w
+        #[arg(long, default_value = "30d")]
+        since: String,
+
+        /// Group purchases by day, week or month
+        #[arg(long, default_value = "day")]
+        bucket: String,
+
+        /// Warn when spend in a coin is over this amount, e.g. 10SUI (repeatable)
+        #[arg(long)]
+        budget: Vec<String>,
+
+        /// Infrapass API base URL (defaults to INFRAPASS_API_URL)
+        #[arg(long)]
+        api_url: Option<String>,
+    },
+
+    /// Summarize what a provider sold per coin and service, from indexed purchases
+    Revenue {
+        /// Provider profile object ID
+        #[arg(long)]
+        provider: String,
+
+        /// Group sales by day, week or month
+        #[arg(long, default_value = "month")]
+        period: String,
+
+        /// How far back to look, e.g. 365d, 12h or 2w
+        #[arg(long, default_value = "365d")]
+        since: String,
+
+        /// Infrapass API base URL (defaults to INFRAPASS_API_URL)
+        #[arg(long)]
+        api_url: Option<String>,
+    },
+
+    /// Show the recorded price changes, deactivations and reactivations of a tier
+    PriceHistory {
+        /// Tier object ID
+        #[arg(short, long)]
+        tier_id: String,
+
+        /// Infrapass API base URL (defaults to INFRAPASS_API_URL)
+        #[arg(long)]
+        api_url: Option<String>,
+    },
+
+    /// List indexed providers a page at a time
+    Providers {
+        /// Only active (true) or inactive (false) providers
+        #[arg(long)]
+        active: Option<bool>,
+
+        #[command(flatten)]
+        list: ListArgs,
+    },
+
+    /// List indexed services a page at a time
+    Services {
+        /// Provider profile object ID
+        #[arg(long)]
+        provider_id: Option<String>,
+
+        #[arg(long)]
+        service_type: Option<String>,
+
+        /// Only active (true) or inactive (false) services
+        #[arg(long)]
+        active: Option<bool>,
+
+        #[command(flatten)]
+        list: ListArgs,
+    },
+
+    /// List indexed tiers a page at a time
+    Tiers {
+        /// Provider profile object ID
+        #[arg(long)]
+        provider_id: Option<String>,
+
+        #[arg(long)]
+        coin_type: Option<String>,
+
+        /// Only active (true) or inactive (false) tiers
+        #[arg(long)]
+        active: Option<bool>,
+
+        #[command(flatten)]
+        list: ListArgs,
+    },
+
+    /// List indexed entitlements of a buyer or to a service a page at a time
+    IndexedEntitlements {
+        /// Buyer address
+        #[arg(
+            long,
+            required_unless_present = "service_id",
+            conflicts_with = "service_id"
+        )]
+        buyer: Option<String>,
+
+        /// Service object ID
+        #[arg(long)]
+        service_id: Option<String>,
+
+        /// Only unexpired (true) or expired (false) entitlements
+        #[arg(long)]
+        active: Option<bool>,
+
+        #[command(flatten)]
+        list: ListArgs,
+    },
     // /// Get service info
     // Service {
     //     /// Service object ID
@@ -27,6 +185,59 @@ pub enum QueryCommands {
     // },
 }
 
+/// Paging for the listing commands
+#[derive(Args)]
+pub struct ListArgs {
+    /// Rows per page, at most 200
+    #[arg(long, default_value_t = 50)]
+    limit: i64,
+
+    /// `next_cursor` printed by the previous page
+    #[arg(long)]
+    cursor: Option<String>,
+
+    /// newest or oldest first
+    #[arg(long, default_value = "newest")]
+    sort: String,
+
+    /// Infrapass API base URL (defaults to INFRAPASS_API_URL)
+    #[arg(long)]
+    api_url: Option<String>,
+}
+
+impl ListArgs {
+    async fn fetch<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        filters: &[(&str, Option<String>)],
+    ) -> Result<Page<T>> {
+        let url = format!("{}{}", resolve_api_url(self.api_url.as_deref())?, path);
+
+        let mut query = vec![
+            ("limit", self.limit.to_string()),
+            ("sort", self.sort.clone()),
+        ];
+        if let Some(cursor) = &self.cursor {
+            query.push(("cursor", cursor.clone()));
+        }
+        for (name, value) in filters {
+            if let Some(value) = value {
+                query.push((*name, value.clone()));
+            }
+        }
+
+        let page = reqwest::Client::new()
+            .get(&url)
+            .query(&query)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        Ok(page)
+    }
+}
+
 impl QueryCommands {
     pub async fn execute(&self, client: &SuiClient) -> Result<()> {
         match self {
@@ -41,6 +252,506 @@ impl QueryCommands {
 
                 Ok(())
             }
+            QueryCommands::Entitlements { owner } => {
+                let owner = resolve_owner(owner.as_deref())?;
+
+                let entitlements = client.get_entitlements(owner).await?;
+                let now_ms = chrono::Utc::now().timestamp_millis() as u64;
+
+                info!("{} entitlements for {}", entitlements.len(), owner);
+
+                for ent in entitlements {
+                    let expires = ent
+                        .expires_at()
+                        .and_then(|ms| chrono::DateTime::from_timestamp_millis(ms as i64))
+                        .map(|dt| dt.to_rfc3339())
+                        .unwrap_or_else(|| "never".to_string());
+                    let remaining = ent
+                        .remaining()
+                        .map(|r| r.to_string())
+                        .unwrap_or_else(|| "unlimited".to_string());
+
+                    info!(
+                        "{} | {} ({}) | service {} | expires {} | remaining {} | {}",
+                        ent.entitlement_id,
+                        ent.tier_name,
+                        ent.config.kind(),
+                        ent.service_id,
+                        expires,
+                        remaining,
+                        if ent.is_active(now_ms) {
+                            "active"
+                        } else {
+                            "inactive"
+                        }
+                    );
+                }
+
+                Ok(())
+            }
+            QueryCommands::Balances { owner } => {
+                let owner = resolve_owner(owner.as_deref())?;
+
+                let balances = client.balances(owner).await?;
+
+                info!("Balances for {}", owner);
+                for (coin_type, total) in balances {
+                    let total = u64::try_from(total).unwrap_or(u64::MAX);
+                    let coin_info = client.coin_info(&coin_type).await?;
+                    info!(
+                        "  {:<5} {}{}",
+                        coin_type.name(),
+                        coin_info.format_amount(total),
+                        usd_suffix(&coin_info, total).await
+                    );
+
+                    if matches!(coin_type, CoinType::SUI) && total < DEFAULT_GAS_BUDGET {
+                        warn!(
+                            "  SUI balance is below the default gas budget of {}",
+                            coin_info.format_amount(DEFAULT_GAS_BUDGET)
+                        );
+                    }
+                }
+
+                Ok(())
+            }
+            QueryCommands::Compare {
+                service_id,
+                api_url,
+            } => {
+                let url = format!(
+                    "{}/services/{}/tiers",
+                    resolve_api_url(api_url.as_deref())?,
+                    service_id
+                );
+                let tiers: Vec<PricingTier> =
+                    reqwest::get(&url).await?.error_for_status()?.json().await?;
+
+                if tiers.is_empty() {
+                    info!("No active tiers for service {}", service_id);
+                    return Ok(());
+                }
+
+                info!("{} tiers for service {}", tiers.len(), service_id);
+                for tier in tiers {
+                    let coin_type = CoinType::from_str(&tier.coin_type)?;
+                    let coin_info = client.coin_info(&coin_type).await?;
+                    let price = tier.price.get();
+                    let quota = tier
+                        .quota_limit
+                        .map(|q| q.to_string())
+                        .unwrap_or_else(|| "unlimited".to_string());
+                    let sla = tier
+                        .sla
+                        .map(|sla| sla.0.to_string())
+                        .unwrap_or_else(|| "no SLA published".to_string());
+
+                    info!(
+                        "{} | {} ({:?}) | {}{} | quota {} | {}",
+                        tier.tier_id,
+                        tier.tier_name,
+                        tier.tier_type,
+                        coin_info.format_amount(price),
+                        usd_suffix(&coin_info, price).await,
+                        quota,
+                        sla
+                    );
+                }
+
+                Ok(())
+            }
+            QueryCommands::PriceHistory { tier_id, api_url } => {
+                let url = format!(
+                    "{}/tiers/{}/history",
+                    resolve_api_url(api_url.as_deref())?,
+                    tier_id
+                );
+                let history: TierHistory =
+                    reqwest::get(&url).await?.error_for_status()?.json().await?;
+
+                let tier = &history.tier;
+                let coin_info = client
+                    .coin_info(&CoinType::from_str(&tier.coin_type)?)
+                    .await?;
+                info!(
+                    "{} | {} | service {} | now {} | {}",
+                    tier.tier_id,
+                    tier.tier_name,
+                    tier.service_id,
+                    coin_info.format_amount(tier.price.get()),
+                    active_label(tier.is_active)
+                );
+
+                if history.changes.is_empty() {
+                    info!("No recorded changes");
+                    return Ok(());
+                }
+
+                for change in &history.changes {
+                    let detail = match change.change {
+                        TierChange::Price => format!(
+                            "price {} -> {}",
+                            change
+                                .old_price
+                                .map(|p| coin_info.format_amount(p.get()))
+                                .unwrap_or_else(|| "-".to_string()),
+                            change
+                                .new_price
+                                .map(|p| coin_info.format_amount(p.get()))
+                                .unwrap_or_else(|| "-".to_string())
+                        ),
+                        TierChange::Deactivated => "deactivated".to_string(),
+                        TierChange::Reactivated => "reactivated".to_string(),
+                    };
+                    info!(
+                        "  {} | {} | checkpoint {} | tx {}",
+                        change.changed_at.to_rfc3339(),
+                        detail,
+                        change.checkpoint_number,
+                        change.transaction_digest.as_deref().unwrap_or("-")
+                    );
+                }
+
+                Ok(())
+            }
+            QueryCommands::Providers { active, list } => {
+                let page: Page<Provider> = list
+                    .fetch("/providers", &[("active", active.map(|a| a.to_string()))])
+                    .await?;
+
+                for provider in &page.items {
+                    info!(
+                        "{} | {} | {} | {}",
+                        provider.profile_id,
+                        provider.provider_address,
+                        provider.metadata_uri,
+                        active_label(provider.is_active)
+                    );
+                }
+                print_next_cursor(&page);
+
+                Ok(())
+            }
+            QueryCommands::Services {
+                provider_id,
+                service_type,
+                active,
+                list,
+            } => {
+                let page: Page<ServiceListing> = list
+                    .fetch(
+                        "/services",
+                        &[
+                            ("provider_id", provider_id.clone()),
+                            ("service_type", service_type.clone()),
+                            ("active", active.map(|a| a.to_string())),
+                        ],
+                    )
+                    .await?;
+
+                for listing in &page.items {
+                    let service = &listing.service;
+                    info!(
+                        "{} | {} | provider {} | {} | {}",
+                        service.service_id,
+                        service.service_type,
+                        service.provider_id,
+                        service.metadata_uri.as_deref().unwrap_or("-"),
+                        active_label(service.is_active)
+                    );
+                    if let Some(window) = &listing.upcoming_maintenance {
+                        info!(
+                            "    maintenance {} to {}{}",
+                            window.starts_at,
+                            window.ends_at,
+                            window
+                                .reason
+                                .as_deref()
+                                .map(|r| format!(" ({})", r))
+                                .unwrap_or_default()
+                        );
+                    }
+                }
+                print_next_cursor(&page);
+
+                Ok(())
+            }
+            QueryCommands::Tiers {
+                provider_id,
+                coin_type,
+                active,
+                list,
+            } => {
+                let page: Page<PricingTier> = list
+                    .fetch(
+                        "/tiers",
+                        &[
+                            ("provider_id", provider_id.clone()),
+                            ("coin_type", coin_type.clone()),
+                            ("active", active.map(|a| a.to_string())),
+                        ],
+                    )
+                    .await?;
+
+                for tier in &page.items {
+                    let coin_type = CoinType::from_str(&tier.coin_type)?;
+                    let coin_info = client.coin_info(&coin_type).await?;
+                    info!(
+                        "{} | {} ({:?}) | service {} | {} | {}",
+                        tier.tier_id,
+                        tier.tier_name,
+                        tier.tier_type,
+                        tier.service_id,
+                        coin_info.format_amount(tier.price.get()),
+                        active_label(tier.is_active)
+                    );
+                }
+                print_next_cursor(&page);
+
+                Ok(())
+            }
+            QueryCommands::Revenue {
+                provider,
+                period,
+                since,
+                api_url,
+            } => {
+                let url = format!(
+                    "{}/providers/{}/revenue",
+                    resolve_api_url(api_url.as_deref())?,
+                    provider
+                );
+                let report: RevenueReport = reqwest::Client::new()
+                    .get(&url)
+                    .query(&[("since", since.as_str()), ("bucket", period.as_str())])
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .json()
+                    .await?;
+
+                if report.totals.is_empty() {
+                    info!("No sales by {} since {}", provider, report.since);
+                    return Ok(());
+                }
+
+                info!("Revenue of {} since {}", provider, report.since);
+                for total in &report.totals {
+                    let coin_info = client
+                        .coin_info(&CoinType::from_str(&total.coin_type)?)
+                        .await?;
+                    let amount = total.total.get();
+                    info!(
+                        "  {}{} | {} purchase(s)",
+                        coin_info.format_amount(amount),
+                        usd_suffix(&coin_info, amount).await,
+                        total.purchases
+                    );
+                }
+
+                info!("Per service:");
+                for service in &report.services {
+                    let coin_info = client
+                        .coin_info(&CoinType::from_str(&service.coin_type)?)
+                        .await?;
+                    info!(
+                        "  {} | {} purchase(s) | {}",
+                        service.service_id,
+                        service.purchases,
+                        coin_info.format_amount(service.total.get())
+                    );
+                }
+
+                info!("Per {}:", report.bucket.as_str());
+                for row in &report.periods {
+                    let coin_info = client
+                        .coin_info(&CoinType::from_str(&row.coin_type)?)
+                        .await?;
+                    info!(
+                        "  {} | service {} | {} purchase(s) | {}",
+                        row.period.format("%Y-%m-%d"),
+                        row.service_id,
+                        row.purchases,
+                        coin_info.format_amount(row.total.get())
+                    );
+                }
+
+                Ok(())
+            }
+            QueryCommands::IndexedEntitlements {
+                buyer,
+                service_id,
+                active,
+                list,
+            } => {
+                let path = match (buyer, service_id) {
+                    (Some(buyer), _) => format!("/buyers/{}/entitlements", buyer),
+                    (None, Some(service_id)) => format!("/services/{}/entitlements", service_id),
+                    (None, None) => anyhow::bail!("Pass --buyer or --service-id"),
+                };
+                let page: Page<Entitlement> = list
+                    .fetch(&path, &[("active", active.map(|a| a.to_string()))])
+                    .await?;
+
+                for ent in &page.items {
+                    let expires = ent
+                        .expires_at
+                        .map(|t| t.to_rfc3339())
+                        .unwrap_or_else(|| "never".to_string());
+                    let remaining = match ent.quota {
+                        Some(quota) => quota.to_string(),
+                        None if ent.expires_at.is_none() => ent.units.to_string(),
+                        None => "unlimited".to_string(),
+                    };
+
+                    info!(
+                        "{} | buyer {} | service {} | tier {} | expires {} | remaining {}",
+                        ent.entitlement_id,
+                        ent.buyer,
+                        ent.service_id,
+                        ent.tier_id,
+                        expires,
+                        remaining
+                    );
+                }
+                print_next_cursor(&page);
+
+                Ok(())
+            }
+            QueryCommands::Spend {
+                owner,
+                since,
+                bucket,
+                budget,
+                api_url,
+            } => {
+                let owner = resolve_owner(owner.as_deref())?;
+                let budgets = budget
+                    .iter()
+                    .map(|b| parse_budget(b))
+                    .collect::<Result<Vec<_>>>()?;
+
+                let url = format!(
+                    "{}/buyers/{}/spend",
+                    resolve_api_url(api_url.as_deref())?,
+                    owner
+                );
+                let report: SpendReport = reqwest::Client::new()
+                    .get(&url)
+                    .query(&[("since", since.as_str()), ("bucket", bucket.as_str())])
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .json()
+                    .await?;
+
+                if report.totals.is_empty() {
+                    info!("No purchases by {} since {}", owner, report.since);
+                    return Ok(());
+                }
+
+                info!("Spend by {} since {}", owner, report.since);
+                for total in &report.totals {
+                    let coin_info = client
+                        .coin_info(&CoinType::from_str(&total.coin_type)?)
+                        .await?;
+                    let amount = total.total.get();
+                    info!(
+                        "  service {} | {} purchase(s) | {}{}",
+                        total.service_id,
+                        total.purchases,
+                        coin_info.format_amount(amount),
+                        usd_suffix(&coin_info, amount).await
+                    );
+                }
+
+                info!("Per {}:", report.bucket.as_str());
+                for row in &report.periods {
+                    let coin_info = client
+                        .coin_info(&CoinType::from_str(&row.coin_type)?)
+                        .await?;
+                    info!(
+                        "  {} | service {} | {} purchase(s) | {}",
+                        row.period.format("%Y-%m-%d"),
+                        row.service_id,
+                        row.purchases,
+                        coin_info.format_amount(row.total.get())
+                    );
+                }
+
+                for (coin_type, limit) in budgets {
+                    let coin_info = client.coin_info(&coin_type).await?;
+                    let limit = limit.to_base_units(coin_info.decimals)?;
+                    let mut spent = 0u64;
+                    for total in &report.totals {
+                        if CoinType::from_str(&total.coin_type)? == coin_type {
+                            spent = spent.saturating_add(total.total.get());
+                        }
+                    }
+
+                    if spent > limit {
+                        warn!(
+                            "Over budget: spent {} of {} since {}",
+                            coin_info.format_amount(spent),
+                            coin_info.format_amount(limit),
+                            report.since
+                        );
+                    } else {
+                        info!(
+                            "Within budget: spent {} of {}",
+                            coin_info.format_amount(spent),
+                            coin_info.format_amount(limit)
+                        );
+                    }
+                }
+
+                Ok(())
+            }
+        }
+    }
+}
+
+fn active_label(is_active: Option<bool>) -> &'static str {
+    if is_active.unwrap_or(false) {
+        "active"
+    } else {
+        "inactive"
+    }
+}
+
+fn print_next_cursor<T>(page: &Page<T>) {
+    match &page.next_cursor {
+        Some(cursor) => info!("More rows follow; pass --cursor {}", cursor),
+        None => info!("{} row(s), end of list", page.items.len()),
+    }
+}
+
+pub(crate) fn resolve_api_url(api_url: Option<&str>) -> Result<String> {
+    let api_url = match api_url {
+        Some(url) => url.to_string(),
+        None => std::env::var("INFRAPASS_API_URL")
+            .map_err(|_| anyhow::anyhow!("Pass --api-url or set INFRAPASS_API_URL"))?,
+    };
+    Ok(api_url.trim_end_matches('/').to_string())
+}
+
+/// Parses a `--budget` such as `10SUI`; the coin suffix is required
+fn parse_budget(budget: &str) -> Result<(CoinType, AmountInput)> {
+    let amount = AmountInput::from_str(budget)?;
+    let coin_type = match &amount.symbol {
+        Some(symbol) => CoinType::from_str(symbol)?,
+        None => anyhow::bail!("Budget {} needs a coin, e.g. 10SUI", budget),
+    };
+    Ok((coin_type, amount))
+}
+
+fn resolve_owner(owner: Option<&str>) -> Result<SuiAddress> {
+    match owner {
+        Some(addr) => Ok(SuiAddress::from_str(addr)?),
+        None => {
+            let default_path = default_wallet_config()?;
+            let mut wallet = load_wallet_context(default_path)?;
+            Ok(wallet.active_address()?)
         }
     }
 }
@@ -1,3 +1,5 @@
+use std::path::PathBuf;
+
 use anyhow::{Ok, Result};
 use clap::Subcommand;
 use sui_json_rpc_types::SuiTransactionBlockEffectsAPI;
@@ -6,14 +8,18 @@ use sui_types::base_types::ObjectID;
 use tracing::info;
 
 use crate::{
+    backend::access::AccessList,
     client::client_ext::SuiClientExt,
+    cmd::query::resolve_api_url,
     transactions::registry::{
-        provider_create_service, register_provider_tx, set_service_active_tx,
-        update_service_metadata_tx,
+        TIER_DEACTIVATIONS_PER_TX, deactivate_service_tx, deactivate_tiers_tx,
+        plan_service_deactivation, provider_create_service, register_provider_tx,
+        set_service_active_tx, update_service_metadata_tx,
     },
     utils::{
         config::{default_wallet_config, load_wallet_context},
         handle_response,
+        preflight::Preflight,
     },
 };
 
@@ -54,6 +60,37 @@ pub enum RegistryCommands {
         #[arg(short, long)]
         service_id: String,
     },
+
+    /// Deactivate a service and all of its active tiers
+    DeactivateService {
+        /// Service object ID
+        #[arg(short, long)]
+        service_id: String,
+
+        /// Only deactivate the service, leaving its tiers purchasable
+        #[arg(long)]
+        no_cascade: bool,
+    },
+
+    /// List the addresses with unexpired entitlements to a service, for an
+    /// access review
+    AccessList {
+        /// Service object ID
+        #[arg(short, long)]
+        service_id: String,
+
+        /// Write the list to this file as CSV instead of printing it
+        #[arg(long)]
+        csv: Option<PathBuf>,
+
+        /// Infrapass API base URL (defaults to INFRAPASS_API_URL)
+        #[arg(long)]
+        api_url: Option<String>,
+
+        /// Backend API key (defaults to INFRAPASS_API_KEY)
+        #[arg(long)]
+        api_key: Option<String>,
+    },
 }
 
 impl RegistryCommands {
@@ -63,6 +100,7 @@ impl RegistryCommands {
                 let default_path = default_wallet_config()?;
                 let mut wallet = load_wallet_context(default_path)?;
                 let sender = wallet.active_address()?;
+                Preflight::new().check(client, sender).await?;
                 info!("Registering provider with address {} ...", sender);
                 let data = register_provider_tx(client, sender, metadata_uri).await?;
                 let resp = client.sign_and_execute_tx(data, &mut wallet).await?;
@@ -78,6 +116,7 @@ impl RegistryCommands {
                 let default_path = default_wallet_config()?;
                 let mut wallet = load_wallet_context(default_path)?;
                 let sender = wallet.active_address()?;
+                Preflight::new().check(client, sender).await?;
                 info!("Creating service with address {} ...", sender);
                 let data =
                     provider_create_service(client, sender, service_type, metadata_uri).await?;
@@ -105,6 +144,7 @@ impl RegistryCommands {
                 let default_path = default_wallet_config()?;
                 let mut wallet = load_wallet_context(default_path)?;
                 let sender = wallet.active_address()?;
+                Preflight::new().check(client, sender).await?;
                 info!("Updating service {} metadata...", service_id);
 
                 let service = ObjectID::from_hex_literal(&service_id)?;
@@ -118,6 +158,7 @@ impl RegistryCommands {
                 let default_path = default_wallet_config()?;
                 let mut wallet = load_wallet_context(default_path)?;
                 let sender = wallet.active_address()?;
+                Preflight::new().check(client, sender).await?;
                 info!("Setting service {} to active...", service_id);
 
                 let service = ObjectID::from_hex_literal(&service_id)?;
@@ -127,6 +168,128 @@ impl RegistryCommands {
                 handle_response(&resp);
                 Ok(())
             }
+            RegistryCommands::DeactivateService {
+                service_id,
+                no_cascade,
+            } => {
+                let default_path = default_wallet_config()?;
+                let mut wallet = load_wallet_context(default_path)?;
+                let sender = wallet.active_address()?;
+                let service = ObjectID::from_hex_literal(&service_id)?;
+
+                let (tiers, already_inactive) = if no_cascade {
+                    (vec![], vec![])
+                } else {
+                    plan_service_deactivation(client, service).await?
+                };
+
+                let batch_count = tiers.len().div_ceil(TIER_DEACTIVATIONS_PER_TX).max(1);
+                Preflight::new()
+                    .transactions(batch_count as u64)
+                    .check(client, sender)
+                    .await?;
+
+                info!(
+                    "Deactivating service {} and {} active tier(s) ...",
+                    service_id,
+                    tiers.len()
+                );
+
+                let mut batches = tiers.chunks(TIER_DEACTIVATIONS_PER_TX);
+                let first = batches.next().unwrap_or_default();
+                let data = deactivate_service_tx(client, sender, service, first).await?;
+                let resp = client.sign_and_execute_tx(data, &mut wallet).await?;
+                handle_response(&resp);
+                if resp.status_ok() != Some(true) {
+                    anyhow::bail!("Service deactivation failed; no tiers were changed");
+                }
+
+                for batch in batches {
+                    let data = deactivate_tiers_tx(client, sender, batch).await?;
+                    let resp = client.sign_and_execute_tx(data, &mut wallet).await?;
+                    handle_response(&resp);
+                    if resp.status_ok() != Some(true) {
+                        anyhow::bail!("Tier batch deactivation failed; rerun to finish the rest");
+                    }
+                }
+
+                info!("Service {} deactivated", service_id);
+                for (tier_id, tier) in &tiers {
+                    info!("  deactivated tier {} ({})", tier_id, tier.coin_type);
+                }
+                if !already_inactive.is_empty() {
+                    info!("  {} tier(s) were already inactive", already_inactive.len());
+                }
+
+                Ok(())
+            }
+            RegistryCommands::AccessList {
+                service_id,
+                csv,
+                api_url,
+                api_key,
+            } => {
+                let url = format!(
+                    "{}/services/{}/access",
+                    resolve_api_url(api_url.as_deref())?,
+                    service_id
+                );
+                let request = reqwest::Client::new()
+                    .get(&url)
+                    .bearer_auth(resolve_api_key(api_key)?);
+
+                if let Some(path) = csv {
+                    let body = request
+                        .query(&[("format", "csv")])
+                        .send()
+                        .await?
+                        .error_for_status()?
+                        .text()
+                        .await?;
+                    std::fs::write(&path, body)?;
+                    info!(
+                        "Access list for service {} written to {}",
+                        service_id,
+                        path.display()
+                    );
+                    return Ok(());
+                }
+
+                let list: AccessList = request.send().await?.error_for_status()?.json().await?;
+                info!(
+                    "{} address(es) with access to service {} as of {}",
+                    list.addresses, list.service_id, list.as_of
+                );
+                for row in &list.entitlements {
+                    let expires = row
+                        .expires_at
+                        .map(|t| t.to_rfc3339())
+                        .unwrap_or_else(|| "never".to_string());
+                    let remaining = row
+                        .remaining
+                        .map(|r| r.to_string())
+                        .unwrap_or_else(|| "unlimited".to_string());
+                    info!(
+                        "  {} | {} | {} ({}) | expires {} | remaining {}",
+                        row.user_address,
+                        row.entitlement_id,
+                        row.tier_name,
+                        row.tier_type,
+                        expires,
+                        remaining
+                    );
+                }
+
+                Ok(())
+            }
         }
     }
 }
+
+fn resolve_api_key(api_key: Option<String>) -> Result<String> {
+    match api_key {
+        Some(key) => Ok(key),
+        None => std::env::var("INFRAPASS_API_KEY")
+            .map_err(|_| anyhow::anyhow!("Pass --api-key or set INFRAPASS_API_KEY")),
+    }
+}
@@ -0,0 +1,323 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use anyhow::{Result, anyhow, bail};
+use clap::Subcommand;
+use serde::{Deserialize, Serialize};
+use sui_types::base_types::ObjectID;
+use tracing::info;
+
+use crate::types::{amount::AmountInput, coin::CoinType, types::TierConfigInput};
+
+const MS_PER_DAY: u64 = 24 * 60 * 60 * 1000;
+
+/// Tiers to create for one service, written by `pricing templates apply`
+/// and submitted with `pricing create-tiers`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PricingManifest {
+    pub service_id: String,
+    /// Coin type, as accepted by `--coin-type`
+    pub coin_type: String,
+    pub tiers: Vec<ManifestTier>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestTier {
+    pub name: String,
+    /// Whole tokens, e.g. `10.5`; per unit for usage-based tiers
+    pub price: String,
+    #[serde(flatten)]
+    pub config: ManifestTierConfig,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ManifestTierConfig {
+    Subscription { duration_days: u64 },
+    Quota { duration_days: u64, quota: u64 },
+    UsageBased {},
+}
+
+impl ManifestTier {
+    pub fn price(&self) -> Result<AmountInput> {
+        AmountInput::from_str(&self.price).map_err(|e| anyhow!("tier {}: {}", self.name, e))
+    }
+
+    pub fn tier_config(&self) -> Result<TierConfigInput> {
+        let duration_ms = |days: u64| {
+            if days == 0 {
+                bail!("tier {}: duration_days must be positive", self.name);
+            }
+            days.checked_mul(MS_PER_DAY)
+                .ok_or_else(|| anyhow!("tier {}: duration_days is too large", self.name))
+        };
+
+        match self.config {
+            ManifestTierConfig::Subscription { duration_days } => {
+                Ok(TierConfigInput::Subscription {
+                    expires_at: duration_ms(duration_days)?,
+                })
+            }
+            ManifestTierConfig::Quota {
+                duration_days,
+                quota,
+            } => {
+                if quota == 0 {
+                    bail!("tier {}: quota must be positive", self.name);
+                }
+                Ok(TierConfigInput::Quota {
+                    quota_limit: quota,
+                    expires_at: duration_ms(duration_days)?,
+                })
+            }
+            ManifestTierConfig::UsageBased {} => Ok(TierConfigInput::UsageBased {}),
+        }
+    }
+}
+
+impl PricingManifest {
+    pub fn load(path: &Path) -> Result<Self> {
+        let raw = fs::read_to_string(path)
+            .map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?;
+        let manifest: Self = serde_json::from_str(&raw)
+            .map_err(|e| anyhow!("Invalid manifest {}: {}", path.display(), e))?;
+
+        if manifest.tiers.is_empty() {
+            bail!("Manifest {} has no tiers", path.display());
+        }
+
+        Ok(manifest)
+    }
+}
+
+/// A built-in starting point for a service's tiers
+struct Template {
+    name: &'static str,
+    description: &'static str,
+    tiers: &'static [TemplateTier],
+}
+
+struct TemplateTier {
+    name: &'static str,
+    price: &'static str,
+    duration_days: Option<u64>,
+    quota: Option<u64>,
+}
+
+impl TemplateTier {
+    fn to_manifest(&self) -> ManifestTier {
+        let config = match (self.duration_days, self.quota) {
+            (Some(duration_days), None) => ManifestTierConfig::Subscription { duration_days },
+            (Some(duration_days), Some(quota)) => ManifestTierConfig::Quota {
+                duration_days,
+                quota,
+            },
+            _ => ManifestTierConfig::UsageBased {},
+        };
+
+        ManifestTier {
+            name: self.name.to_string(),
+            price: self.price.to_string(),
+            config,
+        }
+    }
+}
+
+const TEMPLATES: &[Template] = &[
+    Template {
+        name: "rpc-basic",
+        description: "RPC endpoint: a monthly plan plus a request bundle",
+        tiers: &[
+            TemplateTier {
+                name: "monthly",
+                price: "10",
+                duration_days: Some(30),
+                quota: None,
+            },
+            TemplateTier {
+                name: "100k-requests",
+                price: "2",
+                duration_days: Some(30),
+                quota: Some(100_000),
+            },
+        ],
+    },
+    Template {
+        name: "rpc-pro",
+        description: "RPC endpoint: monthly and yearly plans plus a large request bundle",
+        tiers: &[
+            TemplateTier {
+                name: "monthly",
+                price: "25",
+                duration_days: Some(30),
+                quota: None,
+            },
+            TemplateTier {
+                name: "yearly",
+                price: "250",
+                duration_days: Some(365),
+                quota: None,
+            },
+            TemplateTier {
+                name: "5m-requests",
+                price: "40",
+                duration_days: Some(30),
+                quota: Some(5_000_000),
+            },
+        ],
+    },
+    Template {
+        name: "inference-metered",
+        description: "Model inference: pay per unit, or a discounted monthly bundle",
+        tiers: &[
+            TemplateTier {
+                name: "pay-as-you-go",
+                price: "0.001",
+                duration_days: None,
+                quota: None,
+            },
+            TemplateTier {
+                name: "1m-units",
+                price: "800",
+                duration_days: Some(30),
+                quota: Some(1_000_000),
+            },
+        ],
+    },
+    Template {
+        name: "storage-monthly",
+        description: "Storage or data access: monthly and yearly plans",
+        tiers: &[
+            TemplateTier {
+                name: "monthly",
+                price: "5",
+                duration_days: Some(30),
+                quota: None,
+            },
+            TemplateTier {
+                name: "yearly",
+                price: "50",
+                duration_days: Some(365),
+                quota: None,
+            },
+        ],
+    },
+];
+
+fn find_template(name: &str) -> Result<&'static Template> {
+    TEMPLATES.iter().find(|t| t.name == name).ok_or_else(|| {
+        let names: Vec<_> = TEMPLATES.iter().map(|t| t.name).collect();
+        anyhow!("Unknown template {}. Available: {}", name, names.join(", "))
+    })
+}
+
+#[derive(Subcommand)]
+pub enum TemplateCommands {
+    /// List the built-in pricing templates
+    List,
+
+    /// Write a template as a pricing manifest to edit and then submit with `create-tiers`
+    Apply {
+        /// Template name, see `pricing templates list`
+        template: String,
+
+        /// Service object ID
+        #[arg(short, long)]
+        service_id: String,
+
+        /// Coin type (0=SUI, 1=WAL, 2=USDC, 3=USDT, or a full type like 0x..::coin::COIN)
+        #[arg(short, long)]
+        coin_type: String,
+
+        /// Manifest path (defaults to pricing-<TEMPLATE>.json)
+        #[arg(short, long)]
+        out: Option<PathBuf>,
+
+        /// Overwrite an existing manifest
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+impl TemplateCommands {
+    pub fn execute(&self) -> Result<()> {
+        match self {
+            TemplateCommands::List => {
+                for template in TEMPLATES {
+                    info!("{}: {}", template.name, template.description);
+                    for tier in template.tiers {
+                        let tier = tier.to_manifest();
+                        match tier.config {
+                            ManifestTierConfig::Subscription { duration_days } => info!(
+                                "  {}: subscription, {} days, price {}",
+                                tier.name, duration_days, tier.price
+                            ),
+                            ManifestTierConfig::Quota {
+                                duration_days,
+                                quota,
+                            } => info!(
+                                "  {}: quota, {} units over {} days, price {}",
+                                tier.name, quota, duration_days, tier.price
+                            ),
+                            ManifestTierConfig::UsageBased {} => info!(
+                                "  {}: usage based, price {} per unit",
+                                tier.name, tier.price
+                            ),
+                        }
+                    }
+                }
+
+                Ok(())
+            }
+            TemplateCommands::Apply {
+                template,
+                service_id,
+                coin_type,
+                out,
+                force,
+            } => {
+                let template = find_template(template)?;
+                ObjectID::from_hex_literal(service_id)?;
+                CoinType::from_str(coin_type)?;
+
+                let manifest = PricingManifest {
+                    service_id: service_id.clone(),
+                    coin_type: coin_type.clone(),
+                    tiers: template
+                        .tiers
+                        .iter()
+                        .map(TemplateTier::to_manifest)
+                        .collect(),
+                };
+
+                let path = out
+                    .clone()
+                    .unwrap_or_else(|| PathBuf::from(format!("pricing-{}.json", template.name)));
+                if path.exists() && !force {
+                    bail!(
+                        "{} already exists, pass --force to overwrite it",
+                        path.display()
+                    );
+                }
+
+                fs::write(&path, serde_json::to_string_pretty(&manifest)? + "\n")?;
+
+                info!(
+                    "Wrote {} tiers from {} to {}",
+                    manifest.tiers.len(),
+                    template.name,
+                    path.display()
+                );
+                info!(
+                    "Edit names, prices, durations and quotas, then run: infrapass-cli pricing create-tiers --manifest {}",
+                    path.display()
+                );
+
+                Ok(())
+            }
+        }
+    }
+}
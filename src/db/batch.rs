@@ -0,0 +1,43 @@
+use std::{cell::RefCell, future::Future};
+
+tokio::task_local! {
+    static BUFFER: RefCell<Vec<BufferedEvent>>;
+}
+
+/// A `blockchain_events` row held back until `Repository::flush_events`
+pub(crate) struct BufferedEvent {
+    pub checkpoint: i64,
+    pub tx_digest: Option<String>,
+    pub event_index: i32,
+    pub package_id: String,
+    pub event_type: String,
+    pub module: String,
+    pub event_data: serde_json::Value,
+    pub provider_id: Option<String>,
+    pub service_id: Option<String>,
+    pub tier_id: Option<String>,
+    pub entitlement_id: Option<String>,
+}
+
+/// Runs `f` with the `blockchain_events` rows written by `store_event` held
+/// in memory instead of inserted one at a time. `f` must write them with
+/// `Repository::flush_events` on the transaction that applied their events,
+/// or they are lost. Work spawned onto other tasks from inside `f` is not
+/// buffered.
+pub async fn scope<F: Future>(f: F) -> F::Output {
+    BUFFER.scope(RefCell::new(Vec::new()), f).await
+}
+
+/// Whether the current task is buffering event rows
+pub(crate) fn is_active() -> bool {
+    BUFFER.try_with(|_| ()).is_ok()
+}
+
+pub(crate) fn push(row: BufferedEvent) {
+    let _ = BUFFER.try_with(|buffer| buffer.borrow_mut().push(row));
+}
+
+/// Empties the current task's buffer
+pub(crate) fn take() -> Vec<BufferedEvent> {
+    BUFFER.try_with(|buffer| buffer.take()).unwrap_or_default()
+}
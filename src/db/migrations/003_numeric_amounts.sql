@@ -0,0 +1,11 @@
+ALTER TABLE pricing_tiers
+    ALTER COLUMN price TYPE NUMERIC(20, 0),
+    ALTER COLUMN quota_limit TYPE NUMERIC(20, 0);
+
+ALTER TABLE entitlements
+    ALTER COLUMN price_paid TYPE NUMERIC(20, 0),
+    ALTER COLUMN quota TYPE NUMERIC(20, 0),
+    ALTER COLUMN units TYPE NUMERIC(20, 0);
+
+ALTER TABLE usage_events
+    ALTER COLUMN amount TYPE NUMERIC(20, 0);
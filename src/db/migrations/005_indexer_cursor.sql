@@ -0,0 +1,5 @@
+CREATE TABLE IF NOT EXISTS indexer_cursors (
+    name TEXT PRIMARY KEY,
+    checkpoint_number BIGINT NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
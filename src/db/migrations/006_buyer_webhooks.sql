@@ -0,0 +1,16 @@
+CREATE TABLE IF NOT EXISTS buyer_webhooks (
+    id UUID PRIMARY KEY,
+    user_address TEXT NOT NULL,
+    url TEXT NOT NULL,
+    secret TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (user_address, url)
+);
+
+-- One row per notification already sent, so periodic checks never repeat one
+CREATE TABLE IF NOT EXISTS buyer_notifications (
+    entitlement_id TEXT NOT NULL,
+    event TEXT NOT NULL,
+    sent_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (entitlement_id, event)
+);
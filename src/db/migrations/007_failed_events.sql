@@ -0,0 +1,17 @@
+CREATE TABLE IF NOT EXISTS failed_events (
+    id BIGSERIAL PRIMARY KEY,
+    checkpoint_number BIGINT NOT NULL,
+    transaction_digest TEXT,
+    event_type TEXT NOT NULL,
+    package_id TEXT,
+    protocol_version BIGINT NOT NULL DEFAULT 1,
+    bcs BYTEA NOT NULL,
+    error TEXT NOT NULL,
+    attempts INT NOT NULL DEFAULT 1,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    replayed_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_failed_events_pending
+    ON failed_events(id)
+    WHERE replayed_at IS NULL;
@@ -0,0 +1,2 @@
+-- SLA terms providers attach to a tier; NULL when none were published
+ALTER TABLE pricing_tiers ADD COLUMN IF NOT EXISTS sla JSONB;
@@ -0,0 +1,20 @@
+-- Position of the event within its transaction; with the digest it names an
+-- event uniquely. NULL for rows ingested before this column existed.
+ALTER TABLE blockchain_events ADD COLUMN IF NOT EXISTS event_index INTEGER;
+ALTER TABLE failed_events ADD COLUMN IF NOT EXISTS event_index INTEGER NOT NULL DEFAULT 0;
+
+CREATE INDEX IF NOT EXISTS idx_events_tx_index
+    ON blockchain_events (transaction_digest, event_index)
+    WHERE transaction_digest IS NOT NULL;
+
+-- blockchain_events is a hypertable, and unique keys there must include
+-- event_time, which is assigned at insert. The (transaction_digest,
+-- event_index) key is enforced here instead: a row is written once an event
+-- has been fully handled, and the worker skips events that already have one.
+CREATE TABLE IF NOT EXISTS ingested_events (
+    transaction_digest TEXT NOT NULL,
+    event_index INTEGER NOT NULL,
+    checkpoint_number BIGINT NOT NULL,
+    ingested_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (transaction_digest, event_index)
+);
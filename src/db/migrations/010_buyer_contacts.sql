@@ -0,0 +1,12 @@
+-- Contact a buyer linked to an entitlement, sealed with CONTACT_ENCRYPTION_KEY.
+-- Only the owning provider can read it back; the buyer can delete it.
+CREATE TABLE IF NOT EXISTS buyer_contacts (
+    entitlement_id TEXT PRIMARY KEY REFERENCES entitlements(entitlement_id),
+    buyer TEXT NOT NULL,
+    provider_id TEXT NOT NULL,
+    contact_ciphertext BYTEA NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_buyer_contacts_provider ON buyer_contacts (provider_id);
@@ -0,0 +1,17 @@
+-- Which services list a tier, from TierAddedToService / TierRemovedFromService.
+-- A tier can be listed by services other than the one that created it, and
+-- TierAddedToService is emitted before TierCreated, so tier_id has no FK.
+CREATE TABLE IF NOT EXISTS service_tiers (
+    service_id TEXT NOT NULL,
+    tier_id TEXT NOT NULL,
+    added_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (service_id, tier_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_service_tiers_tier ON service_tiers (tier_id);
+
+-- Every tier indexed so far was listed by the service that created it.
+-- Removals before this migration are only picked up by `reindex`.
+INSERT INTO service_tiers (service_id, tier_id, added_at)
+SELECT service_id, tier_id, created_at FROM pricing_tiers
+ON CONFLICT DO NOTHING;
@@ -0,0 +1,14 @@
+-- Last heartbeat from each sidecar instance, for the provider fleet view.
+-- Rows not seen within the retention window are removed by `prune`.
+CREATE TABLE IF NOT EXISTS sidecar_instances (
+    provider_id TEXT NOT NULL,
+    instance_id TEXT NOT NULL,
+    version TEXT NOT NULL,
+    git_hash TEXT NOT NULL,
+    config_hash TEXT NOT NULL,
+    first_seen_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_seen_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (provider_id, instance_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_sidecar_instances_last_seen ON sidecar_instances (last_seen_at);
@@ -0,0 +1,4 @@
+-- Events that decoded but that the worker could not handle, after its
+-- retries ran out. They keep the decoded payload instead of BCS bytes.
+ALTER TABLE failed_events ADD COLUMN IF NOT EXISTS payload JSONB;
+ALTER TABLE failed_events ALTER COLUMN bcs DROP NOT NULL;
@@ -0,0 +1,13 @@
+-- Spending limit a buyer set for one coin over a rolling window. Crossing it
+-- with a purchase sends a budget_exceeded notification to the buyer's webhooks.
+CREATE TABLE IF NOT EXISTS buyer_budgets (
+    user_address TEXT NOT NULL,
+    coin_type TEXT NOT NULL,
+    amount NUMERIC(20, 0) NOT NULL,
+    period_days INTEGER NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (user_address, coin_type)
+);
+
+CREATE INDEX IF NOT EXISTS idx_entitlements_buyer_created ON entitlements (buyer, created_at);
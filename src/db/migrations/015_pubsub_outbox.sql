@@ -0,0 +1,17 @@
+-- Sidecar notifications written in the same transaction as the event that
+-- caused them, then published to Redis by the outbox publisher. A message is
+-- never lost to a Redis outage; it is retried until it goes out.
+CREATE TABLE IF NOT EXISTS pubsub_outbox (
+    id BIGSERIAL PRIMARY KEY,
+    channel TEXT NOT NULL,
+    message TEXT NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    last_error TEXT,
+    next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    published_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_pubsub_outbox_pending
+    ON pubsub_outbox (id)
+    WHERE published_at IS NULL;
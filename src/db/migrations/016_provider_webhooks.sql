@@ -0,0 +1,40 @@
+CREATE TABLE IF NOT EXISTS provider_webhooks (
+    id UUID PRIMARY KEY,
+    provider_id TEXT NOT NULL,
+    url TEXT NOT NULL,
+    secret TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (provider_id, url)
+);
+
+-- One row per notification per webhook, written in the same transaction as
+-- the event that caused it. The payload is stored as sent, so every retry
+-- carries the same body and signature.
+CREATE TABLE IF NOT EXISTS provider_webhook_deliveries (
+    id BIGSERIAL PRIMARY KEY,
+    webhook_id UUID NOT NULL REFERENCES provider_webhooks (id) ON DELETE CASCADE,
+    event TEXT NOT NULL,
+    -- Entitlement or tier the notification is about
+    subject_id TEXT NOT NULL,
+    payload TEXT NOT NULL,
+    -- pending, delivered or failed
+    status TEXT NOT NULL DEFAULT 'pending',
+    attempts INTEGER NOT NULL DEFAULT 0,
+    last_status_code INTEGER,
+    last_error TEXT,
+    next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    delivered_at TIMESTAMPTZ,
+    UNIQUE (webhook_id, event, subject_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_provider_webhook_deliveries_pending
+    ON provider_webhook_deliveries (next_attempt_at)
+    WHERE status = 'pending';
+
+CREATE INDEX IF NOT EXISTS idx_provider_webhook_deliveries_webhook
+    ON provider_webhook_deliveries (webhook_id, created_at DESC);
+
+CREATE INDEX IF NOT EXISTS idx_entitlements_expires_at
+    ON entitlements (expires_at)
+    WHERE expires_at IS NOT NULL;
@@ -0,0 +1,10 @@
+-- Tier a provider points holders of a retired tier to. Once the old tier is
+-- deactivated, the validator serves its entitlements under the replacement.
+CREATE TABLE IF NOT EXISTS tier_replacements (
+    tier_id TEXT PRIMARY KEY REFERENCES pricing_tiers (tier_id) ON DELETE CASCADE,
+    replacement_tier_id TEXT NOT NULL REFERENCES pricing_tiers (tier_id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    CHECK (tier_id <> replacement_tier_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_tier_replacements_replacement ON tier_replacements (replacement_tier_id);
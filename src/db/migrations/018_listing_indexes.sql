@@ -0,0 +1,4 @@
+-- Keyset pagination walks each listing by (created_at, primary key)
+CREATE INDEX IF NOT EXISTS idx_providers_created ON providers (created_at, profile_id);
+CREATE INDEX IF NOT EXISTS idx_services_created ON services (created_at, service_id);
+CREATE INDEX IF NOT EXISTS idx_tiers_created ON pricing_tiers (created_at, tier_id);
@@ -0,0 +1,78 @@
+-- Optional row-level multi-tenancy for hosted deployments. Each tenant owns a
+-- set of providers; rows under those providers carry the tenant's ID and are
+-- only visible to sessions bound to that tenant. A session bound to no tenant
+-- (the indexer, background jobs, the operator API key) sees every row, so a
+-- single-tenant deployment is unaffected.
+CREATE TABLE IF NOT EXISTS tenants (
+    tenant_id TEXT PRIMARY KEY,
+    name TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Only the SHA-256 of each key is stored
+CREATE TABLE IF NOT EXISTS tenant_api_keys (
+    key_hash TEXT PRIMARY KEY,
+    tenant_id TEXT NOT NULL REFERENCES tenants (tenant_id) ON DELETE CASCADE,
+    label TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    revoked_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_tenant_api_keys_tenant ON tenant_api_keys (tenant_id);
+
+CREATE TABLE IF NOT EXISTS tenant_providers (
+    provider_id TEXT PRIMARY KEY,
+    tenant_id TEXT NOT NULL REFERENCES tenants (tenant_id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_tenant_providers_tenant ON tenant_providers (tenant_id);
+
+-- Whether a row of `row_tenant` is visible to the current session
+CREATE OR REPLACE FUNCTION infrapass_tenant_visible(row_tenant TEXT) RETURNS BOOLEAN AS $$
+    SELECT COALESCE(current_setting('infrapass.tenant_id', true), '') = ''
+        OR row_tenant = current_setting('infrapass.tenant_id', true)
+$$ LANGUAGE SQL STABLE;
+
+-- Fills in `tenant_id` on insert from the owning provider. Rows of providers
+-- that belong to no tenant stay NULL, which a tenant session can't write.
+CREATE OR REPLACE FUNCTION infrapass_assign_tenant() RETURNS TRIGGER AS $$
+BEGIN
+    IF NEW.tenant_id IS NOT NULL THEN
+        RETURN NEW;
+    END IF;
+
+    IF TG_TABLE_NAME = 'providers' THEN
+        SELECT tenant_id INTO NEW.tenant_id FROM tenant_providers WHERE provider_id = NEW.profile_id;
+    ELSIF TG_TABLE_NAME IN ('pricing_tiers', 'maintenance_windows') THEN
+        SELECT tenant_id INTO NEW.tenant_id FROM services WHERE service_id = NEW.service_id;
+    ELSE
+        SELECT tenant_id INTO NEW.tenant_id FROM tenant_providers WHERE provider_id = NEW.provider_id;
+    END IF;
+
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+DO $$
+DECLARE
+    t TEXT;
+BEGIN
+    FOREACH t IN ARRAY ARRAY[
+        'providers', 'services', 'pricing_tiers', 'entitlements', 'maintenance_windows',
+        'provider_webhooks', 'sidecar_instances', 'buyer_contacts'
+    ] LOOP
+        EXECUTE format('ALTER TABLE %I ADD COLUMN IF NOT EXISTS tenant_id TEXT', t);
+        EXECUTE format('CREATE INDEX IF NOT EXISTS %I ON %I (tenant_id) WHERE tenant_id IS NOT NULL', 'idx_' || t || '_tenant', t);
+
+        EXECUTE format('DROP TRIGGER IF EXISTS assign_tenant ON %I', t);
+        EXECUTE format('CREATE TRIGGER assign_tenant BEFORE INSERT ON %I FOR EACH ROW EXECUTE FUNCTION infrapass_assign_tenant()', t);
+
+        -- Forced so the policy also holds for the table owner the backend connects as
+        EXECUTE format('ALTER TABLE %I ENABLE ROW LEVEL SECURITY', t);
+        EXECUTE format('ALTER TABLE %I FORCE ROW LEVEL SECURITY', t);
+        EXECUTE format('DROP POLICY IF EXISTS tenant_isolation ON %I', t);
+        EXECUTE format('CREATE POLICY tenant_isolation ON %I USING (infrapass_tenant_visible(tenant_id)) WITH CHECK (infrapass_tenant_visible(tenant_id))', t);
+    END LOOP;
+END
+$$;
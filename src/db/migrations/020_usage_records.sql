@@ -0,0 +1,15 @@
+-- One row per usage submission that carried a request ID, written in the same
+-- transaction that decrements the entitlement. A sidecar retrying a call it
+-- never got an answer to resends the same ID and is turned away here instead
+-- of being charged twice.
+CREATE TABLE IF NOT EXISTS usage_records (
+    entitlement_id TEXT NOT NULL,
+    request_id TEXT NOT NULL,
+    user_address TEXT NOT NULL,
+    amount NUMERIC(20, 0) NOT NULL,
+    usage_event_id UUID NOT NULL,
+    recorded_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (entitlement_id, request_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_usage_records_recorded ON usage_records (recorded_at);
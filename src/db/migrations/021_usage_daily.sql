@@ -0,0 +1,19 @@
+-- Usage per service, buyer and UTC day, rolled up from usage_events by the
+-- usage_rollup job so reports never scan raw usage. Each run recomputes the
+-- days from the newest one already rolled up, so the current day fills in
+-- as usage arrives and a rerun changes nothing.
+CREATE TABLE IF NOT EXISTS usage_daily (
+    day DATE NOT NULL,
+    service_id TEXT NOT NULL,
+    user_address TEXT NOT NULL,
+    -- Usage submissions; a sampling sidecar sends one for many requests
+    submissions BIGINT NOT NULL,
+    amount NUMERIC(20, 0) NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (day, service_id, user_address)
+);
+
+CREATE INDEX IF NOT EXISTS idx_usage_daily_service_day ON usage_daily (service_id, day);
+CREATE INDEX IF NOT EXISTS idx_usage_daily_buyer_day ON usage_daily (user_address, day);
+
+CREATE INDEX IF NOT EXISTS idx_usage_events_recorded ON usage_events (recorded_at);
@@ -0,0 +1,10 @@
+-- Listings of a buyer's or a service's entitlements page by
+-- (created_at, entitlement_id); validation looks up a buyer's entitlements
+-- to one service
+CREATE INDEX IF NOT EXISTS idx_entitlements_buyer_page ON entitlements (buyer, created_at, entitlement_id);
+CREATE INDEX IF NOT EXISTS idx_entitlements_service_page ON entitlements (service_id, created_at, entitlement_id);
+CREATE INDEX IF NOT EXISTS idx_entitlements_buyer_service ON entitlements (buyer, service_id);
+
+-- Superseded by idx_entitlements_buyer_page
+DROP INDEX IF EXISTS idx_entitlements_buyer_created;
+DROP INDEX IF EXISTS idx_entitlements_buyer;
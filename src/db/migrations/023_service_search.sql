@@ -0,0 +1,18 @@
+-- Cached copy of each service's metadata JSON, fetched from `metadata_uri` by
+-- the metadata refresh job. `metadata_fetched_at` is cleared whenever the URI
+-- changes, so the new document is picked up on the next run.
+ALTER TABLE services ADD COLUMN IF NOT EXISTS metadata JSONB;
+ALTER TABLE services ADD COLUMN IF NOT EXISTS metadata_fetched_at TIMESTAMPTZ;
+ALTER TABLE services ADD COLUMN IF NOT EXISTS metadata_error TEXT;
+
+-- Full-text search over the service type (weighted highest) and every string
+-- value in the cached metadata
+ALTER TABLE services ADD COLUMN IF NOT EXISTS search_vector TSVECTOR
+    GENERATED ALWAYS AS (
+        setweight(to_tsvector('english', service_type), 'A')
+        || setweight(jsonb_to_tsvector('english', COALESCE(metadata, '{}'::JSONB), '["string"]'), 'B')
+    ) STORED;
+
+CREATE INDEX IF NOT EXISTS idx_services_search ON services USING GIN (search_vector);
+CREATE INDEX IF NOT EXISTS idx_services_metadata_fetch ON services (metadata_fetched_at NULLS FIRST)
+    WHERE metadata_uri IS NOT NULL;
@@ -0,0 +1,28 @@
+CREATE TYPE tier_change AS ENUM ('price', 'deactivated', 'reactivated');
+
+-- Audit trail of price and availability changes to each tier, written by the
+-- event worker from the on-chain events. Kept apart from `blockchain_events`
+-- so pruning never removes it, and without a foreign key so it outlives the
+-- tier row. Price changes fill the price columns, the others the active ones.
+CREATE TABLE IF NOT EXISTS tier_history (
+    id BIGSERIAL PRIMARY KEY,
+    tier_id TEXT NOT NULL,
+    change tier_change NOT NULL,
+    old_price NUMERIC(20, 0),
+    new_price NUMERIC(20, 0),
+    old_active BOOLEAN,
+    new_active BOOLEAN,
+    checkpoint_number BIGINT NOT NULL,
+    transaction_digest TEXT,
+    event_index INTEGER,
+    -- On-chain timestamp of the change
+    changed_at TIMESTAMPTZ NOT NULL,
+    recorded_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_tier_history_tier ON tier_history (tier_id, checkpoint_number, id);
+
+-- Replays write each event's row once
+CREATE UNIQUE INDEX IF NOT EXISTS idx_tier_history_event
+    ON tier_history (transaction_digest, event_index)
+    WHERE transaction_digest IS NOT NULL;
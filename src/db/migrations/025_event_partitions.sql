@@ -0,0 +1,6 @@
+-- blockchain_events is a hypertable partitioned on event_time. Weekly chunks
+-- let the event retention job drop history a week at a time instead of
+-- deleting rows; chunks created before this migration keep their interval.
+-- Projected tables and `ingested_events` are separate tables and are never
+-- dropped with the history.
+SELECT set_chunk_time_interval('blockchain_events', INTERVAL '7 days');
@@ -0,0 +1,15 @@
+-- Provider-scoped API keys, so each provider's sidecars authenticate with
+-- their own key instead of the operator's `API_KEY`. Only the SHA-256 of each
+-- key is stored. Keys are issued, rotated and revoked by the operator.
+CREATE TABLE IF NOT EXISTS api_keys (
+    key_id UUID PRIMARY KEY,
+    key_hash TEXT NOT NULL UNIQUE,
+    provider_id TEXT NOT NULL,
+    label TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    -- A rotated key keeps working until this passes
+    revoked_at TIMESTAMPTZ,
+    rotated_from UUID REFERENCES api_keys (key_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_api_keys_provider ON api_keys (provider_id);
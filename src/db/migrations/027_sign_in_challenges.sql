@@ -0,0 +1,10 @@
+-- Nonces handed out for Sign-in-with-Sui. A challenge is deleted when it is
+-- answered, right or wrong, so each signature can be used only once.
+CREATE TABLE IF NOT EXISTS sign_in_challenges (
+    nonce TEXT PRIMARY KEY,
+    provider_id TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_sign_in_challenges_expires ON sign_in_challenges (expires_at);
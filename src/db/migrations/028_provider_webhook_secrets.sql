@@ -0,0 +1,5 @@
+-- With `WEBHOOK_ENCRYPTION_KEY` set, a provider webhook's signing secret is
+-- kept only as AES-256-GCM ciphertext and `secret` is NULL. Secrets stored
+-- before the key was set are sealed when the server starts.
+ALTER TABLE provider_webhooks ADD COLUMN IF NOT EXISTS secret_ciphertext BYTEA;
+ALTER TABLE provider_webhooks ALTER COLUMN secret DROP NOT NULL;
@@ -0,0 +1,21 @@
+-- One row per `settle_usage_batch` transaction the settlement job submits.
+-- Usage events point at the settlement that put them on chain.
+CREATE TABLE IF NOT EXISTS settlements (
+    id UUID PRIMARY KEY,
+    -- submitted, settled or failed
+    status TEXT NOT NULL DEFAULT 'submitted',
+    -- schedule or threshold
+    trigger TEXT NOT NULL,
+    entitlements INTEGER NOT NULL,
+    total_amount NUMERIC(20, 0) NOT NULL,
+    tx_digest TEXT,
+    error TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    finished_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_settlements_created ON settlements (created_at DESC);
+
+ALTER TABLE usage_events ADD COLUMN IF NOT EXISTS settlement_id UUID REFERENCES settlements (id);
+
+CREATE INDEX IF NOT EXISTS idx_usage_events_unsettled ON usage_events (entitlement_id) WHERE settled_at IS NULL;
@@ -0,0 +1,72 @@
+-- Extends tenant isolation to usage and webhook deliveries, which 019 left
+-- unscoped. Usage takes its tenant from the entitlement it was charged to,
+-- rolled-up usage from its service and a delivery from its webhook.
+CREATE OR REPLACE FUNCTION infrapass_assign_tenant() RETURNS TRIGGER AS $$
+BEGIN
+    IF NEW.tenant_id IS NOT NULL THEN
+        RETURN NEW;
+    END IF;
+
+    IF TG_TABLE_NAME = 'providers' THEN
+        SELECT tenant_id INTO NEW.tenant_id FROM tenant_providers WHERE provider_id = NEW.profile_id;
+    ELSIF TG_TABLE_NAME IN ('pricing_tiers', 'maintenance_windows', 'usage_daily') THEN
+        SELECT tenant_id INTO NEW.tenant_id FROM services WHERE service_id = NEW.service_id;
+    ELSIF TG_TABLE_NAME IN ('usage_events', 'usage_records') THEN
+        SELECT tenant_id INTO NEW.tenant_id FROM entitlements WHERE entitlement_id = NEW.entitlement_id;
+    ELSIF TG_TABLE_NAME = 'provider_webhook_deliveries' THEN
+        SELECT tenant_id INTO NEW.tenant_id FROM provider_webhooks WHERE id = NEW.webhook_id;
+    ELSE
+        SELECT tenant_id INTO NEW.tenant_id FROM tenant_providers WHERE provider_id = NEW.provider_id;
+    END IF;
+
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+DO $$
+DECLARE
+    t TEXT;
+BEGIN
+    FOREACH t IN ARRAY ARRAY[
+        'usage_events', 'usage_records', 'usage_daily', 'provider_webhook_deliveries'
+    ] LOOP
+        EXECUTE format('ALTER TABLE %I ADD COLUMN IF NOT EXISTS tenant_id TEXT', t);
+        EXECUTE format('CREATE INDEX IF NOT EXISTS %I ON %I (tenant_id) WHERE tenant_id IS NOT NULL', 'idx_' || t || '_tenant', t);
+
+        EXECUTE format('DROP TRIGGER IF EXISTS assign_tenant ON %I', t);
+        EXECUTE format('CREATE TRIGGER assign_tenant BEFORE INSERT ON %I FOR EACH ROW EXECUTE FUNCTION infrapass_assign_tenant()', t);
+    END LOOP;
+END
+$$;
+
+-- Tag what was written before, while every row is still visible
+UPDATE usage_events u SET tenant_id = e.tenant_id
+FROM entitlements e
+WHERE e.entitlement_id = u.entitlement_id AND e.tenant_id IS NOT NULL;
+
+UPDATE usage_records r SET tenant_id = e.tenant_id
+FROM entitlements e
+WHERE e.entitlement_id = r.entitlement_id AND e.tenant_id IS NOT NULL;
+
+UPDATE usage_daily d SET tenant_id = s.tenant_id
+FROM services s
+WHERE s.service_id = d.service_id AND s.tenant_id IS NOT NULL;
+
+UPDATE provider_webhook_deliveries d SET tenant_id = w.tenant_id
+FROM provider_webhooks w
+WHERE w.id = d.webhook_id AND w.tenant_id IS NOT NULL;
+
+DO $$
+DECLARE
+    t TEXT;
+BEGIN
+    FOREACH t IN ARRAY ARRAY[
+        'usage_events', 'usage_records', 'usage_daily', 'provider_webhook_deliveries'
+    ] LOOP
+        EXECUTE format('ALTER TABLE %I ENABLE ROW LEVEL SECURITY', t);
+        EXECUTE format('ALTER TABLE %I FORCE ROW LEVEL SECURITY', t);
+        EXECUTE format('DROP POLICY IF EXISTS tenant_isolation ON %I', t);
+        EXECUTE format('CREATE POLICY tenant_isolation ON %I USING (infrapass_tenant_visible(tenant_id)) WITH CHECK (infrapass_tenant_visible(tenant_id))', t);
+    END LOOP;
+END
+$$;
@@ -1,18 +1,27 @@
+pub mod batch;
 pub mod models;
+pub mod page;
 pub mod repository;
+pub mod sqlite;
+pub mod storage;
+pub mod tenant;
 
 use anyhow::Result;
 use sqlx::postgres::{PgPool, PgPoolOptions};
 use tracing::info;
 
-pub async fn create_pool(database_url: &str) -> Result<PgPool> {
+/// With `multi_tenant`, every connection is bound to the tenant of the task
+/// that takes it, see `tenant::bind_tenant`
+pub async fn create_pool(database_url: &str, multi_tenant: bool) -> Result<PgPool> {
     info!("Connecting to database: {}", mask_password(database_url));
 
-    let pool = PgPoolOptions::new()
+    let mut options = PgPoolOptions::new()
         .max_connections(20)
-        .acquire_timeout(std::time::Duration::from_secs(10))
-        .connect(database_url)
-        .await?;
+        .acquire_timeout(std::time::Duration::from_secs(10));
+    if multi_tenant {
+        options = tenant::bind_tenant(options);
+    }
+    let pool = options.connect(database_url).await?;
 
     info!("Database connection pool created");
 
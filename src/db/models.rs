@@ -1,8 +1,16 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
-use sqlx::{FromRow, Type};
+use sqlx::{FromRow, Type, types::Json};
 use uuid::Uuid;
 
+use crate::{
+    events::types::EventPayload,
+    types::{
+        amount::{MistAmount, Units},
+        sla::SlaTerms,
+    },
+};
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type)]
 #[sqlx(type_name = "tier_type", rename_all = "snake_case")]
 pub enum TierType {
@@ -21,6 +29,37 @@ pub struct Provider {
     pub updated_at: DateTime<Utc>,
 }
 
+/// A hosted customer whose providers' rows are isolated from other tenants
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct Tenant {
+    pub tenant_id: String,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A provider-scoped API key. The key itself is shown once at issue and only
+/// its hash is stored.
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct ApiKey {
+    pub key_id: Uuid,
+    pub provider_id: String,
+    pub label: Option<String>,
+    pub created_at: DateTime<Utc>,
+    /// In the future while a rotated key is in its grace period
+    pub revoked_at: Option<DateTime<Utc>>,
+    /// The key this one was issued to replace
+    pub rotated_from: Option<Uuid>,
+}
+
+/// Who a provider API key acts for
+#[derive(Debug, Clone, FromRow)]
+pub struct ApiKeyOwner {
+    pub key_id: Uuid,
+    pub provider_id: String,
+    /// The provider's tenant, if it has one
+    pub tenant_id: Option<String>,
+}
+
 #[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
 pub struct Service {
     pub service_id: String,
@@ -32,19 +71,160 @@ pub struct Service {
     pub updated_at: DateTime<Utc>,
 }
 
+/// A catalog listing entry: the service with its next maintenance window,
+/// the current one if it is under way
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServiceListing {
+    #[serde(flatten)]
+    pub service: Service,
+    pub upcoming_maintenance: Option<MaintenanceWindow>,
+}
+
+/// A service matching a catalog search, best matches first
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct ServiceMatch {
+    pub service_id: String,
+    pub provider_id: String,
+    pub service_type: String,
+    pub metadata_uri: Option<String>,
+    /// Cached copy of the document at `metadata_uri`, if it has been fetched
+    pub metadata: Option<serde_json::Value>,
+    pub is_active: Option<bool>,
+    pub rank: f32,
+}
+
+/// A service whose metadata is due to be fetched
+#[derive(Debug, Clone, FromRow)]
+pub struct MetadataTarget {
+    pub service_id: String,
+    pub metadata_uri: String,
+}
+
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct MaintenanceWindow {
+    pub id: Uuid,
+    pub service_id: String,
+    pub starts_at: DateTime<Utc>,
+    pub ends_at: DateTime<Utc>,
+    pub reason: Option<String>,
+    pub cancelled_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct BuyerWebhook {
+    pub id: Uuid,
+    pub user_address: String,
+    pub url: String,
+    /// Only returned once, when the webhook is registered
+    #[serde(skip_serializing)]
+    pub secret: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct ProviderWebhook {
+    pub id: Uuid,
+    pub provider_id: String,
+    pub url: String,
+    /// Only returned once, when the webhook is registered. Unset when the
+    /// secret is stored encrypted.
+    #[serde(skip_serializing)]
+    pub secret: Option<String>,
+    #[serde(skip_serializing)]
+    pub secret_ciphertext: Option<Vec<u8>>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Delivery state of one provider notification to one webhook
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct WebhookDelivery {
+    pub id: i64,
+    pub webhook_id: Uuid,
+    pub event: String,
+    pub subject_id: String,
+    /// `pending`, `delivered` or `failed`
+    pub status: String,
+    pub attempts: i32,
+    pub last_status_code: Option<i32>,
+    pub last_error: Option<String>,
+    pub next_attempt_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    pub delivered_at: Option<DateTime<Utc>>,
+}
+
+/// A delivery that is due, with the webhook it goes to
+#[derive(Debug, Clone, FromRow)]
+pub struct PendingDelivery {
+    pub id: i64,
+    pub url: String,
+    pub secret: Option<String>,
+    pub secret_ciphertext: Option<Vec<u8>>,
+    pub event: String,
+    pub payload: String,
+    pub attempts: i32,
+}
+
 #[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
 pub struct PricingTier {
     pub tier_id: String,
     pub service_id: String,
     pub tier_name: String,
-    pub price: i64,
+    pub price: MistAmount,
     pub coin_type: String,
     pub tier_type: TierType,
-    pub expires_at: Option<i64>,
-    pub quota_limit: Option<i64>,
+    pub duration_ms: Option<i64>,
+    pub quota_limit: Option<Units>,
     pub is_active: Option<bool>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    pub sla: Option<Json<SlaTerms>>,
+    /// Services currently listing the tier; only filled by catalog queries
+    #[sqlx(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub service_ids: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type)]
+#[sqlx(type_name = "tier_change", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum TierChange {
+    Price,
+    Deactivated,
+    Reactivated,
+}
+
+/// One recorded change to a tier. Price changes carry the prices, the
+/// others whether the tier was active before and after.
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct TierHistoryEntry {
+    pub id: i64,
+    pub tier_id: String,
+    pub change: TierChange,
+    pub old_price: Option<MistAmount>,
+    pub new_price: Option<MistAmount>,
+    pub old_active: Option<bool>,
+    pub new_active: Option<bool>,
+    pub checkpoint_number: i64,
+    pub transaction_digest: Option<String>,
+    pub event_index: Option<i32>,
+    pub changed_at: DateTime<Utc>,
+}
+
+/// Body of `GET /tiers/{tier_id}/history`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TierHistory {
+    pub tier: PricingTier,
+    /// Oldest first
+    pub changes: Vec<TierHistoryEntry>,
+}
+
+/// Tier a provider points holders of a retired tier to
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct TierReplacement {
+    pub tier_id: String,
+    pub replacement_tier_id: String,
+    pub created_at: DateTime<Utc>,
 }
 
 #[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
@@ -54,11 +234,135 @@ pub struct Entitlement {
     pub provider_id: String,
     pub service_id: String,
     pub tier_id: String,
-    pub price_paid: i64,
+    pub price_paid: MistAmount,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub quota: Option<Units>,
+    pub units: Units,
+    pub created_at: DateTime<Utc>,
+}
+
+/// An unexpired entitlement to a service, as listed in an access review
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct AccessRow {
+    pub user_address: String,
+    pub entitlement_id: String,
+    pub tier_id: String,
+    pub tier_name: String,
+    pub tier_type: String,
     pub expires_at: Option<DateTime<Utc>>,
-    pub quota: Option<i64>,
-    pub units: i64,
+    /// Quota or units left; `None` for subscriptions
+    pub remaining: Option<Units>,
+    pub purchased_at: DateTime<Utc>,
+}
+
+/// Purchases by one buyer of one service in one coin, within a period
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct SpendRow {
+    pub period: DateTime<Utc>,
+    pub service_id: String,
+    pub coin_type: String,
+    pub purchases: i64,
+    pub total: MistAmount,
+}
+
+/// Sales of one service in one coin within a period
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct RevenueRow {
+    pub period: DateTime<Utc>,
+    pub service_id: String,
+    pub coin_type: String,
+    pub purchases: i64,
+    pub total: MistAmount,
+}
+
+/// One usage event of a provider's service, as exported
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct UsageExportRow {
+    pub id: Uuid,
+    pub recorded_at: DateTime<Utc>,
+    pub entitlement_id: String,
+    pub service_id: String,
+    pub tier_id: String,
+    pub user_address: String,
+    pub amount: Units,
+    pub settled_at: Option<DateTime<Utc>>,
+    pub settlement_id: Option<Uuid>,
+}
+
+/// One purchase of a provider's service, as exported
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct PurchaseExportRow {
+    pub entitlement_id: String,
+    pub created_at: DateTime<Utc>,
+    pub buyer: String,
+    pub service_id: String,
+    pub tier_id: String,
+    pub tier_name: String,
+    pub coin_type: String,
+    pub price_paid: MistAmount,
+}
+
+/// A service's rolled-up usage on one UTC day
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct UsageDay {
+    pub day: NaiveDate,
+    pub buyers: i64,
+    pub submissions: i64,
+    pub amount: Units,
+}
+
+/// One buyer's rolled-up usage of a service
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct BuyerUsage {
+    pub user_address: String,
+    pub submissions: i64,
+    pub amount: Units,
+}
+
+/// Rolled-up usage of one of a provider's services
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct ServiceUsage {
+    pub service_id: String,
+    pub buyers: i64,
+    pub submissions: i64,
+    pub amount: Units,
+}
+
+/// A buyer's spending limit for one coin over the last `period_days`
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct BuyerBudget {
+    pub user_address: String,
+    pub coin_type: String,
+    pub amount: MistAmount,
+    pub period_days: i32,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A buyer's contact for one entitlement, still encrypted
+#[derive(Debug, Clone, FromRow)]
+pub struct BuyerContact {
+    pub entitlement_id: String,
+    pub buyer: String,
+    pub provider_id: String,
+    pub contact_ciphertext: Vec<u8>,
     pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A sidecar as of its last heartbeat
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct SidecarInstance {
+    pub provider_id: String,
+    pub instance_id: String,
+    pub version: String,
+    pub git_hash: String,
+    pub config_hash: String,
+    pub first_seen_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+    /// Set by the API when a newer version is running elsewhere
+    #[sqlx(default)]
+    pub outdated: bool,
 }
 
 #[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
@@ -67,6 +371,7 @@ pub struct BlockchainEvent {
     pub event_time: DateTime<Utc>,
     pub checkpoint_number: i64,
     pub transaction_digest: Option<String>,
+    pub event_index: Option<i32>,
     pub event_type: String,
     pub package_id: String,
     pub module: String,
@@ -77,6 +382,67 @@ pub struct BlockchainEvent {
     pub entitlement_id: Option<String>,
 }
 
+/// One chunk of the `blockchain_events` hypertable, covering events
+/// ingested in `[range_start, range_end)`
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct EventPartition {
+    pub chunk_name: String,
+    pub range_start: DateTime<Utc>,
+    pub range_end: DateTime<Utc>,
+}
+
+/// A listing or price change from `blockchain_events`, joined with the
+/// current service and tier names for the catalog feed
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct CatalogEvent {
+    pub id: i64,
+    pub event_time: DateTime<Utc>,
+    pub timestamp_ms: Option<i64>,
+    pub checkpoint_number: i64,
+    pub transaction_digest: Option<String>,
+    pub event_type: String,
+    pub provider_id: Option<String>,
+    pub service_id: Option<String>,
+    pub tier_id: Option<String>,
+    pub service_type: Option<String>,
+    pub metadata_uri: Option<String>,
+    pub tier_name: Option<String>,
+    pub coin_type: Option<String>,
+    pub price: Option<MistAmount>,
+    pub sla: Option<Json<SlaTerms>>,
+}
+
+/// An event the indexer could not decode, kept for `replay-dlq`
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct FailedEvent {
+    pub id: i64,
+    pub checkpoint_number: i64,
+    pub transaction_digest: Option<String>,
+    pub event_index: i32,
+    pub event_type: String,
+    pub package_id: Option<String>,
+    pub protocol_version: i64,
+    /// Raw event, for events that failed to decode
+    pub bcs: Option<Vec<u8>>,
+    /// Decoded event, for events the worker failed to handle
+    pub payload: Option<Json<EventPayload>>,
+    pub error: String,
+    pub attempts: i32,
+    pub created_at: DateTime<Utc>,
+    pub replayed_at: Option<DateTime<Utc>>,
+}
+
+/// A serialized `PubSubEvent` waiting in the outbox
+#[derive(Debug, Clone, FromRow)]
+pub struct OutboxMessage {
+    pub id: i64,
+    pub channel: String,
+    pub message: String,
+    pub attempts: i32,
+    /// False while backing off after a failed attempt
+    pub due: bool,
+}
+
 #[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
 pub struct ApiRequest {
     pub id: i64,
@@ -100,20 +466,57 @@ pub struct EntitlementWithTier {
     pub buyer: String,
     pub service_id: String,
     pub tier_id: String,
-    pub price_paid: i64,
+    pub price_paid: MistAmount,
     pub expires_at: Option<DateTime<Utc>>,
-    pub quota: Option<i64>,
-    pub units: i64,
+    pub quota: Option<Units>,
+    pub units: Units,
     pub created_at: DateTime<Utc>,
     pub provider_id: String,
     pub tier_type: String,
     pub duration_ms: Option<i64>,
-    pub quota_limit: Option<i64>,
+    pub quota_limit: Option<Units>,
+    pub tier_name: String,
+    pub tier_price: MistAmount,
+    pub coin_type: String,
+    /// Set when the entitlement's tier is retired and served under this one
+    pub replacement_tier_id: Option<String>,
 }
 
 #[derive(sqlx::FromRow)]
 pub struct AggregatedPending {
     pub entitlement_id: String,
-    pub total_amount: i64,
+    pub total_amount: Units,
     pub event_ids: Vec<Uuid>,
 }
+
+/// Usage recorded but not yet settled on chain, across all entitlements
+#[derive(Debug, Clone, FromRow)]
+pub struct UnsettledTotals {
+    pub entitlements: i64,
+    pub amount: Units,
+}
+
+/// One `settle_usage_batch` transaction submitted by the settlement job
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct Settlement {
+    pub id: Uuid,
+    /// `submitted`, `settled` or `failed`
+    pub status: String,
+    /// `schedule` or `threshold`
+    pub trigger: String,
+    pub entitlements: i32,
+    pub total_amount: Units,
+    pub tx_digest: Option<String>,
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+/// Outcome of `Repository::commit_usage`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UsageCommit {
+    Recorded,
+    /// The request ID was already committed for this entitlement; nothing
+    /// was charged
+    Duplicate,
+}
@@ -0,0 +1,167 @@
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Most rows one page may hold
+pub const MAX_PAGE_SIZE: i64 = 200;
+pub const DEFAULT_PAGE_SIZE: i64 = 50;
+
+/// Position after the last row of a page: its `created_at` and primary key.
+/// Handed out as an opaque string, so clients pass it back unchanged.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cursor {
+    pub created_at: DateTime<Utc>,
+    pub id: String,
+}
+
+impl Cursor {
+    pub fn new(created_at: DateTime<Utc>, id: &str) -> Self {
+        Self {
+            created_at,
+            id: id.to_string(),
+        }
+    }
+
+    pub fn encode(&self) -> String {
+        hex::encode(format!(
+            "{}:{}",
+            self.created_at.timestamp_micros(),
+            self.id
+        ))
+    }
+
+    pub fn decode(raw: &str) -> Result<Self> {
+        let invalid = || anyhow!("invalid cursor {}", raw);
+
+        let bytes = hex::decode(raw).map_err(|_| invalid())?;
+        let text = String::from_utf8(bytes).map_err(|_| invalid())?;
+        let (micros, id) = text.split_once(':').ok_or_else(invalid)?;
+        let micros: i64 = micros.parse().map_err(|_| invalid())?;
+        let created_at = DateTime::from_timestamp_micros(micros).ok_or_else(invalid)?;
+
+        Ok(Self::new(created_at, id))
+    }
+}
+
+/// Order of a listing. Both are by `created_at`, ties broken by ID.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    #[default]
+    Newest,
+    Oldest,
+}
+
+impl SortOrder {
+    pub(crate) fn direction(&self) -> &'static str {
+        match self {
+            SortOrder::Newest => "DESC",
+            SortOrder::Oldest => "ASC",
+        }
+    }
+
+    /// Row comparison that keeps only rows after the cursor
+    pub(crate) fn after(&self) -> &'static str {
+        match self {
+            SortOrder::Newest => "<",
+            SortOrder::Oldest => ">",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PageRequest {
+    pub limit: i64,
+    pub cursor: Option<Cursor>,
+    pub sort: SortOrder,
+}
+
+impl PageRequest {
+    /// Clamps `limit` to `1..=MAX_PAGE_SIZE` and decodes `cursor`
+    pub fn new(limit: Option<i64>, cursor: Option<&str>, sort: SortOrder) -> Result<Self> {
+        Ok(Self {
+            limit: limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE),
+            cursor: cursor.map(Cursor::decode).transpose()?,
+            sort,
+        })
+    }
+
+    pub(crate) fn cursor_time(&self) -> Option<DateTime<Utc>> {
+        self.cursor.as_ref().map(|c| c.created_at)
+    }
+
+    pub(crate) fn cursor_id(&self) -> Option<&str> {
+        self.cursor.as_ref().map(|c| c.id.as_str())
+    }
+
+    /// Rows to fetch: one past the page, to tell whether another follows
+    pub(crate) fn fetch_limit(&self) -> i64 {
+        self.limit + 1
+    }
+}
+
+/// One page of a listing. `next_cursor` is set when more rows follow.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub next_cursor: Option<String>,
+}
+
+impl<T> Page<T> {
+    /// Builds a page from up to `fetch_limit` rows, `key` giving each row's
+    /// cursor
+    pub(crate) fn from_rows(
+        mut rows: Vec<T>,
+        request: &PageRequest,
+        key: impl Fn(&T) -> Cursor,
+    ) -> Self {
+        let more = rows.len() as i64 > request.limit;
+        rows.truncate(request.limit as usize);
+        let next_cursor = if more {
+            rows.last().map(|row| key(row).encode())
+        } else {
+            None
+        };
+
+        Self {
+            items: rows,
+            next_cursor,
+        }
+    }
+
+    /// The same page with each item mapped by `f`
+    pub fn map<U>(self, f: impl FnMut(T) -> U) -> Page<U> {
+        Page {
+            items: self.items.into_iter().map(f).collect(),
+            next_cursor: self.next_cursor,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ProviderFilter {
+    pub active: Option<bool>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ServiceFilter {
+    pub provider_id: Option<String>,
+    pub service_type: Option<String>,
+    pub active: Option<bool>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TierFilter {
+    /// Provider of the service that created the tier
+    pub provider_id: Option<String>,
+    /// As indexed, see `spend::indexed_coin_type`
+    pub coin_type: Option<String>,
+    pub active: Option<bool>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct EntitlementFilter {
+    /// Unexpired (true) or expired (false), by the same rule as access
+    /// reviews, grace period included
+    pub active: Option<bool>,
+}
@@ -1,29 +1,66 @@
 
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc};
 
 use anyhow::Result;
-use sqlx::PgPool;
+use chrono::{DateTime, NaiveDate, Utc};
+use sqlx::{Connection, PgConnection, PgPool, Postgres, Transaction, types::Json};
+use tracing::warn;
 use uuid::Uuid;
 
 use crate::{
-    db::models::{AggregatedPending, BlockchainEvent, Entitlement, EntitlementWithTier, PricingTier, Provider, Service, TierType}, events::types::{EntitlementConfig, EntitlementPurchased, ProtocolEvent}, sidecar::validator::ValidateResponse, utils::error::InfrapassError
+    api_types::validator::{RecordUsageRequest, SidecarHeartbeat, ValidateResponse},
+    backend::provider_webhooks::ProviderWebhookPayload,
+    db::models::{AccessRow, AggregatedPending, ApiKey, ApiKeyOwner, BlockchainEvent, BuyerBudget, BuyerContact, BuyerUsage, BuyerWebhook, CatalogEvent, Entitlement, FailedEvent, EntitlementWithTier, EventPartition, MaintenanceWindow, MetadataTarget, OutboxMessage, PendingDelivery, PricingTier, Provider, ProviderWebhook, PurchaseExportRow, RevenueRow, Service, ServiceMatch, ServiceUsage, Settlement, SidecarInstance, SpendRow, TierChange, TierHistoryEntry, TierReplacement, TierType, Tenant, UnsettledTotals, UsageCommit, UsageDay, UsageExportRow, WebhookDelivery}, db::page::{Cursor, EntitlementFilter, Page, PageRequest, ProviderFilter, ServiceFilter, TierFilter}, db::batch::{self, BufferedEvent}, db::tenant, events::types::{EntitlementConfig, EntitlementPurchased, EventPayload, ProtocolEvent}, pubsub::types::PubSubEvent, types::{amount::{MistAmount, Units}, sla::SlaTerms}, utils::{error::InfrapassError, get_channel}
 };
 
+/// Advisory lock held while draining `pubsub_outbox`
+const OUTBOX_LOCK_ID: i64 = 0x6f7574626f78;
+
 pub struct Repository {
-    pool: Arc<PgPool>
+    pool: Arc<PgPool>,
+    /// Replica for reads that can lag the primary, see `read_pool`
+    read_pool: Option<Arc<PgPool>>,
+    /// Seconds an entitlement stays valid past `expires_at`, absorbing clock skew
+    expiry_grace_secs: i64,
 }
 
 impl Repository {
     pub fn new(pool: Arc<PgPool>) -> Self {
-        Self { pool }
+        Self { pool, read_pool: None, expiry_grace_secs: 0 }
+    }
+
+    pub fn with_read_pool(mut self, pool: Arc<PgPool>) -> Self {
+        self.read_pool = Some(pool);
+        self
+    }
+
+    pub fn with_expiry_grace(mut self, secs: u64) -> Self {
+        self.expiry_grace_secs = secs as i64;
+        self
     }
 
     pub fn pool(&self) -> &PgPool {
         &self.pool
     }
 
+    /// The replica when one is configured, else the primary. Only for the
+    /// validator lookup and public listings and reports, which can serve rows
+    /// a moment old; reads that decide a write stay on `pool`. A stale
+    /// validation is harmless, as `commit_usage` checks the balance again on
+    /// the primary.
+    pub fn read_pool(&self) -> &PgPool {
+        self.read_pool.as_deref().unwrap_or(&self.pool)
+    }
+
+    /// Starts a transaction for writes that must land together, such as one
+    /// indexed event and its projection. Pass `&mut tx` as `conn`, then commit.
+    pub async fn begin(&self) -> Result<Transaction<'static, Postgres>> {
+        Ok(self.pool().begin().await?)
+    }
+
     pub async fn create_provider(
         &self,
+        conn: &mut PgConnection,
         profile_id: &str,
         provider_address: String,
         metadata: &str,
@@ -40,7 +77,7 @@ impl Repository {
         .bind(profile_id)
         .bind(provider_address)
         .bind(metadata)
-        .fetch_one(self.pool())
+        .fetch_one(&mut *conn)
         .await?;
 
         Ok(provider)
@@ -70,19 +107,38 @@ impl Repository {
         Ok(provider)
     }
 
-    pub async fn list_providers(&self, limit: i64) -> Result<Vec<Provider>> {
-        let providers = sqlx::query_as(
-            r#"SELECT * FROM providers WHERE is_active = true ORDER BY created_at DESC LIMIT $1"#,
-        )
-        .bind(limit)
-        .fetch_all(self.pool())
-        .await?;
+    pub async fn list_providers(
+        &self,
+        filter: &ProviderFilter,
+        page: &PageRequest,
+    ) -> Result<Page<Provider>> {
+        let query = format!(
+            r#"
+            SELECT * FROM providers
+            WHERE ($1::BOOLEAN IS NULL OR COALESCE(is_active, false) = $1)
+              AND ($2::TIMESTAMPTZ IS NULL OR (created_at, profile_id) {after} ($2, $3))
+            ORDER BY created_at {dir}, profile_id {dir}
+            LIMIT $4
+            "#,
+            after = page.sort.after(),
+            dir = page.sort.direction(),
+        );
+        let providers = sqlx::query_as::<_, Provider>(&query)
+            .bind(filter.active)
+            .bind(page.cursor_time())
+            .bind(page.cursor_id())
+            .bind(page.fetch_limit())
+            .fetch_all(self.read_pool())
+            .await?;
 
-        Ok(providers)
+        Ok(Page::from_rows(providers, page, |p| {
+            Cursor::new(p.created_at, &p.profile_id)
+        }))
     }
 
     pub async fn create_service(
         &self,
+        conn: &mut PgConnection,
         service_id: &str,
         provider_id: &str,
         service_type: &str,
@@ -93,7 +149,12 @@ impl Repository {
             INSERT INTO services (service_id, provider_id, service_type, metadata_uri)
             VALUES ($1, $2, $3, $4)
             ON CONFLICT (service_id) DO UPDATE
-            SET metadata_uri = EXCLUDED.metadata_uri, updated_at = NOW()
+            SET metadata_uri = EXCLUDED.metadata_uri,
+                metadata_fetched_at = CASE
+                    WHEN services.metadata_uri IS DISTINCT FROM EXCLUDED.metadata_uri THEN NULL
+                    ELSE services.metadata_fetched_at
+                END,
+                updated_at = NOW()
             RETURNING *
             "#,
         )
@@ -101,7 +162,7 @@ impl Repository {
         .bind(provider_id)
         .bind(service_type)
         .bind(metadata_uri)
-        .fetch_one(self.pool())
+        .fetch_one(&mut *conn)
         .await?;
 
         Ok(service)
@@ -116,6 +177,17 @@ impl Repository {
         Ok(service)
     }
 
+    /// `get_service` on `conn`, so it sees services written earlier in the
+    /// same transaction
+    pub async fn lookup_service(&self, conn: &mut PgConnection, service_id: &str) -> Result<Option<Service>> {
+        let service = sqlx::query_as("SELECT * FROM services WHERE service_id = $1")
+            .bind(service_id)
+            .fetch_optional(&mut *conn)
+            .await?;
+
+        Ok(service)
+    }
+
     pub async fn list_services_by_provider(&self, provider_id: &str) -> Result<Vec<Service>> {
         let services = sqlx::query_as(
             "SELECT * FROM services WHERE provider_id = $1 ORDER BY created_at DESC",
@@ -127,29 +199,169 @@ impl Repository {
         Ok(services)
     }
 
-    pub async fn list_services(&self, limit: i64) -> Result<Vec<Service>> {
-        let services = sqlx::query_as(
-            "SELECT * FROM services WHERE is_active = true ORDER BY created_at DESC LIMIT $1",
+    pub async fn list_services(
+        &self,
+        filter: &ServiceFilter,
+        page: &PageRequest,
+    ) -> Result<Page<Service>> {
+        let query = format!(
+            r#"
+            SELECT * FROM services
+            WHERE ($1::TEXT IS NULL OR provider_id = $1)
+              AND ($2::TEXT IS NULL OR service_type = $2)
+              AND ($3::BOOLEAN IS NULL OR COALESCE(is_active, false) = $3)
+              AND ($4::TIMESTAMPTZ IS NULL OR (created_at, service_id) {after} ($4, $5))
+            ORDER BY created_at {dir}, service_id {dir}
+            LIMIT $6
+            "#,
+            after = page.sort.after(),
+            dir = page.sort.direction(),
+        );
+        let services = sqlx::query_as::<_, Service>(&query)
+            .bind(filter.provider_id.as_deref())
+            .bind(filter.service_type.as_deref())
+            .bind(filter.active)
+            .bind(page.cursor_time())
+            .bind(page.cursor_id())
+            .bind(page.fetch_limit())
+            .fetch_all(self.read_pool())
+            .await?;
+
+        Ok(Page::from_rows(services, page, |s| {
+            Cursor::new(s.created_at, &s.service_id)
+        }))
+    }
+
+    pub async fn update_service_metadata(
+        &self,
+        conn: &mut PgConnection,
+        service_id: &str,
+        metadata_uri: &str,
+    ) -> Result<Service> {
+        let service = sqlx::query_as(
+            r#"
+            UPDATE services 
+            SET metadata_uri = $1,
+                metadata_fetched_at = CASE
+                    WHEN metadata_uri IS DISTINCT FROM $1 THEN NULL
+                    ELSE metadata_fetched_at
+                END,
+                updated_at = NOW() 
+            WHERE service_id = $2 
+            RETURNING *
+            "#,
+        )
+        .bind(metadata_uri)
+        .bind(service_id)
+        .fetch_one(&mut *conn)
+        .await?;
+
+        Ok(service)
+    }
+
+    /// Services whose metadata has never been fetched, or was last fetched
+    /// more than `refresh_after_secs` ago, oldest first
+    pub async fn services_due_for_metadata(
+        &self,
+        refresh_after_secs: i64,
+        limit: i64,
+    ) -> Result<Vec<MetadataTarget>> {
+        let targets = sqlx::query_as(
+            r#"
+            SELECT service_id, metadata_uri FROM services
+            WHERE metadata_uri IS NOT NULL
+              AND (metadata_fetched_at IS NULL
+                   OR metadata_fetched_at < NOW() - make_interval(secs => $1))
+            ORDER BY metadata_fetched_at NULLS FIRST
+            LIMIT $2
+            "#,
         )
+        .bind(refresh_after_secs as f64)
         .bind(limit)
         .fetch_all(self.pool())
         .await?;
 
-        Ok(services)
+        Ok(targets)
+    }
+
+    /// Records the outcome of fetching `metadata_uri`. A failed fetch keeps
+    /// the last good document so the service stays searchable. Nothing is
+    /// written if the URI changed while the fetch was in flight.
+    pub async fn store_service_metadata(
+        &self,
+        service_id: &str,
+        metadata_uri: &str,
+        fetched: Result<&serde_json::Value, &str>,
+    ) -> Result<()> {
+        let (metadata, error) = match fetched {
+            Ok(metadata) => (Some(metadata), None),
+            Err(error) => (None, Some(error)),
+        };
+
+        sqlx::query(
+            r#"
+            UPDATE services
+            SET metadata = COALESCE($3, metadata),
+                metadata_error = $4,
+                metadata_fetched_at = NOW()
+            WHERE service_id = $1 AND metadata_uri = $2
+            "#,
+        )
+        .bind(service_id)
+        .bind(metadata_uri)
+        .bind(metadata)
+        .bind(error)
+        .execute(self.pool())
+        .await?;
+
+        Ok(())
+    }
+
+    /// Ranks services against a web-search style query (quoted phrases,
+    /// `or`, `-term`) over the service type and cached metadata
+    pub async fn search_services(
+        &self,
+        query: &str,
+        active: Option<bool>,
+        limit: i64,
+    ) -> Result<Vec<ServiceMatch>> {
+        let matches = sqlx::query_as(
+            r#"
+            SELECT service_id, provider_id, service_type, metadata_uri, metadata, is_active,
+                   ts_rank(search_vector, q) AS rank
+            FROM services, websearch_to_tsquery('english', $1) q
+            WHERE search_vector @@ q
+              AND ($2::BOOLEAN IS NULL OR COALESCE(is_active, false) = $2)
+            ORDER BY rank DESC, service_id
+            LIMIT $3
+            "#,
+        )
+        .bind(query)
+        .bind(active)
+        .bind(limit)
+        .fetch_all(self.read_pool())
+        .await?;
+
+        Ok(matches)
     }
 
-    pub async fn update_service_metadata(&self, service_id: &str, metadata_uri: &str) -> Result<Service> {
+    pub async fn set_service_active(
+        &self,
+        conn: &mut PgConnection,
+        service_id: &str,
+        active: bool,
+    ) -> Result<Service> {
         let service = sqlx::query_as(
             r#"
-            UPDATE services 
-            SET metadata_uri = $1, updated_at = NOW() 
-            WHERE service_id = $2 
+            UPDATE services
+            SET is_active = $1, updated_at = NOW()
+            WHERE service_id = $2
             RETURNING *
             "#,
         )
-        .bind(metadata_uri)
+        .bind(active)
         .bind(service_id)
-        .fetch_one(self.pool())
+        .fetch_one(&mut *conn)
         .await?;
 
         Ok(service)
@@ -157,14 +369,15 @@ impl Repository {
 
     pub async fn create_tier(
         &self,
+        conn: &mut PgConnection,
         tier_id: &str,
         service_id: &str,
         tier_name: &str,
-        price: i64,
+        price: MistAmount,
         coin_type: &str,
         tier_type: TierType,
         duration_ms: Option<i64>,
-        quota_limit: Option<i64>,
+        quota_limit: Option<Units>,
     ) -> Result<PricingTier> {
         let tier = sqlx::query_as::<_, PricingTier>(
             r#"
@@ -179,7 +392,7 @@ impl Repository {
             RETURNING 
                 tier_id, service_id, tier_name, price, coin_type,
                 tier_type,
-                duration_ms, quota_limit, is_active, created_at, updated_at
+                duration_ms, quota_limit, is_active, created_at, updated_at, sla
             "#,
         )
         .bind(tier_id)
@@ -190,7 +403,7 @@ impl Repository {
         .bind(tier_type)
         .bind(duration_ms)
         .bind(quota_limit)
-        .fetch_one(self.pool())
+        .fetch_one(&mut *conn)
         .await?;
 
         Ok(tier)
@@ -202,7 +415,11 @@ impl Repository {
             SELECT 
                 tier_id, service_id, tier_name, price, coin_type,
                 tier_type,
-                duration_ms, quota_limit, is_active, created_at, updated_at
+                duration_ms, quota_limit, is_active, created_at, updated_at, sla,
+                ARRAY(
+                    SELECT l.service_id FROM service_tiers l
+                    WHERE l.tier_id = pricing_tiers.tier_id ORDER BY l.added_at
+                ) AS service_ids
             FROM pricing_tiers 
             WHERE tier_id = $1
             "#,
@@ -214,46 +431,153 @@ impl Repository {
         Ok(tier)
     }
 
-    pub async fn list_tiers_by_service(&self, service_id: &str) -> Result<Vec<PricingTier>> {
+    /// Up to `limit` tiers picked at random, for spot checks against chain
+    pub async fn sample_tiers(&self, limit: i64) -> Result<Vec<PricingTier>> {
         let tiers = sqlx::query_as(
             r#"
             SELECT 
                 tier_id, service_id, tier_name, price, coin_type,
                 tier_type,
-                duration_ms, quota_limit, is_active, created_at, updated_at
+                duration_ms, quota_limit, is_active, created_at, updated_at, sla,
+                ARRAY(
+                    SELECT l.service_id FROM service_tiers l
+                    WHERE l.tier_id = pricing_tiers.tier_id ORDER BY l.added_at
+                ) AS service_ids
             FROM pricing_tiers 
-            WHERE service_id = $1 AND is_active = true
-            ORDER BY price ASC
+            ORDER BY random()
+            LIMIT $1
             "#,
         )
-        .bind(service_id)
+        .bind(limit)
         .fetch_all(self.pool())
         .await?;
 
         Ok(tiers)
     }
 
-    pub async fn list_tiers(&self, limit: i64) -> Result<Vec<PricingTier>> {
+    /// Active tiers the service currently lists, which may include tiers
+    /// created by another service
+    pub async fn list_tiers_by_service(&self, service_id: &str) -> Result<Vec<PricingTier>> {
         let tiers = sqlx::query_as(
             r#"
             SELECT 
-                tier_id, service_id, tier_name, price, coin_type,
-                tier_type,
-                duration_ms, quota_limit, is_active, created_at, updated_at
-            FROM pricing_tiers 
-            WHERE is_active = true
-            ORDER BY created_at DESC
-            LIMIT $1
+                t.tier_id, t.service_id, t.tier_name, t.price, t.coin_type,
+                t.tier_type,
+                t.duration_ms, t.quota_limit, t.is_active, t.created_at, t.updated_at, t.sla,
+                ARRAY(
+                    SELECT l.service_id FROM service_tiers l
+                    WHERE l.tier_id = t.tier_id ORDER BY l.added_at
+                ) AS service_ids
+            FROM pricing_tiers t
+            JOIN service_tiers st ON st.tier_id = t.tier_id
+            WHERE st.service_id = $1 AND t.is_active = true
+            ORDER BY t.price ASC
             "#,
         )
-        .bind(limit)
-        .fetch_all(self.pool())
+        .bind(service_id)
+        .fetch_all(self.read_pool())
         .await?;
 
         Ok(tiers)
     }
 
-    pub async fn update_tier_price(&self, tier_id: &str, new_price: i64) -> Result<PricingTier> {
+    pub async fn list_tiers(
+        &self,
+        filter: &TierFilter,
+        page: &PageRequest,
+    ) -> Result<Page<PricingTier>> {
+        let query = format!(
+            r#"
+            SELECT 
+                t.tier_id, t.service_id, t.tier_name, t.price, t.coin_type,
+                t.tier_type,
+                t.duration_ms, t.quota_limit, t.is_active, t.created_at, t.updated_at, t.sla,
+                ARRAY(
+                    SELECT l.service_id FROM service_tiers l
+                    WHERE l.tier_id = t.tier_id ORDER BY l.added_at
+                ) AS service_ids
+            FROM pricing_tiers t
+            JOIN services s ON s.service_id = t.service_id
+            WHERE ($1::TEXT IS NULL OR s.provider_id = $1)
+              AND ($2::TEXT IS NULL OR t.coin_type = $2)
+              AND ($3::BOOLEAN IS NULL OR COALESCE(t.is_active, false) = $3)
+              AND ($4::TIMESTAMPTZ IS NULL OR (t.created_at, t.tier_id) {after} ($4, $5))
+            ORDER BY t.created_at {dir}, t.tier_id {dir}
+            LIMIT $6
+            "#,
+            after = page.sort.after(),
+            dir = page.sort.direction(),
+        );
+        let tiers = sqlx::query_as::<_, PricingTier>(&query)
+            .bind(filter.provider_id.as_deref())
+            .bind(filter.coin_type.as_deref())
+            .bind(filter.active)
+            .bind(page.cursor_time())
+            .bind(page.cursor_id())
+            .bind(page.fetch_limit())
+            .fetch_all(self.read_pool())
+            .await?;
+
+        Ok(Page::from_rows(tiers, page, |t| {
+            Cursor::new(t.created_at, &t.tier_id)
+        }))
+    }
+
+    /// Lists a tier under a service. Idempotent, so replaying the event is a no-op.
+    pub async fn add_service_tier(
+        &self,
+        conn: &mut PgConnection,
+        service_id: &str,
+        tier_id: &str,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO service_tiers (service_id, tier_id)
+            VALUES ($1, $2)
+            ON CONFLICT (service_id, tier_id) DO NOTHING
+            "#,
+        )
+        .bind(service_id)
+        .bind(tier_id)
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn remove_service_tier(
+        &self,
+        conn: &mut PgConnection,
+        service_id: &str,
+        tier_id: &str,
+    ) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM service_tiers WHERE service_id = $1 AND tier_id = $2")
+            .bind(service_id)
+            .bind(tier_id)
+            .execute(&mut *conn)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Every tier the service lists, active or not
+    pub async fn list_service_tier_ids(&self, service_id: &str) -> Result<Vec<String>> {
+        let ids = sqlx::query_scalar(
+            "SELECT tier_id FROM service_tiers WHERE service_id = $1 ORDER BY added_at",
+        )
+        .bind(service_id)
+        .fetch_all(self.pool())
+        .await?;
+
+        Ok(ids)
+    }
+
+    pub async fn update_tier_price(
+        &self,
+        conn: &mut PgConnection,
+        tier_id: &str,
+        new_price: MistAmount,
+    ) -> Result<PricingTier> {
         let tier = sqlx::query_as(
             r#"
             UPDATE pricing_tiers 
@@ -262,18 +586,22 @@ impl Repository {
             RETURNING 
                 tier_id, service_id, tier_name, price, coin_type,
                 tier_type,
-                duration_ms, quota_limit, is_active, created_at, updated_at
+                duration_ms, quota_limit, is_active, created_at, updated_at, sla
             "#,
         )
         .bind(new_price)
         .bind(tier_id)
-        .fetch_one(self.pool())
+        .fetch_one(&mut *conn)
         .await?;
 
         Ok(tier)
     }
 
-    pub async fn deactivate_tier(&self, tier_id: &str) -> Result<PricingTier> {
+    pub async fn deactivate_tier(
+        &self,
+        conn: &mut PgConnection,
+        tier_id: &str,
+    ) -> Result<PricingTier> {
         let tier = sqlx::query_as(
             r#"
             UPDATE pricing_tiers 
@@ -282,17 +610,21 @@ impl Repository {
             RETURNING 
                 tier_id, service_id, tier_name, price, coin_type,
                 tier_type,
-                duration_ms, quota_limit, is_active, created_at, updated_at
+                duration_ms, quota_limit, is_active, created_at, updated_at, sla
             "#,
         )
         .bind(tier_id)
-        .fetch_one(self.pool())
+        .fetch_one(&mut *conn)
         .await?;
 
         Ok(tier)
     }
 
-    pub async fn reactivate_tier(&self, tier_id: &str) -> Result<PricingTier> {
+    pub async fn reactivate_tier(
+        &self,
+        conn: &mut PgConnection,
+        tier_id: &str,
+    ) -> Result<PricingTier> {
         let tier = sqlx::query_as(
             r#"
             UPDATE pricing_tiers 
@@ -301,295 +633,2354 @@ impl Repository {
             RETURNING 
                 tier_id, service_id, tier_name, price, coin_type,
                 tier_type,
-                duration_ms, quota_limit, is_active, created_at, updated_at
+                duration_ms, quota_limit, is_active, created_at, updated_at, sla
             "#,
         )
         .bind(tier_id)
-        .fetch_one(self.pool())
+        .fetch_one(&mut *conn)
         .await?;
 
         Ok(tier)
     }
 
-    pub async fn create_entitlement(
+    /// Appends a change to the tier's history, taking the old values from
+    /// the tier as it stands, so call it before applying the change.
+    /// `new_price` is only read for price changes. A replayed event is
+    /// recorded once.
+    pub async fn record_tier_change(
         &self,
-        event: &EntitlementPurchased,
-    ) -> Result<Entitlement> {
-        let entitlement_id = event.entitlement_id.bytes.to_string();
-        let service_id = event.service_id.bytes.to_string();
-        let tier_id = event.tier_id.bytes.to_string();
-    
-        let created_at = chrono::DateTime::<chrono::Utc>::from_timestamp_millis(
-            event.timestamp as i64
-        )
-        .ok_or_else(|| anyhow::anyhow!("Invalid timestamp"))?;
-    
-        let (expires_at, quota, units) = match &event.inner {
-            &EntitlementConfig::Subscription { expires_at } => {
-                (
-                    Some(
-                        chrono::DateTime::<chrono::Utc>::from_timestamp_millis(
-                            expires_at as i64
-                        )
-                        .ok_or_else(|| anyhow::anyhow!("Invalid expires_at"))?
-                    ),
-                    None,
-                    0i64,
-                )
-            }
-    
-            EntitlementConfig::Quota { expires_at, quota } => {
-                (
-                    Some(
-                        chrono::DateTime::<chrono::Utc>::from_timestamp_millis(
-                            *expires_at as i64
-                        )
-                        .ok_or_else(|| anyhow::anyhow!("Invalid expires_at"))?
-                    ),
-                    Some(*quota as i64),
-                    0i64,
-                )
-            }
-    
-            EntitlementConfig::UsageBased { units } => {
-                (None, None, *units as i64)
-            }
-        };
-    
-        let entitlement = sqlx::query_as::<_, Entitlement>(
+        conn: &mut PgConnection,
+        tier_id: &str,
+        change: TierChange,
+        new_price: Option<MistAmount>,
+        payload: &EventPayload,
+        timestamp_ms: u64,
+    ) -> Result<()> {
+        let changed_at = DateTime::<Utc>::from_timestamp_millis(timestamp_ms as i64)
+            .ok_or_else(|| anyhow::anyhow!("Invalid timestamp"))?;
+
+        sqlx::query(
             r#"
-            WITH inserted AS (
-            INSERT INTO entitlements
-            (entitlement_id, buyer, service_id, tier_id, price_paid, expires_at, quota, units, created_at)
-            VALUES ($1,$2,$3,$4,$5,$6,$7,$8,$9)
-            ON CONFLICT (entitlement_id) DO NOTHING
-            RETURNING *
+            INSERT INTO tier_history (
+                tier_id, change, old_price, new_price, old_active, new_active,
+                checkpoint_number, transaction_digest, event_index, changed_at
             )
-            SELECT 
-            inserted.*,
-            s.provider_id
-            FROM inserted
-            JOIN services s ON s.service_id = inserted.service_id
-                "#,
+            SELECT
+                tier_id, $2,
+                CASE WHEN $2 = 'price' THEN price END,
+                CASE WHEN $2 = 'price' THEN $3 END,
+                CASE WHEN $2 <> 'price' THEN is_active END,
+                CASE $2 WHEN 'deactivated' THEN false WHEN 'reactivated' THEN true END,
+                $4, $5, $6, $7
+            FROM pricing_tiers
+            WHERE tier_id = $1
+            ON CONFLICT (transaction_digest, event_index) WHERE transaction_digest IS NOT NULL
+            DO NOTHING
+            "#,
         )
-        .bind(&entitlement_id)
-        .bind(&event.buyer.to_string())
-        .bind(&service_id)
-        .bind(&tier_id)
-        .bind(event.price_paid as i64)
-        .bind(expires_at)
-        .bind(quota)
-        .bind(units)
-        .bind(created_at)
-        .fetch_one(self.pool())
+        .bind(tier_id)
+        .bind(change)
+        .bind(new_price)
+        .bind(payload.checkpoint as i64)
+        .bind(payload.tx_digest.as_deref())
+        .bind(payload.event_index as i32)
+        .bind(changed_at)
+        .execute(&mut *conn)
         .await?;
-    
-        Ok(entitlement)
-    }
 
-    pub async fn store_event(
-        &self,
-        event: &ProtocolEvent,
-        checkpoint: u64,
-        tx_digest: Option<String>,
-    ) -> Result<()> {
-        match event {
-            ProtocolEvent::ProviderRegistered(e) => {
-                let prof_id = e.profile_id.bytes.to_string();
-                sqlx::query(
-                    r#"
-                    INSERT INTO blockchain_events 
-                    (checkpoint_number, transaction_digest, event_type, package_id, module, event_data, provider_id)
-                    VALUES ($1, $2, $3, $4, $5, $6, $7)
-                    "#,
-                )
-                .bind(checkpoint as i64)
-                .bind(tx_digest)
-                .bind("ProviderRegistered")
-                .bind(crate::utils::constants::PACKAGE_ID)
-                .bind("registry")
-                .bind(serde_json::to_value(e)?)
-                .bind(&prof_id)
-                .execute(self.pool())
-                .await?;
+        Ok(())
+    }
 
-                self.create_provider(&prof_id, e.provider_address.to_string(), &e.metadata)
-                    .await?;
-            }
+    /// The tier's recorded changes, oldest first
+    pub async fn tier_history(&self, tier_id: &str) -> Result<Vec<TierHistoryEntry>> {
+        let history = sqlx::query_as(
+            r#"
+            SELECT id, tier_id, change, old_price, new_price, old_active, new_active,
+                   checkpoint_number, transaction_digest, event_index, changed_at
+            FROM tier_history
+            WHERE tier_id = $1
+            ORDER BY checkpoint_number, id
+            "#,
+        )
+        .bind(tier_id)
+        .fetch_all(self.read_pool())
+        .await?;
 
-            ProtocolEvent::ServiceCreated(e) => {
-                let service_type = String::from_utf8_lossy(&e.service_type).to_string();
-                let metadata_uri = String::from_utf8_lossy(&e.metadata_uri).to_string();
-                let prof_id = e.provider.bytes.to_string();
-                let serv = e.service_id.bytes.to_string();
+        Ok(history)
+    }
 
-                sqlx::query(
-                    r#"
-                    INSERT INTO blockchain_events 
-                    (checkpoint_number, transaction_digest, event_type, package_id, module, event_data, provider_id, service_id)
-                    VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
-                    "#,
-                    
-                )
-                .bind(checkpoint as i64)
-                .bind(tx_digest)
-                .bind("ServiceCreated")
-                .bind(crate::utils::constants::PACKAGE_ID)
-                .bind("registry")
-                .bind(serde_json::to_value(e)?)
-                .bind(&prof_id)
-                .bind(&serv)
-                .execute(self.pool())
-                .await?;
+    /// Replaces the tier's SLA terms; `None` clears them
+    pub async fn set_tier_sla(&self, tier_id: &str, sla: Option<&SlaTerms>) -> Result<Option<PricingTier>> {
+        let tier = sqlx::query_as(
+            r#"
+            UPDATE pricing_tiers 
+            SET sla = $1, updated_at = NOW() 
+            WHERE tier_id = $2 
+            RETURNING 
+                tier_id, service_id, tier_name, price, coin_type,
+                tier_type,
+                duration_ms, quota_limit, is_active, created_at, updated_at, sla
+            "#,
+        )
+        .bind(sla.map(Json))
+        .bind(tier_id)
+        .fetch_optional(self.pool())
+        .await?;
+
+        Ok(tier)
+    }
+
+    /// Points holders of `tier_id` to `replacement_tier_id` once `tier_id`
+    /// is retired, replacing any earlier mapping
+    pub async fn set_tier_replacement(&self, tier_id: &str, replacement_tier_id: &str) -> Result<TierReplacement> {
+        let replacement = sqlx::query_as(
+            r#"
+            INSERT INTO tier_replacements (tier_id, replacement_tier_id)
+            VALUES ($1, $2)
+            ON CONFLICT (tier_id) DO UPDATE
+            SET replacement_tier_id = EXCLUDED.replacement_tier_id, created_at = NOW()
+            RETURNING *
+            "#,
+        )
+        .bind(tier_id)
+        .bind(replacement_tier_id)
+        .fetch_one(self.pool())
+        .await?;
+
+        Ok(replacement)
+    }
+
+    pub async fn clear_tier_replacement(&self, tier_id: &str) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM tier_replacements WHERE tier_id = $1")
+            .bind(tier_id)
+            .execute(self.pool())
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn get_tier_replacement(&self, tier_id: &str) -> Result<Option<TierReplacement>> {
+        let replacement = sqlx::query_as("SELECT * FROM tier_replacements WHERE tier_id = $1")
+            .bind(tier_id)
+            .fetch_optional(self.pool())
+            .await?;
+
+        Ok(replacement)
+    }
+
+    pub async fn create_maintenance_window(
+        &self,
+        service_id: &str,
+        starts_at: DateTime<Utc>,
+        ends_at: DateTime<Utc>,
+        reason: Option<&str>,
+    ) -> Result<MaintenanceWindow> {
+        let window = sqlx::query_as(
+            r#"
+            INSERT INTO maintenance_windows (id, service_id, starts_at, ends_at, reason)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING *
+            "#,
+        )
+        .bind(Uuid::new_v4())
+        .bind(service_id)
+        .bind(starts_at)
+        .bind(ends_at)
+        .bind(reason)
+        .fetch_one(self.pool())
+        .await?;
+
+        Ok(window)
+    }
+
+    /// Active and upcoming windows for a service, soonest first
+    pub async fn get_upcoming_maintenance(&self, service_id: &str) -> Result<Vec<MaintenanceWindow>> {
+        let windows = sqlx::query_as(
+            r#"
+            SELECT * FROM maintenance_windows
+            WHERE service_id = $1
+              AND cancelled_at IS NULL
+              AND ends_at > NOW()
+            ORDER BY starts_at ASC
+            "#,
+        )
+        .bind(service_id)
+        .fetch_all(self.pool())
+        .await?;
+
+        Ok(windows)
+    }
+
+    /// Active and upcoming windows across a provider's services, soonest
+    /// first
+    pub async fn get_provider_upcoming_maintenance(&self, provider_id: &str) -> Result<Vec<MaintenanceWindow>> {
+        let windows = sqlx::query_as(
+            r#"
+            SELECT m.* FROM maintenance_windows m
+            JOIN services s ON s.service_id = m.service_id
+            WHERE s.provider_id = $1
+              AND m.cancelled_at IS NULL
+              AND m.ends_at > NOW()
+            ORDER BY m.starts_at ASC
+            "#,
+        )
+        .bind(provider_id)
+        .fetch_all(self.pool())
+        .await?;
+
+        Ok(windows)
+    }
+
+    /// The soonest active or upcoming window of each of `service_ids`,
+    /// keyed by service
+    pub async fn next_maintenance(&self, service_ids: &[String]) -> Result<HashMap<String, MaintenanceWindow>> {
+        let windows: Vec<MaintenanceWindow> = sqlx::query_as(
+            r#"
+            SELECT DISTINCT ON (service_id) * FROM maintenance_windows
+            WHERE service_id = ANY($1)
+              AND cancelled_at IS NULL
+              AND ends_at > NOW()
+            ORDER BY service_id, starts_at ASC
+            "#,
+        )
+        .bind(service_ids)
+        .fetch_all(self.read_pool())
+        .await?;
+
+        Ok(windows.into_iter().map(|w| (w.service_id.clone(), w)).collect())
+    }
+
+    pub async fn get_maintenance_window(&self, id: Uuid) -> Result<Option<MaintenanceWindow>> {
+        let window = sqlx::query_as("SELECT * FROM maintenance_windows WHERE id = $1")
+            .bind(id)
+            .fetch_optional(self.pool())
+            .await?;
+
+        Ok(window)
+    }
+
+    pub async fn cancel_maintenance_window(&self, id: Uuid) -> Result<Option<MaintenanceWindow>> {
+        let window = sqlx::query_as(
+            r#"
+            UPDATE maintenance_windows
+            SET cancelled_at = NOW()
+            WHERE id = $1 AND cancelled_at IS NULL
+            RETURNING *
+            "#,
+        )
+        .bind(id)
+        .fetch_optional(self.pool())
+        .await?;
+
+        Ok(window)
+    }
+
+    pub async fn create_buyer_webhook(
+        &self,
+        user_address: &str,
+        url: &str,
+        secret: &str,
+    ) -> Result<BuyerWebhook> {
+        let webhook = sqlx::query_as(
+            r#"
+            INSERT INTO buyer_webhooks (id, user_address, url, secret)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (user_address, url) DO UPDATE
+            SET secret = EXCLUDED.secret
+            RETURNING *
+            "#,
+        )
+        .bind(Uuid::new_v4())
+        .bind(user_address)
+        .bind(url)
+        .bind(secret)
+        .fetch_one(self.pool())
+        .await?;
+
+        Ok(webhook)
+    }
+
+    pub async fn list_buyer_webhooks(&self, user_address: &str) -> Result<Vec<BuyerWebhook>> {
+        let webhooks = sqlx::query_as(
+            "SELECT * FROM buyer_webhooks WHERE user_address = $1 ORDER BY created_at",
+        )
+        .bind(user_address)
+        .fetch_all(self.pool())
+        .await?;
+
+        Ok(webhooks)
+    }
+
+    pub async fn delete_buyer_webhook(&self, user_address: &str, id: Uuid) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM buyer_webhooks WHERE id = $1 AND user_address = $2")
+            .bind(id)
+            .bind(user_address)
+            .execute(self.pool())
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Registers a webhook, or gives an existing one for the same URL a new
+    /// secret. The secret is stored either as is or as `secret_ciphertext`.
+    pub async fn create_provider_webhook(
+        &self,
+        provider_id: &str,
+        url: &str,
+        secret: Option<&str>,
+        secret_ciphertext: Option<&[u8]>,
+    ) -> Result<ProviderWebhook> {
+        let webhook = sqlx::query_as(
+            r#"
+            INSERT INTO provider_webhooks (id, provider_id, url, secret, secret_ciphertext)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (provider_id, url) DO UPDATE
+            SET secret = EXCLUDED.secret, secret_ciphertext = EXCLUDED.secret_ciphertext
+            RETURNING *
+            "#,
+        )
+        .bind(Uuid::new_v4())
+        .bind(provider_id)
+        .bind(url)
+        .bind(secret)
+        .bind(secret_ciphertext)
+        .fetch_one(self.pool())
+        .await?;
+
+        Ok(webhook)
+    }
+
+    pub async fn get_provider_webhook(&self, provider_id: &str, id: Uuid) -> Result<Option<ProviderWebhook>> {
+        let webhook = sqlx::query_as("SELECT * FROM provider_webhooks WHERE id = $1 AND provider_id = $2")
+            .bind(id)
+            .bind(provider_id)
+            .fetch_optional(self.pool())
+            .await?;
+
+        Ok(webhook)
+    }
+
+    /// Webhooks whose secret is still stored unencrypted
+    pub async fn unsealed_provider_webhooks(&self) -> Result<Vec<ProviderWebhook>> {
+        let webhooks = sqlx::query_as("SELECT * FROM provider_webhooks WHERE secret IS NOT NULL")
+            .fetch_all(self.pool())
+            .await?;
+
+        Ok(webhooks)
+    }
+
+    /// Replaces a webhook's plaintext secret with its ciphertext
+    pub async fn seal_provider_webhook_secret(&self, id: Uuid, secret_ciphertext: &[u8]) -> Result<()> {
+        sqlx::query("UPDATE provider_webhooks SET secret = NULL, secret_ciphertext = $2 WHERE id = $1")
+            .bind(id)
+            .bind(secret_ciphertext)
+            .execute(self.pool())
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn list_provider_webhooks(&self, provider_id: &str) -> Result<Vec<ProviderWebhook>> {
+        let webhooks = sqlx::query_as(
+            "SELECT * FROM provider_webhooks WHERE provider_id = $1 ORDER BY created_at",
+        )
+        .bind(provider_id)
+        .fetch_all(self.pool())
+        .await?;
+
+        Ok(webhooks)
+    }
+
+    /// Deletes the webhook along with its delivery history
+    pub async fn delete_provider_webhook(&self, provider_id: &str, id: Uuid) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM provider_webhooks WHERE id = $1 AND provider_id = $2")
+            .bind(id)
+            .bind(provider_id)
+            .execute(self.pool())
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Most recent deliveries to one of the provider's webhooks, optionally
+    /// only those in `status`
+    pub async fn list_webhook_deliveries(
+        &self,
+        provider_id: &str,
+        webhook_id: Uuid,
+        status: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<WebhookDelivery>> {
+        let deliveries = sqlx::query_as(
+            r#"
+            SELECT d.*
+            FROM provider_webhook_deliveries d
+            JOIN provider_webhooks w ON w.id = d.webhook_id
+            WHERE w.id = $1 AND w.provider_id = $2
+              AND ($3::TEXT IS NULL OR d.status = $3)
+            ORDER BY d.created_at DESC
+            LIMIT $4
+            "#,
+        )
+        .bind(webhook_id)
+        .bind(provider_id)
+        .bind(status)
+        .bind(limit)
+        .fetch_all(self.pool())
+        .await?;
+
+        Ok(deliveries)
+    }
+
+    /// Queues `payload` for every webhook of its provider. Returns how many
+    /// deliveries were queued; a notification already queued for a webhook
+    /// is not queued again.
+    pub async fn enqueue_provider_webhooks(
+        &self,
+        conn: &mut PgConnection,
+        payload: &ProviderWebhookPayload,
+    ) -> Result<u64> {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO provider_webhook_deliveries (webhook_id, event, subject_id, payload)
+            SELECT id, $2, $3, $4 FROM provider_webhooks WHERE provider_id = $1
+            ON CONFLICT (webhook_id, event, subject_id) DO NOTHING
+            "#,
+        )
+        .bind(&payload.provider_id)
+        .bind(payload.event.as_str())
+        .bind(&payload.subject_id)
+        .bind(serde_json::to_string(payload)?)
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Entitlements that expired within the last `lookback_secs` and have
+    /// not been announced yet to at least one webhook of their provider.
+    /// Only expiries after a webhook was registered are announced to it.
+    pub async fn expired_entitlements_to_announce(
+        &self,
+        lookback_secs: u64,
+    ) -> Result<Vec<Entitlement>> {
+        let entitlements = sqlx::query_as(
+            r#"
+            SELECT e.*
+            FROM entitlements e
+            WHERE e.expires_at <= NOW()
+              AND e.expires_at > NOW() - make_interval(secs => $1)
+              AND EXISTS (
+                  SELECT 1 FROM provider_webhooks w
+                  WHERE w.provider_id = e.provider_id
+                    AND w.created_at < e.expires_at
+                    AND NOT EXISTS (
+                        SELECT 1 FROM provider_webhook_deliveries d
+                        WHERE d.webhook_id = w.id
+                          AND d.event = 'entitlement_expired'
+                          AND d.subject_id = e.entitlement_id
+                    )
+              )
+            "#,
+        )
+        .bind(lookback_secs as f64)
+        .fetch_all(self.pool())
+        .await?;
+
+        Ok(entitlements)
+    }
+
+    /// Claims up to `limit` due deliveries for the rest of `conn`'s
+    /// transaction. Rows another server has claimed are skipped.
+    pub async fn claim_due_deliveries(
+        &self,
+        conn: &mut PgConnection,
+        limit: i64,
+    ) -> Result<Vec<PendingDelivery>> {
+        let deliveries = sqlx::query_as(
+            r#"
+            SELECT d.id, w.url, w.secret, w.secret_ciphertext, d.event, d.payload, d.attempts
+            FROM provider_webhook_deliveries d
+            JOIN provider_webhooks w ON w.id = d.webhook_id
+            WHERE d.status = 'pending' AND d.next_attempt_at <= NOW()
+            ORDER BY d.next_attempt_at
+            LIMIT $1
+            FOR UPDATE OF d SKIP LOCKED
+            "#,
+        )
+        .bind(limit)
+        .fetch_all(&mut *conn)
+        .await?;
+
+        Ok(deliveries)
+    }
+
+    pub async fn mark_delivery_delivered(
+        &self,
+        conn: &mut PgConnection,
+        id: i64,
+        status_code: u16,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            UPDATE provider_webhook_deliveries
+            SET status = 'delivered', attempts = attempts + 1, last_status_code = $2,
+                last_error = NULL, delivered_at = NOW()
+            WHERE id = $1
+            "#,
+        )
+        .bind(id)
+        .bind(status_code as i32)
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Records a failed attempt. The delivery is retried in `retry_in_secs`,
+    /// or given up on as `failed` when that is `None`.
+    pub async fn record_delivery_failure(
+        &self,
+        conn: &mut PgConnection,
+        id: i64,
+        status_code: Option<u16>,
+        error: &str,
+        retry_in_secs: Option<i64>,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            UPDATE provider_webhook_deliveries
+            SET attempts = attempts + 1, last_status_code = $2, last_error = $3,
+                status = CASE WHEN $4::FLOAT8 IS NULL THEN 'failed' ELSE 'pending' END,
+                next_attempt_at = NOW() + make_interval(secs => COALESCE($4, 0))
+            WHERE id = $1
+            "#,
+        )
+        .bind(id)
+        .bind(status_code.map(|c| c as i32))
+        .bind(error)
+        .bind(retry_in_secs.map(|s| s as f64))
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Entitlement with its provider, regardless of expiry
+    pub async fn get_entitlement(&self, entitlement_id: &str) -> Result<Option<Entitlement>> {
+        let entitlement = sqlx::query_as(
+            r#"
+            SELECT
+                e.entitlement_id, e.buyer, s.provider_id, e.service_id, e.tier_id,
+                e.price_paid, e.expires_at, e.quota, COALESCE(e.units, 0) AS units, e.created_at
+            FROM entitlements e
+            JOIN services s ON s.service_id = e.service_id
+            WHERE e.entitlement_id = $1
+            "#,
+        )
+        .bind(entitlement_id)
+        .fetch_optional(self.pool())
+        .await?;
+
+        Ok(entitlement)
+    }
+
+    /// A buyer's entitlements across services, a page at a time
+    pub async fn list_entitlements_by_buyer(
+        &self,
+        buyer: &str,
+        filter: &EntitlementFilter,
+        page: &PageRequest,
+    ) -> Result<Page<Entitlement>> {
+        self.list_entitlements("e.buyer", buyer, filter, page).await
+    }
+
+    /// Every buyer's entitlements to a service, a page at a time
+    pub async fn list_entitlements_by_service(
+        &self,
+        service_id: &str,
+        filter: &EntitlementFilter,
+        page: &PageRequest,
+    ) -> Result<Page<Entitlement>> {
+        self.list_entitlements("e.service_id", service_id, filter, page).await
+    }
+
+    /// Entitlements whose `column` is `value`. `column` is spliced into the
+    /// query, so only pass fixed names.
+    async fn list_entitlements(
+        &self,
+        column: &str,
+        value: &str,
+        filter: &EntitlementFilter,
+        page: &PageRequest,
+    ) -> Result<Page<Entitlement>> {
+        let query = format!(
+            r#"
+            SELECT
+                e.entitlement_id, e.buyer, s.provider_id, e.service_id, e.tier_id,
+                e.price_paid, e.expires_at, e.quota, COALESCE(e.units, 0) AS units, e.created_at
+            FROM entitlements e
+            JOIN services s ON s.service_id = e.service_id
+            WHERE {column} = $1
+              AND ($2::BOOLEAN IS NULL OR COALESCE(
+                    (e.expires_at IS NOT NULL AND e.expires_at > NOW() - make_interval(secs => $3))
+                    OR
+                    (e.expires_at IS NULL AND e.units > 0),
+                    false) = $2)
+              AND ($4::TIMESTAMPTZ IS NULL OR (e.created_at, e.entitlement_id) {after} ($4, $5))
+            ORDER BY e.created_at {dir}, e.entitlement_id {dir}
+            LIMIT $6
+            "#,
+            column = column,
+            after = page.sort.after(),
+            dir = page.sort.direction(),
+        );
+        let entitlements = sqlx::query_as::<_, Entitlement>(&query)
+            .bind(value)
+            .bind(filter.active)
+            .bind(self.expiry_grace_secs as f64)
+            .bind(page.cursor_time())
+            .bind(page.cursor_id())
+            .bind(page.fetch_limit())
+            .fetch_all(self.read_pool())
+            .await?;
+
+        Ok(Page::from_rows(entitlements, page, |e| {
+            Cursor::new(e.created_at, &e.entitlement_id)
+        }))
+    }
+
+    /// Up to `limit` entitlements picked at random, expired ones included
+    pub async fn sample_entitlements(&self, limit: i64) -> Result<Vec<Entitlement>> {
+        let entitlements = sqlx::query_as(
+            r#"
+            SELECT
+                e.entitlement_id, e.buyer, s.provider_id, e.service_id, e.tier_id,
+                e.price_paid, e.expires_at, e.quota, COALESCE(e.units, 0) AS units, e.created_at
+            FROM entitlements e
+            JOIN services s ON s.service_id = e.service_id
+            ORDER BY random()
+            LIMIT $1
+            "#,
+        )
+        .bind(limit)
+        .fetch_all(self.pool())
+        .await?;
+
+        Ok(entitlements)
+    }
+
+    pub async fn set_entitlement_expiry(
+        &self,
+        conn: &mut PgConnection,
+        entitlement_id: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<bool> {
+        let result = sqlx::query("UPDATE entitlements SET expires_at = $2 WHERE entitlement_id = $1")
+            .bind(entitlement_id)
+            .bind(expires_at)
+            .execute(&mut *conn)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn upsert_buyer_contact(
+        &self,
+        entitlement: &Entitlement,
+        contact_ciphertext: &[u8],
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO buyer_contacts (entitlement_id, buyer, provider_id, contact_ciphertext)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (entitlement_id) DO UPDATE
+            SET contact_ciphertext = EXCLUDED.contact_ciphertext, updated_at = NOW()
+            "#,
+        )
+        .bind(&entitlement.entitlement_id)
+        .bind(&entitlement.buyer)
+        .bind(&entitlement.provider_id)
+        .bind(contact_ciphertext)
+        .execute(self.pool())
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn delete_buyer_contact(&self, entitlement_id: &str) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM buyer_contacts WHERE entitlement_id = $1")
+            .bind(entitlement_id)
+            .execute(self.pool())
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn list_buyer_contacts(&self, provider_id: &str) -> Result<Vec<BuyerContact>> {
+        let contacts = sqlx::query_as(
+            "SELECT * FROM buyer_contacts WHERE provider_id = $1 ORDER BY created_at",
+        )
+        .bind(provider_id)
+        .fetch_all(self.pool())
+        .await?;
+
+        Ok(contacts)
+    }
+
+    /// A buyer's purchases since `since`, per service and coin, grouped into
+    /// `bucket` periods (`day`, `week` or `month`)
+    pub async fn buyer_spend(
+        &self,
+        buyer: &str,
+        since: DateTime<Utc>,
+        bucket: &str,
+    ) -> Result<Vec<SpendRow>> {
+        let rows = sqlx::query_as(
+            r#"
+            SELECT date_trunc($3, e.created_at) AS period, e.service_id, t.coin_type,
+                   COUNT(*) AS purchases, SUM(e.price_paid) AS total
+            FROM entitlements e
+            JOIN pricing_tiers t ON t.tier_id = e.tier_id
+            WHERE e.buyer = $1 AND e.created_at >= $2
+            GROUP BY 1, 2, 3
+            ORDER BY 1, 2, 3
+            "#,
+        )
+        .bind(buyer)
+        .bind(since)
+        .bind(bucket)
+        .fetch_all(self.read_pool())
+        .await?;
+
+        Ok(rows)
+    }
+
+    /// What a provider's services sold since `since`, per `bucket` (a
+    /// `date_trunc` field), service and coin
+    pub async fn provider_revenue(
+        &self,
+        provider_id: &str,
+        since: DateTime<Utc>,
+        bucket: &str,
+    ) -> Result<Vec<RevenueRow>> {
+        let rows = sqlx::query_as(
+            r#"
+            SELECT date_trunc($3, e.created_at) AS period, e.service_id, t.coin_type,
+                   COUNT(*) AS purchases, SUM(e.price_paid) AS total
+            FROM entitlements e
+            JOIN services s ON s.service_id = e.service_id
+            JOIN pricing_tiers t ON t.tier_id = e.tier_id
+            WHERE s.provider_id = $1 AND e.created_at >= $2
+            GROUP BY 1, 2, 3
+            ORDER BY 1, 2, 3
+            "#,
+        )
+        .bind(provider_id)
+        .bind(since)
+        .bind(bucket)
+        .fetch_all(self.read_pool())
+        .await?;
+
+        Ok(rows)
+    }
+
+    /// What a buyer paid in `coin_type` since `since`
+    pub async fn buyer_spend_in_coin(
+        &self,
+        buyer: &str,
+        coin_type: &str,
+        since: DateTime<Utc>,
+    ) -> Result<MistAmount> {
+        let (total,): (MistAmount,) = sqlx::query_as(
+            r#"
+            SELECT COALESCE(SUM(e.price_paid), 0)
+            FROM entitlements e
+            JOIN pricing_tiers t ON t.tier_id = e.tier_id
+            WHERE e.buyer = $1 AND t.coin_type = $2 AND e.created_at >= $3
+            "#,
+        )
+        .bind(buyer)
+        .bind(coin_type)
+        .bind(since)
+        .fetch_one(self.pool())
+        .await?;
+
+        Ok(total)
+    }
+
+    pub async fn upsert_buyer_budget(
+        &self,
+        user_address: &str,
+        coin_type: &str,
+        amount: MistAmount,
+        period_days: i32,
+    ) -> Result<BuyerBudget> {
+        let budget = sqlx::query_as(
+            r#"
+            INSERT INTO buyer_budgets (user_address, coin_type, amount, period_days)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (user_address, coin_type) DO UPDATE
+            SET amount = EXCLUDED.amount, period_days = EXCLUDED.period_days, updated_at = NOW()
+            RETURNING *
+            "#,
+        )
+        .bind(user_address)
+        .bind(coin_type)
+        .bind(amount)
+        .bind(period_days)
+        .fetch_one(self.pool())
+        .await?;
+
+        Ok(budget)
+    }
+
+    pub async fn list_buyer_budgets(&self, user_address: &str) -> Result<Vec<BuyerBudget>> {
+        let budgets = sqlx::query_as(
+            "SELECT * FROM buyer_budgets WHERE user_address = $1 ORDER BY coin_type",
+        )
+        .bind(user_address)
+        .fetch_all(self.pool())
+        .await?;
+
+        Ok(budgets)
+    }
+
+    pub async fn delete_buyer_budget(&self, user_address: &str, coin_type: &str) -> Result<bool> {
+        let result =
+            sqlx::query("DELETE FROM buyer_budgets WHERE user_address = $1 AND coin_type = $2")
+                .bind(user_address)
+                .bind(coin_type)
+                .execute(self.pool())
+                .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn record_sidecar_heartbeat(&self, heartbeat: &SidecarHeartbeat) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO sidecar_instances (provider_id, instance_id, version, git_hash, config_hash)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (provider_id, instance_id) DO UPDATE
+            SET version = EXCLUDED.version, git_hash = EXCLUDED.git_hash,
+                config_hash = EXCLUDED.config_hash, last_seen_at = NOW()
+            "#,
+        )
+        .bind(&heartbeat.provider_id)
+        .bind(&heartbeat.instance_id)
+        .bind(&heartbeat.version)
+        .bind(&heartbeat.git_hash)
+        .bind(&heartbeat.config_hash)
+        .execute(self.pool())
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn list_sidecars(&self, provider_id: &str) -> Result<Vec<SidecarInstance>> {
+        let sidecars = sqlx::query_as(
+            "SELECT * FROM sidecar_instances WHERE provider_id = $1 ORDER BY last_seen_at DESC",
+        )
+        .bind(provider_id)
+        .fetch_all(self.pool())
+        .await?;
+
+        Ok(sidecars)
+    }
+
+    /// Records that `event` was sent for an entitlement. Returns false when it
+    /// was already sent, so each notification goes out at most once.
+    pub async fn claim_buyer_notification(&self, entitlement_id: &str, event: &str) -> Result<bool> {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO buyer_notifications (entitlement_id, event)
+            VALUES ($1, $2)
+            ON CONFLICT DO NOTHING
+            "#,
+        )
+        .bind(entitlement_id)
+        .bind(event)
+        .execute(self.pool())
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Entitlements of buyers with a webhook that expire within `within_secs`
+    pub async fn entitlements_expiring_soon(&self, within_secs: u64) -> Result<Vec<Entitlement>> {
+        let entitlements = sqlx::query_as(
+            r#"
+            SELECT
+                e.entitlement_id, e.buyer, s.provider_id, e.service_id, e.tier_id,
+                e.price_paid, e.expires_at, e.quota, COALESCE(e.units, 0) AS units, e.created_at
+            FROM entitlements e
+            JOIN services s ON s.service_id = e.service_id
+            WHERE e.expires_at > NOW()
+              AND e.expires_at <= NOW() + make_interval(secs => $1)
+              AND EXISTS (SELECT 1 FROM buyer_webhooks w WHERE w.user_address = e.buyer)
+            "#,
+        )
+        .bind(within_secs as f64)
+        .fetch_all(self.pool())
+        .await?;
+
+        Ok(entitlements)
+    }
+
+    /// Live quota entitlements of buyers with a webhook that have used at
+    /// least `percent` of their tier's quota
+    pub async fn entitlements_quota_consumed(&self, percent: u8) -> Result<Vec<Entitlement>> {
+        let entitlements = sqlx::query_as(
+            r#"
+            SELECT
+                e.entitlement_id, e.buyer, s.provider_id, e.service_id, e.tier_id,
+                e.price_paid, e.expires_at, e.quota, COALESCE(e.units, 0) AS units, e.created_at
+            FROM entitlements e
+            JOIN services s ON s.service_id = e.service_id
+            JOIN pricing_tiers t ON t.tier_id = e.tier_id
+            WHERE t.quota_limit > 0
+              AND e.quota IS NOT NULL
+              AND (t.quota_limit - e.quota) * 100 >= t.quota_limit * $1
+              AND (e.expires_at IS NULL OR e.expires_at > NOW())
+              AND EXISTS (SELECT 1 FROM buyer_webhooks w WHERE w.user_address = e.buyer)
+            "#,
+        )
+        .bind(percent as i32)
+        .fetch_all(self.pool())
+        .await?;
+
+        Ok(entitlements)
+    }
+
+    pub async fn count_active_entitlements_for_tier(&self, conn: &mut PgConnection, tier_id: &str) -> Result<i64> {
+        let row: (i64,) = sqlx::query_as(
+            r#"
+            SELECT COUNT(*) FROM entitlements
+            WHERE tier_id = $1
+              AND (
+                    (expires_at IS NOT NULL AND expires_at > NOW() - make_interval(secs => $2))
+                    OR
+                    (expires_at IS NULL AND units > 0)
+                  )
+            "#,
+        )
+        .bind(tier_id)
+        .bind(self.expiry_grace_secs as f64)
+        .fetch_one(&mut *conn)
+        .await?;
+
+        Ok(row.0)
+    }
+
+    /// Unexpired entitlements to a service, by the same rule as
+    /// `count_active_entitlements_for_tier`, ordered by holder
+    pub async fn list_service_access(&self, service_id: &str) -> Result<Vec<AccessRow>> {
+        let rows = sqlx::query_as::<_, AccessRow>(
+            r#"
+            SELECT e.buyer AS user_address, e.entitlement_id, e.tier_id, t.tier_name,
+                   t.tier_type::TEXT AS tier_type, e.expires_at,
+                   CASE WHEN e.expires_at IS NULL THEN e.units ELSE e.quota END AS remaining,
+                   e.created_at AS purchased_at
+            FROM entitlements e
+            JOIN pricing_tiers t ON t.tier_id = e.tier_id
+            WHERE e.service_id = $1
+              AND (
+                    (e.expires_at IS NOT NULL AND e.expires_at > NOW() - make_interval(secs => $2))
+                    OR
+                    (e.expires_at IS NULL AND e.units > 0)
+                  )
+            ORDER BY e.buyer, e.created_at
+            "#,
+        )
+        .bind(service_id)
+        .bind(self.expiry_grace_secs as f64)
+        .fetch_all(self.read_pool())
+        .await?;
+
+        Ok(rows)
+    }
+
+    pub async fn create_entitlement(
+        &self,
+        conn: &mut PgConnection,
+        event: &EntitlementPurchased,
+    ) -> Result<Entitlement> {
+        let entitlement_id = event.entitlement_id.bytes.to_string();
+        let service_id = event.service_id.bytes.to_string();
+        let tier_id = event.tier_id.bytes.to_string();
+    
+        let created_at = chrono::DateTime::<chrono::Utc>::from_timestamp_millis(
+            event.timestamp as i64
+        )
+        .ok_or_else(|| anyhow::anyhow!("Invalid timestamp"))?;
+    
+        let (expires_at, quota, units) = match &event.inner {
+            &EntitlementConfig::Subscription { expires_at } => {
+                (
+                    Some(
+                        chrono::DateTime::<chrono::Utc>::from_timestamp_millis(
+                            expires_at as i64
+                        )
+                        .ok_or_else(|| anyhow::anyhow!("Invalid expires_at"))?
+                    ),
+                    None,
+                    Units::ZERO,
+                )
+            }
+    
+            EntitlementConfig::Quota { expires_at, quota } => {
+                (
+                    Some(
+                        chrono::DateTime::<chrono::Utc>::from_timestamp_millis(
+                            *expires_at as i64
+                        )
+                        .ok_or_else(|| anyhow::anyhow!("Invalid expires_at"))?
+                    ),
+                    Some(Units::new(*quota)),
+                    Units::ZERO,
+                )
+            }
+    
+            EntitlementConfig::UsageBased { units } => {
+                (None, None, Units::new(*units))
+            }
+        };
+    
+        let entitlement = sqlx::query_as::<_, Entitlement>(
+            r#"
+            WITH inserted AS (
+            INSERT INTO entitlements
+            (entitlement_id, buyer, service_id, tier_id, price_paid, expires_at, quota, units, created_at)
+            VALUES ($1,$2,$3,$4,$5,$6,$7,$8,$9)
+            -- No-op update so a replayed purchase returns the existing row
+            ON CONFLICT (entitlement_id) DO UPDATE SET entitlement_id = EXCLUDED.entitlement_id
+            RETURNING *
+            )
+            SELECT 
+            inserted.*,
+            s.provider_id
+            FROM inserted
+            JOIN services s ON s.service_id = inserted.service_id
+                "#,
+        )
+        .bind(&entitlement_id)
+        .bind(&event.buyer.to_string())
+        .bind(&service_id)
+        .bind(&tier_id)
+        .bind(MistAmount::new(event.price_paid))
+        .bind(expires_at)
+        .bind(quota)
+        .bind(units)
+        .bind(created_at)
+        .fetch_one(&mut *conn)
+        .await?;
+    
+        Ok(entitlement)
+    }
+
+    /// Appends the event to `blockchain_events` and applies its projection.
+    /// Both are safe to repeat: the row is skipped if one already exists for
+    /// the same transaction digest and event index, and projections upsert.
+    /// Inside `batch::scope` the row waits for `flush_events` instead.
+    pub async fn store_event(
+        &self,
+        conn: &mut PgConnection,
+        event: &ProtocolEvent,
+        checkpoint: u64,
+        tx_digest: Option<String>,
+        event_index: u64,
+        package_id: Option<&str>,
+    ) -> Result<()> {
+        let tx_digest = tx_digest.as_deref();
+        let package_id = package_id.unwrap_or(crate::utils::constants::PACKAGE_ID);
+
+        match event {
+            ProtocolEvent::ProviderRegistered(e) => {
+                let prof_id = e.profile_id.bytes.to_string();
+                self.insert_blockchain_event(
+                    conn,
+                    checkpoint,
+                    tx_digest,
+                    event_index,
+                    package_id,
+                    "ProviderRegistered",
+                    "registry",
+                    serde_json::to_value(e)?,
+                    Some(&prof_id),
+                    None,
+                    None,
+                    None,
+                )
+                .await?;
+
+                self.create_provider(
+                    conn,
+                    &prof_id,
+                    e.provider_address.to_string(),
+                    &e.metadata,
+                )
+                .await?;
+            }
+
+            ProtocolEvent::ServiceCreated(e) => {
+                let service_type = String::from_utf8_lossy(&e.service_type).to_string();
+                let metadata_uri = String::from_utf8_lossy(&e.metadata_uri).to_string();
+                let prof_id = e.provider.bytes.to_string();
+                let serv = e.service_id.bytes.to_string();
+
+                self.insert_blockchain_event(
+                    conn,
+                    checkpoint,
+                    tx_digest,
+                    event_index,
+                    package_id,
+                    "ServiceCreated",
+                    "registry",
+                    serde_json::to_value(e)?,
+                    Some(&prof_id),
+                    Some(&serv),
+                    None,
+                    None,
+                )
+                .await?;
+
+                self.create_service(
+                    conn,
+                    &serv,
+                    &prof_id,
+                    &service_type,
+                    Some(metadata_uri),
+                )
+                .await?;
+            }
+
+            ProtocolEvent::TierAddedToService(e) => {
+                let serv = e.service_id.bytes.to_string();
+                let tier_id = e.tier_id.bytes.to_string();
+
+                self.insert_blockchain_event(
+                    conn,
+                    checkpoint,
+                    tx_digest,
+                    event_index,
+                    package_id,
+                    "TierAddedToService",
+                    "registry",
+                    serde_json::to_value(e)?,
+                    None,
+                    Some(&serv),
+                    Some(&tier_id),
+                    None,
+                )
+                .await?;
+            }
+
+            ProtocolEvent::TierRemovedFromService(e) => {
+                let serv = e.service_id.bytes.to_string();
+                let tier_id = e.tier_id.bytes.to_string();
+
+                self.insert_blockchain_event(
+                    conn,
+                    checkpoint,
+                    tx_digest,
+                    event_index,
+                    package_id,
+                    "TierRemovedFromService",
+                    "registry",
+                    serde_json::to_value(e)?,
+                    None,
+                    Some(&serv),
+                    Some(&tier_id),
+                    None,
+                )
+                .await?;
+            }
+
+            ProtocolEvent::TierCreated(e) => {
+                let tier_name = String::from_utf8_lossy(&e.tier_name).to_string();
+                let tier_id = e.tier_id.bytes.to_string();
+                let serv = e.service_id.bytes.to_string();
+                let coin_type = &e.coin_type;
+
+                self.insert_blockchain_event(
+                    conn,
+                    checkpoint,
+                    tx_digest,
+                    event_index,
+                    package_id,
+                    "TierCreated",
+                    "pricing",
+                    serde_json::to_value(e)?,
+                    None,
+                    Some(&serv),
+                    Some(&tier_id),
+                    None,
+                )
+                .await?;
+
+                self.create_tier(
+                    conn,
+                    &tier_id,
+                    &serv,
+                    &tier_name,
+                    MistAmount::new(e.price),
+                    coin_type,
+                    e.inner.as_tier_type(),
+                    e.inner.duration().map(|d| d as i64),
+                    e.inner.quota().map(Units::new),
+                )
+                .await?;
+            }
+
+            ProtocolEvent::TierPriceUpdated(e) => {
+                let tier_id = e.tier_id.bytes.to_string();
+
+                // service_id is looked up from the tier
+                self.insert_blockchain_event(
+                    conn,
+                    checkpoint,
+                    tx_digest,
+                    event_index,
+                    package_id,
+                    "TierPriceUpdated",
+                    "pricing",
+                    serde_json::to_value(e)?,
+                    None,
+                    None,
+                    Some(&tier_id),
+                    None,
+                )
+                .await?;
+            }
+
+            ProtocolEvent::EntitlementToppedUp(e) => {
+                let service_id = e.service_id.bytes.to_string();
+                let tier_id = e.tier_id.bytes.to_string();
+                let ent_id = e.entitlement_id.bytes.to_string();
+
+                self.insert_blockchain_event(
+                    conn,
+                    checkpoint,
+                    tx_digest,
+                    event_index,
+                    package_id,
+                    "EntitlementToppedUp",
+                    "payments",
+                    serde_json::to_value(e)?,
+                    None,
+                    Some(&service_id),
+                    Some(&tier_id),
+                    Some(&ent_id),
+                )
+                .await?;
+            }
+
+            ProtocolEvent::QuotaConsumed(e) => {
+                let ent_id = e.entitlement_id.bytes.to_string();
+
+                // service_id and tier_id are looked up from the entitlement
+                self.insert_blockchain_event(
+                    conn,
+                    checkpoint,
+                    tx_digest,
+                    event_index,
+                    package_id,
+                    "QuotaConsumed",
+                    "payments",
+                    serde_json::to_value(e)?,
+                    None,
+                    None,
+                    None,
+                    Some(&ent_id),
+                )
+                .await?;
+            }
+
+            _ => {
+                self.insert_blockchain_event(
+                    conn,
+                    checkpoint,
+                    tx_digest,
+                    event_index,
+                    package_id,
+                    &format!("{:?}", event),
+                    "unknown",
+                    serde_json::to_value(event)?,
+                    None,
+                    None,
+                    None,
+                    None,
+                )
+                .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Inserts into `blockchain_events` unless the event is already there.
+    /// A missing `service_id` or `tier_id` is filled in from `tier_id` or
+    /// `entitlement_id`.
+    async fn insert_blockchain_event(
+        &self,
+        conn: &mut PgConnection,
+        checkpoint: u64,
+        tx_digest: Option<&str>,
+        event_index: u64,
+        package_id: &str,
+        event_type: &str,
+        module: &str,
+        event_data: serde_json::Value,
+        provider_id: Option<&str>,
+        service_id: Option<&str>,
+        tier_id: Option<&str>,
+        entitlement_id: Option<&str>,
+    ) -> Result<()> {
+        if batch::is_active() {
+            batch::push(BufferedEvent {
+                checkpoint: checkpoint as i64,
+                tx_digest: tx_digest.map(str::to_string),
+                event_index: event_index as i32,
+                package_id: package_id.to_string(),
+                event_type: event_type.to_string(),
+                module: module.to_string(),
+                event_data,
+                provider_id: provider_id.map(str::to_string),
+                service_id: service_id.map(str::to_string),
+                tier_id: tier_id.map(str::to_string),
+                entitlement_id: entitlement_id.map(str::to_string),
+            });
+            return Ok(());
+        }
+
+        sqlx::query(
+            r#"
+            INSERT INTO blockchain_events
+            (checkpoint_number, transaction_digest, event_index, event_type, package_id, module, event_data, provider_id, service_id, tier_id, entitlement_id)
+            SELECT $1, $2, $3, $4, $5, $6, $7, $8,
+                COALESCE(
+                    $9,
+                    (SELECT service_id FROM pricing_tiers WHERE tier_id = $10),
+                    (SELECT service_id FROM entitlements WHERE entitlement_id = $11)
+                ),
+                COALESCE($10, (SELECT tier_id FROM entitlements WHERE entitlement_id = $11)),
+                $11
+            WHERE $2::TEXT IS NULL OR NOT EXISTS (
+                SELECT 1 FROM blockchain_events
+                WHERE transaction_digest = $2 AND event_index = $3
+            )
+            "#,
+        )
+        .bind(checkpoint as i64)
+        .bind(tx_digest)
+        .bind(event_index as i32)
+        .bind(event_type)
+        .bind(package_id)
+        .bind(module)
+        .bind(event_data)
+        .bind(provider_id)
+        .bind(service_id)
+        .bind(tier_id)
+        .bind(entitlement_id)
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Writes the `blockchain_events` rows buffered by `batch::scope` in one
+    /// multi-row insert, with the same service and tier lookups and dedup as
+    /// a single insert. Rows are looked up after the whole batch is applied,
+    /// which only matters for an event whose tier or entitlement is created
+    /// later in the same batch. Returns the rows written.
+    pub async fn flush_events(&self, conn: &mut PgConnection) -> Result<u64> {
+        let rows = batch::take();
+        if rows.is_empty() {
+            return Ok(0);
+        }
+
+        let mut checkpoints = Vec::with_capacity(rows.len());
+        let mut tx_digests = Vec::with_capacity(rows.len());
+        let mut event_indexes = Vec::with_capacity(rows.len());
+        let mut event_types = Vec::with_capacity(rows.len());
+        let mut package_ids = Vec::with_capacity(rows.len());
+        let mut modules = Vec::with_capacity(rows.len());
+        let mut event_data = Vec::with_capacity(rows.len());
+        let mut provider_ids = Vec::with_capacity(rows.len());
+        let mut service_ids = Vec::with_capacity(rows.len());
+        let mut tier_ids = Vec::with_capacity(rows.len());
+        let mut entitlement_ids = Vec::with_capacity(rows.len());
+        for row in rows {
+            checkpoints.push(row.checkpoint);
+            tx_digests.push(row.tx_digest);
+            event_indexes.push(row.event_index);
+            event_types.push(row.event_type);
+            package_ids.push(row.package_id);
+            modules.push(row.module);
+            event_data.push(row.event_data);
+            provider_ids.push(row.provider_id);
+            service_ids.push(row.service_id);
+            tier_ids.push(row.tier_id);
+            entitlement_ids.push(row.entitlement_id);
+        }
+
+        let result = sqlx::query(
+            r#"
+            INSERT INTO blockchain_events
+            (checkpoint_number, transaction_digest, event_index, event_type, package_id, module, event_data, provider_id, service_id, tier_id, entitlement_id)
+            SELECT b.checkpoint_number, b.transaction_digest, b.event_index, b.event_type, b.package_id, b.module, b.event_data, b.provider_id,
+                COALESCE(
+                    b.service_id,
+                    (SELECT service_id FROM pricing_tiers WHERE tier_id = b.tier_id),
+                    (SELECT service_id FROM entitlements WHERE entitlement_id = b.entitlement_id)
+                ),
+                COALESCE(b.tier_id, (SELECT tier_id FROM entitlements WHERE entitlement_id = b.entitlement_id)),
+                b.entitlement_id
+            FROM UNNEST($1::BIGINT[], $2::TEXT[], $3::INT[], $4::TEXT[], $5::TEXT[], $6::TEXT[], $7::JSONB[], $8::TEXT[], $9::TEXT[], $10::TEXT[], $11::TEXT[])
+                AS b(checkpoint_number, transaction_digest, event_index, event_type, package_id, module, event_data, provider_id, service_id, tier_id, entitlement_id)
+            WHERE b.transaction_digest IS NULL OR NOT EXISTS (
+                SELECT 1 FROM blockchain_events
+                WHERE transaction_digest = b.transaction_digest AND event_index = b.event_index
+            )
+            "#,
+        )
+        .bind(checkpoints)
+        .bind(tx_digests)
+        .bind(event_indexes)
+        .bind(event_types)
+        .bind(package_ids)
+        .bind(modules)
+        .bind(event_data)
+        .bind(provider_ids)
+        .bind(service_ids)
+        .bind(tier_ids)
+        .bind(entitlement_ids)
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Whether the worker already handled this event
+    pub async fn is_event_ingested(&self, tx_digest: &str, event_index: u64) -> Result<bool> {
+        let row: Option<(i32,)> = sqlx::query_as(
+            "SELECT event_index FROM ingested_events WHERE transaction_digest = $1 AND event_index = $2",
+        )
+        .bind(tx_digest)
+        .bind(event_index as i32)
+        .fetch_optional(self.pool())
+        .await?;
+
+        Ok(row.is_some())
+    }
+
+    pub async fn mark_event_ingested(
+        &self,
+        conn: &mut PgConnection,
+        tx_digest: &str,
+        event_index: u64,
+        checkpoint: u64,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO ingested_events (transaction_digest, event_index, checkpoint_number)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (transaction_digest, event_index) DO NOTHING
+            "#,
+        )
+        .bind(tx_digest)
+        .bind(event_index as i32)
+        .bind(checkpoint as i64)
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Empties every table the event worker writes or reads back, for running
+    /// event fixtures against a scratch database. Never point this at a
+    /// database that matters.
+    pub async fn reset_indexed_state(&self) -> Result<()> {
+        sqlx::query(
+            r#"
+            TRUNCATE providers, services, pricing_tiers, service_tiers, entitlements,
+                tier_replacements, blockchain_events, ingested_events, failed_events,
+                pubsub_outbox, provider_webhook_deliveries, usage_events, usage_records,
+                tier_history
+            CASCADE
+            "#,
+        )
+        .execute(self.pool())
+        .await?;
+
+        Ok(())
+    }
+
+    /// Every row of `table` as JSON, ordered by `order_by`. Both are spliced
+    /// into the query, so only pass fixed names.
+    pub async fn table_rows(&self, table: &str, order_by: &str) -> Result<Vec<serde_json::Value>> {
+        let rows = sqlx::query_scalar::<_, serde_json::Value>(&format!(
+            "SELECT to_jsonb(t) FROM {} t ORDER BY {}",
+            table, order_by
+        ))
+        .fetch_all(self.pool())
+        .await?;
+
+        Ok(rows)
+    }
+
+    /// Forgets the events of `from..=to` so a backfill of the range handles
+    /// them again. Rows the handlers derived from them are upserted on replay.
+    pub async fn reset_indexed_range(&self, from: u64, to: u64) -> Result<u64> {
+        let mut tx = self.pool().begin().await?;
+
+        let deleted = sqlx::query(
+            "DELETE FROM ingested_events WHERE checkpoint_number BETWEEN $1 AND $2",
+        )
+        .bind(from as i64)
+        .bind(to as i64)
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query("DELETE FROM blockchain_events WHERE checkpoint_number BETWEEN $1 AND $2")
+            .bind(from as i64)
+            .bind(to as i64)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        Ok(deleted.rows_affected())
+    }
+
+    /// Deletes history older than `cutoff` that nothing reads back: the API
+    /// request log, settled usage, usage request IDs and replayed dead
+    /// letters. The event log is dropped by partition instead, see
+    /// `drop_event_partitions`. Returns the rows deleted per table.
+    pub async fn prune_before(&self, cutoff: DateTime<Utc>) -> Result<Vec<(&'static str, u64)>> {
+        let statements = [
+            ("api_requests", "DELETE FROM api_requests WHERE request_time < $1"),
+            ("usage_events", "DELETE FROM usage_events WHERE settled_at < $1"),
+            ("usage_records", "DELETE FROM usage_records WHERE recorded_at < $1"),
+            ("failed_events", "DELETE FROM failed_events WHERE replayed_at < $1"),
+            ("sidecar_instances", "DELETE FROM sidecar_instances WHERE last_seen_at < $1"),
+            ("pubsub_outbox", "DELETE FROM pubsub_outbox WHERE published_at < $1"),
+            ("provider_webhook_deliveries", "DELETE FROM provider_webhook_deliveries WHERE status <> 'pending' AND created_at < $1"),
+        ];
+
+        let mut pruned = vec![];
+        for (table, statement) in statements {
+            let result = sqlx::query(statement)
+                .bind(cutoff)
+                .execute(self.pool())
+                .await?;
+            pruned.push((table, result.rows_affected()));
+        }
+
+        Ok(pruned)
+    }
+
+    /// Chunks of `blockchain_events`, oldest first
+    pub async fn event_partitions(&self) -> Result<Vec<EventPartition>> {
+        let partitions = sqlx::query_as(
+            r#"
+            SELECT chunk_name::TEXT AS chunk_name, range_start, range_end
+            FROM timescaledb_information.chunks
+            WHERE hypertable_name = 'blockchain_events'
+            ORDER BY range_start
+            "#,
+        )
+        .fetch_all(self.pool())
+        .await?;
+
+        Ok(partitions)
+    }
+
+    /// Drops the chunks of `blockchain_events` that end before `cutoff` and
+    /// returns their names. Chunks holding any newer event are kept whole.
+    pub async fn drop_event_partitions(&self, cutoff: DateTime<Utc>) -> Result<Vec<String>> {
+        let dropped = sqlx::query_scalar(
+            "SELECT drop_chunks('blockchain_events', older_than => $1)::TEXT",
+        )
+        .bind(cutoff)
+        .fetch_all(self.pool())
+        .await?;
+
+        Ok(dropped)
+    }
+
+    pub async fn get_recent_events(&self, limit: i64) -> Result<Vec<BlockchainEvent>> {
+        let events = sqlx::query_as::<_, BlockchainEvent>(
+            r#"SELECT * FROM blockchain_events ORDER BY event_time DESC LIMIT $1"#,
+        )
+        .bind(limit)
+        .fetch_all(self.pool())
+        .await?;
+
+        Ok(events)
+    }
+
+    /// Stored events from `from` onwards, through `to` if given, in the order
+    /// they were indexed. Pages by passing the last row's checkpoint and id as
+    /// `after`.
+    pub async fn list_stored_events(
+        &self,
+        from: u64,
+        to: Option<u64>,
+        after: Option<(i64, i64)>,
+        limit: i64,
+    ) -> Result<Vec<BlockchainEvent>> {
+        let (after_checkpoint, after_id) = after.unwrap_or((-1, -1));
+        let events = sqlx::query_as::<_, BlockchainEvent>(
+            r#"
+            SELECT * FROM blockchain_events
+            WHERE checkpoint_number >= $1
+              AND ($2::BIGINT IS NULL OR checkpoint_number <= $2)
+              AND (checkpoint_number, id) > ($3, $4)
+            ORDER BY checkpoint_number, id
+            LIMIT $5
+            "#,
+        )
+        .bind(from as i64)
+        .bind(to.map(|to| to as i64))
+        .bind(after_checkpoint)
+        .bind(after_id)
+        .bind(limit)
+        .fetch_all(self.pool())
+        .await?;
+
+        Ok(events)
+    }
+
+    /// Newest service listings and tier price changes, for `GET /feed`
+    pub async fn get_catalog_events(&self, limit: i64) -> Result<Vec<CatalogEvent>> {
+        let events = sqlx::query_as::<_, CatalogEvent>(
+            r#"
+            SELECT e.id, e.event_time, (e.event_data->>'timestamp')::BIGINT AS timestamp_ms,
+                   e.checkpoint_number, e.transaction_digest, e.event_type,
+                   COALESCE(e.provider_id, s.provider_id) AS provider_id,
+                   e.service_id, e.tier_id, s.service_type, s.metadata_uri,
+                   t.tier_name, t.coin_type, t.sla,
+                   COALESCE(e.event_data->>'new_price', e.event_data->>'price', t.price::TEXT)::NUMERIC AS price
+            FROM blockchain_events e
+            LEFT JOIN services s ON s.service_id = e.service_id
+            LEFT JOIN pricing_tiers t ON t.tier_id = e.tier_id
+            WHERE e.event_type IN ('ServiceCreated', 'TierCreated', 'TierPriceUpdated', 'TierRemovedFromService')
+               -- Creating a tier also lists it under its own service; that is
+               -- already covered by TierCreated
+               OR (e.event_type = 'TierAddedToService' AND t.service_id IS DISTINCT FROM e.service_id)
+            ORDER BY e.event_time DESC, e.id DESC
+            LIMIT $1
+            "#,
+        )
+        .bind(limit)
+        .fetch_all(self.read_pool())
+        .await?;
 
-                self.create_service(&serv, &prof_id, &service_type, Some(metadata_uri))
-                    .await?;
-            }
+        Ok(events)
+    }
 
-            ProtocolEvent::TierCreated(e) => {
-                let tier_name = String::from_utf8_lossy(&e.tier_name).to_string();
-                let tier_id = e.tier_id.bytes.to_string();
-                let serv = e.service_id.bytes.to_string();
-                let coin_type = &e.coin_type;
+    pub async fn store_failed_event(
+        &self,
+        checkpoint: u64,
+        tx_digest: Option<&str>,
+        event_index: u64,
+        event_type: &str,
+        package_id: Option<&str>,
+        protocol_version: u64,
+        bcs: &[u8],
+        error: &str,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO failed_events
+            (checkpoint_number, transaction_digest, event_index, event_type, package_id, protocol_version, bcs, error)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            "#,
+        )
+        .bind(checkpoint as i64)
+        .bind(tx_digest)
+        .bind(event_index as i32)
+        .bind(event_type)
+        .bind(package_id)
+        .bind(protocol_version as i64)
+        .bind(bcs)
+        .bind(error)
+        .execute(self.pool())
+        .await?;
 
-                sqlx::query(
-                    r#"
-                    INSERT INTO blockchain_events 
-                    (checkpoint_number, transaction_digest, event_type, package_id, module, event_data, service_id, tier_id)
-                    VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
-                    "#,
-                )
-                .bind(checkpoint as i64)
-                .bind(tx_digest)
-                .bind("TierCreated")
-                .bind(crate::utils::constants::PACKAGE_ID)
-                .bind("pricing")
-                .bind(serde_json::to_value(e)?)
-                .bind(&serv)
-                .bind(&tier_id)
-                .execute(self.pool())
+        Ok(())
+    }
+
+    /// Dead-letters an event the worker gave up on, keeping it decoded
+    pub async fn store_failed_payload(
+        &self,
+        payload: &EventPayload,
+        error: &str,
+        attempts: u32,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO failed_events
+            (checkpoint_number, transaction_digest, event_index, event_type, protocol_version, payload, error, attempts)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            "#,
+        )
+        .bind(payload.checkpoint as i64)
+        .bind(&payload.tx_digest)
+        .bind(payload.event_index as i32)
+        .bind(payload.event.label())
+        .bind(payload.protocol_version as i64)
+        .bind(Json(payload))
+        .bind(error)
+        .bind(attempts as i32)
+        .execute(self.pool())
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn list_pending_failed_events(&self, limit: i64) -> Result<Vec<FailedEvent>> {
+        let events = sqlx::query_as(
+            r#"
+            SELECT * FROM failed_events
+            WHERE replayed_at IS NULL
+            ORDER BY checkpoint_number, id
+            LIMIT $1
+            "#,
+        )
+        .bind(limit)
+        .fetch_all(self.pool())
+        .await?;
+
+        Ok(events)
+    }
+
+    pub async fn mark_failed_event_replayed(&self, id: i64) -> Result<()> {
+        sqlx::query("UPDATE failed_events SET replayed_at = NOW() WHERE id = $1")
+            .bind(id)
+            .execute(self.pool())
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn record_failed_event_attempt(&self, id: i64, error: &str) -> Result<()> {
+        sqlx::query("UPDATE failed_events SET attempts = attempts + 1, error = $2 WHERE id = $1")
+            .bind(id)
+            .bind(error)
+            .execute(self.pool())
+            .await?;
+
+        Ok(())
+    }
+
+    /// Queues a message for a provider's sidecars, to be published once
+    /// `conn`'s transaction commits
+    pub async fn enqueue_pubsub(
+        &self,
+        conn: &mut PgConnection,
+        provider_id: &str,
+        event: &PubSubEvent,
+    ) -> Result<()> {
+        sqlx::query("INSERT INTO pubsub_outbox (channel, message) VALUES ($1, $2)")
+            .bind(get_channel(provider_id))
+            .bind(serde_json::to_string(event)?)
+            .execute(&mut *conn)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Takes the outbox for the rest of `conn`'s transaction. False when
+    /// another server is draining it, so messages go out in one order.
+    pub async fn try_lock_outbox(&self, conn: &mut PgConnection) -> Result<bool> {
+        let (locked,): (bool,) = sqlx::query_as("SELECT pg_try_advisory_xact_lock($1)")
+            .bind(OUTBOX_LOCK_ID)
+            .fetch_one(&mut *conn)
+            .await?;
+
+        Ok(locked)
+    }
+
+    /// Oldest unpublished messages
+    pub async fn list_pending_outbox(
+        &self,
+        conn: &mut PgConnection,
+        limit: i64,
+    ) -> Result<Vec<OutboxMessage>> {
+        let messages = sqlx::query_as(
+            r#"
+            SELECT id, channel, message, attempts, next_attempt_at <= NOW() AS due
+            FROM pubsub_outbox
+            WHERE published_at IS NULL
+            ORDER BY id
+            LIMIT $1
+            "#,
+        )
+        .bind(limit)
+        .fetch_all(&mut *conn)
+        .await?;
+
+        Ok(messages)
+    }
+
+    pub async fn mark_outbox_published(&self, conn: &mut PgConnection, id: i64) -> Result<()> {
+        sqlx::query("UPDATE pubsub_outbox SET published_at = NOW() WHERE id = $1")
+            .bind(id)
+            .execute(&mut *conn)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn record_outbox_failure(
+        &self,
+        conn: &mut PgConnection,
+        id: i64,
+        error: &str,
+        retry_in_secs: i64,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            UPDATE pubsub_outbox
+            SET attempts = attempts + 1, last_error = $2,
+                next_attempt_at = NOW() + make_interval(secs => $3)
+            WHERE id = $1
+            "#,
+        )
+        .bind(id)
+        .bind(error)
+        .bind(retry_in_secs as f64)
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn get_checkpoint_cursor(&self, name: &str) -> Result<Option<u64>> {
+        let row: Option<(i64,)> =
+            sqlx::query_as("SELECT checkpoint_number FROM indexer_cursors WHERE name = $1")
+                .bind(name)
+                .fetch_optional(self.pool())
                 .await?;
 
-                self.create_tier(
-                    &tier_id,
-                    &serv,
-                    &tier_name,
-                    e.price as i64,
-                    coin_type,
-                    e.inner.as_tier_type(),
-                    e.inner.duration().map(|d| d as i64),
-                    e.inner.quota().map(|q| q as i64),
-                )
-                .await?;
-            }
+        Ok(row.map(|(checkpoint,)| checkpoint as u64))
+    }
+
+    pub async fn save_checkpoint_cursor(&self, name: &str, checkpoint: u64) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO indexer_cursors (name, checkpoint_number)
+            VALUES ($1, $2)
+            ON CONFLICT (name) DO UPDATE
+            SET checkpoint_number = EXCLUDED.checkpoint_number, updated_at = NOW()
+            WHERE indexer_cursors.checkpoint_number < EXCLUDED.checkpoint_number
+            "#,
+        )
+        .bind(name)
+        .bind(checkpoint as i64)
+        .execute(self.pool())
+        .await?;
+
+        Ok(())
+    }
+
+    /// The buyer's entitlement to a service that can cover a request of
+    /// `cost`, if any. Entitlements on a retired tier with a replacement the
+    /// service lists come back under the replacement; their own expiry and
+    /// counters still decide whether they are valid.
+    pub async fn get_active_entitlement(
+        &self,
+        buyer: &str,
+        service_id: &str,
+        cost: u64,
+    ) -> Result<Option<EntitlementWithTier>> {
+        let row = sqlx::query_as::<_, EntitlementWithTier>(
+            r#"
+            SELECT e.*, t.tier_type, t.duration_ms, t.quota_limit,
+                   COALESCE(nt.tier_name, t.tier_name) AS tier_name,
+                   COALESCE(nt.price, t.price) AS tier_price,
+                   COALESCE(nt.coin_type, t.coin_type) AS coin_type,
+                   nt.tier_id AS replacement_tier_id
+            FROM entitlements e
+            JOIN pricing_tiers t ON e.tier_id = t.tier_id
+            LEFT JOIN tier_replacements r ON r.tier_id = t.tier_id AND t.is_active = false
+            LEFT JOIN service_tiers rst ON rst.tier_id = r.replacement_tier_id AND rst.service_id = e.service_id
+            LEFT JOIN pricing_tiers nt ON nt.tier_id = rst.tier_id
+            WHERE e.buyer = $1
+              AND e.service_id = $2
+              AND (
+                    (t.tier_type = 'subscription' AND (e.expires_at IS NULL OR e.expires_at > NOW() - make_interval(secs => $4)))
+                    OR
+                    (t.tier_type = 'quota' AND e.expires_at > NOW() - make_interval(secs => $4) AND e.quota > $3)
+                    OR
+                    (t.tier_type = 'usage_based' AND e.units > $3)
+                  )
+            LIMIT 1
+            "#,
+        )
+        .bind(buyer)
+        .bind(service_id)
+        .bind(Units::new(cost))
+        .bind(self.expiry_grace_secs as f64)
+        .fetch_optional(self.read_pool())
+        .await?;
+
+        Ok(row)
+    }
+
+    pub async fn get_valid_entitlement_response(
+        &self,
+        user_address: &str,
+        service_id: &str,
+        cost: u64,
+        include_detail: bool,
+    ) -> Result<Option<ValidateResponse>, InfrapassError> {
+        let row = self
+            .get_active_entitlement(user_address, service_id, cost)
+            .await?;
+
+        if let Some(r) = &row {
+            if r.expires_at.is_some_and(|exp| exp <= chrono::Utc::now()) {
+                warn!(
+                    entitlement_id = %r.entitlement_id,
+                    "Entitlement accepted within expiry grace"
+                );
+            }
+        }
+
+        Ok(row.map(|r| ValidateResponse {
+            entitlement_id: r.entitlement_id,
+            tier: r.replacement_tier_id.clone().unwrap_or_else(|| r.tier_id.clone()),
+            replaced_tier: r.replacement_tier_id.is_some().then_some(r.tier_id),
+            quota: r.quota.map(|q| q.get()),
+            units: Some(r.units.get()),
+            tier_type: match r.tier_type.as_str() {
+                "subscription" => 0,
+                "quota" => 1,
+                "usage_based" => 2,
+                _ => 0,
+            },
+            expires_at: r.expires_at,
+            notify_provider: None,
+            tier_name: include_detail.then(|| r.tier_name.clone()),
+            price: include_detail.then(|| r.tier_price.get()),
+            coin_type: include_detail.then(|| r.coin_type.clone()),
+        }))
+    }
+
+    /// Lowers an entitlement's remaining quota or units to what the chain
+    /// reports after a settlement. Usage is committed here before it is
+    /// settled, so the local count is normally lower already and is kept; it
+    /// only drops when the chain saw usage the backend didn't.
+    pub async fn reconcile_consumption(
+        &self,
+        conn: &mut PgConnection,
+        entitlement_id: &str,
+        onchain: &EntitlementConfig,
+    ) -> Result<Option<Entitlement>> {
+        let entitlement = sqlx::query_as::<_, Entitlement>(
+            r#"
+            WITH updated AS (
+            UPDATE entitlements
+            SET quota = CASE WHEN $2::NUMERIC IS NULL THEN quota ELSE LEAST(quota, $2) END,
+                units = CASE WHEN $3::NUMERIC IS NULL THEN units ELSE LEAST(COALESCE(units, 0), $3) END
+            WHERE entitlement_id = $1
+            RETURNING *
+            )
+            SELECT
+            updated.*,
+            s.provider_id
+            FROM updated
+            JOIN services s ON s.service_id = updated.service_id
+            "#,
+        )
+        .bind(entitlement_id)
+        .bind(onchain.quota().map(Units::new))
+        .bind(onchain.units().map(Units::new))
+        .fetch_optional(&mut *conn)
+        .await?;
+
+        Ok(entitlement)
+    }
+
+    /// Adds a top-up's units and payment to an entitlement. Usage charged
+    /// here but not yet settled stays charged, so the units are added rather
+    /// than copied from the chain.
+    pub async fn top_up_entitlement(
+        &self,
+        conn: &mut PgConnection,
+        entitlement_id: &str,
+        units_added: Units,
+        price_paid: MistAmount,
+    ) -> Result<Option<Entitlement>> {
+        let entitlement = sqlx::query_as::<_, Entitlement>(
+            r#"
+            WITH updated AS (
+            UPDATE entitlements
+            SET units = COALESCE(units, 0) + $2,
+                price_paid = price_paid + $3
+            WHERE entitlement_id = $1
+            RETURNING *
+            )
+            SELECT
+            updated.*,
+            s.provider_id
+            FROM updated
+            JOIN services s ON s.service_id = updated.service_id
+            "#,
+        )
+        .bind(entitlement_id)
+        .bind(units_added)
+        .bind(price_paid)
+        .fetch_optional(&mut *conn)
+        .await?;
+
+        Ok(entitlement)
+    }
+
+    /// Charges `cost` to an entitlement and queues it for settlement. With a
+    /// `request_id`, a second submission of the same ID for the entitlement
+    /// charges nothing and comes back as `UsageCommit::Duplicate`.
+    pub async fn commit_usage(&self, entitlement_id: &str, user_address: &str, cost: Units, request_id: Option<&str>) -> Result<UsageCommit, InfrapassError> {
+        let mut tx = self.pool().begin().await?;
+        let commit = Self::charge_usage(&mut tx, entitlement_id, user_address, cost, request_id).await?;
+        tx.commit().await?;
+
+        Ok(commit)
+    }
+
+    /// Charges records in order in one transaction, each under a savepoint
+    /// so one the entitlement can't cover is rolled back and reported on its
+    /// own. A database error fails the whole batch and nothing is charged.
+    pub async fn commit_usage_batch(&self, records: &[RecordUsageRequest]) -> Result<Vec<Result<UsageCommit, InfrapassError>>, InfrapassError> {
+        let mut tx = self.pool().begin().await?;
+        let mut results = Vec::with_capacity(records.len());
+
+        for record in records {
+            let mut savepoint = tx.begin().await?;
+            match Self::charge_usage(&mut savepoint, &record.entitlement_id, &record.user_address, Units::new(record.cost), record.request_id.as_deref()).await {
+                Ok(commit) => {
+                    savepoint.commit().await?;
+                    results.push(Ok(commit));
+                }
+                Err(e @ InfrapassError::ValidationError(_)) => {
+                    savepoint.rollback().await?;
+                    results.push(Err(e));
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        tx.commit().await?;
+        Ok(results)
+    }
+
+    /// Charges one usage record on `conn`, for `commit_usage` and
+    /// `commit_usage_batch`
+    async fn charge_usage(conn: &mut PgConnection, entitlement_id: &str, user_address: &str, cost: Units, request_id: Option<&str>) -> Result<UsageCommit, InfrapassError> {
+        let usage_event_id = Uuid::new_v4();
+
+        if let Some(request_id) = request_id {
+            let recorded = sqlx::query(r#"
+            INSERT INTO usage_records (entitlement_id, request_id, user_address, amount, usage_event_id)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (entitlement_id, request_id) DO NOTHING
+            "#)
+            .bind(entitlement_id)
+            .bind(request_id)
+            .bind(user_address)
+            .bind(cost)
+            .bind(usage_event_id)
+            .execute(&mut *conn)
+            .await?;
+
+            if recorded.rows_affected() == 0 {
+                return Ok(UsageCommit::Duplicate);
+            }
+        }
+
+        let (quota, units, tier_type) = sqlx::query_as::<_, (Option<Units>, Units, TierType)>(r#"
+        SELECT e.quota, COALESCE(e.units, 0) AS units, t.tier_type
+        FROM entitlements e
+        JOIN pricing_tiers t ON t.tier_id = e.tier_id
+        WHERE e.entitlement_id = $1 AND e.buyer = $2
+        FOR UPDATE OF e
+        "#)
+        .bind(entitlement_id)
+        .bind(user_address)
+        .fetch_optional(&mut *conn)
+        .await?
+        .ok_or_else(|| InfrapassError::ValidationError("entitlement not found".into()))?;
+
+        let (quota, units) = match tier_type {
+            // Subscriptions carry no counters
+            TierType::Subscription => (quota, units),
+            TierType::Quota => (
+                Some(quota.unwrap_or(Units::ZERO).checked_sub(cost).ok_or_else(|| {
+                    InfrapassError::ValidationError("usage exceeds remaining quota".into())
+                })?),
+                units,
+            ),
+            TierType::UsageBased => (
+                quota,
+                units.checked_sub(cost).ok_or_else(|| {
+                    InfrapassError::ValidationError("usage exceeds remaining units".into())
+                })?,
+            ),
+        };
+
+        sqlx::query(r#"
+        UPDATE entitlements
+        SET quota = $2, units = $3
+        WHERE entitlement_id = $1
+        "#)
+        .bind(entitlement_id)
+        .bind(quota)
+        .bind(units)
+        .execute(&mut *conn)
+        .await?;
+
+        sqlx::query(r#"
+            INSERT INTO usage_events (id, entitlement_id, user_address, amount)
+            VALUES ($1, $2, $3, $4)
+        "#)
+        .bind(usage_event_id)
+        .bind(entitlement_id)
+        .bind(user_address)
+        .bind(cost)
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(UsageCommit::Recorded)
+    }
+
+    /// Recomputes `usage_daily` from `usage_events` for every day from the
+    /// newest one already rolled up, or from the oldest usage when nothing
+    /// is. Usage is pruned only once settled and past retention, so this must
+    /// run at least once per `PRUNE_RETENTION_DAYS`. Returns the rows written.
+    pub async fn rollup_usage(&self) -> Result<u64> {
+        let result = sqlx::query(
+            r#"
+            WITH start AS (
+                SELECT COALESCE(
+                    (SELECT MAX(day) FROM usage_daily),
+                    (SELECT MIN(recorded_at AT TIME ZONE 'UTC')::DATE FROM usage_events)
+                )::TIMESTAMP AT TIME ZONE 'UTC' AS at
+            )
+            INSERT INTO usage_daily (day, service_id, user_address, submissions, amount)
+            SELECT (u.recorded_at AT TIME ZONE 'UTC')::DATE, e.service_id, u.user_address,
+                   COUNT(*), SUM(u.amount)
+            FROM usage_events u
+            JOIN entitlements e ON e.entitlement_id = u.entitlement_id
+            WHERE u.recorded_at >= (SELECT at FROM start)
+            GROUP BY 1, 2, 3
+            ON CONFLICT (day, service_id, user_address) DO UPDATE
+            SET submissions = EXCLUDED.submissions, amount = EXCLUDED.amount, updated_at = NOW()
+            "#,
+        )
+        .execute(self.pool())
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// A service's rolled-up usage per day from `since`
+    pub async fn service_usage_by_day(&self, service_id: &str, since: NaiveDate) -> Result<Vec<UsageDay>> {
+        let days = sqlx::query_as(
+            r#"
+            SELECT day, COUNT(*) AS buyers, SUM(submissions)::BIGINT AS submissions, SUM(amount) AS amount
+            FROM usage_daily
+            WHERE service_id = $1 AND day >= $2
+            GROUP BY day
+            ORDER BY day
+            "#,
+        )
+        .bind(service_id)
+        .bind(since)
+        .fetch_all(self.read_pool())
+        .await?;
+
+        Ok(days)
+    }
+
+    /// A service's heaviest buyers from `since`, by amount used
+    pub async fn service_usage_by_buyer(&self, service_id: &str, since: NaiveDate, limit: i64) -> Result<Vec<BuyerUsage>> {
+        let buyers = sqlx::query_as(
+            r#"
+            SELECT user_address, SUM(submissions)::BIGINT AS submissions, SUM(amount) AS amount
+            FROM usage_daily
+            WHERE service_id = $1 AND day >= $2
+            GROUP BY user_address
+            ORDER BY amount DESC, user_address
+            LIMIT $3
+            "#,
+        )
+        .bind(service_id)
+        .bind(since)
+        .bind(limit)
+        .fetch_all(self.read_pool())
+        .await?;
+
+        Ok(buyers)
+    }
+
+    /// Rolled-up usage of each of a provider's services from `since`
+    pub async fn provider_usage_by_service(&self, provider_id: &str, since: NaiveDate) -> Result<Vec<ServiceUsage>> {
+        let services = sqlx::query_as(
+            r#"
+            SELECT d.service_id, COUNT(DISTINCT d.user_address) AS buyers,
+                   SUM(d.submissions)::BIGINT AS submissions, SUM(d.amount) AS amount
+            FROM usage_daily d
+            JOIN services s ON s.service_id = d.service_id
+            WHERE s.provider_id = $1 AND d.day >= $2
+            GROUP BY d.service_id
+            ORDER BY d.service_id
+            "#,
+        )
+        .bind(provider_id)
+        .bind(since)
+        .fetch_all(self.read_pool())
+        .await?;
+
+        Ok(services)
+    }
 
-            _ => {
-                sqlx::query(
-                    r#"
-                    INSERT INTO blockchain_events 
-                    (checkpoint_number, transaction_digest, event_type, package_id, module, event_data)
-                    VALUES ($1, $2, $3, $4, $5, $6)
-                    "#,
-                )
-                .bind(checkpoint as i64)
-                .bind(tx_digest)
-                .bind(format!("{:?}", event))
-                .bind(crate::utils::constants::PACKAGE_ID,)
-                .bind("unknown")
-                .bind(serde_json::to_value(event)?)
-                .execute(self.pool())
-                .await?;
-            }
-        }
+    /// A provider's rolled-up usage per day from `since`, across services
+    pub async fn provider_usage_by_day(&self, provider_id: &str, since: NaiveDate) -> Result<Vec<UsageDay>> {
+        let days = sqlx::query_as(
+            r#"
+            SELECT d.day, COUNT(DISTINCT d.user_address) AS buyers,
+                   SUM(d.submissions)::BIGINT AS submissions, SUM(d.amount) AS amount
+            FROM usage_daily d
+            JOIN services s ON s.service_id = d.service_id
+            WHERE s.provider_id = $1 AND d.day >= $2
+            GROUP BY d.day
+            ORDER BY d.day
+            "#,
+        )
+        .bind(provider_id)
+        .bind(since)
+        .fetch_all(self.read_pool())
+        .await?;
 
-        Ok(())
+        Ok(days)
     }
 
-    pub async fn get_recent_events(&self, limit: i64) -> Result<Vec<BlockchainEvent>> {
-        let events = sqlx::query_as::<_, BlockchainEvent>(
-            r#"SELECT * FROM blockchain_events ORDER BY event_time DESC LIMIT $1"#,
+    /// A provider's heaviest buyers from `since`, by amount used across services
+    pub async fn provider_usage_by_buyer(&self, provider_id: &str, since: NaiveDate, limit: i64) -> Result<Vec<BuyerUsage>> {
+        let buyers = sqlx::query_as(
+            r#"
+            SELECT d.user_address, SUM(d.submissions)::BIGINT AS submissions, SUM(d.amount) AS amount
+            FROM usage_daily d
+            JOIN services s ON s.service_id = d.service_id
+            WHERE s.provider_id = $1 AND d.day >= $2
+            GROUP BY d.user_address
+            ORDER BY amount DESC, d.user_address
+            LIMIT $3
+            "#,
         )
+        .bind(provider_id)
+        .bind(since)
         .bind(limit)
-        .fetch_all(self.pool())
+        .fetch_all(self.read_pool())
         .await?;
 
-        Ok(events)
+        Ok(buyers)
     }
 
-    pub async fn get_valid_entitlement_response(
+    /// A page of a provider's usage events recorded in `[from, to)`, oldest
+    /// first
+    pub async fn export_provider_usage(
         &self,
-        user_address: &str,
-        service_id: &str,
-        cost: u64,
-    ) -> Result<Option<ValidateResponse>, InfrapassError> {
-        let row = sqlx::query_as::<_, EntitlementWithTier>(
+        provider_id: &str,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        page: &PageRequest,
+    ) -> Result<Page<UsageExportRow>> {
+        let rows = sqlx::query_as::<_, UsageExportRow>(
             r#"
-            SELECT e.*, t.tier_type, t.duration_ms, t.quota_limit
-            FROM entitlements e
-            JOIN pricing_tiers t ON e.tier_id = t.tier_id
-            WHERE e.buyer = $1
-              AND e.service_id = $2
-              AND (
-                    (t.tier_type = 'subscription' AND (e.expires_at IS NULL OR e.expires_at > NOW()))
-                    OR
-                    (t.tier_type = 'quota' AND e.expires_at > NOW() AND e.quota > $3)
-                    OR
-                    (t.tier_type = 'usage_based' AND e.units > $3)
-                  )
-            LIMIT 1
+            SELECT u.id, u.recorded_at, u.entitlement_id, e.service_id, e.tier_id,
+                   u.user_address, u.amount, u.settled_at, u.settlement_id
+            FROM usage_events u
+            JOIN entitlements e ON e.entitlement_id = u.entitlement_id
+            JOIN services s ON s.service_id = e.service_id
+            WHERE s.provider_id = $1 AND u.recorded_at >= $2 AND u.recorded_at < $3
+              AND ($4::TIMESTAMPTZ IS NULL OR (u.recorded_at, u.id::TEXT) > ($4, $5))
+            ORDER BY u.recorded_at, u.id::TEXT
+            LIMIT $6
             "#,
         )
-        .bind(user_address)
-        .bind(service_id)
-        .bind(cost as i64)
-        .fetch_optional(self.pool())
+        .bind(provider_id)
+        .bind(from)
+        .bind(to)
+        .bind(page.cursor_time())
+        .bind(page.cursor_id())
+        .bind(page.fetch_limit())
+        .fetch_all(self.read_pool())
         .await?;
-    
-        Ok(row.map(|r| ValidateResponse {
-            entitlement_id: r.entitlement_id,
-            tier: r.tier_id,
-            quota: r.quota.map(|q| q as u64),
-            units: Some(r.units as u64),
-            tier_type: match r.tier_type.as_str() {
-                "subscription" => 0,
-                "quota" => 1,
-                "usage_based" => 2,
-                _ => 0,
-            },
-            expires_at: r.expires_at,
-            notify_provider: None,
-        }))
-    }
 
-    pub async fn commit_usage(&self, entitlement_id: &str, user_address: &str, cost: u64) -> Result<(), InfrapassError> {
-        let mut tx = self.pool().begin().await?;
+        Ok(Page::from_rows(rows, page, |r| Cursor::new(r.recorded_at, &r.id.to_string())))
+    }
 
-        sqlx::query(r#"
-        UPDATE entitlements
-        SET 
-            quota = CASE WHEN quota IS NOT NULL THEN quota - $3 ELSE NULL END,
-            units = CASE WHEN units IS NOT NULL THEN units - $3 ELSE NULL END
-        WHERE entitlement_id = $1 AND buyer = $2
-        "#)
-        .bind(entitlement_id)
-        .bind(user_address)
-        .bind(cost as i64)
-        .execute(&mut *tx)
+    /// A page of the purchases of a provider's services made in `[from, to)`,
+    /// oldest first
+    pub async fn export_provider_purchases(
+        &self,
+        provider_id: &str,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        page: &PageRequest,
+    ) -> Result<Page<PurchaseExportRow>> {
+        let rows = sqlx::query_as::<_, PurchaseExportRow>(
+            r#"
+            SELECT e.entitlement_id, e.created_at, e.buyer, e.service_id, e.tier_id,
+                   t.tier_name, t.coin_type, e.price_paid
+            FROM entitlements e
+            JOIN services s ON s.service_id = e.service_id
+            JOIN pricing_tiers t ON t.tier_id = e.tier_id
+            WHERE s.provider_id = $1 AND e.created_at >= $2 AND e.created_at < $3
+              AND ($4::TIMESTAMPTZ IS NULL OR (e.created_at, e.entitlement_id) > ($4, $5))
+            ORDER BY e.created_at, e.entitlement_id
+            LIMIT $6
+            "#,
+        )
+        .bind(provider_id)
+        .bind(from)
+        .bind(to)
+        .bind(page.cursor_time())
+        .bind(page.cursor_id())
+        .bind(page.fetch_limit())
+        .fetch_all(self.read_pool())
         .await?;
 
-        sqlx::query(r#"
-            INSERT INTO usage_events (entitlement_id, user_address, amount)
-            VALUES ($1, $2, $3)
-        "#)
-        .bind(entitlement_id)
-        .bind(user_address)
-        .bind(cost as i64)
-        .execute(&mut *tx)
-        .await?;
+        Ok(Page::from_rows(rows, page, |r| Cursor::new(r.created_at, &r.entitlement_id)))
+    }
 
-        tx.commit().await?;
+    /// Entitlements to a provider's services that are unexpired, or usage
+    /// based with units left, as in `list_service_access`
+    pub async fn count_active_entitlements(&self, provider_id: &str) -> Result<i64> {
+        let (count,): (i64,) = sqlx::query_as(
+            r#"
+            SELECT COUNT(*)
+            FROM entitlements e
+            JOIN services s ON s.service_id = e.service_id
+            WHERE s.provider_id = $1
+              AND (
+                    (e.expires_at IS NOT NULL AND e.expires_at > NOW() - make_interval(secs => $2))
+                    OR
+                    (e.expires_at IS NULL AND e.units > 0)
+                  )
+            "#,
+        )
+        .bind(provider_id)
+        .bind(self.expiry_grace_secs as f64)
+        .fetch_one(self.read_pool())
+        .await?;
 
-        Ok(())
+        Ok(count)
     }
 
     pub async fn get_unsettled_aggregated(&self) -> Result<Vec<AggregatedPending>, InfrapassError> {
@@ -610,14 +3001,354 @@ impl Repository {
         Ok(row)
     }
     
-    pub async fn mark_settled(&self, event_ids: &[Uuid]) -> Result<(), InfrapassError> {
+    pub async fn unsettled_totals(&self) -> Result<UnsettledTotals> {
+        let totals = sqlx::query_as(
+            r#"
+            SELECT COUNT(DISTINCT entitlement_id) AS entitlements, COALESCE(SUM(amount), 0) AS amount
+            FROM usage_events
+            WHERE settled_at IS NULL
+            "#,
+        )
+        .fetch_one(self.pool())
+        .await?;
+
+        Ok(totals)
+    }
+
+    /// Records a settlement about to be submitted
+    pub async fn create_settlement(&self, trigger: &str, entitlements: usize, total_amount: Units) -> Result<Uuid> {
+        let id = Uuid::new_v4();
+        sqlx::query("INSERT INTO settlements (id, trigger, entitlements, total_amount) VALUES ($1, $2, $3, $4)")
+            .bind(id)
+            .bind(trigger)
+            .bind(entitlements as i32)
+            .bind(total_amount)
+            .execute(self.pool())
+            .await?;
+
+        Ok(id)
+    }
+
+    /// Marks a settlement executed on chain and its usage events settled,
+    /// in one transaction
+    pub async fn complete_settlement(&self, id: Uuid, tx_digest: &str, event_ids: &[Uuid]) -> Result<()> {
+        let mut tx = self.begin().await?;
+
         sqlx::query(r#"
-            UPDATE usage_events SET settled_at = NOW()
+            UPDATE usage_events SET settled_at = NOW(), settlement_id = $2
             WHERE id = ANY($1)
         "#)
         .bind(event_ids)
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query("UPDATE settlements SET status = 'settled', tx_digest = $2, finished_at = NOW() WHERE id = $1")
+            .bind(id)
+            .bind(tx_digest)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    pub async fn fail_settlement(&self, id: Uuid, error: &str) -> Result<()> {
+        sqlx::query("UPDATE settlements SET status = 'failed', error = $2, finished_at = NOW() WHERE id = $1")
+            .bind(id)
+            .bind(error)
+            .execute(self.pool())
+            .await?;
+
+        Ok(())
+    }
+
+    /// Most recent settlements, optionally only those in `status`
+    pub async fn list_settlements(&self, status: Option<&str>, limit: i64) -> Result<Vec<Settlement>> {
+        let settlements = sqlx::query_as(
+            r#"
+            SELECT * FROM settlements
+            WHERE ($1::TEXT IS NULL OR status = $1)
+            ORDER BY created_at DESC
+            LIMIT $2
+            "#,
+        )
+        .bind(status)
+        .bind(limit)
+        .fetch_all(self.pool())
+        .await?;
+
+        Ok(settlements)
+    }
+
+    pub async fn create_tenant(&self, tenant_id: &str, name: &str) -> Result<Tenant> {
+        let tenant = sqlx::query_as::<_, Tenant>(
+            "INSERT INTO tenants (tenant_id, name) VALUES ($1, $2) RETURNING *",
+        )
+        .bind(tenant_id)
+        .bind(name)
+        .fetch_one(self.pool())
+        .await?;
+
+        Ok(tenant)
+    }
+
+    pub async fn list_tenants(&self) -> Result<Vec<Tenant>> {
+        let tenants = sqlx::query_as("SELECT * FROM tenants ORDER BY created_at")
+            .fetch_all(self.pool())
+            .await?;
+
+        Ok(tenants)
+    }
+
+    /// Issues an API key bound to `tenant_id` and returns it. Only its hash
+    /// is kept.
+    pub async fn create_tenant_api_key(&self, tenant_id: &str, label: Option<&str>) -> Result<String> {
+        let key = tenant::generate_api_key();
+        sqlx::query("INSERT INTO tenant_api_keys (key_hash, tenant_id, label) VALUES ($1, $2, $3)")
+            .bind(tenant::hash_api_key(&key))
+            .bind(tenant_id)
+            .bind(label)
+            .execute(self.pool())
+            .await?;
+
+        Ok(key)
+    }
+
+    /// Revokes every key of `tenant_id` with the given label. Returns the
+    /// number revoked.
+    pub async fn revoke_tenant_api_keys(&self, tenant_id: &str, label: &str) -> Result<u64> {
+        let result = sqlx::query(
+            r#"
+            UPDATE tenant_api_keys SET revoked_at = NOW()
+            WHERE tenant_id = $1 AND label = $2 AND revoked_at IS NULL
+            "#,
+        )
+        .bind(tenant_id)
+        .bind(label)
         .execute(self.pool())
         .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// The tenant an API key belongs to, unless it is unknown or revoked
+    pub async fn resolve_tenant_api_key(&self, key: &str) -> Result<Option<String>> {
+        let tenant: Option<(String,)> = sqlx::query_as(
+            "SELECT tenant_id FROM tenant_api_keys WHERE key_hash = $1 AND revoked_at IS NULL",
+        )
+        .bind(tenant::hash_api_key(key))
+        .fetch_optional(self.pool())
+        .await?;
+
+        Ok(tenant.map(|(tenant_id,)| tenant_id))
+    }
+
+    /// Puts a provider under `tenant_id`. Rows indexed later are tagged by
+    /// trigger; the provider's existing rows are tagged here, in one
+    /// transaction.
+    pub async fn assign_tenant_provider(&self, tenant_id: &str, provider_id: &str) -> Result<()> {
+        let mut tx = self.begin().await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO tenant_providers (provider_id, tenant_id) VALUES ($1, $2)
+            ON CONFLICT (provider_id) DO UPDATE SET tenant_id = EXCLUDED.tenant_id
+            "#,
+        )
+        .bind(provider_id)
+        .bind(tenant_id)
+        .execute(&mut *tx)
+        .await?;
+
+        let statements = [
+            "UPDATE providers SET tenant_id = $1 WHERE profile_id = $2",
+            "UPDATE services SET tenant_id = $1 WHERE provider_id = $2",
+            "UPDATE pricing_tiers SET tenant_id = $1 WHERE service_id IN (SELECT service_id FROM services WHERE provider_id = $2)",
+            "UPDATE maintenance_windows SET tenant_id = $1 WHERE service_id IN (SELECT service_id FROM services WHERE provider_id = $2)",
+            "UPDATE entitlements SET tenant_id = $1 WHERE provider_id = $2",
+            "UPDATE provider_webhooks SET tenant_id = $1 WHERE provider_id = $2",
+            "UPDATE sidecar_instances SET tenant_id = $1 WHERE provider_id = $2",
+            "UPDATE buyer_contacts SET tenant_id = $1 WHERE provider_id = $2",
+            "UPDATE usage_events SET tenant_id = $1 WHERE entitlement_id IN (SELECT entitlement_id FROM entitlements WHERE provider_id = $2)",
+            "UPDATE usage_records SET tenant_id = $1 WHERE entitlement_id IN (SELECT entitlement_id FROM entitlements WHERE provider_id = $2)",
+            "UPDATE usage_daily SET tenant_id = $1 WHERE service_id IN (SELECT service_id FROM services WHERE provider_id = $2)",
+            "UPDATE provider_webhook_deliveries SET tenant_id = $1 WHERE webhook_id IN (SELECT id FROM provider_webhooks WHERE provider_id = $2)",
+        ];
+        for statement in statements {
+            sqlx::query(statement)
+                .bind(tenant_id)
+                .bind(provider_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        tx.commit().await?;
         Ok(())
     }
+
+    /// Issues an API key for `provider_id` and returns it with its record.
+    /// Only its hash is kept.
+    pub async fn create_api_key(&self, provider_id: &str, label: Option<&str>) -> Result<(ApiKey, String)> {
+        let key = tenant::generate_api_key();
+        let api_key = sqlx::query_as(
+            r#"
+            INSERT INTO api_keys (key_id, key_hash, provider_id, label)
+            VALUES ($1, $2, $3, $4)
+            RETURNING key_id, provider_id, label, created_at, revoked_at, rotated_from
+            "#,
+        )
+        .bind(Uuid::new_v4())
+        .bind(tenant::hash_api_key(&key))
+        .bind(provider_id)
+        .bind(label)
+        .fetch_one(self.pool())
+        .await?;
+
+        Ok((api_key, key))
+    }
+
+    /// API keys, newest first, optionally of one provider, revoked ones included
+    pub async fn list_api_keys(&self, provider_id: Option<&str>) -> Result<Vec<ApiKey>> {
+        let keys = sqlx::query_as(
+            r#"
+            SELECT key_id, provider_id, label, created_at, revoked_at, rotated_from
+            FROM api_keys
+            WHERE $1::TEXT IS NULL OR provider_id = $1
+            ORDER BY created_at DESC
+            "#,
+        )
+        .bind(provider_id)
+        .fetch_all(self.pool())
+        .await?;
+
+        Ok(keys)
+    }
+
+    /// Issues a key replacing `key_id`, for the same provider and label, and
+    /// revokes the old one once `grace` has passed, or sooner if it was
+    /// already due. `None` if the old key is unknown or already revoked.
+    pub async fn rotate_api_key(&self, key_id: Uuid, grace: chrono::Duration) -> Result<Option<(ApiKey, String)>> {
+        let mut tx = self.begin().await?;
+
+        let old: Option<ApiKey> = sqlx::query_as(
+            r#"
+            UPDATE api_keys SET revoked_at = LEAST(COALESCE(revoked_at, 'infinity'), NOW() + $2)
+            WHERE key_id = $1 AND (revoked_at IS NULL OR revoked_at > NOW())
+            RETURNING key_id, provider_id, label, created_at, revoked_at, rotated_from
+            "#,
+        )
+        .bind(key_id)
+        .bind(grace)
+        .fetch_optional(&mut *tx)
+        .await?;
+        let Some(old) = old else {
+            return Ok(None);
+        };
+
+        let key = tenant::generate_api_key();
+        let api_key = sqlx::query_as(
+            r#"
+            INSERT INTO api_keys (key_id, key_hash, provider_id, label, rotated_from)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING key_id, provider_id, label, created_at, revoked_at, rotated_from
+            "#,
+        )
+        .bind(Uuid::new_v4())
+        .bind(tenant::hash_api_key(&key))
+        .bind(&old.provider_id)
+        .bind(&old.label)
+        .bind(old.key_id)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(Some((api_key, key)))
+    }
+
+    /// Revokes `key_id` at once, cutting short any rotation grace period.
+    /// `None` if it is unknown or already revoked.
+    pub async fn revoke_api_key(&self, key_id: Uuid) -> Result<Option<ApiKey>> {
+        let api_key = sqlx::query_as(
+            r#"
+            UPDATE api_keys SET revoked_at = NOW()
+            WHERE key_id = $1 AND (revoked_at IS NULL OR revoked_at > NOW())
+            RETURNING key_id, provider_id, label, created_at, revoked_at, rotated_from
+            "#,
+        )
+        .bind(key_id)
+        .fetch_optional(self.pool())
+        .await?;
+
+        Ok(api_key)
+    }
+
+    /// The provider an API key acts for, unless it is unknown or revoked
+    pub async fn resolve_api_key(&self, key: &str) -> Result<Option<ApiKeyOwner>> {
+        let owner = sqlx::query_as(
+            r#"
+            SELECT k.key_id, k.provider_id, tp.tenant_id
+            FROM api_keys k
+            LEFT JOIN tenant_providers tp ON tp.provider_id = k.provider_id
+            WHERE k.key_hash = $1 AND (k.revoked_at IS NULL OR k.revoked_at > NOW())
+            "#,
+        )
+        .bind(tenant::hash_api_key(key))
+        .fetch_optional(self.pool())
+        .await?;
+
+        Ok(owner)
+    }
+
+    /// Hands out a sign-in nonce for `provider_id` that can be answered for
+    /// `ttl`, and clears out challenges that expired unanswered
+    pub async fn create_sign_in_challenge(&self, provider_id: &str, ttl: chrono::Duration) -> Result<(String, DateTime<Utc>)> {
+        sqlx::query("DELETE FROM sign_in_challenges WHERE expires_at < NOW()")
+            .execute(self.pool())
+            .await?;
+
+        let nonce = Uuid::new_v4().simple().to_string();
+        let expires_at = sqlx::query_scalar(
+            r#"
+            INSERT INTO sign_in_challenges (nonce, provider_id, expires_at)
+            VALUES ($1, $2, NOW() + $3)
+            RETURNING expires_at
+            "#,
+        )
+        .bind(&nonce)
+        .bind(provider_id)
+        .bind(ttl)
+        .fetch_one(self.pool())
+        .await?;
+
+        Ok((nonce, expires_at))
+    }
+
+    /// Deletes an unexpired challenge of `provider_id` and returns when it
+    /// would have expired. `None` if it is unknown, expired or already used.
+    pub async fn take_sign_in_challenge(&self, nonce: &str, provider_id: &str) -> Result<Option<DateTime<Utc>>> {
+        let expires_at = sqlx::query_scalar(
+            r#"
+            DELETE FROM sign_in_challenges
+            WHERE nonce = $1 AND provider_id = $2 AND expires_at > NOW()
+            RETURNING expires_at
+            "#,
+        )
+        .bind(nonce)
+        .bind(provider_id)
+        .fetch_optional(self.pool())
+        .await?;
+
+        Ok(expires_at)
+    }
+
+    /// The tenant `provider_id` is assigned to, if any
+    pub async fn provider_tenant(&self, provider_id: &str) -> Result<Option<String>> {
+        let tenant_id = sqlx::query_scalar("SELECT tenant_id FROM tenant_providers WHERE provider_id = $1")
+            .bind(provider_id)
+            .fetch_optional(self.pool())
+            .await?;
+
+        Ok(tenant_id)
+    }
 }
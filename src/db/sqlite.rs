@@ -0,0 +1,664 @@
+use std::{str::FromStr, time::Duration};
+
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::{
+    Connection, SqliteConnection, SqlitePool,
+    sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions},
+    types::Json,
+};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::{
+    api_types::validator::{RecordUsageRequest, ValidateResponse},
+    db::{models::UsageCommit, storage::Storage},
+    events::types::{EntitlementConfig, EventPayload, ProtocolEvent},
+    types::amount::Units,
+    utils::error::InfrapassError,
+};
+
+/// Storage over a single SQLite file. It indexes providers, services,
+/// tiers and entitlements and records usage, which is all `/validate` and
+/// `/record_usage` need; the event log, catalog listings, reports, webhooks
+/// and tenancy stay Postgres only.
+///
+/// The pool holds one connection: SQLite allows a single writer, and
+/// queueing in the pool is cheaper than retrying on `SQLITE_BUSY`.
+pub struct SqliteStorage {
+    pool: SqlitePool,
+    /// Seconds an entitlement stays valid past `expires_at`
+    expiry_grace_secs: i64,
+}
+
+impl SqliteStorage {
+    /// Opens or creates the database at `database_url` (e.g.
+    /// `sqlite://infrapass.db`) and applies pending migrations
+    pub async fn connect(database_url: &str) -> Result<Self> {
+        info!("Opening SQLite database: {}", database_url);
+
+        let options = SqliteConnectOptions::from_str(database_url)?
+            .create_if_missing(true)
+            .journal_mode(SqliteJournalMode::Wal)
+            .busy_timeout(Duration::from_secs(10));
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect_with(options)
+            .await?;
+
+        sqlx::migrate!("src/db/sqlite_migrations")
+            .run(&pool)
+            .await?;
+        info!("SQLite migrations completed");
+
+        Ok(Self {
+            pool,
+            expiry_grace_secs: 0,
+        })
+    }
+
+    pub fn with_expiry_grace(mut self, secs: u64) -> Self {
+        self.expiry_grace_secs = secs as i64;
+        self
+    }
+
+    pub fn pool(&self) -> &SqlitePool {
+        &self.pool
+    }
+
+    /// Applies one event's projection, at most once per transaction digest
+    /// and event index
+    pub async fn apply_event(&self, payload: &EventPayload) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+
+        if let Some(tx_digest) = &payload.tx_digest {
+            let marked = sqlx::query(
+                r#"
+                INSERT INTO ingested_events (transaction_digest, event_index, checkpoint_number)
+                VALUES (?1, ?2, ?3)
+                ON CONFLICT DO NOTHING
+                "#,
+            )
+            .bind(tx_digest)
+            .bind(payload.event_index as i64)
+            .bind(payload.checkpoint as i64)
+            .execute(&mut *tx)
+            .await?;
+            if marked.rows_affected() == 0 {
+                return Ok(());
+            }
+        }
+
+        project(&mut *tx, &payload.event).await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Dead-letters an event that decoded but could not be applied
+    pub async fn store_failed_payload(
+        &self,
+        payload: &EventPayload,
+        error: &str,
+        attempts: u32,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO failed_events
+            (checkpoint_number, transaction_digest, event_index, event_type, protocol_version, payload, error, attempts, created_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+            "#,
+        )
+        .bind(payload.checkpoint as i64)
+        .bind(&payload.tx_digest)
+        .bind(payload.event_index as i64)
+        .bind(payload.event.label())
+        .bind(payload.protocol_version as i64)
+        .bind(Json(payload))
+        .bind(error)
+        .bind(attempts as i64)
+        .bind(Utc::now())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+}
+
+async fn project(conn: &mut SqliteConnection, event: &ProtocolEvent) -> Result<()> {
+    let now = Utc::now();
+
+    match event {
+        ProtocolEvent::ProviderRegistered(e) => {
+            sqlx::query(
+                r#"
+                INSERT INTO providers (profile_id, provider_address, metadata_uri, created_at, updated_at)
+                VALUES (?1, ?2, ?3, ?4, ?4)
+                ON CONFLICT (profile_id) DO UPDATE
+                SET provider_address = excluded.provider_address,
+                    metadata_uri = excluded.metadata_uri,
+                    updated_at = excluded.updated_at
+                "#,
+            )
+            .bind(e.profile_id.bytes.to_string())
+            .bind(e.provider_address.to_string())
+            .bind(&e.metadata)
+            .bind(now)
+            .execute(&mut *conn)
+            .await?;
+        }
+
+        ProtocolEvent::ServiceCreated(e) => {
+            sqlx::query(
+                r#"
+                INSERT INTO services (service_id, provider_id, service_type, metadata_uri, created_at, updated_at)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?5)
+                ON CONFLICT (service_id) DO UPDATE
+                SET metadata_uri = excluded.metadata_uri, updated_at = excluded.updated_at
+                "#,
+            )
+            .bind(e.service_id.bytes.to_string())
+            .bind(e.provider.bytes.to_string())
+            .bind(String::from_utf8_lossy(&e.service_type).to_string())
+            .bind(String::from_utf8_lossy(&e.metadata_uri).to_string())
+            .bind(now)
+            .execute(&mut *conn)
+            .await?;
+        }
+
+        ProtocolEvent::ServiceUpdated(e) => {
+            sqlx::query(
+                "UPDATE services SET metadata_uri = ?1, updated_at = ?2 WHERE service_id = ?3",
+            )
+            .bind(String::from_utf8_lossy(&e.metadata_uri).to_string())
+            .bind(now)
+            .bind(e.service_id.bytes.to_string())
+            .execute(&mut *conn)
+            .await?;
+        }
+
+        ProtocolEvent::ServiceDeactivated(e) => {
+            set_service_active(conn, &e.service_id.bytes.to_string(), false).await?;
+        }
+
+        ProtocolEvent::ServiceReactivated(e) => {
+            set_service_active(conn, &e.service_id.bytes.to_string(), true).await?;
+        }
+
+        ProtocolEvent::TierAddedToService(e) => {
+            sqlx::query(
+                r#"
+                INSERT INTO service_tiers (service_id, tier_id, added_at)
+                VALUES (?1, ?2, ?3)
+                ON CONFLICT DO NOTHING
+                "#,
+            )
+            .bind(e.service_id.bytes.to_string())
+            .bind(e.tier_id.bytes.to_string())
+            .bind(now)
+            .execute(&mut *conn)
+            .await?;
+        }
+
+        ProtocolEvent::TierRemovedFromService(e) => {
+            sqlx::query("DELETE FROM service_tiers WHERE service_id = ?1 AND tier_id = ?2")
+                .bind(e.service_id.bytes.to_string())
+                .bind(e.tier_id.bytes.to_string())
+                .execute(&mut *conn)
+                .await?;
+        }
+
+        ProtocolEvent::TierCreated(e) => {
+            sqlx::query(
+                r#"
+                INSERT INTO pricing_tiers
+                (tier_id, service_id, tier_name, price, coin_type, tier_type, duration_ms, quota_limit, created_at, updated_at)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?9)
+                ON CONFLICT (tier_id) DO NOTHING
+                "#,
+            )
+            .bind(e.tier_id.bytes.to_string())
+            .bind(e.service_id.bytes.to_string())
+            .bind(String::from_utf8_lossy(&e.tier_name).to_string())
+            .bind(integer(e.price)?)
+            .bind(&e.coin_type)
+            .bind(e.inner.as_tier_type_string())
+            .bind(e.inner.duration().map(integer).transpose()?)
+            .bind(e.inner.quota().map(integer).transpose()?)
+            .bind(now)
+            .execute(&mut *conn)
+            .await?;
+        }
+
+        ProtocolEvent::TierPriceUpdated(e) => {
+            sqlx::query("UPDATE pricing_tiers SET price = ?1, updated_at = ?2 WHERE tier_id = ?3")
+                .bind(integer(e.new_price)?)
+                .bind(now)
+                .bind(e.tier_id.bytes.to_string())
+                .execute(&mut *conn)
+                .await?;
+        }
+
+        ProtocolEvent::TierDeactivated(e) => {
+            set_tier_active(conn, &e.tier_id.bytes.to_string(), false).await?;
+        }
+
+        ProtocolEvent::TierReactivated(e) => {
+            set_tier_active(conn, &e.tier_id.bytes.to_string(), true).await?;
+        }
+
+        ProtocolEvent::EntitlementPurchased(e) => {
+            let created_at = timestamp(e.timestamp)?;
+            let (expires_at, quota, units) = match &e.inner {
+                EntitlementConfig::Subscription { expires_at } => {
+                    (Some(timestamp(*expires_at)?), None, 0)
+                }
+                EntitlementConfig::Quota { expires_at, quota } => {
+                    (Some(timestamp(*expires_at)?), Some(integer(*quota)?), 0)
+                }
+                EntitlementConfig::UsageBased { units } => (None, None, integer(*units)?),
+            };
+
+            sqlx::query(
+                r#"
+                INSERT INTO entitlements
+                (entitlement_id, buyer, service_id, tier_id, price_paid, expires_at, quota, units, created_at)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+                ON CONFLICT (entitlement_id) DO NOTHING
+                "#,
+            )
+            .bind(e.entitlement_id.bytes.to_string())
+            .bind(e.buyer.to_string())
+            .bind(e.service_id.bytes.to_string())
+            .bind(e.tier_id.bytes.to_string())
+            .bind(integer(e.price_paid)?)
+            .bind(expires_at)
+            .bind(quota)
+            .bind(units)
+            .bind(created_at)
+            .execute(&mut *conn)
+            .await?;
+        }
+
+        // Added to rather than copied, like usage committed before settlement
+        ProtocolEvent::EntitlementToppedUp(e) => {
+            let updated = sqlx::query(
+                r#"
+                UPDATE entitlements
+                SET units = units + ?2, price_paid = price_paid + ?3
+                WHERE entitlement_id = ?1
+                "#,
+            )
+            .bind(e.entitlement_id.bytes.to_string())
+            .bind(integer(e.units_added)?)
+            .bind(integer(e.price_paid)?)
+            .execute(&mut *conn)
+            .await?;
+
+            if updated.rows_affected() == 0 {
+                warn!(entitlement_id = %e.entitlement_id.bytes, "Top-up for unknown entitlement");
+            }
+        }
+
+        // Usage is committed here before it is settled, so the local count
+        // only drops when the chain saw usage this backend didn't
+        ProtocolEvent::QuotaConsumed(e) => {
+            let updated = sqlx::query(
+                r#"
+                UPDATE entitlements
+                SET quota = CASE WHEN ?2 IS NULL THEN quota ELSE MIN(quota, ?2) END,
+                    units = CASE WHEN ?3 IS NULL THEN units ELSE MIN(units, ?3) END
+                WHERE entitlement_id = ?1
+                "#,
+            )
+            .bind(e.entitlement_id.bytes.to_string())
+            .bind(e.inner.quota().map(integer).transpose()?)
+            .bind(e.inner.units().map(integer).transpose()?)
+            .execute(&mut *conn)
+            .await?;
+
+            if updated.rows_affected() == 0 {
+                warn!(entitlement_id = %e.entitlement_id.bytes, "Quota consumed for unknown entitlement");
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn set_service_active(
+    conn: &mut SqliteConnection,
+    service_id: &str,
+    active: bool,
+) -> Result<()> {
+    sqlx::query("UPDATE services SET is_active = ?1, updated_at = ?2 WHERE service_id = ?3")
+        .bind(active)
+        .bind(Utc::now())
+        .bind(service_id)
+        .execute(&mut *conn)
+        .await?;
+    Ok(())
+}
+
+async fn set_tier_active(conn: &mut SqliteConnection, tier_id: &str, active: bool) -> Result<()> {
+    sqlx::query("UPDATE pricing_tiers SET is_active = ?1, updated_at = ?2 WHERE tier_id = ?3")
+        .bind(active)
+        .bind(Utc::now())
+        .bind(tier_id)
+        .execute(&mut *conn)
+        .await?;
+    Ok(())
+}
+
+/// Charges one usage record on `conn`, for `commit_usage` and
+/// `commit_usage_batch`
+async fn charge_usage(
+    conn: &mut SqliteConnection,
+    entitlement_id: &str,
+    user_address: &str,
+    cost: Units,
+    request_id: Option<&str>,
+) -> Result<UsageCommit, InfrapassError> {
+    let cost = integer(cost.get())?;
+    let now = Utc::now();
+    let usage_event_id = Uuid::new_v4().to_string();
+
+    if let Some(request_id) = request_id {
+        let recorded = sqlx::query(
+            r#"
+            INSERT INTO usage_records (entitlement_id, request_id, user_address, amount, usage_event_id, recorded_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+            ON CONFLICT (entitlement_id, request_id) DO NOTHING
+            "#,
+        )
+        .bind(entitlement_id)
+        .bind(request_id)
+        .bind(user_address)
+        .bind(cost)
+        .bind(&usage_event_id)
+        .bind(now)
+        .execute(&mut *conn)
+        .await?;
+
+        if recorded.rows_affected() == 0 {
+            return Ok(UsageCommit::Duplicate);
+        }
+    }
+
+    // The pool's single connection serializes this read and the update
+    let (quota, units, tier_type) = sqlx::query_as::<_, (Option<i64>, i64, String)>(
+        r#"
+        SELECT e.quota, e.units, t.tier_type
+        FROM entitlements e
+        JOIN pricing_tiers t ON t.tier_id = e.tier_id
+        WHERE e.entitlement_id = ?1 AND e.buyer = ?2
+        "#,
+    )
+    .bind(entitlement_id)
+    .bind(user_address)
+    .fetch_optional(&mut *conn)
+    .await?
+    .ok_or_else(|| InfrapassError::ValidationError("entitlement not found".into()))?;
+
+    let (quota, units) = match tier_type.as_str() {
+        // Subscriptions carry no counters
+        "subscription" => (quota, units),
+        "quota" => match quota.unwrap_or(0) {
+            q if q < cost => {
+                return Err(InfrapassError::ValidationError(
+                    "usage exceeds remaining quota".into(),
+                ));
+            }
+            q => (Some(q - cost), units),
+        },
+        _ if units < cost => {
+            return Err(InfrapassError::ValidationError(
+                "usage exceeds remaining units".into(),
+            ));
+        }
+        _ => (quota, units - cost),
+    };
+
+    sqlx::query("UPDATE entitlements SET quota = ?2, units = ?3 WHERE entitlement_id = ?1")
+        .bind(entitlement_id)
+        .bind(quota)
+        .bind(units)
+        .execute(&mut *conn)
+        .await?;
+
+    sqlx::query(
+        r#"
+        INSERT INTO usage_events (id, entitlement_id, user_address, amount, recorded_at)
+        VALUES (?1, ?2, ?3, ?4, ?5)
+        "#,
+    )
+    .bind(&usage_event_id)
+    .bind(entitlement_id)
+    .bind(user_address)
+    .bind(cost)
+    .bind(now)
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(UsageCommit::Recorded)
+}
+
+/// SQLite integers are signed 64-bit
+fn integer(value: u64) -> Result<i64> {
+    i64::try_from(value).map_err(|_| anyhow!("{} does not fit in a SQLite integer", value))
+}
+
+fn timestamp(ms: u64) -> Result<DateTime<Utc>> {
+    DateTime::<Utc>::from_timestamp_millis(ms as i64)
+        .ok_or_else(|| anyhow!("Invalid timestamp {}", ms))
+}
+
+#[derive(sqlx::FromRow)]
+struct ValidEntitlementRow {
+    entitlement_id: String,
+    tier_id: String,
+    expires_at: Option<DateTime<Utc>>,
+    quota: Option<i64>,
+    units: i64,
+    tier_type: String,
+    tier_name: String,
+    price: i64,
+    coin_type: String,
+}
+
+#[async_trait]
+impl Storage for SqliteStorage {
+    async fn get_checkpoint_cursor(&self, name: &str) -> Result<Option<u64>> {
+        let row: Option<(i64,)> =
+            sqlx::query_as("SELECT checkpoint_number FROM indexer_cursors WHERE name = ?1")
+                .bind(name)
+                .fetch_optional(&self.pool)
+                .await?;
+
+        Ok(row.map(|(checkpoint,)| checkpoint as u64))
+    }
+
+    async fn save_checkpoint_cursor(&self, name: &str, checkpoint: u64) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO indexer_cursors (name, checkpoint_number, updated_at)
+            VALUES (?1, ?2, ?3)
+            ON CONFLICT (name) DO UPDATE
+            SET checkpoint_number = excluded.checkpoint_number, updated_at = excluded.updated_at
+            WHERE indexer_cursors.checkpoint_number < excluded.checkpoint_number
+            "#,
+        )
+        .bind(name)
+        .bind(checkpoint as i64)
+        .bind(Utc::now())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn store_failed_event(
+        &self,
+        checkpoint: u64,
+        tx_digest: Option<&str>,
+        event_index: u64,
+        event_type: &str,
+        package_id: Option<&str>,
+        protocol_version: u64,
+        bcs: &[u8],
+        error: &str,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO failed_events
+            (checkpoint_number, transaction_digest, event_index, event_type, package_id, protocol_version, bcs, error, created_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+            "#,
+        )
+        .bind(checkpoint as i64)
+        .bind(tx_digest)
+        .bind(event_index as i64)
+        .bind(event_type)
+        .bind(package_id)
+        .bind(protocol_version as i64)
+        .bind(bcs)
+        .bind(error)
+        .bind(Utc::now())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Same rule as on Postgres, without tier replacements, which are set
+    /// through the Postgres-only API
+    async fn get_valid_entitlement_response(
+        &self,
+        user_address: &str,
+        service_id: &str,
+        cost: u64,
+        include_detail: bool,
+    ) -> Result<Option<ValidateResponse>, InfrapassError> {
+        let cutoff = Utc::now() - chrono::Duration::seconds(self.expiry_grace_secs);
+        let cost = integer(cost)?;
+
+        let row = sqlx::query_as::<_, ValidEntitlementRow>(
+            r#"
+            SELECT e.entitlement_id, e.tier_id, e.expires_at, e.quota, e.units,
+                   t.tier_type, t.tier_name, t.price, t.coin_type
+            FROM entitlements e
+            JOIN pricing_tiers t ON e.tier_id = t.tier_id
+            WHERE e.buyer = ?1
+              AND e.service_id = ?2
+              AND (
+                    (t.tier_type = 'subscription' AND (e.expires_at IS NULL OR e.expires_at > ?4))
+                    OR
+                    (t.tier_type = 'quota' AND e.expires_at > ?4 AND e.quota > ?3)
+                    OR
+                    (t.tier_type = 'usage_based' AND e.units > ?3)
+                  )
+            LIMIT 1
+            "#,
+        )
+        .bind(user_address)
+        .bind(service_id)
+        .bind(cost)
+        .bind(cutoff)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.map(|r| ValidateResponse {
+            entitlement_id: r.entitlement_id,
+            tier: r.tier_id,
+            replaced_tier: None,
+            quota: r.quota.map(|q| q as u64),
+            units: Some(r.units as u64),
+            tier_type: match r.tier_type.as_str() {
+                "subscription" => 0,
+                "quota" => 1,
+                "usage_based" => 2,
+                _ => 0,
+            },
+            expires_at: r.expires_at,
+            notify_provider: None,
+            tier_name: include_detail.then_some(r.tier_name),
+            price: include_detail.then_some(r.price as u64),
+            coin_type: include_detail.then_some(r.coin_type),
+        }))
+    }
+
+    async fn commit_usage(
+        &self,
+        entitlement_id: &str,
+        user_address: &str,
+        cost: Units,
+        request_id: Option<&str>,
+    ) -> Result<UsageCommit, InfrapassError> {
+        let mut tx = self.pool.begin().await?;
+        let commit = charge_usage(&mut tx, entitlement_id, user_address, cost, request_id).await?;
+        tx.commit().await?;
+
+        Ok(commit)
+    }
+
+    async fn commit_usage_batch(
+        &self,
+        records: &[RecordUsageRequest],
+    ) -> Result<Vec<Result<UsageCommit, InfrapassError>>, InfrapassError> {
+        let mut tx = self.pool.begin().await?;
+        let mut results = Vec::with_capacity(records.len());
+
+        for record in records {
+            let mut savepoint = tx.begin().await?;
+            let result = charge_usage(
+                &mut savepoint,
+                &record.entitlement_id,
+                &record.user_address,
+                Units::new(record.cost),
+                record.request_id.as_deref(),
+            )
+            .await;
+            match result {
+                Ok(commit) => {
+                    savepoint.commit().await?;
+                    results.push(Ok(commit));
+                }
+                Err(e @ InfrapassError::ValidationError(_)) => {
+                    savepoint.rollback().await?;
+                    results.push(Err(e));
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        tx.commit().await?;
+        Ok(results)
+    }
+
+    async fn service_provider(&self, service_id: &str) -> Result<Option<String>> {
+        let row: Option<(String,)> =
+            sqlx::query_as("SELECT provider_id FROM services WHERE service_id = ?1")
+                .bind(service_id)
+                .fetch_optional(&self.pool)
+                .await?;
+
+        Ok(row.map(|(provider_id,)| provider_id))
+    }
+
+    async fn entitlement_provider(&self, entitlement_id: &str) -> Result<Option<String>> {
+        let row: Option<(String,)> = sqlx::query_as(
+            r#"
+            SELECT s.provider_id
+            FROM entitlements e
+            JOIN services s ON s.service_id = e.service_id
+            WHERE e.entitlement_id = ?1
+            "#,
+        )
+        .bind(entitlement_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.map(|(provider_id,)| provider_id))
+    }
+}
@@ -0,0 +1,107 @@
+-- Schema of the SQLite backend: the catalog and entitlements the validator
+-- reads, recorded usage and the indexer's bookkeeping. Amounts are INTEGER
+-- (i64) and timestamps are RFC 3339 TEXT written by the backend, so they
+-- compare as strings.
+CREATE TABLE IF NOT EXISTS providers (
+    profile_id TEXT PRIMARY KEY,
+    provider_address TEXT NOT NULL,
+    metadata_uri TEXT NOT NULL,
+    is_active INTEGER NOT NULL DEFAULT 1,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS services (
+    service_id TEXT PRIMARY KEY,
+    provider_id TEXT NOT NULL,
+    service_type TEXT NOT NULL,
+    metadata_uri TEXT,
+    is_active INTEGER NOT NULL DEFAULT 1,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS pricing_tiers (
+    tier_id TEXT PRIMARY KEY,
+    service_id TEXT NOT NULL,
+    tier_name TEXT NOT NULL,
+    price INTEGER NOT NULL,
+    coin_type TEXT NOT NULL,
+    tier_type TEXT NOT NULL CHECK (tier_type IN ('subscription', 'quota', 'usage_based')),
+    duration_ms INTEGER,
+    quota_limit INTEGER,
+    is_active INTEGER NOT NULL DEFAULT 1,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS service_tiers (
+    service_id TEXT NOT NULL,
+    tier_id TEXT NOT NULL,
+    added_at TEXT NOT NULL,
+    PRIMARY KEY (service_id, tier_id)
+);
+
+CREATE TABLE IF NOT EXISTS entitlements (
+    entitlement_id TEXT PRIMARY KEY,
+    buyer TEXT NOT NULL,
+    service_id TEXT NOT NULL,
+    tier_id TEXT NOT NULL,
+    price_paid INTEGER NOT NULL,
+    expires_at TEXT,
+    quota INTEGER,
+    units INTEGER NOT NULL DEFAULT 0,
+    created_at TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_entitlements_buyer_service ON entitlements (buyer, service_id);
+
+CREATE TABLE IF NOT EXISTS usage_events (
+    id TEXT PRIMARY KEY,
+    entitlement_id TEXT NOT NULL,
+    user_address TEXT NOT NULL,
+    amount INTEGER NOT NULL,
+    recorded_at TEXT NOT NULL,
+    settled_at TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_usage_events_unsettled ON usage_events (entitlement_id) WHERE settled_at IS NULL;
+
+CREATE TABLE IF NOT EXISTS usage_records (
+    entitlement_id TEXT NOT NULL,
+    request_id TEXT NOT NULL,
+    user_address TEXT NOT NULL,
+    amount INTEGER NOT NULL,
+    usage_event_id TEXT NOT NULL,
+    recorded_at TEXT NOT NULL,
+    PRIMARY KEY (entitlement_id, request_id)
+);
+
+CREATE TABLE IF NOT EXISTS indexer_cursors (
+    name TEXT PRIMARY KEY,
+    checkpoint_number INTEGER NOT NULL,
+    updated_at TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS ingested_events (
+    transaction_digest TEXT NOT NULL,
+    event_index INTEGER NOT NULL,
+    checkpoint_number INTEGER NOT NULL,
+    PRIMARY KEY (transaction_digest, event_index)
+);
+
+-- Either `bcs` (failed to decode) or `payload` (failed to apply) is set
+CREATE TABLE IF NOT EXISTS failed_events (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    checkpoint_number INTEGER NOT NULL,
+    transaction_digest TEXT,
+    event_index INTEGER NOT NULL DEFAULT 0,
+    event_type TEXT NOT NULL,
+    package_id TEXT,
+    protocol_version INTEGER NOT NULL DEFAULT 1,
+    bcs BLOB,
+    payload TEXT,
+    error TEXT NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 1,
+    created_at TEXT NOT NULL
+);
@@ -0,0 +1,169 @@
+use anyhow::Result;
+use async_trait::async_trait;
+
+use crate::{
+    api_types::validator::{RecordUsageRequest, ValidateResponse},
+    db::{models::UsageCommit, repository::Repository},
+    types::amount::Units,
+    utils::error::InfrapassError,
+};
+
+/// Database behind the server, chosen by the scheme of `DATABASE_URL`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageBackend {
+    Postgres,
+    /// `sqlite:` URLs. Runs the indexer and the validator API only.
+    Sqlite,
+}
+
+impl StorageBackend {
+    pub fn from_url(database_url: &str) -> Result<Self> {
+        let scheme = database_url.split(':').next().unwrap_or_default();
+        match scheme {
+            "postgres" | "postgresql" => Ok(StorageBackend::Postgres),
+            "sqlite" => Ok(StorageBackend::Sqlite),
+            _ => anyhow::bail!(
+                "Unsupported DATABASE_URL scheme {}, expected postgres:// or sqlite:",
+                scheme
+            ),
+        }
+    }
+}
+
+/// What the event listener and the validator hot path need from a database.
+/// `Repository` implements it over the full Postgres schema; `SqliteStorage`
+/// over a single file, for providers who don't want to run Postgres.
+#[async_trait]
+pub trait Storage: Send + Sync {
+    async fn get_checkpoint_cursor(&self, name: &str) -> Result<Option<u64>>;
+
+    /// Only moves the cursor forward
+    async fn save_checkpoint_cursor(&self, name: &str, checkpoint: u64) -> Result<()>;
+
+    /// Dead-letters an event that failed to decode, keeping its BCS bytes
+    #[allow(clippy::too_many_arguments)]
+    async fn store_failed_event(
+        &self,
+        checkpoint: u64,
+        tx_digest: Option<&str>,
+        event_index: u64,
+        event_type: &str,
+        package_id: Option<&str>,
+        protocol_version: u64,
+        bcs: &[u8],
+        error: &str,
+    ) -> Result<()>;
+
+    /// The buyer's entitlement to a service that covers a request of `cost`
+    async fn get_valid_entitlement_response(
+        &self,
+        user_address: &str,
+        service_id: &str,
+        cost: u64,
+        include_detail: bool,
+    ) -> Result<Option<ValidateResponse>, InfrapassError>;
+
+    /// Charges `cost` to an entitlement and queues it for settlement, once
+    /// per `request_id`
+    async fn commit_usage(
+        &self,
+        entitlement_id: &str,
+        user_address: &str,
+        cost: Units,
+        request_id: Option<&str>,
+    ) -> Result<UsageCommit, InfrapassError>;
+
+    /// Charges records in order in one transaction. A record the entitlement
+    /// can't cover is skipped with its error; any other error fails the batch.
+    async fn commit_usage_batch(
+        &self,
+        records: &[RecordUsageRequest],
+    ) -> Result<Vec<Result<UsageCommit, InfrapassError>>, InfrapassError>;
+
+    /// The provider that owns a service, for checking provider API keys
+    async fn service_provider(&self, service_id: &str) -> Result<Option<String>>;
+
+    /// The provider whose service an entitlement is to
+    async fn entitlement_provider(&self, entitlement_id: &str) -> Result<Option<String>>;
+}
+
+#[async_trait]
+impl Storage for Repository {
+    async fn get_checkpoint_cursor(&self, name: &str) -> Result<Option<u64>> {
+        Repository::get_checkpoint_cursor(self, name).await
+    }
+
+    async fn save_checkpoint_cursor(&self, name: &str, checkpoint: u64) -> Result<()> {
+        Repository::save_checkpoint_cursor(self, name, checkpoint).await
+    }
+
+    async fn store_failed_event(
+        &self,
+        checkpoint: u64,
+        tx_digest: Option<&str>,
+        event_index: u64,
+        event_type: &str,
+        package_id: Option<&str>,
+        protocol_version: u64,
+        bcs: &[u8],
+        error: &str,
+    ) -> Result<()> {
+        Repository::store_failed_event(
+            self,
+            checkpoint,
+            tx_digest,
+            event_index,
+            event_type,
+            package_id,
+            protocol_version,
+            bcs,
+            error,
+        )
+        .await
+    }
+
+    async fn get_valid_entitlement_response(
+        &self,
+        user_address: &str,
+        service_id: &str,
+        cost: u64,
+        include_detail: bool,
+    ) -> Result<Option<ValidateResponse>, InfrapassError> {
+        Repository::get_valid_entitlement_response(
+            self,
+            user_address,
+            service_id,
+            cost,
+            include_detail,
+        )
+        .await
+    }
+
+    async fn commit_usage(
+        &self,
+        entitlement_id: &str,
+        user_address: &str,
+        cost: Units,
+        request_id: Option<&str>,
+    ) -> Result<UsageCommit, InfrapassError> {
+        Repository::commit_usage(self, entitlement_id, user_address, cost, request_id).await
+    }
+
+    async fn commit_usage_batch(
+        &self,
+        records: &[RecordUsageRequest],
+    ) -> Result<Vec<Result<UsageCommit, InfrapassError>>, InfrapassError> {
+        Repository::commit_usage_batch(self, records).await
+    }
+
+    async fn service_provider(&self, service_id: &str) -> Result<Option<String>> {
+        Ok(self.get_service(service_id).await?.map(|s| s.provider_id))
+    }
+
+    async fn entitlement_provider(&self, entitlement_id: &str) -> Result<Option<String>> {
+        Ok(self
+            .get_entitlement(entitlement_id)
+            .await?
+            .map(|e| e.provider_id))
+    }
+}
@@ -0,0 +1,55 @@
+use std::future::Future;
+
+use sha2::{Digest, Sha256};
+use sqlx::{PgConnection, postgres::PgPoolOptions};
+use uuid::Uuid;
+
+tokio::task_local! {
+    static TENANT: String;
+}
+
+/// Runs `f` bound to `tenant`: every connection it takes from a pool built
+/// with `bind_tenant` only sees that tenant's rows. Work spawned onto other
+/// tasks from inside `f` is not bound.
+pub async fn scope<F: Future>(tenant: String, f: F) -> F::Output {
+    TENANT.scope(tenant, f).await
+}
+
+/// The tenant the current task is bound to, if any
+pub fn current() -> Option<String> {
+    TENANT.try_with(|t| t.clone()).ok()
+}
+
+/// Makes every connection handed out by the pool carry the current task's
+/// tenant in `infrapass.tenant_id`, which the row-level security policies
+/// read. Both hooks run in the task that acquires the connection, so a new
+/// connection and a reused one are bound the same way; unbound tasks reset
+/// the setting and see every row.
+pub fn bind_tenant(options: PgPoolOptions) -> PgPoolOptions {
+    options
+        .after_connect(|conn, _meta| Box::pin(async move { set_session_tenant(conn).await }))
+        .before_acquire(|conn, _meta| {
+            Box::pin(async move {
+                set_session_tenant(conn).await?;
+                Ok(true)
+            })
+        })
+}
+
+async fn set_session_tenant(conn: &mut PgConnection) -> Result<(), sqlx::Error> {
+    sqlx::query("SELECT set_config('infrapass.tenant_id', $1, false)")
+        .bind(current().unwrap_or_default())
+        .execute(conn)
+        .await?;
+    Ok(())
+}
+
+/// A new tenant or provider API key. Only its hash is stored, so it is shown
+/// once.
+pub fn generate_api_key() -> String {
+    format!("ipk_{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple())
+}
+
+pub fn hash_api_key(key: &str) -> String {
+    hex::encode(Sha256::digest(key.as_bytes()))
+}
@@ -0,0 +1,83 @@
+use anyhow::Result;
+use sqlx::types::Json;
+use tokio::sync::mpsc;
+use tracing::{info, warn};
+
+use crate::{
+    db::repository::Repository,
+    events::{listener::decode_event, types::EventPayload},
+};
+
+#[derive(Debug, Default)]
+pub struct ReplaySummary {
+    pub replayed: usize,
+    pub still_failing: usize,
+    pub unhandled: usize,
+}
+
+/// Decodes dead-lettered events again and feeds the ones that now parse into
+/// the worker pipeline, along with events the worker gave up on. Events that
+/// still fail to decode stay in `failed_events`; events the worker fails on
+/// again are dead-lettered as a new row.
+pub async fn replay_failed_events(
+    repo: &Repository,
+    event_tx: &mpsc::Sender<EventPayload>,
+    limit: i64,
+) -> Result<ReplaySummary> {
+    let mut summary = ReplaySummary::default();
+
+    for failed in repo.list_pending_failed_events(limit).await? {
+        let decoded = match &failed.payload {
+            Some(Json(payload)) => Ok(Some(payload.clone())),
+            None => decode_event(
+                &failed.event_type,
+                failed.bcs.as_deref().unwrap_or_default(),
+            )
+            .map(|event| {
+                event.map(|event| EventPayload {
+                    event,
+                    tx_digest: failed.transaction_digest.clone(),
+                    event_index: failed.event_index as u64,
+                    checkpoint: failed.checkpoint_number as u64,
+                    protocol_version: failed.protocol_version as u64,
+                    package_id: failed.package_id.clone(),
+                })
+            }),
+        };
+
+        match decoded {
+            Ok(Some(payload)) => {
+                if event_tx.send(payload).await.is_err() {
+                    return Err(anyhow::anyhow!("Event receiver dropped"));
+                }
+
+                repo.mark_failed_event_replayed(failed.id).await?;
+                info!(
+                    id = failed.id,
+                    event_type = %failed.event_type,
+                    checkpoint = failed.checkpoint_number,
+                    "Replayed dead-lettered event"
+                );
+                summary.replayed += 1;
+            }
+            Ok(None) => {
+                repo.record_failed_event_attempt(failed.id, "unhandled event type")
+                    .await?;
+                summary.unhandled += 1;
+            }
+            Err(e) => {
+                warn!(
+                    id = failed.id,
+                    event_type = %failed.event_type,
+                    error = %e,
+                    "Dead-lettered event still fails to decode"
+                );
+                repo.record_failed_event_attempt(failed.id, &e.to_string())
+                    .await?;
+                summary.still_failing += 1;
+            }
+        }
+    }
+
+    Ok(summary)
+}
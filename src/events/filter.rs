@@ -0,0 +1,91 @@
+use std::collections::HashSet;
+
+use anyhow::{Result, anyhow};
+
+/// Every event the listener can decode, as `module::Name`
+pub const KNOWN_EVENTS: &[&str] = &[
+    "registry::ProviderRegistered",
+    "registry::ServiceCreated",
+    "registry::ServiceUpdated",
+    "registry::ServiceDeactivated",
+    "registry::ServiceReactivated",
+    "registry::TierAddedToService",
+    "registry::TierRemovedFromService",
+    "pricing::TierCreated",
+    "pricing::TierPriceUpdated",
+    "pricing::TierDeactivated",
+    "pricing::TierReactivated",
+    "payments::EntitlementPurchased",
+    "payments::EntitlementToppedUp",
+    "payments::QuotaConsumed",
+];
+
+/// Which package events the listener decodes and hands to the worker. The
+/// rest are skipped before decoding, so they cost no BCS work or DB writes.
+#[derive(Debug, Clone, Default)]
+pub struct EventFilter {
+    /// `None` lets every event through
+    allowed: Option<HashSet<&'static str>>,
+}
+
+impl EventFilter {
+    pub fn all() -> Self {
+        Self::default()
+    }
+
+    /// Parses a comma separated list of modules (`payments`) and events
+    /// (`pricing::TierPriceUpdated` or just `TierPriceUpdated`)
+    pub fn parse(raw: &str) -> Result<Self> {
+        let mut allowed = HashSet::new();
+
+        for entry in raw.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let matched: Vec<&'static str> = KNOWN_EVENTS
+                .iter()
+                .copied()
+                .filter(|label| {
+                    let (module, name) = label.split_once("::").unwrap();
+                    *label == entry || module == entry || name == entry
+                })
+                .collect();
+
+            if matched.is_empty() {
+                return Err(anyhow!(
+                    "Unknown event or module {}; expected one of {}",
+                    entry,
+                    KNOWN_EVENTS.join(", ")
+                ));
+            }
+            allowed.extend(matched);
+        }
+
+        if allowed.is_empty() {
+            return Err(anyhow!("No event types given"));
+        }
+
+        Ok(Self {
+            allowed: Some(allowed),
+        })
+    }
+
+    /// Checks a full `pkg::module::Name` event type
+    pub fn allows(&self, event_type: &str) -> bool {
+        self.allows_label(event_label(event_type))
+    }
+
+    /// Checks a `module::Name` label, as returned by `ProtocolEvent::label`
+    pub fn allows_label(&self, label: &str) -> bool {
+        match &self.allowed {
+            None => true,
+            Some(allowed) => allowed.contains(label),
+        }
+    }
+}
+
+/// `module::Name` part of a full event type, or the type itself if it has no
+/// package prefix
+pub fn event_label(event_type: &str) -> &str {
+    match event_type.split_once("::") {
+        Some((_, label)) => label,
+        None => event_type,
+    }
+}
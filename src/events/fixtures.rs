@@ -0,0 +1,217 @@
+use std::{collections::BTreeMap, fmt, path::Path, sync::Arc};
+
+use anyhow::{Context, Result, anyhow, bail};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    alerting::manager::AlertManager,
+    db::repository::Repository,
+    events::{
+        listener::decode_event,
+        types::{EventPayload, ProtocolEvent},
+        worker::EventWorker,
+    },
+};
+
+/// Tables a fixture may set expectations on, with the order their rows are
+/// compared in
+pub const PROJECTION_TABLES: &[(&str, &str)] = &[
+    ("providers", "profile_id"),
+    ("services", "service_id"),
+    ("pricing_tiers", "tier_id"),
+    ("service_tiers", "service_id, tier_id"),
+    ("entitlements", "entitlement_id"),
+    ("tier_replacements", "tier_id"),
+    ("tier_history", "tier_id, checkpoint_number, id"),
+    ("blockchain_events", "checkpoint_number, id"),
+    ("pubsub_outbox", "id"),
+];
+
+/// A recorded run of the event pipeline: the events as they came off the
+/// chain and the projection they must leave behind.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventFixture {
+    #[serde(default)]
+    pub description: Option<String>,
+    pub events: Vec<FixtureEvent>,
+    /// Rows expected in each of `PROJECTION_TABLES`, in that table's order.
+    /// A table that is listed must hold exactly as many rows; each row is
+    /// compared only on the columns it names.
+    pub expect: BTreeMap<String, Vec<Value>>,
+}
+
+/// One event, either as raw BCS (captured from a checkpoint, and decoded
+/// through `decode_event`) or already decoded
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FixtureEvent {
+    pub checkpoint: u64,
+    #[serde(default)]
+    pub tx_digest: Option<String>,
+    #[serde(default)]
+    pub event_index: u64,
+    /// `pkg::module::Name`, required with `bcs`
+    #[serde(default)]
+    pub event_type: Option<String>,
+    /// Hex encoded event contents
+    #[serde(default)]
+    pub bcs: Option<String>,
+    #[serde(default)]
+    pub event: Option<ProtocolEvent>,
+}
+
+impl FixtureEvent {
+    pub fn payload(&self) -> Result<EventPayload> {
+        let event = match (&self.event, &self.bcs, &self.event_type) {
+            (Some(event), None, _) => event.clone(),
+            (None, Some(bcs), Some(event_type)) => {
+                let bytes = hex::decode(bcs.trim_start_matches("0x"))
+                    .with_context(|| format!("invalid hex for {}", event_type))?;
+                decode_event(event_type, &bytes)?
+                    .ok_or_else(|| anyhow!("{} is not an indexed event", event_type))?
+            }
+            _ => bail!(
+                "event at checkpoint {} needs either `event`, or `event_type` and `bcs`",
+                self.checkpoint
+            ),
+        };
+
+        Ok(EventPayload {
+            event,
+            tx_digest: self.tx_digest.clone(),
+            event_index: self.event_index,
+            checkpoint: self.checkpoint,
+            protocol_version: 0,
+            package_id: None,
+        })
+    }
+}
+
+impl EventFixture {
+    pub fn load(path: &Path) -> Result<Self> {
+        let raw = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        let fixture: Self = serde_json::from_str(&raw)
+            .with_context(|| format!("failed to parse {}", path.display()))?;
+
+        for table in fixture.expect.keys() {
+            if order_for(table).is_none() {
+                bail!(
+                    "{}: no expectations can be set on {}",
+                    path.display(),
+                    table
+                );
+            }
+        }
+
+        Ok(fixture)
+    }
+}
+
+/// A difference between a fixture's expected rows and the projection
+#[derive(Debug, Clone)]
+pub struct FixtureMismatch {
+    pub table: String,
+    pub detail: String,
+}
+
+impl fmt::Display for FixtureMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.table, self.detail)
+    }
+}
+
+/// Empties the indexed tables, feeds the fixture's events through a single
+/// shard worker in order and compares the projection to `expect`. Nothing
+/// is announced: the worker runs without notifiers or sinks. Only run this
+/// against a scratch database.
+pub async fn run_fixture(
+    repo: Arc<Repository>,
+    alerts: Arc<AlertManager>,
+    fixture: &EventFixture,
+) -> Result<Vec<FixtureMismatch>> {
+    let payloads = fixture
+        .events
+        .iter()
+        .map(FixtureEvent::payload)
+        .collect::<Result<Vec<_>>>()?;
+
+    repo.reset_indexed_state().await?;
+
+    let (tx, rx) = mpsc::channel::<EventPayload>(payloads.len().max(1));
+    let worker = EventWorker::new(repo.clone(), rx, alerts);
+    let worker_handle = tokio::spawn(worker.run(CancellationToken::new()));
+    for payload in payloads {
+        if tx.send(payload).await.is_err() {
+            bail!("Event worker stopped");
+        }
+    }
+    drop(tx);
+    worker_handle.await??;
+
+    let mut mismatches = vec![];
+    for (table, expected) in &fixture.expect {
+        let order_by = order_for(table).ok_or_else(|| anyhow!("unknown table {}", table))?;
+        let actual = repo.table_rows(table, order_by).await?;
+        compare_rows(table, expected, &actual, &mut mismatches);
+    }
+
+    Ok(mismatches)
+}
+
+fn order_for(table: &str) -> Option<&'static str> {
+    PROJECTION_TABLES
+        .iter()
+        .find(|(name, _)| *name == table)
+        .map(|(_, order_by)| *order_by)
+}
+
+fn compare_rows(
+    table: &str,
+    expected: &[Value],
+    actual: &[Value],
+    mismatches: &mut Vec<FixtureMismatch>,
+) {
+    let mut mismatch = |detail: String| {
+        mismatches.push(FixtureMismatch {
+            table: table.to_string(),
+            detail,
+        })
+    };
+
+    if expected.len() != actual.len() {
+        mismatch(format!(
+            "expected {} row(s), found {}",
+            expected.len(),
+            actual.len()
+        ));
+    }
+
+    for (i, (want, got)) in expected.iter().zip(actual).enumerate() {
+        let Some(want) = want.as_object() else {
+            mismatch(format!("expected row {} is not an object", i));
+            continue;
+        };
+
+        for (column, value) in want {
+            let found = got.get(column).unwrap_or(&Value::Null);
+            if !values_match(value, found) {
+                mismatch(format!(
+                    "row {} column {}: expected {}, found {}",
+                    i, column, value, found
+                ));
+            }
+        }
+    }
+}
+
+/// Numbers are compared by value, since `NUMERIC` columns come back in
+/// whatever form Postgres renders them
+fn values_match(expected: &Value, actual: &Value) -> bool {
+    match (expected, actual) {
+        (Value::Number(a), Value::Number(b)) => a.as_f64() == b.as_f64(),
+        _ => expected == actual,
+    }
+}
@@ -0,0 +1,67 @@
+use std::time::Duration;
+
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use rdkafka::{
+    ClientConfig,
+    message::{Header, OwnedHeaders},
+    producer::{FutureProducer, FutureRecord},
+};
+
+use crate::events::sink::{EventSink, SinkRecord};
+
+/// How long a record may wait in the producer's queue when it is full
+const QUEUE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Publishes every indexed event as JSON to one Kafka topic, keyed by the
+/// object the event is about so each object's events land on one partition
+/// in order
+pub struct KafkaSink {
+    producer: FutureProducer,
+    topic: String,
+}
+
+impl KafkaSink {
+    /// `brokers` is a comma separated `host:port` list
+    pub fn new(brokers: &str, topic: impl Into<String>) -> Result<Self> {
+        let producer = ClientConfig::new()
+            .set("bootstrap.servers", brokers)
+            .set("enable.idempotence", "true")
+            .set("compression.type", "lz4")
+            .set("message.timeout.ms", "30000")
+            .create()?;
+
+        Ok(Self {
+            producer,
+            topic: topic.into(),
+        })
+    }
+}
+
+#[async_trait]
+impl EventSink for KafkaSink {
+    fn name(&self) -> &'static str {
+        "kafka"
+    }
+
+    async fn publish(&self, record: &SinkRecord<'_>) -> Result<()> {
+        let key = record.subject_id();
+        let payload = serde_json::to_vec(record)?;
+
+        self.producer
+            .send(
+                FutureRecord::to(&self.topic)
+                    .key(&key)
+                    .payload(&payload)
+                    .headers(OwnedHeaders::new().insert(Header {
+                        key: "event_type",
+                        value: Some(record.event_type),
+                    })),
+                QUEUE_TIMEOUT,
+            )
+            .await
+            .map_err(|(e, _)| anyhow!("failed to publish to {}: {}", self.topic, e))?;
+
+        Ok(())
+    }
+}
@@ -1,40 +1,155 @@
-use std::{sync::Arc, time::Duration};
+use std::{fmt, str::FromStr, sync::Arc, time::Duration};
 
 use crate::{
+    alerting::{manager::AlertManager, types::Alert},
+    db::storage::Storage,
     events::{
-        metrics::EventMetrics,
+        filter::{EventFilter, event_label},
+        metrics::{EventMetrics, INDEXER_METRICS},
+        packages::WatchedPackage,
         types::{EventPayload, ProtocolEvent, ProviderRegistered, ServiceCreated},
     },
-    utils::constants::PACKAGE_ID,
 };
 use anyhow::Result;
-use futures::StreamExt;
+use futures::{Stream, StreamExt};
+use prost::Message;
 use prost_types::{FieldMask, Value as ProstValue, value::Kind};
 use serde_json::Value as JsonValue;
 use sui_grpc::{
     Client,
     proto::sui::rpc::v2::{
-        Checkpoint, Event, SubscribeCheckpointsRequest,
+        Checkpoint, Event, SubscribeCheckpointsRequest, SubscribeCheckpointsResponse,
         subscription_service_client::SubscriptionServiceClient,
     },
 };
 use sui_json_rpc_types::CheckpointId;
 use sui_sdk::SuiClient;
-use sui_types::base_types::ObjectID;
+use sui_types::{base_types::ObjectID, digests::TransactionDigest};
 use tokio::{
     sync::{RwLock, mpsc},
     time::Instant,
 };
-use tonic::transport::Channel;
+use tokio_util::sync::CancellationToken;
+use tonic::{Streaming, transport::Channel};
 use tracing::{error, info, warn};
 
+/// Row name of the listener's cursor in `indexer_cursors`
+const CURSOR_NAME: &str = "event_listener";
+
+/// Transactions fetched per read API call while filling a gap
+const GAP_FETCH_CHUNK: usize = 50;
+
+/// Checkpoints between backfill progress logs
+const BACKFILL_PROGRESS_EVERY: u64 = 1000;
+
+/// Checkpoints decoded ahead of the one being committed
+pub const DEFAULT_PIPELINE_DEPTH: usize = 8;
+
+/// How often the polling source asks for the latest checkpoint
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Most checkpoints the polling source reads before checking the tip again
+const POLL_MAX_BATCH: u64 = 100;
+
+/// Checkpoint fields the subscription streams. The subscription service has
+/// no server-side filter, so every checkpoint still arrives; this keeps each
+/// one down to what `decode_checkpoint` reads. Transaction bodies, effects,
+/// signatures and object sets are never used by the indexer, so they are left
+/// out rather than fetched later.
+const STREAM_READ_MASK: &[&str] = &[
+    "transactions.digest",
+    "transactions.events.events.package_id",
+    "transactions.events.events.event_type",
+    "transactions.events.events.contents.value",
+];
+
+type CheckpointStream = Streaming<SubscribeCheckpointsResponse>;
+
+/// Where the listener reads checkpoints from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckpointSource {
+    /// gRPC subscription, falling back to polling if it cannot connect
+    Auto,
+    /// gRPC subscription only
+    Grpc,
+    /// JSON-RPC polling, for full nodes without the subscription service
+    Polling,
+}
+
+impl fmt::Display for CheckpointSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            CheckpointSource::Auto => "auto",
+            CheckpointSource::Grpc => "grpc",
+            CheckpointSource::Polling => "polling",
+        })
+    }
+}
+
+impl FromStr for CheckpointSource {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "auto" => Ok(CheckpointSource::Auto),
+            "grpc" => Ok(CheckpointSource::Grpc),
+            "polling" | "rpc" => Ok(CheckpointSource::Polling),
+            other => Err(anyhow::anyhow!(
+                "Unknown checkpoint source '{}', expected auto, grpc or polling",
+                other
+            )),
+        }
+    }
+}
+
+/// Package events of one checkpoint, decoded and waiting to be committed
+struct DecodedCheckpoint {
+    sequence: u64,
+    events: Vec<EventPayload>,
+    failures: Vec<FailedDecode>,
+    /// Labels of events the filter skipped
+    skipped: Vec<String>,
+}
+
+impl DecodedCheckpoint {
+    fn new(sequence: u64) -> Self {
+        Self {
+            sequence,
+            events: vec![],
+            failures: vec![],
+            skipped: vec![],
+        }
+    }
+}
+
+/// An event that failed to decode, bound for `failed_events`
+struct FailedDecode {
+    tx_digest: Option<String>,
+    event_index: u64,
+    event_type: String,
+    package_id: Option<String>,
+    protocol_version: u64,
+    bcs: Vec<u8>,
+    error: anyhow::Error,
+}
+
 #[derive(Clone)]
 pub struct EventListener {
     pub sui_client: Arc<SuiClient>,
     pub client: Client,
-    pub package_id: String,
+    pub packages: Vec<WatchedPackage>,
     pub event_tx: mpsc::Sender<EventPayload>,
     metrics: Arc<RwLock<EventMetrics>>,
+    alerts: Arc<AlertManager>,
+    repo: Arc<dyn Storage>,
+    /// Last checkpoint whose events were handed to the worker
+    last_cursor: Option<u64>,
+    pipeline_depth: usize,
+    filter: EventFilter,
+    source: CheckpointSource,
+    poll_interval: Duration,
+    /// Extra gRPC endpoints streamed alongside `client`
+    mirrors: Vec<Client>,
 }
 
 impl EventListener {
@@ -42,36 +157,128 @@ impl EventListener {
         sui_client: Arc<SuiClient>,
         grpc_url: &str,
         event_tx: mpsc::Sender<EventPayload>,
+        alerts: Arc<AlertManager>,
+        repo: Arc<dyn Storage>,
     ) -> Result<Self> {
         let client = Client::new(grpc_url.to_string())?;
+        let last_cursor = repo.get_checkpoint_cursor(CURSOR_NAME).await?;
+
+        if let Some(cursor) = last_cursor {
+            info!("Resuming event listener from checkpoint {}", cursor);
+        }
 
         Ok(Self {
             client,
             sui_client,
-            package_id: PACKAGE_ID.to_string(),
+            packages: vec![WatchedPackage::original()],
             event_tx,
             metrics: Arc::new(RwLock::new(EventMetrics::default())),
+            alerts,
+            repo,
+            last_cursor,
+            pipeline_depth: DEFAULT_PIPELINE_DEPTH,
+            filter: EventFilter::all(),
+            source: CheckpointSource::Auto,
+            poll_interval: DEFAULT_POLL_INTERVAL,
+            mirrors: vec![],
         })
     }
 
-    pub async fn run(mut self) -> Result<()> {
-        info!(
-            "Starting checkpoint subscription for package: {}",
-            self.package_id
-        );
+    /// Connection state and progress of this listener and its clones, for
+    /// readiness checks
+    pub fn health(&self) -> Arc<RwLock<EventMetrics>> {
+        self.metrics.clone()
+    }
+
+    /// Index events from every listed package version instead of only the
+    /// original `PACKAGE_ID`
+    pub fn with_packages(mut self, packages: Vec<WatchedPackage>) -> Self {
+        self.packages = packages;
+        self
+    }
+
+    /// How many checkpoints are decoded concurrently while events are still
+    /// committed in checkpoint order
+    pub fn with_pipeline_depth(mut self, depth: usize) -> Self {
+        self.pipeline_depth = depth.max(1);
+        self
+    }
+
+    /// Only decode and forward the events the filter allows
+    pub fn with_event_filter(mut self, filter: EventFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    pub fn with_checkpoint_source(mut self, source: CheckpointSource) -> Self {
+        self.source = source;
+        self
+    }
+
+    pub fn with_poll_interval(mut self, interval: Duration) -> Self {
+        self.poll_interval = interval;
+        self
+    }
+
+    /// Streams these endpoints too, committing each checkpoint from whichever
+    /// source delivers it first, so one node going down doesn't pause indexing
+    pub fn with_additional_endpoints(mut self, grpc_urls: &[String]) -> Result<Self> {
+        self.mirrors = grpc_urls
+            .iter()
+            .map(|url| Client::new(url.clone()))
+            .collect::<Result<_, _>>()?;
+        Ok(self)
+    }
+
+    /// Protocol version of a watched package, `None` for foreign packages
+    fn protocol_version(&self, package_id: &ObjectID) -> Option<u64> {
+        protocol_version(&self.packages, package_id)
+    }
+
+    /// Streams checkpoints until `shutdown` is cancelled. Shutdown is only
+    /// observed between checkpoints, so every event of the last checkpoint is
+    /// handed to the worker and its cursor saved before returning. Dropping
+    /// the listener then closes the channel, which lets the worker drain.
+    pub async fn run(mut self, shutdown: CancellationToken) -> Result<()> {
+        for package in &self.packages {
+            info!(
+                "Starting checkpoint {} for package: {} (v{})",
+                self.source, package.package_id, package.protocol_version
+            );
+        }
 
         let metrics_clone = self.metrics.clone();
+        let alerts = self.alerts.clone();
         tokio::spawn(async move {
-            Self::health_monitor(metrics_clone).await;
+            Self::health_monitor(metrics_clone, alerts).await;
         });
 
         loop {
             {
                 let mut metrics = self.metrics.write().await;
-                metrics.connection_healthy = false;
+                metrics.set_connection_healthy(false);
             }
 
-            match self.subscribe_and_process().await {
+            let result = match self.source {
+                CheckpointSource::Polling => self.poll_and_process(&shutdown).await,
+                _ => match self.open_subscriptions().await {
+                    Ok(streams) => self.process_subscriptions(streams, &shutdown).await,
+                    Err(e) if self.source == CheckpointSource::Auto => {
+                        // Stays on polling until restart; a node without the
+                        // subscription service won't grow one
+                        warn!(
+                            "gRPC checkpoint subscription unavailable ({}), falling back to JSON-RPC polling",
+                            e
+                        );
+                        self.source = CheckpointSource::Polling;
+                        continue;
+                    }
+                    Err(e) => Err(e),
+                },
+            };
+
+            match result {
+                Ok(_) if shutdown.is_cancelled() => break,
                 Ok(_) => {
                     warn!("Checkpoint stream ended normally");
                 }
@@ -81,173 +288,612 @@ impl EventListener {
             }
 
             warn!("Reconnecting in 5s...");
-            tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
+            tokio::select! {
+                _ = shutdown.cancelled() => break,
+                _ = tokio::time::sleep(tokio::time::Duration::from_secs(5)) => {}
+            }
         }
+
+        info!(
+            cursor = ?self.last_cursor,
+            "Event listener stopped, final cursor persisted"
+        );
+        Ok(())
     }
 
-    pub async fn subscribe_and_process(&mut self) -> Result<()> {
-        info!("Connecting to: {}", self.client.uri());
+    pub async fn subscribe_and_process(&mut self, shutdown: &CancellationToken) -> Result<()> {
+        let stream = self.open_subscription().await?;
+        self.process_subscription(stream, shutdown).await
+    }
 
-        let tls_config = tonic::transport::ClientTlsConfig::new().with_enabled_roots();
+    async fn open_subscription(&self) -> Result<CheckpointStream> {
+        subscribe(&self.client.uri().to_string()).await
+    }
 
-        let channel = Channel::from_shared(self.client.uri().to_string())?
-            .tls_config(tls_config)?
-            .connect()
-            .await?;
+    /// Opens a subscription on every endpoint. Endpoints that can't be
+    /// reached yet are retried by their source task; it's an error only when
+    /// none of them can.
+    async fn open_subscriptions(&self) -> Result<Vec<Option<CheckpointStream>>> {
+        if self.mirrors.is_empty() {
+            return Ok(vec![Some(self.open_subscription().await?)]);
+        }
 
-        let mut client = SubscriptionServiceClient::new(channel);
+        let mut streams = vec![];
+        for uri in self.endpoints() {
+            match subscribe(&uri).await {
+                Ok(stream) => streams.push(Some(stream)),
+                Err(e) => {
+                    warn!(source = %uri, "Checkpoint source unavailable: {}", e);
+                    INDEXER_METRICS
+                        .source_connected
+                        .with_label_values(&[&uri])
+                        .set(0);
+                    streams.push(None);
+                }
+            }
+        }
 
-        let mut req_msg = SubscribeCheckpointsRequest::default();
-        req_msg.read_mask = Some(FieldMask {
-            paths: vec![
-                "events".to_string(),
-                "effects".to_string(),
-                "transactions".to_string(),
-            ],
-        });
-        let request = tonic::Request::new(req_msg);
+        if streams.iter().all(Option::is_none) {
+            return Err(anyhow::anyhow!(
+                "No gRPC checkpoint source could be reached"
+            ));
+        }
+        Ok(streams)
+    }
+
+    fn endpoints(&self) -> Vec<String> {
+        std::iter::once(&self.client)
+            .chain(&self.mirrors)
+            .map(|client| client.uri().to_string())
+            .collect()
+    }
+
+    async fn process_subscriptions(
+        &mut self,
+        mut streams: Vec<Option<CheckpointStream>>,
+        shutdown: &CancellationToken,
+    ) -> Result<()> {
+        if streams.len() == 1 {
+            if let Some(stream) = streams.pop().flatten() {
+                return self.process_subscription(stream, shutdown).await;
+            }
+        }
+
+        let endpoints = self.endpoints();
+        let (tx, mut rx) = mpsc::channel(self.pipeline_depth * endpoints.len());
+        // Stops the source tasks however this returns
+        let sources_shutdown = shutdown.child_token();
+        let _stop_sources = sources_shutdown.clone().drop_guard();
+
+        let packages = Arc::new(self.packages.clone());
+        let filter = Arc::new(self.filter.clone());
+        for (source, (uri, stream)) in endpoints.iter().zip(streams).enumerate() {
+            let task = SourceTask {
+                source,
+                uri: uri.clone(),
+                packages: packages.clone(),
+                filter: filter.clone(),
+                depth: self.pipeline_depth,
+                tx: tx.clone(),
+                shutdown: sources_shutdown.clone(),
+            };
+            tokio::spawn(task.run(stream));
+        }
+        drop(tx);
+
+        info!("Streaming checkpoints from {} sources", endpoints.len());
+        let mut heads = SourceHeads::new(endpoints);
+
+        {
+            let mut metrics = self.metrics.write().await;
+            metrics.set_connection_healthy(true);
+        }
+
+        loop {
+            let item = tokio::select! {
+                biased;
+                _ = shutdown.cancelled() => {
+                    info!("Shutdown requested, closing checkpoint streams");
+                    break;
+                }
+                item = rx.recv() => match item {
+                    Some(item) => item,
+                    None => break,
+                },
+            };
+
+            heads.record(item.source, item.cursor);
+
+            // Another source got here first
+            if self.last_cursor.is_some_and(|last| item.cursor <= last) {
+                INDEXER_METRICS
+                    .source_duplicates
+                    .with_label_values(&[heads.label(item.source)])
+                    .inc();
+                continue;
+            }
+
+            INDEXER_METRICS
+                .source_first
+                .with_label_values(&[heads.label(item.source)])
+                .inc();
+
+            self.fill_gap(item.cursor, shutdown).await?;
+            if shutdown.is_cancelled() {
+                break;
+            }
+
+            {
+                let mut metrics = self.metrics.write().await;
+                metrics.record_checkpoint(item.cursor);
+            }
+            if let Some(decoded) = item.decoded {
+                if !self.commit_decoded(decoded).await {
+                    return Err(anyhow::anyhow!("Event receiver dropped"));
+                }
+            }
+            self.commit_cursor(item.cursor).await?;
+        }
 
-        let response = client.subscribe_checkpoints(request).await?;
-        let mut stream = response.into_inner();
+        Ok(())
+    }
+
+    async fn process_subscription(
+        &mut self,
+        stream: CheckpointStream,
+        shutdown: &CancellationToken,
+    ) -> Result<()> {
+        let mut stream = std::pin::pin!(decode_stream(
+            stream,
+            Arc::new(self.packages.clone()),
+            Arc::new(self.filter.clone()),
+            self.pipeline_depth,
+        ));
 
         info!("Checkpoint stream connected");
 
         {
             let mut metrics = self.metrics.write().await;
-            metrics.connection_healthy = true;
+            metrics.set_connection_healthy(true);
         }
 
-        while let Some(result) = stream.next().await {
-            match result {
-                Ok(checkpoint_response) => {
-                    if checkpoint_response.cursor.is_some() {
-                        let mut metrics = self.metrics.write().await;
-                        metrics.last_checkpoint_received = checkpoint_response.cursor;
-                        metrics.last_checkpoint_received_at = Some(Instant::now());
-                        metrics.total_checkpoints_processed += 1;
-                    };
-                    if let Some(checkpoint) = checkpoint_response.checkpoint {
-                        self.process_checkpoint(&checkpoint, checkpoint_response.cursor)
-                            .await;
-                    }
+        loop {
+            let result = tokio::select! {
+                biased;
+                _ = shutdown.cancelled() => {
+                    info!("Shutdown requested, closing checkpoint stream");
+                    break;
                 }
+                next = stream.next() => match next {
+                    Some(result) => result,
+                    None => break,
+                },
+            };
+
+            let (cursor, decoded) = match result {
+                Ok(item) => item,
                 Err(e) => {
                     error!("Checkpoint error: {}", e);
-                    return Err(e.into());
+                    return Err(e);
                 }
+            };
+
+            if let Some(cursor) = cursor {
+                if self.last_cursor.is_some_and(|last| cursor <= last) {
+                    continue;
+                }
+                self.fill_gap(cursor, shutdown).await?;
+                if shutdown.is_cancelled() {
+                    break;
+                }
+
+                let mut metrics = self.metrics.write().await;
+                metrics.record_checkpoint(cursor);
+            }
+            if let Some(decoded) = decoded {
+                if !self.commit_decoded(decoded).await {
+                    return Err(anyhow::anyhow!("Event receiver dropped"));
+                }
+            }
+            if let Some(cursor) = cursor {
+                self.commit_cursor(cursor).await?;
             }
         }
 
         Ok(())
     }
 
+    /// Tails the chain over JSON-RPC: asks for the latest checkpoint every
+    /// `poll_interval` and reads everything after the cursor through the read
+    /// API. Without a cursor it starts at the tip, like the gRPC stream.
+    pub async fn poll_and_process(&mut self, shutdown: &CancellationToken) -> Result<()> {
+        info!(
+            "Polling checkpoints over JSON-RPC every {}ms",
+            self.poll_interval.as_millis()
+        );
+
+        loop {
+            let latest = self
+                .sui_client
+                .read_api()
+                .get_latest_checkpoint_sequence_number()
+                .await?;
+
+            {
+                let mut metrics = self.metrics.write().await;
+                metrics.set_connection_healthy(true);
+            }
+
+            let from = match self.last_cursor {
+                Some(last) => last + 1,
+                None => latest,
+            };
+
+            if from <= latest {
+                let to = latest.min(from + POLL_MAX_BATCH - 1);
+                self.poll_range(from, to, shutdown).await?;
+            }
+
+            if shutdown.is_cancelled() {
+                info!("Shutdown requested, stopping checkpoint polling");
+                return Ok(());
+            }
+
+            // Keep reading without pausing while behind the tip
+            if self.last_cursor.is_some_and(|last| last < latest) {
+                continue;
+            }
+
+            tokio::select! {
+                _ = shutdown.cancelled() => {
+                    info!("Shutdown requested, stopping checkpoint polling");
+                    return Ok(());
+                }
+                _ = tokio::time::sleep(self.poll_interval) => {}
+            }
+        }
+    }
+
+    /// Reads `from..=to` and commits each checkpoint and its cursor in order.
+    /// Stops early on shutdown; the cursor always reflects what was committed,
+    /// even when a read fails partway.
+    async fn poll_range(&mut self, from: u64, to: u64, shutdown: &CancellationToken) -> Result<()> {
+        let mut committed = None;
+        let mut outcome = Ok(());
+        {
+            let mut decoded = std::pin::pin!(self.decoded_range(from, to));
+            while let Some(checkpoint) = decoded.next().await {
+                let checkpoint = match checkpoint {
+                    Ok(checkpoint) => checkpoint,
+                    Err(e) => {
+                        outcome = Err(e);
+                        break;
+                    }
+                };
+                let sequence = checkpoint.sequence;
+
+                {
+                    let mut metrics = self.metrics.write().await;
+                    metrics.record_checkpoint(sequence);
+                }
+                if !self.commit_decoded(checkpoint).await {
+                    outcome = Err(anyhow::anyhow!("Event receiver dropped"));
+                    break;
+                }
+                if let Err(e) = self
+                    .repo
+                    .save_checkpoint_cursor(CURSOR_NAME, sequence)
+                    .await
+                {
+                    outcome = Err(e);
+                    break;
+                }
+                committed = Some(sequence);
+
+                if shutdown.is_cancelled() {
+                    break;
+                }
+            }
+        }
+
+        if committed.is_some() {
+            self.last_cursor = committed;
+        }
+        outcome
+    }
+
     pub async fn process_checkpoint(
         &mut self,
         checkpoint: &Checkpoint,
         checkpoint_cursor: Option<u64>,
     ) {
-        for tx in &checkpoint.transactions {
-            if let Some(tx_events) = &tx.events {
-                for event in tx_events.events() {
-                    if let Some(event_package_id) = &event.package_id {
-                        if event_package_id != self.package_id.as_str() {
-                            continue;
-                        }
-                    }
+        let decoded = decode_checkpoint(
+            &self.packages,
+            &self.filter,
+            checkpoint,
+            checkpoint_cursor.unwrap_or(0),
+        );
+        self.commit_decoded(decoded).await;
+    }
 
-                    match self.parse_event(event) {
-                        Some(parsed) => {
-                            {
-                                let mut metrics = self.metrics.write().await;
-                                metrics.last_checkpoint_with_event = checkpoint_cursor;
-                                metrics.last_event_seen_at = Some(Instant::now());
-                                metrics.total_events_processed += 1;
-                            }
-
-                            let payload = EventPayload {
-                                event: parsed,
-                                tx_digest: tx.digest.clone(),
-                                checkpoint: checkpoint_cursor.unwrap_or(0),
-                            };
-
-                            if self.event_tx.send(payload).await.is_err() {
-                                warn!("Event receiver dropped, shutting down");
-                                return;
-                            }
-                        }
-                        None => {
-                            warn!(
-                                "Failed to parse event of type {:?} in checkpoint {:?}",
-                                event.event_type, checkpoint_cursor
-                            );
-                        }
-                    }
-                }
-            } else {
-                continue;
+    /// Commit stage of the pipeline: dead-letters the failures and hands the
+    /// events of one checkpoint to the worker. Returns false once the worker
+    /// has gone away.
+    async fn commit_decoded(&self, decoded: DecodedCheckpoint) -> bool {
+        if !decoded.skipped.is_empty() {
+            let mut metrics = self.metrics.write().await;
+            for label in &decoded.skipped {
+                metrics.record_skipped(label);
             }
         }
+
+        for failure in &decoded.failures {
+            self.dead_letter(decoded.sequence, failure).await;
+        }
+
+        for payload in decoded.events {
+            if !self.emit(payload).await {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    /// Records the event in metrics and hands it to the worker. Returns false
+    /// once the worker has gone away.
+    async fn emit(&self, payload: EventPayload) -> bool {
+        {
+            let mut metrics = self.metrics.write().await;
+            metrics.record_event(payload.checkpoint, payload.event.label());
+        }
+
+        if self.event_tx.send(payload).await.is_err() {
+            warn!("Event receiver dropped, shutting down");
+            return false;
+        }
+
+        true
     }
 
-    pub fn parse_event(&self, event: &Event) -> Option<ProtocolEvent> {
-        let event_type = &event.event_type.as_ref()?;
+    async fn commit_cursor(&mut self, checkpoint: u64) -> Result<()> {
+        self.repo
+            .save_checkpoint_cursor(CURSOR_NAME, checkpoint)
+            .await?;
+        self.last_cursor = Some(checkpoint);
+        Ok(())
+    }
+
+    /// Fetches and decodes up to `pipeline_depth` past checkpoints at once,
+    /// yielding them in checkpoint order
+    fn decoded_range(
+        &self,
+        from: u64,
+        to: u64,
+    ) -> impl Stream<Item = Result<DecodedCheckpoint>> + '_ {
+        futures::stream::iter(from..=to)
+            .map(move |sequence| self.decode_historical_checkpoint(sequence))
+            .buffered(self.pipeline_depth)
+    }
 
-        let parts: Vec<&str> = event_type.split("::").collect();
-        if parts.len() != 3 {
-            warn!("Invalid event type format: {}", event_type);
-            return None;
+    /// The stream always starts at the live tip, so checkpoints produced while
+    /// disconnected are read back through the read API before `next` is
+    /// processed. Stops early on shutdown, leaving the cursor at the last
+    /// filled checkpoint.
+    async fn fill_gap(&mut self, next: u64, shutdown: &CancellationToken) -> Result<()> {
+        let Some(last) = self.last_cursor else {
+            return Ok(());
+        };
+        if next <= last + 1 {
+            return Ok(());
         }
 
-        let module = parts[1];
-        let event_name = parts[2];
-        let label = format!("{}::{}", module, event_name);
+        warn!(
+            "Checkpoint gap detected: filling {}..{} ({} checkpoints)",
+            last + 1,
+            next,
+            next - last - 1
+        );
 
-        let bcs_contents = event.contents.as_ref()?;
-        let bcs_bytes = bcs_contents.value.as_ref()?;
+        let mut filled = last;
+        {
+            let mut decoded = std::pin::pin!(self.decoded_range(last + 1, next - 1));
+            while let Some(checkpoint) = decoded.next().await {
+                let checkpoint = checkpoint?;
+                let sequence = checkpoint.sequence;
+                if !self.commit_decoded(checkpoint).await {
+                    return Err(anyhow::anyhow!("Event receiver dropped"));
+                }
+                self.repo
+                    .save_checkpoint_cursor(CURSOR_NAME, sequence)
+                    .await?;
+                filled = sequence;
 
-        match label.as_str() {
-            "registry::ProviderRegistered" => {
-                let inner: ProviderRegistered = bcs::from_bytes(bcs_bytes).ok()?;
-                Some(ProtocolEvent::ProviderRegistered(inner))
-            }
-            "registry::ServiceCreated" => {
-                let inner: ServiceCreated = bcs::from_bytes(bcs_bytes).ok()?;
-                Some(ProtocolEvent::ServiceCreated(inner))
-            }
-            "registry::ServiceUpdated" => {
-                let inner: crate::events::types::ServiceUpdated =
-                    bcs::from_bytes(bcs_bytes).ok()?;
-                Some(ProtocolEvent::ServiceUpdated(inner))
-            }
-            "pricing::TierCreated" => {
-                let inner: crate::events::types::TierCreated = bcs::from_bytes(bcs_bytes).ok()?;
-                Some(ProtocolEvent::TierCreated(inner))
-            }
-            "pricing::TierPriceUpdated" => {
-                let inner: crate::events::types::TierPriceUpdated =
-                    bcs::from_bytes(bcs_bytes).ok()?;
-                Some(ProtocolEvent::TierPriceUpdated(inner))
-            }
-            "pricing::TierDeactivated" => {
-                let inner: crate::events::types::TierDeactivated =
-                    bcs::from_bytes(bcs_bytes).ok()?;
-                Some(ProtocolEvent::TierDeactivated(inner))
-            }
-            "pricing::TierReactivated" => {
-                let inner: crate::events::types::TierReactivated =
-                    bcs::from_bytes(bcs_bytes).ok()?;
-                Some(ProtocolEvent::TierReactivated(inner))
+                if shutdown.is_cancelled() {
+                    break;
+                }
             }
-            "payments::EntitlementPurchased" => {
-                let inner: crate::events::types::EntitlementPurchased =
-                    bcs::from_bytes(bcs_bytes).ok()?;
-                Some(ProtocolEvent::EntitlementPurchased(inner))
+        }
+        self.last_cursor = Some(filled);
+
+        if filled < next - 1 {
+            info!("Checkpoint gap fill interrupted at {}", filled);
+        } else {
+            info!("Checkpoint gap filled up to {}", filled);
+        }
+        Ok(())
+    }
+
+    /// Walks `from..=to` through the read API and feeds package events into the
+    /// worker pipeline. The worker stops once the range is done and the
+    /// listener is dropped.
+    pub async fn backfill(mut self, from: u64, to: u64) -> Result<()> {
+        if from > to {
+            return Err(anyhow::anyhow!(
+                "Invalid checkpoint range: {} is after {}",
+                from,
+                to
+            ));
+        }
+
+        info!(
+            "Backfilling checkpoints {}..={} ({} in flight)",
+            from, to, self.pipeline_depth
+        );
+        let started = Instant::now();
+
+        {
+            let mut decoded = std::pin::pin!(self.decoded_range(from, to));
+            while let Some(checkpoint) = decoded.next().await {
+                let checkpoint = checkpoint?;
+                let sequence = checkpoint.sequence;
+                if !self.commit_decoded(checkpoint).await {
+                    return Err(anyhow::anyhow!("Event receiver dropped"));
+                }
+
+                let done = sequence - from + 1;
+                if done % BACKFILL_PROGRESS_EVERY == 0 {
+                    let metrics = self.metrics.read().await;
+                    info!(
+                        "Backfill progress: checkpoint {} ({}/{}), {} events",
+                        sequence,
+                        done,
+                        to - from + 1,
+                        metrics.total_events_processed
+                    );
+                }
             }
-            _ => {
-                warn!("Unhandled event type: {}", label);
-                None
+        }
+
+        // Lets the live listener resume after the backfilled range instead of
+        // the tip; the cursor only ever moves forward
+        self.commit_cursor(to).await?;
+
+        let metrics = self.metrics.read().await;
+        info!(
+            "Backfill complete: {} checkpoints, {} events in {:.1}s",
+            to - from + 1,
+            metrics.total_events_processed,
+            started.elapsed().as_secs_f64()
+        );
+
+        Ok(())
+    }
+
+    /// Reads a past checkpoint through the read API and forwards its package
+    /// events to the worker
+    pub async fn process_historical_checkpoint(&self, sequence: u64) -> Result<()> {
+        let decoded = self.decode_historical_checkpoint(sequence).await?;
+        if !self.commit_decoded(decoded).await {
+            return Err(anyhow::anyhow!("Event receiver dropped"));
+        }
+        Ok(())
+    }
+
+    /// Decode stage for past checkpoints; only reads, so any number of these
+    /// can run at once
+    async fn decode_historical_checkpoint(&self, sequence: u64) -> Result<DecodedCheckpoint> {
+        let checkpoint = self
+            .sui_client
+            .read_api()
+            .get_checkpoint(CheckpointId::SequenceNumber(sequence))
+            .await?;
+
+        let digests: Vec<TransactionDigest> = checkpoint.transactions;
+        let mut decoded = DecodedCheckpoint::new(sequence);
+
+        for chunk in digests.chunks(GAP_FETCH_CHUNK) {
+            let txs = self
+                .sui_client
+                .read_api()
+                .multi_get_transactions_with_options(
+                    chunk.to_vec(),
+                    sui_json_rpc_types::SuiTransactionBlockResponseOptions::new().with_events(),
+                )
+                .await?;
+
+            for tx in txs {
+                let Some(tx_events) = &tx.events else {
+                    continue;
+                };
+
+                for event in &tx_events.data {
+                    let Some(protocol_version) = self.protocol_version(&event.package_id) else {
+                        continue;
+                    };
+
+                    let event_type = event.type_.to_string();
+                    if !self.filter.allows(&event_type) {
+                        decoded.skipped.push(event_label(&event_type).to_string());
+                        continue;
+                    }
+
+                    match decode_event(&event_type, event.bcs.bytes()) {
+                        Ok(Some(parsed)) => decoded.events.push(EventPayload {
+                            event: parsed,
+                            tx_digest: Some(tx.digest.base58_encode()),
+                            event_index: event.id.event_seq,
+                            checkpoint: sequence,
+                            protocol_version,
+                            package_id: Some(event.package_id.to_string()),
+                        }),
+                        Ok(None) => {}
+                        Err(error) => decoded.failures.push(FailedDecode {
+                            tx_digest: Some(tx.digest.base58_encode()),
+                            event_index: event.id.event_seq,
+                            event_type,
+                            package_id: Some(event.package_id.to_string()),
+                            protocol_version,
+                            bcs: event.bcs.bytes().to_vec(),
+                            error,
+                        }),
+                    }
+                }
             }
         }
+
+        Ok(decoded)
+    }
+
+    pub fn parse_event(&self, event: &Event) -> Result<Option<ProtocolEvent>> {
+        if !self
+            .filter
+            .allows(event.event_type.as_deref().unwrap_or_default())
+        {
+            return Ok(None);
+        }
+        parse_grpc_event(event)
+    }
+
+    /// Stores an event that failed to decode so it can be replayed once the
+    /// decoder is fixed
+    async fn dead_letter(&self, checkpoint: u64, failure: &FailedDecode) {
+        warn!(
+            "Failed to decode event of type {} in checkpoint {}: {}",
+            failure.event_type, checkpoint, failure.error
+        );
+
+        {
+            let mut metrics = self.metrics.write().await;
+            metrics.record_dead_letter();
+        }
+
+        if let Err(e) = self
+            .repo
+            .store_failed_event(
+                checkpoint,
+                failure.tx_digest.as_deref(),
+                failure.event_index,
+                &failure.event_type,
+                failure.package_id.as_deref(),
+                failure.protocol_version,
+                &failure.bcs,
+                &failure.error.to_string(),
+            )
+            .await
+        {
+            error!("Failed to store dead-lettered event: {}", e);
+        }
     }
 
     pub async fn process_rpc_checkpoint(
@@ -261,8 +907,6 @@ impl EventListener {
             .get_checkpoint(checkpoint_id)
             .await?;
 
-        let expected_package_id = ObjectID::from_hex_literal(&self.package_id)?;
-
         for tx in &checkpoint.transactions {
             if tx.base58_encode() != tx_digest {
                 continue;
@@ -281,7 +925,7 @@ impl EventListener {
 
             if let Some(tx_events) = &full_tx.events {
                 for event in &tx_events.data {
-                    if event.package_id != expected_package_id {
+                    if self.protocol_version(&event.package_id).is_none() {
                         continue;
                     }
                 }
@@ -293,7 +937,7 @@ impl EventListener {
         Ok(())
     }
 
-    async fn health_monitor(health: Arc<RwLock<EventMetrics>>) {
+    async fn health_monitor(health: Arc<RwLock<EventMetrics>>, alerts: Arc<AlertManager>) {
         let mut interval = tokio::time::interval(Duration::from_secs(30));
 
         loop {
@@ -328,7 +972,7 @@ impl EventListener {
 
             info!(
                 target: "health",
-                "Health | Connection: {} | Last CP: {} | Last Event: {} (cp #{:?}) | Totals: {} checkpoints, {} events",
+                "Health | Connection: {} | Last CP: {} | Last Event: {} (cp #{:?}) | Totals: {} checkpoints, {} events, {} dead-lettered",
                 checkpoint_status,
                 metrics.last_checkpoint_received
                     .map(|c| c.to_string())
@@ -336,17 +980,392 @@ impl EventListener {
                 event_info,
                 metrics.last_checkpoint_with_event,
                 metrics.total_checkpoints_processed,
-                metrics.total_events_processed
+                metrics.total_events_processed,
+                metrics.total_events_dead_lettered
             );
 
             if metrics.connection_healthy {
                 if let Some(last_time) = metrics.last_checkpoint_received_at {
-                    let elapsed = now.duration_since(last_time).as_secs();
-                    if elapsed > 120 {
-                        error!("ALERT: No checkpoint received in {}s", elapsed);
+                    let elapsed = now.duration_since(last_time);
+                    if elapsed > alerts.checkpoint_lag_threshold() {
+                        error!("ALERT: No checkpoint received in {}s", elapsed.as_secs());
+                        let last_checkpoint = metrics.last_checkpoint_received;
+                        drop(metrics);
+                        alerts
+                            .raise(Alert::checkpoint_lag(elapsed.as_secs(), last_checkpoint))
+                            .await;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// A checkpoint decoded from one of several gRPC sources
+struct SourcedCheckpoint {
+    source: usize,
+    cursor: u64,
+    decoded: Option<DecodedCheckpoint>,
+}
+
+/// Newest checkpoint each gRPC source has delivered, exported as how far
+/// each one trails the freshest
+struct SourceHeads {
+    labels: Vec<String>,
+    heads: Vec<Option<u64>>,
+}
+
+impl SourceHeads {
+    fn new(labels: Vec<String>) -> Self {
+        let heads = vec![None; labels.len()];
+        Self { labels, heads }
+    }
+
+    fn label(&self, source: usize) -> &str {
+        &self.labels[source]
+    }
+
+    fn record(&mut self, source: usize, checkpoint: u64) {
+        let head = &mut self.heads[source];
+        *head = Some(head.map_or(checkpoint, |h| h.max(checkpoint)));
+
+        INDEXER_METRICS
+            .source_last_checkpoint
+            .with_label_values(&[self.label(source)])
+            .set(checkpoint as i64);
+
+        let Some(newest) = self.heads.iter().flatten().max().copied() else {
+            return;
+        };
+        for (label, head) in self.labels.iter().zip(&self.heads) {
+            if let Some(head) = head {
+                INDEXER_METRICS
+                    .source_lag_checkpoints
+                    .with_label_values(&[label])
+                    .set((newest - head) as i64);
+            }
+        }
+    }
+}
+
+/// Feeds one gRPC source's decoded checkpoints to the merge loop,
+/// reconnecting until shut down or the merge loop goes away
+struct SourceTask {
+    source: usize,
+    uri: String,
+    packages: Arc<Vec<WatchedPackage>>,
+    filter: Arc<EventFilter>,
+    depth: usize,
+    tx: mpsc::Sender<SourcedCheckpoint>,
+    shutdown: CancellationToken,
+}
+
+impl SourceTask {
+    async fn run(self, mut stream: Option<CheckpointStream>) {
+        loop {
+            let subscription = match stream.take() {
+                Some(subscription) => Some(subscription),
+                None => match subscribe(&self.uri).await {
+                    Ok(subscription) => Some(subscription),
+                    Err(e) => {
+                        warn!(source = %self.uri, "Checkpoint source unavailable: {}", e);
+                        None
                     }
+                },
+            };
+
+            if let Some(subscription) = subscription {
+                INDEXER_METRICS
+                    .source_connected
+                    .with_label_values(&[&self.uri])
+                    .set(1);
+
+                let done = self.forward(subscription).await;
+
+                INDEXER_METRICS
+                    .source_connected
+                    .with_label_values(&[&self.uri])
+                    .set(0);
+                if done {
+                    return;
                 }
             }
+
+            warn!(source = %self.uri, "Reconnecting checkpoint source in 5s...");
+            tokio::select! {
+                _ = self.shutdown.cancelled() => return,
+                _ = tokio::time::sleep(Duration::from_secs(5)) => {}
+            }
+        }
+    }
+
+    /// Forwards checkpoints until the stream fails. Returns true once the
+    /// task should stop for good.
+    async fn forward(&self, subscription: CheckpointStream) -> bool {
+        let mut decoded = std::pin::pin!(decode_stream(
+            subscription,
+            self.packages.clone(),
+            self.filter.clone(),
+            self.depth,
+        ));
+
+        loop {
+            let next = tokio::select! {
+                biased;
+                _ = self.shutdown.cancelled() => return true,
+                next = decoded.next() => next,
+            };
+
+            match next {
+                Some(Ok((Some(cursor), decoded))) => {
+                    let checkpoint = SourcedCheckpoint {
+                        source: self.source,
+                        cursor,
+                        decoded,
+                    };
+                    if self.tx.send(checkpoint).await.is_err() {
+                        return true;
+                    }
+                }
+                // Without a cursor the checkpoint can't be ordered against
+                // the other sources
+                Some(Ok((None, _))) => {}
+                Some(Err(e)) => {
+                    error!(source = %self.uri, "Checkpoint stream error: {}", e);
+                    return false;
+                }
+                None => {
+                    warn!(source = %self.uri, "Checkpoint stream ended");
+                    return false;
+                }
+            }
+        }
+    }
+}
+
+async fn subscribe(uri: &str) -> Result<CheckpointStream> {
+    info!("Connecting to: {}", uri);
+
+    let tls_config = tonic::transport::ClientTlsConfig::new().with_enabled_roots();
+
+    let channel = Channel::from_shared(uri.to_string())?
+        .tls_config(tls_config)?
+        .connect()
+        .await?;
+
+    let mut client = SubscriptionServiceClient::new(channel);
+
+    let mut req_msg = SubscribeCheckpointsRequest::default();
+    req_msg.read_mask = Some(FieldMask {
+        paths: STREAM_READ_MASK.iter().map(|p| p.to_string()).collect(),
+    });
+    let request = tonic::Request::new(req_msg);
+
+    let response = client.subscribe_checkpoints(request).await?;
+    Ok(response.into_inner())
+}
+
+/// Checkpoints are decoded on the blocking pool, up to `depth` at a time;
+/// `buffered` yields them back in stream order
+fn decode_stream(
+    stream: CheckpointStream,
+    packages: Arc<Vec<WatchedPackage>>,
+    filter: Arc<EventFilter>,
+    depth: usize,
+) -> impl Stream<Item = Result<(Option<u64>, Option<DecodedCheckpoint>)>> {
+    stream
+        .map(move |result| {
+            let packages = packages.clone();
+            let filter = filter.clone();
+            async move {
+                let response = result?;
+                INDEXER_METRICS
+                    .stream_bytes
+                    .inc_by(response.encoded_len() as u64);
+                let cursor = response.cursor;
+                let decoded = match response.checkpoint {
+                    Some(checkpoint) => Some(
+                        tokio::task::spawn_blocking(move || {
+                            decode_checkpoint(&packages, &filter, &checkpoint, cursor.unwrap_or(0))
+                        })
+                        .await?,
+                    ),
+                    None => None,
+                };
+                Ok::<_, anyhow::Error>((cursor, decoded))
+            }
+        })
+        .buffered(depth)
+}
+
+fn protocol_version(packages: &[WatchedPackage], package_id: &ObjectID) -> Option<u64> {
+    packages
+        .iter()
+        .find(|p| p.package_id == *package_id)
+        .map(|p| p.protocol_version)
+}
+
+fn latest_protocol_version(packages: &[WatchedPackage]) -> u64 {
+    packages
+        .iter()
+        .map(|p| p.protocol_version)
+        .max()
+        .unwrap_or(1)
+}
+
+/// Decode stage for streamed checkpoints. Pure CPU work, so the live
+/// pipeline runs it on the blocking pool.
+fn decode_checkpoint(
+    packages: &[WatchedPackage],
+    filter: &EventFilter,
+    checkpoint: &Checkpoint,
+    sequence: u64,
+) -> DecodedCheckpoint {
+    let mut decoded = DecodedCheckpoint::new(sequence);
+
+    for tx in &checkpoint.transactions {
+        let Some(tx_events) = &tx.events else {
+            continue;
+        };
+
+        for (event_index, event) in tx_events.events().iter().enumerate() {
+            let event_index = event_index as u64;
+            let protocol_version = match &event.package_id {
+                Some(event_package_id) => {
+                    let version = ObjectID::from_hex_literal(event_package_id)
+                        .ok()
+                        .and_then(|id| protocol_version(packages, &id));
+                    match version {
+                        Some(version) => version,
+                        None => continue,
+                    }
+                }
+                None => latest_protocol_version(packages),
+            };
+
+            let event_type = event.event_type.as_deref().unwrap_or_default();
+            if !filter.allows(event_type) {
+                decoded.skipped.push(event_label(event_type).to_string());
+                continue;
+            }
+
+            match parse_grpc_event(event) {
+                Ok(Some(parsed)) => decoded.events.push(EventPayload {
+                    event: parsed,
+                    tx_digest: tx.digest.clone(),
+                    event_index,
+                    checkpoint: sequence,
+                    protocol_version,
+                    package_id: event.package_id.clone(),
+                }),
+                Ok(None) => {}
+                Err(error) => decoded.failures.push(FailedDecode {
+                    tx_digest: tx.digest.clone(),
+                    event_index,
+                    event_type: event.event_type.clone().unwrap_or_default(),
+                    package_id: event.package_id.clone(),
+                    protocol_version,
+                    bcs: event
+                        .contents
+                        .as_ref()
+                        .and_then(|c| c.value.as_ref())
+                        .map(|b| b.to_vec())
+                        .unwrap_or_default(),
+                    error,
+                }),
+            }
+        }
+    }
+
+    decoded
+}
+
+fn parse_grpc_event(event: &Event) -> Result<Option<ProtocolEvent>> {
+    let event_type = event
+        .event_type
+        .as_ref()
+        .ok_or_else(|| anyhow::anyhow!("Event has no type"))?;
+    let bcs_bytes = event
+        .contents
+        .as_ref()
+        .and_then(|c| c.value.as_ref())
+        .ok_or_else(|| anyhow::anyhow!("Event {} has no contents", event_type))?;
+
+    decode_event(event_type, bcs_bytes)
+}
+
+/// Decodes a package event from its `pkg::module::Name` type and BCS bytes.
+/// Returns `Ok(None)` for event types the indexer does not handle.
+pub fn decode_event(event_type: &str, bcs_bytes: &[u8]) -> Result<Option<ProtocolEvent>> {
+    let parts: Vec<&str> = event_type.split("::").collect();
+    if parts.len() != 3 {
+        return Err(anyhow::anyhow!("Invalid event type format: {}", event_type));
+    }
+
+    let module = parts[1];
+    let event_name = parts[2];
+    let label = format!("{}::{}", module, event_name);
+
+    match label.as_str() {
+        "registry::ProviderRegistered" => {
+            let inner: ProviderRegistered = bcs::from_bytes(bcs_bytes)?;
+            Ok(Some(ProtocolEvent::ProviderRegistered(inner)))
+        }
+        "registry::ServiceCreated" => {
+            let inner: ServiceCreated = bcs::from_bytes(bcs_bytes)?;
+            Ok(Some(ProtocolEvent::ServiceCreated(inner)))
+        }
+        "registry::ServiceUpdated" => {
+            let inner: crate::events::types::ServiceUpdated = bcs::from_bytes(bcs_bytes)?;
+            Ok(Some(ProtocolEvent::ServiceUpdated(inner)))
+        }
+        "registry::ServiceDeactivated" => {
+            let inner: crate::events::types::ServiceDeactivated = bcs::from_bytes(bcs_bytes)?;
+            Ok(Some(ProtocolEvent::ServiceDeactivated(inner)))
+        }
+        "registry::ServiceReactivated" => {
+            let inner: crate::events::types::ServiceReactivated = bcs::from_bytes(bcs_bytes)?;
+            Ok(Some(ProtocolEvent::ServiceReactivated(inner)))
+        }
+        "registry::TierAddedToService" => {
+            let inner: crate::events::types::TierAddedToService = bcs::from_bytes(bcs_bytes)?;
+            Ok(Some(ProtocolEvent::TierAddedToService(inner)))
+        }
+        "registry::TierRemovedFromService" => {
+            let inner: crate::events::types::TierRemovedFromService = bcs::from_bytes(bcs_bytes)?;
+            Ok(Some(ProtocolEvent::TierRemovedFromService(inner)))
+        }
+        "pricing::TierCreated" => {
+            let inner: crate::events::types::TierCreated = bcs::from_bytes(bcs_bytes)?;
+            Ok(Some(ProtocolEvent::TierCreated(inner)))
+        }
+        "pricing::TierPriceUpdated" => {
+            let inner: crate::events::types::TierPriceUpdated = bcs::from_bytes(bcs_bytes)?;
+            Ok(Some(ProtocolEvent::TierPriceUpdated(inner)))
+        }
+        "pricing::TierDeactivated" => {
+            let inner: crate::events::types::TierDeactivated = bcs::from_bytes(bcs_bytes)?;
+            Ok(Some(ProtocolEvent::TierDeactivated(inner)))
+        }
+        "pricing::TierReactivated" => {
+            let inner: crate::events::types::TierReactivated = bcs::from_bytes(bcs_bytes)?;
+            Ok(Some(ProtocolEvent::TierReactivated(inner)))
+        }
+        "payments::EntitlementPurchased" => {
+            let inner: crate::events::types::EntitlementPurchased = bcs::from_bytes(bcs_bytes)?;
+            Ok(Some(ProtocolEvent::EntitlementPurchased(inner)))
+        }
+        "payments::EntitlementToppedUp" => {
+            let inner: crate::events::types::EntitlementToppedUp = bcs::from_bytes(bcs_bytes)?;
+            Ok(Some(ProtocolEvent::EntitlementToppedUp(inner)))
+        }
+        "payments::QuotaConsumed" => {
+            let inner: crate::events::types::QuotaConsumed = bcs::from_bytes(bcs_bytes)?;
+            Ok(Some(ProtocolEvent::QuotaConsumed(inner)))
+        }
+        _ => {
+            warn!("Unhandled event type: {}", label);
+            Ok(None)
         }
     }
 }
@@ -0,0 +1,93 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::Result;
+use tokio::sync::mpsc::Receiver;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
+
+use crate::{
+    db::sqlite::SqliteStorage,
+    events::{metrics::INDEXER_METRICS, types::EventPayload},
+};
+
+/// Attempts per event before it is dead-lettered
+const MAX_ATTEMPTS: u32 = 5;
+
+/// Delay before the first retry, doubled after each failed attempt
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(200);
+const RETRY_MAX_DELAY: Duration = Duration::from_secs(5);
+
+/// Event worker of the SQLite backend. It applies each event's projection in
+/// order on the one connection SQLite allows to write, and has none of
+/// `EventWorker`'s shards, sinks, outbox or webhooks.
+pub struct LiteWorker {
+    storage: Arc<SqliteStorage>,
+    rx: Receiver<EventPayload>,
+}
+
+impl LiteWorker {
+    pub fn new(storage: Arc<SqliteStorage>, rx: Receiver<EventPayload>) -> Self {
+        Self { storage, rx }
+    }
+
+    /// Handles events until the channel closes, draining the ones already
+    /// buffered once `shutdown` is cancelled
+    pub async fn run(mut self, shutdown: CancellationToken) -> Result<()> {
+        info!("Event worker started");
+        loop {
+            let payload = tokio::select! {
+                biased;
+                payload = self.rx.recv() => payload,
+                _ = shutdown.cancelled(), if !self.rx.is_closed() => {
+                    info!(buffered = self.rx.len(), "Shutdown requested, draining events");
+                    self.rx.close();
+                    continue;
+                }
+            };
+            let Some(payload) = payload else {
+                break;
+            };
+
+            self.apply_with_retry(&payload).await;
+        }
+
+        info!("Event worker stopped");
+        Ok(())
+    }
+
+    async fn apply_with_retry(&self, payload: &EventPayload) {
+        let mut delay = RETRY_BASE_DELAY;
+        for attempt in 1..=MAX_ATTEMPTS {
+            let Err(e) = self.storage.apply_event(payload).await else {
+                return;
+            };
+
+            if attempt == MAX_ATTEMPTS {
+                error!(
+                    "Failed to handle payload {:?} after {} attempts: {}",
+                    payload, MAX_ATTEMPTS, e
+                );
+                INDEXER_METRICS.events_dead_lettered.inc();
+                if let Err(store_err) = self
+                    .storage
+                    .store_failed_payload(payload, &e.to_string(), MAX_ATTEMPTS)
+                    .await
+                {
+                    error!("Failed to store dead-lettered payload: {}", store_err);
+                }
+                return;
+            }
+
+            warn!(
+                event_type = payload.event.label(),
+                checkpoint = payload.checkpoint,
+                attempt,
+                retry_in_ms = delay.as_millis() as u64,
+                error = %e,
+                "Failed to handle event, retrying"
+            );
+            tokio::time::sleep(delay).await;
+            delay = (delay * 2).min(RETRY_MAX_DELAY);
+        }
+    }
+}
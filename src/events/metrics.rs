@@ -1,3 +1,9 @@
+use std::collections::HashMap;
+
+use once_cell::sync::Lazy;
+use prometheus::{
+    Gauge, IntCounter, IntCounterVec, IntGauge, IntGaugeVec, Opts, Registry, TextEncoder,
+};
 use tokio::time::Instant;
 
 #[derive(Debug, Clone)]
@@ -8,6 +14,12 @@ pub struct EventMetrics {
     pub last_event_seen_at: Option<Instant>,
     pub total_checkpoints_processed: u64,
     pub total_events_processed: u64,
+    /// Events that failed to decode and were written to `failed_events`
+    pub total_events_dead_lettered: u64,
+    /// Events handed to the worker, by `module::Name`
+    pub events_by_type: HashMap<&'static str, u64>,
+    /// Events skipped by the event filter, by `module::Name`
+    pub events_skipped: HashMap<String, u64>,
     pub connection_healthy: bool,
 }
 
@@ -20,7 +32,278 @@ impl Default for EventMetrics {
             last_event_seen_at: None,
             total_checkpoints_processed: 0,
             total_events_processed: 0,
+            total_events_dead_lettered: 0,
+            events_by_type: HashMap::new(),
+            events_skipped: HashMap::new(),
             connection_healthy: false,
         }
     }
 }
+
+impl EventMetrics {
+    pub fn record_checkpoint(&mut self, checkpoint: u64) {
+        self.last_checkpoint_received = Some(checkpoint);
+        self.last_checkpoint_received_at = Some(Instant::now());
+        self.total_checkpoints_processed += 1;
+
+        INDEXER_METRICS.checkpoints_processed.inc();
+        INDEXER_METRICS.last_checkpoint.set(checkpoint as i64);
+        INDEXER_METRICS
+            .last_checkpoint_at
+            .set(chrono::Utc::now().timestamp_millis() as f64 / 1000.0);
+    }
+
+    pub fn record_event(&mut self, checkpoint: u64, label: &'static str) {
+        self.last_checkpoint_with_event = Some(checkpoint);
+        self.last_event_seen_at = Some(Instant::now());
+        self.total_events_processed += 1;
+        *self.events_by_type.entry(label).or_default() += 1;
+
+        INDEXER_METRICS.events_processed.inc();
+        INDEXER_METRICS
+            .events_by_type
+            .with_label_values(&[label])
+            .inc();
+    }
+
+    pub fn record_skipped(&mut self, label: &str) {
+        *self.events_skipped.entry(label.to_string()).or_default() += 1;
+
+        INDEXER_METRICS
+            .events_skipped
+            .with_label_values(&[label])
+            .inc();
+    }
+
+    pub fn record_dead_letter(&mut self) {
+        self.total_events_dead_lettered += 1;
+
+        INDEXER_METRICS.events_dead_lettered.inc();
+    }
+
+    pub fn set_connection_healthy(&mut self, healthy: bool) {
+        self.connection_healthy = healthy;
+
+        INDEXER_METRICS.connection_healthy.set(healthy as i64);
+    }
+}
+
+/// Prometheus view of the listener's `EventMetrics`, served by the server's
+/// `/metrics` endpoint
+pub struct IndexerMetrics {
+    pub checkpoints_processed: IntCounter,
+    pub events_processed: IntCounter,
+    pub events_dead_lettered: IntCounter,
+    pub events_by_type: IntCounterVec,
+    pub events_skipped: IntCounterVec,
+    pub last_checkpoint: IntGauge,
+    pub connection_healthy: IntGauge,
+    /// Per gRPC source when streaming from several endpoints
+    pub source_connected: IntGaugeVec,
+    pub source_last_checkpoint: IntGaugeVec,
+    pub source_lag_checkpoints: IntGaugeVec,
+    pub source_first: IntCounterVec,
+    pub source_duplicates: IntCounterVec,
+    /// Encoded size of the checkpoints streamed, summed over sources
+    pub stream_bytes: IntCounter,
+    /// Per `EventSink`, by its name
+    pub sink_published: IntCounterVec,
+    pub sink_failures: IntCounterVec,
+    /// Unix time the last checkpoint arrived; the lag gauge is derived from it
+    /// on every scrape so it keeps growing while the indexer is stalled
+    last_checkpoint_at: Gauge,
+    lag_seconds: Gauge,
+    registry: Registry,
+}
+
+impl IndexerMetrics {
+    fn new() -> Self {
+        let registry = Registry::new();
+
+        let checkpoints_processed = IntCounter::new(
+            "infrapass_indexer_checkpoints_processed_total",
+            "Checkpoints received from the stream",
+        )
+        .unwrap();
+        let events_processed = IntCounter::new(
+            "infrapass_indexer_events_processed_total",
+            "Package events handed to the worker",
+        )
+        .unwrap();
+        let events_dead_lettered = IntCounter::new(
+            "infrapass_indexer_events_dead_lettered_total",
+            "Events written to failed_events, undecodable or failing every worker attempt",
+        )
+        .unwrap();
+        let events_by_type = IntCounterVec::new(
+            Opts::new(
+                "infrapass_indexer_events_by_type_total",
+                "Package events handed to the worker, by event type",
+            ),
+            &["event_type"],
+        )
+        .unwrap();
+        let events_skipped = IntCounterVec::new(
+            Opts::new(
+                "infrapass_indexer_events_skipped_total",
+                "Package events skipped by the event filter, by event type",
+            ),
+            &["event_type"],
+        )
+        .unwrap();
+        let last_checkpoint = IntGauge::new(
+            "infrapass_indexer_last_checkpoint",
+            "Sequence number of the last checkpoint received",
+        )
+        .unwrap();
+        let connection_healthy = IntGauge::new(
+            "infrapass_indexer_connection_healthy",
+            "1 while the checkpoint stream is connected",
+        )
+        .unwrap();
+        let source_connected = IntGaugeVec::new(
+            Opts::new(
+                "infrapass_indexer_source_connected",
+                "1 while the checkpoint stream of a gRPC source is connected",
+            ),
+            &["source"],
+        )
+        .unwrap();
+        let source_last_checkpoint = IntGaugeVec::new(
+            Opts::new(
+                "infrapass_indexer_source_last_checkpoint",
+                "Newest checkpoint delivered by a gRPC source",
+            ),
+            &["source"],
+        )
+        .unwrap();
+        let source_lag_checkpoints = IntGaugeVec::new(
+            Opts::new(
+                "infrapass_indexer_source_lag_checkpoints",
+                "Checkpoints a gRPC source trails the freshest source by",
+            ),
+            &["source"],
+        )
+        .unwrap();
+        let source_first = IntCounterVec::new(
+            Opts::new(
+                "infrapass_indexer_source_checkpoints_first_total",
+                "Checkpoints committed from a gRPC source because it delivered them first",
+            ),
+            &["source"],
+        )
+        .unwrap();
+        let source_duplicates = IntCounterVec::new(
+            Opts::new(
+                "infrapass_indexer_source_checkpoints_duplicate_total",
+                "Checkpoints from a gRPC source dropped because another source delivered them first",
+            ),
+            &["source"],
+        )
+        .unwrap();
+        let stream_bytes = IntCounter::new(
+            "infrapass_indexer_stream_bytes_total",
+            "Bytes of checkpoint data received over gRPC subscriptions",
+        )
+        .unwrap();
+        let sink_published = IntCounterVec::new(
+            Opts::new(
+                "infrapass_indexer_sink_published_total",
+                "Indexed events published to an event sink",
+            ),
+            &["sink"],
+        )
+        .unwrap();
+        let sink_failures = IntCounterVec::new(
+            Opts::new(
+                "infrapass_indexer_sink_failures_total",
+                "Indexed events an event sink failed to publish",
+            ),
+            &["sink"],
+        )
+        .unwrap();
+        let last_checkpoint_at = Gauge::new(
+            "infrapass_indexer_last_checkpoint_timestamp_seconds",
+            "Unix time the last checkpoint was received",
+        )
+        .unwrap();
+        let lag_seconds = Gauge::new(
+            "infrapass_indexer_lag_seconds",
+            "Seconds since the last checkpoint was received",
+        )
+        .unwrap();
+
+        registry
+            .register(Box::new(checkpoints_processed.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(events_processed.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(events_dead_lettered.clone()))
+            .unwrap();
+        registry.register(Box::new(events_by_type.clone())).unwrap();
+        registry.register(Box::new(events_skipped.clone())).unwrap();
+        registry
+            .register(Box::new(last_checkpoint.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(connection_healthy.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(source_connected.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(source_last_checkpoint.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(source_lag_checkpoints.clone()))
+            .unwrap();
+        registry.register(Box::new(source_first.clone())).unwrap();
+        registry
+            .register(Box::new(source_duplicates.clone()))
+            .unwrap();
+        registry.register(Box::new(stream_bytes.clone())).unwrap();
+        registry.register(Box::new(sink_published.clone())).unwrap();
+        registry.register(Box::new(sink_failures.clone())).unwrap();
+        registry
+            .register(Box::new(last_checkpoint_at.clone()))
+            .unwrap();
+        registry.register(Box::new(lag_seconds.clone())).unwrap();
+
+        Self {
+            checkpoints_processed,
+            events_processed,
+            events_dead_lettered,
+            events_by_type,
+            events_skipped,
+            last_checkpoint,
+            connection_healthy,
+            source_connected,
+            source_last_checkpoint,
+            source_lag_checkpoints,
+            source_first,
+            source_duplicates,
+            stream_bytes,
+            sink_published,
+            sink_failures,
+            last_checkpoint_at,
+            lag_seconds,
+            registry,
+        }
+    }
+
+    pub fn encode(&self) -> String {
+        let last = self.last_checkpoint_at.get();
+        if last > 0.0 {
+            let now = chrono::Utc::now().timestamp_millis() as f64 / 1000.0;
+            self.lag_seconds.set((now - last).max(0.0));
+        }
+
+        let encoder = TextEncoder::new();
+        let families = self.registry.gather();
+        encoder.encode_to_string(&families).unwrap_or_default()
+    }
+}
+
+pub static INDEXER_METRICS: Lazy<IndexerMetrics> = Lazy::new(IndexerMetrics::new);
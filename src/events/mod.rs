@@ -1,4 +1,17 @@
+pub mod dlq;
+pub mod filter;
+pub mod fixtures;
+#[cfg(feature = "kafka")]
+pub mod kafka;
 pub mod listener;
+pub mod lite;
 pub mod metrics;
+#[cfg(feature = "nats")]
+pub mod nats;
+pub mod packages;
+pub mod replay;
+pub mod shard;
+pub mod sink;
+pub mod stream;
 pub mod types;
 pub mod worker;
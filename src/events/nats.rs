@@ -0,0 +1,107 @@
+use anyhow::{Context, Result, anyhow};
+use async_nats::{
+    HeaderMap,
+    header::NATS_MESSAGE_ID,
+    jetstream::{
+        self,
+        stream::{Config, StorageType},
+    },
+};
+use async_trait::async_trait;
+
+use crate::events::sink::{EventSink, SinkRecord};
+
+/// Where and how durably a `NatsSink` stores events
+#[derive(Debug, Clone)]
+pub struct NatsSinkConfig {
+    /// JetStream stream holding the events, created if missing
+    pub stream: String,
+    /// Events go to `<subject_prefix>.<module>.<Name>`
+    pub subject_prefix: String,
+    pub storage: StorageType,
+    pub replicas: usize,
+    /// Wait for the stream to acknowledge each event before the next one.
+    /// Without it, an event the server rejects is lost silently.
+    pub await_ack: bool,
+}
+
+impl Default for NatsSinkConfig {
+    fn default() -> Self {
+        Self {
+            stream: "INFRAPASS_EVENTS".to_string(),
+            subject_prefix: "infrapass.events".to_string(),
+            storage: StorageType::File,
+            replicas: 1,
+            await_ack: true,
+        }
+    }
+}
+
+/// Publishes every indexed event as JSON to a JetStream stream, on a subject
+/// per event type, e.g. `infrapass.events.payments.EntitlementPurchased`.
+/// Each message carries the event's digest and index as its message ID, so
+/// JetStream drops copies republished within its duplicate window.
+pub struct NatsSink {
+    js: jetstream::Context,
+    cfg: NatsSinkConfig,
+}
+
+impl NatsSink {
+    pub async fn connect(url: &str, cfg: NatsSinkConfig) -> Result<Self> {
+        let client = async_nats::connect(url)
+            .await
+            .with_context(|| format!("failed to connect to NATS at {}", url))?;
+        let js = jetstream::new(client);
+
+        js.get_or_create_stream(Config {
+            name: cfg.stream.clone(),
+            subjects: vec![format!("{}.>", cfg.subject_prefix)],
+            storage: cfg.storage,
+            num_replicas: cfg.replicas,
+            ..Default::default()
+        })
+        .await
+        .map_err(|e| anyhow!("failed to set up stream {}: {}", cfg.stream, e))?;
+
+        Ok(Self { js, cfg })
+    }
+
+    fn subject(&self, record: &SinkRecord<'_>) -> String {
+        format!(
+            "{}.{}",
+            self.cfg.subject_prefix,
+            record.event_type.replace("::", ".")
+        )
+    }
+}
+
+#[async_trait]
+impl EventSink for NatsSink {
+    fn name(&self) -> &'static str {
+        "nats"
+    }
+
+    async fn publish(&self, record: &SinkRecord<'_>) -> Result<()> {
+        let subject = self.subject(record);
+        let payload = serde_json::to_vec(record)?;
+
+        let mut headers = HeaderMap::new();
+        if let Some(tx_digest) = record.tx_digest {
+            let id = format!("{}:{}", tx_digest, record.event_index);
+            headers.insert(NATS_MESSAGE_ID, id.as_str());
+        }
+
+        let ack = self
+            .js
+            .publish_with_headers(subject.clone(), headers, payload.into())
+            .await
+            .map_err(|e| anyhow!("failed to publish to {}: {}", subject, e))?;
+        if self.cfg.await_ack {
+            ack.await.map_err(|e| {
+                anyhow!("{} did not acknowledge {}: {}", self.cfg.stream, subject, e)
+            })?;
+        }
+
+        Ok(())
+    }
+}
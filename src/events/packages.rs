@@ -0,0 +1,64 @@
+use anyhow::{Result, anyhow};
+use sui_types::base_types::ObjectID;
+
+use crate::utils::constants::PACKAGE_ID;
+
+/// A package whose events the listener indexes. Each upgrade publishes a new
+/// package ID, and events carry the ID of the version that emitted them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WatchedPackage {
+    pub package_id: ObjectID,
+    pub protocol_version: u64,
+}
+
+impl WatchedPackage {
+    /// The original package as version 1
+    pub fn original() -> Self {
+        Self {
+            package_id: ObjectID::from_hex_literal(PACKAGE_ID).expect("PACKAGE_ID is a valid ID"),
+            protocol_version: 1,
+        }
+    }
+}
+
+/// Parses `0xabc=1,0xdef=2`. Entries without `=version` are numbered by
+/// their position, so `0xabc,0xdef` is the same list.
+pub fn parse_watched_packages(raw: &str) -> Result<Vec<WatchedPackage>> {
+    let mut packages: Vec<WatchedPackage> = vec![];
+
+    for (i, entry) in raw
+        .split(',')
+        .map(str::trim)
+        .filter(|e| !e.is_empty())
+        .enumerate()
+    {
+        let (id, version) = match entry.split_once('=') {
+            Some((id, version)) => (
+                id.trim(),
+                version
+                    .trim()
+                    .parse::<u64>()
+                    .map_err(|_| anyhow!("Invalid protocol version in {}", entry))?,
+            ),
+            None => (entry, i as u64 + 1),
+        };
+
+        let package_id = ObjectID::from_hex_literal(id)
+            .map_err(|e| anyhow!("Invalid package ID {}: {}", id, e))?;
+
+        if packages.iter().any(|p| p.package_id == package_id) {
+            return Err(anyhow!("Package {} is listed twice", package_id));
+        }
+
+        packages.push(WatchedPackage {
+            package_id,
+            protocol_version: version,
+        });
+    }
+
+    if packages.is_empty() {
+        return Err(anyhow!("No package IDs given"));
+    }
+
+    Ok(packages)
+}
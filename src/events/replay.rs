@@ -0,0 +1,144 @@
+use anyhow::Result;
+use serde_json::Value;
+use tokio::sync::mpsc;
+use tracing::{info, warn};
+
+use crate::{
+    db::{models::BlockchainEvent, repository::Repository},
+    events::{
+        filter::EventFilter,
+        types::{
+            EventPayload, ProtocolEvent, ProviderRegistered, QuotaConsumed, ServiceCreated,
+            TierAddedToService, TierCreated, TierPriceUpdated, TierRemovedFromService,
+        },
+    },
+};
+
+/// Rows read from `blockchain_events` per query
+const PAGE_SIZE: i64 = 500;
+
+#[derive(Debug, Default)]
+pub struct EventReplaySummary {
+    pub replayed: usize,
+    /// Rows skipped by the `--event-type` filter
+    pub filtered: usize,
+    /// Rows whose `event_data` no longer decodes, or of a type that is not
+    /// replayed
+    pub undecodable: usize,
+}
+
+/// Feeds the events stored in `blockchain_events` from checkpoint `from`
+/// onwards back into the worker pipeline, oldest first, so derived tables are
+/// rebuilt with the current handlers. The worker must be built with
+/// `with_reprocess`, or every event is skipped as already ingested.
+pub async fn replay_stored_events(
+    repo: &Repository,
+    event_tx: &mpsc::Sender<EventPayload>,
+    from: u64,
+    to: Option<u64>,
+    filter: &EventFilter,
+) -> Result<EventReplaySummary> {
+    let mut summary = EventReplaySummary::default();
+    let mut after = None;
+
+    loop {
+        let rows = repo.list_stored_events(from, to, after, PAGE_SIZE).await?;
+        let Some(last) = rows.last() else {
+            break;
+        };
+        after = Some((last.checkpoint_number, last.id));
+
+        for row in &rows {
+            let payload = match stored_payload(row) {
+                Ok(Some(payload)) => payload,
+                Ok(None) => {
+                    summary.undecodable += 1;
+                    continue;
+                }
+                Err(e) => {
+                    warn!(
+                        id = row.id,
+                        event_type = %row.event_type,
+                        checkpoint = row.checkpoint_number,
+                        error = %e,
+                        "Stored event no longer decodes"
+                    );
+                    summary.undecodable += 1;
+                    continue;
+                }
+            };
+
+            if !filter.allows_label(payload.event.label()) {
+                summary.filtered += 1;
+                continue;
+            }
+
+            if event_tx.send(payload).await.is_err() {
+                return Err(anyhow::anyhow!("Event receiver dropped"));
+            }
+            summary.replayed += 1;
+        }
+
+        info!(
+            checkpoint = after.map(|(checkpoint, _)| checkpoint),
+            replayed = summary.replayed,
+            "Replaying stored events"
+        );
+    }
+
+    Ok(summary)
+}
+
+/// Rebuilds the payload the worker handled from a stored row. Returns
+/// `Ok(None)` for rows stored under the `unknown` module. Purchases, service
+/// updates and tier (de)activations are never written to `blockchain_events`,
+/// so they cannot be replayed. The package's protocol version is not stored
+/// and is left at 0.
+pub fn stored_payload(row: &BlockchainEvent) -> Result<Option<EventPayload>> {
+    let Some(event) = decode_stored_event(&row.module, &row.event_type, &row.event_data)? else {
+        return Ok(None);
+    };
+
+    Ok(Some(EventPayload {
+        event,
+        tx_digest: row.transaction_digest.clone(),
+        event_index: row.event_index.unwrap_or_default() as u64,
+        checkpoint: row.checkpoint_number as u64,
+        protocol_version: 0,
+        package_id: Some(row.package_id.clone()),
+    }))
+}
+
+fn decode_stored_event(
+    module: &str,
+    event_type: &str,
+    data: &Value,
+) -> Result<Option<ProtocolEvent>> {
+    let data = data.clone();
+    let event = match (module, event_type) {
+        ("registry", "ProviderRegistered") => {
+            ProtocolEvent::ProviderRegistered(serde_json::from_value::<ProviderRegistered>(data)?)
+        }
+        ("registry", "ServiceCreated") => {
+            ProtocolEvent::ServiceCreated(serde_json::from_value::<ServiceCreated>(data)?)
+        }
+        ("registry", "TierAddedToService") => {
+            ProtocolEvent::TierAddedToService(serde_json::from_value::<TierAddedToService>(data)?)
+        }
+        ("registry", "TierRemovedFromService") => ProtocolEvent::TierRemovedFromService(
+            serde_json::from_value::<TierRemovedFromService>(data)?,
+        ),
+        ("pricing", "TierCreated") => {
+            ProtocolEvent::TierCreated(serde_json::from_value::<TierCreated>(data)?)
+        }
+        ("pricing", "TierPriceUpdated") => {
+            ProtocolEvent::TierPriceUpdated(serde_json::from_value::<TierPriceUpdated>(data)?)
+        }
+        ("payments", "QuotaConsumed") => {
+            ProtocolEvent::QuotaConsumed(serde_json::from_value::<QuotaConsumed>(data)?)
+        }
+        _ => return Ok(None),
+    };
+
+    Ok(Some(event))
+}
@@ -0,0 +1,95 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    hash::{DefaultHasher, Hash, Hasher},
+};
+
+use crate::events::types::ProtocolEvent;
+
+/// Picks the worker shard for each event so that an event always runs after
+/// the ones that created what it touches: a service after its provider, a
+/// tier after its service, a purchase after its tier, quota usage after its
+/// purchase. Unrelated providers are handled in parallel.
+///
+/// Services, tiers and entitlements stay on the shard of their parent while
+/// they are among the last `MAX_OWNERS` created. Older ones were handled long
+/// ago, like those created before the router started, so their rows are
+/// already committed and any shard will do.
+pub struct ShardRouter {
+    shards: usize,
+    /// Shard of the most recently created services, tiers and entitlements
+    owners: HashMap<String, usize>,
+    /// `owners` keys, oldest first
+    created: VecDeque<String>,
+}
+
+/// Far more creations than the shard buffers hold, so an ID is only
+/// forgotten once its creation is committed
+const MAX_OWNERS: usize = 100_000;
+
+impl ShardRouter {
+    pub fn new(shards: usize) -> Self {
+        Self {
+            shards: shards.max(1),
+            owners: HashMap::new(),
+            created: VecDeque::new(),
+        }
+    }
+
+    pub fn route(&mut self, event: &ProtocolEvent) -> usize {
+        if self.shards == 1 {
+            return 0;
+        }
+
+        match event {
+            ProtocolEvent::ProviderRegistered(e) => self.shard_of(&e.profile_id.bytes.to_string()),
+            ProtocolEvent::ServiceCreated(e) => {
+                let shard = self.shard_of(&e.provider.bytes.to_string());
+                self.assign(e.service_id.bytes.to_string(), shard)
+            }
+            ProtocolEvent::ServiceUpdated(e) => self.shard_of(&e.service_id.bytes.to_string()),
+            ProtocolEvent::ServiceDeactivated(e) => self.shard_of(&e.service_id.bytes.to_string()),
+            ProtocolEvent::ServiceReactivated(e) => self.shard_of(&e.service_id.bytes.to_string()),
+            ProtocolEvent::TierAddedToService(e) => self.shard_of(&e.service_id.bytes.to_string()),
+            ProtocolEvent::TierRemovedFromService(e) => {
+                self.shard_of(&e.service_id.bytes.to_string())
+            }
+            ProtocolEvent::TierCreated(e) => {
+                let shard = self.shard_of(&e.service_id.bytes.to_string());
+                self.assign(e.tier_id.bytes.to_string(), shard)
+            }
+            ProtocolEvent::TierPriceUpdated(e) => self.shard_of(&e.tier_id.bytes.to_string()),
+            ProtocolEvent::TierDeactivated(e) => self.shard_of(&e.tier_id.bytes.to_string()),
+            ProtocolEvent::TierReactivated(e) => self.shard_of(&e.tier_id.bytes.to_string()),
+            ProtocolEvent::EntitlementPurchased(e) => {
+                let shard = self.shard_of(&e.tier_id.bytes.to_string());
+                self.assign(e.entitlement_id.bytes.to_string(), shard)
+            }
+            ProtocolEvent::EntitlementToppedUp(e) => {
+                self.shard_of(&e.entitlement_id.bytes.to_string())
+            }
+            ProtocolEvent::QuotaConsumed(e) => self.shard_of(&e.entitlement_id.bytes.to_string()),
+        }
+    }
+
+    fn shard_of(&self, id: &str) -> usize {
+        if let Some(shard) = self.owners.get(id) {
+            return *shard;
+        }
+
+        let mut hasher = DefaultHasher::new();
+        id.hash(&mut hasher);
+        (hasher.finish() % self.shards as u64) as usize
+    }
+
+    fn assign(&mut self, id: String, shard: usize) -> usize {
+        if self.owners.insert(id.clone(), shard).is_none() {
+            self.created.push_back(id);
+        }
+        while self.created.len() > MAX_OWNERS {
+            if let Some(oldest) = self.created.pop_front() {
+                self.owners.remove(&oldest);
+            }
+        }
+        shard
+    }
+}
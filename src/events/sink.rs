@@ -0,0 +1,63 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::Serialize;
+
+use crate::events::types::{EventPayload, ProtocolEvent};
+
+/// Somewhere outside Postgres that indexed events are copied to, such as a
+/// Kafka topic. The worker publishes each event once its transaction commits;
+/// a sink that fails is logged and counted, and never holds indexing back.
+#[async_trait]
+pub trait EventSink: Send + Sync {
+    /// Short name used in logs and the `sink` metric label
+    fn name(&self) -> &'static str;
+
+    async fn publish(&self, record: &SinkRecord<'_>) -> Result<()>;
+}
+
+/// What a sink receives for each event: the decoded event together with
+/// where it was found on chain
+#[derive(Debug, Serialize)]
+pub struct SinkRecord<'a> {
+    /// The event's `module::Name` label
+    pub event_type: &'static str,
+    pub checkpoint: u64,
+    pub tx_digest: Option<&'a str>,
+    pub event_index: u64,
+    pub protocol_version: u64,
+    pub event: &'a ProtocolEvent,
+}
+
+impl<'a> SinkRecord<'a> {
+    pub fn new(payload: &'a EventPayload) -> Self {
+        Self {
+            event_type: payload.event.label(),
+            checkpoint: payload.checkpoint,
+            tx_digest: payload.tx_digest.as_deref(),
+            event_index: payload.event_index,
+            protocol_version: payload.protocol_version,
+            event: &payload.event,
+        }
+    }
+
+    /// ID of the provider, service, tier or entitlement the event is about,
+    /// so consumers can keep one object's events together and in order
+    pub fn subject_id(&self) -> String {
+        match self.event {
+            ProtocolEvent::ProviderRegistered(e) => e.profile_id.bytes.to_string(),
+            ProtocolEvent::ServiceCreated(e) => e.service_id.bytes.to_string(),
+            ProtocolEvent::ServiceUpdated(e) => e.service_id.bytes.to_string(),
+            ProtocolEvent::ServiceDeactivated(e) => e.service_id.bytes.to_string(),
+            ProtocolEvent::ServiceReactivated(e) => e.service_id.bytes.to_string(),
+            ProtocolEvent::TierAddedToService(e) => e.service_id.bytes.to_string(),
+            ProtocolEvent::TierRemovedFromService(e) => e.service_id.bytes.to_string(),
+            ProtocolEvent::TierCreated(e) => e.tier_id.bytes.to_string(),
+            ProtocolEvent::TierPriceUpdated(e) => e.tier_id.bytes.to_string(),
+            ProtocolEvent::TierDeactivated(e) => e.tier_id.bytes.to_string(),
+            ProtocolEvent::TierReactivated(e) => e.tier_id.bytes.to_string(),
+            ProtocolEvent::EntitlementPurchased(e) => e.entitlement_id.bytes.to_string(),
+            ProtocolEvent::EntitlementToppedUp(e) => e.entitlement_id.bytes.to_string(),
+            ProtocolEvent::QuotaConsumed(e) => e.entitlement_id.bytes.to_string(),
+        }
+    }
+}
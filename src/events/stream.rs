@@ -0,0 +1,118 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use tokio::sync::broadcast;
+
+use crate::{
+    db::repository::Repository,
+    events::{
+        sink::{EventSink, SinkRecord},
+        types::ProtocolEvent,
+    },
+};
+
+/// Events buffered per subscriber before a slow one starts missing them
+const STREAM_BUFFER: usize = 1024;
+
+/// An indexed event as sent to `/events/stream` subscribers
+#[derive(Debug, Clone)]
+pub struct StreamedEvent {
+    /// The event's `module::Name` label
+    pub event_type: &'static str,
+    /// `None` if the event's service or tier is not indexed
+    pub provider_id: Option<String>,
+    /// `<checkpoint>:<event_index>`
+    pub id: String,
+    /// The `SinkRecord` as JSON, with `provider_id` added
+    pub data: Arc<str>,
+}
+
+/// Fans indexed events out to the API's server-sent event subscribers. It
+/// runs as one of the worker's sinks, so subscribers only see events once
+/// they are committed, and does nothing while no one is subscribed.
+pub struct EventStream {
+    repo: Arc<Repository>,
+    tx: broadcast::Sender<StreamedEvent>,
+}
+
+impl EventStream {
+    pub fn new(repo: Arc<Repository>) -> Self {
+        let (tx, _) = broadcast::channel(STREAM_BUFFER);
+        Self { repo, tx }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<StreamedEvent> {
+        self.tx.subscribe()
+    }
+
+    /// The provider the event belongs to. Read after the event's transaction
+    /// commits, so the rows it created are visible.
+    async fn provider_of(&self, event: &ProtocolEvent) -> Result<Option<String>> {
+        let service_id = match event {
+            ProtocolEvent::ProviderRegistered(e) => {
+                return Ok(Some(e.profile_id.bytes.to_string()));
+            }
+            ProtocolEvent::ServiceCreated(e) => return Ok(Some(e.provider.bytes.to_string())),
+            ProtocolEvent::ServiceUpdated(e) => e.service_id.bytes.to_string(),
+            ProtocolEvent::ServiceDeactivated(e) => e.service_id.bytes.to_string(),
+            ProtocolEvent::ServiceReactivated(e) => e.service_id.bytes.to_string(),
+            ProtocolEvent::TierAddedToService(e) => e.service_id.bytes.to_string(),
+            ProtocolEvent::TierRemovedFromService(e) => e.service_id.bytes.to_string(),
+            ProtocolEvent::EntitlementPurchased(e) => e.service_id.bytes.to_string(),
+            ProtocolEvent::EntitlementToppedUp(e) => e.service_id.bytes.to_string(),
+            ProtocolEvent::TierCreated(e) => e.service_id.bytes.to_string(),
+            ProtocolEvent::TierPriceUpdated(e) => {
+                self.tier_service(&e.tier_id.bytes.to_string()).await?
+            }
+            ProtocolEvent::TierDeactivated(e) => {
+                self.tier_service(&e.tier_id.bytes.to_string()).await?
+            }
+            ProtocolEvent::TierReactivated(e) => {
+                self.tier_service(&e.tier_id.bytes.to_string()).await?
+            }
+            ProtocolEvent::QuotaConsumed(e) => {
+                let entitlement = self
+                    .repo
+                    .get_entitlement(&e.entitlement_id.bytes.to_string())
+                    .await?;
+                return Ok(entitlement.map(|e| e.provider_id));
+            }
+        };
+
+        let service = self.repo.get_service(&service_id).await?;
+        Ok(service.map(|s| s.provider_id))
+    }
+
+    /// ID of the service that created a tier, or an empty ID if it is unknown
+    async fn tier_service(&self, tier_id: &str) -> Result<String> {
+        let tier = self.repo.get_tier(tier_id).await?;
+        Ok(tier.map(|t| t.service_id).unwrap_or_default())
+    }
+}
+
+#[async_trait]
+impl EventSink for EventStream {
+    fn name(&self) -> &'static str {
+        "stream"
+    }
+
+    async fn publish(&self, record: &SinkRecord<'_>) -> Result<()> {
+        if self.tx.receiver_count() == 0 {
+            return Ok(());
+        }
+
+        let provider_id = self.provider_of(record.event).await?;
+        let mut data = serde_json::to_value(record)?;
+        data["provider_id"] = serde_json::json!(provider_id);
+
+        // Fails only when the last subscriber left meanwhile
+        let _ = self.tx.send(StreamedEvent {
+            event_type: record.event_type,
+            provider_id,
+            id: format!("{}:{}", record.checkpoint, record.event_index),
+            data: data.to_string().into(),
+        });
+        Ok(())
+    }
+}
@@ -27,6 +27,18 @@ pub struct ServiceUpdated {
     pub timestamp: u64,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServiceDeactivated {
+    pub service_id: ID,
+    pub timestamp: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServiceReactivated {
+    pub service_id: ID,
+    pub timestamp: u64,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TierCreated {
     pub tier_id: ID,
@@ -82,10 +94,25 @@ pub struct EntitlementPurchased {
     pub inner: EntitlementConfig,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EntitlementToppedUp {
+    pub entitlement_id: ID,
+    pub buyer: SuiAddress,
+    pub service_id: ID,
+    pub tier_id: ID,
+    pub price_paid: u64,
+    pub units_added: u64,
+    pub timestamp: u64,
+    /// The entitlement after the top-up, with what remains on chain
+    pub inner: EntitlementConfig,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct QuotaConsumed {
     pub entitlement_id: ID,
     pub amount: u64,
+    /// The entitlement after the consumption, with what remains on chain
+    pub inner: EntitlementConfig,
     pub timestamp: u64,
 }
 
@@ -93,7 +120,17 @@ pub struct QuotaConsumed {
 pub struct EventPayload {
     pub event: ProtocolEvent,
     pub tx_digest: Option<String>,
+    /// Position of the event within its transaction; with `tx_digest` it
+    /// identifies the event across reprocessing
+    #[serde(default)]
+    pub event_index: u64,
     pub checkpoint: u64,
+    /// Protocol version of the package that emitted the event
+    #[serde(default)]
+    pub protocol_version: u64,
+    /// ID of the package version that emitted the event, when known
+    #[serde(default)]
+    pub package_id: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -102,6 +139,10 @@ pub enum ProtocolEvent {
     ProviderRegistered(ProviderRegistered),
     ServiceCreated(ServiceCreated),
     ServiceUpdated(ServiceUpdated),
+    ServiceDeactivated(ServiceDeactivated),
+    ServiceReactivated(ServiceReactivated),
+    TierAddedToService(TierAddedToService),
+    TierRemovedFromService(TierRemovedFromService),
     // Pricing
     TierCreated(TierCreated),
     TierPriceUpdated(TierPriceUpdated),
@@ -109,6 +150,41 @@ pub enum ProtocolEvent {
     TierReactivated(TierReactivated),
     // Payments
     EntitlementPurchased(EntitlementPurchased),
+    EntitlementToppedUp(EntitlementToppedUp),
+    QuotaConsumed(QuotaConsumed),
+}
+
+impl ProtocolEvent {
+    /// The event's `module::Name` label, as matched by `EventFilter`
+    pub fn label(&self) -> &'static str {
+        match self {
+            ProtocolEvent::ProviderRegistered(_) => "registry::ProviderRegistered",
+            ProtocolEvent::ServiceCreated(_) => "registry::ServiceCreated",
+            ProtocolEvent::ServiceUpdated(_) => "registry::ServiceUpdated",
+            ProtocolEvent::ServiceDeactivated(_) => "registry::ServiceDeactivated",
+            ProtocolEvent::ServiceReactivated(_) => "registry::ServiceReactivated",
+            ProtocolEvent::TierAddedToService(_) => "registry::TierAddedToService",
+            ProtocolEvent::TierRemovedFromService(_) => "registry::TierRemovedFromService",
+            ProtocolEvent::TierCreated(_) => "pricing::TierCreated",
+            ProtocolEvent::TierPriceUpdated(_) => "pricing::TierPriceUpdated",
+            ProtocolEvent::TierDeactivated(_) => "pricing::TierDeactivated",
+            ProtocolEvent::TierReactivated(_) => "pricing::TierReactivated",
+            ProtocolEvent::EntitlementPurchased(_) => "payments::EntitlementPurchased",
+            ProtocolEvent::EntitlementToppedUp(_) => "payments::EntitlementToppedUp",
+            ProtocolEvent::QuotaConsumed(_) => "payments::QuotaConsumed",
+        }
+    }
+
+    /// Whether the event changes providers, services or tiers rather than
+    /// entitlements
+    pub fn is_catalog(&self) -> bool {
+        !matches!(
+            self,
+            ProtocolEvent::EntitlementPurchased(_)
+                | ProtocolEvent::EntitlementToppedUp(_)
+                | ProtocolEvent::QuotaConsumed(_)
+        )
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -1,48 +1,510 @@
-use std::sync::Arc;
+use std::{collections::HashSet, sync::Arc, time::Duration};
 
-use anyhow::Result;
-use redis::Client as RedisClient;
-use tokio::sync::mpsc::Receiver;
-use tracing::{error, info};
+use anyhow::{Result, bail};
+use sqlx::PgConnection;
+use tokio::{
+    sync::{
+        Notify,
+        mpsc::{self, Receiver},
+    },
+    task::JoinSet,
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, warn};
 
+use crate::alerting::{manager::AlertManager, types::Alert};
+use crate::backend::catalog_cache::CatalogCache;
+use crate::backend::provider_webhooks::ProviderWebhookPayload;
+use crate::backend::webhooks::BuyerNotifier;
+use crate::db::batch;
+use crate::db::models::{Entitlement, TierChange};
+use crate::events::metrics::INDEXER_METRICS;
+use crate::events::shard::ShardRouter;
+use crate::events::sink::{EventSink, SinkRecord};
 use crate::events::types::{EventPayload, ProtocolEvent};
 
 use crate::db::repository::Repository;
-use crate::pubsub::publisher::PubSubPublisher;
-use crate::utils::error::InfrapassError;
+use crate::pubsub::types::PubSubEvent;
+use crate::types::amount::{MistAmount, Units};
+
+/// Attempts per event before it is dead-lettered
+const MAX_ATTEMPTS: u32 = 5;
+
+/// Delay before the first retry, doubled after each failed attempt
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(200);
+const RETRY_MAX_DELAY: Duration = Duration::from_secs(5);
+
+/// Events queued per shard before the worker waits for that shard
+const SHARD_BUFFER: usize = 64;
+
+/// Work left after an event's transaction commits, so webhooks never see
+/// rows that are not visible yet
+enum FollowUp {
+    /// Carries the alert raised when the tier still had active entitlements
+    TierDeactivated(Option<Alert>),
+    Purchase(Entitlement),
+    /// A sidecar notification and provider webhooks were queued
+    TopUp,
+    /// A sidecar notification was queued in the outbox
+    Published,
+}
 
 pub struct EventWorker {
-    repo: Arc<Repository>,
-    pub publisher: PubSubPublisher,
+    handler: EventHandler,
     rx: Receiver<EventPayload>,
+    shards: usize,
+    /// Events a shard commits together, 1 for one transaction per event
+    batch_size: usize,
+    flush_interval: Duration,
+}
+
+/// Applies events to the database; shared by all of the worker's shards
+struct EventHandler {
+    repo: Arc<Repository>,
+    alerts: Arc<AlertManager>,
+    buyer_notifier: Option<BuyerNotifier>,
+    outbox: Option<Arc<Notify>>,
+    provider_webhooks: Option<Arc<Notify>>,
+    sinks: Vec<Arc<dyn EventSink>>,
+    catalog: Option<Arc<CatalogCache>>,
+    /// Handle events even if already recorded in `ingested_events`
+    reprocess: bool,
 }
 
 impl EventWorker {
-    pub async fn new(
+    pub fn new(
         repo: Arc<Repository>,
         rx: Receiver<EventPayload>,
-        redis_client: RedisClient,
-    ) -> Result<Self, InfrapassError> {
-        let publisher = PubSubPublisher::new(redis_client.clone()).await?;
-        Ok(Self {
-            repo,
+        alerts: Arc<AlertManager>,
+    ) -> Self {
+        Self {
+            handler: EventHandler {
+                repo,
+                alerts,
+                buyer_notifier: None,
+                outbox: None,
+                provider_webhooks: None,
+                sinks: Vec::new(),
+                catalog: None,
+                reprocess: false,
+            },
             rx,
-            publisher,
-        })
+            shards: 1,
+            batch_size: 1,
+            flush_interval: Duration::ZERO,
+        }
+    }
+
+    /// Sends purchase confirmations to buyer webhooks. Left unset for
+    /// backfills so historical purchases are not announced again.
+    pub fn with_buyer_notifier(mut self, notifier: BuyerNotifier) -> Self {
+        self.handler.buyer_notifier = Some(notifier);
+        self
+    }
+
+    /// Wakes the outbox publisher after each commit that queued a sidecar
+    /// notification. Without it, queued messages wait for its next poll.
+    pub fn with_outbox_waker(mut self, waker: Arc<Notify>) -> Self {
+        self.handler.outbox = Some(waker);
+        self
+    }
+
+    /// Queues purchase and tier deactivation notices for provider webhooks
+    /// and wakes the dispatcher after each commit that queued one. Left
+    /// unset for backfills so historical events are not announced again.
+    pub fn with_provider_webhooks(mut self, waker: Arc<Notify>) -> Self {
+        self.handler.provider_webhooks = Some(waker);
+        self
+    }
+
+    /// Copies every event to `sinks` once it is indexed. An event the
+    /// worker skips as already indexed is not published again.
+    pub fn with_sinks(mut self, sinks: Vec<Arc<dyn EventSink>>) -> Self {
+        self.handler.sinks = sinks;
+        self
+    }
+
+    /// Clears the API's catalog responses after each committed catalog
+    /// event
+    pub fn with_catalog_cache(mut self, catalog: Arc<CatalogCache>) -> Self {
+        self.handler.catalog = Some(catalog);
+        self
+    }
+
+    /// Handles events that were already ingested again instead of skipping
+    /// them, for replaying stored events after a projection fix. Leave the
+    /// notifiers and sinks unset alongside it so nothing is announced twice.
+    pub fn with_reprocess(mut self) -> Self {
+        self.handler.reprocess = true;
+        self
+    }
+
+    /// Handles events on `shards` concurrent tasks. Events are routed by
+    /// `ShardRouter`, so everything under one provider stays in order.
+    pub fn with_shards(mut self, shards: usize) -> Self {
+        self.shards = shards.max(1);
+        self
+    }
+
+    /// Commits up to `size` events per shard in one transaction, writing
+    /// their `blockchain_events` rows in one insert. A shard waits at most
+    /// `flush_interval` after the first event for the batch to fill. For
+    /// backfills; live events would wait for the interval.
+    pub fn with_batching(mut self, size: usize, flush_interval: Duration) -> Self {
+        self.batch_size = size.max(1);
+        self.flush_interval = flush_interval;
+        self
     }
 
-    pub async fn run(mut self) -> Result<()> {
-        info!("Event worker started");
-        while let Some(payload) = self.rx.recv().await {
-            if let Err(e) = self.handle_event(&payload).await {
-                error!("Failed to handle payload {:?}: {}", payload, e);
+    /// Handles events until the channel closes. Once `shutdown` is cancelled
+    /// the channel is closed to new events and the ones already buffered are
+    /// drained, each event's DB writes finishing before the next one of its
+    /// shard is taken.
+    pub async fn run(self, shutdown: CancellationToken) -> Result<()> {
+        let Self {
+            handler,
+            mut rx,
+            shards,
+            batch_size,
+            flush_interval,
+        } = self;
+        let handler = Arc::new(handler);
+        let mut router = ShardRouter::new(shards);
+
+        let mut senders = Vec::with_capacity(shards);
+        let mut tasks = JoinSet::new();
+        for _ in 0..shards {
+            let (tx, mut shard_rx) = mpsc::channel::<EventPayload>(SHARD_BUFFER);
+            let handler = handler.clone();
+            tasks.spawn(async move {
+                while let Some(payloads) =
+                    next_batch(&mut shard_rx, batch_size, flush_interval).await
+                {
+                    handler.ingest_batch(&payloads).await;
+                }
+            });
+            senders.push(tx);
+        }
+
+        info!(shards, "Event worker started");
+        loop {
+            let payload = tokio::select! {
+                biased;
+                payload = rx.recv() => payload,
+                _ = shutdown.cancelled(), if !rx.is_closed() => {
+                    info!(buffered = rx.len(), "Shutdown requested, draining events");
+                    rx.close();
+                    continue;
+                }
+            };
+            let Some(payload) = payload else {
+                break;
+            };
+
+            let shard = router.route(&payload.event);
+            if senders[shard].send(payload).await.is_err() {
+                bail!("Event worker shard {} stopped", shard);
             }
         }
+
+        // Closing the shard channels lets each shard finish what it holds
+        drop(senders);
+        while let Some(result) = tasks.join_next().await {
+            result?;
+        }
+
         info!("Event worker stopped");
         Ok(())
     }
+}
+
+/// Waits for an event, then takes more until `size` are held or
+/// `flush_interval` has passed. `None` once the channel is closed and empty.
+async fn next_batch(
+    rx: &mut Receiver<EventPayload>,
+    size: usize,
+    flush_interval: Duration,
+) -> Option<Vec<EventPayload>> {
+    let mut payloads = vec![rx.recv().await?];
+    let deadline = tokio::time::Instant::now() + flush_interval;
+    while payloads.len() < size {
+        match tokio::time::timeout_at(deadline, rx.recv()).await {
+            Ok(Some(payload)) => payloads.push(payload),
+            Ok(None) | Err(_) => break,
+        }
+    }
+    Some(payloads)
+}
+
+impl EventHandler {
+    /// Handles a batch in one transaction. If any of it fails the batch is
+    /// rolled back and each event is handled on its own, so a bad event
+    /// only dead-letters itself.
+    async fn ingest_batch(&self, payloads: &[EventPayload]) {
+        if let [payload] = payloads {
+            self.ingest_with_retry(payload).await;
+            return;
+        }
+
+        if let Err(e) = self.try_ingest_batch(payloads).await {
+            warn!(
+                events = payloads.len(),
+                error = %e,
+                "Failed to handle event batch, handling its events one by one"
+            );
+            for payload in payloads {
+                self.ingest_with_retry(payload).await;
+            }
+        }
+    }
+
+    /// `ingest` for many events: the same skips, one transaction, and the
+    /// event rows written by `Repository::flush_events` before it commits
+    async fn try_ingest_batch(&self, payloads: &[EventPayload]) -> Result<()> {
+        let mut seen = HashSet::new();
+        let mut pending = Vec::with_capacity(payloads.len());
+        for payload in payloads {
+            if let Some(tx_digest) = payload.tx_digest.as_deref() {
+                if !seen.insert((tx_digest, payload.event_index)) {
+                    continue;
+                }
+                if !self.reprocess
+                    && self
+                        .repo
+                        .is_event_ingested(tx_digest, payload.event_index)
+                        .await?
+                {
+                    continue;
+                }
+            }
+            pending.push(payload);
+        }
+
+        let mut tx = self.repo.begin().await?;
+        let follow_ups = batch::scope(async {
+            let mut follow_ups = Vec::with_capacity(pending.len());
+            for payload in &pending {
+                follow_ups.push(self.handle_event(&mut tx, payload).await?);
+                if let Some(tx_digest) = payload.tx_digest.as_deref() {
+                    self.repo
+                        .mark_event_ingested(
+                            &mut tx,
+                            tx_digest,
+                            payload.event_index,
+                            payload.checkpoint,
+                        )
+                        .await?;
+                }
+            }
+            self.repo.flush_events(&mut tx).await?;
+            anyhow::Ok(follow_ups)
+        })
+        .await?;
+        tx.commit().await?;
+
+        debug!(
+            events = pending.len(),
+            skipped = payloads.len() - pending.len(),
+            "Committed event batch"
+        );
+        for (payload, follow_up) in pending.into_iter().zip(follow_ups) {
+            self.after_commit(payload, follow_up).await;
+        }
+
+        Ok(())
+    }
+
+    /// Retries `ingest` with exponential backoff, since most failures are a
+    /// transient database error. Once the attempts run out the payload goes to
+    /// `failed_events` for `replay-dlq`.
+    async fn ingest_with_retry(&self, payload: &EventPayload) {
+        let mut delay = RETRY_BASE_DELAY;
+        for attempt in 1..=MAX_ATTEMPTS {
+            let Err(e) = self.ingest(payload).await else {
+                return;
+            };
+
+            if attempt == MAX_ATTEMPTS {
+                self.dead_letter(payload, &e).await;
+                return;
+            }
+
+            warn!(
+                event_type = payload.event.label(),
+                checkpoint = payload.checkpoint,
+                attempt,
+                retry_in_ms = delay.as_millis() as u64,
+                error = %e,
+                "Failed to handle event, retrying"
+            );
+            tokio::time::sleep(delay).await;
+            delay = (delay * 2).min(RETRY_MAX_DELAY);
+        }
+    }
+
+    async fn dead_letter(&self, payload: &EventPayload, e: &anyhow::Error) {
+        error!(
+            "Failed to handle payload {:?} after {} attempts: {}",
+            payload, MAX_ATTEMPTS, e
+        );
+        INDEXER_METRICS.events_dead_lettered.inc();
+
+        if let Err(store_err) = self
+            .repo
+            .store_failed_payload(payload, &e.to_string(), MAX_ATTEMPTS)
+            .await
+        {
+            error!("Failed to store dead-lettered payload: {}", store_err);
+        }
+    }
+
+    /// Handles each event at most once. Events already recorded in
+    /// `ingested_events` (a checkpoint replayed after a reconnect, an
+    /// overlapping backfill) are skipped unless the worker was built
+    /// `with_reprocess`. The event row, its projection, the `ingested_events`
+    /// marker, any sidecar notification queued in the outbox and any provider
+    /// webhook delivery commit in one transaction, so a crash leaves either
+    /// all of them or none, and an error here is always safe to retry. Event
+    /// sinks, alerts and buyer notifications run after the commit; if one
+    /// fails it is logged and the event is not handled again.
+    pub async fn ingest(&self, payload: &EventPayload) -> Result<()> {
+        let tx_digest = payload.tx_digest.as_deref();
+
+        if let Some(tx_digest) = tx_digest.filter(|_| !self.reprocess) {
+            if self
+                .repo
+                .is_event_ingested(tx_digest, payload.event_index)
+                .await?
+            {
+                debug!(
+                    tx_digest = %tx_digest,
+                    event_index = payload.event_index,
+                    "Skipping already ingested event"
+                );
+                return Ok(());
+            }
+        }
+
+        let mut tx = self.repo.begin().await?;
+        let follow_up = self.handle_event(&mut tx, payload).await?;
+        if let Some(tx_digest) = tx_digest {
+            self.repo
+                .mark_event_ingested(&mut tx, tx_digest, payload.event_index, payload.checkpoint)
+                .await?;
+        }
+        tx.commit().await?;
+
+        self.after_commit(payload, follow_up).await;
+
+        Ok(())
+    }
+
+    async fn after_commit(&self, payload: &EventPayload, follow_up: Option<FollowUp>) {
+        if let Some(catalog) = &self.catalog {
+            if payload.event.is_catalog() {
+                catalog.invalidate_all();
+            }
+        }
+        self.publish_to_sinks(payload).await;
+
+        if let Some(follow_up) = follow_up {
+            if let Err(e) = self.follow_up(follow_up).await {
+                warn!(
+                    event_type = payload.event.label(),
+                    error = %e,
+                    "Event indexed but its notifications failed"
+                );
+            }
+        }
+    }
+
+    /// Publishes a committed event to every sink. Failures are logged and
+    /// counted only, since the event is already indexed.
+    async fn publish_to_sinks(&self, payload: &EventPayload) {
+        if self.sinks.is_empty() {
+            return;
+        }
+
+        let record = SinkRecord::new(payload);
+        for sink in &self.sinks {
+            match sink.publish(&record).await {
+                Ok(()) => INDEXER_METRICS
+                    .sink_published
+                    .with_label_values(&[sink.name()])
+                    .inc(),
+                Err(e) => {
+                    INDEXER_METRICS
+                        .sink_failures
+                        .with_label_values(&[sink.name()])
+                        .inc();
+                    warn!(
+                        sink = sink.name(),
+                        event_type = record.event_type,
+                        checkpoint = record.checkpoint,
+                        error = %e,
+                        "Failed to publish event to sink"
+                    );
+                }
+            }
+        }
+    }
+
+    fn wake_outbox(&self) {
+        if let Some(outbox) = &self.outbox {
+            outbox.notify_one();
+        }
+    }
+
+    /// Queues `payload` for the provider's webhooks, unless they are disabled
+    async fn queue_provider_webhooks(
+        &self,
+        conn: &mut PgConnection,
+        payload: ProviderWebhookPayload,
+    ) -> Result<()> {
+        if self.provider_webhooks.is_some() {
+            self.repo.enqueue_provider_webhooks(conn, &payload).await?;
+        }
+        Ok(())
+    }
 
-    pub async fn handle_event(&self, payload: &EventPayload) -> Result<()> {
+    fn wake_provider_webhooks(&self) {
+        if let Some(dispatcher) = &self.provider_webhooks {
+            dispatcher.notify_one();
+        }
+    }
+
+    async fn follow_up(&self, follow_up: FollowUp) -> Result<()> {
+        match follow_up {
+            FollowUp::TierDeactivated(alert) => {
+                self.wake_provider_webhooks();
+                if let Some(alert) = alert {
+                    self.alerts.raise(alert).await;
+                }
+            }
+            FollowUp::Purchase(ent) => {
+                self.wake_outbox();
+                self.wake_provider_webhooks();
+                if let Some(notifier) = &self.buyer_notifier {
+                    notifier.purchase_confirmed(&ent).await;
+                }
+            }
+            FollowUp::TopUp => {
+                self.wake_outbox();
+                self.wake_provider_webhooks();
+            }
+            FollowUp::Published => self.wake_outbox(),
+        }
+
+        Ok(())
+    }
+
+    /// Applies one event's writes on `conn` and returns what to do once they
+    /// are committed
+    async fn handle_event(
+        &self,
+        conn: &mut PgConnection,
+        payload: &EventPayload,
+    ) -> Result<Option<FollowUp>> {
         match &payload.event {
             ProtocolEvent::ProviderRegistered(e) => {
                 let profile_id = e.profile_id.bytes.to_string();
@@ -50,9 +512,12 @@ impl EventWorker {
 
                 self.repo
                     .store_event(
+                        conn,
                         &payload.event,
                         payload.checkpoint,
                         payload.tx_digest.clone(),
+                        payload.event_index,
+                        payload.package_id.as_deref(),
                     )
                     .await?;
 
@@ -62,7 +527,7 @@ impl EventWorker {
                     "Provider registered"
                 );
 
-                Ok(())
+                Ok(None)
             }
 
             ProtocolEvent::ServiceCreated(e) => {
@@ -71,9 +536,12 @@ impl EventWorker {
 
                 self.repo
                     .store_event(
+                        conn,
                         &payload.event,
                         payload.checkpoint,
                         payload.tx_digest.clone(),
+                        payload.event_index,
+                        payload.package_id.as_deref(),
                     )
                     .await?;
 
@@ -83,7 +551,7 @@ impl EventWorker {
                     "Service created"
                 );
 
-                Ok(())
+                Ok(None)
             }
 
             ProtocolEvent::ServiceUpdated(e) => {
@@ -92,7 +560,7 @@ impl EventWorker {
                 let service_id = e.service_id.bytes.to_string();
                 let updated_service = self
                     .repo
-                    .update_service_metadata(&service_id, &metadata_uri)
+                    .update_service_metadata(conn, &service_id, &metadata_uri)
                     .await?;
 
                 info!(
@@ -101,7 +569,77 @@ impl EventWorker {
                     "Service updated"
                 );
 
-                Ok(())
+                Ok(None)
+            }
+
+            ProtocolEvent::ServiceDeactivated(e) => {
+                let service_id = e.service_id.bytes.to_string();
+                let service = self
+                    .repo
+                    .set_service_active(conn, &service_id, false)
+                    .await?;
+                info!(service_id = ?service.service_id, "Service deactivated");
+
+                Ok(None)
+            }
+
+            ProtocolEvent::ServiceReactivated(e) => {
+                let service_id = e.service_id.bytes.to_string();
+                let service = self
+                    .repo
+                    .set_service_active(conn, &service_id, true)
+                    .await?;
+                info!(service_id = ?service.service_id, "Service reactivated");
+
+                Ok(None)
+            }
+
+            ProtocolEvent::TierAddedToService(e) => {
+                self.repo
+                    .store_event(
+                        conn,
+                        &payload.event,
+                        payload.checkpoint,
+                        payload.tx_digest.clone(),
+                        payload.event_index,
+                        payload.package_id.as_deref(),
+                    )
+                    .await?;
+
+                let service_id = e.service_id.bytes.to_string();
+                let tier_id = e.tier_id.bytes.to_string();
+                self.repo
+                    .add_service_tier(conn, &service_id, &tier_id)
+                    .await?;
+                info!(service_id = %service_id, tier_id = %tier_id, "Tier added to service");
+
+                Ok(None)
+            }
+
+            ProtocolEvent::TierRemovedFromService(e) => {
+                self.repo
+                    .store_event(
+                        conn,
+                        &payload.event,
+                        payload.checkpoint,
+                        payload.tx_digest.clone(),
+                        payload.event_index,
+                        payload.package_id.as_deref(),
+                    )
+                    .await?;
+
+                let service_id = e.service_id.bytes.to_string();
+                let tier_id = e.tier_id.bytes.to_string();
+                if !self
+                    .repo
+                    .remove_service_tier(conn, &service_id, &tier_id)
+                    .await?
+                {
+                    warn!(service_id = %service_id, tier_id = %tier_id, "Removed tier was not listed");
+                }
+                info!(service_id = %service_id, tier_id = %tier_id, "Tier removed from service");
+
+                Ok(None)
             }
 
             ProtocolEvent::TierCreated(e) => {
@@ -109,9 +647,12 @@ impl EventWorker {
 
                 self.repo
                     .store_event(
+                        conn,
                         &payload.event,
                         payload.checkpoint,
                         payload.tx_digest.clone(),
+                        payload.event_index,
+                        payload.package_id.as_deref(),
                     )
                     .await?;
 
@@ -123,14 +664,36 @@ impl EventWorker {
                     "Tier created"
                 );
 
-                Ok(())
+                Ok(None)
             }
 
             ProtocolEvent::TierPriceUpdated(e) => {
+                self.repo
+                    .store_event(
+                        conn,
+                        &payload.event,
+                        payload.checkpoint,
+                        payload.tx_digest.clone(),
+                        payload.event_index,
+                        payload.package_id.as_deref(),
+                    )
+                    .await?;
+
                 let tier_id = e.tier_id.bytes.to_string();
+                let new_price = MistAmount::new(e.new_price);
+                self.repo
+                    .record_tier_change(
+                        conn,
+                        &tier_id,
+                        TierChange::Price,
+                        Some(new_price),
+                        payload,
+                        e.timestamp,
+                    )
+                    .await?;
                 let tier = self
                     .repo
-                    .update_tier_price(&tier_id, e.new_price as i64)
+                    .update_tier_price(conn, &tier_id, new_price)
                     .await?;
                 info!(
                     tier_id = ?tier.tier_id,
@@ -138,27 +701,66 @@ impl EventWorker {
                     "Tier price updated"
                 );
 
-                Ok(())
+                Ok(None)
             }
 
             ProtocolEvent::TierDeactivated(e) => {
                 let tier_id = e.tier_id.bytes.to_string();
-                let tier = self.repo.deactivate_tier(&tier_id).await?;
+                self.repo
+                    .record_tier_change(
+                        conn,
+                        &tier_id,
+                        TierChange::Deactivated,
+                        None,
+                        payload,
+                        e.timestamp,
+                    )
+                    .await?;
+                let tier = self.repo.deactivate_tier(conn, &tier_id).await?;
                 info!(tier_id = ?tier.tier_id, "Tier deactivated");
 
-                Ok(())
+                let active = self
+                    .repo
+                    .count_active_entitlements_for_tier(conn, &tier_id)
+                    .await?;
+
+                if let Some(service) = self.repo.lookup_service(conn, &tier.service_id).await? {
+                    self.queue_provider_webhooks(
+                        conn,
+                        ProviderWebhookPayload::tier_deactivated(
+                            &service.provider_id,
+                            &tier,
+                            active,
+                        ),
+                    )
+                    .await?;
+                }
+
+                let alert = (active > 0)
+                    .then(|| Alert::tier_deactivated(&tier_id, &tier.service_id, active));
+                Ok(Some(FollowUp::TierDeactivated(alert)))
             }
 
             ProtocolEvent::TierReactivated(e) => {
                 let tier_id = e.tier_id.bytes.to_string();
-                let tier = self.repo.reactivate_tier(&tier_id).await?;
+                self.repo
+                    .record_tier_change(
+                        conn,
+                        &tier_id,
+                        TierChange::Reactivated,
+                        None,
+                        payload,
+                        e.timestamp,
+                    )
+                    .await?;
+                let tier = self.repo.reactivate_tier(conn, &tier_id).await?;
                 info!(tier_id = ?tier.tier_id, "Tier reactivated");
 
-                Ok(())
+                Ok(None)
             }
 
             ProtocolEvent::EntitlementPurchased(e) => {
-                let ent = self.repo.create_entitlement(&e).await?;
+                let ent = self.repo.create_entitlement(conn, e).await?;
                 info!(
                     entitlement_id = ?e.entitlement_id,
                     buyer = %e.buyer,
@@ -168,9 +770,98 @@ impl EventWorker {
                     "Entitlement purchased"
                 );
 
-                self.publisher.publish_refresh(&ent.provider_id, e).await?;
+                self.repo
+                    .enqueue_pubsub(conn, &ent.provider_id, &PubSubEvent::refresh(e)?)
+                    .await?;
+                self.queue_provider_webhooks(conn, ProviderWebhookPayload::purchased(&ent))
+                    .await?;
+
+                Ok(Some(FollowUp::Purchase(ent)))
+            }
+
+            ProtocolEvent::EntitlementToppedUp(e) => {
+                self.repo
+                    .store_event(
+                        conn,
+                        &payload.event,
+                        payload.checkpoint,
+                        payload.tx_digest.clone(),
+                        payload.event_index,
+                        payload.package_id.as_deref(),
+                    )
+                    .await?;
+
+                let entitlement_id = e.entitlement_id.bytes.to_string();
+                let Some(ent) = self
+                    .repo
+                    .top_up_entitlement(
+                        conn,
+                        &entitlement_id,
+                        Units::new(e.units_added),
+                        MistAmount::new(e.price_paid),
+                    )
+                    .await?
+                else {
+                    warn!(entitlement_id = %entitlement_id, "Top-up for unknown entitlement");
+                    return Ok(None);
+                };
+
+                info!(
+                    entitlement_id = %entitlement_id,
+                    buyer = %e.buyer,
+                    units_added = e.units_added,
+                    price_paid = e.price_paid,
+                    units = %ent.units,
+                    "Entitlement topped up"
+                );
+
+                self.repo
+                    .enqueue_pubsub(conn, &ent.provider_id, &PubSubEvent::topped_up(&ent))
+                    .await?;
+                self.queue_provider_webhooks(
+                    conn,
+                    ProviderWebhookPayload::topped_up(&ent, e.units_added, e.price_paid),
+                )
+                .await?;
+
+                Ok(Some(FollowUp::TopUp))
+            }
+
+            ProtocolEvent::QuotaConsumed(e) => {
+                self.repo
+                    .store_event(
+                        conn,
+                        &payload.event,
+                        payload.checkpoint,
+                        payload.tx_digest.clone(),
+                        payload.event_index,
+                        payload.package_id.as_deref(),
+                    )
+                    .await?;
+
+                let entitlement_id = e.entitlement_id.bytes.to_string();
+                let Some(ent) = self
+                    .repo
+                    .reconcile_consumption(conn, &entitlement_id, &e.inner)
+                    .await?
+                else {
+                    warn!(entitlement_id = %entitlement_id, "Quota consumed for unknown entitlement");
+                    return Ok(None);
+                };
+
+                info!(
+                    entitlement_id = %entitlement_id,
+                    amount = e.amount,
+                    quota = ?ent.quota,
+                    units = %ent.units,
+                    "Quota consumed on chain"
+                );
+
+                self.repo
+                    .enqueue_pubsub(conn, &ent.provider_id, &PubSubEvent::reconcile_quota(&ent))
+                    .await?;
 
-                Ok(())
+                Ok(Some(FollowUp::Published))
             }
         }
     }
@@ -1,4 +1,6 @@
 pub mod sidecar;
+pub mod alerting;
+pub mod api_types;
 pub mod backend;
 pub mod client;
 pub mod cmd;
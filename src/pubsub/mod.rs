@@ -1,3 +1,4 @@
+pub mod outbox;
 pub mod publisher;
 pub mod subscriber;
 pub mod types;
@@ -0,0 +1,119 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::Result;
+use tokio::sync::Notify;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, warn};
+
+use crate::{db::repository::Repository, pubsub::publisher::PubSubPublisher};
+
+/// Messages published per outbox transaction
+const BATCH_SIZE: i64 = 100;
+
+/// How often the outbox is checked when nobody wakes the publisher
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Longest wait between attempts at a message Redis keeps rejecting
+const MAX_RETRY_SECS: i64 = 60;
+
+/// Publishes the sidecar notifications the event worker queued in
+/// `pubsub_outbox`, oldest first. A message that fails stays queued and is
+/// retried with backoff; later messages wait behind it so each channel keeps
+/// its order. With several servers, one drains the outbox at a time.
+pub struct OutboxPublisher {
+    repo: Arc<Repository>,
+    publisher: Arc<PubSubPublisher>,
+    wake: Arc<Notify>,
+}
+
+impl OutboxPublisher {
+    pub fn new(repo: Arc<Repository>, publisher: Arc<PubSubPublisher>) -> Self {
+        Self {
+            repo,
+            publisher,
+            wake: Arc::new(Notify::new()),
+        }
+    }
+
+    /// Notified by the worker after a commit that queued messages, so they
+    /// go out without waiting for the next poll
+    pub fn waker(&self) -> Arc<Notify> {
+        self.wake.clone()
+    }
+
+    /// Drains the outbox until `shutdown` is cancelled, then makes one last
+    /// attempt at what is left
+    pub async fn run(self, shutdown: CancellationToken) -> Result<()> {
+        info!("Outbox publisher started");
+        loop {
+            match self.drain().await {
+                // A full batch means more may be waiting
+                Ok(published) if published == BATCH_SIZE as usize => continue,
+                Ok(_) => {}
+                Err(e) => error!("Failed to drain the pub/sub outbox: {}", e),
+            }
+
+            tokio::select! {
+                _ = self.wake.notified() => {}
+                _ = tokio::time::sleep(POLL_INTERVAL) => {}
+                _ = shutdown.cancelled() => break,
+            }
+        }
+
+        if let Err(e) = self.drain().await {
+            warn!("Outbox not drained before shutdown: {}", e);
+        }
+        info!("Outbox publisher stopped");
+        Ok(())
+    }
+
+    /// Publishes one batch of due messages, returning how many went out
+    async fn drain(&self) -> Result<usize> {
+        let mut tx = self.repo.begin().await?;
+        if !self.repo.try_lock_outbox(&mut tx).await? {
+            return Ok(0);
+        }
+        let messages = self.repo.list_pending_outbox(&mut tx, BATCH_SIZE).await?;
+
+        let mut published = 0;
+        for message in messages {
+            if !message.due {
+                break;
+            }
+
+            match self
+                .publisher
+                .publish_message(&message.channel, &message.message)
+                .await
+            {
+                Ok(()) => {
+                    self.repo.mark_outbox_published(&mut tx, message.id).await?;
+                    published += 1;
+                }
+                Err(e) => {
+                    let retry_in_secs = 2i64
+                        .saturating_pow(message.attempts as u32)
+                        .min(MAX_RETRY_SECS);
+                    warn!(
+                        id = message.id,
+                        channel = %message.channel,
+                        attempts = message.attempts + 1,
+                        retry_in_secs,
+                        error = %e,
+                        "Failed to publish outbox message"
+                    );
+                    self.repo
+                        .record_outbox_failure(&mut tx, message.id, &e.to_string(), retry_in_secs)
+                        .await?;
+                    break;
+                }
+            }
+        }
+        tx.commit().await?;
+
+        if published > 0 {
+            debug!(published, "Published outbox messages");
+        }
+        Ok(published)
+    }
+}
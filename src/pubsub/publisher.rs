@@ -1,9 +1,9 @@
 use redis::{Client as RedisClient, aio::MultiplexedConnection};
 use tracing::info;
+use uuid::Uuid;
 
 use crate::{
-    events::types::EntitlementPurchased,
-    pubsub::types::{EntitlementUpdateEvent, PubSubAction, PubSubEvent, TierEntitlement},
+    pubsub::types::{MaintenanceNotice, PubSubAction, PubSubEvent},
     utils::{error::InfrapassError, get_channel, logs_fmt::abbrev},
 };
 
@@ -21,44 +21,100 @@ impl PubSubPublisher {
         })
     }
 
-    pub async fn publish_refresh(
+    pub async fn publish_maintenance(
         &self,
         provider_id: &str,
-        event: &EntitlementPurchased,
+        service_id: &str,
+        notice: MaintenanceNotice,
     ) -> Result<(), InfrapassError> {
         let channel = get_channel(provider_id);
-        let tier_type = event.inner.type_u8();
-        let tier_id = event.tier_id.bytes.to_string();
-        let ent_id = event.entitlement_id.bytes.to_string();
-        let user = event.buyer.to_string();
-        let service = event.service_id.bytes.to_string();
-        let inner = TierEntitlement::from_u8(
-            &tier_type,
-            &event.inner.expires_at(),
-            &event.inner.quota(),
-            &event.inner.units(),
-        )?;
-        let ent = EntitlementUpdateEvent::new(ent_id, tier_id, tier_type, inner);
         let pubsub_event = PubSubEvent {
-            user,
-            service,
-            action: PubSubAction::Refresh(ent),
+            user: String::new(),
+            service: service_id.to_string(),
+            action: PubSubAction::Maintenance(notice),
         };
+        self.publish(&channel, &pubsub_event).await?;
 
-        let message = serde_json::to_string(&pubsub_event)?;
+        info!(
+            event = "maintenance.published",
+            provider_id = %abbrev(&provider_id),
+            service = %abbrev(&service_id),
+        );
+        Ok(())
+    }
+
+    pub async fn publish_maintenance_cancelled(
+        &self,
+        provider_id: &str,
+        service_id: &str,
+        window_id: Uuid,
+    ) -> Result<(), InfrapassError> {
+        let channel = get_channel(provider_id);
+        let pubsub_event = PubSubEvent {
+            user: String::new(),
+            service: service_id.to_string(),
+            action: PubSubAction::MaintenanceCancelled(window_id),
+        };
+        self.publish(&channel, &pubsub_event).await?;
+
+        info!(
+            event = "maintenance.cancelled",
+            provider_id = %abbrev(&provider_id),
+            service = %abbrev(&service_id),
+        );
+        Ok(())
+    }
+
+    /// Tells a provider's sidecars, and the backend's validation cache, to
+    /// drop what they cached for a buyer's access to a service
+    pub async fn publish_invalidate(
+        &self,
+        provider_id: &str,
+        user_address: &str,
+        service_id: &str,
+    ) -> Result<(), InfrapassError> {
+        let channel = get_channel(provider_id);
+        let pubsub_event = PubSubEvent {
+            user: user_address.to_string(),
+            service: service_id.to_string(),
+            action: PubSubAction::Invalidate,
+        };
+        self.publish(&channel, &pubsub_event).await?;
+
+        info!(
+            event = "invalidate.published",
+            provider_id = %abbrev(&provider_id),
+            user = %abbrev(&user_address),
+            service = %abbrev(&service_id),
+        );
+        Ok(())
+    }
+
+    /// Whether Redis answers on the publishing connection
+    pub async fn ping(&self) -> Result<(), InfrapassError> {
+        let mut conn = self.redis.clone();
+        let _: String = redis::cmd("PING").query_async(&mut conn).await?;
+        Ok(())
+    }
+
+    async fn publish(&self, channel: &str, event: &PubSubEvent) -> Result<(), InfrapassError> {
+        self.publish_message(channel, &serde_json::to_string(event)?)
+            .await
+    }
+
+    /// Publishes an already serialized `PubSubEvent`, as stored in the outbox
+    pub async fn publish_message(
+        &self,
+        channel: &str,
+        message: &str,
+    ) -> Result<(), InfrapassError> {
         let mut conn = self.redis.clone();
         let _: i64 = redis::cmd("PUBLISH")
-            .arg(&channel)
+            .arg(channel)
             .arg(message)
             .query_async(&mut conn)
             .await?;
 
-        info!(
-            event = "ent.published",
-            provider_id = %abbrev(&provider_id),
-            user = %abbrev(&pubsub_event.user),
-            service = %abbrev(&pubsub_event.service),
-        );
         Ok(())
     }
 }
@@ -1,4 +1,4 @@
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc, time::Duration};
 
 use futures::StreamExt;
 use redis::aio::PubSub;
@@ -6,10 +6,13 @@ use tracing::{info, warn};
 
 use crate::{
     sidecar::{error::ProxyError, proxy::ProxyState},
-    pubsub::types::{PubSubAction, PubSubEvent},
+    pubsub::types::{MaintenanceNotice, PubSubAction, PubSubEvent},
     utils::{get_channel, logs_fmt::abbrev},
 };
 
+/// Wait before resubscribing after the Pub/Sub connection drops
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
 pub struct PubSubSubscriber {
     state: Arc<ProxyState>,
 }
@@ -19,9 +22,59 @@ impl PubSubSubscriber {
         Self { state }
     }
 
+    /// Listens until the process exits, resubscribing whenever the
+    /// connection drops
     pub async fn run(&self) -> Result<(), ProxyError> {
-        run_pubsub_listener(self.state.clone()).await
+        loop {
+            if let Err(e) = run_pubsub_listener(self.state.clone()).await {
+                warn!(error = %e, "Pub/Sub listener failed");
+            }
+            tokio::time::sleep(RECONNECT_DELAY).await;
+        }
+    }
+}
+
+/// Loads the provider's active and upcoming maintenance windows from the
+/// backend. Windows are otherwise only heard of over Pub/Sub, so those
+/// scheduled while the sidecar was down or disconnected would go unenforced.
+async fn seed_maintenance(state: &ProxyState) {
+    let windows = match state
+        .validator
+        .upcoming_maintenance(&state.cfg.provider_id)
+        .await
+    {
+        Ok(windows) => windows,
+        Err(e) => {
+            warn!(error = %e, "Failed to load maintenance windows");
+            return;
+        }
+    };
+
+    let mut by_service: HashMap<String, Vec<MaintenanceNotice>> = HashMap::new();
+    for window in windows {
+        by_service
+            .entry(window.service_id)
+            .or_default()
+            .push(MaintenanceNotice {
+                id: window.id,
+                starts_at: window.starts_at,
+                ends_at: window.ends_at,
+                reason: window.reason,
+            });
+    }
+
+    let mut stored = 0;
+    for (service, notices) in &by_service {
+        match state.replace_maintenance(service, notices).await {
+            Ok(()) => stored += notices.len(),
+            Err(e) => warn!(
+                service = %abbrev(service),
+                error = %e,
+                "Failed to store maintenance windows"
+            ),
+        }
     }
+    info!(windows = stored, "Maintenance windows loaded");
 }
 
 pub async fn run_pubsub_listener(state: Arc<ProxyState>) -> Result<(), ProxyError> {
@@ -33,6 +86,9 @@ pub async fn run_pubsub_listener(state: Arc<ProxyState>) -> Result<(), ProxyErro
 
     info!(channel = %abbrev(&channel), "Subscribed");
 
+    // After subscribing, so no window scheduled in between is missed
+    seed_maintenance(&state).await;
+
     let mut stream = pubsub_conn.on_message();
 
     while let Some(msg) = stream.next().await {
@@ -93,6 +149,50 @@ pub async fn run_pubsub_listener(state: Arc<ProxyState>) -> Result<(), ProxyErro
                     "Cache refreshed"
                 );
             }
+            PubSubAction::ReconcileQuota(remaining) => {
+                match state
+                    .cap_quota(&event.user, &event.service, remaining)
+                    .await
+                {
+                    Ok(Some(current)) => info!(
+                        event = "quota.reconciled",
+                        user = %abbrev(&event.user),
+                        service = %abbrev(&event.service),
+                        onchain = remaining,
+                        current,
+                        "Quota reconciled"
+                    ),
+                    // Not cached; the next validation seeds it from the backend
+                    Ok(None) => {}
+                    Err(e) => warn!(
+                        user = %abbrev(&event.user),
+                        service = %abbrev(&event.service),
+                        error = %e,
+                        "Failed to reconcile quota"
+                    ),
+                }
+            }
+            PubSubAction::Maintenance(notice) => {
+                let _ = state.set_maintenance(&event.service, &notice).await;
+
+                info!(
+                    event = "maintenance.scheduled",
+                    service = %abbrev(&event.service),
+                    starts_at = %notice.starts_at,
+                    ends_at = %notice.ends_at,
+                    "Maintenance window stored"
+                );
+            }
+            PubSubAction::MaintenanceCancelled(id) => {
+                let _ = state.remove_maintenance(&event.service, id).await;
+
+                info!(
+                    event = "maintenance.cancelled",
+                    service = %abbrev(&event.service),
+                    window_id = %id,
+                    "Maintenance window removed"
+                );
+            }
         }
     }
 
@@ -1,7 +1,14 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
-use crate::{sidecar::cache::CachedEntitlement, utils::error::InfrapassError};
+use uuid::Uuid;
+
+use crate::{
+    db::models::{Entitlement, MaintenanceWindow},
+    events::types::EntitlementPurchased,
+    sidecar::cache::CachedEntitlement,
+    utils::error::InfrapassError,
+};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct PubSubEvent {
@@ -10,10 +17,99 @@ pub struct PubSubEvent {
     pub action: PubSubAction,
 }
 
+impl PubSubEvent {
+    /// Tells the sidecars about a new purchase so it is served without a
+    /// validator round trip
+    pub fn refresh(event: &EntitlementPurchased) -> Result<Self, InfrapassError> {
+        let tier_type = event.inner.type_u8();
+        let inner = TierEntitlement::from_u8(
+            &tier_type,
+            &event.inner.expires_at(),
+            &event.inner.quota(),
+            &event.inner.units(),
+        )?;
+        let ent = EntitlementUpdateEvent::new(
+            event.entitlement_id.bytes.to_string(),
+            event.tier_id.bytes.to_string(),
+            tier_type,
+            inner,
+        );
+
+        Ok(Self {
+            user: event.buyer.to_string(),
+            service: event.service_id.bytes.to_string(),
+            action: PubSubAction::Refresh(ent),
+        })
+    }
+
+    /// Tells the sidecars the units a usage-based entitlement has after a
+    /// top-up, net of usage charged but not yet settled
+    pub fn topped_up(ent: &Entitlement) -> Self {
+        let inner = TierEntitlement::UsageBased {
+            units: ent.units.get(),
+        };
+        let ent_update =
+            EntitlementUpdateEvent::new(ent.entitlement_id.clone(), ent.tier_id.clone(), 2, inner);
+
+        Self {
+            user: ent.buyer.clone(),
+            service: ent.service_id.clone(),
+            action: PubSubAction::Refresh(ent_update),
+        }
+    }
+
+    /// Tells the sidecar what remains of an entitlement after a settlement,
+    /// so usage it never saw is reflected in its counter
+    pub fn reconcile_quota(ent: &Entitlement) -> Self {
+        let remaining = match ent.quota {
+            Some(quota) => quota.get(),
+            None => ent.units.get(),
+        };
+
+        Self {
+            user: ent.buyer.clone(),
+            service: ent.service_id.clone(),
+            action: PubSubAction::ReconcileQuota(remaining),
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub enum PubSubAction {
     Invalidate,
     Refresh(EntitlementUpdateEvent),
+    /// Remaining quota or units after an on-chain settlement; counters above
+    /// it are lowered, lower ones are left alone
+    ReconcileQuota(u64),
+    /// Service-wide; `user` is empty
+    Maintenance(MaintenanceNotice),
+    /// Service-wide; `user` is empty
+    MaintenanceCancelled(Uuid),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MaintenanceNotice {
+    pub id: Uuid,
+    pub starts_at: DateTime<Utc>,
+    pub ends_at: DateTime<Utc>,
+    pub reason: Option<String>,
+}
+
+impl MaintenanceNotice {
+    pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+        self.starts_at <= now && now < self.ends_at
+    }
+}
+
+impl From<&MaintenanceWindow> for MaintenanceNotice {
+    fn from(window: &MaintenanceWindow) -> Self {
+        Self {
+            id: window.id,
+            starts_at: window.starts_at,
+            ends_at: window.ends_at,
+            reason: window.reason.clone(),
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -60,6 +156,10 @@ impl EntitlementUpdateEvent {
                     })
                     .transpose()?,
                 cached_at: Some(chrono::Utc::now()),
+                tier_name: None,
+                price: None,
+                coin_type: None,
+                replaced_tier: None,
             }),
             1 => Ok(CachedEntitlement {
                 id: self.ent_id.clone(),
@@ -77,6 +177,10 @@ impl EntitlementUpdateEvent {
                     })
                     .transpose()?,
                 cached_at: Some(chrono::Utc::now()),
+                tier_name: None,
+                price: None,
+                coin_type: None,
+                replaced_tier: None,
             }),
             2 => Ok(CachedEntitlement {
                 id: self.ent_id.clone(),
@@ -86,6 +190,10 @@ impl EntitlementUpdateEvent {
                 tier_type: self.tier_type,
                 expires_at: None,
                 cached_at: Some(chrono::Utc::now()),
+                tier_name: None,
+                price: None,
+                coin_type: None,
+                replaced_tier: None,
             }),
             _ => Err(InfrapassError::Other(format!("invalid tier type"))),
         }
@@ -0,0 +1,100 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use tracing::{info, warn};
+
+use crate::sidecar::metrics::METRICS;
+
+#[derive(Debug)]
+enum BreakerState {
+    Closed {
+        failures: u32,
+    },
+    Open {
+        until: Instant,
+    },
+    /// A single probe call is on its way
+    HalfOpen {
+        since: Instant,
+    },
+}
+
+/// Stops calling a dependency after `threshold` consecutive failures. Once
+/// `cooldown` has passed one probe call is let through: its success closes
+/// the breaker and its failure opens it for another `cooldown`. A probe that
+/// never reports back, e.g. because its request was cancelled, is replaced
+/// after another `cooldown`.
+pub struct CircuitBreaker {
+    threshold: u32,
+    cooldown: Duration,
+    state: Mutex<BreakerState>,
+}
+
+impl CircuitBreaker {
+    /// A `threshold` of 0 never opens
+    pub fn new(threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            threshold,
+            cooldown,
+            state: Mutex::new(BreakerState::Closed { failures: 0 }),
+        }
+    }
+
+    /// Whether a call may go out now
+    pub fn allow(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        let probe = match *state {
+            BreakerState::Closed { .. } => return true,
+            BreakerState::Open { until } => now >= until,
+            BreakerState::HalfOpen { since } => now >= since + self.cooldown,
+        };
+
+        if probe {
+            *state = BreakerState::HalfOpen { since: now };
+            true
+        } else {
+            METRICS.validator_breaker_rejections.inc();
+            false
+        }
+    }
+
+    pub fn record_success(&self) {
+        let mut state = self.state.lock().unwrap();
+        if !matches!(*state, BreakerState::Closed { .. }) {
+            info!("Validator API recovered, circuit closed");
+            METRICS.validator_breaker_open.set(0);
+        }
+        *state = BreakerState::Closed { failures: 0 };
+    }
+
+    pub fn record_failure(&self) {
+        if self.threshold == 0 {
+            return;
+        }
+
+        let mut state = self.state.lock().unwrap();
+        let failures = match *state {
+            BreakerState::Closed { failures } => failures + 1,
+            // A failed probe opens it again right away
+            BreakerState::HalfOpen { .. } => self.threshold,
+            BreakerState::Open { .. } => return,
+        };
+
+        if failures >= self.threshold {
+            warn!(
+                failures,
+                cooldown_ms = self.cooldown.as_millis() as u64,
+                "Validator API failing, circuit open"
+            );
+            METRICS.validator_breaker_open.set(1);
+            *state = BreakerState::Open {
+                until: Instant::now() + self.cooldown,
+            };
+        } else {
+            *state = BreakerState::Closed { failures };
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Duration, Utc};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -10,21 +10,44 @@ pub struct CachedEntitlement {
     pub tier_type: u8,
     pub expires_at: Option<DateTime<Utc>>,
     pub cached_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub tier_name: Option<String>,
+    #[serde(default)]
+    pub price: Option<u64>,
+    #[serde(default)]
+    pub coin_type: Option<String>,
+    /// Retired tier the entitlement was bought on, when `tier` replaces it
+    #[serde(default)]
+    pub replaced_tier: Option<String>,
 }
 
 impl CachedEntitlement {
-    pub fn allowed(&self) -> bool {
+    /// `grace` extends expiry to tolerate clock skew against the chain
+    pub fn allowed(&self, grace: Duration) -> bool {
+        let deadline = Utc::now() - grace;
         match self.tier_type {
-            0 => self.expires_at.map_or(false, |exp| exp > Utc::now()),
+            0 => self.expires_at.map_or(false, |exp| exp > deadline),
             1 => {
                 self.quota.map_or(false, |q| q > 0)
-                    && self.expires_at.map_or(false, |exp| exp > Utc::now())
+                    && self.expires_at.map_or(false, |exp| exp > deadline)
             }
             2 => self.units.map_or(false, |u| u > 0),
             _ => false,
         }
     }
 
+    /// True when the entitlement has expired but is still within `grace`
+    pub fn in_grace(&self, grace: Duration) -> bool {
+        let now = Utc::now();
+        self.expires_at
+            .is_some_and(|exp| exp <= now && exp > now - grace)
+    }
+
+    /// Whether requests are charged against a quota counter
+    pub fn metered(&self) -> bool {
+        self.tier_type != 0 && (self.quota.is_some() || self.units.is_some())
+    }
+
     pub fn units(&self) -> Option<u64> {
         self.units
     }
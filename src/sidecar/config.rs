@@ -1,9 +1,21 @@
 use anyhow::Result;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
-use crate::sidecar::{error::ProxyError, middleware::AuthMode};
+use crate::{
+    api_types::validator::MAX_RECORD_USAGE_BATCH,
+    sidecar::{
+        cost::parse_cost_rules,
+        error::ProxyError,
+        headers::parse_header_templates,
+        middleware::AuthMode,
+        sampling::parse_tier_types,
+        upstream::UpstreamHttpVersion,
+        upstream_auth::{UpstreamAuth, UpstreamAuthMode},
+        websocket::WsMetering,
+    },
+};
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SidecarConfig {
     /// Port the sidecar listens on (default 8080)
     #[serde(default = "default_port")]
@@ -14,6 +26,61 @@ pub struct SidecarConfig {
     /// Your provider's actual service URL — sidecar forwards here after validation
     pub upstream_url: String,
 
+    /// Protocol used to reach the upstream: auto (HTTP/2 when negotiated over
+    /// TLS), http1, or http2 (prior knowledge, also for cleartext h2c)
+    #[serde(default)]
+    pub upstream_http_version: UpstreamHttpVersion,
+
+    /// Idle connections kept open to the upstream for reuse. With HTTP/2 a
+    /// single connection carries many concurrent requests.
+    #[serde(default = "default_upstream_max_idle_connections")]
+    pub upstream_max_idle_connections: usize,
+
+    /// Seconds an idle upstream connection is kept before being closed
+    #[serde(default = "default_upstream_idle_timeout_secs")]
+    pub upstream_idle_timeout_secs: u64,
+
+    /// Time allowed to open an upstream connection, in ms. 0 waits for the
+    /// whole request timeout.
+    #[serde(default = "default_upstream_connect_timeout_ms")]
+    pub upstream_connect_timeout_ms: u64,
+
+    /// TCP keep-alive probe interval on upstream connections, in seconds.
+    /// 0 disables.
+    #[serde(default = "default_upstream_tcp_keepalive_secs")]
+    pub upstream_tcp_keepalive_secs: u64,
+
+    /// HTTP/2 ping interval on upstream connections, including idle ones,
+    /// in seconds. 0 disables.
+    #[serde(default)]
+    pub upstream_http2_keepalive_secs: u64,
+
+    /// Grow HTTP/2 flow-control windows with the measured bandwidth, for
+    /// large responses over high-latency links
+    #[serde(default)]
+    pub upstream_http2_adaptive_window: bool,
+
+    /// Credentials the sidecar adds when calling the upstream: none, header,
+    /// basic or oauth2 (client credentials). Clients never see them.
+    #[serde(default)]
+    pub upstream_auth: UpstreamAuthMode,
+
+    /// Header name and value for the header mode, e.g. "X-Api-Key"
+    pub upstream_auth_header: Option<String>,
+    pub upstream_auth_value: Option<String>,
+
+    /// Username and password for the basic mode
+    pub upstream_auth_username: Option<String>,
+    pub upstream_auth_password: Option<String>,
+
+    /// Token endpoint and client for the oauth2 mode. The token is cached
+    /// and refreshed shortly before it expires.
+    pub upstream_oauth_token_url: Option<String>,
+    pub upstream_oauth_client_id: Option<String>,
+    pub upstream_oauth_client_secret: Option<String>,
+    /// Space-separated scopes to request, if the token endpoint needs them
+    pub upstream_oauth_scope: Option<String>,
+
     /// Your Sui protocol's validation API
     pub validator_api_url: String,
 
@@ -23,6 +90,15 @@ pub struct SidecarConfig {
     /// The provider ID this sidecar is protecting (registered in your protocol)
     pub provider_id: String,
 
+    /// Name this instance reports in heartbeats (default: the host name)
+    #[serde(default = "default_instance_id")]
+    pub instance_id: String,
+
+    /// How often version and config fingerprint are reported to the
+    /// validator API, in seconds. 0 disables heartbeats.
+    #[serde(default = "default_heartbeat_interval_secs")]
+    pub heartbeat_interval_secs: u64,
+
     #[serde(default)]
     pub auth_mode: AuthMode,
 
@@ -34,14 +110,42 @@ pub struct SidecarConfig {
     #[serde(default = "default_cache_ttl_ms")]
     pub cache_ttl_ms: u64,
 
-    /// Max cache entries (one per unique user address)
+    /// Max entries in the in-memory entitlement cache (one per user and
+    /// service)
     #[serde(default = "default_cache_max_entries")]
     pub cache_max_entries: u64,
 
+    /// How long an entitlement read from Redis is kept in memory
+    /// (milliseconds), sparing hot users a Redis round trip. Pub/sub
+    /// invalidations clear it right away; changes cached by other sidecars
+    /// show once it expires. 0 disables.
+    #[serde(default = "default_local_cache_ttl_ms")]
+    pub local_cache_ttl_ms: u64,
+
     /// Per-request timeout in ms before sidecar returns 504
     #[serde(default = "default_timeout_ms")]
     pub request_timeout_ms: u64,
 
+    /// Pass request and response bodies through as they arrive instead of
+    /// reading them whole first, for large uploads and streamed responses
+    #[serde(default = "default_stream_bodies")]
+    pub stream_bodies: bool,
+
+    /// Largest body read whole when `stream_bodies` is off, in bytes. Larger
+    /// requests get 413 and larger upstream responses 502.
+    #[serde(default = "default_max_buffer_bytes")]
+    pub max_buffer_bytes: usize,
+
+    /// How an open WebSocket connection is charged after its handshake,
+    /// which costs what a request would: message (each text or binary
+    /// message from the client) or minute (each started minute connected)
+    #[serde(default)]
+    pub ws_metering: WsMetering,
+
+    /// Units charged per WebSocket message or minute
+    #[serde(default = "default_ws_cost")]
+    pub ws_cost: u64,
+
     /// Header name where clients send their Sui wallet address
     /// e.g. "X-Sui-Address"
     #[serde(default = "default_address_header")]
@@ -52,18 +156,92 @@ pub struct SidecarConfig {
     #[serde(default = "default_service_header")]
     pub service_header: String,
 
-    /// Header name for passing the cost of the request (optional; depends on your pricing model)
+    /// Header name where clients declare the cost of a request, read only
+    /// with `trust_cost_header`
     /// e.g. "X-Request-Cost"
-    /// If not provided, sidecar assumes a default cost of 1 for all requests
     #[serde(default = "default_cost_header")]
     pub cost_header: String,
 
+    /// Take a request's cost from `cost_header` when the client sends one.
+    /// Off by default, since clients could under-report their own usage.
+    #[serde(default)]
+    pub trust_cost_header: bool,
+
+    /// Cost of a request by method and path, as `[METHOD] /path=cost` rules
+    /// separated by `;`, first match wins. `*` matches one path segment and a
+    /// trailing `**` the rest of the path, e.g.
+    /// "POST /v1/completions=10;/v1/embeddings/*=2". Requests no rule
+    /// matches cost 1.
+    #[serde(default)]
+    pub cost_rules: String,
+
     /// If true, on validator API failure → ALLOW request (fail open)
     /// If false, on failure → REJECT request (fail closed)  
     /// Fail closed is safer; fail open is better for availability
     #[serde(default)]
     pub fail_open: bool,
 
+    /// Consecutive validator API failures (unreachable or 5xx) after which
+    /// validations fail at once, taking the `fail_open` decision without
+    /// waiting on the API. 0 disables the circuit breaker.
+    #[serde(default = "default_validator_breaker_failures")]
+    pub validator_breaker_failures: u32,
+
+    /// How long the circuit stays open before a single probe validation is
+    /// let through, in milliseconds
+    #[serde(default = "default_validator_breaker_cooldown_ms")]
+    pub validator_breaker_cooldown_ms: u64,
+
+    /// Ask the validator for tier name, price and coin type, forwarded
+    /// upstream as `X-Infrapass-Tier-*` headers
+    #[serde(default)]
+    pub tier_detail: bool,
+
+    /// Seconds an entitlement is still accepted after its expiry, to tolerate
+    /// clock skew between this host and the chain
+    #[serde(default)]
+    pub expiry_grace_secs: u64,
+
+    /// Headers added to every proxied response, as `Name=template` pairs
+    /// separated by `;`. Templates may use {user_address}, {service_id},
+    /// {entitlement_id}, {tier}, {tier_name}, {tier_type}, {replaced_tier},
+    /// {remaining} and {expires_at}, e.g. "X-Powered-By=Infrapass;X-Usage-Remaining={remaining}"
+    #[serde(default)]
+    pub response_headers: String,
+
+    /// Tier types served in sampling mode, comma separated (e.g. "1,2").
+    /// Their quota is decremented in local accumulators and flushed to Redis
+    /// every `sampling_flush_ms`, trading a small overshoot for far fewer
+    /// Redis calls and usage posts. Empty disables sampling.
+    #[serde(default)]
+    pub sampling_tier_types: String,
+
+    /// How often sampled usage is flushed to Redis and the validator API
+    #[serde(default = "default_sampling_flush_ms")]
+    pub sampling_flush_ms: u64,
+
+    /// Usage records posted together to the validator API, up to 100. 0
+    /// posts each proxied request's usage in its own call.
+    #[serde(default = "default_usage_batch_size")]
+    pub usage_batch_size: usize,
+
+    /// Longest a usage record waits for its batch to fill, in milliseconds
+    #[serde(default = "default_usage_flush_ms")]
+    pub usage_flush_ms: u64,
+
+    /// Shed load (503 + Retry-After) once the upstream's p99 latency over the
+    /// last 10s exceeds this many milliseconds. 0 disables.
+    #[serde(default)]
+    pub shed_p99_ms: u64,
+
+    /// Shed load once this many requests are waiting on the upstream. 0 disables.
+    #[serde(default)]
+    pub shed_max_in_flight: u64,
+
+    /// Retry-After sent with shed responses, in seconds
+    #[serde(default = "default_shed_retry_after_secs")]
+    pub shed_retry_after_secs: u64,
+
     /// Webhook URL to notify your provider when quota events occur
     pub provider_webhook_url: Option<String>,
 
@@ -96,8 +274,32 @@ impl SidecarConfig {
     }
 
     pub fn validate(&self) -> Result<(), ProxyError> {
+        parse_header_templates(&self.response_headers)?;
+        parse_cost_rules(&self.cost_rules)?;
+        parse_tier_types(&self.sampling_tier_types)?;
+        UpstreamAuth::from_config(self, reqwest::Client::new())?;
+        if self.sampling_flush_ms == 0 {
+            return Err(ProxyError::ConfigError(
+                "sampling_flush_ms must be greater than 0".to_string(),
+            ));
+        }
+        if self.usage_batch_size > MAX_RECORD_USAGE_BATCH {
+            return Err(ProxyError::ConfigError(format!(
+                "usage_batch_size must be at most {}",
+                MAX_RECORD_USAGE_BATCH
+            )));
+        }
+        if self.usage_batch_size > 0 && self.usage_flush_ms == 0 {
+            return Err(ProxyError::ConfigError(
+                "usage_flush_ms must be greater than 0".to_string(),
+            ));
+        }
         Ok(())
     }
+
+    pub fn expiry_grace(&self) -> chrono::Duration {
+        chrono::Duration::seconds(self.expiry_grace_secs as i64)
+    }
 }
 
 fn default_port() -> u16 {
@@ -109,9 +311,54 @@ fn default_cache_ttl_ms() -> u64 {
 fn default_cache_max_entries() -> u64 {
     10_000
 }
+fn default_local_cache_ttl_ms() -> u64 {
+    2_000
+}
+fn default_validator_breaker_failures() -> u32 {
+    5
+}
+fn default_validator_breaker_cooldown_ms() -> u64 {
+    5_000
+}
 fn default_timeout_ms() -> u64 {
     5_000
 }
+fn default_stream_bodies() -> bool {
+    true
+}
+fn default_max_buffer_bytes() -> usize {
+    10 * 1024 * 1024
+}
+fn default_ws_cost() -> u64 {
+    1
+}
+fn default_upstream_max_idle_connections() -> usize {
+    100
+}
+fn default_upstream_idle_timeout_secs() -> u64 {
+    90
+}
+fn default_upstream_connect_timeout_ms() -> u64 {
+    2_000
+}
+fn default_upstream_tcp_keepalive_secs() -> u64 {
+    60
+}
+fn default_heartbeat_interval_secs() -> u64 {
+    30
+}
+fn default_sampling_flush_ms() -> u64 {
+    100
+}
+fn default_usage_batch_size() -> usize {
+    50
+}
+fn default_usage_flush_ms() -> u64 {
+    200
+}
+fn default_shed_retry_after_secs() -> u64 {
+    5
+}
 fn default_address_header() -> String {
     "X-Infrapass-Address".to_string()
 }
@@ -123,3 +370,7 @@ fn default_cost_header() -> String {
 fn default_service_header() -> String {
     "X-Infrapass-Service-Id".to_string()
 }
+
+fn default_instance_id() -> String {
+    std::env::var("HOSTNAME").unwrap_or_else(|_| uuid::Uuid::new_v4().to_string())
+}
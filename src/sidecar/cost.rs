@@ -0,0 +1,102 @@
+use axum::http::Method;
+
+use crate::sidecar::error::ProxyError;
+
+/// Cost of a request no rule matches
+pub const DEFAULT_COST: u64 = 1;
+
+/// The cost of requests matching a method and path pattern
+#[derive(Debug, Clone)]
+pub struct CostRule {
+    /// Any method when unset
+    pub method: Option<Method>,
+    pub segments: Vec<String>,
+    pub cost: u64,
+}
+
+impl CostRule {
+    fn matches(&self, method: &Method, path: &str) -> bool {
+        if self.method.as_ref().is_some_and(|m| m != method) {
+            return false;
+        }
+
+        let mut parts = path.trim_matches('/').split('/').filter(|p| !p.is_empty());
+        for segment in &self.segments {
+            if segment == "**" {
+                return true;
+            }
+            match parts.next() {
+                Some(part) if segment == "*" || segment == part => {}
+                _ => return false,
+            }
+        }
+        parts.next().is_none()
+    }
+}
+
+/// Parses `[METHOD] /path=cost` rules separated by `;`, e.g.
+/// `POST /v1/completions=10;/v1/embeddings/*=2;GET /v1/models/**=0`. `*`
+/// matches one path segment and a trailing `**` the rest of the path.
+pub fn parse_cost_rules(spec: &str) -> Result<Vec<CostRule>, ProxyError> {
+    spec.split(';')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let (pattern, cost) = entry.rsplit_once('=').ok_or_else(|| {
+                ProxyError::ConfigError(format!(
+                    "cost rule '{}' must be [METHOD] /path=cost",
+                    entry
+                ))
+            })?;
+            let cost = cost.trim().parse::<u64>().map_err(|_| {
+                ProxyError::ConfigError(format!("invalid cost in cost rule '{}'", entry))
+            })?;
+
+            let (method, path) = match pattern.trim().split_once(char::is_whitespace) {
+                Some((method, path)) => {
+                    let method = Method::from_bytes(method.to_ascii_uppercase().as_bytes())
+                        .map_err(|_| {
+                            ProxyError::ConfigError(format!(
+                                "invalid method in cost rule '{}'",
+                                entry
+                            ))
+                        })?;
+                    (Some(method), path.trim())
+                }
+                None => (None, pattern.trim()),
+            };
+            if !path.starts_with('/') {
+                return Err(ProxyError::ConfigError(format!(
+                    "path in cost rule '{}' must start with /",
+                    entry
+                )));
+            }
+
+            let segments: Vec<String> = path
+                .split('/')
+                .filter(|s| !s.is_empty())
+                .map(str::to_string)
+                .collect();
+            if segments.iter().rev().skip(1).any(|s| s == "**") {
+                return Err(ProxyError::ConfigError(format!(
+                    "** must be the last segment in cost rule '{}'",
+                    entry
+                )));
+            }
+
+            Ok(CostRule {
+                method,
+                segments,
+                cost,
+            })
+        })
+        .collect()
+}
+
+/// The cost of the first rule matching the request, else `DEFAULT_COST`
+pub fn request_cost(rules: &[CostRule], method: &Method, path: &str) -> u64 {
+    rules
+        .iter()
+        .find(|rule| rule.matches(method, path))
+        .map_or(DEFAULT_COST, |rule| rule.cost)
+}
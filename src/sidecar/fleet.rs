@@ -0,0 +1,104 @@
+use std::{sync::Arc, time::Duration};
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tracing::{debug, warn};
+
+use crate::{
+    api_types::validator::SidecarHeartbeat,
+    sidecar::{config::SidecarConfig, proxy::ProxyState},
+};
+
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+pub const GIT_HASH: &str = env!("INFRAPASS_GIT_HASH");
+
+/// Config fields that only count as set or unset in the fingerprint
+const SECRET_FIELDS: &[&str] = &[
+    "redis_url",
+    "validator_api_key",
+    "auth_secret",
+    "upstream_auth_value",
+    "upstream_auth_password",
+    "upstream_oauth_client_secret",
+    "provider_webhook_secret",
+];
+
+/// Fields that differ between instances by design
+const INSTANCE_FIELDS: &[&str] = &["instance_id"];
+
+/// What this sidecar reports about itself on `/healthz`, in heartbeats and
+/// at startup
+#[derive(Debug, Clone, Serialize)]
+pub struct BuildInfo {
+    pub version: &'static str,
+    pub git_hash: &'static str,
+    pub config_hash: String,
+}
+
+impl BuildInfo {
+    pub fn new(cfg: &SidecarConfig) -> Self {
+        Self {
+            version: VERSION,
+            git_hash: GIT_HASH,
+            config_hash: config_fingerprint(cfg),
+        }
+    }
+}
+
+/// Short SHA-256 of the config with secrets reduced to whether they are set,
+/// so instances can be compared without exposing credentials
+pub fn config_fingerprint(cfg: &SidecarConfig) -> String {
+    let mut value = serde_json::to_value(cfg).unwrap_or_default();
+    if let Some(fields) = value.as_object_mut() {
+        for field in INSTANCE_FIELDS {
+            fields.remove(*field);
+        }
+        for field in SECRET_FIELDS {
+            if let Some(v) = fields.get_mut(*field) {
+                let set = !(v.is_null() || v.as_str() == Some(""));
+                *v = serde_json::Value::Bool(set);
+            }
+        }
+    }
+
+    // Object keys are sorted, so the same config always hashes the same
+    let digest = Sha256::digest(value.to_string().as_bytes());
+    hex::encode(&digest[..8])
+}
+
+/// Whether `version` is older than `latest`, comparing the numeric
+/// `major.minor.patch` parts and ignoring pre-release suffixes
+pub fn is_older_version(version: &str, latest: &str) -> bool {
+    fn parts(v: &str) -> Vec<u64> {
+        v.trim_start_matches('v')
+            .split(['-', '+'])
+            .next()
+            .unwrap_or("")
+            .split('.')
+            .map(|p| p.parse().unwrap_or(0))
+            .collect()
+    }
+
+    parts(version) < parts(latest)
+}
+
+/// Reports this instance to the validator API every `interval`, so the
+/// backend can list the provider's fleet and flag outdated sidecars
+pub async fn run_heartbeat(state: Arc<ProxyState>, interval: Duration) {
+    let heartbeat = SidecarHeartbeat {
+        provider_id: state.cfg.provider_id.clone(),
+        instance_id: state.cfg.instance_id.clone(),
+        version: state.build.version.to_string(),
+        git_hash: state.build.git_hash.to_string(),
+        config_hash: state.build.config_hash.clone(),
+    };
+
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        ticker.tick().await;
+        match state.validator.heartbeat(&heartbeat).await {
+            Ok(()) => debug!("Heartbeat sent"),
+            Err(e) => warn!(error = %e, "Heartbeat failed"),
+        }
+    }
+}
@@ -0,0 +1,80 @@
+use axum::{
+    body::Body,
+    extract::Request,
+    http::{
+        HeaderMap, HeaderValue, StatusCode,
+        header::{CONTENT_TYPE, RETRY_AFTER},
+    },
+    middleware::Next,
+    response::Response,
+};
+
+use crate::sidecar::proxy::DenyReason;
+
+/// Whether a request or response is gRPC, by its content type
+pub fn is_grpc(headers: &HeaderMap) -> bool {
+    headers
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/grpc"))
+}
+
+/// gRPC clients can't read an HTTP error, so for gRPC requests any response
+/// the sidecar answers itself becomes a trailers-only gRPC response, with the
+/// deny reason as `grpc-message`. Responses from a gRPC upstream pass as is.
+pub async fn grpc_status_middleware(req: Request, next: Next) -> Response {
+    if !is_grpc(req.headers()) {
+        return next.run(req).await;
+    }
+
+    let response = next.run(req).await;
+    if is_grpc(response.headers()) {
+        return response;
+    }
+
+    let status = response.status();
+    let message = match response.extensions().get::<DenyReason>() {
+        Some(DenyReason(reason)) => reason.clone(),
+        None => status.canonical_reason().unwrap_or("unknown").to_string(),
+    };
+
+    let mut grpc = Response::new(Body::empty());
+    let headers = grpc.headers_mut();
+    headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/grpc"));
+    headers.insert("grpc-status", HeaderValue::from(grpc_code(status)));
+    if let Ok(value) = HeaderValue::from_str(&encode_message(&message)) {
+        headers.insert("grpc-message", value);
+    }
+    if let Some(retry_after) = response.headers().get(RETRY_AFTER) {
+        headers.insert(RETRY_AFTER, retry_after.clone());
+    }
+    grpc
+}
+
+/// The gRPC status code for a sidecar response status
+fn grpc_code(status: StatusCode) -> u16 {
+    match status {
+        StatusCode::BAD_REQUEST => 3,   // INVALID_ARGUMENT
+        StatusCode::UNAUTHORIZED => 16, // UNAUTHENTICATED
+        StatusCode::FORBIDDEN => 7,     // PERMISSION_DENIED
+        StatusCode::NOT_FOUND => 12,    // UNIMPLEMENTED
+        StatusCode::REQUEST_TIMEOUT | StatusCode::GATEWAY_TIMEOUT => 4, // DEADLINE_EXCEEDED
+        StatusCode::PAYLOAD_TOO_LARGE | StatusCode::TOO_MANY_REQUESTS => 8, // RESOURCE_EXHAUSTED
+        StatusCode::INTERNAL_SERVER_ERROR => 13, // INTERNAL
+        StatusCode::BAD_GATEWAY | StatusCode::SERVICE_UNAVAILABLE => 14, // UNAVAILABLE
+        _ => 2,                         // UNKNOWN
+    }
+}
+
+/// Percent-encodes what `grpc-message` can't carry as is
+fn encode_message(message: &str) -> String {
+    let mut encoded = String::with_capacity(message.len());
+    for byte in message.bytes() {
+        if (0x20..=0x7e).contains(&byte) && byte != b'%' {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
@@ -0,0 +1,98 @@
+use axum::http::{HeaderMap, HeaderName, HeaderValue};
+use tracing::warn;
+
+use crate::sidecar::{cache::CachedEntitlement, error::ProxyError};
+
+/// A response header whose value is rendered per request from `{variable}`
+/// placeholders
+#[derive(Debug, Clone)]
+pub struct HeaderTemplate {
+    pub name: HeaderName,
+    pub template: String,
+}
+
+/// Values available to response header templates
+pub struct HeaderContext<'a> {
+    pub user_address: &'a str,
+    pub service_id: &'a str,
+    pub entitlement: &'a CachedEntitlement,
+    /// Quota or units left after this request, when known
+    pub remaining: Option<i64>,
+}
+
+impl HeaderContext<'_> {
+    fn lookup(&self, var: &str) -> Option<String> {
+        let ent = self.entitlement;
+        match var {
+            "user_address" => Some(self.user_address.to_string()),
+            "service_id" => Some(self.service_id.to_string()),
+            "entitlement_id" => Some(ent.id.clone()),
+            "tier" => Some(ent.tier.clone()),
+            "tier_type" => Some(ent.tier_type.to_string()),
+            "tier_name" => ent.tier_name.clone(),
+            "replaced_tier" => ent.replaced_tier.clone(),
+            "remaining" => self
+                .remaining
+                .map(|r| r.to_string())
+                .or_else(|| ent.quota.or(ent.units).map(|r| r.to_string())),
+            "expires_at" => ent.expires_at.map(|e| e.to_rfc3339()),
+            _ => None,
+        }
+    }
+}
+
+/// Parses `Name=template;Name=template`, e.g.
+/// `X-Powered-By=Infrapass;X-Usage-Remaining={remaining}`
+pub fn parse_header_templates(spec: &str) -> Result<Vec<HeaderTemplate>, ProxyError> {
+    spec.split(';')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let (name, template) = entry.split_once('=').ok_or_else(|| {
+                ProxyError::ConfigError(format!("response header '{}' must be Name=value", entry))
+            })?;
+            let name = HeaderName::try_from(name.trim()).map_err(|e| {
+                ProxyError::ConfigError(format!("invalid response header name '{}': {}", name, e))
+            })?;
+            Ok(HeaderTemplate {
+                name,
+                template: template.trim().to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Renders each template and inserts it into `headers`. Headers whose
+/// variables are unavailable for this request are skipped.
+pub fn apply_header_templates(
+    templates: &[HeaderTemplate],
+    ctx: &HeaderContext<'_>,
+    headers: &mut HeaderMap,
+) {
+    for tpl in templates {
+        let Some(rendered) = render(&tpl.template, ctx) else {
+            continue;
+        };
+        match HeaderValue::from_str(&rendered) {
+            Ok(value) => {
+                headers.insert(tpl.name.clone(), value);
+            }
+            Err(_) => warn!(header = %tpl.name, "Rendered header value is not valid"),
+        }
+    }
+}
+
+fn render(template: &str, ctx: &HeaderContext<'_>) -> Option<String> {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+        let end = rest[start..].find('}')? + start;
+        out.push_str(&ctx.lookup(&rest[start + 1..end])?);
+        rest = &rest[end + 1..];
+    }
+    out.push_str(rest);
+
+    Some(out)
+}
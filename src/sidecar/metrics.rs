@@ -1,13 +1,79 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
 use once_cell::sync::Lazy;
-use prometheus::{Counter, Histogram, HistogramOpts, Registry, TextEncoder};
+use prometheus::{
+    Counter, Histogram, HistogramOpts, IntCounterVec, IntGauge, Opts, Registry, TextEncoder,
+};
+
+/// A near-exhausted user/service pair not seen for this long leaves the
+/// gauge, e.g. once its entitlement expires or the buyer goes quiet
+const NEAR_EXHAUSTION_TTL: Duration = Duration::from_secs(600);
+
+/// Requests sweep stale pairs at most this often, scrapes always do
+const NEAR_EXHAUSTION_SWEEP: Duration = Duration::from_secs(60);
+
+/// Near-exhausted pairs, with when each was last seen
+struct NearExhaustion {
+    pairs: HashMap<String, Instant>,
+    swept: Instant,
+}
+
+impl NearExhaustion {
+    fn expire(&mut self) {
+        self.pairs
+            .retain(|_, seen| seen.elapsed() < NEAR_EXHAUSTION_TTL);
+        self.swept = Instant::now();
+    }
+}
+
+/// Outcome of the atomic quota check-and-decrement script.
+#[derive(Debug, Clone, Copy)]
+pub enum QuotaOutcome {
+    Allowed,
+    Exceeded,
+    NotReady,
+    UnknownTier,
+}
+
+impl QuotaOutcome {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            QuotaOutcome::Allowed => "allowed",
+            QuotaOutcome::Exceeded => "exceeded",
+            QuotaOutcome::NotReady => "not_ready",
+            QuotaOutcome::UnknownTier => "unknown_tier",
+        }
+    }
+}
 
 pub struct SidecarMetrics {
     pub requests_allowed: Counter,
     pub requests_denied: Counter,
     pub cache_hits: Counter,
     pub cache_misses: Counter,
+    pub local_cache_hits: Counter,
     pub validator_errors: Counter,
+    pub validator_breaker_open: IntGauge,
+    pub validator_breaker_rejections: Counter,
     pub request_duration: Histogram,
+    pub expiry_grace_applied: Counter,
+    pub quota_outcomes: IntCounterVec,
+    pub quota_near_exhaustion: IntGauge,
+    pub sampled_requests: Counter,
+    pub sampling_flushes: Counter,
+    pub usage_records_failed: Counter,
+    pub requests_shed: Counter,
+    pub shed_active: IntGauge,
+    pub upstream_in_flight: IntGauge,
+    pub upstream_responses: IntCounterVec,
+    pub upstream_connect_errors: Counter,
+    pub websocket_connections: IntGauge,
+    pub websocket_policy_closes: Counter,
+    near_exhaustion: Mutex<NearExhaustion>,
     registry: Registry,
 }
 
@@ -35,11 +101,26 @@ impl SidecarMetrics {
             "Entitlement cache misses",
         )
         .unwrap();
+        let local_cache_hits = Counter::new(
+            "infrapass_sidecar_local_cache_hits_total",
+            "Entitlement cache hits answered from memory without Redis",
+        )
+        .unwrap();
         let validator_errors = Counter::new(
             "infrapass_sidecar_validator_errors_total",
             "Validator API errors",
         )
         .unwrap();
+        let validator_breaker_open = IntGauge::new(
+            "infrapass_sidecar_validator_breaker_open",
+            "1 while the validator API circuit breaker is open or probing",
+        )
+        .unwrap();
+        let validator_breaker_rejections = Counter::new(
+            "infrapass_sidecar_validator_breaker_rejections_total",
+            "Validations failed at once because the circuit breaker was open",
+        )
+        .unwrap();
         let request_duration = Histogram::with_opts(
             HistogramOpts::new(
                 "infrapass_sidecar_request_duration_seconds",
@@ -48,6 +129,77 @@ impl SidecarMetrics {
             .buckets(vec![0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0]),
         )
         .unwrap();
+        let expiry_grace_applied = Counter::new(
+            "infrapass_sidecar_expiry_grace_applied_total",
+            "Requests allowed only because of the expiry grace window",
+        )
+        .unwrap();
+        let quota_outcomes = IntCounterVec::new(
+            Opts::new(
+                "infrapass_sidecar_quota_outcomes_total",
+                "Quota script results by outcome",
+            ),
+            &["outcome"],
+        )
+        .unwrap();
+        let quota_near_exhaustion = IntGauge::new(
+            "infrapass_sidecar_quota_near_exhaustion_users",
+            "User/service pairs whose remaining quota is below the low-quota threshold",
+        )
+        .unwrap();
+        let sampled_requests = Counter::new(
+            "infrapass_sidecar_sampled_requests_total",
+            "Requests admitted against a local usage accumulator",
+        )
+        .unwrap();
+        let sampling_flushes = Counter::new(
+            "infrapass_sidecar_sampling_flushes_total",
+            "Usage accumulators flushed to Redis",
+        )
+        .unwrap();
+        let usage_records_failed = Counter::new(
+            "infrapass_sidecar_usage_records_failed_total",
+            "Usage records the validator API rejected or never received",
+        )
+        .unwrap();
+        let requests_shed = Counter::new(
+            "infrapass_sidecar_requests_shed_total",
+            "Requests rejected with 503 while the upstream was overloaded",
+        )
+        .unwrap();
+        let shed_active = IntGauge::new(
+            "infrapass_sidecar_shed_active",
+            "1 while the sidecar is shedding load",
+        )
+        .unwrap();
+        let upstream_in_flight = IntGauge::new(
+            "infrapass_sidecar_upstream_in_flight",
+            "Requests currently waiting on the upstream",
+        )
+        .unwrap();
+        let upstream_responses = IntCounterVec::new(
+            Opts::new(
+                "infrapass_sidecar_upstream_responses_total",
+                "Upstream responses by HTTP version of the connection they came over",
+            ),
+            &["version"],
+        )
+        .unwrap();
+        let upstream_connect_errors = Counter::new(
+            "infrapass_sidecar_upstream_connect_errors_total",
+            "Upstream requests that failed to get a connection",
+        )
+        .unwrap();
+        let websocket_connections = IntGauge::new(
+            "infrapass_sidecar_websocket_connections",
+            "WebSocket connections currently relayed to the upstream",
+        )
+        .unwrap();
+        let websocket_policy_closes = Counter::new(
+            "infrapass_sidecar_websocket_policy_closes_total",
+            "WebSocket connections closed because the entitlement or quota ran out",
+        )
+        .unwrap();
 
         registry
             .register(Box::new(requests_allowed.clone()))
@@ -57,25 +209,115 @@ impl SidecarMetrics {
             .unwrap();
         registry.register(Box::new(cache_hits.clone())).unwrap();
         registry.register(Box::new(cache_misses.clone())).unwrap();
+        registry
+            .register(Box::new(local_cache_hits.clone()))
+            .unwrap();
         registry
             .register(Box::new(validator_errors.clone()))
             .unwrap();
+        registry
+            .register(Box::new(validator_breaker_open.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(validator_breaker_rejections.clone()))
+            .unwrap();
         registry
             .register(Box::new(request_duration.clone()))
             .unwrap();
+        registry
+            .register(Box::new(expiry_grace_applied.clone()))
+            .unwrap();
+        registry.register(Box::new(quota_outcomes.clone())).unwrap();
+        registry
+            .register(Box::new(quota_near_exhaustion.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(sampled_requests.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(sampling_flushes.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(usage_records_failed.clone()))
+            .unwrap();
+        registry.register(Box::new(requests_shed.clone())).unwrap();
+        registry.register(Box::new(shed_active.clone())).unwrap();
+        registry
+            .register(Box::new(upstream_in_flight.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(upstream_responses.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(upstream_connect_errors.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(websocket_connections.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(websocket_policy_closes.clone()))
+            .unwrap();
 
         Self {
             requests_allowed,
             requests_denied,
             cache_hits,
             cache_misses,
+            local_cache_hits,
             validator_errors,
+            validator_breaker_open,
+            validator_breaker_rejections,
             request_duration,
+            expiry_grace_applied,
+            quota_outcomes,
+            quota_near_exhaustion,
+            sampled_requests,
+            sampling_flushes,
+            usage_records_failed,
+            requests_shed,
+            shed_active,
+            upstream_in_flight,
+            upstream_responses,
+            upstream_connect_errors,
+            websocket_connections,
+            websocket_policy_closes,
+            near_exhaustion: Mutex::new(NearExhaustion {
+                pairs: HashMap::new(),
+                swept: Instant::now(),
+            }),
             registry,
         }
     }
 
+    pub fn record_quota_outcome(&self, outcome: QuotaOutcome) {
+        self.quota_outcomes
+            .with_label_values(&[outcome.as_str()])
+            .inc();
+    }
+
+    /// Tracks whether a user/service pair is close to exhausting its quota.
+    /// The gauge counts distinct pairs, so repeated requests are not double counted.
+    /// Pairs not seen for `NEAR_EXHAUSTION_TTL` are dropped.
+    pub fn set_near_exhaustion(&self, key: &str, near: bool) {
+        let mut tracked = self.near_exhaustion.lock().unwrap();
+        if near {
+            tracked.pairs.insert(key.to_string(), Instant::now());
+        } else {
+            tracked.pairs.remove(key);
+        }
+        if tracked.swept.elapsed() >= NEAR_EXHAUSTION_SWEEP {
+            tracked.expire();
+        }
+        self.quota_near_exhaustion.set(tracked.pairs.len() as i64);
+    }
+
     pub fn encode(&self) -> String {
+        // Stale pairs leave the gauge even when no request comes in
+        {
+            let mut tracked = self.near_exhaustion.lock().unwrap();
+            tracked.expire();
+            self.quota_near_exhaustion.set(tracked.pairs.len() as i64);
+        }
         let encoder = TextEncoder::new();
         let families = self.registry.gather();
         encoder.encode_to_string(&families).unwrap_or_default()
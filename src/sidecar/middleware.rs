@@ -6,14 +6,14 @@ use axum::{
     middleware::Next,
     response::Response,
 };
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 use crate::sidecar::{
     error::ProxyError,
     proxy::{ProxyState, deny_response},
 };
 
-#[derive(Debug, Clone, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 #[serde(rename_all = "snake_case")]
 pub enum AuthMode {
     #[default]
@@ -1,7 +1,18 @@
+pub mod breaker;
 pub mod cache;
 pub mod config;
+pub mod cost;
 pub mod error;
+pub mod fleet;
+pub mod grpc;
+pub mod headers;
 pub mod metrics;
 pub mod middleware;
 pub mod proxy;
+pub mod sampling;
+pub mod shed;
+pub mod upstream;
+pub mod upstream_auth;
+pub mod usage;
 pub mod validator;
+pub mod websocket;
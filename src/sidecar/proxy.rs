@@ -1,56 +1,119 @@
 use axum::{
     body::Body,
     extract::{Request, State},
-    http::StatusCode,
+    http::{self, HeaderMap, Method, StatusCode},
     response::Response,
 };
+use bytes::{Bytes, BytesMut};
 use chrono::Utc;
+use moka::future::Cache;
 use redis::{Client as RedisClient, aio::MultiplexedConnection};
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 use tracing::{instrument, warn};
+use uuid::Uuid;
 
 use crate::{
+    api_types::validator::ProviderNotification,
+    pubsub::types::MaintenanceNotice,
     sidecar::{
         cache::CachedEntitlement,
         config::SidecarConfig,
+        cost::{CostRule, parse_cost_rules, request_cost},
         error::ProxyError,
-        metrics::METRICS,
-        validator::{ProviderNotification, ValidatorClient, to_cached},
+        fleet::BuildInfo,
+        grpc,
+        headers::{HeaderContext, HeaderTemplate, apply_header_templates, parse_header_templates},
+        metrics::{METRICS, QuotaOutcome},
+        sampling::{SampledDecision, UsageSampler, parse_tier_types},
+        shed::{InFlightBody, LoadShedder},
+        upstream::{upstream_client, version_label},
+        upstream_auth::UpstreamAuth,
+        usage::{UsageReporter, report_usage},
+        validator::{ValidatorClient, ValidatorError, to_cached},
+        websocket,
+    },
+    utils::{
+        constants::{LUA_ATOMIC_CHECK_AND_DECREMENT, LUA_CAP_QUOTA},
+        webhook::post_signed,
     },
-    utils::constants::LUA_ATOMIC_CHECK_AND_DECREMENT,
 };
 
-use hmac::{Hmac, Mac};
-use sha2::Sha256;
-pub type HmacSha256 = Hmac<Sha256>;
+/// Remaining quota below which a user is reported as near exhaustion.
+pub const LOW_QUOTA_THRESHOLD: i64 = 10;
 
 pub struct ProxyState {
     pub cfg: SidecarConfig,
     pub validator: ValidatorClient,
+    /// For the provider webhook and token endpoint, which may not speak
+    /// the protocol configured for the upstream
     pub http_client: reqwest::Client,
+    pub upstream_client: reqwest::Client,
     pub redis: MultiplexedConnection,
     pub redis_client: RedisClient,
+    /// Entitlements read from Redis, kept for `local_cache_ttl_ms`. `None`
+    /// when disabled.
+    pub local_entitlements: Option<Cache<String, CachedEntitlement>>,
+    pub header_templates: Vec<HeaderTemplate>,
+    pub cost_rules: Vec<CostRule>,
+    pub sampler: UsageSampler,
+    pub usage: UsageReporter,
+    pub shedder: LoadShedder,
+    pub upstream_auth: UpstreamAuth,
+    pub build: BuildInfo,
 }
 
 impl ProxyState {
     pub async fn new(cfg: SidecarConfig) -> Result<Self, ProxyError> {
         let validator =
-            ValidatorClient::new(cfg.validator_api_url.clone(), cfg.validator_api_key.clone());
+            ValidatorClient::new(cfg.validator_api_url.clone(), cfg.validator_api_key.clone())
+                .with_tier_detail(cfg.tier_detail)
+                .with_circuit_breaker(
+                    cfg.validator_breaker_failures,
+                    Duration::from_millis(cfg.validator_breaker_cooldown_ms),
+                );
 
-        let http_client = reqwest::Client::builder()
-            .pool_max_idle_per_host(100)
-            .pool_idle_timeout(std::time::Duration::from_secs(90))
-            .build()?;
+        let http_client = reqwest::Client::new();
+        let upstream_client = upstream_client(&cfg)?;
 
         let redis_client = RedisClient::open(cfg.redis_url.clone())?;
         let redis = redis_client.get_multiplexed_async_connection().await?;
+        let local_entitlements = (cfg.local_cache_ttl_ms > 0).then(|| {
+            Cache::builder()
+                .max_capacity(cfg.cache_max_entries)
+                .time_to_live(Duration::from_millis(cfg.local_cache_ttl_ms))
+                .build()
+        });
+
+        let header_templates = parse_header_templates(&cfg.response_headers)?;
+        let cost_rules = parse_cost_rules(&cfg.cost_rules)?;
+        let sampler = UsageSampler::new(
+            parse_tier_types(&cfg.sampling_tier_types)?,
+            cfg.sampling_flush_ms,
+        );
+        let usage = UsageReporter::new(cfg.usage_batch_size, cfg.usage_flush_ms);
+        let shedder = LoadShedder::new(
+            cfg.shed_p99_ms,
+            cfg.shed_max_in_flight,
+            cfg.shed_retry_after_secs,
+        );
+        let upstream_auth = UpstreamAuth::from_config(&cfg, http_client.clone())?;
+        let build = BuildInfo::new(&cfg);
 
         Ok(Self {
             cfg,
             validator,
             http_client,
+            upstream_client,
             redis,
             redis_client,
+            local_entitlements,
+            header_templates,
+            cost_rules,
+            sampler,
+            usage,
+            shedder,
+            upstream_auth,
+            build,
         })
     }
 
@@ -58,18 +121,31 @@ impl ProxyState {
         format!("entitlement:{}:{}", user, service)
     }
 
-    fn quota_key(&self, user: &str, service: &str) -> String {
+    pub fn quota_key(&self, user: &str, service: &str) -> String {
         format!("quota:{}:{}", user, service)
     }
 
     pub async fn get_entitlement(&self, user: &str, service: &str) -> Option<CachedEntitlement> {
+        let key = self.entitlement_key(user, service);
+        if let Some(local) = &self.local_entitlements {
+            if let Some(ent) = local.get(&key).await {
+                METRICS.local_cache_hits.inc();
+                return Some(ent);
+            }
+        }
+
         let mut conn = self.redis.clone();
         let json: Option<String> = redis::cmd("GET")
-            .arg(&self.entitlement_key(user, service))
+            .arg(&key)
             .query_async(&mut conn)
             .await
             .ok()?;
-        json.and_then(|j| serde_json::from_str(&j).ok())
+        let ent: CachedEntitlement = serde_json::from_str(&json?).ok()?;
+
+        if let Some(local) = &self.local_entitlements {
+            local.insert(key, ent.clone()).await;
+        }
+        Some(ent)
     }
 
     pub async fn set_entitlement(
@@ -86,6 +162,8 @@ impl ProxyState {
             .expire(&self.entitlement_key(user, service), ttl_secs as i64)
             .query_async(&mut conn)
             .await?;
+        // Read back from Redis on next use, which holds the expiry
+        self.invalidate_local(user, service).await;
 
         Ok(())
     }
@@ -110,19 +188,227 @@ impl ProxyState {
         Ok(())
     }
 
+    /// Lowers the quota counter to `remaining` if it is above it. Returns the
+    /// counter afterwards, `None` when it isn't cached.
+    pub async fn cap_quota(
+        &self,
+        user: &str,
+        service: &str,
+        remaining: u64,
+    ) -> Result<Option<i64>, ProxyError> {
+        let mut conn = self.redis.clone();
+        let current: Option<i64> = redis::Script::new(LUA_CAP_QUOTA)
+            .key(&self.quota_key(user, service))
+            .arg(remaining as i64)
+            .invoke_async(&mut conn)
+            .await?;
+
+        Ok(current)
+    }
+
+    fn maintenance_key(&self, service: &str) -> String {
+        format!("maintenance:{}", service)
+    }
+
+    /// Stores a window in the service's maintenance hash, keeping the hash
+    /// alive until the latest window ends
+    pub async fn set_maintenance(
+        &self,
+        service: &str,
+        notice: &MaintenanceNotice,
+    ) -> Result<(), ProxyError> {
+        let mut conn = self.redis.clone();
+        let key = self.maintenance_key(service);
+        let json = serde_json::to_string(notice)?;
+
+        let current_expiry: i64 = redis::cmd("EXPIRETIME")
+            .arg(&key)
+            .query_async(&mut conn)
+            .await
+            .unwrap_or(-2);
+        let expire_at = current_expiry.max(notice.ends_at.timestamp());
+
+        let _: () = redis::pipe()
+            .hset(&key, notice.id.to_string(), json)
+            .expire_at(&key, expire_at)
+            .query_async(&mut conn)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Replaces the service's maintenance hash with `notices`, dropping
+    /// windows cancelled while no sidecar was listening
+    pub async fn replace_maintenance(
+        &self,
+        service: &str,
+        notices: &[MaintenanceNotice],
+    ) -> Result<(), ProxyError> {
+        let mut conn = self.redis.clone();
+        let key = self.maintenance_key(service);
+
+        let mut pipe = redis::pipe();
+        pipe.atomic().del(&key);
+        for notice in notices {
+            pipe.hset(&key, notice.id.to_string(), serde_json::to_string(notice)?);
+        }
+        if let Some(ends_at) = notices.iter().map(|n| n.ends_at.timestamp()).max() {
+            pipe.expire_at(&key, ends_at);
+        }
+        let _: () = pipe.query_async(&mut conn).await?;
+
+        Ok(())
+    }
+
+    pub async fn remove_maintenance(&self, service: &str, id: Uuid) -> Result<(), ProxyError> {
+        let mut conn = self.redis.clone();
+        let _: () = redis::cmd("HDEL")
+            .arg(&self.maintenance_key(service))
+            .arg(id.to_string())
+            .query_async(&mut conn)
+            .await?;
+
+        Ok(())
+    }
+
+    /// The maintenance window covering `now`, if any
+    pub async fn active_maintenance(&self, service: &str) -> Option<MaintenanceNotice> {
+        let mut conn = self.redis.clone();
+        let windows: Vec<String> = redis::cmd("HVALS")
+            .arg(&self.maintenance_key(service))
+            .query_async(&mut conn)
+            .await
+            .ok()?;
+
+        let now = Utc::now();
+        windows
+            .iter()
+            .filter_map(|j| serde_json::from_str::<MaintenanceNotice>(j).ok())
+            .filter(|w| w.is_active(now))
+            .max_by_key(|w| w.ends_at)
+    }
+
+    async fn invalidate_local(&self, user: &str, service: &str) {
+        if let Some(local) = &self.local_entitlements {
+            local.invalidate(&self.entitlement_key(user, service)).await;
+        }
+    }
+
     pub async fn invalidate_entitlement(
         &self,
         user: &str,
         service: &str,
     ) -> Result<(), ProxyError> {
         let mut conn = self.redis.clone();
-        let _: () = redis::cmd("DEL")
+        let deleted: Result<(), _> = redis::cmd("DEL")
             .arg(&self.entitlement_key(user, service))
             .query_async(&mut conn)
-            .await?;
+            .await;
+        // Only after Redis, so a concurrent miss can't refill the local cache
+        // from the stale Redis entry
+        self.invalidate_local(user, service).await;
+        deleted?;
 
         Ok(())
     }
+
+    /// The cached entitlement, or the validator's answer, cached and with
+    /// its quota counter seeded. Also whether it currently grants access.
+    pub async fn lookup_entitlement(
+        &self,
+        user: &str,
+        service: &str,
+        cost: u64,
+    ) -> Result<(bool, CachedEntitlement), ValidatorError> {
+        if let Some(cached) = self.get_entitlement(user, service).await {
+            METRICS.cache_hits.inc();
+            return Ok((cached.allowed(self.cfg.expiry_grace()), cached));
+        }
+
+        METRICS.cache_misses.inc();
+        let resp = self.validator.validate(user, service, cost).await?;
+        let resp_to_cache_type = to_cached(&resp);
+        let allowed = resp_to_cache_type.allowed(self.cfg.expiry_grace());
+        let ttl_secs: u64 = match resp_to_cache_type.expires_at {
+            Some(exp) => {
+                let now = Utc::now();
+                let remaining = (exp + self.cfg.expiry_grace() - now).num_seconds();
+                if remaining > 0 { remaining as u64 } else { 0 }
+            }
+            None => self.cfg.cache_ttl_ms / 1000,
+        };
+        let _ = self
+            .set_entitlement(user, service, &resp_to_cache_type, ttl_secs)
+            .await;
+
+        if allowed {
+            match resp_to_cache_type.tier_type {
+                0 => {
+                    // Subscription — no quota key needed, expiry is enforced by allowed()
+                }
+                2 => {
+                    // Quota-within-window — seed from quota field
+                    if let Some(quota) = resp_to_cache_type.quota {
+                        let _ = self.set_quota(user, service, quota as i64, ttl_secs).await;
+                        METRICS.set_near_exhaustion(&self.quota_key(user, service), false);
+                    }
+                }
+                3 => {
+                    // Pay-per-request — seed from units field
+                    if let Some(units) = resp_to_cache_type.units {
+                        let _ = self.set_quota(user, service, units as i64, ttl_secs).await;
+                        METRICS.set_near_exhaustion(&self.quota_key(user, service), false);
+                    }
+                }
+                _ => {
+                    warn!(
+                        tier_type = resp_to_cache_type.tier_type,
+                        "Unknown tier type during quota seeding"
+                    );
+                }
+            }
+        }
+
+        Ok((allowed, resp_to_cache_type))
+    }
+
+    /// Runs the atomic check-and-decrement of `cost` against the quota
+    /// counter: what is left, or -1 when exceeded, -2 when the counter isn't
+    /// cached and -3 for an unknown tier type
+    pub async fn consume(
+        &self,
+        user: &str,
+        service: &str,
+        cost: u64,
+        tier_type: u8,
+    ) -> Result<i64, ProxyError> {
+        let mut conn = self.redis.clone();
+        let n: i64 = redis::Script::new(LUA_ATOMIC_CHECK_AND_DECREMENT)
+            .key(&self.quota_key(user, service))
+            .arg(cost as i64)
+            .arg(tier_type as i64)
+            .invoke_async(&mut conn)
+            .await?;
+
+        Ok(n)
+    }
+}
+
+/// A request let in by `admit`, and what it was charged against
+pub struct Admission {
+    pub user_address: String,
+    pub service_id: String,
+    pub cost: u64,
+    pub entitlement: CachedEntitlement,
+    /// Quota or units left after the charge, on a metered tier
+    pub remaining: Option<i64>,
+    /// Charged to a local accumulator, whose usage the flusher reports
+    pub sampled: bool,
+}
+
+pub enum Admit {
+    Granted(Admission),
+    Denied(Response),
 }
 
 #[instrument(skip(state, req), fields(path = %req.uri().path()))]
@@ -130,196 +416,358 @@ pub async fn proxy_handler(
     State(state): State<Arc<ProxyState>>,
     req: Request,
 ) -> Result<Response, ProxyError> {
+    if websocket::is_upgrade(req.headers()) {
+        return websocket::websocket_handler(state, req).await;
+    }
+
     let timer = std::time::Instant::now();
+    // gRPC streams in both directions and ends with trailers, so its bodies
+    // are never buffered
+    let grpc = grpc::is_grpc(req.headers());
+
+    let Admission {
+        user_address,
+        service_id,
+        cost,
+        entitlement,
+        remaining,
+        sampled,
+    } = match admit(&state, req.method(), req.uri().path(), req.headers()).await? {
+        Admit::Granted(admission) => admission,
+        Admit::Denied(response) => return Ok(response),
+    };
+
+    let path_and_query = req
+        .uri()
+        .path_and_query()
+        .ok_or_else(|| ProxyError::InvalidRequest("Missing path and query".into()))?
+        .as_str();
+    let upstream_url = format!("{}{}", state.cfg.upstream_url, path_and_query);
+
+    let mut upstream_req = state
+        .upstream_client
+        .request(req.method().clone(), &upstream_url);
+
+    let injected = state.upstream_auth.header_name();
+    for (name, value) in req.headers().iter() {
+        if injected.as_ref() == Some(name) {
+            continue;
+        }
+        upstream_req = upstream_req.header(name, value);
+    }
+
+    upstream_req = match state.upstream_auth.apply(upstream_req).await {
+        Ok(r) => r,
+        Err(e) => {
+            warn!(error = %e, "Upstream credentials unavailable");
+            return Ok(deny_response(
+                StatusCode::BAD_GATEWAY,
+                "upstream_auth_error",
+            )?);
+        }
+    };
+
+    upstream_req = upstream_req.header("X-Infrapass-User-Address", &user_address);
+    upstream_req = upstream_req.header("X-Infrapass-Validated", "true");
+    if let Some(name) = &entitlement.tier_name {
+        upstream_req = upstream_req.header("X-Infrapass-Tier-Name", name);
+    }
+    if let Some(price) = entitlement.price {
+        upstream_req = upstream_req.header("X-Infrapass-Tier-Price", price.to_string());
+    }
+    if let Some(coin_type) = &entitlement.coin_type {
+        upstream_req = upstream_req.header("X-Infrapass-Coin-Type", coin_type);
+    }
+
+    let body = if grpc || state.cfg.stream_bodies {
+        reqwest::Body::wrap_stream(req.into_body().into_data_stream())
+    } else {
+        // Past the limit is the only read error worth answering; a client
+        // that broke off its upload never sees the response
+        match axum::body::to_bytes(req.into_body(), state.cfg.max_buffer_bytes).await {
+            Ok(bytes) => reqwest::Body::from(bytes),
+            Err(_) => {
+                return Ok(deny_response(
+                    StatusCode::PAYLOAD_TOO_LARGE,
+                    "request_body_too_large",
+                )?);
+            }
+        }
+    };
+    upstream_req = upstream_req.body(body);
+
+    let in_flight = state.shedder.start();
+    let upstream_timer = std::time::Instant::now();
+    let upstream_resp = match upstream_req.send().await {
+        Ok(r) => {
+            METRICS
+                .upstream_responses
+                .with_label_values(&[version_label(r.version())])
+                .inc();
+            r
+        }
+        Err(e) => {
+            state.shedder.record_latency(upstream_timer.elapsed());
+            if e.is_connect() {
+                METRICS.upstream_connect_errors.inc();
+            }
+            warn!(error = %e, "Upstream request failed");
+            return Ok(deny_response(StatusCode::BAD_GATEWAY, "upstream_error")?);
+        }
+    };
+
+    // Sampled usage is posted in aggregate by the flusher
+    if !sampled {
+        report_usage(&state, &user_address, &entitlement.id, cost);
+    }
+
+    METRICS
+        .request_duration
+        .observe(timer.elapsed().as_secs_f64());
+
+    let status = StatusCode::from_u16(upstream_resp.status().as_u16())?;
+    if status == StatusCode::UNAUTHORIZED {
+        state.upstream_auth.rejected().await;
+    }
+    let headers = upstream_resp.headers().clone();
+    let body = if grpc {
+        // As a body rather than a byte stream, to keep the trailers carrying
+        // `grpc-status`
+        state.shedder.record_latency(upstream_timer.elapsed());
+        Body::new(http::Response::from(upstream_resp).into_body())
+    } else if state.cfg.stream_bodies {
+        // Timed to the response headers, since a streamed body lasts as long
+        // as the upstream keeps sending
+        state.shedder.record_latency(upstream_timer.elapsed());
+        Body::from_stream(upstream_resp.bytes_stream())
+    } else {
+        let body = read_limited(upstream_resp, state.cfg.max_buffer_bytes).await?;
+        state.shedder.record_latency(upstream_timer.elapsed());
+        match body {
+            Some(body) => Body::from(body),
+            None => {
+                warn!(
+                    max_buffer_bytes = state.cfg.max_buffer_bytes,
+                    "Upstream response too large to buffer"
+                );
+                return Ok(deny_response(
+                    StatusCode::BAD_GATEWAY,
+                    "upstream_response_too_large",
+                )?);
+            }
+        }
+    };
+    let body = Body::new(InFlightBody::new(body, in_flight));
+
+    let mut response = Response::new(body);
+    *response.status_mut() = status;
+    for (name, value) in headers.iter() {
+        response.headers_mut().insert(name, value.clone());
+    }
+
+    apply_header_templates(
+        &state.header_templates,
+        &HeaderContext {
+            user_address: &user_address,
+            service_id: &service_id,
+            entitlement: &entitlement,
+            remaining,
+        },
+        response.headers_mut(),
+    );
 
-    let user_address = match req.headers().get(&state.cfg.address_header) {
+    Ok(response)
+}
+
+/// Runs a request through the address, service, maintenance, load shedding,
+/// entitlement and quota checks, charging its cost when it is let in
+pub async fn admit(
+    state: &Arc<ProxyState>,
+    method: &Method,
+    path: &str,
+    headers: &HeaderMap,
+) -> Result<Admit, ProxyError> {
+    let user_address = match headers.get(&state.cfg.address_header) {
         Some(val) => match val.to_str() {
             Ok(addr) => addr.to_string(),
             Err(_) => {
-                return Ok(deny_response(
+                return Ok(Admit::Denied(deny_response(
                     StatusCode::BAD_REQUEST,
                     "invalid_address_header",
-                )?);
+                )?));
             }
         },
         None => {
             METRICS.requests_denied.inc();
-            return Ok(deny_response(
+            return Ok(Admit::Denied(deny_response(
                 StatusCode::UNAUTHORIZED,
                 "missing_sui_address",
-            )?);
+            )?));
         }
     };
 
-    let cost = match req.headers().get(&state.cfg.cost_header) {
+    let declared = if state.cfg.trust_cost_header {
+        headers.get(&state.cfg.cost_header)
+    } else {
+        None
+    };
+    let cost = match declared {
         Some(val) => match val.to_str() {
             Ok(cost_str) => match cost_str.parse::<u64>() {
                 Ok(c) => c,
                 Err(_) => {
-                    return Ok(deny_response(
+                    return Ok(Admit::Denied(deny_response(
                         StatusCode::BAD_REQUEST,
                         "invalid_cost_header",
-                    )?);
+                    )?));
                 }
             },
             Err(_) => {
-                return Ok(deny_response(
+                return Ok(Admit::Denied(deny_response(
                     StatusCode::BAD_REQUEST,
                     "invalid_cost_header",
-                )?);
+                )?));
             }
         },
-        None => 1,
+        None => request_cost(&state.cost_rules, method, path),
     };
 
-    let service_id = match req.headers().get(&state.cfg.service_header) {
+    let service_id = match headers.get(&state.cfg.service_header) {
         Some(val) => match val.to_str() {
             Ok(sid) => sid.to_string(),
             Err(_) => {
-                return Ok(deny_response(
+                return Ok(Admit::Denied(deny_response(
                     StatusCode::BAD_REQUEST,
                     "invalid_service_header",
-                )?);
+                )?));
             }
         },
         None => {
             METRICS.requests_denied.inc();
-            return Ok(deny_response(
+            return Ok(Admit::Denied(deny_response(
                 StatusCode::BAD_REQUEST,
                 "missing_service_id",
-            )?);
+            )?));
         }
     };
 
-    let (has_entitlement, entitlement) =
-        if let Some(cached) = state.get_entitlement(&user_address, &service_id).await {
-            METRICS.cache_hits.inc();
-            (cached.allowed(), cached)
-        } else {
-            METRICS.cache_misses.inc();
-            let resp = match state
-                .validator
-                .validate(&user_address, &service_id, cost)
-                .await
-            {
-                Ok(r) => r,
-                Err(e) => {
-                    METRICS.validator_errors.inc();
-                    warn!(error = ?e, "Validator API error");
-                    if state.cfg.fail_open {
-                        warn!("Failing open due to validator error");
-                        return Ok(deny_response(
-                            StatusCode::OK,
-                            "validator_error, failing_open",
-                        )?);
-                    } else {
-                        warn!("Failing closed due to validator error");
-                    }
-                    return Ok(deny_response(
-                        StatusCode::SERVICE_UNAVAILABLE,
-                        "validator_error",
-                    )?);
-                }
-            };
-            let resp_to_cache_type = to_cached(&resp);
-            let allowed = resp_to_cache_type.allowed();
-            let ttl_secs: u64 = match resp_to_cache_type.expires_at {
-                Some(exp) => {
-                    let now = Utc::now();
-                    let remaining = (exp - now).num_seconds();
-                    if remaining > 0 { remaining as u64 } else { 0 }
-                }
-                None => state.cfg.cache_ttl_ms / 1000,
-            };
-            let _ = state
-                .set_entitlement(&user_address, &service_id, &resp_to_cache_type, ttl_secs)
-                .await;
-
-            if allowed {
-                match resp_to_cache_type.tier_type {
-                    0 => {
-                        // Subscription — no quota key needed, expiry is enforced by allowed()
-                    }
-                    2 => {
-                        // Quota-within-window — seed from quota field
-                        if let Some(quota) = resp_to_cache_type.quota {
-                            let _ = state
-                                .set_quota(&user_address, &service_id, quota as i64, ttl_secs)
-                                .await;
-                        }
-                    }
-                    3 => {
-                        // Pay-per-request — seed from units field
-                        if let Some(units) = resp_to_cache_type.units {
-                            let _ = state
-                                .set_quota(&user_address, &service_id, units as i64, ttl_secs)
-                                .await;
-                        }
-                    }
-                    _ => {
-                        warn!(
-                            tier_type = resp_to_cache_type.tier_type,
-                            "Unknown tier type during quota seeding"
-                        );
-                    }
-                }
-            }
+    if let Some(window) = state.active_maintenance(&service_id).await {
+        METRICS.requests_denied.inc();
+        return Ok(Admit::Denied(maintenance_response(&window)?));
+    }
 
-            (allowed, resp_to_cache_type)
-        };
+    // Checked before the quota decrement so shed requests are not charged
+    if state.shedder.should_shed() {
+        return Ok(Admit::Denied(shed_response(
+            state.shedder.retry_after_secs(),
+        )?));
+    }
+
+    let (has_entitlement, entitlement) = match state
+        .lookup_entitlement(&user_address, &service_id, cost)
+        .await
+    {
+        Ok(found) => found,
+        Err(e) => {
+            if fails_open(&state, &e) {
+                return Ok(Admit::Denied(deny_response(
+                    StatusCode::OK,
+                    "validator_error, failing_open",
+                )?));
+            }
+            return Ok(Admit::Denied(deny_response(
+                StatusCode::SERVICE_UNAVAILABLE,
+                "validator_error",
+            )?));
+        }
+    };
 
     if !has_entitlement {
         METRICS.requests_denied.inc();
-        return Ok(deny_response(
+        return Ok(Admit::Denied(deny_response(
             StatusCode::FORBIDDEN,
             "access_denied, no entitlement",
-        )?);
+        )?));
     }
 
-    let mut conn = state.redis.clone();
+    if entitlement.in_grace(state.cfg.expiry_grace()) {
+        METRICS.expiry_grace_applied.inc();
+    }
 
-    if (entitlement.tier_type != 0)
-        && (entitlement.quota().is_some() || entitlement.units().is_some())
-    {
-        let result: i64 = redis::Script::new(LUA_ATOMIC_CHECK_AND_DECREMENT)
-            .key(&state.quota_key(&user_address, &service_id))
-            .arg(cost as i64)
-            .arg(entitlement.tier_type as i64)
-            .invoke_async(&mut conn)
-            .await?;
+    let mut remaining = None;
+    let sampled = entitlement.metered() && state.sampler.samples(entitlement.tier_type);
+
+    if entitlement.metered() {
+        let quota_key = state.quota_key(&user_address, &service_id);
+
+        let local = if sampled {
+            state.sampler.try_consume(&quota_key, cost)
+        } else {
+            None
+        };
+
+        let result: i64 = match local {
+            Some(SampledDecision::Allowed(n)) => {
+                METRICS.sampled_requests.inc();
+                n
+            }
+            Some(SampledDecision::Exceeded) => -1,
+            None => {
+                let n = state
+                    .consume(&user_address, &service_id, cost, entitlement.tier_type)
+                    .await?;
+                if sampled && n >= 0 {
+                    state
+                        .sampler
+                        .seed(&quota_key, &user_address, &entitlement.id, n, cost);
+                }
+                n
+            }
+        };
 
         match result {
-            0 => {} // subscription — allowed, no counter
+            0 => METRICS.record_quota_outcome(QuotaOutcome::Allowed), // subscription — allowed, no counter
             -1 => {
+                METRICS.record_quota_outcome(QuotaOutcome::Exceeded);
+                METRICS.set_near_exhaustion(&quota_key, true);
                 METRICS.requests_denied.inc();
-                return Ok(deny_response(
+                return Ok(Admit::Denied(deny_response(
                     StatusCode::TOO_MANY_REQUESTS,
                     "quota_exceeded",
-                )?);
+                )?));
             }
             -2 => {
+                METRICS.record_quota_outcome(QuotaOutcome::NotReady);
                 METRICS.requests_denied.inc();
                 warn!(
                     user = %user_address,
                     tier_type = entitlement.tier_type,
                     "Quota key not initialized"
                 );
-                return Ok(deny_response(
+                return Ok(Admit::Denied(deny_response(
                     StatusCode::SERVICE_UNAVAILABLE,
                     "quota_not_ready",
-                )?);
+                )?));
             }
             -3 => {
+                METRICS.record_quota_outcome(QuotaOutcome::UnknownTier);
                 METRICS.requests_denied.inc();
                 warn!(
                     user = %user_address,
                     tier_type = entitlement.tier_type,
                     "Unknown tier type in Lua script"
                 );
-                return Ok(deny_response(StatusCode::BAD_REQUEST, "unknown_tier_type")?);
+                return Ok(Admit::Denied(deny_response(
+                    StatusCode::BAD_REQUEST,
+                    "unknown_tier_type",
+                )?));
             }
             n => {
-                if n < 10 {
+                remaining = Some(n);
+                METRICS.record_quota_outcome(QuotaOutcome::Allowed);
+                let low = n < LOW_QUOTA_THRESHOLD;
+                METRICS.set_near_exhaustion(&quota_key, low);
+                if low {
                     warn!(
                         user = %user_address,
                         service = %service_id,
@@ -333,58 +781,55 @@ pub async fn proxy_handler(
 
     METRICS.requests_allowed.inc();
 
-    let path_and_query = req
-        .uri()
-        .path_and_query()
-        .ok_or_else(|| ProxyError::InvalidRequest("Missing path and query".into()))?
-        .as_str();
-    let upstream_url = format!("{}{}", state.cfg.upstream_url, path_and_query);
-
-    let mut upstream_req = state
-        .http_client
-        .request(req.method().clone(), &upstream_url);
+    Ok(Admit::Granted(Admission {
+        user_address,
+        service_id,
+        cost,
+        entitlement,
+        remaining,
+        sampled,
+    }))
+}
 
-    for (name, value) in req.headers().iter() {
-        upstream_req = upstream_req.header(name, value);
+/// Reads an upstream response whole, or `None` once it passes `max` bytes
+async fn read_limited(
+    mut resp: reqwest::Response,
+    max: usize,
+) -> Result<Option<Bytes>, ProxyError> {
+    if resp.content_length().is_some_and(|len| len > max as u64) {
+        return Ok(None);
     }
 
-    upstream_req = upstream_req.header("X-Infrapass-User-Address", &user_address);
-    upstream_req = upstream_req.header("X-Infrapass-Validated", "true");
-
-    let body_bytes = axum::body::to_bytes(req.into_body(), usize::MAX).await?;
-
-    upstream_req = upstream_req.body(body_bytes);
-
-    let upstream_resp = match upstream_req.send().await {
-        Ok(r) => r,
-        Err(e) => {
-            warn!(error = %e, "Upstream request failed");
-            return Ok(deny_response(StatusCode::BAD_GATEWAY, "upstream_error")?);
+    let mut body = BytesMut::new();
+    while let Some(chunk) = resp.chunk().await? {
+        if body.len() + chunk.len() > max {
+            return Ok(None);
         }
-    };
-
-    let state_clone = state.clone();
-    let addr = user_address.clone();
-    let ent = entitlement.id.clone();
-    tokio::spawn(async move {
-        let _ = state_clone.validator.record_usage(&addr, &ent, cost).await;
-    });
-
-    METRICS
-        .request_duration
-        .observe(timer.elapsed().as_secs_f64());
-
-    let status = StatusCode::from_u16(upstream_resp.status().as_u16())?;
-    let headers = upstream_resp.headers().clone();
-    let body = upstream_resp.bytes().await?;
-
-    let mut response = Response::new(Body::from(body));
-    *response.status_mut() = status;
-    for (name, value) in headers.iter() {
-        response.headers_mut().insert(name, value.clone());
+        body.extend_from_slice(&chunk);
     }
+    Ok(Some(body.freeze()))
+}
 
-    Ok(response)
+/// Why the sidecar answered a request itself, kept on the response for
+/// `grpc_status_middleware`
+#[derive(Debug, Clone)]
+pub struct DenyReason(pub String);
+
+/// Counts and logs an entitlement lookup the validator API failed, and
+/// whether `fail_open` lets the caller through. Admission and WebSocket
+/// rechecks both go through it, so they treat the failure alike.
+pub(crate) fn fails_open(state: &ProxyState, e: &ValidatorError) -> bool {
+    // Calls the open circuit breaker refused are counted by it
+    if !matches!(e, ValidatorError::CircuitOpen) {
+        METRICS.validator_errors.inc();
+        warn!(error = ?e, "Validator API error");
+    }
+    if state.cfg.fail_open {
+        warn!("Failing open due to validator error");
+    } else {
+        warn!("Failing closed due to validator error");
+    }
+    state.cfg.fail_open
 }
 
 pub fn deny_response(status: StatusCode, reason: &str) -> Result<Response, ProxyError> {
@@ -395,6 +840,38 @@ pub fn deny_response(status: StatusCode, reason: &str) -> Result<Response, Proxy
     Ok(Response::builder()
         .status(status)
         .header("Content-Type", "application/json")
+        .extension(DenyReason(reason.to_string()))
+        .body(Body::from(body.to_string()))?)
+}
+
+pub fn maintenance_response(window: &MaintenanceNotice) -> Result<Response, ProxyError> {
+    let status = StatusCode::SERVICE_UNAVAILABLE;
+    let retry_after = (window.ends_at - Utc::now()).num_seconds().max(1);
+    let body = serde_json::json!({
+        "error": "maintenance",
+        "status": status.as_u16(),
+        "ends_at": window.ends_at,
+        "reason": window.reason,
+    });
+    Ok(Response::builder()
+        .status(status)
+        .header("Content-Type", "application/json")
+        .header("Retry-After", retry_after.to_string())
+        .extension(DenyReason("maintenance".to_string()))
+        .body(Body::from(body.to_string()))?)
+}
+
+pub fn shed_response(retry_after_secs: u64) -> Result<Response, ProxyError> {
+    let status = StatusCode::SERVICE_UNAVAILABLE;
+    let body = serde_json::json!({
+        "error": "overloaded",
+        "status": status.as_u16(),
+    });
+    Ok(Response::builder()
+        .status(status)
+        .header("Content-Type", "application/json")
+        .header("Retry-After", retry_after_secs.to_string())
+        .extension(DenyReason("overloaded".to_string()))
         .body(Body::from(body.to_string()))?)
 }
 
@@ -421,19 +898,9 @@ pub async fn deliver_notification(
         }
     };
 
-    let mut mac = HmacSha256::new_from_slice(secret.as_bytes())?;
-    mac.update(&payload);
-    let sig = hex::encode(mac.finalize().into_bytes());
-
-    let _ = state
-        .http_client
-        .post(&webhook_url)
-        .header("Content-Type", "application/json")
-        .header("X-Infrapass-Signature", sig)
-        .body(payload)
-        .timeout(std::time::Duration::from_secs(3))
-        .send()
-        .await;
+    if let Err(e) = post_signed(&state.http_client, &webhook_url, &secret, payload).await {
+        warn!(error = %e, "Provider webhook delivery failed");
+    }
 
     Ok(())
 }
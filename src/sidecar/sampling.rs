@@ -0,0 +1,170 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use tracing::{debug, warn};
+
+use crate::{
+    sidecar::{
+        error::ProxyError,
+        metrics::METRICS,
+        proxy::{LOW_QUOTA_THRESHOLD, ProxyState},
+        usage::report_usage,
+    },
+    utils::constants::LUA_FLUSH_DECREMENT,
+};
+
+/// Parses the `sampling_tier_types` setting, e.g. "1,2"
+pub fn parse_tier_types(raw: &str) -> Result<HashSet<u8>, ProxyError> {
+    raw.split(',')
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+        .map(|t| {
+            t.parse::<u8>().map_err(|_| {
+                ProxyError::ConfigError(format!("Invalid tier type in sampling_tier_types: {}", t))
+            })
+        })
+        .collect()
+}
+
+/// Outcome of admitting a request against a local accumulator
+#[derive(Debug, Clone, Copy)]
+pub enum SampledDecision {
+    Allowed(i64),
+    Exceeded,
+}
+
+#[derive(Debug)]
+struct Accumulator {
+    user_address: String,
+    entitlement_id: String,
+    /// Last value read from Redis minus everything admitted locally since
+    remaining: i64,
+    /// Cost admitted locally but not yet decremented in Redis
+    decrement: u64,
+    /// Cost not yet reported to the validator API
+    usage: u64,
+}
+
+/// Local quota accumulators for tiers served in sampling mode. The first
+/// request for a key goes through the Lua script and seeds an accumulator;
+/// later requests are admitted against it until the next flush applies the
+/// batched cost to Redis and drops the accumulator. Other sidecars can admit
+/// against the same stale remaining value, so quota may overshoot by up to one
+/// flush interval of traffic.
+pub struct UsageSampler {
+    tier_types: HashSet<u8>,
+    flush_interval: Duration,
+    accumulators: Mutex<HashMap<String, Accumulator>>,
+}
+
+impl UsageSampler {
+    pub fn new(tier_types: HashSet<u8>, flush_interval_ms: u64) -> Self {
+        Self {
+            tier_types,
+            flush_interval: Duration::from_millis(flush_interval_ms),
+            accumulators: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.tier_types.is_empty()
+    }
+
+    pub fn samples(&self, tier_type: u8) -> bool {
+        self.tier_types.contains(&tier_type)
+    }
+
+    /// Admits `cost` locally. `None` means there is no accumulator for the
+    /// key yet and the caller must run the Lua script.
+    pub fn try_consume(&self, quota_key: &str, cost: u64) -> Option<SampledDecision> {
+        let mut accumulators = self.accumulators.lock().unwrap();
+        let acc = accumulators.get_mut(quota_key)?;
+
+        if acc.remaining < cost as i64 {
+            return Some(SampledDecision::Exceeded);
+        }
+
+        acc.remaining -= cost as i64;
+        acc.decrement += cost;
+        acc.usage += cost;
+        Some(SampledDecision::Allowed(acc.remaining))
+    }
+
+    /// Starts an accumulator after the Lua script already decremented `cost`
+    /// in Redis, leaving only the usage report pending
+    pub fn seed(
+        &self,
+        quota_key: &str,
+        user_address: &str,
+        entitlement_id: &str,
+        remaining: i64,
+        cost: u64,
+    ) {
+        let mut accumulators = self.accumulators.lock().unwrap();
+        let acc = accumulators
+            .entry(quota_key.to_string())
+            .or_insert_with(|| Accumulator {
+                user_address: user_address.to_string(),
+                entitlement_id: entitlement_id.to_string(),
+                remaining,
+                decrement: 0,
+                usage: 0,
+            });
+        acc.remaining = acc.remaining.min(remaining);
+        acc.usage += cost;
+    }
+
+    fn drain(&self) -> Vec<(String, Accumulator)> {
+        let mut accumulators = self.accumulators.lock().unwrap();
+        accumulators.drain().collect()
+    }
+}
+
+/// Applies batched decrements to Redis and posts aggregated usage every flush
+/// interval. Runs for the lifetime of the sidecar.
+pub async fn run_flusher(state: Arc<ProxyState>) {
+    let mut ticker = tokio::time::interval(state.sampler.flush_interval);
+
+    loop {
+        ticker.tick().await;
+        flush(&state).await;
+    }
+}
+
+async fn flush(state: &Arc<ProxyState>) {
+    let batch = state.sampler.drain();
+    if batch.is_empty() {
+        return;
+    }
+
+    let mut conn = state.redis.clone();
+    for (quota_key, acc) in batch {
+        if acc.decrement > 0 {
+            let result: Result<Option<i64>, _> = redis::Script::new(LUA_FLUSH_DECREMENT)
+                .key(&quota_key)
+                .arg(acc.decrement as i64)
+                .invoke_async(&mut conn)
+                .await;
+
+            match result {
+                Ok(Some(remaining)) => {
+                    METRICS.set_near_exhaustion(&quota_key, remaining < LOW_QUOTA_THRESHOLD);
+                    if remaining < 0 {
+                        debug!(key = %quota_key, overshoot = -remaining, "Sampled quota overshot");
+                    }
+                }
+                Ok(None) => debug!(key = %quota_key, "Quota key gone before flush"),
+                Err(e) => warn!(key = %quota_key, error = %e, "Failed to flush sampled usage"),
+            }
+        }
+
+        if acc.usage > 0 {
+            report_usage(state, &acc.user_address, &acc.entitlement_id, acc.usage);
+        }
+
+        METRICS.sampling_flushes.inc();
+    }
+}
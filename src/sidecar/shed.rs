@@ -0,0 +1,204 @@
+use std::{
+    collections::VecDeque,
+    pin::Pin,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use axum::body::Body;
+use bytes::Bytes;
+use hyper::body::{Body as HttpBody, Frame, SizeHint};
+use tracing::warn;
+
+use crate::sidecar::metrics::METRICS;
+
+/// Upstream latencies older than this no longer count towards p99
+const LATENCY_WINDOW: Duration = Duration::from_secs(10);
+
+/// Below this many samples p99 is not trusted and never triggers shedding
+const MIN_SAMPLES: usize = 20;
+
+/// How often p99 is recomputed, so requests don't sort the window each time
+const EVALUATE_EVERY: Duration = Duration::from_millis(250);
+
+/// Shedding stops only once load falls below this fraction of the threshold,
+/// so the sidecar doesn't flap around the limit
+const RECOVERY_RATIO: f64 = 0.8;
+
+struct ShedState {
+    samples: VecDeque<(Instant, Duration)>,
+    shedding: bool,
+    p99: Option<Duration>,
+    evaluated_at: Instant,
+}
+
+/// Rejects requests before they reach the upstream while its p99 latency or
+/// the number of in-flight requests is over the configured limits
+pub struct LoadShedder {
+    p99_threshold: Option<Duration>,
+    max_in_flight: Option<u64>,
+    retry_after_secs: u64,
+    in_flight: Arc<AtomicU64>,
+    state: Mutex<ShedState>,
+}
+
+/// Counts a request as in flight until dropped
+pub struct InFlightGuard {
+    in_flight: Arc<AtomicU64>,
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        let now = self.in_flight.fetch_sub(1, Ordering::Relaxed) - 1;
+        METRICS.upstream_in_flight.set(now as i64);
+    }
+}
+
+/// A response body that keeps its request in flight until the body ends or
+/// is dropped, so streamed responses count towards the limit for as long as
+/// they hold the upstream connection
+pub struct InFlightBody {
+    inner: Body,
+    guard: Option<InFlightGuard>,
+}
+
+impl InFlightBody {
+    pub fn new(inner: Body, guard: InFlightGuard) -> Self {
+        Self {
+            inner,
+            guard: Some(guard),
+        }
+    }
+}
+
+impl HttpBody for InFlightBody {
+    type Data = Bytes;
+    type Error = axum::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, axum::Error>>> {
+        let this = self.get_mut();
+        let frame = Pin::new(&mut this.inner).poll_frame(cx);
+        if let Poll::Ready(None) = frame {
+            this.guard.take();
+        }
+        frame
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+impl LoadShedder {
+    /// A threshold of 0 disables that trigger
+    pub fn new(p99_threshold_ms: u64, max_in_flight: u64, retry_after_secs: u64) -> Self {
+        Self {
+            p99_threshold: (p99_threshold_ms > 0).then(|| Duration::from_millis(p99_threshold_ms)),
+            max_in_flight: (max_in_flight > 0).then_some(max_in_flight),
+            retry_after_secs,
+            in_flight: Arc::new(AtomicU64::new(0)),
+            state: Mutex::new(ShedState {
+                samples: VecDeque::new(),
+                shedding: false,
+                p99: None,
+                evaluated_at: Instant::now(),
+            }),
+        }
+    }
+
+    pub fn retry_after_secs(&self) -> u64 {
+        self.retry_after_secs
+    }
+
+    /// Decides whether the next request is shed, entering or leaving shed
+    /// mode as the upstream's load crosses the thresholds
+    pub fn should_shed(&self) -> bool {
+        if self.p99_threshold.is_none() && self.max_in_flight.is_none() {
+            return false;
+        }
+
+        let in_flight = self.in_flight.load(Ordering::Relaxed);
+        let mut state = self.state.lock().unwrap();
+
+        if state.evaluated_at.elapsed() >= EVALUATE_EVERY {
+            state.p99 = p99(&mut state.samples);
+            state.evaluated_at = Instant::now();
+        }
+
+        let overloaded = self.over_limits(in_flight, state.p99, 1.0);
+        let recovered = !self.over_limits(in_flight, state.p99, RECOVERY_RATIO);
+
+        if !state.shedding && overloaded {
+            state.shedding = true;
+            METRICS.shed_active.set(1);
+            warn!(in_flight, p99 = ?state.p99, "Upstream overloaded, shedding load");
+        } else if state.shedding && recovered {
+            state.shedding = false;
+            METRICS.shed_active.set(0);
+            warn!(in_flight, p99 = ?state.p99, "Upstream recovered, no longer shedding");
+        }
+
+        if state.shedding {
+            METRICS.requests_shed.inc();
+        }
+        state.shedding
+    }
+
+    pub fn start(&self) -> InFlightGuard {
+        let now = self.in_flight.fetch_add(1, Ordering::Relaxed) + 1;
+        METRICS.upstream_in_flight.set(now as i64);
+        InFlightGuard {
+            in_flight: self.in_flight.clone(),
+        }
+    }
+
+    pub fn record_latency(&self, latency: Duration) {
+        if self.p99_threshold.is_none() {
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        state.samples.push_back((Instant::now(), latency));
+    }
+
+    fn over_limits(&self, in_flight: u64, p99: Option<Duration>, ratio: f64) -> bool {
+        let in_flight_over = self
+            .max_in_flight
+            .is_some_and(|max| in_flight as f64 >= max as f64 * ratio);
+        let latency_over = match (self.p99_threshold, p99) {
+            (Some(threshold), Some(p99)) => p99 >= threshold.mul_f64(ratio),
+            _ => false,
+        };
+        in_flight_over || latency_over
+    }
+}
+
+/// Drops expired samples and returns the p99 of the rest. While shedding no
+/// new samples arrive, so the window empties and lets traffic back in.
+fn p99(samples: &mut VecDeque<(Instant, Duration)>) -> Option<Duration> {
+    while samples
+        .front()
+        .is_some_and(|(at, _)| at.elapsed() > LATENCY_WINDOW)
+    {
+        samples.pop_front();
+    }
+
+    if samples.len() < MIN_SAMPLES {
+        return None;
+    }
+
+    let mut latencies: Vec<Duration> = samples.iter().map(|(_, l)| *l).collect();
+    latencies.sort_unstable();
+    let idx = ((latencies.len() as f64 * 0.99).ceil() as usize).saturating_sub(1);
+    latencies.get(idx).copied()
+}
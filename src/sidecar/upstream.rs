@@ -0,0 +1,64 @@
+use std::time::Duration;
+
+use reqwest::{Client, Version};
+use serde::{Deserialize, Serialize};
+
+use crate::sidecar::{config::SidecarConfig, error::ProxyError};
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum UpstreamHttpVersion {
+    #[default]
+    Auto, // HTTP/2 when the upstream offers it over TLS (ALPN), else HTTP/1.1
+    Http1, // HTTP/1.1 only
+    Http2, // HTTP/2 without negotiation, also over plain http (h2c)
+}
+
+/// Client for requests to the upstream, with the connection settings from
+/// the `upstream_*` config
+pub fn upstream_client(cfg: &SidecarConfig) -> Result<Client, ProxyError> {
+    let mut builder = Client::builder()
+        .pool_max_idle_per_host(cfg.upstream_max_idle_connections)
+        .pool_idle_timeout(Duration::from_secs(cfg.upstream_idle_timeout_secs))
+        .tcp_nodelay(true);
+
+    if cfg.upstream_connect_timeout_ms > 0 {
+        builder = builder.connect_timeout(Duration::from_millis(cfg.upstream_connect_timeout_ms));
+    }
+    if cfg.upstream_tcp_keepalive_secs > 0 {
+        builder = builder.tcp_keepalive(Duration::from_secs(cfg.upstream_tcp_keepalive_secs));
+    }
+
+    builder = match cfg.upstream_http_version {
+        UpstreamHttpVersion::Auto => builder,
+        UpstreamHttpVersion::Http1 => builder.http1_only(),
+        UpstreamHttpVersion::Http2 => builder.http2_prior_knowledge(),
+    };
+    if cfg.upstream_http_version != UpstreamHttpVersion::Http1 {
+        builder = builder.http2_adaptive_window(cfg.upstream_http2_adaptive_window);
+        // Pings keep idle multiplexed connections from being dropped by
+        // load balancers in between
+        if cfg.upstream_http2_keepalive_secs > 0 {
+            builder = builder
+                .http2_keep_alive_interval(Duration::from_secs(cfg.upstream_http2_keepalive_secs))
+                .http2_keep_alive_timeout(Duration::from_secs(
+                    cfg.upstream_http2_keepalive_secs.max(10),
+                ))
+                .http2_keep_alive_while_idle(true);
+        }
+    }
+
+    Ok(builder.build()?)
+}
+
+/// Label for the protocol an upstream response came back over
+pub fn version_label(version: Version) -> &'static str {
+    match version {
+        Version::HTTP_09 => "0.9",
+        Version::HTTP_10 => "1.0",
+        Version::HTTP_11 => "1.1",
+        Version::HTTP_2 => "2",
+        Version::HTTP_3 => "3",
+        _ => "unknown",
+    }
+}
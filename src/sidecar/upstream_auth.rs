@@ -0,0 +1,228 @@
+use std::time::{Duration, Instant};
+
+use axum::http::{HeaderName, HeaderValue, header::AUTHORIZATION};
+use base64::{Engine, engine::general_purpose::STANDARD};
+use reqwest::RequestBuilder;
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+use tracing::info;
+
+use crate::sidecar::{config::SidecarConfig, error::ProxyError};
+
+/// A token is refreshed this long before it expires
+const TOKEN_REFRESH_MARGIN: Duration = Duration::from_secs(30);
+
+/// Lifetime assumed when the token endpoint omits `expires_in`
+const DEFAULT_TOKEN_LIFETIME: Duration = Duration::from_secs(300);
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum UpstreamAuthMode {
+    #[default]
+    None, // upstream is reached without credentials
+    Header, // static header, e.g. X-Api-Key: <key>
+    Basic,  // Authorization: Basic <user:password>
+    #[serde(rename = "oauth2")]
+    OAuth2, // client-credentials grant, Authorization: Bearer <token>
+}
+
+/// Provider credentials added to every upstream request. Clients never send
+/// or see them: a client header with the same name is dropped before the
+/// request is forwarded.
+pub enum UpstreamAuth {
+    None,
+    Header {
+        name: HeaderName,
+        value: HeaderValue,
+    },
+    Basic {
+        username: String,
+        password: String,
+    },
+    OAuth2(OAuth2Client),
+}
+
+impl UpstreamAuth {
+    pub fn from_config(cfg: &SidecarConfig, http: reqwest::Client) -> Result<Self, ProxyError> {
+        match cfg.upstream_auth {
+            UpstreamAuthMode::None => Ok(Self::None),
+            UpstreamAuthMode::Header => {
+                let name = required(&cfg.upstream_auth_header, "upstream_auth_header")?;
+                let value = required(&cfg.upstream_auth_value, "upstream_auth_value")?;
+                let name = HeaderName::try_from(name).map_err(|e| {
+                    ProxyError::ConfigError(format!("invalid upstream_auth_header: {}", e))
+                })?;
+                let mut value = HeaderValue::try_from(value).map_err(|e| {
+                    ProxyError::ConfigError(format!("invalid upstream_auth_value: {}", e))
+                })?;
+                value.set_sensitive(true);
+                Ok(Self::Header { name, value })
+            }
+            UpstreamAuthMode::Basic => Ok(Self::Basic {
+                username: required(&cfg.upstream_auth_username, "upstream_auth_username")?
+                    .to_string(),
+                password: required(&cfg.upstream_auth_password, "upstream_auth_password")?
+                    .to_string(),
+            }),
+            UpstreamAuthMode::OAuth2 => Ok(Self::OAuth2(OAuth2Client {
+                http,
+                token_url: required(&cfg.upstream_oauth_token_url, "upstream_oauth_token_url")?
+                    .to_string(),
+                client_id: required(&cfg.upstream_oauth_client_id, "upstream_oauth_client_id")?
+                    .to_string(),
+                client_secret: required(
+                    &cfg.upstream_oauth_client_secret,
+                    "upstream_oauth_client_secret",
+                )?
+                .to_string(),
+                scope: cfg.upstream_oauth_scope.clone().filter(|s| !s.is_empty()),
+                token: RwLock::new(None),
+            })),
+        }
+    }
+
+    /// The header the credentials are sent in
+    pub fn header_name(&self) -> Option<HeaderName> {
+        match self {
+            Self::None => None,
+            Self::Header { name, .. } => Some(name.clone()),
+            Self::Basic { .. } | Self::OAuth2(_) => Some(AUTHORIZATION),
+        }
+    }
+
+    pub async fn apply(&self, req: RequestBuilder) -> Result<RequestBuilder, ProxyError> {
+        match self {
+            Self::None => Ok(req),
+            Self::Header { name, value } => Ok(req.header(name, value)),
+            Self::Basic { username, password } => Ok(req.basic_auth(username, Some(password))),
+            Self::OAuth2(client) => Ok(req.header(AUTHORIZATION, client.authorization().await?)),
+        }
+    }
+
+    /// The credentials as a header, for upstream connections not made with
+    /// reqwest
+    pub async fn header(&self) -> Result<Option<(HeaderName, HeaderValue)>, ProxyError> {
+        match self {
+            Self::None => Ok(None),
+            Self::Header { name, value } => Ok(Some((name.clone(), value.clone()))),
+            Self::Basic { username, password } => {
+                let encoded = STANDARD.encode(format!("{}:{}", username, password));
+                let mut value = HeaderValue::try_from(format!("Basic {}", encoded))
+                    .map_err(|e| ProxyError::InternalError(e.to_string()))?;
+                value.set_sensitive(true);
+                Ok(Some((AUTHORIZATION, value)))
+            }
+            Self::OAuth2(client) => Ok(Some((AUTHORIZATION, client.authorization().await?))),
+        }
+    }
+
+    /// Called when the upstream rejects the credentials, so an OAuth2 token
+    /// revoked before its expiry is replaced on the next request
+    pub async fn rejected(&self) {
+        if let Self::OAuth2(client) = self {
+            client.token.write().await.take();
+        }
+    }
+}
+
+fn required<'a>(value: &'a Option<String>, name: &str) -> Result<&'a str, ProxyError> {
+    match value.as_deref() {
+        Some(v) if !v.is_empty() => Ok(v),
+        _ => Err(ProxyError::ConfigError(format!(
+            "{} must be set for this upstream_auth mode",
+            name
+        ))),
+    }
+}
+
+/// OAuth2 client-credentials grant with the token cached until shortly
+/// before it expires
+pub struct OAuth2Client {
+    http: reqwest::Client,
+    token_url: String,
+    client_id: String,
+    client_secret: String,
+    scope: Option<String>,
+    token: RwLock<Option<CachedToken>>,
+}
+
+struct CachedToken {
+    header: HeaderValue,
+    refresh_at: Instant,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: Option<u64>,
+}
+
+impl OAuth2Client {
+    async fn authorization(&self) -> Result<HeaderValue, ProxyError> {
+        if let Some(token) = self.token.read().await.as_ref() {
+            if token.refresh_at > Instant::now() {
+                return Ok(token.header.clone());
+            }
+        }
+
+        // Requests waiting here reuse the token the first one fetched
+        let mut token = self.token.write().await;
+        if let Some(cached) = token.as_ref() {
+            if cached.refresh_at > Instant::now() {
+                return Ok(cached.header.clone());
+            }
+        }
+
+        let fetched = self.fetch().await?;
+        let header = fetched.header.clone();
+        *token = Some(fetched);
+
+        Ok(header)
+    }
+
+    async fn fetch(&self) -> Result<CachedToken, ProxyError> {
+        let mut form = vec![("grant_type", "client_credentials")];
+        if let Some(scope) = &self.scope {
+            form.push(("scope", scope.as_str()));
+        }
+
+        let resp = self
+            .http
+            .post(&self.token_url)
+            .basic_auth(&self.client_id, Some(&self.client_secret))
+            .form(&form)
+            .send()
+            .await?;
+
+        if !resp.status().is_success() {
+            return Err(ProxyError::BadGateway(format!(
+                "upstream token endpoint returned {}",
+                resp.status()
+            )));
+        }
+
+        let body: TokenResponse = resp.json().await?;
+        let mut header = HeaderValue::try_from(format!("Bearer {}", body.access_token))
+            .map_err(|_| ProxyError::BadGateway("upstream token is not a valid header".into()))?;
+        header.set_sensitive(true);
+
+        let lifetime = body
+            .expires_in
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_TOKEN_LIFETIME);
+        let refresh_in = if lifetime > TOKEN_REFRESH_MARGIN * 2 {
+            lifetime - TOKEN_REFRESH_MARGIN
+        } else {
+            lifetime / 2
+        };
+        info!(
+            expires_in_secs = lifetime.as_secs(),
+            "Fetched upstream OAuth2 token"
+        );
+
+        Ok(CachedToken {
+            header,
+            refresh_at: Instant::now() + refresh_in,
+        })
+    }
+}
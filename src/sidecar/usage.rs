@@ -0,0 +1,126 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use tokio::sync::Notify;
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+use crate::{
+    api_types::validator::RecordUsageRequest,
+    sidecar::{metrics::METRICS, proxy::ProxyState},
+};
+
+/// Usage waiting to be posted to `/record_usage/batch`. Each record gets
+/// its request ID when it is queued, so a batch retried after a timeout is
+/// only charged once. Records still queued when the sidecar exits, and those
+/// of a batch that fails all its attempts, are never charged.
+pub struct UsageReporter {
+    batch_size: usize,
+    flush_interval: Duration,
+    pending: Mutex<Vec<RecordUsageRequest>>,
+    full: Notify,
+}
+
+impl UsageReporter {
+    /// A `batch_size` of 0 posts each record to `/record_usage` on its own
+    pub fn new(batch_size: usize, flush_interval_ms: u64) -> Self {
+        Self {
+            batch_size,
+            flush_interval: Duration::from_millis(flush_interval_ms),
+            pending: Mutex::new(Vec::new()),
+            full: Notify::new(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.batch_size > 0
+    }
+
+    fn push(&self, record: RecordUsageRequest) {
+        let mut pending = self.pending.lock().unwrap();
+        pending.push(record);
+        if pending.len() >= self.batch_size {
+            self.full.notify_one();
+        }
+    }
+
+    fn drain(&self) -> Vec<RecordUsageRequest> {
+        std::mem::take(&mut *self.pending.lock().unwrap())
+    }
+}
+
+/// Reports `cost` of usage on an entitlement: queued for the next batch, or
+/// posted right away when batching is off
+pub fn report_usage(state: &Arc<ProxyState>, user_address: &str, entitlement_id: &str, cost: u64) {
+    if !state.usage.is_enabled() {
+        let state = state.clone();
+        let user_address = user_address.to_string();
+        let entitlement_id = entitlement_id.to_string();
+        tokio::spawn(async move {
+            let _ = state
+                .validator
+                .record_usage(&user_address, &entitlement_id, cost)
+                .await;
+        });
+        return;
+    }
+
+    state.usage.push(RecordUsageRequest {
+        user_address: user_address.to_string(),
+        entitlement_id: entitlement_id.to_string(),
+        cost,
+        request_id: Some(Uuid::new_v4().to_string()),
+    });
+}
+
+/// Posts queued usage every flush interval, or as soon as a batch is full.
+/// Runs for the lifetime of the sidecar.
+pub async fn run_reporter(state: Arc<ProxyState>) {
+    let mut ticker = tokio::time::interval(state.usage.flush_interval);
+
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {}
+            _ = state.usage.full.notified() => {}
+        }
+        flush(&state);
+    }
+}
+
+fn flush(state: &Arc<ProxyState>) {
+    let mut records = state.usage.drain();
+
+    while !records.is_empty() {
+        let rest = records.split_off(records.len().min(state.usage.batch_size));
+        let batch = std::mem::replace(&mut records, rest);
+        let count = batch.len();
+
+        let state = state.clone();
+        tokio::spawn(async move {
+            match state.validator.record_usage_batch(batch).await {
+                Ok(results) => {
+                    // 409 means an earlier attempt already charged it
+                    let failed = results
+                        .iter()
+                        .filter(|r| !matches!(r.status, 200 | 409))
+                        .count();
+                    if failed > 0 {
+                        warn!(
+                            failed,
+                            records = count,
+                            "Validator API rejected usage records"
+                        );
+                        METRICS.usage_records_failed.inc_by(failed as f64);
+                    }
+                    debug!(records = count, "Usage batch posted");
+                }
+                Err(e) => {
+                    warn!(error = %e, records = count, "Failed to post usage batch");
+                    METRICS.usage_records_failed.inc_by(count as f64);
+                }
+            }
+        });
+    }
+}
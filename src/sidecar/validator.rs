@@ -1,41 +1,32 @@
-use chrono::{DateTime, Utc};
-use reqwest::Client;
-use serde::{Deserialize, Serialize};
-use std::time::Duration;
+use reqwest::{Client, RequestBuilder, Response};
+use std::{future::Future, time::Duration};
 use tracing::{error, warn};
+use uuid::Uuid;
 
-use crate::sidecar::cache::CachedEntitlement;
+use crate::{
+    api_types::{
+        validator::{
+            IDEMPOTENCY_KEY, RecordUsageBatchRequest, RecordUsageBatchResponse,
+            RecordUsageBatchResult, RecordUsageRequest, ScheduledMaintenance, SidecarHeartbeat,
+            ValidateBatchRequest, ValidateBatchResponse, ValidateBatchResult, ValidateRequest,
+            ValidateResponse,
+        },
+        version::{self, ACCEPT_VERSION},
+    },
+    sidecar::{breaker::CircuitBreaker, cache::CachedEntitlement},
+};
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct ValidateRequest {
-    pub user_address: String,
-    pub service_id: String,
-    pub request_cost: u64,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct ValidateResponse {
-    pub entitlement_id: String,
-    pub tier: String,
-    pub quota: Option<u64>,
-    pub units: Option<u64>,
-    pub tier_type: u8,
-    pub expires_at: Option<DateTime<Utc>>,
-    pub notify_provider: Option<ProviderNotification>,
-}
-
-#[derive(Debug, Deserialize, Serialize, Clone)]
-pub struct ProviderNotification {
-    pub event: String,
-    pub user_address: String,
-    pub service_id: String,
-    pub detail: serde_json::Value,
-}
+/// Tries per usage submission, including the first
+const USAGE_ATTEMPTS: u32 = 3;
+/// Wait before each retry, multiplied by the attempt number
+const USAGE_RETRY_DELAY: Duration = Duration::from_millis(200);
 
 pub struct ValidatorClient {
     client: Client,
     api_url: String,
     api_key: String,
+    tier_detail: bool,
+    breaker: CircuitBreaker,
 }
 
 impl ValidatorClient {
@@ -52,7 +43,56 @@ impl ValidatorClient {
             client,
             api_url,
             api_key,
+            tier_detail: false,
+            breaker: CircuitBreaker::new(0, Duration::ZERO),
+        }
+    }
+
+    /// Request tier name, price and coin type along with each validation
+    pub fn with_tier_detail(mut self, enabled: bool) -> Self {
+        self.tier_detail = enabled;
+        self
+    }
+
+    /// Fail validations at once, without calling the API, for `cooldown`
+    /// after `failures` in a row were unreachable or answered 5xx
+    pub fn with_circuit_breaker(mut self, failures: u32, cooldown: Duration) -> Self {
+        self.breaker = CircuitBreaker::new(failures, cooldown);
+        self
+    }
+
+    /// Runs a validation call through the circuit breaker
+    async fn guarded<T>(
+        &self,
+        call: impl Future<Output = Result<T, ValidatorError>>,
+    ) -> Result<T, ValidatorError> {
+        if !self.breaker.allow() {
+            return Err(ValidatorError::CircuitOpen);
+        }
+
+        let result = call.await;
+        match &result {
+            Err(e) if e.is_transient() => self.breaker.record_failure(),
+            _ => self.breaker.record_success(),
         }
+        result
+    }
+
+    /// A POST to the validator API, authenticated and tagged with the
+    /// contract version this sidecar was built against
+    fn post(&self, url: &str) -> RequestBuilder {
+        self.client
+            .post(url)
+            .header("Authorization", format!("Bearer {}", self.api_key))
+            .header(ACCEPT_VERSION, version::CURRENT.requirement())
+    }
+
+    /// A GET to the validator API, tagged like `post`
+    fn get(&self, url: &str) -> RequestBuilder {
+        self.client
+            .get(url)
+            .header("Authorization", format!("Bearer {}", self.api_key))
+            .header(ACCEPT_VERSION, version::CURRENT.requirement())
     }
 
     pub async fn validate(
@@ -61,13 +101,24 @@ impl ValidatorClient {
         service_id: &str,
         cost: u64,
     ) -> Result<ValidateResponse, ValidatorError> {
-        let url = format!("{}/validate", self.api_url);
+        self.guarded(self.send_validate(user_address, service_id, cost))
+            .await
+    }
+
+    async fn send_validate(
+        &self,
+        user_address: &str,
+        service_id: &str,
+        cost: u64,
+    ) -> Result<ValidateResponse, ValidatorError> {
+        let url = if self.tier_detail {
+            format!("{}/validate?detail=full", self.api_url)
+        } else {
+            format!("{}/validate", self.api_url)
+        };
 
         let resp = self
-            .client
             .post(&url)
-            .header("Authorization", format!("Bearer {}", self.api_key))
-            .header("Content-Type", "application/json")
             .json(&ValidateRequest {
                 user_address: user_address.to_string(),
                 service_id: service_id.to_string(),
@@ -82,7 +133,7 @@ impl ValidatorClient {
 
         if !resp.status().is_success() {
             warn!(status = %resp.status(), "Validator API returned non-2xx");
-            return Err(ValidatorError::ApiError(resp.status().as_u16()));
+            return Err(ValidatorError::from_response(resp).await);
         }
 
         resp.json::<ValidateResponse>().await.map_err(|e| {
@@ -91,6 +142,53 @@ impl ValidatorClient {
         })
     }
 
+    /// Validates many buyers and services in one call, e.g. to fill the
+    /// cache at startup. Results come back in the order of `items`, with a
+    /// per-item `status` as `/validate` would have answered.
+    pub async fn validate_batch(
+        &self,
+        items: Vec<ValidateRequest>,
+    ) -> Result<Vec<ValidateBatchResult>, ValidatorError> {
+        self.guarded(self.send_validate_batch(items)).await
+    }
+
+    async fn send_validate_batch(
+        &self,
+        items: Vec<ValidateRequest>,
+    ) -> Result<Vec<ValidateBatchResult>, ValidatorError> {
+        let url = if self.tier_detail {
+            format!("{}/validate/batch?detail=full", self.api_url)
+        } else {
+            format!("{}/validate/batch", self.api_url)
+        };
+
+        let resp = self
+            .post(&url)
+            .json(&ValidateBatchRequest { items })
+            .send()
+            .await
+            .map_err(|e| {
+                error!(error = %e, "Validator API unreachable");
+                ValidatorError::Unreachable(e.to_string())
+            })?;
+
+        if !resp.status().is_success() {
+            warn!(status = %resp.status(), "Validator API returned non-2xx");
+            return Err(ValidatorError::from_response(resp).await);
+        }
+
+        resp.json::<ValidateBatchResponse>()
+            .await
+            .map(|r| r.results)
+            .map_err(|e| {
+                error!(error = %e, "Failed to parse validator response");
+                ValidatorError::ParseError(e.to_string())
+            })
+    }
+
+    /// Posts usage under a fresh request ID, retrying transient failures
+    /// with the same ID and `Idempotency-Key` so a call that landed but went
+    /// unanswered isn't charged twice
     pub async fn record_usage(
         &self,
         user_address: &str,
@@ -98,17 +196,95 @@ impl ValidatorClient {
         cost: u64,
     ) -> Result<(), ValidatorError> {
         let url = format!("{}/record_usage", self.api_url);
+        let request_id = Uuid::new_v4().to_string();
+        let request = RecordUsageRequest {
+            user_address: user_address.to_string(),
+            entitlement_id: entitlement_id.to_string(),
+            cost,
+            request_id: Some(request_id.clone()),
+        };
+
+        let mut attempt = 1;
+        loop {
+            match self.send_usage(&url, &request_id, &request).await {
+                Err(e) if e.is_transient() && attempt < USAGE_ATTEMPTS => {
+                    warn!(error = %e, attempt, "Retrying record_usage");
+                    tokio::time::sleep(USAGE_RETRY_DELAY * attempt).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
 
+    /// Posts usage records in one call, retrying transient failures with
+    /// the same request IDs so records that landed aren't charged twice.
+    /// Every record must carry a `request_id`.
+    pub async fn record_usage_batch(
+        &self,
+        records: Vec<RecordUsageRequest>,
+    ) -> Result<Vec<RecordUsageBatchResult>, ValidatorError> {
+        let url = format!("{}/record_usage/batch", self.api_url);
+        let request = RecordUsageBatchRequest { records };
+        let idempotency_key = Uuid::new_v4().to_string();
+
+        let mut attempt = 1;
+        loop {
+            match self
+                .send_usage_batch(&url, &idempotency_key, &request)
+                .await
+            {
+                Err(e) if e.is_transient() && attempt < USAGE_ATTEMPTS => {
+                    warn!(error = %e, attempt, "Retrying record_usage batch");
+                    tokio::time::sleep(USAGE_RETRY_DELAY * attempt).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    async fn send_usage_batch(
+        &self,
+        url: &str,
+        idempotency_key: &str,
+        request: &RecordUsageBatchRequest,
+    ) -> Result<Vec<RecordUsageBatchResult>, ValidatorError> {
         let resp = self
-            .client
-            .post(&url)
-            .header("Authorization", format!("Bearer {}", self.api_key))
-            .header("Content-Type", "application/json")
-            .json(&serde_json::json!({
-                "user_address": user_address,
-                "entitlement_id": entitlement_id,
-                "cost": cost,
-            }))
+            .post(url)
+            .header(IDEMPOTENCY_KEY, idempotency_key)
+            .json(request)
+            .send()
+            .await
+            .map_err(|e| {
+                error!(error = %e, "Validator API unreachable");
+                ValidatorError::Unreachable(e.to_string())
+            })?;
+
+        if !resp.status().is_success() {
+            warn!(status = %resp.status(), "Validator API returned non-2xx on record_usage batch");
+            return Err(ValidatorError::from_response(resp).await);
+        }
+
+        resp.json::<RecordUsageBatchResponse>()
+            .await
+            .map(|r| r.results)
+            .map_err(|e| {
+                error!(error = %e, "Failed to parse validator response");
+                ValidatorError::ParseError(e.to_string())
+            })
+    }
+
+    async fn send_usage(
+        &self,
+        url: &str,
+        idempotency_key: &str,
+        request: &RecordUsageRequest,
+    ) -> Result<(), ValidatorError> {
+        let resp = self
+            .post(url)
+            .header(IDEMPOTENCY_KEY, idempotency_key)
+            .json(request)
             .send()
             .await
             .map_err(|e| {
@@ -116,13 +292,56 @@ impl ValidatorClient {
                 ValidatorError::Unreachable(e.to_string())
             })?;
 
+        // An earlier attempt was committed, or is still running
+        if resp.status() == reqwest::StatusCode::CONFLICT {
+            return Ok(());
+        }
         if !resp.status().is_success() {
             warn!(status = %resp.status(), "Validator API returned non-2xx on record_usage");
-            return Err(ValidatorError::ApiError(resp.status().as_u16()));
+            return Err(ValidatorError::from_response(resp).await);
         }
 
         Ok(())
     }
+
+    pub async fn heartbeat(&self, heartbeat: &SidecarHeartbeat) -> Result<(), ValidatorError> {
+        let url = format!("{}/sidecars/heartbeat", self.api_url);
+
+        let resp = self
+            .post(&url)
+            .json(heartbeat)
+            .send()
+            .await
+            .map_err(|e| ValidatorError::Unreachable(e.to_string()))?;
+
+        if !resp.status().is_success() {
+            return Err(ValidatorError::from_response(resp).await);
+        }
+
+        Ok(())
+    }
+
+    /// Active and upcoming maintenance windows across the provider's services
+    pub async fn upcoming_maintenance(
+        &self,
+        provider_id: &str,
+    ) -> Result<Vec<ScheduledMaintenance>, ValidatorError> {
+        let url = format!("{}/providers/{}/maintenance", self.api_url, provider_id);
+
+        let resp = self
+            .get(&url)
+            .send()
+            .await
+            .map_err(|e| ValidatorError::Unreachable(e.to_string()))?;
+
+        if !resp.status().is_success() {
+            return Err(ValidatorError::from_response(resp).await);
+        }
+
+        resp.json::<Vec<ScheduledMaintenance>>()
+            .await
+            .map_err(|e| ValidatorError::ParseError(e.to_string()))
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -133,9 +352,29 @@ pub enum ValidatorError {
     ApiError(u16),
     #[error("Parse error: {0}")]
     ParseError(String),
+    #[error("Validator API version mismatch: {0}")]
+    VersionMismatch(String),
+    #[error("Validator API circuit open")]
+    CircuitOpen,
 }
 
 impl ValidatorError {
+    async fn from_response(resp: Response) -> Self {
+        if resp.status() != reqwest::StatusCode::NOT_ACCEPTABLE {
+            return ValidatorError::ApiError(resp.status().as_u16());
+        }
+
+        let body: serde_json::Value = resp.json().await.unwrap_or_default();
+        let reason = body["error"]
+            .as_str()
+            .unwrap_or("backend does not serve this sidecar's API version");
+        error!(
+            sidecar_api_version = %version::CURRENT,
+            reason, "Validator API rejected the sidecar's API version; upgrade the backend"
+        );
+        ValidatorError::VersionMismatch(reason.to_string())
+    }
+
     pub fn is_transient(&self) -> bool {
         matches!(
             self,
@@ -153,5 +392,9 @@ pub fn to_cached(resp: &ValidateResponse) -> CachedEntitlement {
         tier_type: resp.tier_type,
         expires_at: resp.expires_at,
         cached_at: None,
+        tier_name: resp.tier_name.clone(),
+        price: resp.price,
+        coin_type: resp.coin_type.clone(),
+        replaced_tier: resp.replaced_tier.clone(),
     }
 }
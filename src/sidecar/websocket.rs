@@ -0,0 +1,388 @@
+use std::{sync::Arc, time::Duration};
+
+use axum::{
+    extract::{
+        FromRequestParts, Request,
+        ws::{self, Utf8Bytes, WebSocket, WebSocketUpgrade},
+    },
+    http::{
+        HeaderMap, HeaderName, HeaderValue, StatusCode,
+        header::{
+            CONNECTION, HOST, SEC_WEBSOCKET_EXTENSIONS, SEC_WEBSOCKET_KEY, SEC_WEBSOCKET_PROTOCOL,
+            SEC_WEBSOCKET_VERSION, UPGRADE,
+        },
+    },
+    response::{IntoResponse, Response},
+};
+use futures::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use tokio::net::TcpStream;
+use tokio_tungstenite::{
+    MaybeTlsStream, WebSocketStream, connect_async,
+    tungstenite::{self, client::IntoClientRequest, protocol::frame::coding::CloseCode},
+};
+use tracing::{debug, warn};
+
+use crate::sidecar::{
+    error::ProxyError,
+    metrics::{METRICS, QuotaOutcome},
+    proxy::{Admission, Admit, LOW_QUOTA_THRESHOLD, ProxyState, admit, deny_response, fails_open},
+    usage::report_usage,
+};
+
+/// Close code sent when the entitlement or quota runs out mid-connection
+const POLICY_VIOLATION: u16 = 1008;
+
+/// Close code sent when the quota can't be checked
+const INTERNAL_ERROR: u16 = 1011;
+
+/// How often an open connection re-checks its entitlement, and the period
+/// charged in the minute metering mode
+const RECHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Handshake headers the upstream connection sets for itself
+const HANDSHAKE_HEADERS: [HeaderName; 6] = [
+    HOST,
+    CONNECTION,
+    UPGRADE,
+    SEC_WEBSOCKET_KEY,
+    SEC_WEBSOCKET_VERSION,
+    SEC_WEBSOCKET_EXTENSIONS,
+];
+
+type UpstreamSocket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum WsMetering {
+    #[default]
+    /// `ws_cost` per text or binary message from the client
+    Message,
+    /// `ws_cost` per started minute the connection is open
+    Minute,
+}
+
+/// Why the sidecar is closing a connection
+struct Closing {
+    code: u16,
+    reason: &'static str,
+}
+
+impl Closing {
+    fn policy(reason: &'static str) -> Self {
+        Self {
+            code: POLICY_VIOLATION,
+            reason,
+        }
+    }
+}
+
+pub fn is_upgrade(headers: &HeaderMap) -> bool {
+    headers
+        .get(UPGRADE)
+        .is_some_and(|v| v.as_bytes().eq_ignore_ascii_case(b"websocket"))
+}
+
+/// Admits the handshake like any request, charging its cost, then opens a
+/// connection to the upstream and relays between the two until either side
+/// closes or the entitlement runs out
+pub async fn websocket_handler(
+    state: Arc<ProxyState>,
+    req: Request,
+) -> Result<Response, ProxyError> {
+    let (mut parts, _) = req.into_parts();
+    let upgrade = match WebSocketUpgrade::from_request_parts(&mut parts, &state).await {
+        Ok(upgrade) => upgrade,
+        Err(rejection) => return Ok(rejection.into_response()),
+    };
+
+    let admission = match admit(&state, &parts.method, parts.uri.path(), &parts.headers).await? {
+        Admit::Granted(admission) => admission,
+        Admit::Denied(response) => return Ok(response),
+    };
+
+    let path_and_query = parts.uri.path_and_query().map_or("/", |p| p.as_str());
+    let upstream_url = format!(
+        "{}{}",
+        websocket_base(&state.cfg.upstream_url),
+        path_and_query
+    );
+    let mut upstream_req = upstream_url
+        .as_str()
+        .into_client_request()
+        .map_err(|e| ProxyError::InternalError(format!("Invalid upstream URL: {}", e)))?;
+
+    let injected = state.upstream_auth.header_name();
+    for (name, value) in parts.headers.iter() {
+        if HANDSHAKE_HEADERS.contains(name) || injected.as_ref() == Some(name) {
+            continue;
+        }
+        upstream_req.headers_mut().append(name, value.clone());
+    }
+
+    match state.upstream_auth.header().await {
+        Ok(Some((name, value))) => {
+            upstream_req.headers_mut().insert(name, value);
+        }
+        Ok(None) => {}
+        Err(e) => {
+            warn!(error = %e, "Upstream credentials unavailable");
+            return Ok(deny_response(
+                StatusCode::BAD_GATEWAY,
+                "upstream_auth_error",
+            )?);
+        }
+    }
+
+    let headers = upstream_req.headers_mut();
+    insert(headers, "X-Infrapass-User-Address", &admission.user_address);
+    insert(headers, "X-Infrapass-Validated", "true");
+    if let Some(name) = &admission.entitlement.tier_name {
+        insert(headers, "X-Infrapass-Tier-Name", name);
+    }
+    if let Some(price) = admission.entitlement.price {
+        insert(headers, "X-Infrapass-Tier-Price", &price.to_string());
+    }
+    if let Some(coin_type) = &admission.entitlement.coin_type {
+        insert(headers, "X-Infrapass-Coin-Type", coin_type);
+    }
+
+    let (upstream, upstream_resp) = match connect_async(upstream_req).await {
+        Ok(connected) => connected,
+        Err(e) => {
+            if let tungstenite::Error::Http(resp) = &e {
+                if resp.status() == StatusCode::UNAUTHORIZED {
+                    state.upstream_auth.rejected().await;
+                }
+            } else if matches!(e, tungstenite::Error::Io(_)) {
+                METRICS.upstream_connect_errors.inc();
+            }
+            warn!(error = %e, "Upstream WebSocket handshake failed");
+            return Ok(deny_response(StatusCode::BAD_GATEWAY, "upstream_error")?);
+        }
+    };
+
+    if !admission.sampled {
+        report_usage(
+            &state,
+            &admission.user_address,
+            &admission.entitlement.id,
+            admission.cost,
+        );
+    }
+
+    // Answer the client with the subprotocol the upstream picked
+    let protocol = upstream_resp
+        .headers()
+        .get(SEC_WEBSOCKET_PROTOCOL)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+    let upgrade = match protocol {
+        Some(protocol) => upgrade.protocols([protocol]),
+        None => upgrade,
+    };
+
+    Ok(upgrade.on_upgrade(move |client| relay(state, admission, client, upstream)))
+}
+
+async fn relay(
+    state: Arc<ProxyState>,
+    admission: Admission,
+    client: WebSocket,
+    upstream: UpstreamSocket,
+) {
+    METRICS.websocket_connections.inc();
+
+    let (mut client_tx, mut client_rx) = client.split();
+    let (mut upstream_tx, mut upstream_rx) = upstream.split();
+    // The handshake was just admitted and charged for the first minute, so
+    // the first tick is a minute in, as the second minute starts
+    let mut recheck = tokio::time::interval_at(
+        tokio::time::Instant::now() + RECHECK_INTERVAL,
+        RECHECK_INTERVAL,
+    );
+
+    let closing = loop {
+        tokio::select! {
+            msg = client_rx.next() => {
+                let Some(Ok(msg)) = msg else { break None };
+                let billable = matches!(msg, ws::Message::Text(_) | ws::Message::Binary(_));
+                if billable && state.cfg.ws_metering == WsMetering::Message {
+                    if let Err(closing) = charge(&state, &admission).await {
+                        break Some(closing);
+                    }
+                }
+                if let Some(msg) = to_upstream(msg) {
+                    if upstream_tx.send(msg).await.is_err() {
+                        break None;
+                    }
+                }
+            }
+            msg = upstream_rx.next() => {
+                let Some(Ok(msg)) = msg else { break None };
+                if let Some(msg) = to_client(msg) {
+                    if client_tx.send(msg).await.is_err() {
+                        break None;
+                    }
+                }
+            }
+            _ = recheck.tick() => {
+                if let Err(closing) = recheck_entitlement(&state, &admission).await {
+                    break Some(closing);
+                }
+                if state.cfg.ws_metering == WsMetering::Minute {
+                    if let Err(closing) = charge(&state, &admission).await {
+                        break Some(closing);
+                    }
+                }
+            }
+        }
+    };
+
+    if let Some(closing) = closing {
+        if closing.code == POLICY_VIOLATION {
+            METRICS.websocket_policy_closes.inc();
+        }
+        debug!(
+            user = %admission.user_address,
+            service = %admission.service_id,
+            reason = closing.reason,
+            "Closing WebSocket connection"
+        );
+        let _ = client_tx
+            .send(ws::Message::Close(Some(ws::CloseFrame {
+                code: closing.code,
+                reason: Utf8Bytes::from_static(closing.reason),
+            })))
+            .await;
+        let _ = upstream_tx.send(tungstenite::Message::Close(None)).await;
+    }
+
+    METRICS.websocket_connections.dec();
+}
+
+/// Charges `ws_cost` against the quota counter, re-seeding it once if it
+/// expired from the cache while the connection was open
+async fn charge(state: &Arc<ProxyState>, admission: &Admission) -> Result<(), Closing> {
+    let entitlement = &admission.entitlement;
+    let cost = state.cfg.ws_cost;
+    if !entitlement.metered() || cost == 0 {
+        return Ok(());
+    }
+
+    let user = &admission.user_address;
+    let service = &admission.service_id;
+    let mut result = state
+        .consume(user, service, cost, entitlement.tier_type)
+        .await;
+    if matches!(result, Ok(-2)) {
+        let _ = state.lookup_entitlement(user, service, cost).await;
+        result = state
+            .consume(user, service, cost, entitlement.tier_type)
+            .await;
+    }
+
+    let quota_key = state.quota_key(user, service);
+    match result {
+        Ok(-1) => {
+            METRICS.record_quota_outcome(QuotaOutcome::Exceeded);
+            METRICS.set_near_exhaustion(&quota_key, true);
+            Err(Closing::policy("quota_exceeded"))
+        }
+        Ok(-2) => {
+            METRICS.record_quota_outcome(QuotaOutcome::NotReady);
+            Err(Closing::policy("quota_not_ready"))
+        }
+        Ok(-3) => {
+            METRICS.record_quota_outcome(QuotaOutcome::UnknownTier);
+            Err(Closing::policy("unknown_tier_type"))
+        }
+        Ok(n) => {
+            METRICS.record_quota_outcome(QuotaOutcome::Allowed);
+            METRICS.set_near_exhaustion(&quota_key, n < LOW_QUOTA_THRESHOLD);
+            report_usage(state, user, &entitlement.id, cost);
+            Ok(())
+        }
+        Err(e) => {
+            warn!(error = %e, "Quota check failed on WebSocket connection");
+            Err(Closing {
+                code: INTERNAL_ERROR,
+                reason: "quota_unavailable",
+            })
+        }
+    }
+}
+
+/// Closes the connection once the entitlement no longer grants access, e.g.
+/// a subscription that expired or was revoked while it was open
+async fn recheck_entitlement(
+    state: &Arc<ProxyState>,
+    admission: &Admission,
+) -> Result<(), Closing> {
+    match state
+        .lookup_entitlement(
+            &admission.user_address,
+            &admission.service_id,
+            state.cfg.ws_cost,
+        )
+        .await
+    {
+        Ok((true, _)) => Ok(()),
+        Ok((false, _)) => Err(Closing::policy("access_denied, no entitlement")),
+        Err(e) if fails_open(state, &e) => Ok(()),
+        Err(_) => Err(Closing {
+            code: INTERNAL_ERROR,
+            reason: "validator_error",
+        }),
+    }
+}
+
+/// The upstream URL with its http(s) scheme swapped for ws(s)
+fn websocket_base(upstream_url: &str) -> String {
+    if let Some(rest) = upstream_url.strip_prefix("https://") {
+        format!("wss://{}", rest)
+    } else if let Some(rest) = upstream_url.strip_prefix("http://") {
+        format!("ws://{}", rest)
+    } else {
+        upstream_url.to_string()
+    }
+}
+
+fn insert(headers: &mut HeaderMap, name: &'static str, value: &str) {
+    if let Ok(value) = HeaderValue::from_str(value) {
+        headers.insert(name, value);
+    }
+}
+
+/// Pings and pongs are answered by each side's own connection and not relayed
+fn to_upstream(msg: ws::Message) -> Option<tungstenite::Message> {
+    match msg {
+        ws::Message::Text(text) => Some(tungstenite::Message::text(text.as_str().to_owned())),
+        ws::Message::Binary(data) => Some(tungstenite::Message::Binary(data)),
+        ws::Message::Close(frame) => Some(tungstenite::Message::Close(frame.map(|f| {
+            tungstenite::protocol::CloseFrame {
+                code: CloseCode::from(f.code),
+                reason: f.reason.as_str().to_owned().into(),
+            }
+        }))),
+        ws::Message::Ping(_) | ws::Message::Pong(_) => None,
+    }
+}
+
+fn to_client(msg: tungstenite::Message) -> Option<ws::Message> {
+    match msg {
+        tungstenite::Message::Text(text) => {
+            Some(ws::Message::Text(text.as_str().to_owned().into()))
+        }
+        tungstenite::Message::Binary(data) => Some(ws::Message::Binary(data)),
+        tungstenite::Message::Close(frame) => {
+            Some(ws::Message::Close(frame.map(|f| ws::CloseFrame {
+                code: f.code.into(),
+                reason: f.reason.as_str().to_owned().into(),
+            })))
+        }
+        tungstenite::Message::Ping(_)
+        | tungstenite::Message::Pong(_)
+        | tungstenite::Message::Frame(_) => None,
+    }
+}
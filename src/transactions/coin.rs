@@ -0,0 +1,235 @@
+use std::str::FromStr;
+
+use anyhow::{Result, anyhow};
+use sui_json_rpc_types::{Coin, SuiObjectDataFilter, SuiObjectDataOptions, SuiObjectResponseQuery};
+use sui_sdk::SuiClient;
+use sui_types::{
+    Identifier,
+    base_types::{ObjectID, SuiAddress},
+    parse_sui_struct_tag,
+    programmable_transaction_builder::ProgrammableTransactionBuilder,
+    transaction::{Argument, Command as SuiCommand, TransactionData},
+};
+
+use crate::{
+    client::client_ext::SuiClientExt,
+    ptb::object_ext::ObjectIDExt,
+    types::coin::CoinType,
+    utils::{coin::merge_coins, constants::DEFAULT_GAS_BUDGET, network::Network},
+};
+
+/// Finds the `TreasuryCap` for a test token in the sender's wallet. Only the
+/// wallet that published the test tokens holds one.
+pub async fn find_treasury_cap(
+    client: &SuiClient,
+    owner: SuiAddress,
+    coin_type: &CoinType,
+) -> Result<ObjectID> {
+    let cap_type = parse_sui_struct_tag(&format!(
+        "0x2::coin::TreasuryCap<{}>",
+        coin_type.to_type_tag()?
+    ))?;
+
+    let objects = client
+        .read_api()
+        .get_owned_objects(
+            owner,
+            Some(SuiObjectResponseQuery::new(
+                Some(SuiObjectDataFilter::StructType(cap_type)),
+                Some(SuiObjectDataOptions::new().with_type()),
+            )),
+            None,
+            Some(1),
+        )
+        .await?;
+
+    objects
+        .data
+        .into_iter()
+        .find_map(|o| o.data.map(|d| d.object_id))
+        .ok_or_else(|| {
+            anyhow!(
+                "No TreasuryCap<{}> owned by {}. Only the test token deployer can mint; \
+                 ask them to run `coin faucet --coin {} --recipient {}`",
+                coin_type.name(),
+                owner,
+                coin_type.name().to_lowercase(),
+                owner
+            )
+        })
+}
+
+pub async fn mint_test_token_tx(
+    client: &SuiClient,
+    sender: SuiAddress,
+    coin_type: CoinType,
+    amount: u64,
+    recipient: SuiAddress,
+) -> Result<TransactionData> {
+    if matches!(coin_type, CoinType::SUI) {
+        anyhow::bail!("SUI cannot be minted; use the network faucet instead");
+    }
+    if !Network::current().uses_test_tokens() {
+        anyhow::bail!("Test tokens can only be minted on test networks");
+    }
+    if let CoinType::Custom(tag) = &coin_type {
+        anyhow::bail!("Only the bundled test tokens can be minted, got {}", tag);
+    }
+
+    let package_id = ObjectID::from_hex_literal(&coin_type.package_id())?;
+    let treasury_cap = find_treasury_cap(client, sender, &coin_type).await?;
+
+    let mut ptb = ProgrammableTransactionBuilder::new();
+
+    let cap_arg = treasury_cap.to_owned_ptb_arg(client, &mut ptb).await?;
+    let amount_arg = ptb.pure(amount)?;
+    let recipient_arg = ptb.pure(recipient)?;
+
+    ptb.command(SuiCommand::move_call(
+        package_id,
+        Identifier::new(coin_type.name().to_lowercase())?,
+        Identifier::new("mint")?,
+        vec![],
+        vec![cap_arg, amount_arg, recipient_arg],
+    ));
+
+    let pt = ptb.finish();
+    client.build_tx_data(pt, sender).await
+}
+
+/// Coin objects merged per consolidation transaction, below the PTB input
+/// and gas payment limits
+pub const MAX_COINS_PER_CONSOLIDATION: usize = 250;
+
+/// Fetches up to `limit` coin objects of `coin_type` owned by `owner`,
+/// only those holding less than `below` base units when it is set
+async fn owned_coins(
+    client: &SuiClient,
+    owner: SuiAddress,
+    coin_type: &CoinType,
+    limit: usize,
+    below: Option<u64>,
+) -> Result<Vec<Coin>> {
+    let coin_type = coin_type.to_type_tag()?.to_string();
+    let mut coins = vec![];
+    let mut cursor = None;
+
+    loop {
+        let page = client
+            .coin_read_api()
+            .get_coins(owner, Some(coin_type.clone()), cursor, None)
+            .await?;
+        coins.extend(
+            page.data
+                .into_iter()
+                .filter(|c| below.is_none_or(|below| c.balance < below)),
+        );
+
+        if !page.has_next_page || coins.len() >= limit {
+            break;
+        }
+        cursor = page.next_cursor;
+    }
+
+    coins.truncate(limit);
+    Ok(coins)
+}
+
+/// Coin types `owner` holds in at least `min_coins` objects, with the
+/// number of objects of each
+pub async fn fragmented_coin_types(
+    client: &SuiClient,
+    owner: SuiAddress,
+    min_coins: usize,
+) -> Result<Vec<(CoinType, usize)>> {
+    let balances = client.coin_read_api().get_all_balances(owner).await?;
+
+    let mut fragmented = vec![];
+    for balance in balances {
+        if balance.coin_object_count >= min_coins.max(2) {
+            fragmented.push((
+                CoinType::from_str(&balance.coin_type)?,
+                balance.coin_object_count,
+            ));
+        }
+    }
+
+    Ok(fragmented)
+}
+
+/// Merges up to `MAX_COINS_PER_CONSOLIDATION` of the sender's coin objects
+/// of one type into a single object, only those below `below` base units
+/// when it is set. Returns `None` when fewer than `min_coins` qualify.
+///
+/// SUI is consolidated by paying gas with every coin, which the protocol
+/// smashes into the first one; other coins use a `MergeCoins` PTB.
+pub async fn consolidate_coins_tx(
+    client: &SuiClient,
+    sender: SuiAddress,
+    coin_type: &CoinType,
+    below: Option<u64>,
+    min_coins: usize,
+) -> Result<Option<(TransactionData, usize)>> {
+    let coins = owned_coins(
+        client,
+        sender,
+        coin_type,
+        MAX_COINS_PER_CONSOLIDATION,
+        below,
+    )
+    .await?;
+    if coins.len() < min_coins.max(2) {
+        return Ok(None);
+    }
+
+    let mut ptb = ProgrammableTransactionBuilder::new();
+
+    if *coin_type == CoinType::SUI {
+        let total: u64 = coins.iter().map(|c| c.balance).sum();
+        if total < DEFAULT_GAS_BUDGET {
+            anyhow::bail!(
+                "The {} SUI coins to merge hold {} MIST, less than the {} MIST gas budget",
+                coins.len(),
+                total,
+                DEFAULT_GAS_BUDGET
+            );
+        }
+
+        ptb.transfer_arg(sender, Argument::GasCoin);
+
+        let gas_payment = coins.iter().map(|c| c.object_ref()).collect();
+        let gas_price = client.read_api().get_reference_gas_price().await?;
+        let tx_data = TransactionData::new_programmable(
+            sender,
+            gas_payment,
+            ptb.finish(),
+            DEFAULT_GAS_BUDGET,
+            gas_price,
+        );
+        return Ok(Some((tx_data, coins.len())));
+    }
+
+    merge_coins(&mut ptb, &coins)?;
+
+    let pt = ptb.finish();
+    Ok(Some((client.build_tx_data(pt, sender).await?, coins.len())))
+}
+
+/// Requests gas from a Sui faucet HTTP endpoint
+pub async fn request_sui_from_faucet(faucet_url: &str, recipient: SuiAddress) -> Result<()> {
+    let resp = reqwest::Client::new()
+        .post(faucet_url)
+        .json(&serde_json::json!({
+            "FixedAmountRequest": { "recipient": recipient.to_string() }
+        }))
+        .send()
+        .await?;
+
+    if !resp.status().is_success() {
+        let status = resp.status();
+        let body = resp.text().await.unwrap_or_default();
+        anyhow::bail!("Faucet request failed with {}: {}", status, body);
+    }
+
+    Ok(())
+}
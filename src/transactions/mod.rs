@@ -1,3 +1,4 @@
+pub mod coin;
 pub mod payments;
 pub mod pricing;
 pub mod provider;
@@ -15,6 +15,7 @@ use crate::{
     utils::{
         coin::prepare_payment_coin,
         constants::{ENTITLEMENT_STORE_ID, PACKAGE_ID, REGISTRY_ID, USAGE_RELAYER_ID},
+        package::upgraded_package_id,
     },
 };
 
@@ -30,10 +31,11 @@ pub async fn purchase_entitlement_tx(
     let tier_obj = client.get_tier_info(tier_id).await?;
 
     if payment_amount < tier_obj.price {
+        let coin_info = client.coin_info(&tier_obj.coin_type).await?;
         anyhow::bail!(
             "Payment amount {} is less than tier price {}",
-            tier_obj.coin_type.format_amount(payment_amount),
-            tier_obj.coin_type.format_amount(tier_obj.price)
+            coin_info.format_amount(payment_amount),
+            coin_info.format_amount(tier_obj.price)
         );
     }
 
@@ -72,6 +74,69 @@ pub async fn purchase_entitlement_tx(
     client.build_tx_data(pt, sender).await
 }
 
+/// Adds units to `sender`'s usage-based entitlement, bought at the tier's
+/// current price, in place of a new purchase
+pub async fn top_up_entitlement_tx(
+    client: &SuiClient,
+    sender: SuiAddress,
+    entitlement_id: ObjectID,
+    service_id: ObjectID,
+    tier_id: ObjectID,
+    payment_amount: u64,
+) -> Result<TransactionData> {
+    let mut ptb = ProgrammableTransactionBuilder::new();
+
+    let tier_obj = client.get_tier_info(tier_id).await?;
+
+    if !tier_obj.active {
+        anyhow::bail!("Tier {} is not active", tier_id);
+    }
+    if payment_amount < tier_obj.price {
+        let coin_info = client.coin_info(&tier_obj.coin_type).await?;
+        anyhow::bail!(
+            "Payment amount {} is less than tier price {}",
+            coin_info.format_amount(payment_amount),
+            coin_info.format_amount(tier_obj.price)
+        );
+    }
+
+    let coin_type = tier_obj.coin_type;
+    let coin_type_tag = coin_type.to_type_tag()?;
+
+    let package_id = upgraded_package_id("payments::top_up_entitlement")?;
+    let registry_id = ObjectID::from_hex_literal(REGISTRY_ID)?;
+    let store_id = ObjectID::from_hex_literal(ENTITLEMENT_STORE_ID)?;
+
+    let store_arg = store_id.to_shared_mut_ptb_arg(client, &mut ptb).await?;
+    let service_arg = service_id.to_owned_ptb_arg(client, &mut ptb).await?;
+    let registry_arg = registry_id.to_shared_imm_ptb_arg(client, &mut ptb).await?;
+    let tier_arg = tier_id.to_owned_ptb_arg(client, &mut ptb).await?;
+    let entitlement_arg = ptb.pure(ID::new(entitlement_id))?;
+    let clock_arg = clock_arg(client, &mut ptb).await?;
+
+    let payment_arg =
+        prepare_payment_coin(&mut ptb, client, sender, coin_type, payment_amount).await?;
+
+    ptb.command(SuiCommand::move_call(
+        package_id,
+        Identifier::new("payments")?,
+        Identifier::new("top_up_entitlement")?,
+        vec![coin_type_tag],
+        vec![
+            store_arg,
+            service_arg,
+            registry_arg,
+            tier_arg,
+            entitlement_arg,
+            payment_arg,
+            clock_arg,
+        ],
+    ));
+
+    let pt = ptb.finish();
+    client.build_tx_data(pt, sender).await
+}
+
 pub async fn settle_usage_batch_tx(
     client: &SuiClient,
     sender: SuiAddress,
@@ -22,7 +22,7 @@ pub async fn create_pricing_tier_tx(
     tier_name: String,
     price: u64,
     config: TierConfigInput,
-    coin_type: u8,
+    coin_type: &CoinType,
 ) -> Result<TransactionData> {
     let mut ptb = ProgrammableTransactionBuilder::new();
 
@@ -47,7 +47,7 @@ pub async fn create_pricing_tier_tx(
     let name_arg = ptb.pure(tier_name.into_bytes())?;
     let price_arg = ptb.pure(price)?;
 
-    let coin_type_tag = CoinType::u8_to_typetag(coin_type)?;
+    let coin_type_tag = coin_type.to_type_tag()?;
 
     ptb.command(SuiCommand::move_call(
         package_id,
@@ -122,7 +122,7 @@ pub async fn update_tier_price_tx(
     sender: SuiAddress,
     new_price: u64,
     tier_id: ObjectID,
-    coin_type: u8,
+    coin_type: &CoinType,
 ) -> Result<TransactionData> {
     let package_id = ObjectID::from_hex_literal(PACKAGE_ID)?;
 
@@ -140,7 +140,7 @@ pub async fn update_tier_price_tx(
     let clock_arg = clock_arg(client, &mut ptb).await?;
 
     let price_arg = ptb.pure(new_price)?;
-    let coin_type_tag = CoinType::u8_to_typetag(coin_type)?;
+    let coin_type_tag = coin_type.to_type_tag()?;
 
     ptb.command(SuiCommand::move_call(
         package_id,
@@ -159,7 +159,7 @@ pub async fn deactivate_tier_tx(
     client: &SuiClient,
     sender: SuiAddress,
     tier_id: ObjectID,
-    coin_type: u8,
+    coin_type: &CoinType,
 ) -> Result<TransactionData> {
     let package_id = ObjectID::from_hex_literal(PACKAGE_ID)?;
 
@@ -176,7 +176,7 @@ pub async fn deactivate_tier_tx(
 
     let clock_arg = clock_arg(client, &mut ptb).await?;
 
-    let coin_type_tag = CoinType::u8_to_typetag(coin_type)?;
+    let coin_type_tag = coin_type.to_type_tag()?;
 
     ptb.command(SuiCommand::move_call(
         package_id,
@@ -195,7 +195,7 @@ pub async fn reactivate_tier_tx(
     client: &SuiClient,
     sender: SuiAddress,
     tier_id: ObjectID,
-    coin_type: u8,
+    coin_type: &CoinType,
 ) -> Result<TransactionData> {
     let package_id = ObjectID::from_hex_literal(PACKAGE_ID)?;
 
@@ -212,7 +212,7 @@ pub async fn reactivate_tier_tx(
 
     let clock_arg = clock_arg(client, &mut ptb).await?;
 
-    let coin_type_tag = CoinType::u8_to_typetag(coin_type)?;
+    let coin_type_tag = coin_type.to_type_tag()?;
 
     ptb.command(SuiCommand::move_call(
         package_id,
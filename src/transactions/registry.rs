@@ -4,14 +4,18 @@ use sui_types::{
     Identifier,
     base_types::{ObjectID, SequenceNumber, SuiAddress},
     programmable_transaction_builder::ProgrammableTransactionBuilder,
-    transaction::{Command, ObjectArg, SharedObjectMutability, TransactionData},
+    transaction::{Argument, Command, ObjectArg, SharedObjectMutability, TransactionData},
 };
 
 use crate::{
     client::client_ext::SuiClientExt,
     ptb::{clock::clock_arg, object_ext::ObjectIDExt},
-    transactions::provider::get_provider_state,
-    utils::constants::{CLOCK_OBJECT_ID, PACKAGE_ID, REGISTRY_ID},
+    transactions::provider::{fetch_tiers_for_service, get_provider_state},
+    types::types::TierInfo,
+    utils::{
+        constants::{CLOCK_OBJECT_ID, PACKAGE_ID, REGISTRY_ID},
+        package::{latest_package_id, upgraded_package_id},
+    },
 };
 
 pub async fn register_provider_tx(
@@ -107,7 +111,9 @@ pub async fn set_service_active_tx(
     service_id: ObjectID,
 ) -> Result<TransactionData> {
     let registry_id = ObjectID::from_hex_literal(REGISTRY_ID)?;
-    let package_id = ObjectID::from_hex_literal(PACKAGE_ID)?;
+    // The upgraded version emits `ServiceReactivated`, which undoes a
+    // deactivation in the index
+    let package_id = latest_package_id()?;
 
     let mut ptb = ProgrammableTransactionBuilder::new();
 
@@ -130,6 +136,105 @@ pub async fn set_service_active_tx(
     client.build_tx_data(pt, sender).await
 }
 
+/// Upper bound on `deactivate_tier` calls packed into one PTB
+pub const TIER_DEACTIVATIONS_PER_TX: usize = 100;
+
+/// Splits a service's tiers into those still active (to deactivate) and
+/// those already inactive
+pub async fn plan_service_deactivation(
+    client: &SuiClient,
+    service_id: ObjectID,
+) -> Result<(Vec<(ObjectID, TierInfo)>, Vec<ObjectID>)> {
+    let mut active = vec![];
+    let mut inactive = vec![];
+
+    for tier_id in fetch_tiers_for_service(client, service_id).await? {
+        let info = client.get_tier_info(tier_id).await?;
+        if info.active {
+            active.push((tier_id, info));
+        } else {
+            inactive.push(tier_id);
+        }
+    }
+
+    Ok((active, inactive))
+}
+
+/// Deactivates the service together with a first batch of its tiers
+pub async fn deactivate_service_tx(
+    client: &SuiClient,
+    sender: SuiAddress,
+    service_id: ObjectID,
+    tiers: &[(ObjectID, TierInfo)],
+) -> Result<TransactionData> {
+    let registry_id = ObjectID::from_hex_literal(REGISTRY_ID)?;
+    let package_id = upgraded_package_id("registry::set_service_inactive_entry")?;
+
+    let mut ptb = ProgrammableTransactionBuilder::new();
+
+    let registry_arg = registry_id.to_shared_imm_ptb_arg(client, &mut ptb).await?;
+    let service_arg = service_id.to_owned_ptb_arg(client, &mut ptb).await?;
+    let clock_arg = clock_arg(client, &mut ptb).await?;
+
+    ptb.command(Command::move_call(
+        package_id,
+        Identifier::new("registry")?,
+        Identifier::new("set_service_inactive_entry")?,
+        vec![],
+        vec![registry_arg, service_arg, clock_arg],
+    ));
+
+    if !tiers.is_empty() {
+        add_tier_deactivations(client, sender, &mut ptb, tiers, clock_arg).await?;
+    }
+
+    let pt = ptb.finish();
+
+    client.build_tx_data(pt, sender).await
+}
+
+/// Deactivates a batch of tiers in one PTB
+pub async fn deactivate_tiers_tx(
+    client: &SuiClient,
+    sender: SuiAddress,
+    tiers: &[(ObjectID, TierInfo)],
+) -> Result<TransactionData> {
+    let mut ptb = ProgrammableTransactionBuilder::new();
+
+    let clock_arg = clock_arg(client, &mut ptb).await?;
+    add_tier_deactivations(client, sender, &mut ptb, tiers, clock_arg).await?;
+
+    let pt = ptb.finish();
+
+    client.build_tx_data(pt, sender).await
+}
+
+async fn add_tier_deactivations(
+    client: &SuiClient,
+    sender: SuiAddress,
+    ptb: &mut ProgrammableTransactionBuilder,
+    tiers: &[(ObjectID, TierInfo)],
+    clock_arg: Argument,
+) -> Result<()> {
+    let package_id = ObjectID::from_hex_literal(PACKAGE_ID)?;
+    let provider_state = get_provider_state(client, sender).await?;
+
+    let cap_arg = provider_state.cap_id.to_owned_ptb_arg(client, ptb).await?;
+
+    for (tier_id, info) in tiers {
+        let tier_arg = tier_id.to_owned_ptb_arg(client, ptb).await?;
+        ptb.command(Command::move_call(
+            package_id,
+            Identifier::new("pricing")?,
+            Identifier::new("deactivate_tier")?,
+            vec![info.coin_type.to_type_tag()?],
+            vec![tier_arg, cap_arg, clock_arg],
+        ));
+    }
+
+    Ok(())
+}
+
 pub async fn update_service_metadata_tx(
     client: &SuiClient,
     sender: SuiAddress,
@@ -0,0 +1,184 @@
+use std::str::FromStr;
+
+use rust_decimal::{Decimal, prelude::ToPrimitive};
+use serde::{Deserialize, Serialize};
+use sqlx::{
+    Decode, Encode, Postgres, Type,
+    encode::IsNull,
+    error::BoxDynError,
+    postgres::{PgArgumentBuffer, PgTypeInfo, PgValueRef},
+};
+
+use crate::utils::error::InfrapassError;
+
+fn decimal_to_u64(value: Decimal) -> Result<u64, BoxDynError> {
+    if !value.fract().is_zero() {
+        return Err(format!("expected an integer amount, got {}", value).into());
+    }
+    value
+        .to_u64()
+        .ok_or_else(|| format!("amount {} does not fit in u64", value).into())
+}
+
+/// Declares a u64 newtype stored as `NUMERIC(20, 0)` so the full u64 range
+/// round-trips through Postgres without the `as i64` truncation.
+macro_rules! numeric_u64 {
+    ($(#[$meta:meta])* $name:ident) => {
+        $(#[$meta])*
+        #[derive(
+            Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+        )]
+        #[serde(transparent)]
+        pub struct $name(u64);
+
+        impl $name {
+            pub const ZERO: Self = Self(0);
+
+            pub const fn new(value: u64) -> Self {
+                Self(value)
+            }
+
+            pub const fn get(&self) -> u64 {
+                self.0
+            }
+
+            pub fn is_zero(&self) -> bool {
+                self.0 == 0
+            }
+
+            pub fn checked_add(self, rhs: Self) -> Option<Self> {
+                self.0.checked_add(rhs.0).map(Self)
+            }
+
+            pub fn checked_sub(self, rhs: Self) -> Option<Self> {
+                self.0.checked_sub(rhs.0).map(Self)
+            }
+
+            pub fn saturating_sub(self, rhs: Self) -> Self {
+                Self(self.0.saturating_sub(rhs.0))
+            }
+        }
+
+        impl From<u64> for $name {
+            fn from(value: u64) -> Self {
+                Self(value)
+            }
+        }
+
+        impl From<$name> for u64 {
+            fn from(value: $name) -> Self {
+                value.0
+            }
+        }
+
+        impl std::fmt::Display for $name {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                write!(f, "{}", self.0)
+            }
+        }
+
+        impl Type<Postgres> for $name {
+            fn type_info() -> PgTypeInfo {
+                <Decimal as Type<Postgres>>::type_info()
+            }
+
+            fn compatible(ty: &PgTypeInfo) -> bool {
+                <Decimal as Type<Postgres>>::compatible(ty)
+            }
+        }
+
+        impl<'q> Encode<'q, Postgres> for $name {
+            fn encode_by_ref(&self, buf: &mut PgArgumentBuffer) -> Result<IsNull, BoxDynError> {
+                <Decimal as Encode<'q, Postgres>>::encode_by_ref(&Decimal::from(self.0), buf)
+            }
+        }
+
+        impl<'r> Decode<'r, Postgres> for $name {
+            fn decode(value: PgValueRef<'r>) -> Result<Self, BoxDynError> {
+                let decimal = <Decimal as Decode<'r, Postgres>>::decode(value)?;
+                decimal_to_u64(decimal).map(Self)
+            }
+        }
+    };
+}
+
+numeric_u64!(
+    /// A coin amount in the coin's smallest unit (MIST for SUI)
+    MistAmount
+);
+
+numeric_u64!(
+    /// A count of quota or usage units
+    Units
+);
+
+/// A human-readable amount from the CLI such as `10.5`, `2SUI` or `0.25 usdc`.
+/// Values are whole tokens; the optional suffix names the coin.
+#[derive(Debug, Clone)]
+pub struct AmountInput {
+    pub value: Decimal,
+    pub symbol: Option<String>,
+}
+
+impl FromStr for AmountInput {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let split = s
+            .find(|c: char| c.is_ascii_alphabetic())
+            .unwrap_or(s.len());
+        let (number, symbol) = s.split_at(split);
+
+        let value = Decimal::from_str(number.trim())
+            .map_err(|_| anyhow::anyhow!("Invalid amount: {}", s))?;
+        if value.is_sign_negative() {
+            anyhow::bail!("Amount must not be negative: {}", s);
+        }
+
+        let symbol = symbol.trim();
+        Ok(Self {
+            value,
+            symbol: (!symbol.is_empty()).then(|| symbol.to_uppercase()),
+        })
+    }
+}
+
+impl AmountInput {
+    /// Converts to the coin's smallest unit, rejecting inputs with more
+    /// fractional digits than the coin supports. Decimals come from on-chain
+    /// metadata, so a coin with more than a u64 can scale to is refused.
+    pub fn to_base_units(&self, decimals: u8) -> anyhow::Result<u64> {
+        let unit = 10u64.checked_pow(decimals as u32).ok_or_else(|| {
+            InfrapassError::ValidationError(format!(
+                "Coin has {} decimals, more than a u64 amount can hold",
+                decimals
+            ))
+        })?;
+        let scaled = self
+            .value
+            .checked_mul(Decimal::from(unit))
+            .ok_or_else(|| anyhow::anyhow!("Amount {} is too large", self.value))?;
+
+        if !scaled.fract().is_zero() {
+            anyhow::bail!(
+                "Amount {} has more than {} decimal places and would lose precision",
+                self.value,
+                decimals
+            );
+        }
+
+        scaled
+            .to_u64()
+            .ok_or_else(|| anyhow::anyhow!("Amount {} does not fit in u64", self.value))
+    }
+}
+
+impl std::fmt::Display for AmountInput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.symbol {
+            Some(symbol) => write!(f, "{} {}", self.value.normalize(), symbol),
+            None => write!(f, "{}", self.value.normalize()),
+        }
+    }
+}
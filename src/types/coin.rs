@@ -1,32 +1,38 @@
 use std::str::FromStr;
 
-use crate::utils::constants::TEST_TOKEN_PACKAGE_ID;
+use crate::utils::network::Network;
 use anyhow::{Ok, Result};
 use sui_types::TypeTag;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum CoinType {
     SUI,
     WAL,
     USDC,
     USDT,
+    /// Any other Sui coin, identified by its full type tag
+    Custom(TypeTag),
 }
 
 impl CoinType {
+    /// Resolves the coin's type tag on the selected network
     pub fn to_type_tag(&self) -> Result<TypeTag> {
+        let packages = Network::current().coin_packages();
         let type_str = match self {
+            CoinType::Custom(tag) => return Ok(tag.clone()),
+
             CoinType::SUI => "0x2::sui::SUI".to_string(),
 
             CoinType::WAL => {
-                format!("{TEST_TOKEN_PACKAGE_ID}::wal::WAL")
+                format!("{}::wal::WAL", packages.wal)
             }
 
             CoinType::USDC => {
-                format!("{TEST_TOKEN_PACKAGE_ID}::usdc::USDC")
+                format!("{}::usdc::USDC", packages.usdc)
             }
 
             CoinType::USDT => {
-                format!("{TEST_TOKEN_PACKAGE_ID}::usdt::USDT")
+                format!("{}::usdt::USDT", packages.usdt)
             }
         };
 
@@ -34,12 +40,22 @@ impl CoinType {
             .map_err(|e| anyhow::anyhow!("Invalid type tag for {:?}: {}", self, e))
     }
 
-    pub fn package_id(&self) -> &str {
+    /// Maps a coin type tag onto a known coin, falling back to `Custom`
+    pub fn from_type_tag(tag: TypeTag) -> Self {
+        CoinType::all()
+            .into_iter()
+            .find(|known| known.to_type_tag().is_ok_and(|t| t == tag))
+            .unwrap_or(CoinType::Custom(tag))
+    }
+
+    pub fn package_id(&self) -> String {
         match self {
-            CoinType::SUI => "0x2",
-            CoinType::WAL => TEST_TOKEN_PACKAGE_ID,
-            CoinType::USDC => TEST_TOKEN_PACKAGE_ID,
-            CoinType::USDT => TEST_TOKEN_PACKAGE_ID,
+            CoinType::SUI => "0x2".to_string(),
+            CoinType::WAL => Network::current().coin_packages().wal.to_string(),
+            CoinType::USDC => Network::current().coin_packages().usdc.to_string(),
+            CoinType::USDT => Network::current().coin_packages().usdt.to_string(),
+            CoinType::Custom(TypeTag::Struct(tag)) => tag.address.to_hex_literal(),
+            CoinType::Custom(tag) => tag.to_string(),
         }
     }
 
@@ -62,6 +78,10 @@ impl CoinType {
             Self::WAL => Ok(1),
             Self::USDC => Ok(2),
             Self::USDT => Ok(3),
+            Self::Custom(tag) => Err(anyhow::anyhow!(
+                "Custom coin type {} has no numeric id",
+                tag
+            )),
         }
     }
 
@@ -76,6 +96,8 @@ impl CoinType {
             CoinType::WAL => "WAL",
             CoinType::USDC => "USDC",
             CoinType::USDT => "USDT",
+            CoinType::Custom(TypeTag::Struct(tag)) => tag.name.as_str(),
+            CoinType::Custom(_) => "UNKNOWN",
         }
     }
 
@@ -83,26 +105,40 @@ impl CoinType {
         self.name()
     }
 
-    /// Parse from string
+    /// Parse from string: a symbol, a numeric id or a full coin type
+    /// such as `0x...::my_coin::MY_COIN`
     pub fn from_str(s: &str) -> Result<Self> {
+        if s.contains("::") {
+            let tag = TypeTag::from_str(s)
+                .map_err(|e| anyhow::anyhow!("Invalid coin type {}: {}", s, e))?;
+            return Ok(CoinType::from_type_tag(tag));
+        }
+
+        if let std::result::Result::Ok(id) = s.parse::<u8>() {
+            return CoinType::from_u8(id);
+        }
+
         match s.to_uppercase().as_str() {
             "SUI" => Ok(CoinType::SUI),
             "WAL" => Ok(CoinType::WAL),
             "USDC" => Ok(CoinType::USDC),
             "USDT" => Ok(CoinType::USDT),
             _ => Err(anyhow::anyhow!(
-                "Unknown coin type: {}. Supported: SUI, WAL, USDC, USDT",
+                "Unknown coin type: {}. Supported: SUI, WAL, USDC, USDT or a full coin type",
                 s
             )),
         }
     }
 
+    /// Built-in decimals, used when on-chain metadata is unavailable.
+    /// Prefer `SuiClientExt::coin_info` for display and amount conversion.
     pub fn decimals(&self) -> u8 {
         match self {
-            CoinType::SUI => 9,  // SUI has 9 decimals (1 SUI = 1,000,000,000 MIST)
-            CoinType::WAL => 9,  // WAL has 9 decimals (same as SUI)
-            CoinType::USDC => 6, // USDC has 6 decimals (standard for stablecoins)
-            CoinType::USDT => 6, // USDT has 6 decimals (standard for stablecoins)
+            CoinType::SUI => 9,       // SUI has 9 decimals (1 SUI = 1,000,000,000 MIST)
+            CoinType::WAL => 9,       // WAL has 9 decimals (same as SUI)
+            CoinType::USDC => 6,      // USDC has 6 decimals (standard for stablecoins)
+            CoinType::USDT => 6,      // USDT has 6 decimals (standard for stablecoins)
+            CoinType::Custom(_) => 0, // unknown without CoinMetadata, amounts stay in base units
         }
     }
 
@@ -125,7 +161,7 @@ impl CoinType {
         format!("{} {}", self.from_smallest_unit(amount), self.symbol())
     }
 
-    /// Get all supported coin types
+    /// Get all built-in coin types
     pub fn all() -> Vec<CoinType> {
         vec![CoinType::SUI, CoinType::WAL, CoinType::USDC, CoinType::USDT]
     }
@@ -0,0 +1,98 @@
+use std::str::FromStr;
+
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use sui_types::base_types::{ObjectID, SuiAddress};
+
+use crate::events::types::EntitlementConfig;
+
+/// An `infrapass::payments::Entitlement` as read from chain
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OnchainEntitlement {
+    pub entitlement_id: ObjectID,
+    pub holder: SuiAddress,
+    pub service_id: ObjectID,
+    pub tier_id: ObjectID,
+    pub tier_name: String,
+    pub purchased_at: u64,
+    pub config: EntitlementConfig,
+}
+
+impl OnchainEntitlement {
+    /// Parse the JSON fields of an `Entitlement` move object
+    pub fn from_json(fields: &JsonValue) -> Result<Self> {
+        let inner = fields
+            .get("inner")
+            .ok_or_else(|| anyhow!("Entitlement missing inner config"))?;
+        let variant = inner
+            .get("variant")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| anyhow!("Entitlement config missing variant"))?;
+        let inner_fields = inner.get("fields").unwrap_or(&JsonValue::Null);
+
+        let config = match variant {
+            "Subscription" => EntitlementConfig::Subscription {
+                expires_at: json_u64(inner_fields, "expires_at")?,
+            },
+            "Quota" => EntitlementConfig::Quota {
+                expires_at: json_u64(inner_fields, "expires_at")?,
+                quota: json_u64(inner_fields, "quota")?,
+            },
+            "UsageBased" => EntitlementConfig::UsageBased {
+                units: json_u64(inner_fields, "units")?,
+            },
+            other => return Err(anyhow!("Unknown entitlement variant: {}", other)),
+        };
+
+        let entitlement_id = fields
+            .pointer("/id/id")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| anyhow!("Entitlement missing id"))?;
+
+        Ok(Self {
+            entitlement_id: ObjectID::from_hex_literal(entitlement_id)?,
+            holder: SuiAddress::from_str(json_str(fields, "holder")?)?,
+            service_id: ObjectID::from_hex_literal(json_str(fields, "service_id")?)?,
+            tier_id: ObjectID::from_hex_literal(json_str(fields, "tier_id")?)?,
+            tier_name: json_str(fields, "tier_name")?.to_string(),
+            purchased_at: json_u64(fields, "purchased_at")?,
+            config,
+        })
+    }
+
+    /// Expiry in unix milliseconds, `None` for usage based entitlements
+    pub fn expires_at(&self) -> Option<u64> {
+        self.config.expires_at()
+    }
+
+    /// Remaining quota or units as of the last on-chain settlement
+    pub fn remaining(&self) -> Option<u64> {
+        self.config.quota().or(self.config.units())
+    }
+
+    pub fn is_active(&self, now_ms: u64) -> bool {
+        match &self.config {
+            EntitlementConfig::Subscription { expires_at } => now_ms < *expires_at,
+            EntitlementConfig::Quota { expires_at, quota } => now_ms < *expires_at && *quota > 0,
+            EntitlementConfig::UsageBased { units } => *units > 0,
+        }
+    }
+}
+
+fn json_str<'a>(fields: &'a JsonValue, key: &str) -> Result<&'a str> {
+    fields
+        .get(key)
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| anyhow!("Entitlement missing field {}", key))
+}
+
+fn json_u64(fields: &JsonValue, key: &str) -> Result<u64> {
+    match fields.get(key) {
+        Some(JsonValue::String(s)) => Ok(s.parse()?),
+        Some(JsonValue::Number(n)) => n
+            .as_u64()
+            .ok_or_else(|| anyhow!("Entitlement field {} is not a u64", key)),
+        _ => Err(anyhow!("Entitlement missing field {}", key)),
+    }
+}
@@ -1,3 +1,6 @@
+pub mod amount;
 pub mod coin;
+pub mod entitlement;
 pub mod settlement;
+pub mod sla;
 pub mod types;
@@ -0,0 +1,85 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SupportTier {
+    Community,
+    Standard,
+    Priority,
+    Dedicated,
+}
+
+impl SupportTier {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SupportTier::Community => "community",
+            SupportTier::Standard => "standard",
+            SupportTier::Priority => "priority",
+            SupportTier::Dedicated => "dedicated",
+        }
+    }
+}
+
+/// Service level a provider commits to for a pricing tier. Kept off-chain
+/// next to the indexed tier, since tier objects carry no metadata.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SlaTerms {
+    /// Monthly uptime target, e.g. 99.9
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uptime_percent: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latency_p50_ms: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latency_p99_ms: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub support: Option<SupportTier>,
+}
+
+impl SlaTerms {
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(uptime) = self.uptime_percent {
+            if !(0.0..=100.0).contains(&uptime) {
+                return Err(format!(
+                    "uptime_percent must be within 0-100, got {}",
+                    uptime
+                ));
+            }
+        }
+        if let (Some(p50), Some(p99)) = (self.latency_p50_ms, self.latency_p99_ms) {
+            if p50 > p99 {
+                return Err(format!(
+                    "latency_p50_ms ({}) cannot exceed latency_p99_ms ({})",
+                    p50, p99
+                ));
+            }
+        }
+        if self.latency_p50_ms == Some(0) || self.latency_p99_ms == Some(0) {
+            return Err("latency targets must be greater than 0".to_string());
+        }
+        if *self == Self::default() {
+            return Err("at least one SLA field must be set".to_string());
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for SlaTerms {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = vec![];
+        if let Some(uptime) = self.uptime_percent {
+            parts.push(format!("{}% uptime", uptime));
+        }
+        if let Some(p50) = self.latency_p50_ms {
+            parts.push(format!("p50 {}ms", p50));
+        }
+        if let Some(p99) = self.latency_p99_ms {
+            parts.push(format!("p99 {}ms", p99));
+        }
+        if let Some(support) = self.support {
+            parts.push(format!("{} support", support.as_str()));
+        }
+        f.write_str(&parts.join(", "))
+    }
+}
@@ -15,6 +15,7 @@ pub struct TierInfo {
     pub coin_type: CoinType,
     pub price: u64,
     pub tier_type_string: String,
+    pub active: bool,
 }
 
 impl TierConfigInput {
@@ -1,5 +1,7 @@
 use anyhow::Result;
+use sui_json_rpc_types::Coin;
 use sui_sdk::SuiClient;
+use sui_types::parse_sui_struct_tag;
 use sui_types::{
     TypeTag,
     base_types::{ObjectID, SuiAddress},
@@ -7,7 +9,12 @@ use sui_types::{
     transaction::{Argument, Command as SuiCommand, ObjectArg},
 };
 
-use crate::types::coin::CoinType;
+use tracing::info;
+
+use crate::{
+    client::{client_ext::SuiClientExt, price_quote::usd_suffix},
+    types::{amount::AmountInput, coin::CoinType},
+};
 
 pub async fn find_coin_object(
     client: &SuiClient,
@@ -29,6 +36,26 @@ pub async fn find_coin_object(
     Err(anyhow::anyhow!("Insufficient balance"))
 }
 
+/// Converts a human-readable CLI amount to base units for `coin_type`,
+/// logging both representations so the user can confirm the conversion
+pub async fn resolve_amount(
+    client: &SuiClient,
+    coin_type: &CoinType,
+    input: &AmountInput,
+) -> Result<u64> {
+    let coin_info = client.coin_info(coin_type).await?;
+    let base_units = coin_info.parse_amount(input)?;
+
+    info!(
+        "Amount: {} = {} base units{}",
+        coin_info.format_amount(base_units),
+        base_units,
+        usd_suffix(&coin_info, base_units).await
+    );
+
+    Ok(base_units)
+}
+
 pub async fn prepare_payment_coin(
     ptb: &mut ProgrammableTransactionBuilder,
     client: &SuiClient,
@@ -36,7 +63,7 @@ pub async fn prepare_payment_coin(
     coin_type: CoinType,
     exact_amount: u64,
 ) -> Result<Argument> {
-    if coin_type.to_u8()? == 0 {
+    if coin_type == CoinType::SUI {
         let amount_arg = ptb.pure(exact_amount)?;
         return Ok(ptb.command(SuiCommand::SplitCoins(Argument::GasCoin, vec![amount_arg])));
     }
@@ -57,11 +84,12 @@ pub async fn prepare_payment_coin(
 
     let total_balance: u64 = coins.data.iter().map(|c| c.balance).sum();
     if total_balance < exact_amount {
+        let coin_info = client.coin_info(&coin_type).await?;
         anyhow::bail!(
             "Insufficient {} balance\nRequired: {}\nAvailable: {}",
-            coin_type.name(),
-            coin_type.format_amount(exact_amount),
-            coin_type.format_amount(total_balance)
+            coin_info.symbol,
+            coin_info.format_amount(exact_amount),
+            coin_info.format_amount(total_balance)
         );
     }
 
@@ -81,11 +109,22 @@ pub async fn prepare_payment_coin(
         coins.data.len()
     );
 
-    let primary_coin = &coins.data[0];
+    let primary_arg = merge_coins(ptb, &coins.data)?;
+
+    let amount_arg = ptb.pure(exact_amount)?;
+
+    Ok(ptb.command(SuiCommand::SplitCoins(primary_arg, vec![amount_arg])))
+}
+
+/// Merges `coins` into the first one and returns it as a PTB argument
+pub fn merge_coins(ptb: &mut ProgrammableTransactionBuilder, coins: &[Coin]) -> Result<Argument> {
+    let primary_coin = coins
+        .first()
+        .ok_or_else(|| anyhow::anyhow!("No coins to merge"))?;
     let primary_arg = ptb.obj(ObjectArg::ImmOrOwnedObject(primary_coin.object_ref()))?;
 
-    if coins.data.len() > 1 {
-        let merge_args: Vec<Argument> = coins.data[1..]
+    if coins.len() > 1 {
+        let merge_args: Vec<Argument> = coins[1..]
             .iter()
             .map(|coin| ptb.obj(ObjectArg::ImmOrOwnedObject(coin.object_ref())))
             .collect::<Result<Vec<_>, _>>()?;
@@ -93,23 +132,22 @@ pub async fn prepare_payment_coin(
         ptb.command(SuiCommand::MergeCoins(primary_arg, merge_args));
     }
 
-    let amount_arg = ptb.pure(exact_amount)?;
-
-    Ok(ptb.command(SuiCommand::SplitCoins(primary_arg, vec![amount_arg])))
+    Ok(primary_arg)
 }
 
+/// Reads the coin type from the generic parameter of a tier's object type,
+/// e.g. `0x..::pricing::PricingTier<0x2::sui::SUI>`
 pub fn extract_coin_type_from_tier_type(tier_type: &str) -> Result<CoinType> {
-    if tier_type.contains("0x2::sui::SUI>") {
-        Ok(CoinType::SUI)
-    } else if tier_type.contains("wal::WAL>") {
-        Ok(CoinType::WAL)
-    } else if tier_type.contains("usdc::USDC>") {
-        Ok(CoinType::USDC)
-    } else if tier_type.contains("usdt::USDT>") {
-        Ok(CoinType::USDT)
-    } else {
-        Err(anyhow::anyhow!("Unknown coin type in tier: {}", tier_type))
-    }
+    let tag = parse_sui_struct_tag(tier_type)
+        .map_err(|e| anyhow::anyhow!("Invalid tier type {}: {}", tier_type, e))?;
+
+    let coin_tag = tag
+        .type_params
+        .into_iter()
+        .next()
+        .ok_or_else(|| anyhow::anyhow!("Unknown coin type in tier: {}", tier_type))?;
+
+    Ok(CoinType::from_type_tag(coin_tag))
 }
 
 pub fn extract_price_from_content(
@@ -130,3 +168,20 @@ pub fn extract_price_from_content(
 
     Err(anyhow::anyhow!("Could not extract price from tier"))
 }
+
+pub fn extract_active_from_content(
+    content: &Option<sui_json_rpc_types::SuiParsedData>,
+) -> Result<bool> {
+    let content = content
+        .as_ref()
+        .ok_or_else(|| anyhow::anyhow!("No content in tier"))?;
+
+    if let sui_json_rpc_types::SuiParsedData::MoveObject(move_obj) = content {
+        let fields = move_obj.fields.clone().to_json_value();
+        if let Some(active) = fields.get("active").and_then(|v| v.as_bool()) {
+            return Ok(active);
+        }
+    }
+
+    Err(anyhow::anyhow!("Could not extract active flag from tier"))
+}
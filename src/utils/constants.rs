@@ -11,12 +11,12 @@ pub const USAGE_RELAYER_ID: &str =
 pub const TEST_TOKEN_PACKAGE_ID: &str =
     "0x842f1bc7ec3e93164b3fc28a2b696409d7e41d152c9233cf300bb9a185e5066b";
 pub const MAINNET_WAL: &str = "0x356a26eb9e012a68958082340d4c4116e7f55615cf27affcff209cf0ae544f59";
-pub const MAINNET_USDC: &str = "dba34672e30cb065b1f93e3ab55318768fd6fef66c15942c9f7cb846e2f900e7";
-pub const MAINNET_USDT: &str = "375f70cf2ae4c00bf37117d0c85a2c71545e6ee05c4a5c7d282cd66a4504b068";
+pub const MAINNET_USDC: &str = "0xdba34672e30cb065b1f93e3ab55318768fd6fef66c15942c9f7cb846e2f900e7";
+pub const MAINNET_USDT: &str = "0x375f70cf2ae4c00bf37117d0c85a2c71545e6ee05c4a5c7d282cd66a4504b068";
 
-pub const TEST_WAL: &str = "0x356a26eb9e012a68958082340d4c4116e7f55615cf27affcff209cf0ae544f59";
-pub const TEST_USDC: &str = "dba34672e30cb065b1f93e3ab55318768fd6fef66c15942c9f7cb846e2f900e7";
-pub const TEST_USDT: &str = "375f70cf2ae4c00bf37117d0c85a2c71545e6ee05c4a5c7d282cd66a4504b068";
+pub const DEFAULT_GAS_BUDGET: u64 = 10_000_000;
+
+pub const TESTNET_FAUCET_URL: &str = "https://faucet.testnet.sui.io/v2/gas";
 
 pub const MIGRATIONS_PATH: &str = "src/db/migrations";
 
@@ -58,3 +58,36 @@ pub const LUA_ATOMIC_CHECK_AND_DECREMENT: &str = r#"
     -- Unknown tier type
     return -3
 "#;
+
+/// Applies a batched decrement from sampling mode. Unlike the check script it
+/// never refuses, so the counter may go below zero; a missing key returns nil.
+/// Lowers a quota counter to ARGV[1] if it is higher, keeping its TTL.
+/// Returns the counter afterwards, or false if the key doesn't exist.
+pub const LUA_CAP_QUOTA: &str = r#"
+    local quota_key = KEYS[1]
+    local remaining = tonumber(ARGV[1])
+
+    local current = redis.call('GET', quota_key)
+    if current == false then
+        return false
+    end
+
+    current = tonumber(current)
+    if current ~= nil and current <= remaining then
+        return current
+    end
+
+    redis.call('SET', quota_key, remaining, 'KEEPTTL')
+    return remaining
+"#;
+
+pub const LUA_FLUSH_DECREMENT: &str = r#"
+    local quota_key = KEYS[1]
+    local cost = tonumber(ARGV[1])
+
+    if redis.call('EXISTS', quota_key) == 0 then
+        return false
+    end
+
+    return redis.call('DECRBY', quota_key, cost)
+"#;
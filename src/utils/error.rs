@@ -12,6 +12,8 @@ pub enum InfrapassError {
     AdapterError(String),
     EventProcessingError(String),
     ValidationError(String),
+    /// The caller is authenticated but may not act on the resource
+    Forbidden(String),
     Other(String),
     ProxyError(ProxyError),
     RedisError(redis::RedisError),
@@ -27,6 +29,7 @@ impl std::fmt::Display for InfrapassError {
                 write!(f, "Event processing error: {}", msg)
             }
             InfrapassError::ValidationError(msg) => write!(f, "Validation error: {}", msg),
+            InfrapassError::Forbidden(msg) => write!(f, "Forbidden: {}", msg),
             InfrapassError::Other(msg) => write!(f, "Other error: {}", msg),
             InfrapassError::ProxyError(err) => write!(f, "Proxy error: {}", err),
             InfrapassError::RedisError(err) => write!(f, "Redis error: {}", err),
@@ -40,6 +43,7 @@ impl IntoResponse for InfrapassError {
         let (status, message) = match &self {
             InfrapassError::DatabaseError(msg) => (StatusCode::INTERNAL_SERVER_ERROR, msg.clone()),
             InfrapassError::ValidationError(msg) => (StatusCode::BAD_REQUEST, msg.clone()),
+            InfrapassError::Forbidden(msg) => (StatusCode::FORBIDDEN, msg.clone()),
             InfrapassError::Other(msg) => (StatusCode::INTERNAL_SERVER_ERROR, msg.clone()),
             InfrapassError::RedisError(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
             InfrapassError::SerdeError(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
@@ -9,6 +9,11 @@ pub mod config;
 pub mod constants;
 pub mod error;
 pub mod logs_fmt;
+pub mod network;
+pub mod package;
+pub mod preflight;
+pub mod vault;
+pub mod webhook;
 
 pub fn handle_response(resp: &SuiTransactionBlockResponse) {
     match resp.status_ok() {
@@ -0,0 +1,92 @@
+use std::sync::OnceLock;
+
+use clap::ValueEnum;
+
+use crate::utils::constants::{
+    MAINNET_USDC, MAINNET_USDT, MAINNET_WAL, TEST_TOKEN_PACKAGE_ID, TESTNET_FAUCET_URL,
+};
+
+static SELECTED: OnceLock<Network> = OnceLock::new();
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum Network {
+    Mainnet,
+    #[default]
+    Testnet,
+    Devnet,
+    Localnet,
+}
+
+/// Package addresses of the non-SUI coins supported on a network
+#[derive(Debug, Clone, Copy)]
+pub struct CoinPackages {
+    pub wal: &'static str,
+    pub usdc: &'static str,
+    pub usdt: &'static str,
+}
+
+impl Network {
+    /// Sets the process-wide network. Only the first call takes effect.
+    pub fn select(self) {
+        let _ = SELECTED.set(self);
+    }
+
+    /// The selected network, testnet when none was selected
+    pub fn current() -> Self {
+        SELECTED.get().copied().unwrap_or_default()
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Network::Mainnet => "mainnet",
+            Network::Testnet => "testnet",
+            Network::Devnet => "devnet",
+            Network::Localnet => "localnet",
+        }
+    }
+
+    pub fn default_rpc_url(&self) -> &'static str {
+        match self {
+            Network::Mainnet => "https://fullnode.mainnet.sui.io:443",
+            Network::Testnet => "https://fullnode.testnet.sui.io:443",
+            Network::Devnet => "https://fullnode.devnet.sui.io:443",
+            Network::Localnet => "http://127.0.0.1:9000",
+        }
+    }
+
+    pub fn coin_packages(&self) -> CoinPackages {
+        match self {
+            Network::Mainnet => CoinPackages {
+                wal: MAINNET_WAL,
+                usdc: MAINNET_USDC,
+                usdt: MAINNET_USDT,
+            },
+            // Test networks use the bundled test tokens, which share one package
+            Network::Testnet | Network::Devnet | Network::Localnet => CoinPackages {
+                wal: TEST_TOKEN_PACKAGE_ID,
+                usdc: TEST_TOKEN_PACKAGE_ID,
+                usdt: TEST_TOKEN_PACKAGE_ID,
+            },
+        }
+    }
+
+    /// Whether the stable/WAL coins are the mintable test tokens
+    pub fn uses_test_tokens(&self) -> bool {
+        *self != Network::Mainnet
+    }
+
+    pub fn faucet_url(&self) -> Option<&'static str> {
+        match self {
+            Network::Testnet => Some(TESTNET_FAUCET_URL),
+            Network::Devnet => Some("https://faucet.devnet.sui.io/v2/gas"),
+            Network::Localnet => Some("http://127.0.0.1:9123/v2/gas"),
+            Network::Mainnet => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Network {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
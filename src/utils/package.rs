@@ -0,0 +1,59 @@
+use anyhow::{Result, anyhow};
+use sui_types::base_types::ObjectID;
+
+use crate::{events::packages::WatchedPackage, utils::constants::PACKAGE_ID};
+
+/// The package version to call entry functions through that a later upgrade
+/// added, from `INFRAPASS_PACKAGE_ID` (the upgrade's `published-at` ID).
+/// Types that existed before the upgrade keep the original `PACKAGE_ID`, but
+/// structs the upgrade added, such as `ServiceDeactivated`, originate in the
+/// upgraded version. Fails, naming `function`, while no upgraded version is
+/// configured.
+pub fn upgraded_package_id(function: &str) -> Result<ObjectID> {
+    let missing = || {
+        anyhow!(
+            "{} needs the upgraded infrapass package; publish it with `sui client upgrade` \
+             and set INFRAPASS_PACKAGE_ID to its published-at ID",
+            function
+        )
+    };
+
+    let raw = std::env::var("INFRAPASS_PACKAGE_ID").map_err(|_| missing())?;
+    let package_id = ObjectID::from_hex_literal(raw.trim())
+        .map_err(|e| anyhow!("Invalid INFRAPASS_PACKAGE_ID {}: {}", raw, e))?;
+    if package_id == ObjectID::from_hex_literal(PACKAGE_ID)? {
+        return Err(missing());
+    }
+
+    Ok(package_id)
+}
+
+/// Fails when `INFRAPASS_PACKAGE_ID` is set but missing from `watched`.
+/// Events added by the upgrade carry its package ID in their type, so the
+/// indexer would silently drop them.
+pub fn ensure_upgrade_watched(watched: &[WatchedPackage]) -> Result<()> {
+    let Ok(raw) = std::env::var("INFRAPASS_PACKAGE_ID") else {
+        return Ok(());
+    };
+    let package_id = ObjectID::from_hex_literal(raw.trim())
+        .map_err(|e| anyhow!("Invalid INFRAPASS_PACKAGE_ID {}: {}", raw, e))?;
+
+    if !watched.iter().any(|p| p.package_id == package_id) {
+        return Err(anyhow!(
+            "INFRAPASS_PACKAGE_ID {} is not listed in PACKAGE_IDS; add it so the events \
+             the upgrade added are indexed",
+            package_id
+        ));
+    }
+    Ok(())
+}
+
+/// The upgraded package when `INFRAPASS_PACKAGE_ID` is set, else the
+/// original, for entry functions every version has
+pub fn latest_package_id() -> Result<ObjectID> {
+    match std::env::var("INFRAPASS_PACKAGE_ID") {
+        Ok(raw) => ObjectID::from_hex_literal(raw.trim())
+            .map_err(|e| anyhow!("Invalid INFRAPASS_PACKAGE_ID {}: {}", raw, e)),
+        Err(_) => Ok(ObjectID::from_hex_literal(PACKAGE_ID)?),
+    }
+}
@@ -0,0 +1,109 @@
+use anyhow::Result;
+use sui_sdk::SuiClient;
+use sui_types::base_types::SuiAddress;
+
+use crate::{
+    client::client_ext::SuiClientExt,
+    types::coin::CoinType,
+    utils::{constants::DEFAULT_GAS_BUDGET, network::Network},
+};
+
+/// Balances a command needs before it builds any transaction. Every
+/// shortfall is reported in a single error instead of failing mid-way.
+pub struct Preflight {
+    transactions: u64,
+    payments: Vec<(CoinType, u64)>,
+}
+
+impl Preflight {
+    /// Requires gas for a single transaction
+    pub fn new() -> Self {
+        Self {
+            transactions: 1,
+            payments: vec![],
+        }
+    }
+
+    /// Requires gas for `count` sequential transactions
+    pub fn transactions(mut self, count: u64) -> Self {
+        self.transactions = count.max(1);
+        self
+    }
+
+    /// Requires `amount` base units of `coin_type` on top of gas
+    pub fn payment(mut self, coin_type: CoinType, amount: u64) -> Self {
+        self.payments.push((coin_type, amount));
+        self
+    }
+
+    pub async fn check(self, client: &SuiClient, owner: SuiAddress) -> Result<()> {
+        let pays_in_sui = self.payments.iter().any(|(c, _)| *c == CoinType::SUI);
+        let mut required: Vec<(CoinType, u64)> =
+            vec![(CoinType::SUI, DEFAULT_GAS_BUDGET * self.transactions)];
+        for (coin_type, amount) in self.payments {
+            match required.iter_mut().find(|(c, _)| *c == coin_type) {
+                Some((_, total)) => *total += amount,
+                None => required.push((coin_type, amount)),
+            }
+        }
+
+        let mut shortfalls = vec![];
+        for (coin_type, amount) in required {
+            let available = client.get_balance(owner, coin_type.clone()).await?;
+            if available >= amount as u128 {
+                continue;
+            }
+
+            let coin_info = client.coin_info(&coin_type).await?;
+            let label = match coin_type {
+                CoinType::SUI if pays_in_sui => "gas + payment",
+                CoinType::SUI => "gas",
+                _ => "payment",
+            };
+            shortfalls.push(format!(
+                "  {} ({}): need {}, have {}\n    hint: {}",
+                coin_info.symbol,
+                label,
+                coin_info.format_amount(amount),
+                // Only reached when below `amount`, so it fits in a u64
+                coin_info.format_amount(available as u64),
+                funding_hint(&coin_type, owner)
+            ));
+        }
+
+        if shortfalls.is_empty() {
+            return Ok(());
+        }
+
+        anyhow::bail!(
+            "Insufficient balance for {}:\n{}",
+            owner,
+            shortfalls.join("\n")
+        )
+    }
+}
+
+impl Default for Preflight {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn funding_hint(coin_type: &CoinType, owner: SuiAddress) -> String {
+    let network = Network::current();
+
+    match coin_type {
+        CoinType::SUI if network.faucet_url().is_some() => {
+            "run `infrapass-cli coin faucet --coin sui`".to_string()
+        }
+        CoinType::WAL | CoinType::USDC | CoinType::USDT if network.uses_test_tokens() => format!(
+            "run `infrapass-cli coin faucet --coin {}`",
+            coin_type.symbol().to_lowercase()
+        ),
+        _ => format!(
+            "acquire {} and transfer it to {}",
+            coin_type.symbol(),
+            owner
+        ),
+    }
+}
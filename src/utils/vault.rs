@@ -0,0 +1,72 @@
+use aes_gcm::{
+    Aes256Gcm, Key, Nonce,
+    aead::{Aead, AeadCore, KeyInit, OsRng},
+};
+use anyhow::{Result, anyhow};
+
+use crate::utils::error::InfrapassError;
+
+const NONCE_LEN: usize = 12;
+
+/// Seals values with AES-256-GCM under a key from the environment. Without a
+/// key, every call fails with `disabled`.
+pub struct SecretVault {
+    cipher: Option<Aes256Gcm>,
+    disabled: &'static str,
+}
+
+impl SecretVault {
+    /// `key_hex` is the 32-byte key from `var`, hex encoded
+    pub fn new(var: &str, key_hex: Option<&str>, disabled: &'static str) -> Result<Self> {
+        let cipher = match key_hex {
+            Some(key_hex) => {
+                let key = hex::decode(key_hex.trim_start_matches("0x"))
+                    .map_err(|e| anyhow!("{} is not valid hex: {}", var, e))?;
+                if key.len() != 32 {
+                    return Err(anyhow!("{} must be 32 bytes, got {}", var, key.len()));
+                }
+                Some(Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key)))
+            }
+            None => None,
+        };
+
+        Ok(Self { cipher, disabled })
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.cipher.is_some()
+    }
+
+    fn cipher(&self) -> Result<&Aes256Gcm, InfrapassError> {
+        self.cipher
+            .as_ref()
+            .ok_or_else(|| InfrapassError::ValidationError(self.disabled.into()))
+    }
+
+    /// Returns the random nonce followed by the ciphertext
+    pub fn seal(&self, plaintext: &str) -> Result<Vec<u8>, InfrapassError> {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher()?
+            .encrypt(&nonce, plaintext.as_bytes())
+            .map_err(|_| InfrapassError::Other("failed to encrypt secret".into()))?;
+
+        let mut sealed = nonce.to_vec();
+        sealed.extend(ciphertext);
+        Ok(sealed)
+    }
+
+    pub fn open(&self, sealed: &[u8]) -> Result<String, InfrapassError> {
+        if sealed.len() < NONCE_LEN {
+            return Err(InfrapassError::Other("stored secret is truncated".into()));
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        let plaintext = self
+            .cipher()?
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| InfrapassError::Other("failed to decrypt secret".into()))?;
+
+        String::from_utf8(plaintext)
+            .map_err(|_| InfrapassError::Other("stored secret is not UTF-8".into()))
+    }
+}
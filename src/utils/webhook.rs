@@ -0,0 +1,46 @@
+use hmac::{Hmac, Mac, digest::InvalidLength};
+use sha2::Sha256;
+use uuid::Uuid;
+
+pub type HmacSha256 = Hmac<Sha256>;
+
+/// Header carrying the hex HMAC-SHA256 of the raw request body
+pub const SIGNATURE_HEADER: &str = "X-Infrapass-Signature";
+
+pub fn sign_payload(secret: &str, payload: &[u8]) -> Result<String, InvalidLength> {
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes())?;
+    mac.update(payload);
+    Ok(hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Random signing secret handed out once when a webhook is registered
+pub fn generate_secret() -> String {
+    format!(
+        "whsec_{}{}",
+        Uuid::new_v4().simple(),
+        Uuid::new_v4().simple()
+    )
+}
+
+/// Posts a signed JSON payload. Delivery is best effort: the caller decides
+/// what to do with a failed or rejected request.
+pub async fn post_signed(
+    client: &reqwest::Client,
+    url: &str,
+    secret: &str,
+    payload: Vec<u8>,
+) -> anyhow::Result<reqwest::Response> {
+    let sig = sign_payload(secret, &payload)
+        .map_err(|e| anyhow::anyhow!("Invalid webhook secret: {}", e))?;
+
+    let resp = client
+        .post(url)
+        .header("Content-Type", "application/json")
+        .header(SIGNATURE_HEADER, sig)
+        .body(payload)
+        .timeout(std::time::Duration::from_secs(3))
+        .send()
+        .await?;
+
+    Ok(resp)
+}
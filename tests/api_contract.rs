@@ -0,0 +1,119 @@
+//! Serde compatibility of the sidecar to validator API contract. Minor
+//! versions only add optional fields, so payloads from older clients and
+//! backends must keep deserializing.
+
+use infrapass::api_types::{
+    validator::{RecordUsageRequest, ValidateRequest, ValidateResponse},
+    version::{ApiVersion, CURRENT, negotiate},
+};
+use serde::{Serialize, de::DeserializeOwned};
+use serde_json::{Value, json};
+
+/// Serializes `value`, reads it back and checks nothing changed on the wire
+fn round_trip<T: Serialize + DeserializeOwned>(value: &T) -> T {
+    let json = serde_json::to_value(value).unwrap();
+    let back: T = serde_json::from_value(json.clone()).unwrap();
+    assert_eq!(serde_json::to_value(&back).unwrap(), json);
+    back
+}
+
+#[test]
+fn validate_request_round_trips() {
+    let request = round_trip(&ValidateRequest {
+        user_address: "0xa11ce".into(),
+        service_id: "0x5e41".into(),
+        request_cost: 3,
+    });
+    assert_eq!(request.request_cost, 3);
+}
+
+#[test]
+fn validate_response_round_trips() {
+    let response = round_trip(&ValidateResponse {
+        entitlement_id: "0xe47".into(),
+        tier: "0x7132".into(),
+        quota: Some(99),
+        units: None,
+        tier_type: 1,
+        expires_at: Some("2026-01-01T00:00:00Z".parse().unwrap()),
+        notify_provider: None,
+        tier_name: Some("pro".into()),
+        price: Some(1_000_000_000),
+        coin_type: Some("0x2::sui::SUI".into()),
+        replaced_tier: Some("0x01d".into()),
+    });
+    assert_eq!(response.tier_name.as_deref(), Some("pro"));
+    assert_eq!(response.replaced_tier.as_deref(), Some("0x01d"));
+}
+
+#[test]
+fn validate_response_leaves_out_unset_optional_fields() {
+    let json = serde_json::to_value(ValidateResponse::denied()).unwrap();
+    for field in ["tier_name", "price", "coin_type", "replaced_tier"] {
+        assert!(json.get(field).is_none(), "{} serialized", field);
+    }
+}
+
+#[test]
+fn record_usage_request_round_trips() {
+    let request = round_trip(&RecordUsageRequest {
+        user_address: "0xa11ce".into(),
+        entitlement_id: "0xe47".into(),
+        cost: 5,
+        request_id: Some("req-1".into()),
+    });
+    assert_eq!(request.request_id.as_deref(), Some("req-1"));
+}
+
+#[test]
+fn v1_0_validate_response_deserializes() {
+    let payload = json!({
+        "entitlement_id": "0xe47",
+        "tier": "0x7132",
+        "quota": 10,
+        "units": null,
+        "tier_type": 1,
+        "expires_at": null,
+        "notify_provider": null
+    });
+    let response: ValidateResponse = serde_json::from_value(payload).unwrap();
+    assert_eq!(response.quota, Some(10));
+    assert!(response.tier_name.is_none());
+    assert!(response.price.is_none());
+    assert!(response.coin_type.is_none());
+    assert!(response.replaced_tier.is_none());
+}
+
+#[test]
+fn v1_0_record_usage_request_deserializes() {
+    let payload = json!({
+        "user_address": "0xa11ce",
+        "entitlement_id": "0xe47",
+        "cost": 1
+    });
+    let request: RecordUsageRequest = serde_json::from_value(payload).unwrap();
+    assert!(request.request_id.is_none());
+
+    let json: Value = serde_json::to_value(&request).unwrap();
+    assert!(json.get("request_id").is_none());
+}
+
+#[test]
+fn negotiate_serves_compatible_versions() {
+    assert_eq!(negotiate(None), Ok(CURRENT));
+    assert_eq!(negotiate(Some("1.0")), Ok(CURRENT));
+    assert_eq!(negotiate(Some("v1")), Ok(CURRENT));
+    assert_eq!(negotiate(Some(&CURRENT.requirement())), Ok(CURRENT));
+    assert_eq!(negotiate(Some(&CURRENT.to_string())), Ok(CURRENT));
+}
+
+#[test]
+fn negotiate_rejects_unsupported_versions() {
+    let newer_minor = ApiVersion::new(CURRENT.major, CURRENT.minor + 1, 0);
+    let newer_major = ApiVersion::new(CURRENT.major + 1, 0, 0);
+
+    assert!(negotiate(Some(&newer_minor.requirement())).is_err());
+    assert!(negotiate(Some(&newer_major.requirement())).is_err());
+    assert!(negotiate(Some("0.9")).is_err());
+    assert!(negotiate(Some("latest")).is_err());
+}
@@ -0,0 +1,81 @@
+//! Replays the event fixtures in `fixtures/events` through the worker.
+//!
+//! The replay tests need a Postgres with TimescaleDB in `DATABASE_URL`;
+//! `sqlx::test` creates a fresh database on it for each test.
+
+use std::{collections::HashSet, path::PathBuf, sync::Arc};
+
+use infrapass::{
+    alerting::{config::AlertConfig, manager::AlertManager},
+    db::repository::Repository,
+    events::{
+        filter::KNOWN_EVENTS,
+        fixtures::{EventFixture, run_fixture},
+    },
+};
+use sqlx::PgPool;
+
+fn fixture_files() -> Vec<PathBuf> {
+    let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/events");
+    let mut files: Vec<PathBuf> = std::fs::read_dir(&dir)
+        .expect("fixtures/events exists")
+        .map(|entry| entry.expect("fixture entry").path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+    files.sort();
+    files
+}
+
+#[test]
+fn fixtures_decode() {
+    for file in fixture_files() {
+        let fixture = EventFixture::load(&file).unwrap();
+        for event in &fixture.events {
+            if let Err(e) = event.payload() {
+                panic!("{}: {:#}", file.display(), e);
+            }
+        }
+    }
+}
+
+/// Every event the listener decodes is covered by at least one event
+/// captured as raw BCS, so a change to an event's layout fails here
+#[test]
+fn every_indexed_event_has_a_bcs_fixture() {
+    let mut covered = HashSet::new();
+    for file in fixture_files() {
+        let fixture = EventFixture::load(&file).unwrap();
+        for event in fixture.events.iter().filter(|e| e.bcs.is_some()) {
+            covered.insert(event.payload().unwrap().event.label());
+        }
+    }
+
+    let missing: Vec<&str> = KNOWN_EVENTS
+        .iter()
+        .copied()
+        .filter(|label| !covered.contains(label))
+        .collect();
+    assert!(missing.is_empty(), "no BCS fixture for {:?}", missing);
+}
+
+#[sqlx::test(migrations = "src/db/migrations")]
+async fn fixtures_replay(pool: PgPool) {
+    let repo = Arc::new(Repository::new(Arc::new(pool)));
+    let alerts = Arc::new(AlertManager::new(AlertConfig::load().unwrap()));
+
+    let mut failures = vec![];
+    for file in fixture_files() {
+        let fixture = EventFixture::load(&file).unwrap();
+        let mismatches = run_fixture(repo.clone(), alerts.clone(), &fixture)
+            .await
+            .unwrap_or_else(|e| panic!("{}: {:#}", file.display(), e));
+
+        failures.extend(
+            mismatches
+                .iter()
+                .map(|mismatch| format!("{}: {}", file.display(), mismatch)),
+        );
+    }
+
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}